@@ -0,0 +1,78 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Throughput benchmarks for the tokenizer, the parser and the serializer.
+//!
+//! Save a baseline with `cargo bench --bench parse -- --save-baseline main` and compare a
+//! change against it with `cargo bench --bench parse -- --baseline main`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use json::parser::{parse, parse_with_stats, CountingAllocator};
+use json::serializer::Serialize;
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const CORPORA: [(&str, &str); 3] = [
+    ("twitter", include_str!("data/twitter.json")),
+    ("canada", include_str!("data/canada.json")),
+    ("citm_catalog", include_str!("data/citm_catalog.json")),
+];
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, corpus) in CORPORA.iter() {
+        let (_, stats) = parse_with_stats(corpus).unwrap();
+        println!("{}: {} bytes, {} tokens, {} allocations ({} bytes)",
+                 name, stats.bytes(), stats.tokens(), stats.allocations(), stats.allocated_bytes());
+        group.throughput(Throughput::Bytes(corpus.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| parse(black_box(corpus)).unwrap()));
+    }
+    group.finish();
+}
+
+fn bench_serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    for (name, corpus) in CORPORA.iter() {
+        let j_value = parse(corpus).unwrap();
+        group.throughput(Throughput::Bytes(j_value.serialize().len() as u64));
+        group.bench_function(*name, |b| b.iter(|| black_box(&j_value).serialize()));
+    }
+    group.finish();
+}
+
+#[cfg(feature = "arena")]
+fn bench_parse_in(c: &mut Criterion) {
+    use json::parser::{parse_in, Bump};
+
+    let mut group = c.benchmark_group("parse_in");
+    for (name, corpus) in CORPORA.iter() {
+        group.throughput(Throughput::Bytes(corpus.len() as u64));
+        let mut arena = Bump::new();
+        group.bench_function(*name, |b| b.iter(|| {
+            arena.reset();
+            parse_in(&arena, black_box(corpus)).unwrap();
+        }));
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "arena"))]
+fn bench_parse_in(_: &mut Criterion) {}
+
+criterion_group!(benches, bench_parse, bench_parse_in, bench_serialize);
+criterion_main!(benches);
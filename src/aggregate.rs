@@ -0,0 +1,172 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Aggregations over arrays of objects, such as the sum of a field.
+//!
+//! The field is a JSON Pointer into each element, `""` for the element itself. Elements where it
+//! is missing or `null` are skipped, and any other value that is not a number is an error.
+//! Numbers are added exactly where [`JNumber`] arithmetic allows.
+
+use std::collections::BTreeMap;
+
+use crate::convert::describe;
+use crate::data_structures::{JNumber, JObject, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::serializer::Serialize;
+
+/// Returns the sum of `field` over the elements of `array`, `0` for none.
+///
+/// ```
+/// # use json::json;
+/// # use json::aggregate::sum;
+/// let orders = json!("[{\"total\": 10.5}, {\"total\": 0.25}, {\"total\": null}, {}]");
+/// assert_eq!(json!("10.75"), sum(&orders, "/total").unwrap());
+/// assert!(sum(&json!("[{\"total\": \"10\"}]"), "/total").is_err());
+/// ```
+pub fn sum(array: &JValue, field: &str) -> Result<JValue, JsonError> {
+    let mut total = JNumber::from_f64(0.0).unwrap();
+    for n in numbers(array, field)? {
+        total = total.checked_add(n).ok_or_else(overflow)?;
+    }
+    Ok(JValue::Number(total))
+}
+
+/// Returns the mean of `field` over the elements of `array`, `null` for none.
+///
+/// ```
+/// # use json::json;
+/// # use json::aggregate::avg;
+/// assert_eq!(json!("2.5"), avg(&json!("[{\"n\": 2}, {\"n\": 3}]"), "/n").unwrap());
+/// assert_eq!(json!("null"), avg(&json!("[]"), "/n").unwrap());
+/// ```
+pub fn avg(array: &JValue, field: &str) -> Result<JValue, JsonError> {
+    let numbers = numbers(array, field)?;
+    if numbers.is_empty() {
+        return Ok(JValue::Null);
+    }
+    let mut total = JNumber::from_f64(0.0).unwrap();
+    for n in &numbers {
+        total = total.checked_add(n).ok_or_else(overflow)?;
+    }
+    let count = JNumber::from_f64(numbers.len() as f64).unwrap();
+    Ok(JValue::Number(total.checked_div(&count).ok_or_else(overflow)?))
+}
+
+/// Returns the smallest `field` of the elements of `array`, as written, `null` for none.
+///
+/// ```
+/// # use json::json;
+/// # use json::aggregate::{max, min};
+/// let prices = json!("[{\"price\": 3}, {\"price\": 1.50}, {\"price\": 2}]");
+/// assert_eq!(json!("1.50"), min(&prices, "/price").unwrap());
+/// assert_eq!(json!("3"), max(&prices, "/price").unwrap());
+/// ```
+pub fn min(array: &JValue, field: &str) -> Result<JValue, JsonError> {
+    Ok(numbers(array, field)?.into_iter().min().map_or(JValue::Null, |n| JValue::Number(n.clone())))
+}
+
+/// Returns the largest `field` of the elements of `array`, as written, `null` for none.
+pub fn max(array: &JValue, field: &str) -> Result<JValue, JsonError> {
+    Ok(numbers(array, field)?.into_iter().max().map_or(JValue::Null, |n| JValue::Number(n.clone())))
+}
+
+/// Groups the elements of `array` by their `key` into an object from each key to the array of
+/// its elements, in their original order. String keys are used as they are, and other keys as
+/// their serialization; elements without the key are grouped under `null`.
+///
+/// ```
+/// # use json::json;
+/// # use json::aggregate::{group_by, sum};
+/// let orders = json!("[{\"user\": \"ada\", \"total\": 3}, {\"user\": 7, \"total\": 1}, {\"user\": \"ada\", \"total\": 2}]");
+/// let groups = group_by(&orders, "/user").unwrap();
+/// assert_eq!(json!("{\"ada\": [{\"user\": \"ada\", \"total\": 3}, {\"user\": \"ada\", \"total\": 2}],\
+///                    \"7\": [{\"user\": 7, \"total\": 1}]}"), groups);
+/// assert_eq!(json!("5"), sum(groups.pointer("/ada").unwrap(), "/total").unwrap());
+/// ```
+pub fn group_by(array: &JValue, key: &str) -> Result<JValue, JsonError> {
+    let mut groups: BTreeMap<String, Vec<JValue>> = BTreeMap::new();
+    for element in elements(array)? {
+        let group = match element.pointer(key) {
+            Some(JValue::String(s)) => s.as_str().to_string(),
+            Some(value) => value.serialize(),
+            None => JValue::Null.serialize()
+        };
+        groups.entry(group).or_default().push(element.clone());
+    }
+    let mut object = JObject::new();
+    for (group, elements) in groups {
+        object.insert(group, JValue::Array(elements));
+    }
+    Ok(JValue::Object(object))
+}
+
+fn elements(array: &JValue) -> Result<&Vec<JValue>, JsonError> {
+    match array {
+        JValue::Array(a) => Ok(a),
+        _ => Err(JsonError::new(ErrorKind::InvalidType, format!("Expected an array, found {}", describe(array))))
+    }
+}
+
+/// The numbers at `field` in the elements of `array`, skipping missing fields and nulls
+fn numbers<'a>(array: &'a JValue, field: &str) -> Result<Vec<&'a JNumber>, JsonError> {
+    let mut numbers = Vec::new();
+    for (i, element) in elements(array)?.iter().enumerate() {
+        match element.pointer(field) {
+            Some(JValue::Number(n)) => numbers.push(n),
+            Some(JValue::Null) | None => {}
+            Some(value) => return Err(JsonError::new(
+                ErrorKind::InvalidType, format!("Expected a number, found {} at /{}{}", describe(value), i, field)))
+        }
+    }
+    Ok(numbers)
+}
+
+fn overflow() -> JsonError {
+    JsonError::new(ErrorKind::InvalidType, "The result is too large for a number".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregate::{avg, group_by, max, min, sum};
+    use crate::error::ErrorKind;
+    use crate::json;
+
+    #[test]
+    fn test_aggregates() {
+        let values = json!("[{\"a\": {\"n\": 0.1}}, {\"a\": {\"n\": 0.2}}, {\"a\": null}, {\"a\": {\"n\": -1}}]");
+        assert_eq!(json!("-0.7"), sum(&values, "/a/n").unwrap());
+        assert_eq!(json!("-0.7").to_string(), sum(&values, "/a/n").unwrap().to_string());
+        assert_eq!(json!("-1"), min(&values, "/a/n").unwrap());
+        assert_eq!(json!("0.2"), max(&values, "/a/n").unwrap());
+        assert_eq!(json!("0.5"), avg(&json!("[0, 1]"), "").unwrap());
+        assert_eq!(json!("0"), sum(&json!("[]"), "/n").unwrap());
+        assert_eq!(json!("null"), max(&json!("[{}]"), "/n").unwrap());
+        let error = sum(&json!("[{\"n\": 1}, {\"n\": true}]"), "/n").unwrap_err();
+        assert_eq!(ErrorKind::InvalidType, error.kind());
+        assert_eq!("Expected a number, found the boolean true at /1/n", error.message());
+        assert_eq!(ErrorKind::InvalidType, avg(&json!("{}"), "/n").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_group_by() {
+        let values = json!("[{\"k\": true}, {}, {\"k\": \"true\"}, {\"k\": [1]}, {\"k\": null}]");
+        assert_eq!(json!("{\"true\": [{\"k\": true}, {\"k\": \"true\"}], \"null\": [{}, {\"k\": null}], \"[1]\": [{\"k\": [1]}]}"),
+                   group_by(&values, "/k").unwrap());
+        assert_eq!(json!("{}"), group_by(&json!("[]"), "/k").unwrap());
+    }
+}
@@ -0,0 +1,410 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Arrays of objects as [Apache Arrow] record batches, the columnar format analytics tools
+//! share. The batches can be written to Parquet files with the `ArrowWriter` of the [parquet]
+//! crate.
+//!
+//! Each row is an object, and each column a member of the objects. A row without the member,
+//! or with `null` for it, has a null in the column.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use json::json;
+//! # use json::arrow::{infer_schema, to_record_batch};
+//! use arrow_array::{Array, Int64Array, StringArray};
+//!
+//! let rows = json!("[{\"id\": 1, \"name\": \"ada\"}, {\"id\": 2}]");
+//! let schema = Arc::new(infer_schema(&rows).unwrap());
+//! let batch = to_record_batch(&rows, &schema).unwrap();
+//! assert_eq!(2, batch.num_rows());
+//! let ids = batch.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+//! assert_eq!(2, ids.value(1));
+//! let names = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+//! assert!(names.is_null(1));
+//! ```
+//!
+//! [Apache Arrow]:https://arrow.apache.org/
+//! [parquet]:https://crates.io/crates/parquet
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use arrow_array::types::{Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type,
+                         UInt32Type, UInt64Type, UInt8Type};
+use arrow_array::{ArrayRef, BooleanArray, LargeStringArray, ListArray, NullArray, PrimitiveArray, RecordBatch,
+                  RecordBatchOptions, StringArray, StructArray};
+use arrow_buffer::{NullBuffer, OffsetBuffer, ScalarBuffer};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef};
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JObject, JValue};
+use crate::error::{ErrorKind, JsonError};
+
+/// The type of a column found in the rows so far
+#[derive(Debug, Clone, PartialEq)]
+enum Inferred {
+    Null,
+    Boolean,
+    Int,
+    Float,
+    Utf8,
+    List(Box<Inferred>),
+    Struct(Vec<(String, Inferred)>),
+}
+
+/// A value of a column, with the row it is in
+type Cell<'a> = (usize, Option<&'a JValue>);
+
+/// Infers the schema of an array of objects. Every field is nullable, and the fields of the
+/// schema and of structs are sorted by name.
+///
+/// Integers are `Int64` columns, and a column with other numbers too is `Float64`. Strings are
+/// `Utf8`, arrays `List` and objects `Struct`, both with their items or members merged the same
+/// way. A column of nulls only is `Null`. A column with values of different types, such as a
+/// string and a number, fails with [`ErrorKind::InvalidType`].
+pub fn infer_schema(value: &JValue) -> Result<Schema, JsonError> {
+    let mut inferred = Inferred::Struct(Vec::new());
+    for (row, object) in rows(value)?.into_iter().enumerate() {
+        inferred = merge(inferred, infer_object(object, row, "")?, row, "")?;
+    }
+    match inferred {
+        Inferred::Struct(fields) => Ok(Schema::new(fields.into_iter().map(|(name, t)| field(&name, t)).collect::<Vec<_>>())),
+        _ => unreachable!()
+    }
+}
+
+/// Converts an array of objects to a record batch with the given schema, which may come from
+/// [`infer_schema`].
+///
+/// The supported types are `Null`, `Boolean`, the integer and floating point types, `Utf8`,
+/// `LargeUtf8`, `List` and `Struct`; others fail with [`ErrorKind::InvalidSchema`]. A value
+/// which does not fit its column fails with [`ErrorKind::InvalidType`], and a null in a field
+/// which is not nullable with [`ErrorKind::Validation`].
+pub fn to_record_batch(value: &JValue, schema: &SchemaRef) -> Result<RecordBatch, JsonError> {
+    batch(&rows(value)?, 0, schema)
+}
+
+/// Converts an array of objects to record batches of at most `batch_size` rows each, see
+/// [`to_record_batch`]
+pub fn to_record_batches(value: &JValue, schema: &SchemaRef, batch_size: usize) -> Result<Vec<RecordBatch>, JsonError> {
+    let batch_size = batch_size.max(1);
+    rows(value)?.chunks(batch_size).enumerate().map(|(i, rows)| batch(rows, i * batch_size, schema)).collect()
+}
+
+/// Converts rows to a batch, numbering them from `first` in errors
+fn batch(rows: &[&JObject], first: usize, schema: &SchemaRef) -> Result<RecordBatch, JsonError> {
+    let columns = schema.fields().iter().map(|field| {
+        let cells: Vec<Cell> = rows.iter().enumerate()
+            .map(|(row, object)| (first + row, member(object, field.name())))
+            .collect();
+        column(&cells, field, field.name())
+    }).collect::<Result<Vec<_>, _>>()?;
+    let options = RecordBatchOptions::new().with_row_count(Some(rows.len()));
+    RecordBatch::try_new_with_options(schema.clone(), columns, &options).map_err(arrow_error)
+}
+
+fn rows(value: &JValue) -> Result<Vec<&JObject>, JsonError> {
+    let rows = match value {
+        JValue::Array(a) => a,
+        _ => return Err(JsonError::new(
+            ErrorKind::InvalidType, format!("Expected an array of objects, found {}", describe(value))))
+    };
+    rows.iter().enumerate().map(|(i, row)| match row {
+        JValue::Object(o) => Ok(o),
+        _ => Err(JsonError::new(ErrorKind::InvalidType, format!("Expected an object, found {} at /{}", describe(row), i)))
+    }).collect()
+}
+
+fn member<'a>(object: &'a JObject, name: &str) -> Option<&'a JValue> {
+    object.get(&name.to_string()).filter(|value| !matches!(value, JValue::Null))
+}
+
+fn child_path(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_string() } else { format!("{}.{}", path, name) }
+}
+
+fn arrow_error(error: arrow_schema::ArrowError) -> JsonError {
+    JsonError::new(ErrorKind::Validation, error.to_string())
+}
+
+fn infer(value: &JValue, row: usize, path: &str) -> Result<Inferred, JsonError> {
+    Ok(match value {
+        JValue::Null => Inferred::Null,
+        JValue::Boolean(_) => Inferred::Boolean,
+        JValue::Number(_) if i64::from_jvalue(value).is_ok() => Inferred::Int,
+        JValue::Number(_) => Inferred::Float,
+        JValue::String(_) => Inferred::Utf8,
+        JValue::Array(items) => {
+            let mut item = Inferred::Null;
+            for value in items {
+                item = merge(item, infer(value, row, path)?, row, path)?;
+            }
+            Inferred::List(Box::new(item))
+        }
+        JValue::Object(object) => infer_object(object, row, path)?
+    })
+}
+
+fn infer_object(object: &JObject, row: usize, path: &str) -> Result<Inferred, JsonError> {
+    let mut fields = object.iter()
+        .map(|(name, value)| Ok((name.clone(), infer(value, row, &child_path(path, name))?)))
+        .collect::<Result<Vec<_>, JsonError>>()?;
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(Inferred::Struct(fields))
+}
+
+fn merge(a: Inferred, b: Inferred, row: usize, path: &str) -> Result<Inferred, JsonError> {
+    Ok(match (a, b) {
+        (Inferred::Null, t) | (t, Inferred::Null) => t,
+        (Inferred::Int, Inferred::Float) | (Inferred::Float, Inferred::Int) => Inferred::Float,
+        (Inferred::List(a), Inferred::List(b)) => Inferred::List(Box::new(merge(*a, *b, row, path)?)),
+        (Inferred::Struct(a), Inferred::Struct(b)) => {
+            let mut fields = a;
+            for (name, t) in b {
+                match fields.iter().position(|(n, _)| *n == name) {
+                    Some(i) => {
+                        let existing = std::mem::replace(&mut fields[i].1, Inferred::Null);
+                        fields[i].1 = merge(existing, t, row, &child_path(path, &name))?;
+                    }
+                    None => fields.push((name, t))
+                }
+            }
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            Inferred::Struct(fields)
+        }
+        (a, b) if a == b => a,
+        (a, b) => return Err(JsonError::new(ErrorKind::InvalidType, format!(
+            "The field {} is {} in the row {}, but {} before", path, type_name(&b), row, type_name(&a))))
+    })
+}
+
+fn type_name(t: &Inferred) -> &'static str {
+    match t {
+        Inferred::Null => "null",
+        Inferred::Boolean => "a boolean",
+        Inferred::Int | Inferred::Float => "a number",
+        Inferred::Utf8 => "a string",
+        Inferred::List(_) => "an array",
+        Inferred::Struct(_) => "an object"
+    }
+}
+
+fn field(name: &str, t: Inferred) -> Field {
+    let data_type = match t {
+        Inferred::Null => DataType::Null,
+        Inferred::Boolean => DataType::Boolean,
+        Inferred::Int => DataType::Int64,
+        Inferred::Float => DataType::Float64,
+        Inferred::Utf8 => DataType::Utf8,
+        Inferred::List(item) => DataType::List(Arc::new(field("item", *item))),
+        Inferred::Struct(fields) => DataType::Struct(fields.into_iter().map(|(n, t)| field(&n, t)).collect::<Fields>())
+    };
+    Field::new(name, data_type, true)
+}
+
+fn mismatch(expected: &str, value: &JValue, row: usize, path: &str) -> JsonError {
+    JsonError::new(ErrorKind::InvalidType, format!(
+        "Expected {} for the field {}, found {} in the row {}", expected, path, describe(value), row))
+}
+
+fn nulls(cells: &[Cell], field: &Field, path: &str) -> Result<Option<NullBuffer>, JsonError> {
+    if let Some((row, _)) = cells.iter().find(|(_, value)| value.is_none()) {
+        if !field.is_nullable() {
+            return Err(JsonError::new(ErrorKind::Validation, format!(
+                "The field {} is not nullable, but it is null in the row {}", path, row)));
+        }
+        return Ok(Some(NullBuffer::from(cells.iter().map(|(_, value)| value.is_some()).collect::<Vec<_>>())));
+    }
+    Ok(None)
+}
+
+macro_rules! numbers {
+    ($cells:expr, $path:expr, $t:ty, $expected:expr, |$value:ident| $convert:expr) => {
+        Arc::new($cells.iter().map(|&(row, value)| match value {
+            Some($value @ JValue::Number(_)) => $convert.map(Some).ok_or_else(|| mismatch($expected, $value, row, $path)),
+            Some(value) => Err(mismatch($expected, value, row, $path)),
+            None => Ok(None)
+        }).collect::<Result<PrimitiveArray<$t>, JsonError>>()?)
+    };
+}
+
+macro_rules! integers {
+    ($cells:expr, $path:expr, $t:ty, $native:ty, $expected:expr) => {
+        numbers!($cells, $path, $t, $expected, |value| <$native>::from_jvalue(value).ok())
+    };
+}
+
+fn column(cells: &[Cell], field: &Field, path: &str) -> Result<ArrayRef, JsonError> {
+    let nulls = nulls(cells, field, path)?;
+    let float = |value: &JValue| match value {
+        JValue::Number(n) => Some(n.get_f64_value()),
+        _ => None
+    };
+    let array: ArrayRef = match field.data_type() {
+        DataType::Null => match cells.iter().find_map(|&(row, value)| value.map(|value| (row, value))) {
+            Some((row, value)) => return Err(mismatch("null", value, row, path)),
+            None => Arc::new(NullArray::new(cells.len()))
+        },
+        DataType::Boolean => Arc::new(cells.iter().map(|&(row, value)| match value {
+            Some(JValue::Boolean(b)) => Ok(Some(*b)),
+            Some(value) => Err(mismatch("a boolean", value, row, path)),
+            None => Ok(None)
+        }).collect::<Result<BooleanArray, JsonError>>()?),
+        DataType::Int8 => integers!(cells, path, Int8Type, i8, "an 8 bit integer"),
+        DataType::Int16 => integers!(cells, path, Int16Type, i16, "a 16 bit integer"),
+        DataType::Int32 => integers!(cells, path, Int32Type, i32, "a 32 bit integer"),
+        DataType::Int64 => integers!(cells, path, Int64Type, i64, "a 64 bit integer"),
+        DataType::UInt8 => integers!(cells, path, UInt8Type, u8, "an unsigned 8 bit integer"),
+        DataType::UInt16 => integers!(cells, path, UInt16Type, u16, "an unsigned 16 bit integer"),
+        DataType::UInt32 => integers!(cells, path, UInt32Type, u32, "an unsigned 32 bit integer"),
+        DataType::UInt64 => integers!(cells, path, UInt64Type, u64, "an unsigned 64 bit integer"),
+        DataType::Float32 => numbers!(cells, path, Float32Type, "a number", |value| float(value).map(|f| f as f32)),
+        DataType::Float64 => numbers!(cells, path, Float64Type, "a number", |value| float(value)),
+        DataType::Utf8 => Arc::new(strings(cells, path)?.into_iter().collect::<StringArray>()),
+        DataType::LargeUtf8 => Arc::new(strings(cells, path)?.into_iter().collect::<LargeStringArray>()),
+        DataType::List(item) => {
+            let mut offsets = vec![0i32];
+            let mut items = Vec::new();
+            for &(row, value) in cells {
+                match value {
+                    Some(JValue::Array(values)) => items.extend(values.iter().map(|value| {
+                        (row, Some(value).filter(|value| !matches!(value, JValue::Null)))
+                    })),
+                    Some(value) => return Err(mismatch("an array", value, row, path)),
+                    None => {}
+                }
+                offsets.push(i32::try_from(items.len()).map_err(|_| JsonError::new(
+                    ErrorKind::Validation, format!("The field {} has too many items for a list", path)))?);
+            }
+            let values = column(&items, item, &format!("{}[]", path))?;
+            Arc::new(ListArray::try_new(item.clone(), OffsetBuffer::new(ScalarBuffer::from(offsets)), values, nulls)
+                .map_err(arrow_error)?)
+        }
+        DataType::Struct(fields) => {
+            let mut objects = Vec::with_capacity(cells.len());
+            for &(row, value) in cells {
+                match value {
+                    Some(JValue::Object(object)) => objects.push((row, Some(object))),
+                    Some(value) => return Err(mismatch("an object", value, row, path)),
+                    None => objects.push((row, None))
+                }
+            }
+            let arrays = fields.iter().map(|field| {
+                let cells: Vec<Cell> = objects.iter()
+                    .map(|&(row, object)| (row, object.and_then(|object| member(object, field.name()))))
+                    .collect();
+                column(&cells, field, &child_path(path, field.name()))
+            }).collect::<Result<Vec<_>, _>>()?;
+            Arc::new(StructArray::try_new_with_length(fields.clone(), arrays, nulls, cells.len()).map_err(arrow_error)?)
+        }
+        other => return Err(JsonError::new(
+            ErrorKind::InvalidSchema, format!("The type {} of the field {} is not supported", other, path)))
+    };
+    Ok(array)
+}
+
+fn strings<'a>(cells: &[Cell<'a>], path: &str) -> Result<Vec<Option<&'a str>>, JsonError> {
+    cells.iter().map(|&(row, value)| match value {
+        Some(JValue::String(s)) => Ok(Some(s.as_str())),
+        Some(value) => Err(mismatch("a string", value, row, path)),
+        None => Ok(None)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, Int64Type, UInt8Type};
+    use arrow_array::Array;
+    use arrow_schema::{DataType, Field, Fields, Schema};
+
+    use crate::arrow::{infer_schema, to_record_batch, to_record_batches};
+    use crate::error::ErrorKind;
+    use crate::json;
+
+    #[test]
+    fn test_infer_schema() {
+        let rows = json!("[{\"n\": 1, \"tags\": [], \"user\": {\"id\": 1}, \"none\": null},
+                           {\"n\": 1.5, \"tags\": [\"a\", null], \"user\": {\"name\": \"ada\"}, \"ok\": true}]");
+        let user = Fields::from(vec![Field::new("id", DataType::Int64, true), Field::new("name", DataType::Utf8, true)]);
+        assert_eq!(Ok(Schema::new(vec![
+            Field::new("n", DataType::Float64, true),
+            Field::new("none", DataType::Null, true),
+            Field::new("ok", DataType::Boolean, true),
+            Field::new_list("tags", Field::new("item", DataType::Utf8, true), true),
+            Field::new_struct("user", user, true),
+        ])), infer_schema(&rows));
+        let error = infer_schema(&json!("[{\"a\": {\"b\": 1}}, {\"a\": {\"b\": \"x\"}}]")).unwrap_err();
+        assert_eq!((ErrorKind::InvalidType, "The field a.b is a string in the row 1, but a number before"),
+                   (error.kind(), error.message()));
+        assert_eq!("Expected an object, found the number 1 at /0", infer_schema(&json!("[1]")).unwrap_err().message());
+    }
+
+    #[test]
+    fn test_to_record_batch() {
+        let rows = json!("[{\"n\": 1, \"tags\": [], \"user\": {\"id\": 1}, \"none\": null},
+                           {\"n\": 1.5, \"tags\": [\"a\", null], \"user\": {\"name\": \"ada\"}, \"ok\": true}, {}]");
+        let schema = Arc::new(infer_schema(&rows).unwrap());
+        let batch = to_record_batch(&rows, &schema).unwrap();
+        assert_eq!((3, 5), (batch.num_rows(), batch.num_columns()));
+        let n = batch.column(0).as_primitive::<Float64Type>();
+        assert_eq!((1.0, 1.5, true), (n.value(0), n.value(1), n.is_null(2)));
+        assert_eq!(3, batch.column(1).len());
+        let tags = batch.column(3).as_list::<i32>();
+        assert_eq!((0, 2, true), (tags.value(0).len(), tags.value(1).len(), tags.is_null(2)));
+        assert!(tags.value(1).is_null(1));
+        let user = batch.column(4).as_struct();
+        assert_eq!("ada", user.column(1).as_string::<i32>().value(1));
+        assert!(user.column(0).is_null(1));
+        assert!(user.is_null(2));
+
+        let batches = to_record_batches(&rows, &schema, 2).unwrap();
+        assert_eq!(vec![2, 1], batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>());
+        assert_eq!("Expected a number for the field n, found the string \"x\" in the row 2",
+                   to_record_batches(&json!("[{}, {}, {\"n\": \"x\"}]"), &schema, 2).unwrap_err().message());
+        let empty = to_record_batch(&json!("[{}, {}]"), &Arc::new(Schema::empty())).unwrap();
+        assert_eq!(2, empty.num_rows());
+    }
+
+    #[test]
+    fn test_provided_schema() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("byte", DataType::UInt8, false),
+            Field::new("big", DataType::Int64, true),
+        ]));
+        let batch = to_record_batch(&json!("[{\"byte\": 255, \"big\": 9007199254740993, \"extra\": 1}]"), &schema).unwrap();
+        assert_eq!(255, batch.column(0).as_primitive::<UInt8Type>().value(0));
+        assert_eq!(9007199254740993, batch.column(1).as_primitive::<Int64Type>().value(0));
+        for (rows, kind, message) in [
+            ("[{\"byte\": 256}]", ErrorKind::InvalidType,
+             "Expected an unsigned 8 bit integer for the field byte, found the number 256 in the row 0"),
+            ("[{\"byte\": 1}, {\"big\": 1}]", ErrorKind::Validation,
+             "The field byte is not nullable, but it is null in the row 1"),
+            ("[{\"byte\": 1, \"big\": \"1\"}]", ErrorKind::InvalidType,
+             "Expected a 64 bit integer for the field big, found the string \"1\" in the row 0"),
+        ] {
+            let error = to_record_batch(&json!(rows), &schema).unwrap_err();
+            assert_eq!((kind, message), (error.kind(), error.message()));
+        }
+        let schema = Arc::new(Schema::new(vec![Field::new("d", DataType::Date32, true)]));
+        assert_eq!(ErrorKind::InvalidSchema, to_record_batch(&json!("[{}]"), &schema).unwrap_err().kind());
+    }
+}
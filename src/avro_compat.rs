@@ -0,0 +1,391 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! The [JSON encoding] of Apache Avro, for tools which exchange Avro data as JSON without an
+//! Avro library.
+//!
+//! Avro's JSON encoding differs from plain JSON in two ways: a value of a union type other than
+//! `null` is wrapped in an object naming its branch, as `{"string": "a"}`, and `bytes` and
+//! `fixed` values are strings with a code point from U+0000 to U+00FF for each byte. [`encode`]
+//! converts plain JSON to this encoding and [`decode`] converts it back, both checking the value
+//! against the schema. Logical types are handled as their underlying types.
+//!
+//! ```
+//! # use json::json;
+//! # use json::avro_compat::{decode, encode};
+//! let schema = json!("{\"type\": \"record\", \"name\": \"User\", \"namespace\": \"com.example\", \"fields\": [
+//!     {\"name\": \"name\", \"type\": \"string\"},
+//!     {\"name\": \"email\", \"type\": [\"null\", \"string\"], \"default\": null},
+//!     {\"name\": \"tags\", \"type\": {\"type\": \"array\", \"items\": \"string\"}, \"default\": []}
+//! ]}");
+//! let plain = json!("{\"name\": \"Ada\", \"email\": \"ada@example.com\"}");
+//! let encoded = encode(&plain, &schema).unwrap();
+//! assert_eq!(json!("{\"name\": \"Ada\", \"email\": {\"string\": \"ada@example.com\"}, \"tags\": []}"), encoded);
+//! assert_eq!(Ok(json!("{\"name\": \"Ada\", \"email\": \"ada@example.com\", \"tags\": []}")),
+//!            decode(&encoded, &schema));
+//! assert_eq!("Expected a string, found the number 1 at /name",
+//!            encode(&json!("{\"name\": 1}"), &schema).unwrap_err().message());
+//! ```
+//!
+//! [JSON encoding]:https://avro.apache.org/docs/1.11.1/specification/#json-encoding
+
+use std::collections::HashMap;
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::location;
+use crate::transform::push_token;
+
+const PRIMITIVES: [&str; 8] = ["null", "boolean", "int", "long", "float", "double", "bytes", "string"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Encode,
+    Decode,
+}
+
+/// The named types of a schema
+struct Names<'s> {
+    by_name: HashMap<String, &'s JValue>,
+    full_names: HashMap<*const JValue, String>,
+}
+
+/// Converts a plain JSON value to Avro's JSON encoding of it with the Avro `schema`.
+///
+/// A value of a union is encoded as the first branch it is valid for. Missing fields of a
+/// record are filled in with their defaults. A `bytes` value is a string of code points up to
+/// U+00FF, or an array of the bytes as numbers. Fails with [`ErrorKind::Validation`] if the
+/// value does not match the schema, or [`ErrorKind::InvalidSchema`] if the schema is invalid.
+pub fn encode(value: &JValue, schema: &JValue) -> Result<JValue, JsonError> {
+    let names = Names::new(schema)?;
+    names.convert(value, schema, Mode::Encode, &mut String::new())
+}
+
+/// Converts a value in Avro's JSON encoding with the Avro `schema` back to plain JSON, the
+/// reverse of [`encode`]. `bytes` and `fixed` values stay strings.
+pub fn decode(value: &JValue, schema: &JValue) -> Result<JValue, JsonError> {
+    let names = Names::new(schema)?;
+    names.convert(value, schema, Mode::Decode, &mut String::new())
+}
+
+fn invalid_schema(message: String) -> JsonError {
+    JsonError::new(ErrorKind::InvalidSchema, message)
+}
+
+fn mismatch(expected: &str, value: &JValue, path: &str) -> JsonError {
+    JsonError::new(ErrorKind::Validation,
+                   format!("Expected {}, found {} at {}", expected, describe(value), location(path)))
+}
+
+fn is_integer_in(value: &JValue, min: i64, max: i64) -> bool {
+    matches!(value, JValue::Number(_)) && i64::from_jvalue(value).is_ok_and(|i| (min..=max).contains(&i))
+}
+
+fn is_bytes(text: &str) -> bool {
+    text.chars().all(|c| c <= '\u{ff}')
+}
+
+impl<'s> Names<'s> {
+    fn new(schema: &'s JValue) -> Result<Names<'s>, JsonError> {
+        let mut names = Names { by_name: HashMap::new(), full_names: HashMap::new() };
+        names.collect(schema, "")?;
+        Ok(names)
+    }
+
+    fn collect(&mut self, schema: &'s JValue, namespace: &str) -> Result<(), JsonError> {
+        let object = match schema {
+            JValue::Array(branches) => return branches.iter().try_for_each(|branch| self.collect(branch, namespace)),
+            JValue::Object(object) => object,
+            _ => return Ok(())
+        };
+        match &schema["type"] {
+            JValue::String(t) if matches!(t.as_str(), "record" | "error" | "enum" | "fixed") => {
+                let name = match &schema["name"] {
+                    JValue::String(name) => name.as_str(),
+                    _ => return Err(invalid_schema(format!("The {} schema has no name", t)))
+                };
+                let namespace = match &schema["namespace"] {
+                    JValue::String(namespace) if !name.contains('.') => namespace.as_str(),
+                    _ => namespace
+                };
+                let full_name = if name.contains('.') || namespace.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}.{}", namespace, name)
+                };
+                let short_name = full_name.rsplit('.').next().unwrap().to_string();
+                if self.by_name.insert(full_name.clone(), schema).is_some() {
+                    return Err(invalid_schema(format!("The type {} is defined twice", full_name)));
+                }
+                self.by_name.entry(short_name).or_insert(schema);
+                let namespace = full_name.rsplit_once('.').map_or("", |(namespace, _)| namespace).to_string();
+                self.full_names.insert(schema, full_name);
+                if let Some(JValue::Array(fields)) = object.get(&"fields".to_string()) {
+                    for field in fields {
+                        self.collect(&field["type"], &namespace)?;
+                    }
+                }
+                Ok(())
+            }
+            JValue::String(t) if t.as_str() == "array" => self.collect(&schema["items"], namespace),
+            JValue::String(t) if t.as_str() == "map" => self.collect(&schema["values"], namespace),
+            nested => self.collect(nested, namespace)
+        }
+    }
+
+    /// Returns the definition of a schema which is a name and the primitive type or the `type`
+    /// of any other schema
+    fn resolve(&self, schema: &'s JValue) -> Result<(&'s JValue, &'s str), JsonError> {
+        match schema {
+            JValue::String(name) if PRIMITIVES.contains(&name.as_str()) => Ok((schema, name.as_str())),
+            JValue::String(name) => match self.by_name.get(name.as_str()) {
+                Some(definition) => self.resolve(definition),
+                None => Err(invalid_schema(format!("Unknown type {}", name)))
+            },
+            JValue::Array(_) => Ok((schema, "union")),
+            JValue::Object(_) => match &schema["type"] {
+                JValue::String(t) if PRIMITIVES.contains(&t.as_str())
+                    || matches!(t.as_str(), "record" | "error" | "enum" | "array" | "map" | "fixed") => Ok((schema, t.as_str())),
+                JValue::String(_) | JValue::Object(_) | JValue::Array(_) => self.resolve(&schema["type"]),
+                _ => Err(invalid_schema(format!("The schema {} has no type", schema)))
+            },
+            _ => Err(invalid_schema(format!("The schema {} is not a name, an object or a union", schema)))
+        }
+    }
+
+    /// Returns the name of a union branch in the JSON encoding
+    fn branch_name(&self, branch: &'s JValue) -> Result<String, JsonError> {
+        let (definition, t) = self.resolve(branch)?;
+        Ok(match self.full_names.get(&(definition as *const JValue)) {
+            Some(full_name) => full_name.clone(),
+            None => t.to_string()
+        })
+    }
+
+    /// Checks whether a plain value is valid for a union branch, not looking into containers
+    fn matches(&self, value: &JValue, branch: &'s JValue) -> Result<bool, JsonError> {
+        let (definition, t) = self.resolve(branch)?;
+        Ok(match (t, value) {
+            ("null", JValue::Null) | ("boolean", JValue::Boolean(_)) => true,
+            ("int", _) => is_integer_in(value, i32::MIN as i64, i32::MAX as i64),
+            ("long", _) => is_integer_in(value, i64::MIN, i64::MAX),
+            ("float" | "double", JValue::Number(_)) | ("string", JValue::String(_)) => true,
+            ("bytes", JValue::String(s)) => is_bytes(s),
+            ("bytes", JValue::Array(a)) => a.iter().all(|b| is_integer_in(b, 0, 255)),
+            ("enum", JValue::String(s)) => match &definition["symbols"] {
+                JValue::Array(symbols) => symbols.iter().any(|symbol| matches!(symbol, JValue::String(x) if x == s)),
+                _ => false
+            },
+            ("fixed", JValue::String(s)) => is_bytes(s) && usize::from_jvalue(&definition["size"]) == Ok(s.chars().count()),
+            ("array", JValue::Array(_)) | ("map", JValue::Object(_)) => true,
+            ("record" | "error", JValue::Object(object)) => match &definition["fields"] {
+                JValue::Array(fields) => object.iter().all(|(k, _)| fields.iter().any(|f| matches!(&f["name"], JValue::String(n) if n == k)))
+                    && fields.iter().all(|f| match &f["name"] {
+                        JValue::String(n) => object.get(&n.to_string()).is_some() || f.pointer("/default").is_some(),
+                        _ => false
+                    }),
+                _ => false
+            },
+            _ => false
+        })
+    }
+
+    fn convert(&self, value: &JValue, schema: &'s JValue, mode: Mode, path: &mut String) -> Result<JValue, JsonError> {
+        let (definition, t) = self.resolve(schema)?;
+        match (t, value) {
+            ("null", JValue::Null) | ("boolean", JValue::Boolean(_)) | ("string", JValue::String(_))
+            | ("float" | "double", JValue::Number(_)) => Ok(value.clone()),
+            ("null", _) => Err(mismatch("null", value, path)),
+            ("boolean", _) => Err(mismatch("a boolean", value, path)),
+            ("string", _) => Err(mismatch("a string", value, path)),
+            ("float" | "double", _) => Err(mismatch("a number", value, path)),
+            ("int", _) if is_integer_in(value, i32::MIN as i64, i32::MAX as i64) => Ok(value.clone()),
+            ("int", _) => Err(mismatch("a 32 bit integer", value, path)),
+            ("long", _) if is_integer_in(value, i64::MIN, i64::MAX) => Ok(value.clone()),
+            ("long", _) => Err(mismatch("a 64 bit integer", value, path)),
+            ("bytes", JValue::String(s)) if is_bytes(s) => Ok(value.clone()),
+            ("bytes", JValue::Array(a)) if mode == Mode::Encode && a.iter().all(|b| is_integer_in(b, 0, 255)) => {
+                let text: String = a.iter().map(|b| u8::from_jvalue(b).unwrap() as char).collect();
+                Ok(JValue::String(JString::from(text)))
+            }
+            ("bytes", _) => Err(mismatch("bytes", value, path)),
+            ("enum" | "fixed", _) => if self.matches(value, definition)? {
+                Ok(value.clone())
+            } else if t == "enum" {
+                Err(mismatch(&format!("a symbol of {}", self.branch_name(definition)?), value, path))
+            } else {
+                Err(mismatch(&format!("{} bytes", definition["size"]), value, path))
+            },
+            ("array", JValue::Array(items)) => {
+                let len = path.len();
+                let mut converted = Vec::with_capacity(items.len());
+                for (i, item) in items.iter().enumerate() {
+                    push_token(path, &i.to_string());
+                    converted.push(self.convert(item, &definition["items"], mode, path)?);
+                    path.truncate(len);
+                }
+                Ok(JValue::Array(converted))
+            }
+            ("array", _) => Err(mismatch("an array", value, path)),
+            ("map", JValue::Object(object)) => {
+                let len = path.len();
+                let mut converted = JObject::new();
+                for (key, member) in object.iter() {
+                    push_token(path, key);
+                    converted.insert(key.clone(), self.convert(member, &definition["values"], mode, path)?);
+                    path.truncate(len);
+                }
+                Ok(JValue::Object(converted))
+            }
+            ("map", _) => Err(mismatch("an object", value, path)),
+            ("record" | "error", JValue::Object(object)) => self.convert_record(object, definition, mode, path),
+            ("record" | "error", _) => Err(mismatch(&format!("a {} record", self.branch_name(definition)?), value, path)),
+            ("union", _) => self.convert_union(value, definition, mode, path),
+            _ => Err(invalid_schema(format!("Unknown type {}", t)))
+        }
+    }
+
+    fn convert_record(&self, object: &JObject, definition: &'s JValue, mode: Mode, path: &mut String)
+                      -> Result<JValue, JsonError> {
+        let fields = match &definition["fields"] {
+            JValue::Array(fields) => fields,
+            _ => return Err(invalid_schema(format!("The record {} has no fields", self.branch_name(definition)?)))
+        };
+        let mut converted = JObject::new();
+        let len = path.len();
+        for field in fields {
+            let name = match &field["name"] {
+                JValue::String(name) => name.to_string(),
+                _ => return Err(invalid_schema(format!("A field of {} has no name", self.branch_name(definition)?)))
+            };
+            push_token(path, &name);
+            let value = match (object.get(&name), field.pointer("/default")) {
+                (Some(value), _) => self.convert(value, &field["type"], mode, path)?,
+                // Defaults are plain JSON, with the value of a union for its first branch.
+                (None, Some(default)) if mode == Mode::Encode => self.convert(default, &field["type"], mode, path)?,
+                (None, Some(default)) => default.clone(),
+                (None, None) => return Err(JsonError::new(
+                    ErrorKind::Validation, format!("Missing field {} at {}", name, location(&path[..len])))),
+            };
+            converted.insert(name, value);
+            path.truncate(len);
+        }
+        if let Some((key, _)) = object.iter().find(|(key, _)| converted.get(key).is_none()) {
+            return Err(JsonError::new(ErrorKind::Validation, format!(
+                "Unknown field {} of {} at {}", key, self.branch_name(definition)?, location(path))));
+        }
+        Ok(JValue::Object(converted))
+    }
+
+    fn convert_union(&self, value: &JValue, definition: &'s JValue, mode: Mode, path: &mut String)
+                     -> Result<JValue, JsonError> {
+        let branches = match definition {
+            JValue::Array(branches) => branches,
+            _ => unreachable!()
+        };
+        let names = branches.iter().map(|branch| self.branch_name(branch)).collect::<Result<Vec<_>, _>>()?;
+        if mode == Mode::Encode {
+            for (branch, name) in branches.iter().zip(&names) {
+                if self.matches(value, branch)? {
+                    let converted = self.convert(value, branch, mode, path)?;
+                    if name == "null" {
+                        return Ok(converted);
+                    }
+                    let mut wrapped = JObject::new();
+                    wrapped.insert(name.clone(), converted);
+                    return Ok(JValue::Object(wrapped));
+                }
+            }
+            return Err(mismatch(&format!("a value of the union [{}]", names.join(", ")), value, path));
+        }
+        let wrapped = match value {
+            JValue::Null if names.iter().any(|name| name == "null") => return Ok(JValue::Null),
+            JValue::Object(object) if object.len() == 1 => object.iter().next().unwrap(),
+            _ => return Err(mismatch(&format!("a value of the union [{}]", names.join(", ")), value, path))
+        };
+        match names.iter().position(|name| name == wrapped.0 && name != "null") {
+            Some(i) => {
+                let len = path.len();
+                push_token(path, wrapped.0);
+                let converted = self.convert(wrapped.1, &branches[i], mode, path)?;
+                path.truncate(len);
+                Ok(converted)
+            }
+            None => Err(JsonError::new(ErrorKind::Validation, format!(
+                "Unknown branch {} of the union [{}] at {}", wrapped.0, names.join(", "), location(path))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::avro_compat::{decode, encode};
+    use crate::error::ErrorKind;
+    use crate::json;
+
+    #[test]
+    fn test_encode_and_decode() {
+        let schema = json!("{\"type\": \"record\", \"name\": \"a.Event\", \"fields\": [
+            {\"name\": \"id\", \"type\": {\"type\": \"fixed\", \"name\": \"Id\", \"size\": 2}},
+            {\"name\": \"payload\", \"type\": [\"null\", \"bytes\", {\"type\": \"map\", \"values\": \"long\"}, \"Id\"]},
+            {\"name\": \"kind\", \"type\": [{\"type\": \"enum\", \"name\": \"Kind\", \"symbols\": [\"A\", \"B\"]}, \"string\"]},
+            {\"name\": \"next\", \"type\": [\"null\", \"Event\"], \"default\": null},
+            {\"name\": \"at\", \"type\": {\"type\": \"long\", \"logicalType\": \"timestamp-millis\"}, \"default\": 0}
+        ]}");
+        let plain = json!("{\"id\": \"\\u00ff\\u0000\", \"payload\": [104, 105], \"kind\": \"B\",
+                            \"next\": {\"id\": \"ab\", \"payload\": {\"n\": 1}, \"kind\": \"C\"}}");
+        let encoded = json!("{\"id\": \"\\u00ff\\u0000\", \"payload\": {\"bytes\": \"hi\"}, \"kind\": {\"a.Kind\": \"B\"},
+                              \"next\": {\"a.Event\": {\"id\": \"ab\", \"payload\": {\"map\": {\"n\": 1}},
+                                         \"kind\": {\"string\": \"C\"}, \"next\": null, \"at\": 0}}, \"at\": 0}");
+        assert_eq!(Ok(encoded.clone()), encode(&plain, &schema));
+        let decoded = json!("{\"id\": \"\\u00ff\\u0000\", \"payload\": \"hi\", \"kind\": \"B\",
+                              \"next\": {\"id\": \"ab\", \"payload\": {\"n\": 1}, \"kind\": \"C\", \"next\": null, \"at\": 0},
+                              \"at\": 0}");
+        assert_eq!(Ok(decoded.clone()), decode(&encoded, &schema));
+        assert_eq!(Ok(encoded), encode(&decoded, &schema));
+
+        assert_eq!(Ok(json!("null")), decode(&json!("null"), &json!("[\"null\", \"int\"]")));
+        assert_eq!(Ok(json!("{\"int\": 1}")), encode(&json!("1"), &json!("[\"null\", \"int\"]")));
+        assert_eq!(Ok(json!("{\"long\": 3000000000}")), encode(&json!("3000000000"), &json!("[\"int\", \"long\"]")));
+        assert_eq!(Ok(json!("{\"double\": 1.5}")), encode(&json!("1.5"), &json!("[\"int\", \"double\"]")));
+    }
+
+    #[test]
+    fn test_errors() {
+        let schema = json!("{\"type\": \"record\", \"name\": \"R\", \"fields\": [{\"name\": \"u\", \"type\": [\"null\", \"int\"]}]}");
+        for (result, kind, message) in [
+            (encode(&json!("{}"), &schema), ErrorKind::Validation, "Missing field u at the root"),
+            (encode(&json!("{\"u\": 1, \"v\": 2}"), &schema), ErrorKind::Validation, "Unknown field v of R at the root"),
+            (encode(&json!("{\"u\": \"1\"}"), &schema), ErrorKind::Validation,
+             "Expected a value of the union [null, int], found the string \"1\" at /u"),
+            (decode(&json!("{\"u\": {\"long\": 1}}"), &schema), ErrorKind::Validation,
+             "Unknown branch long of the union [null, int] at /u"),
+            (decode(&json!("{\"u\": {\"int\": 1.5}}"), &schema), ErrorKind::Validation,
+             "Expected a 32 bit integer, found the number 1.5 at /u/int"),
+            (encode(&json!("[\"\\u0100\"]"), &json!("{\"type\": \"array\", \"items\": \"bytes\"}")), ErrorKind::Validation,
+             "Expected bytes, found the string \"\u{100}\" at /0"),
+            (encode(&json!("1"), &json!("\"Missing\"")), ErrorKind::InvalidSchema, "Unknown type Missing"),
+            (encode(&json!("1"), &json!("{\"type\": \"enum\", \"symbols\": []}")), ErrorKind::InvalidSchema,
+             "The enum schema has no name"),
+        ] {
+            let error = result.unwrap_err();
+            assert_eq!((kind, message), (error.kind(), error.message()));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Base64 encoding (RFC 4648), with the standard and the URL-safe alphabets
+
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes `bytes`, padded with `=` for the standard alphabet and unpadded for the URL-safe one
+pub(crate) fn encode(bytes: &[u8], url_safe: bool) -> String {
+    let alphabet = if url_safe { URL_SAFE } else { STANDARD };
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..=chunk.len() {
+            result.push(alphabet[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        if !url_safe {
+            for _ in chunk.len()..3 {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Decodes `text` in the given alphabet, with or without padding. Returns `None` on characters
+/// outside the alphabet, a truncated input or non-zero trailing bits.
+pub(crate) fn decode(text: &str, url_safe: bool) -> Option<Vec<u8>> {
+    let alphabet = if url_safe { URL_SAFE } else { STANDARD };
+    let text = text.as_bytes();
+    let unpadded = if text.len().is_multiple_of(4) && !text.is_empty() {
+        let padding = text.iter().rev().take(2).take_while(|b| **b == b'=').count();
+        &text[..text.len() - padding]
+    } else {
+        text
+    };
+    if unpadded.len() % 4 == 1 {
+        return None;
+    }
+    let mut result = Vec::with_capacity(unpadded.len() * 3 / 4);
+    for chunk in unpadded.chunks(4) {
+        let mut n = 0_u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let index = alphabet.iter().position(|a| a == c)? as u32;
+            n |= index << (18 - 6 * i);
+        }
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        let len = chunk.len() - 1;
+        if bytes[len..].iter().any(|b| *b != 0) {
+            return None;
+        }
+        result.extend_from_slice(&bytes[..len]);
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base64::{decode, encode};
+
+    #[test]
+    fn test_encode() {
+        // Test vectors from RFC 4648, section 10
+        let vectors = ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy"];
+        for (i, expected) in vectors.iter().enumerate() {
+            assert_eq!(*expected, encode(&b"foobar"[..i], false));
+            assert_eq!(Some(b"foobar"[..i].to_vec()), decode(expected, false));
+            assert_eq!(Some(b"foobar"[..i].to_vec()), decode(expected.trim_end_matches('='), false));
+        }
+        assert_eq!("-_8", encode(&[0xfb, 0xff], true));
+        assert_eq!("+/8=", encode(&[0xfb, 0xff], false));
+        assert_eq!(Some(vec![0xfb, 0xff]), decode("-_8", true));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(None, decode("Zg=", false));
+        assert_eq!(None, decode("Z", false));
+        assert_eq!(None, decode("Zh==", false));
+        assert_eq!(None, decode("-_8", false));
+        assert_eq!(None, decode("Zm9v!", false));
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! `json-tool`: validate, format, minify, compare and query JSON files from the command line.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::process::exit;
+
+use json::data_structures::JValue;
+use json::format::{minify_stream, to_colored_string, to_pretty_string, PrettyOptions, Theme};
+use json::jsonpath::JsonPath;
+use json::parser::parse;
+use json::schema::Schema;
+use json::serializer::Serialize;
+use json::stream::extract;
+
+const USAGE: &str = "\
+Usage: json-tool <command> [arguments]
+
+Commands:
+  validate <file> [--schema <schema-file>]  check that a file is valid JSON
+  fmt <file> [--indent <n>]                 pretty-print a file
+  minify <file>                             print a file without whitespace
+  diff <file> <file>                        list the differences between two files
+  get <pointer-or-path> <file>              print the values at a JSON Pointer or JSONPath
+  repl <file>                               query a file interactively
+
+A <file> of - reads the standard input.";
+
+/// The failure of a command, with its exit status
+enum Failure {
+    /// The input is invalid, or the files differ: status 1
+    Negative(String),
+    /// The command line is wrong or a file can not be read: status 2
+    Usage(String),
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(failure) = run(&args) {
+        match failure {
+            Failure::Negative(message) => {
+                if !message.is_empty() {
+                    eprintln!("{}", message);
+                }
+                exit(1);
+            }
+            Failure::Usage(message) => {
+                eprintln!("{}", message);
+                exit(2);
+            }
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Failure> {
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    match args.as_slice() {
+        ["validate", file] => validate(file, None),
+        ["validate", file, "--schema", schema] => validate(file, Some(schema)),
+        ["fmt", file] => format(file, "2"),
+        ["fmt", file, "--indent", indent] => format(file, indent),
+        ["minify", file] => minify(file),
+        ["diff", file1, file2] => diff(file1, file2),
+        ["get", path, file] => get(path, file),
+        ["repl", file] => repl(file),
+        ["help" | "--help" | "-h"] => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(Failure::Usage(USAGE.to_string()))
+    }
+}
+
+fn open(file: &str) -> Result<Box<dyn Read>, Failure> {
+    if file == "-" {
+        return Ok(Box::new(io::stdin()));
+    }
+    File::open(file).map(|f| Box::new(BufReader::new(f)) as Box<dyn Read>)
+        .map_err(|e| Failure::Usage(format!("{}: {}", file, e)))
+}
+
+fn load(file: &str) -> Result<JValue, Failure> {
+    let mut text = String::new();
+    open(file)?.read_to_string(&mut text).map_err(|e| Failure::Usage(format!("{}: {}", file, e)))?;
+    parse(&text).map_err(|e| Failure::Negative(format!("{}: {}", file, e)))
+}
+
+fn validate(file: &str, schema: Option<&str>) -> Result<(), Failure> {
+    let value = load(file)?;
+    if let Some(schema_file) = schema {
+        let schema = Schema::compile(&load(schema_file)?)
+            .map_err(|e| Failure::Usage(format!("{}: {}", schema_file, e)))?;
+        schema.validate(&value).map_err(|e| Failure::Negative(format!("{}: {}", file, e)))?;
+    }
+    println!("{}: valid", file);
+    Ok(())
+}
+
+fn format(file: &str, indent: &str) -> Result<(), Failure> {
+    let indent = indent.parse().map_err(|_| Failure::Usage(format!("Invalid indent {}", indent)))?;
+    let options = PrettyOptions { indent, ..PrettyOptions::default() };
+    println!("{}", to_pretty_string(&load(file)?, &options));
+    Ok(())
+}
+
+/// Streams the file, so that files too large to load can be minified
+fn minify(file: &str) -> Result<(), Failure> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    minify_stream(open(file)?, &mut out).map_err(|e| Failure::Negative(format!("{}: {}", file, e)))?;
+    writeln!(out).map_err(|e| Failure::Negative(e.to_string()))
+}
+
+fn diff(file1: &str, file2: &str) -> Result<(), Failure> {
+    let differences = load(file1)?.diff(&load(file2)?);
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    if differences.is_empty() {
+        Ok(())
+    } else {
+        Err(Failure::Negative(String::new()))
+    }
+}
+
+/// Streams the file, so that values can be picked out of files too large to load
+fn get(path: &str, file: &str) -> Result<(), Failure> {
+    let count = extract(open(file)?, path, |value| println!("{}", value.serialize()))
+        .map_err(|e| match e.kind() {
+            json::error::ErrorKind::InvalidPointer => Failure::Usage(e.to_string()),
+            _ => Failure::Negative(format!("{}: {}", file, e))
+        })?;
+    if count == 0 {
+        return Err(Failure::Negative(format!("{}: No value at {}", file, path)));
+    }
+    Ok(())
+}
+
+const REPL_HELP: &str = "\
+Enter a JSON Pointer such as /items/0 or a JSONPath such as $.items[*].id to print the values
+it selects. An empty line prints the whole document.
+
+  :help  show this help
+  :quit  exit";
+
+/// Reads expressions from the standard input and prints the values they select
+fn repl(file: &str) -> Result<(), Failure> {
+    if file == "-" {
+        return Err(Failure::Usage("The repl reads expressions from the standard input, it can not \
+                                   read the document from it too".to_string()));
+    }
+    let document = load(file)?;
+    let interactive = io::stdin().is_terminal();
+    let theme = if io::stdout().is_terminal() { Theme::from_env() } else { Theme::none() };
+    if interactive {
+        println!("Loaded {}. Type :help for help.", file);
+    }
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if interactive {
+            print!("> ");
+            // The prompt is only a convenience
+            let _ = io::stdout().flush();
+        }
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(e)) => return Err(Failure::Usage(format!("Can not read the input: {}", e))),
+            None => return Ok(())
+        };
+        let expression = line.trim();
+        let values = match expression {
+            ":quit" | ":q" => return Ok(()),
+            ":help" => {
+                println!("{}", REPL_HELP);
+                continue;
+            }
+            "" => vec![&document],
+            _ if expression.starts_with('$') => match JsonPath::parse(expression) {
+                Ok(path) => path.select(&document),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            },
+            _ => match document.pointer(expression) {
+                Some(value) => vec![value],
+                None if expression.starts_with('/') => Vec::new(),
+                None => {
+                    eprintln!("Unknown command or expression {}, type :help for help", expression);
+                    continue;
+                }
+            }
+        };
+        if values.is_empty() {
+            eprintln!("No value at {}", expression);
+        }
+        for value in values {
+            println!("{}", to_colored_string(value, &theme));
+        }
+    }
+}
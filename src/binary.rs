@@ -0,0 +1,52 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Binary data embedded in JSON documents as base64 strings.
+
+use crate::base64;
+use crate::data_structures::{JString, JValue};
+
+impl JValue {
+    /// Creates a string value holding `bytes` encoded in standard, padded base64.
+    ///
+    /// ```
+    /// # use json::data_structures::JValue;
+    /// let j_value = JValue::from_bytes_base64(b"\x00\xffjson");
+    /// assert_eq!(j_value, JValue::String("AP9qc29u".into()));
+    /// assert_eq!(Some(b"\x00\xffjson".to_vec()), j_value.as_bytes_base64());
+    /// ```
+    pub fn from_bytes_base64(bytes: &[u8]) -> JValue {
+        JValue::String(JString::from(base64::encode(bytes, false)))
+    }
+
+    /// Decodes a base64 string value, in the standard or the URL-safe alphabet and with or
+    /// without padding. Returns [`None`] for other values and for strings that are not base64.
+    ///
+    /// ```
+    /// # use json::data_structures::JValue;
+    /// assert_eq!(Some(vec![0xfb, 0xff]), JValue::String("+/8=".into()).as_bytes_base64());
+    /// assert_eq!(Some(vec![0xfb, 0xff]), JValue::String("-_8".into()).as_bytes_base64());
+    /// assert_eq!(None, JValue::String("not base64!".into()).as_bytes_base64());
+    /// assert_eq!(None, JValue::Null.as_bytes_base64());
+    /// ```
+    pub fn as_bytes_base64(&self) -> Option<Vec<u8>> {
+        match self {
+            JValue::String(s) => base64::decode(s, false).or_else(|| base64::decode(s, true)),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Canonical serialization following the [JSON Canonicalization Scheme] (RFC 8785), and hashes
+//! computed over it.
+//!
+//! Object members are sorted by the UTF-16 code units of their keys, no whitespace is emitted,
+//! numbers are written in their shortest round-trip form as ECMAScript does, and strings only
+//! escape what JSON requires. Documents that differ only in key order, whitespace or number
+//! spelling (`1.0`, `1`, `10e-1`) have the same canonical form. A number out of the range of a
+//! double, such as `1e400`, has no canonical form, and canonicalizing it is an error.
+//!
+//! [JSON Canonicalization Scheme]:https://datatracker.ietf.org/doc/html/rfc8785
+
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+
+impl JValue {
+    /// Returns the canonical serialization of the value. Fails with [`ErrorKind::InvalidType`]
+    /// if the value has a number out of the range of a double.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("{\"b\": [1.0, 1e21, 0.000001, \"x\"], \"a\": {\"y\": null, \"x\": true}}");
+    /// assert_eq!(Ok("{\"a\":{\"x\":true,\"y\":null},\"b\":[1,1e+21,0.000001,\"x\"]}".to_string()),
+    ///            j_value.to_canonical_string());
+    /// assert!(json!("{\"a\": 1e400}").to_canonical_string().is_err());
+    /// ```
+    pub fn to_canonical_string(&self) -> Result<String, JsonError> {
+        let mut result = String::new();
+        write_canonical(self, &mut result)?;
+        Ok(result)
+    }
+
+    /// Hashes the canonical serialization of the value with the digest `D`. Fails like
+    /// [`JValue::to_canonical_string`].
+    ///
+    /// ```
+    /// # #[cfg(feature = "sha256")] {
+    /// # use json::json;
+    /// use sha2::Sha256;
+    /// let a = json!("{\"a\": 1, \"b\": 2}");
+    /// let b = json!("{ \"b\" : 2.0, \"a\" : 1 }");
+    /// assert_eq!(a.canonical_hash::<Sha256>().unwrap(), b.canonical_hash::<Sha256>().unwrap());
+    /// # }
+    /// ```
+    #[cfg(feature = "digest")]
+    pub fn canonical_hash<D: digest::Digest>(&self) -> Result<digest::Output<D>, JsonError> {
+        self.to_canonical_string().map(|s| D::digest(s.as_bytes()))
+    }
+
+    /// Returns the SHA-256 hash of the canonical serialization of the value. Fails like
+    /// [`JValue::to_canonical_string`].
+    ///
+    /// ```
+    /// # use json::json;
+    /// let hash = json!("{\"a\": [1, 2]}").hash_sha256().unwrap();
+    /// assert_eq!(hash, json!("{\"a\":[1.0,2]}").hash_sha256().unwrap());
+    /// assert_ne!(hash, json!("{\"a\":[2,1]}").hash_sha256().unwrap());
+    /// assert!(json!("{\"a\": 1e400}").hash_sha256().is_err());
+    /// ```
+    #[cfg(feature = "sha256")]
+    pub fn hash_sha256(&self) -> Result<[u8; 32], JsonError> {
+        self.canonical_hash::<sha2::Sha256>().map(Into::into)
+    }
+}
+
+/// Appends the canonical serialization of `value` to `out`, failing like
+/// [`JValue::to_canonical_string`]. On an error `out` holds the serialization up to the number.
+pub fn write_canonical(value: &JValue, out: &mut String) -> Result<(), JsonError> {
+    match value {
+        JValue::Object(o) => {
+            let mut members: Vec<(Vec<u16>, &String, &JValue)> =
+                o.iter().map(|(k, v)| (k.encode_utf16().collect(), k, v)).collect();
+            members.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            out.push('{');
+            for (i, (_, k, v)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(k, out);
+                out.push(':');
+                write_canonical(v, out)?;
+            }
+            out.push('}');
+        }
+        JValue::Array(a) => {
+            out.push('[');
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(v, out)?;
+            }
+            out.push(']');
+        }
+        JValue::String(s) => write_string(s, out),
+        JValue::Number(n) if !n.get_f64_value().is_finite() => {
+            return Err(JsonError::new(ErrorKind::InvalidType,
+                                      format!("The number {} is out of the range of a double and has no canonical form", n)))
+        }
+        JValue::Number(n) => write_number(n.get_f64_value(), out),
+        JValue::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        JValue::Null => out.push_str("null"),
+    }
+    Ok(())
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Writes a number as ECMAScript's `Number.prototype.toString` does. Non-finite numbers, which
+/// JSON can not represent, are written as `null` like `JSON.stringify` does.
+pub(crate) fn write_number(n: f64, out: &mut String) {
+    if !n.is_finite() {
+        out.push_str("null");
+        return;
+    }
+    if n == 0.0 {
+        out.push('0');
+        return;
+    }
+    if n < 0.0 {
+        out.push('-');
+    }
+    // `{:e}` gives the shortest digits that round-trip, as `d.ddde[-]x`
+    let scientific = format!("{:e}", n.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let point = exponent[1..].parse::<i32>().unwrap() + 1;
+    if k <= point && point <= 21 {
+        out.push_str(&digits);
+        out.extend(std::iter::repeat_n('0', (point - k) as usize));
+    } else if 0 < point && point <= 21 {
+        out.push_str(&digits[..point as usize]);
+        out.push('.');
+        out.push_str(&digits[point as usize..]);
+    } else if -6 < point && point <= 0 {
+        out.push_str("0.");
+        out.extend(std::iter::repeat_n('0', -point as usize));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        out.push('e');
+        out.push(if point > 0 { '+' } else { '-' });
+        out.push_str(&(point - 1).abs().to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canonical::write_number;
+    use crate::data_structures::{JObject, JValue};
+    use crate::error::ErrorKind;
+    use crate::json;
+
+    fn number(n: f64) -> String {
+        let mut result = String::new();
+        write_number(n, &mut result);
+        result
+    }
+
+    #[test]
+    fn test_numbers() {
+        // Samples from RFC 8785, appendix B
+        assert_eq!("0", number(-0.0));
+        assert_eq!("1e+21", number(1e21));
+        assert_eq!("333333333.3333333", number("333333333.33333329".parse().unwrap()));
+        assert_eq!("1e+23", number(1e23));
+        assert_eq!("9007199254740992", number(9007199254740992.0));
+        assert_eq!("295147905179352830000", number(295147905179352830000.0));
+        assert_eq!("1e-7", number(1e-7));
+        assert_eq!("0.000001", number(0.000001));
+        assert_eq!("-1.5", number(-1.5));
+        assert_eq!("5e-324", number(5e-324));
+        assert_eq!("1.7976931348623157e+308", number(f64::MAX));
+        assert_eq!("null", number(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_canonical_string() {
+        let mut j_object = JObject::new();
+        for (i, key) in ["\u{20ac}", "\r", "\u{fb33}", "1", "\u{1f600}", "\u{fc}"].iter().enumerate() {
+            j_object.insert(key.to_string(), crate::parser::parse(&i.to_string()).unwrap());
+        }
+        // Sorted by UTF-16 code units, which puts U+1F600 (D83D DE00) before U+FB33
+        assert_eq!("{\"\\r\":1,\"1\":3,\"\u{fc}\":5,\"\u{20ac}\":0,\"\u{1f600}\":4,\"\u{fb33}\":2}",
+                   JValue::Object(j_object).to_canonical_string().unwrap());
+        let j_value = JValue::String("a\"\u{1}\n/".into());
+        assert_eq!("\"a\\\"\\u0001\\n/\"", j_value.to_canonical_string().unwrap());
+        assert_eq!(json!("{\"b\": [], \"a\": {}}").to_canonical_string().unwrap(), "{\"a\":{},\"b\":[]}");
+    }
+
+    #[test]
+    fn test_numbers_out_of_range() {
+        for json in ["1e400", "[1, -1e400]", "{\"a\": {\"b\": 1e309}}"] {
+            let error = json!(json).to_canonical_string().unwrap_err();
+            assert_eq!(ErrorKind::InvalidType, error.kind(), "{}", json);
+        }
+        assert_eq!("The number 1e400 is out of the range of a double and has no canonical form",
+                   json!("[1e400]").to_canonical_string().unwrap_err().message());
+        assert_eq!(Ok("[1e+308,null]".to_string()), json!("[1e308, null]").to_canonical_string());
+    }
+}
@@ -0,0 +1,355 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Generation of Rust type definitions from sample JSON documents
+
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+
+/// Options for [`rust_types`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodegenOptions {
+    /// The name of the struct generated for the root object
+    pub root_name: String,
+    /// The traits every generated struct derives
+    pub derives: Vec<String>,
+    /// Derive `serde::Serialize` and `serde::Deserialize`, and add `#[serde(rename)]` attributes
+    /// to fields whose name differs from their JSON key
+    pub serde: bool,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        CodegenOptions {
+            root_name: "Root".to_string(),
+            derives: vec!["Debug".to_string(), "Clone".to_string(), "PartialEq".to_string()],
+            serde: false,
+        }
+    }
+}
+
+/// The inferred type of the values seen at one position of the samples
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    /// Only nulls or empty arrays were seen
+    Unknown,
+    Bool,
+    Integer,
+    Float,
+    String,
+    Array(Box<Shape>),
+    Object(Vec<Field>),
+    /// Values of incompatible types were seen
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    key: String,
+    shape: Shape,
+    optional: bool,
+}
+
+/// Generates Rust struct definitions that can hold the given sample.
+///
+/// The sample is an object, or an array of objects that are merged into one struct. Fields
+/// that are missing from some of the objects, or that are `null`, become an [`Option`]. Nested
+/// objects become their own structs, named after their key. Values with incompatible types
+/// become a [`JValue`](crate::data_structures::JValue).
+///
+/// ```
+/// # use json::json;
+/// # use json::codegen::{rust_types, CodegenOptions};
+/// let sample = json!("[{\"id\": 1, \"userName\": \"a\", \"address\": {\"city\": \"x\"}},
+///                      {\"id\": 2, \"userName\": \"b\", \"score\": 0.5}]");
+/// let options = CodegenOptions { root_name: "User".to_string(), ..CodegenOptions::default() };
+/// assert_eq!(rust_types(&sample, &options).unwrap(), "\
+/// #[derive(Debug, Clone, PartialEq)]
+/// pub struct User {
+///     pub address: Option<Address>,
+///     pub id: i64,
+///     pub score: Option<f64>,
+///     // JSON key: \"userName\"
+///     pub user_name: String,
+/// }
+///
+/// #[derive(Debug, Clone, PartialEq)]
+/// pub struct Address {
+///     pub city: String,
+/// }
+/// ");
+/// ```
+pub fn rust_types(sample: &JValue, options: &CodegenOptions) -> Result<String, JsonError> {
+    let shape = match infer(sample) {
+        Shape::Array(element) => *element,
+        shape => shape
+    };
+    let fields = match shape {
+        Shape::Object(fields) => fields,
+        _ => return Err(JsonError::new(
+            ErrorKind::InvalidType, "The sample must be an object or an array of objects"))
+    };
+    let mut generator = Generator { options, names: Vec::new(), output: String::new() };
+    let root_name = generator.unique_name(&options.root_name);
+    generator.generate_struct(&root_name, &fields);
+    Ok(generator.output)
+}
+
+fn infer(value: &JValue) -> Shape {
+    match value {
+        JValue::Null => Shape::Unknown,
+        JValue::Boolean(_) => Shape::Bool,
+        JValue::Number(n) => {
+            let text = n.to_string();
+            if text.contains(['.', 'e', 'E']) {
+                Shape::Float
+            } else {
+                Shape::Integer
+            }
+        }
+        JValue::String(_) => Shape::String,
+        JValue::Array(a) => Shape::Array(Box::new(
+            a.iter().map(infer).fold(Shape::Unknown, merge))),
+        JValue::Object(o) => {
+            let mut fields: Vec<Field> = o.iter().map(|(k, v)| {
+                let shape = infer(v);
+                Field { key: k.clone(), optional: shape == Shape::Unknown, shape }
+            }).collect();
+            fields.sort_by(|f1, f2| f1.key.cmp(&f2.key));
+            Shape::Object(fields)
+        }
+    }
+}
+
+fn merge(s1: Shape, s2: Shape) -> Shape {
+    match (s1, s2) {
+        (Shape::Unknown, s) | (s, Shape::Unknown) => s,
+        (Shape::Integer, Shape::Float) | (Shape::Float, Shape::Integer) => Shape::Float,
+        (Shape::Array(e1), Shape::Array(e2)) => Shape::Array(Box::new(merge(*e1, *e2))),
+        (Shape::Object(f1), Shape::Object(f2)) => Shape::Object(merge_fields(f1, f2)),
+        (s1, s2) if s1 == s2 => s1,
+        _ => Shape::Any
+    }
+}
+
+fn merge_fields(f1: Vec<Field>, mut f2: Vec<Field>) -> Vec<Field> {
+    let mut result = Vec::new();
+    for mut field in f1 {
+        match f2.iter().position(|f| f.key == field.key) {
+            Some(i) => {
+                let other = f2.remove(i);
+                let nullable = field.shape == Shape::Unknown || other.shape == Shape::Unknown;
+                field.optional = field.optional || other.optional || nullable;
+                field.shape = merge(field.shape, other.shape);
+            }
+            None => field.optional = true
+        }
+        result.push(field);
+    }
+    for mut field in f2 {
+        field.optional = true;
+        result.push(field);
+    }
+    result.sort_by(|a, b| a.key.cmp(&b.key));
+    result
+}
+
+struct Generator<'a> {
+    options: &'a CodegenOptions,
+    names: Vec<String>,
+    output: String,
+}
+
+impl Generator<'_> {
+    fn unique_name(&mut self, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut i = 2;
+        while self.names.contains(&unique) {
+            unique = format!("{}{}", name, i);
+            i += 1;
+        }
+        self.names.push(unique.clone());
+        unique
+    }
+
+    fn generate_struct(&mut self, name: &str, fields: &[Field]) {
+        let mut nested = Vec::new();
+        let mut derives = self.options.derives.clone();
+        if self.options.serde {
+            derives.push("serde::Serialize".to_string());
+            derives.push("serde::Deserialize".to_string());
+        }
+        let mut result = String::new();
+        if !derives.is_empty() {
+            result.push_str(&format!("#[derive({})]\n", derives.join(", ")));
+        }
+        result.push_str(&format!("pub struct {} {{\n", name));
+        for field in fields {
+            let field_name = field_name(&field.key);
+            if field_name.trim_start_matches("r#") != field.key {
+                if self.options.serde {
+                    result.push_str(&format!("    #[serde(rename = {:?})]\n", field.key));
+                } else {
+                    result.push_str(&format!("    // JSON key: {:?}\n", field.key));
+                }
+            }
+            let mut type_name = self.type_name(&field.key, &field.shape, &mut nested);
+            if field.optional {
+                type_name = format!("Option<{}>", type_name);
+            }
+            result.push_str(&format!("    pub {}: {},\n", field_name, type_name));
+        }
+        result.push_str("}\n");
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output.push_str(&result);
+        for (name, fields) in nested {
+            self.generate_struct(&name, &fields);
+        }
+    }
+
+    fn type_name(&mut self, key: &str, shape: &Shape, nested: &mut Vec<(String, Vec<Field>)>)
+                 -> String {
+        match shape {
+            Shape::Unknown | Shape::Any => "json::data_structures::JValue".to_string(),
+            Shape::Bool => "bool".to_string(),
+            Shape::Integer => "i64".to_string(),
+            Shape::Float => "f64".to_string(),
+            Shape::String => "String".to_string(),
+            Shape::Array(element) => format!("Vec<{}>", self.type_name(key, element, nested)),
+            Shape::Object(fields) => {
+                let name = self.unique_name(&type_name(key));
+                nested.push((name.clone(), fields.clone()));
+                name
+            }
+        }
+    }
+}
+
+/// Splits a JSON key into lowercase words at non-alphanumeric characters and case changes
+fn words(key: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lowercase = false;
+    for c in key.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lowercase = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lowercase && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lowercase = c.is_lowercase() || c.is_numeric();
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+const KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "abstract", "box", "try", "yield",
+];
+
+fn field_name(key: &str) -> String {
+    let mut name = words(key).join("_");
+    if name.is_empty() || name.starts_with(|c: char| c.is_numeric()) {
+        name.insert(0, '_');
+    }
+    if ["self", "super", "crate"].contains(&name.as_str()) {
+        name.push('_');
+    } else if KEYWORDS.contains(&name.as_str()) {
+        name.insert_str(0, "r#");
+    }
+    name
+}
+
+fn type_name(key: &str) -> String {
+    let mut name: String = words(key).iter().map(|w| {
+        let mut chars = w.chars();
+        match chars.next() {
+            Some(c) => c.to_uppercase().chain(chars).collect(),
+            None => String::new()
+        }
+    }).collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_numeric()) {
+        name.insert(0, 'T');
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{field_name, rust_types, type_name, CodegenOptions};
+    use crate::error::ErrorKind;
+    use crate::json;
+
+    #[test]
+    fn test_names() {
+        assert_eq!("user_id", field_name("userID"));
+        assert_eq!("user_id", field_name("user-id"));
+        assert_eq!("http_status2", field_name("HTTP status2"));
+        assert_eq!("r#type", field_name("type"));
+        assert_eq!("self_", field_name("self"));
+        assert_eq!("_1st", field_name("1st"));
+        assert_eq!("_", field_name("$"));
+        assert_eq!("SeatCategories", type_name("seat_categories"));
+        assert_eq!("T0", type_name("0"));
+    }
+
+    #[test]
+    fn test_serde() {
+        let options = CodegenOptions { serde: true, derives: vec![], ..CodegenOptions::default() };
+        let generated = rust_types(&json!("{\"type\": [1, 2.5], \"a b\": null, \"c\": [[]]}"),
+                                   &options).unwrap();
+        assert_eq!("\
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Root {
+    #[serde(rename = \"a b\")]
+    pub a_b: Option<json::data_structures::JValue>,
+    pub c: Vec<Vec<json::data_structures::JValue>>,
+    pub r#type: Vec<f64>,
+}
+", generated);
+    }
+
+    #[test]
+    fn test_nested_names() {
+        let generated = rust_types(&json!("{\"root\": {\"a\": true}, \"items\": [{\"root\": {}}]}"),
+                                   &CodegenOptions::default()).unwrap();
+        assert!(generated.contains("pub items: Vec<Items>,"));
+        assert!(generated.contains("pub root: Root2,"));
+        assert!(generated.contains("pub struct Items {\n    pub root: Root3,\n}"));
+    }
+
+    #[test]
+    fn test_mixed_types() {
+        let generated = rust_types(&json!("[{\"a\": 1}, {\"a\": \"x\"}]"),
+                                   &CodegenOptions::default()).unwrap();
+        assert!(generated.contains("pub a: json::data_structures::JValue,"));
+        let e = rust_types(&json!("[1]"), &CodegenOptions::default()).unwrap_err();
+        assert_eq!(ErrorKind::InvalidType, e.kind());
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Casting values to booleans, numbers and strings, one at a time or a whole document against
+//! the shape of an example.
+//!
+//! Two sets of rules are offered. [`Mode::Strict`] follows the rules of
+//! [`FromJValue`](crate::convert::FromJValue): only booleans and the strings `"true"` and
+//! `"false"` are booleans, numbers and strings containing a JSON number are numbers, and
+//! strings, numbers and booleans are strings. [`Mode::JavaScript`] follows the `Boolean()`,
+//! `Number()` and `String()` functions of JavaScript, except that values JavaScript turns into
+//! `NaN` are errors here.
+
+use std::str::FromStr;
+
+use crate::canonical::write_number;
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::location;
+use crate::transform::push_token;
+
+/// The rules of a coercion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Accept only values that are a representation of the target
+    #[default]
+    Strict,
+    /// Accept what JavaScript does: `null`, `0` and `""` are `false`, `null` is `0`, `"  42 "`
+    /// is `42`, and `[1, 2]` is `"1,2"`
+    JavaScript,
+}
+
+/// Coerces a value to a boolean.
+///
+/// ```
+/// # use json::json;
+/// # use json::coerce::{to_bool, Mode};
+/// assert_eq!(true, to_bool(&json!("\"true\""), Mode::Strict).unwrap());
+/// assert!(to_bool(&json!("0"), Mode::Strict).is_err());
+/// assert_eq!(false, to_bool(&json!("0"), Mode::JavaScript).unwrap());
+/// assert_eq!(true, to_bool(&json!("\"false\""), Mode::JavaScript).unwrap());
+/// ```
+pub fn to_bool(value: &JValue, mode: Mode) -> Result<bool, JsonError> {
+    match mode {
+        Mode::Strict => bool::from_jvalue(value),
+        Mode::JavaScript => Ok(match value {
+            JValue::Null => false,
+            JValue::Boolean(b) => *b,
+            JValue::Number(n) => n.get_f64_value() != 0.0,
+            JValue::String(s) => !s.as_str().is_empty(),
+            JValue::Array(_) | JValue::Object(_) => true,
+        })
+    }
+}
+
+/// Coerces a value to a number. Numbers, and strings containing a JSON number, keep their
+/// digits as written.
+///
+/// ```
+/// # use json::json;
+/// # use json::coerce::{to_number, Mode};
+/// assert_eq!("4.20", to_number(&json!("\"4.20\""), Mode::Strict).unwrap().to_string());
+/// assert!(to_number(&json!("\" 42 \""), Mode::Strict).is_err());
+/// assert_eq!("42", to_number(&json!("\" 42 \""), Mode::JavaScript).unwrap().to_string());
+/// assert_eq!("31", to_number(&json!("\"0x1f\""), Mode::JavaScript).unwrap().to_string());
+/// assert_eq!("7", to_number(&json!("[\"7\"]"), Mode::JavaScript).unwrap().to_string());
+/// assert_eq!("0", to_number(&json!("null"), Mode::JavaScript).unwrap().to_string());
+/// assert!(to_number(&json!("\"4 2\""), Mode::JavaScript).is_err());
+/// ```
+pub fn to_number(value: &JValue, mode: Mode) -> Result<JNumber, JsonError> {
+    let number = match (value, mode) {
+        (JValue::Number(n), _) => Some(n.clone()),
+        (JValue::String(s), Mode::Strict) => JNumber::from_str(s.as_str()).ok(),
+        (JValue::Null, Mode::JavaScript) => JNumber::from_f64(0.0),
+        (JValue::Boolean(b), Mode::JavaScript) => JNumber::from_f64(if *b { 1.0 } else { 0.0 }),
+        (JValue::String(s), Mode::JavaScript) => parse_js_number(s.as_str()),
+        (JValue::Array(_), Mode::JavaScript) => parse_js_number(&js_string(value)),
+        _ => None
+    };
+    number.ok_or_else(|| invalid_type("a number", value))
+}
+
+/// Coerces a value to a string. Strings are returned without quotes.
+///
+/// ```
+/// # use json::json;
+/// # use json::coerce::{to_string, Mode};
+/// assert_eq!("1.50", to_string(&json!("1.50"), Mode::Strict).unwrap());
+/// assert!(to_string(&json!("null"), Mode::Strict).is_err());
+/// assert_eq!("1.5", to_string(&json!("1.50"), Mode::JavaScript).unwrap());
+/// assert_eq!("null", to_string(&json!("null"), Mode::JavaScript).unwrap());
+/// assert_eq!("1,,a", to_string(&json!("[1, null, \"a\"]"), Mode::JavaScript).unwrap());
+/// ```
+pub fn to_string(value: &JValue, mode: Mode) -> Result<String, JsonError> {
+    match mode {
+        Mode::Strict => String::from_jvalue(value),
+        Mode::JavaScript => Ok(js_string(value))
+    }
+}
+
+/// Coerces the values of a document to the types of the values at the same places in `shape`.
+/// A boolean, number or string in the shape makes the value there one, a `null` accepts any
+/// value, an object coerces the members it has and keeps the others, and an array coerces
+/// every element against its first one. Errors name the place of the value that could not be
+/// coerced.
+///
+/// ```
+/// # use json::json;
+/// # use json::coerce::{to_shape, Mode};
+/// let shape = json!("{\"id\": 0, \"admin\": false, \"tags\": [\"\"], \"extra\": null}");
+/// let document = json!("{\"id\": \"42\", \"admin\": \"true\", \"tags\": [1, true], \"name\": \"ada\"}");
+/// assert_eq!(json!("{\"id\": 42, \"admin\": true, \"tags\": [\"1\", \"true\"], \"name\": \"ada\"}"),
+///            to_shape(&document, &shape, Mode::Strict).unwrap());
+/// assert_eq!("Expected a number, found the string \"x\" at /id",
+///            to_shape(&json!("{\"id\": \"x\"}"), &shape, Mode::Strict).unwrap_err().message());
+/// ```
+pub fn to_shape(value: &JValue, shape: &JValue, mode: Mode) -> Result<JValue, JsonError> {
+    to_shape_at(value, shape, mode, &mut String::new())
+}
+
+fn to_shape_at(value: &JValue, shape: &JValue, mode: Mode, path: &mut String) -> Result<JValue, JsonError> {
+    let at = |e: JsonError, path: &str| JsonError::new(e.kind(), format!("{} at {}", e.message(), location(path)));
+    Ok(match shape {
+        JValue::Null => value.clone(),
+        JValue::Boolean(_) => JValue::Boolean(to_bool(value, mode).map_err(|e| at(e, path))?),
+        JValue::Number(_) => JValue::Number(to_number(value, mode).map_err(|e| at(e, path))?),
+        JValue::String(_) => JValue::String(JString::from(to_string(value, mode).map_err(|e| at(e, path))?)),
+        JValue::Object(members) => {
+            let object = match value {
+                JValue::Object(o) => o,
+                _ => return Err(at(invalid_type("an object", value), path))
+            };
+            let mut result = JObject::new();
+            for (key, member) in object.iter() {
+                let coerced = match members.get(key) {
+                    Some(member_shape) => {
+                        let len = path.len();
+                        push_token(path, key);
+                        let coerced = to_shape_at(member, member_shape, mode, path)?;
+                        path.truncate(len);
+                        coerced
+                    }
+                    None => member.clone()
+                };
+                result.insert(key.clone(), coerced);
+            }
+            JValue::Object(result)
+        }
+        JValue::Array(elements) => {
+            let array = match value {
+                JValue::Array(a) => a,
+                _ => return Err(at(invalid_type("an array", value), path))
+            };
+            let element_shape = match elements.first() {
+                Some(element_shape) => element_shape,
+                None => return Ok(value.clone())
+            };
+            let mut result = Vec::with_capacity(array.len());
+            for (i, element) in array.iter().enumerate() {
+                let len = path.len();
+                push_token(path, &i.to_string());
+                result.push(to_shape_at(element, element_shape, mode, path)?);
+                path.truncate(len);
+            }
+            JValue::Array(result)
+        }
+    })
+}
+
+/// Parses a string as JavaScript's `Number()` does: surrounding whitespace is ignored, an empty
+/// string is `0`, and hexadecimal, octal and binary integers are accepted. `Infinity` and
+/// anything else that is not a finite number is `None`.
+fn parse_js_number(s: &str) -> Option<JNumber> {
+    let s = s.trim();
+    if s.is_empty() {
+        return JNumber::from_f64(0.0);
+    }
+    if let Ok(n) = JNumber::from_str(s) {
+        return Some(n);
+    }
+    for (prefix, radix) in [("0x", 16), ("0X", 16), ("0o", 8), ("0O", 8), ("0b", 2), ("0B", 2)] {
+        if let Some(digits) = s.strip_prefix(prefix) {
+            let n = u128::from_str_radix(digits, radix).ok()?;
+            return JNumber::from_str(&n.to_string()).ok();
+        }
+    }
+    // Rust also accepts `inf` and `NaN`, JavaScript only decimal digits here
+    if !s.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+        return None;
+    }
+    JNumber::from_f64(s.parse::<f64>().ok()?)
+}
+
+/// The string JavaScript's `String()` makes of a value
+fn js_string(value: &JValue) -> String {
+    match value {
+        JValue::Null => "null".to_string(),
+        JValue::Boolean(b) => b.to_string(),
+        JValue::Number(n) => {
+            let mut text = String::new();
+            write_number(n.get_f64_value(), &mut text);
+            text
+        }
+        JValue::String(s) => s.as_str().to_string(),
+        // Array.prototype.join writes null elements as empty strings
+        JValue::Array(a) => a.iter()
+            .map(|e| if *e == JValue::Null { String::new() } else { js_string(e) })
+            .collect::<Vec<_>>()
+            .join(","),
+        JValue::Object(_) => "[object Object]".to_string(),
+    }
+}
+
+fn invalid_type(expected: &str, value: &JValue) -> JsonError {
+    JsonError::new(ErrorKind::InvalidType, format!("Expected {}, found {}", expected, describe(value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coerce::{to_bool, to_number, to_shape, to_string, Mode};
+    use crate::json;
+
+    #[test]
+    fn test_javascript_rules() {
+        let js = Mode::JavaScript;
+        for falsy in ["null", "false", "0", "-0.0", "\"\""] {
+            assert!(!to_bool(&json!(falsy), js).unwrap(), "{}", falsy);
+        }
+        for truthy in ["[]", "{}", "\"0\"", "0.1", "true"] {
+            assert!(to_bool(&json!(truthy), js).unwrap(), "{}", truthy);
+        }
+        let number = |s| to_number(&json!(s), js).map(|n| n.to_string());
+        assert_eq!(Ok("1".to_string()), number("true"));
+        assert_eq!(Ok("0".to_string()), number("\"  \""));
+        assert_eq!(Ok("0".to_string()), number("[]"));
+        assert_eq!(Ok("7".to_string()), number("[\"7\"]"));
+        assert_eq!(Ok("0.5".to_string()), number("\".5\""));
+        assert_eq!(Ok("5".to_string()), number("\"+5\""));
+        assert_eq!(Ok("5".to_string()), number("\"0b101\""));
+        for nan in ["[1, 2]", "{}", "\"Infinity\"", "\"NaN\"", "\"inf\"", "\"0x\""] {
+            assert!(number(nan).is_err(), "{}", nan);
+        }
+        assert_eq!("[object Object]", to_string(&json!("{\"a\": 1}"), js).unwrap());
+        assert_eq!("1e+21,true,,x", to_string(&json!("[1e21, true, null, \"x\"]"), js).unwrap());
+    }
+
+    #[test]
+    fn test_strict_rules() {
+        assert!(to_bool(&json!("\"yes\""), Mode::Strict).is_err());
+        assert!(to_number(&json!("true"), Mode::Strict).is_err());
+        assert!(to_number(&json!("\"1 \""), Mode::Strict).is_err());
+        assert_eq!("false", to_string(&json!("false"), Mode::Strict).unwrap());
+        assert!(to_string(&json!("[]"), Mode::Strict).is_err());
+    }
+
+    #[test]
+    fn test_to_shape() {
+        let shape = json!("{\"a\": [{\"b\": 0}], \"c\": [], \"d\": \"\"}");
+        let value = json!("{\"a\": [{\"b\": \"1\"}, {\"b\": 2, \"e\": \"3\"}], \"c\": [\"4\"], \"d\": 5}");
+        assert_eq!(json!("{\"a\": [{\"b\": 1}, {\"b\": 2, \"e\": \"3\"}], \"c\": [\"4\"], \"d\": \"5\"}"),
+                   to_shape(&value, &shape, Mode::Strict).unwrap());
+        assert_eq!("Expected an array, found the number 1 at /a",
+                   to_shape(&json!("{\"a\": 1}"), &shape, Mode::Strict).unwrap_err().message());
+        assert_eq!("Expected a number, found the boolean true at /a/1/b",
+                   to_shape(&json!("{\"a\": [{}, {\"b\": true}]}"), &shape, Mode::Strict).unwrap_err().message());
+        assert_eq!(json!("{\"a\": [{\"b\": 1}]}"),
+                   to_shape(&json!("{\"a\": [{\"b\": true}]}"), &shape, Mode::JavaScript).unwrap());
+        assert_eq!("Expected a boolean, found null at the root",
+                   to_shape(&json!("null"), &json!("true"), Mode::Strict).unwrap_err().message());
+    }
+}
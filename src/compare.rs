@@ -0,0 +1,252 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Configurable equality and structural differences for comparing JSON data-structures
+
+use std::fmt::{Display, Formatter};
+
+use crate::data_structures::{JObject, JValue};
+use crate::serializer::Serialize;
+use crate::transform::push_token;
+
+/// A difference between two values at a JSON Pointer path, found by [`JValue::diff`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub pointer: String,
+    /// The value in the first document, [`None`] if the value was added
+    pub old: Option<JValue>,
+    /// The value in the second document, [`None`] if the value was removed
+    pub new: Option<JValue>,
+}
+
+impl Display for Difference {
+    /// Writes `+ /added: value`, `- /removed: value` or `~ /changed: old -> new`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.old, &self.new) {
+            (None, Some(new)) => write!(f, "+ {}: {}", self.pointer, new.serialize()),
+            (Some(old), None) => write!(f, "- {}: {}", self.pointer, old.serialize()),
+            (Some(old), Some(new)) =>
+                write!(f, "~ {}: {} -> {}", self.pointer, old.serialize(), new.serialize()),
+            (None, None) => write!(f, "  {}", self.pointer)
+        }
+    }
+}
+
+/// Options for [`JValue::equals_with`]. The default options compare like `==`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EqOptions {
+    /// Numbers are equal if they differ by at most this amount
+    pub epsilon: f64,
+    /// Treat arrays as sets: every element of one array must equal some element of the other
+    pub unordered_arrays: bool,
+    /// JSON Pointer paths whose values are not compared, e.g. `/meta/timestamp`. A `*` token
+    /// matches any key or index, e.g. `/items/*/id`.
+    pub ignored_paths: Vec<String>,
+}
+
+impl JValue {
+    /// Compares two values using the given [`EqOptions`].
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::compare::EqOptions;
+    /// let expected = json!("{\"ids\": [1, 2, 3], \"ratio\": 0.3, \"time\": 1}");
+    /// let actual = json!("{\"ids\": [3, 1, 2], \"ratio\": 0.30000001, \"time\": 2}");
+    /// assert_ne!(expected, actual);
+    ///
+    /// let options = EqOptions {
+    ///     epsilon: 1e-6,
+    ///     unordered_arrays: true,
+    ///     ignored_paths: vec!["/time".to_string()],
+    /// };
+    /// assert!(expected.equals_with(&actual, &options));
+    /// ```
+    pub fn equals_with(&self, other: &JValue, options: &EqOptions) -> bool {
+        equals(self, other, &mut String::new(), options)
+    }
+
+    /// Lists the differences from this value to `other`, ordered by pointer. Objects and arrays
+    /// are compared member by member and element by element, and any other change is reported
+    /// at the value that changed.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let old = json!("{\"name\": \"a\", \"tags\": [1, 2], \"gone\": null}");
+    /// let new = json!("{\"name\": \"b\", \"tags\": [1, 2, 3]}");
+    /// let differences: Vec<String> = old.diff(&new).iter().map(|d| d.to_string()).collect();
+    /// assert_eq!(vec!["- /gone: null", "~ /name: \"a\" -> \"b\"", "+ /tags/2: 3"], differences);
+    /// ```
+    pub fn diff(&self, other: &JValue) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        diff(self, other, &mut String::new(), &mut differences);
+        differences
+    }
+}
+
+fn diff(v1: &JValue, v2: &JValue, path: &mut String, differences: &mut Vec<Difference>) {
+    let len = path.len();
+    match (v1, v2) {
+        (JValue::Object(o1), JValue::Object(o2)) => {
+            let mut keys: Vec<&String> = o1.iter().map(|(k, _)| k)
+                .chain(o2.iter().map(|(k, _)| k).filter(|k| o1.get(k).is_none()))
+                .collect();
+            keys.sort();
+            for key in keys {
+                push_token(path, key);
+                match (o1.get(key), o2.get(key)) {
+                    (Some(m1), Some(m2)) => diff(m1, m2, path, differences),
+                    (m1, m2) => push_difference(differences, path, m1, m2)
+                }
+                path.truncate(len);
+            }
+        }
+        (JValue::Array(a1), JValue::Array(a2)) => {
+            for i in 0..a1.len().max(a2.len()) {
+                push_token(path, &i.to_string());
+                match (a1.get(i), a2.get(i)) {
+                    (Some(e1), Some(e2)) => diff(e1, e2, path, differences),
+                    (e1, e2) => push_difference(differences, path, e1, e2)
+                }
+                path.truncate(len);
+            }
+        }
+        _ if v1 != v2 => push_difference(differences, path, Some(v1), Some(v2)),
+        _ => ()
+    }
+}
+
+fn push_difference(differences: &mut Vec<Difference>, path: &str, old: Option<&JValue>, new: Option<&JValue>) {
+    differences.push(Difference { pointer: path.to_string(), old: old.cloned(), new: new.cloned() });
+}
+
+fn is_ignored(path: &str, options: &EqOptions) -> bool {
+    options.ignored_paths.iter().any(|pattern| {
+        let mut pattern_tokens = pattern.split('/');
+        let mut path_tokens = path.split('/');
+        loop {
+            match (pattern_tokens.next(), path_tokens.next()) {
+                (None, None) => return true,
+                (Some(p), Some(t)) if p == "*" || p == t => continue,
+                _ => return false
+            }
+        }
+    })
+}
+
+fn equals(v1: &JValue, v2: &JValue, path: &mut String, options: &EqOptions) -> bool {
+    if is_ignored(path, options) {
+        return true;
+    }
+    match (v1, v2) {
+        (JValue::Number(n1), JValue::Number(n2)) =>
+            n1 == n2 || (n1.get_f64_value() - n2.get_f64_value()).abs() <= options.epsilon,
+        (JValue::Array(a1), JValue::Array(a2)) if options.unordered_arrays =>
+            contains_all(a1, a2, path, options) && contains_all(a2, a1, path, options),
+        (JValue::Array(a1), JValue::Array(a2)) => {
+            let len = path.len();
+            a1.len() == a2.len() && a1.iter().zip(a2.iter()).enumerate().all(|(i, (e1, e2))| {
+                push_token(path, &i.to_string());
+                let result = equals(e1, e2, path, options);
+                path.truncate(len);
+                result
+            })
+        }
+        (JValue::Object(o1), JValue::Object(o2)) =>
+            contains_members(o1, o2, path, options) && contains_members(o2, o1, path, options),
+        _ => v1 == v2
+    }
+}
+
+/// Returns `true` if every element of `a1` equals some element of `a2`
+fn contains_all(a1: &[JValue], a2: &[JValue], path: &mut String, options: &EqOptions) -> bool {
+    let len = path.len();
+    a1.iter().enumerate().all(|(i, e1)| {
+        push_token(path, &i.to_string());
+        let result = is_ignored(path, options) || a2.iter().any(|e2| equals(e1, e2, path, options));
+        path.truncate(len);
+        result
+    })
+}
+
+/// Returns `true` if every member of `o1` equals the member of `o2` with the same key
+fn contains_members(o1: &JObject, o2: &JObject, path: &mut String, options: &EqOptions) -> bool {
+    let len = path.len();
+    o1.iter().all(|(k, v1)| {
+        push_token(path, k);
+        let result = match o2.get(k) {
+            Some(v2) => equals(v1, v2, path, options),
+            None => is_ignored(path, options)
+        };
+        path.truncate(len);
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::{Difference, EqOptions};
+    use crate::json;
+
+    #[test]
+    fn test_diff() {
+        let old = json!("{\"a\": [1, {\"b\": true}], \"c\": {\"d/e\": 1}, \"f\": 1}");
+        let new = json!("{\"a\": [1], \"c\": {\"d/e\": 1.0}, \"f\": [1], \"g\": {}}");
+        assert_eq!(vec![
+            Difference { pointer: "/a/1".to_string(), old: Some(json!("{\"b\": true}")), new: None },
+            Difference { pointer: "/f".to_string(), old: Some(json!("1")), new: Some(json!("[1]")) },
+            Difference { pointer: "/g".to_string(), old: None, new: Some(json!("{}")) },
+        ], old.diff(&new));
+        assert!(old.diff(&old.clone()).is_empty());
+        assert_eq!(vec![Difference { pointer: "".to_string(), old: Some(json!("1")), new: Some(json!("2")) }],
+                   json!("1").diff(&json!("2")));
+    }
+
+    #[test]
+    fn test_default_options() {
+        let options = EqOptions::default();
+        assert!(json!("[1, {\"a\": null}]").equals_with(&json!("[1.0, {\"a\": null}]"), &options));
+        assert!(!json!("[1, 2]").equals_with(&json!("[2, 1]"), &options));
+        assert!(!json!("{\"a\": 1}").equals_with(&json!("{\"a\": 1, \"b\": 2}"), &options));
+    }
+
+    #[test]
+    fn test_epsilon() {
+        let options = EqOptions { epsilon: 0.01, ..EqOptions::default() };
+        assert!(json!("[1.001]").equals_with(&json!("[1]"), &options));
+        assert!(!json!("[1.1]").equals_with(&json!("[1]"), &options));
+    }
+
+    #[test]
+    fn test_unordered_arrays() {
+        let options = EqOptions { unordered_arrays: true, ..EqOptions::default() };
+        assert!(json!("[[1, 2], 3]").equals_with(&json!("[3, [2, 1]]"), &options));
+        assert!(json!("[1, 1, 2]").equals_with(&json!("[2, 1]"), &options));
+        assert!(!json!("[1, 2]").equals_with(&json!("[1, 3]"), &options));
+    }
+
+    #[test]
+    fn test_ignored_paths() {
+        let options = EqOptions {
+            ignored_paths: vec!["/items/*/id".to_string(), "/missing".to_string()],
+            ..EqOptions::default()
+        };
+        assert!(json!("{\"items\": [{\"id\": 1, \"v\": 0}], \"missing\": 1}")
+            .equals_with(&json!("{\"items\": [{\"id\": 2, \"v\": 0}]}"), &options));
+        assert!(!json!("{\"items\": [{\"id\": 1, \"v\": 0}]}")
+            .equals_with(&json!("{\"items\": [{\"id\": 1, \"v\": 1}]}"), &options));
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Loading of configuration documents with `$env` and `$include` directives.
+//!
+//! An object with the single member `"$env"` is replaced by the value of the environment
+//! variable it names, as a string. An object with the single member `"$include"` is replaced by
+//! the document in the file it names, resolved relative to the directory of the including file
+//! and itself resolved recursively.
+//!
+//! ```no_run
+//! # use json::config;
+//! // server.json: {"port": {"$env": "PORT"}, "tls": {"$include": "tls.json"}}
+//! let config = config::load("server.json").unwrap();
+//! ```
+//!
+//! Several documents, such as built-in defaults, a system file and a user file, are combined
+//! with [`layer`].
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::convert::describe;
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::location;
+use crate::transform::push_token;
+
+/// Reads and parses the file at `path`, then resolves its directives with the environment of
+/// the process.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<JValue, JsonError> {
+    load_with(path, |name| std::env::var(name).ok())
+}
+
+/// Like [`load`], looking environment variables up with `env`.
+pub fn load_with<P: AsRef<Path>, F: FnMut(&str) -> Option<String>>(path: P, mut env: F)
+                                                                  -> Result<JValue, JsonError> {
+    let mut resolver = Resolver { env: &mut env, includes: Vec::new() };
+    resolver.include(path.as_ref(), Path::new(""))
+}
+
+/// Resolves the directives of an already parsed document. Included files are resolved
+/// relative to `base_dir`.
+pub fn resolve<P: AsRef<Path>>(value: JValue, base_dir: P) -> Result<JValue, JsonError> {
+    resolve_with(value, base_dir, |name| std::env::var(name).ok())
+}
+
+/// Like [`resolve`], looking environment variables up with `env`.
+///
+/// ```
+/// # use json::json;
+/// # use json::config;
+/// let config = json!("{\"server\": {\"port\": {\"$env\": \"PORT\"}}}");
+/// let resolved = config::resolve_with(config, ".", |name| Some(format!("value of {}", name))).unwrap();
+/// assert_eq!(json!("{\"server\": {\"port\": \"value of PORT\"}}"), resolved);
+/// ```
+pub fn resolve_with<P: AsRef<Path>, F: FnMut(&str) -> Option<String>>(value: JValue, base_dir: P, mut env: F)
+                                                                     -> Result<JValue, JsonError> {
+    let mut resolver = Resolver { env: &mut env, includes: Vec::new() };
+    resolver.resolve(value, base_dir.as_ref(), &mut String::new())
+}
+
+enum Directive {
+    Env(String),
+    Include(String),
+}
+
+struct Resolver<'a> {
+    env: &'a mut dyn FnMut(&str) -> Option<String>,
+    /// The files being included, outermost first, to detect cycles
+    includes: Vec<PathBuf>,
+}
+
+impl Resolver<'_> {
+    fn include(&mut self, file: &Path, base_dir: &Path) -> Result<JValue, JsonError> {
+        let path = base_dir.join(file);
+        let io_error = |e: std::io::Error| JsonError::new(
+            ErrorKind::Io, format!("Can not read {}: {}", path.display(), e));
+        let canonical = fs::canonicalize(&path).map_err(io_error)?;
+        if self.includes.contains(&canonical) {
+            let chain: Vec<String> = self.includes.iter().chain(Some(&canonical))
+                .map(|p| p.display().to_string()).collect();
+            return Err(JsonError::new(ErrorKind::Cycle, format!("Include cycle {}", chain.join(" -> "))));
+        }
+        let text = fs::read_to_string(&canonical).map_err(io_error)?;
+        let value = crate::parser::parse(&text).map_err(|e| {
+            let mut error = JsonError::new(e.kind(), format!("{} in {}", e.message(), path.display()));
+            if let Some(offset) = e.offset() {
+                error = error.with_position(&text, offset);
+            }
+            match e.hint() {
+                Some(hint) => error.with_hint(hint),
+                None => error
+            }
+        })?;
+        self.includes.push(canonical);
+        let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        let result = self.resolve(value, &dir, &mut String::new()).map_err(|e| match e.kind() {
+            ErrorKind::Cycle | ErrorKind::Io | ErrorKind::Syntax => e,
+            kind => JsonError::new(kind, format!("{} in {}", e.message(), path.display()))
+        });
+        self.includes.pop();
+        result
+    }
+
+    fn resolve(&mut self, value: JValue, base_dir: &Path, path: &mut String) -> Result<JValue, JsonError> {
+        let len = path.len();
+        match value {
+            JValue::Object(o) => {
+                match directive(&o, path)? {
+                    Some(Directive::Env(name)) => match (self.env)(&name) {
+                        Some(value) => Ok(JValue::String(JString::from(value))),
+                        None => Err(JsonError::new(ErrorKind::NotFound, format!(
+                            "The environment variable {} is not set at {}", name, location(path))))
+                    },
+                    Some(Directive::Include(file)) => self.include(Path::new(&file), base_dir),
+                    None => {
+                        let mut obj = JObject::new();
+                        for (k, v) in o {
+                            push_token(path, &k);
+                            let v = self.resolve(v, base_dir, path)?;
+                            path.truncate(len);
+                            obj.insert(k, v);
+                        }
+                        Ok(JValue::Object(obj))
+                    }
+                }
+            }
+            JValue::Array(a) => {
+                let mut vec = Vec::with_capacity(a.len());
+                for (i, v) in a.into_iter().enumerate() {
+                    push_token(path, &i.to_string());
+                    vec.push(self.resolve(v, base_dir, path)?);
+                    path.truncate(len);
+                }
+                Ok(JValue::Array(vec))
+            }
+            other => Ok(other)
+        }
+    }
+}
+
+fn directive(object: &JObject, path: &str) -> Result<Option<Directive>, JsonError> {
+    let (key, value) = match object.iter().next() {
+        Some((key, value)) if object.len() == 1 && (key == "$env" || key == "$include") => (key, value),
+        _ => return Ok(None)
+    };
+    let argument = match value {
+        JValue::String(s) => s.to_string(),
+        other => return Err(JsonError::new(ErrorKind::InvalidType, format!(
+            "Expected a string as the argument of {}, found {} at {}", key, describe(other), location(path))))
+    };
+    Ok(Some(if key == "$env" { Directive::Env(argument) } else { Directive::Include(argument) }))
+}
+
+/// Documents merged by [`layer`], remembering which layer supplied each value
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayeredValue {
+    value: JValue,
+    sources: BTreeMap<String, usize>,
+}
+
+impl LayeredValue {
+    /// Returns the merged document
+    pub fn value(&self) -> &JValue {
+        &self.value
+    }
+
+    /// Returns the merged document, dropping the provenance
+    pub fn into_value(self) -> JValue {
+        self.value
+    }
+
+    /// Returns the index of the layer that supplied the value at the given JSON Pointer, or
+    /// [`None`] if there is no value at it. For an object merged from several layers, this is
+    /// the last layer that has an object there.
+    pub fn source(&self, pointer: &str) -> Option<usize> {
+        self.sources.get(pointer).copied()
+    }
+
+    /// Returns the JSON Pointer of every value of the merged document, sorted, with the index
+    /// of the layer that supplied it.
+    pub fn sources(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.sources.iter().map(|(pointer, layer)| (pointer.as_str(), *layer))
+    }
+}
+
+/// Merges `layers`, each taking precedence over the ones before it, so defaults come first
+/// and overrides last. Objects are merged member by member at every depth; any other value,
+/// including an array or `null`, replaces what the previous layers had at its location.
+///
+/// ```
+/// # use json::json;
+/// # use json::config;
+/// let defaults = json!("{\"server\": {\"host\": \"localhost\", \"port\": 80}, \"debug\": false}");
+/// let user = json!("{\"server\": {\"port\": 8080}}");
+/// let layered = config::layer(&[defaults, user]);
+///
+/// assert_eq!(&json!("{\"server\": {\"host\": \"localhost\", \"port\": 8080}, \"debug\": false}"),
+///            layered.value());
+/// assert_eq!(Some(1), layered.source("/server/port"));
+/// assert_eq!(Some(0), layered.source("/server/host"));
+/// assert_eq!(None, layered.source("/server/tls"));
+/// ```
+pub fn layer(layers: &[JValue]) -> LayeredValue {
+    let mut result = LayeredValue { value: JValue::Null, sources: BTreeMap::new() };
+    for (index, layer) in layers.iter().enumerate() {
+        merge(&mut result.value, layer, index, &mut String::new(), &mut result.sources);
+    }
+    result
+}
+
+fn merge(base: &mut JValue, layer: &JValue, index: usize, path: &mut String,
+         sources: &mut BTreeMap<String, usize>) {
+    match (base, layer) {
+        (JValue::Object(base), JValue::Object(layer)) => {
+            sources.insert(path.clone(), index);
+            let len = path.len();
+            for (k, v) in layer {
+                push_token(path, k);
+                match base.get_mut(k) {
+                    Some(existing) => merge(existing, v, index, path, sources),
+                    None => {
+                        record(v, index, path, sources);
+                        base.insert(k.clone(), v.clone());
+                    }
+                }
+                path.truncate(len);
+            }
+        }
+        (base, layer) => {
+            let prefix = format!("{}/", path);
+            let replaced: Vec<String> = sources.range(prefix.clone()..)
+                .take_while(|(k, _)| k.starts_with(&prefix)).map(|(k, _)| k.clone()).collect();
+            for pointer in replaced {
+                sources.remove(&pointer);
+            }
+            record(layer, index, path, sources);
+            *base = layer.clone();
+        }
+    }
+}
+
+fn record(value: &JValue, index: usize, path: &mut String, sources: &mut BTreeMap<String, usize>) {
+    sources.insert(path.clone(), index);
+    let len = path.len();
+    match value {
+        JValue::Object(o) => {
+            for (k, v) in o {
+                push_token(path, k);
+                record(v, index, path, sources);
+                path.truncate(len);
+            }
+        }
+        JValue::Array(a) => {
+            for (i, v) in a.iter().enumerate() {
+                push_token(path, &i.to_string());
+                record(v, index, path, sources);
+                path.truncate(len);
+            }
+        }
+        _ => ()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+use std::fs;
+    use std::path::PathBuf;
+
+    use crate::config::{layer, load_with, resolve_with};
+    use crate::error::ErrorKind;
+    use crate::json;
+
+    fn directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("json-config-test-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for (file, content) in files {
+            fs::write(dir.join(file), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = directory("include", &[
+            ("main.json", "{\"db\": {\"$include\": \"sub/db.json\"}, \"list\": [{\"$env\": \"A\"}]}"),
+            ("sub/db.json", "{\"url\": {\"$env\": \"DB\"}, \"pool\": {\"$include\": \"pool.json\"}}"),
+            ("sub/pool.json", "{\"size\": 4}"),
+        ]);
+        let config = load_with(dir.join("main.json"), |name| Some(name.to_lowercase())).unwrap();
+        assert_eq!(json!("{\"db\": {\"url\": \"db\", \"pool\": {\"size\": 4}}, \"list\": [\"a\"]}"), config);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let dir = directory("errors", &[
+            ("a.json", "{\"b\": {\"$include\": \"b.json\"}}"),
+            ("b.json", "[{\"$include\": \"a.json\"}]"),
+            ("env.json", "{\"x\": {\"$env\": \"MISSING\"}}"),
+            ("broken.json", "{\"x\": }"),
+        ]);
+        assert_eq!(ErrorKind::Cycle, load_with(dir.join("a.json"), |_| None).unwrap_err().kind());
+        assert_eq!(ErrorKind::Io, load_with(dir.join("none.json"), |_| None).unwrap_err().kind());
+        assert_eq!(ErrorKind::Syntax, load_with(dir.join("broken.json"), |_| None).unwrap_err().kind());
+        let error = load_with(dir.join("env.json"), |_| None).unwrap_err();
+        assert_eq!(ErrorKind::NotFound, error.kind());
+        assert!(error.message().starts_with("The environment variable MISSING is not set at /x in "));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_directives() {
+        let env = |_: &str| Some("v".to_string());
+        // Objects with other members are not directives
+        let value = json!("{\"$env\": \"A\", \"other\": 1}");
+        assert_eq!(value.clone(), resolve_with(value, ".", env).unwrap());
+        let error = resolve_with(json!("[{\"$env\": 1}]"), ".", env).unwrap_err();
+        assert_eq!("Expected a string as the argument of $env, found the number 1 at /0", error.message());
+    }
+
+    #[test]
+    fn test_layer() {
+        let layered = layer(&[
+            json!("{\"a\": {\"b\": [1, 2], \"c\": 1}, \"d\": {\"e\": 1}}"),
+            json!("{\"a\": {\"b\": [3]}, \"d\": null}"),
+            json!("{\"a\": {\"f\": true}}"),
+        ]);
+        assert_eq!(&json!("{\"a\": {\"b\": [3], \"c\": 1, \"f\": true}, \"d\": null}"), layered.value());
+        let sources: Vec<(&str, usize)> = layered.sources().collect();
+        assert_eq!(vec![("", 2), ("/a", 2), ("/a/b", 1), ("/a/b/0", 1), ("/a/c", 0), ("/a/f", 2), ("/d", 1)],
+                   sources);
+        assert_eq!(&crate::data_structures::JValue::Null, layer(&[]).value());
+    }
+}
@@ -0,0 +1,372 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Conversions from JSON data-structures to Rust types
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+use crate::data_structures::{JNumber, JObject, JValue};
+use crate::error::{ErrorKind, JsonError};
+
+/// A type that can be extracted from a [`JValue`].
+///
+/// The implementations for the standard types follow these coercion rules:
+///
+/// | Target     | Accepted values                                                          |
+/// |------------|--------------------------------------------------------------------------|
+/// | `String`   | strings; numbers and booleans are converted to their JSON text           |
+/// | `bool`     | booleans; the strings `"true"` and `"false"`                             |
+/// | integers   | numbers with an integral value in range; strings containing such a number |
+/// | `f64`      | numbers; strings containing a JSON number                                |
+///
+/// Everything else, including `null`, is an [`ErrorKind::InvalidType`] error.
+///
+/// ```
+/// # use json::json;
+/// # use json::convert::FromJValue;
+/// assert_eq!(Ok(1000_u16), u16::from_jvalue(&json!("1e3")));
+/// assert_eq!(Ok(-7_i8), i8::from_jvalue(&json!("\"-7\"")));
+/// assert_eq!(Ok("2.5".to_string()), String::from_jvalue(&json!("2.5")));
+/// assert!(u8::from_jvalue(&json!("256")).is_err());
+/// assert!(bool::from_jvalue(&json!("null")).is_err());
+/// ```
+pub trait FromJValue: Sized {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError>;
+}
+
+/// Describes the given value for error messages
+pub(crate) fn describe(value: &JValue) -> String {
+    match value {
+        JValue::Object(_) => "an object".to_string(),
+        JValue::Array(_) => "an array".to_string(),
+        JValue::String(s) => format!("the string \"{}\"", s),
+        JValue::Number(n) => format!("the number {}", n),
+        JValue::Boolean(b) => format!("the boolean {}", b),
+        JValue::Null => "null".to_string()
+    }
+}
+
+fn invalid_type(expected: &str, value: &JValue) -> JsonError {
+    JsonError::new(ErrorKind::InvalidType, format!("Expected {}, found {}", expected, describe(value)))
+}
+
+impl FromJValue for JValue {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromJValue for String {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        match value {
+            JValue::String(s) => Ok(s.to_string()),
+            JValue::Number(n) => Ok(n.to_string()),
+            JValue::Boolean(b) => Ok(b.to_string()),
+            _ => Err(invalid_type("a string", value))
+        }
+    }
+}
+
+impl FromJValue for bool {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        match value {
+            JValue::Boolean(b) => Ok(*b),
+            JValue::String(s) if s == "true" => Ok(true),
+            JValue::String(s) if s == "false" => Ok(false),
+            _ => Err(invalid_type("a boolean", value))
+        }
+    }
+}
+
+impl FromJValue for f64 {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        match value {
+            JValue::Number(n) => Ok(n.get_f64_value()),
+            JValue::String(s) => match JNumber::from_str(s) {
+                Ok(n) => Ok(n.get_f64_value()),
+                Err(_) => Err(invalid_type("a number", value))
+            },
+            _ => Err(invalid_type("a number", value))
+        }
+    }
+}
+
+macro_rules! impl_from_jvalue_for_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FromJValue for $t {
+                fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+                    let n = match value {
+                        JValue::Number(n) => n.clone(),
+                        JValue::String(s) => match JNumber::from_str(s) {
+                            Ok(n) => n,
+                            Err(_) => return Err(invalid_type("an integer", value))
+                        },
+                        _ => return Err(invalid_type("an integer", value))
+                    };
+                    // Parse the text first, f64 can not represent all 64 bit integers.
+                    if let Ok(i) = n.to_string().parse::<$t>() {
+                        return Ok(i);
+                    }
+                    let f = n.get_f64_value();
+                    if f.fract() != 0.0 || !f.is_finite() {
+                        Err(invalid_type("an integer", value))
+                    } else if f < <$t>::MIN as f64 || f > <$t>::MAX as f64 {
+                        Err(JsonError::new(ErrorKind::InvalidType, format!(
+                            "The number {} is out of range for {}", n, stringify!($t))))
+                    } else {
+                        Ok(f as $t)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_jvalue_for_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// Implements `TryFrom<JValue>` and `TryFrom<&JValue>` for a type and for an `Option` of it,
+/// with `null` converting to `None`
+macro_rules! impl_try_from {
+    ($t:ty, $expected:expr, |$value:ident| $pattern:pat => $convert:expr) => {
+        impl TryFrom<&JValue> for $t {
+            type Error = JsonError;
+
+            fn try_from($value: &JValue) -> Result<Self, Self::Error> {
+                match $value {
+                    $pattern => $convert,
+                    _ => Err(invalid_type($expected, $value))
+                }
+            }
+        }
+
+        impl TryFrom<JValue> for $t {
+            type Error = JsonError;
+
+            fn try_from(value: JValue) -> Result<Self, Self::Error> {
+                <$t>::try_from(&value)
+            }
+        }
+
+        impl TryFrom<&JValue> for Option<$t> {
+            type Error = JsonError;
+
+            fn try_from(value: &JValue) -> Result<Self, Self::Error> {
+                match value {
+                    JValue::Null => Ok(None),
+                    _ => <$t>::try_from(value).map(Some)
+                }
+            }
+        }
+
+        impl TryFrom<JValue> for Option<$t> {
+            type Error = JsonError;
+
+            fn try_from(value: JValue) -> Result<Self, Self::Error> {
+                <Option<$t>>::try_from(&value)
+            }
+        }
+    };
+}
+
+// Unlike `FromJValue`, `TryFrom` does not coerce: a string is never a number or a boolean, and
+// a number is never a string.
+impl_try_from!(bool, "a boolean", |value| JValue::Boolean(b) => Ok(*b));
+impl_try_from!(i64, "an integer", |value| JValue::Number(_) => i64::from_jvalue(value));
+impl_try_from!(f64, "a number", |value| JValue::Number(n) => Ok(n.get_f64_value()));
+
+impl TryFrom<JValue> for String {
+    type Error = JsonError;
+
+    /// Takes the string out of a string value.
+    ///
+    /// ```
+    /// # use std::convert::{TryFrom, TryInto};
+    /// # use json::json;
+    /// # use json::error::JsonError;
+    /// # fn main() -> Result<(), JsonError> {
+    /// let document = json!("{\"name\": \"ada\", \"age\": 36, \"email\": null}");
+    /// let name: String = document["name"].clone().try_into()?;
+    /// let age: i64 = (&document["age"]).try_into()?;
+    /// let email: Option<String> = (&document["email"]).try_into()?;
+    /// assert_eq!(("ada".to_string(), 36, None), (name, age, email));
+    ///
+    /// let error = String::try_from(document["age"].clone()).unwrap_err();
+    /// assert_eq!("Expected a string, found the number 36", error.message());
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn try_from(value: JValue) -> Result<Self, Self::Error> {
+        match value {
+            JValue::String(s) => Ok(s.into()),
+            other => Err(invalid_type("a string", &other))
+        }
+    }
+}
+
+impl TryFrom<&JValue> for String {
+    type Error = JsonError;
+
+    fn try_from(value: &JValue) -> Result<Self, Self::Error> {
+        <&str>::try_from(value).map(str::to_string)
+    }
+}
+
+impl TryFrom<JValue> for Option<String> {
+    type Error = JsonError;
+
+    fn try_from(value: JValue) -> Result<Self, Self::Error> {
+        match value {
+            JValue::Null => Ok(None),
+            other => String::try_from(other).map(Some)
+        }
+    }
+}
+
+impl TryFrom<&JValue> for Option<String> {
+    type Error = JsonError;
+
+    fn try_from(value: &JValue) -> Result<Self, Self::Error> {
+        match value {
+            JValue::Null => Ok(None),
+            other => String::try_from(other).map(Some)
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a JValue> for &'a str {
+    type Error = JsonError;
+
+    fn try_from(value: &'a JValue) -> Result<Self, Self::Error> {
+        match value {
+            JValue::String(s) => Ok(s.as_str()),
+            _ => Err(invalid_type("a string", value))
+        }
+    }
+}
+
+impl TryFrom<JValue> for Vec<JValue> {
+    type Error = JsonError;
+
+    fn try_from(value: JValue) -> Result<Self, Self::Error> {
+        match value {
+            JValue::Array(a) => Ok(a),
+            other => Err(invalid_type("an array", &other))
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a JValue> for &'a [JValue] {
+    type Error = JsonError;
+
+    fn try_from(value: &'a JValue) -> Result<Self, Self::Error> {
+        match value {
+            JValue::Array(a) => Ok(a),
+            _ => Err(invalid_type("an array", value))
+        }
+    }
+}
+
+impl TryFrom<JValue> for JObject {
+    type Error = JsonError;
+
+    fn try_from(value: JValue) -> Result<Self, Self::Error> {
+        match value {
+            JValue::Object(o) => Ok(o),
+            other => Err(invalid_type("an object", &other))
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a JValue> for &'a JObject {
+    type Error = JsonError;
+
+    fn try_from(value: &'a JValue) -> Result<Self, Self::Error> {
+        match value {
+            JValue::Object(o) => Ok(o),
+            _ => Err(invalid_type("an object", value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::FromJValue;
+    use crate::json;
+
+    #[test]
+    fn test_string() {
+        assert_eq!(Ok("a".to_string()), String::from_jvalue(&json!("\"a\"")));
+        assert_eq!(Ok("false".to_string()), String::from_jvalue(&json!("false")));
+        assert_eq!(Ok("-1.5e3".to_string()), String::from_jvalue(&json!("-1.5e3")));
+        assert!(String::from_jvalue(&json!("null")).is_err());
+        assert!(String::from_jvalue(&json!("[]")).is_err());
+    }
+
+    #[test]
+    fn test_bool() {
+        assert_eq!(Ok(true), bool::from_jvalue(&json!("true")));
+        assert_eq!(Ok(false), bool::from_jvalue(&json!("\"false\"")));
+        assert!(bool::from_jvalue(&json!("\"yes\"")).is_err());
+        assert!(bool::from_jvalue(&json!("1")).is_err());
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(Ok(5898499948554533445_u64), u64::from_jvalue(&json!("5898499948554533445")));
+        assert_eq!(Ok(-128_i8), i8::from_jvalue(&json!("-128")));
+        assert_eq!(Ok(25_usize), usize::from_jvalue(&json!("2.5e1")));
+        assert_eq!("The number -1 is out of range for u32",
+                   u32::from_jvalue(&json!("-1")).unwrap_err().to_string());
+        assert_eq!("Expected an integer, found the string \"x\"",
+                   i32::from_jvalue(&json!("\"x\"")).unwrap_err().to_string());
+        assert!(i64::from_jvalue(&json!("1.5")).is_err());
+    }
+
+    #[test]
+    fn test_f64() {
+        assert_eq!(Ok(1.5), f64::from_jvalue(&json!("1.5")));
+        assert_eq!(Ok(-2.0), f64::from_jvalue(&json!("\"-2\"")));
+        assert!(f64::from_jvalue(&json!("\"NaN\"")).is_err());
+        assert!(f64::from_jvalue(&json!("\"\"")).is_err());
+        assert!(f64::from_jvalue(&json!("true")).is_err());
+    }
+
+    #[test]
+    fn test_try_from() {
+        use std::convert::{TryFrom, TryInto};
+        use crate::data_structures::{JObject, JValue};
+        let document = json!("{\"s\": \"x\", \"b\": true, \"i\": -3, \"f\": 0.5, \"a\": [1], \"o\": {}, \"n\": null}");
+        assert_eq!(Ok("x"), <&str>::try_from(&document["s"]));
+        assert_eq!(Ok(true), document["b"].clone().try_into());
+        assert_eq!(Ok(-3_i64), (&document["i"]).try_into());
+        assert_eq!(Ok(0.5), f64::try_from(&document["f"]));
+        assert_eq!(Ok(vec![json!("1")]), Vec::<JValue>::try_from(document["a"].clone()));
+        assert_eq!(1, <&[JValue]>::try_from(&document["a"]).unwrap().len());
+        assert_eq!(0, <&JObject>::try_from(&document["o"]).unwrap().len());
+        assert_eq!(0, JObject::try_from(document["o"].clone()).unwrap().len());
+        assert_eq!(Ok(None), <Option<i64>>::try_from(&document["n"]));
+        assert_eq!(Ok(None), <Option<bool>>::try_from(&document["missing"]));
+        assert_eq!(Ok(Some(0.5)), <Option<f64>>::try_from(document["f"].clone()));
+        assert_eq!("Expected an integer, found the number 0.5", i64::try_from(&document["f"]).unwrap_err().message());
+        assert_eq!("Expected an integer, found the string \"x\"", i64::try_from(&document["s"]).unwrap_err().message());
+        assert_eq!("Expected a boolean, found the string \"x\"", bool::try_from(&document["s"]).unwrap_err().message());
+        assert_eq!("Expected an object, found an array", JObject::try_from(document["a"].clone()).unwrap_err().message());
+        assert!(String::try_from(&document["i"]).is_err());
+        assert!(<Option<String>>::try_from(&document["b"]).is_err());
+    }
+}
@@ -0,0 +1,681 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! An experimental JSON CRDT: documents edited concurrently on several replicas, possibly
+//! offline, which converge to the same value once they have seen the same edits.
+//!
+//! Every edit is an [`Operation`] identified by an [`Id`], a Lamport timestamp paired with the
+//! actor which made it. Object members are last-writer-wins registers: of concurrent edits to a
+//! member the one with the greatest id wins. Arrays are replicated growable arrays: an element
+//! is inserted after the element it followed, concurrent insertions at the same place are
+//! ordered by their ids, and removed elements stay as tombstones others can still be inserted
+//! after. The state of a document is its set of operations applied in the order of their ids,
+//! which is the same on every replica, so replicas converge whatever order they exchange them in.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::{array_index, parse_pointer};
+
+/// The id of an operation, and of the object or array or array element it creates. Ids are
+/// ordered by their counters and then by their actors, and are written as `counter@actor`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Id {
+    pub counter: u64,
+    pub actor: String,
+}
+
+impl Id {
+    /// The id of the root object of every document
+    pub fn root() -> Id {
+        Id { counter: 0, actor: String::new() }
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.counter, self.actor)
+    }
+}
+
+impl FromStr for Id {
+    type Err = JsonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_once('@').and_then(|(counter, actor)| {
+            counter.parse().ok().map(|counter| Id { counter, actor: actor.to_string() })
+        }).ok_or_else(|| JsonError::new(ErrorKind::InvalidType, format!("Invalid CRDT id {:?}", s)))
+    }
+}
+
+/// The value an operation writes: a scalar, or a new empty object or array which later
+/// operations fill in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    Value(JValue),
+    Object,
+    Array,
+}
+
+impl Content {
+    fn of(value: &JValue) -> Content {
+        match value {
+            JValue::Object(_) => Content::Object,
+            JValue::Array(_) => Content::Array,
+            scalar => Content::Value(scalar.clone())
+        }
+    }
+}
+
+/// An edit of a [`Document`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Sets the member `key` of `object`
+    Set { id: Id, object: Id, key: String, content: Content },
+    /// Deletes the member `key` of `object`
+    Delete { id: Id, object: Id, key: String },
+    /// Inserts an element into `array` after the element `after`, or at the start
+    Insert { id: Id, array: Id, after: Option<Id>, content: Content },
+    /// Removes the element `element` of `array`
+    Remove { id: Id, array: Id, element: Id },
+}
+
+impl Operation {
+    /// Returns the id of the operation
+    pub fn id(&self) -> &Id {
+        match self {
+            Operation::Set { id, .. } | Operation::Delete { id, .. }
+            | Operation::Insert { id, .. } | Operation::Remove { id, .. } => id
+        }
+    }
+
+    /// Returns the object or array the operation edits
+    fn target(&self) -> &Id {
+        match self {
+            Operation::Set { object, .. } | Operation::Delete { object, .. } => object,
+            Operation::Insert { array, .. } | Operation::Remove { array, .. } => array
+        }
+    }
+
+    /// Writes the operation as an object like
+    /// `{"op": "set", "id": "2@a", "object": "0@", "key": "name", "value": "x"}`, with
+    /// `"new": "object"` or `"new": "array"` instead of a value for a new container
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = JObject::new();
+        let string = |s: &str| JValue::String(JString::from(s));
+        let (op, content) = match self {
+            Operation::Set { object: target, key, content, .. } => {
+                object.insert("object".to_string(), string(&target.to_string()));
+                object.insert("key".to_string(), string(key));
+                ("set", Some(content))
+            }
+            Operation::Delete { object: target, key, .. } => {
+                object.insert("object".to_string(), string(&target.to_string()));
+                object.insert("key".to_string(), string(key));
+                ("delete", None)
+            }
+            Operation::Insert { array, after, content, .. } => {
+                object.insert("array".to_string(), string(&array.to_string()));
+                object.insert("after".to_string(), after.as_ref().map_or(JValue::Null, |after| string(&after.to_string())));
+                ("insert", Some(content))
+            }
+            Operation::Remove { array, element, .. } => {
+                object.insert("array".to_string(), string(&array.to_string()));
+                object.insert("element".to_string(), string(&element.to_string()));
+                ("remove", None)
+            }
+        };
+        object.insert("op".to_string(), string(op));
+        object.insert("id".to_string(), string(&self.id().to_string()));
+        match content {
+            Some(Content::Value(value)) => {
+                object.insert("value".to_string(), value.clone());
+            }
+            Some(Content::Object) => {
+                object.insert("new".to_string(), string("object"));
+            }
+            Some(Content::Array) => {
+                object.insert("new".to_string(), string("array"));
+            }
+            None => {}
+        }
+        JValue::Object(object)
+    }
+}
+
+impl FromJValue for Operation {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        let invalid = |message: String| JsonError::new(ErrorKind::InvalidType, format!("Invalid CRDT operation: {}", message));
+        let object = match value {
+            JValue::Object(object) => object,
+            _ => return Err(invalid(format!("expected an object, found {}", describe(value))))
+        };
+        let string = |key: &str| match object.get(&key.to_string()) {
+            Some(JValue::String(s)) => Ok(s.to_string()),
+            Some(other) => Err(invalid(format!("the {} is {}", key, describe(other)))),
+            None => Err(invalid(format!("the {} is missing", key)))
+        };
+        let id = |key: &str| string(key)?.parse::<Id>().map_err(|e| invalid(format!("the {} is not an id: {}", key, e.message())));
+        let content = || match (object.get(&"value".to_string()), object.get(&"new".to_string())) {
+            (Some(JValue::Object(_)), None) | (Some(JValue::Array(_)), None) =>
+                Err(invalid("the value is not a scalar".to_string())),
+            (Some(value), None) => Ok(Content::Value(value.clone())),
+            (None, Some(JValue::String(new))) if new.as_str() == "object" => Ok(Content::Object),
+            (None, Some(JValue::String(new))) if new.as_str() == "array" => Ok(Content::Array),
+            (None, Some(new)) => Err(invalid(format!("the new container is {}", describe(new)))),
+            _ => Err(invalid("expected either a value or a new container".to_string()))
+        };
+        let op = string("op")?;
+        Ok(match op.as_str() {
+            "set" => Operation::Set { id: id("id")?, object: id("object")?, key: string("key")?, content: content()? },
+            "delete" => Operation::Delete { id: id("id")?, object: id("object")?, key: string("key")? },
+            "insert" => {
+                let after = match object.get(&"after".to_string()) {
+                    None | Some(JValue::Null) => None,
+                    Some(_) => Some(id("after")?)
+                };
+                Operation::Insert { id: id("id")?, array: id("array")?, after, content: content()? }
+            }
+            "remove" => Operation::Remove { id: id("id")?, array: id("array")?, element: id("element")? },
+            other => return Err(invalid(format!("unknown op {}", other)))
+        })
+    }
+}
+
+enum Slot {
+    Value(JValue),
+    Container(Id),
+}
+
+struct Entry {
+    id: Id,
+    slot: Slot,
+}
+
+struct Element {
+    id: Id,
+    slot: Slot,
+    removed: bool,
+}
+
+enum Node {
+    Object(HashMap<String, Entry>),
+    Array(Vec<Element>),
+}
+
+/// A replica of a JSON CRDT document, whose root is an object.
+///
+/// ```
+/// # use json::json;
+/// # use json::crdt::Document;
+/// let mut phone = Document::new("phone");
+/// phone.set("/todo", &json!("[\"milk\"]")).unwrap();
+/// let mut laptop = Document::new("laptop");
+/// laptop.merge(&phone).unwrap();
+///
+/// // Concurrent offline edits
+/// phone.set("/todo/-", &json!("\"eggs\"")).unwrap();
+/// laptop.insert("/todo/0", &json!("\"bread\"")).unwrap();
+/// laptop.set("/owner", &json!("\"sam\"")).unwrap();
+///
+/// phone.merge(&laptop).unwrap();
+/// laptop.merge(&phone).unwrap();
+/// assert_eq!(json!("{\"todo\": [\"bread\", \"milk\", \"eggs\"], \"owner\": \"sam\"}"), phone.to_jvalue());
+/// assert_eq!(phone.to_jvalue(), laptop.to_jvalue());
+/// ```
+pub struct Document {
+    actor: String,
+    clock: u64,
+    operations: Vec<Operation>,
+    seen: HashSet<Id>,
+    nodes: HashMap<Id, Node>,
+}
+
+impl Document {
+    /// Creates an empty replica for `actor`. Every replica needs an actor of its own.
+    pub fn new(actor: &str) -> Document {
+        let mut nodes = HashMap::new();
+        nodes.insert(Id::root(), Node::Object(HashMap::new()));
+        Document { actor: actor.to_string(), clock: 0, operations: Vec::new(), seen: HashSet::new(), nodes }
+    }
+
+    /// Returns the actor of this replica
+    pub fn actor(&self) -> &str {
+        &self.actor
+    }
+
+    /// Returns all the operations seen, ordered by id
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Returns the operations with an id after `counter`, the operations a replica which has
+    /// seen every operation up to `counter` is missing
+    pub fn operations_since(&self, counter: u64) -> Vec<Operation> {
+        self.operations.iter().filter(|operation| operation.id().counter > counter).cloned().collect()
+    }
+
+    /// Returns the greatest counter seen
+    pub fn clock(&self) -> u64 {
+        self.clock
+    }
+
+    /// Applies operations from other replicas. Operations already seen are skipped, and an
+    /// operation on a value which was not seen yet takes effect once that value arrives.
+    ///
+    /// Fails with [`ErrorKind::InvalidType`], applying none of the operations, if one has the
+    /// counter 0 of the root or the greatest counter, after which no operation could follow, if
+    /// it edits the value it creates, or if another operation has the same id.
+    pub fn apply(&mut self, operations: &[Operation]) -> Result<(), JsonError> {
+        let mut batch: HashMap<&Id, &Operation> = HashMap::with_capacity(operations.len());
+        for operation in operations {
+            let id = operation.id();
+            let invalid = |message: &str| JsonError::new(ErrorKind::InvalidType, format!("Invalid CRDT operation {}: {}", id, message));
+            if id.counter == 0 || id.counter == u64::MAX {
+                return Err(invalid("the counter is out of range"));
+            }
+            if id == operation.target() {
+                return Err(invalid("it edits the value it creates"));
+            }
+            let seen = self.operations.binary_search_by(|seen| seen.id().cmp(id)).ok().map(|i| &self.operations[i]);
+            if seen.or_else(|| batch.get(id).copied()).is_some_and(|other| other != operation) {
+                return Err(invalid("another operation has the same id"));
+            }
+            batch.insert(id, operation);
+        }
+        let mut changed = false;
+        for operation in operations {
+            if self.seen.insert(operation.id().clone()) {
+                self.clock = self.clock.max(operation.id().counter);
+                self.operations.push(operation.clone());
+                changed = true;
+            }
+        }
+        if changed {
+            self.operations.sort_by(|a, b| a.id().cmp(b.id()));
+            self.nodes.clear();
+            self.nodes.insert(Id::root(), Node::Object(HashMap::new()));
+            for operation in &self.operations {
+                apply(&mut self.nodes, operation);
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies all the operations of another replica, failing like [`Document::apply`]
+    pub fn merge(&mut self, other: &Document) -> Result<(), JsonError> {
+        self.apply(&other.operations)
+    }
+
+    /// Sets the value at the given JSON Pointer. A member of an object is set, an element of an
+    /// array is replaced by a new element, and the array index `-` or the length of the array
+    /// appends to it. Replacing an element inserts the new one and removes the old one, so
+    /// concurrent replacements of an element keep both new elements.
+    pub fn set(&mut self, pointer: &str, value: &JValue) -> Result<(), JsonError> {
+        let (parent, token) = self.parent(pointer)?;
+        let content = Content::of(value);
+        let id = match &self.nodes[&parent] {
+            Node::Object(_) => {
+                let id = self.next_id()?;
+                self.push(Operation::Set { id: id.clone(), object: parent, key: token, content });
+                id
+            }
+            Node::Array(elements) => {
+                let visible: Vec<Id> = visible(elements).map(|element| element.id.clone()).collect();
+                let id = self.next_id()?;
+                if token == "-" || array_index(&token) == Some(visible.len()) {
+                    self.push(Operation::Insert { id: id.clone(), array: parent, after: visible.last().cloned(), content });
+                } else {
+                    let element = array_index(&token).and_then(|i| visible.get(i)).ok_or_else(|| not_found(pointer))?;
+                    self.push(Operation::Insert { id: id.clone(), array: parent.clone(), after: Some(element.clone()), content });
+                    let remove = self.next_id()?;
+                    self.push(Operation::Remove { id: remove, array: parent, element: element.clone() });
+                }
+                id
+            }
+        };
+        self.fill(id, value)
+    }
+
+    /// Inserts an element into the array at the given JSON Pointer, before the element at its
+    /// index, or at the end for the index `-` or the length of the array
+    pub fn insert(&mut self, pointer: &str, value: &JValue) -> Result<(), JsonError> {
+        let (parent, token) = self.parent(pointer)?;
+        let after = match &self.nodes[&parent] {
+            Node::Array(elements) => {
+                let visible: Vec<&Id> = visible(elements).map(|element| &element.id).collect();
+                let index = if token == "-" { Some(visible.len()) } else { array_index(&token) };
+                match index {
+                    Some(0) => None,
+                    Some(i) if i <= visible.len() => Some(visible[i - 1].clone()),
+                    _ => return Err(not_found(pointer))
+                }
+            }
+            Node::Object(_) => return Err(JsonError::new(
+                ErrorKind::InvalidType, format!("Expected an array as the parent of {}, found an object", pointer)))
+        };
+        let id = self.next_id()?;
+        self.push(Operation::Insert { id: id.clone(), array: parent, after, content: Content::of(value) });
+        self.fill(id, value)
+    }
+
+    /// Removes the value at the given JSON Pointer
+    pub fn remove(&mut self, pointer: &str) -> Result<(), JsonError> {
+        let (parent, token) = self.parent(pointer)?;
+        // The removed element of an array, or None for a member of an object
+        let element = match &self.nodes[&parent] {
+            Node::Object(members) if members.contains_key(&token) => None,
+            Node::Array(elements) => match array_index(&token).and_then(|i| visible(elements).nth(i)) {
+                Some(element) => Some(element.id.clone()),
+                None => return Err(not_found(pointer))
+            },
+            Node::Object(_) => return Err(not_found(pointer))
+        };
+        let id = self.next_id()?;
+        self.push(match element {
+            None => Operation::Delete { id, object: parent, key: token },
+            Some(element) => Operation::Remove { id, array: parent, element }
+        });
+        Ok(())
+    }
+
+    /// Returns the current value of the document
+    pub fn to_jvalue(&self) -> JValue {
+        self.value(&Id::root())
+    }
+
+    fn value(&self, id: &Id) -> JValue {
+        let slot = |slot: &Slot| match slot {
+            Slot::Value(value) => value.clone(),
+            Slot::Container(id) => self.value(id)
+        };
+        match &self.nodes[id] {
+            Node::Object(members) => {
+                let mut object = JObject::new();
+                for (key, entry) in members {
+                    object.insert(key.clone(), slot(&entry.slot));
+                }
+                JValue::Object(object)
+            }
+            Node::Array(elements) => JValue::Array(visible(elements).map(|element| slot(&element.slot)).collect())
+        }
+    }
+
+    /// Returns the id of the next local operation. The greatest counter is never used, as
+    /// other replicas reject it.
+    fn next_id(&mut self) -> Result<Id, JsonError> {
+        self.clock = self.clock.checked_add(1).filter(|counter| *counter < u64::MAX).ok_or_else(|| JsonError::new(
+            ErrorKind::InvalidType, "The counter of the CRDT document can not be incremented any more"))?;
+        Ok(Id { counter: self.clock, actor: self.actor.clone() })
+    }
+
+    /// Records and applies a local operation, whose id is greater than any seen
+    fn push(&mut self, operation: Operation) {
+        apply(&mut self.nodes, &operation);
+        self.seen.insert(operation.id().clone());
+        self.operations.push(operation);
+    }
+
+    /// Adds the members or elements of `value` to the container just created as `id`
+    fn fill(&mut self, id: Id, value: &JValue) -> Result<(), JsonError> {
+        match value {
+            JValue::Object(o) => for (key, member) in o.iter() {
+                let member_id = self.next_id()?;
+                self.push(Operation::Set { id: member_id.clone(), object: id.clone(), key: key.clone(), content: Content::of(member) });
+                self.fill(member_id, member)?;
+            },
+            JValue::Array(a) => {
+                let mut after = None;
+                for element in a {
+                    let element_id = self.next_id()?;
+                    self.push(Operation::Insert { id: element_id.clone(), array: id.clone(), after, content: Content::of(element) });
+                    self.fill(element_id.clone(), element)?;
+                    after = Some(element_id);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the container holding the value at `pointer` and the last token of `pointer`
+    fn parent(&self, pointer: &str) -> Result<(Id, String), JsonError> {
+        let mut tokens = parse_pointer(pointer)?;
+        let last = tokens.pop().ok_or_else(|| JsonError::new(
+            ErrorKind::InvalidPointer, "The root of a CRDT document is always an object"))?;
+        let mut id = Id::root();
+        for token in tokens {
+            let slot = match &self.nodes[&id] {
+                Node::Object(members) => members.get(&token).map(|entry| &entry.slot),
+                Node::Array(elements) => array_index(&token).and_then(|i| visible(elements).nth(i)).map(|element| &element.slot)
+            };
+            id = match slot {
+                Some(Slot::Container(child)) => child.clone(),
+                Some(Slot::Value(value)) => return Err(JsonError::new(ErrorKind::InvalidType, format!(
+                    "Expected an object or an array as the parent of {}, found {}", pointer, describe(value)))),
+                None => return Err(JsonError::new(ErrorKind::NotFound, format!("No parent value for {}", pointer)))
+            };
+        }
+        Ok((id, last))
+    }
+}
+
+impl fmt::Debug for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Document")
+            .field("actor", &self.actor)
+            .field("clock", &self.clock)
+            .field("value", &self.to_jvalue())
+            .finish()
+    }
+}
+
+fn visible(elements: &[Element]) -> impl Iterator<Item = &Element> {
+    elements.iter().filter(|element| !element.removed)
+}
+
+fn not_found(pointer: &str) -> JsonError {
+    JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer))
+}
+
+/// Applies an operation to the state. Operations must be applied in the order of their ids.
+fn apply(nodes: &mut HashMap<Id, Node>, operation: &Operation) {
+    let mut create = |id: &Id, content: &Content| match content {
+        Content::Value(value) => Slot::Value(value.clone()),
+        Content::Object => {
+            nodes.insert(id.clone(), Node::Object(HashMap::new()));
+            Slot::Container(id.clone())
+        }
+        Content::Array => {
+            nodes.insert(id.clone(), Node::Array(Vec::new()));
+            Slot::Container(id.clone())
+        }
+    };
+    match operation {
+        Operation::Set { id, object, key, content } => {
+            let slot = create(id, content);
+            if let Some(Node::Object(members)) = nodes.get_mut(object) {
+                if members.get(key).is_none_or(|entry| entry.id < *id) {
+                    members.insert(key.clone(), Entry { id: id.clone(), slot });
+                }
+            }
+        }
+        Operation::Delete { id, object, key } => {
+            if let Some(Node::Object(members)) = nodes.get_mut(object) {
+                if members.get(key).is_some_and(|entry| entry.id < *id) {
+                    members.remove(key);
+                }
+            }
+        }
+        Operation::Insert { id, array, after, content } => {
+            let slot = create(id, content);
+            if let Some(Node::Array(elements)) = nodes.get_mut(array) {
+                let mut position = match after {
+                    None => 0,
+                    Some(after) => match elements.iter().position(|element| element.id == *after) {
+                        Some(i) => i + 1,
+                        None => return
+                    }
+                };
+                // Concurrent insertions after the same element are ordered by descending ids
+                while position < elements.len() && elements[position].id > *id {
+                    position += 1;
+                }
+                elements.insert(position, Element { id: id.clone(), slot, removed: false });
+            }
+        }
+        Operation::Remove { array, element, .. } => {
+            if let Some(Node::Array(elements)) = nodes.get_mut(array) {
+                if let Some(element) = elements.iter_mut().find(|e| e.id == *element) {
+                    element.removed = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::FromJValue;
+    use crate::crdt::{Document, Id, Operation};
+    use crate::error::ErrorKind;
+    use crate::json;
+
+    fn converged(a: &mut Document, b: &mut Document) -> crate::data_structures::JValue {
+        a.merge(b).unwrap();
+        b.merge(a).unwrap();
+        assert_eq!(a.to_jvalue(), b.to_jvalue());
+        a.to_jvalue()
+    }
+
+    #[test]
+    fn test_last_writer_wins() {
+        let mut a = Document::new("a");
+        let mut b = Document::new("b");
+        a.set("/x", &json!("1")).unwrap();
+        b.set("/x", &json!("2")).unwrap();
+        // Equal counters, the greater actor wins
+        assert_eq!(json!("{\"x\": 2}"), converged(&mut a, &mut b));
+
+        a.set("/x", &json!("3")).unwrap();
+        b.remove("/x").unwrap();
+        b.set("/y", &json!("{\"z\": [1]}")).unwrap();
+        // b has made more edits, so its removal has the greater counter
+        assert_eq!(json!("{\"y\": {\"z\": [1]}}"), converged(&mut a, &mut b));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let mut a = Document::new("a");
+        a.set("/list", &json!("[1, 2, 3]")).unwrap();
+        let mut b = Document::new("b");
+        b.merge(&a).unwrap();
+
+        a.remove("/list/1").unwrap();
+        a.insert("/list/1", &json!("\"a\"")).unwrap();
+        b.insert("/list/2", &json!("\"b\"")).unwrap();
+        b.set("/list/0", &json!("\"first\"")).unwrap();
+        let merged = converged(&mut a, &mut b);
+        assert_eq!(json!("{\"list\": [\"first\", \"a\", \"b\", 3]}"), merged);
+
+        b.set("/list/-", &json!("{\"k\": true}")).unwrap();
+        b.set("/list/4/k", &json!("false")).unwrap();
+        assert_eq!(Some(&json!("{\"k\": false}")), b.to_jvalue().pointer("/list/4"));
+    }
+
+    #[test]
+    fn test_out_of_order_delivery() {
+        let mut a = Document::new("a");
+        a.set("/config", &json!("{\"ports\": [80]}")).unwrap();
+        a.set("/config/ports/-", &json!("443")).unwrap();
+        let mut operations = a.operations().to_vec();
+        operations.reverse();
+
+        let mut b = Document::new("b");
+        b.apply(&operations[..2]).unwrap();
+        b.apply(&operations).unwrap();
+        b.apply(&operations).unwrap();
+        assert_eq!(a.to_jvalue(), b.to_jvalue());
+        assert_eq!(a.clock(), b.clock());
+        assert_eq!(2, b.operations_since(a.clock() - 2).len());
+    }
+
+    #[test]
+    fn test_operation_round_trip() {
+        let mut a = Document::new("a@home");
+        a.set("/list", &json!("[null, {\"x\": 1}]")).unwrap();
+        a.remove("/list/0").unwrap();
+        a.remove("/list").unwrap();
+        for operation in a.operations() {
+            assert_eq!(Ok(operation.clone()), Operation::from_jvalue(&operation.to_jvalue()));
+        }
+        assert_eq!(Ok(Id { counter: 3, actor: "a@home".to_string() }), "3@a@home".parse());
+        let invalid = json!("{\"op\": \"set\", \"id\": \"1@a\", \"object\": \"0@\", \"key\": \"k\", \"value\": []}");
+        assert_eq!("Invalid CRDT operation: the value is not a scalar", Operation::from_jvalue(&invalid).unwrap_err().message());
+    }
+
+    #[test]
+    fn test_invalid_operations() {
+        let operation = |json: &str| Operation::from_jvalue(&json!(json)).unwrap();
+        let mut a = Document::new("a");
+        a.set("/x", &json!("1")).unwrap();
+        let cycle = operation("{\"op\": \"set\", \"id\": \"0@\", \"object\": \"0@\", \"key\": \"a\", \"new\": \"object\"}");
+        let own_child = operation("{\"op\": \"insert\", \"id\": \"5@b\", \"array\": \"5@b\", \"after\": null, \"value\": 1}");
+        let last = operation("{\"op\": \"set\", \"id\": \"18446744073709551615@b\", \"object\": \"0@\", \"key\": \"a\", \"value\": 1}");
+        let reused = operation("{\"op\": \"delete\", \"id\": \"1@a\", \"object\": \"0@\", \"key\": \"x\"}");
+        let valid = operation("{\"op\": \"set\", \"id\": \"2@b\", \"object\": \"0@\", \"key\": \"y\", \"value\": 2}");
+        for invalid in [cycle, own_child, last, reused] {
+            let error = a.apply(&[valid.clone(), invalid.clone()]).unwrap_err();
+            assert_eq!(ErrorKind::InvalidType, error.kind(), "{:?}", invalid);
+            assert_eq!(json!("{\"x\": 1}"), a.to_jvalue());
+        }
+        let conflicting = operation("{\"op\": \"set\", \"id\": \"2@b\", \"object\": \"0@\", \"key\": \"y\", \"value\": 3}");
+        assert!(a.apply(&[valid.clone(), conflicting]).is_err());
+        a.apply(&[valid.clone(), valid]).unwrap();
+        assert_eq!(json!("{\"x\": 1, \"y\": 2}"), a.to_jvalue());
+
+        let almost_last = operation("{\"op\": \"set\", \"id\": \"18446744073709551613@b\", \"object\": \"0@\", \"key\": \"z\", \"value\": 1}");
+        a.apply(&[almost_last]).unwrap();
+        a.set("/z", &json!("2")).unwrap();
+        assert_eq!(u64::MAX - 1, a.clock());
+        assert_eq!(ErrorKind::InvalidType, a.set("/z", &json!("3")).unwrap_err().kind());
+        assert_eq!(Some(&json!("2")), a.to_jvalue().pointer("/z"));
+        let mut b = Document::new("b");
+        b.merge(&a).unwrap();
+        assert_eq!(a.to_jvalue(), b.to_jvalue());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut a = Document::new("a");
+        a.set("/s", &json!("\"text\"")).unwrap();
+        a.set("/list", &json!("[]")).unwrap();
+        assert_eq!(ErrorKind::InvalidPointer, a.set("", &json!("{}")).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidType, a.set("/s/x", &json!("1")).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, a.set("/none/x", &json!("1")).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, a.set("/list/1", &json!("1")).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, a.insert("/list/1", &json!("1")).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidType, a.insert("/x", &json!("1")).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, a.remove("/x").unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, a.remove("/list/0").unwrap_err().kind());
+        assert_eq!(json!("{\"s\": \"text\", \"list\": []}"), a.to_jvalue());
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::pointer::{array_index, parse_pointer};
+
+/// A JSON value whose arrays, objects and strings are shared through [`Arc`]s.
+///
+/// Cloning an `ArcValue` only increments reference counts, so one parsed document can be handed
+/// to many threads cheaply. Mutation is copy-on-write: the mutable accessors copy the shared
+/// containers on the way to the value being changed, and every other subtree stays shared.
+///
+/// ```
+/// # use json::json;
+/// # use json::data_structures::ArcValue;
+/// let shared = ArcValue::from(&json!("{\"a\": {\"b\": 1}, \"big\": [1, 2, 3]}"));
+/// let mut copy = shared.clone();
+/// *copy.pointer_mut("/a/b").unwrap() = ArcValue::from(&json!("2"));
+///
+/// assert_eq!(json!("{\"a\": {\"b\": 1}, \"big\": [1, 2, 3]}"), shared.to_value());
+/// assert_eq!(json!("{\"a\": {\"b\": 2}, \"big\": [1, 2, 3]}"), copy.to_value());
+/// assert!(shared.get("big").unwrap().ptr_eq(copy.get("big").unwrap()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArcValue {
+    Object(Arc<HashMap<String, ArcValue>>),
+    Array(Arc<Vec<ArcValue>>),
+    String(Arc<str>),
+    Number(JNumber),
+    Boolean(bool),
+    Null,
+}
+
+impl ArcValue {
+    /// Returns the value of the member with the given key if this is an object. Otherwise
+    /// returns [`None`].
+    pub fn get(&self, key: &str) -> Option<&ArcValue> {
+        match self {
+            ArcValue::Object(members) => members.get(key),
+            _ => None
+        }
+    }
+
+    /// Returns the element at the given index if this is an array. Otherwise returns [`None`].
+    pub fn index(&self, index: usize) -> Option<&ArcValue> {
+        match self {
+            ArcValue::Array(elements) => elements.get(index),
+            _ => None
+        }
+    }
+
+    /// Returns the value at the given JSON Pointer, like [`JValue::pointer`]
+    pub fn pointer(&self, pointer: &str) -> Option<&ArcValue> {
+        let mut value = self;
+        for token in parse_pointer(pointer).ok()? {
+            value = match value {
+                ArcValue::Object(_) => value.get(&token)?,
+                ArcValue::Array(_) => value.index(array_index(&token)?)?,
+                _ => return None
+            };
+        }
+        Some(value)
+    }
+
+    /// Returns a mutable reference to the value at the given JSON Pointer, copying the shared
+    /// containers on the way to it
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut ArcValue> {
+        let tokens = parse_pointer(pointer).ok()?;
+        // Look the value up first so that nothing is copied for a missing value
+        self.pointer(pointer)?;
+        let mut value = self;
+        for token in tokens {
+            value = match value {
+                ArcValue::Object(members) => Arc::make_mut(members).get_mut(&token)?,
+                ArcValue::Array(elements) => Arc::make_mut(elements).get_mut(array_index(&token)?)?,
+                _ => return None
+            };
+        }
+        Some(value)
+    }
+
+    /// Returns the members if this is an object, copying them if they are shared. Otherwise
+    /// returns [`None`].
+    pub fn as_object_mut(&mut self) -> Option<&mut HashMap<String, ArcValue>> {
+        match self {
+            ArcValue::Object(members) => Some(Arc::make_mut(members)),
+            _ => None
+        }
+    }
+
+    /// Returns the elements if this is an array, copying them if they are shared. Otherwise
+    /// returns [`None`].
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<ArcValue>> {
+        match self {
+            ArcValue::Array(elements) => Some(Arc::make_mut(elements)),
+            _ => None
+        }
+    }
+
+    /// Returns the string if this is a string. Otherwise returns [`None`].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ArcValue::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    /// Returns an f64 representation of the number if this is a number. Otherwise returns
+    /// [`None`].
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ArcValue::Number(n) => Some(n.get_f64_value()),
+            _ => None
+        }
+    }
+
+    /// Returns the boolean if this is a boolean. Otherwise returns [`None`].
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ArcValue::Boolean(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    /// Returns `true` if this is null
+    pub fn is_null(&self) -> bool {
+        *self == ArcValue::Null
+    }
+
+    /// Whether both values share the same array, object or string
+    pub fn ptr_eq(&self, other: &ArcValue) -> bool {
+        match (self, other) {
+            (ArcValue::Object(a), ArcValue::Object(b)) => Arc::ptr_eq(a, b),
+            (ArcValue::Array(a), ArcValue::Array(b)) => Arc::ptr_eq(a, b),
+            (ArcValue::String(a), ArcValue::String(b)) => Arc::ptr_eq(a, b),
+            _ => false
+        }
+    }
+
+    /// Creates an owned [`JValue`] copy of this value.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::ArcValue;
+    /// let j_value = json!("{\"key\": [true, null, 1.5e3, \"value\"]}");
+    /// assert_eq!(j_value, ArcValue::from(&j_value).to_value());
+    /// ```
+    pub fn to_value(&self) -> JValue {
+        match self {
+            ArcValue::Object(members) => {
+                let mut obj = JObject::new();
+                for (k, v) in members.iter() {
+                    obj.insert(k.clone(), v.to_value());
+                }
+                JValue::Object(obj)
+            }
+            ArcValue::Array(elements) => JValue::Array(elements.iter().map(|v| v.to_value()).collect()),
+            ArcValue::String(s) => JValue::String(JString::from(&**s)),
+            ArcValue::Number(n) => JValue::Number(n.clone()),
+            ArcValue::Boolean(b) => JValue::Boolean(*b),
+            ArcValue::Null => JValue::Null
+        }
+    }
+}
+
+impl From<&JValue> for ArcValue {
+    fn from(value: &JValue) -> Self {
+        match value {
+            JValue::Object(o) => ArcValue::Object(Arc::new(
+                o.iter().map(|(k, v)| (k.clone(), ArcValue::from(v))).collect())),
+            JValue::Array(a) => ArcValue::Array(Arc::new(a.iter().map(ArcValue::from).collect())),
+            JValue::String(s) => ArcValue::String(Arc::from(s.as_str())),
+            JValue::Number(n) => ArcValue::Number(n.clone()),
+            JValue::Boolean(b) => ArcValue::Boolean(*b),
+            JValue::Null => ArcValue::Null
+        }
+    }
+}
+
+impl From<JValue> for ArcValue {
+    fn from(value: JValue) -> Self {
+        ArcValue::from(&value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::data_structures::ArcValue;
+    use crate::json;
+
+    #[test]
+    fn test_copy_on_write() {
+        let shared = ArcValue::from(json!("{\"a\": [{\"b\": 1}, {\"c\": 2}], \"d\": \"text\"}"));
+        let mut copy = shared.clone();
+        assert!(shared.ptr_eq(&copy));
+        copy.as_object_mut().unwrap().insert("e".to_string(), ArcValue::Null);
+        assert!(!shared.ptr_eq(&copy));
+        assert!(shared.get("a").unwrap().ptr_eq(copy.get("a").unwrap()));
+
+        *copy.pointer_mut("/a/1/c").unwrap() = ArcValue::Boolean(true);
+        assert_eq!(Some(2.0), shared.pointer("/a/1/c").and_then(|v| v.as_f64()));
+        assert_eq!(Some(true), copy.pointer("/a/1/c").and_then(|v| v.as_bool()));
+        // Only the path to the changed value was copied
+        assert!(shared.pointer("/a/0").unwrap().ptr_eq(copy.pointer("/a/0").unwrap()));
+        assert!(shared.get("d").unwrap().ptr_eq(copy.get("d").unwrap()));
+
+        let before = copy.clone();
+        assert_eq!(None, copy.pointer_mut("/a/5"));
+        assert!(before.get("a").unwrap().ptr_eq(copy.get("a").unwrap()));
+        let mut subtree = copy.get("a").unwrap().clone();
+        subtree.as_array_mut().unwrap().clear();
+        assert_eq!(json!("[{\"b\": 1}, {\"c\": true}]"), copy.get("a").unwrap().to_value());
+    }
+
+    #[test]
+    fn test_share_across_threads() {
+        let shared = ArcValue::from(json!("{\"workers\": [1, 2, 3, 4]}"));
+        let sums: Vec<f64> = (0..4).map(|i| {
+            let mut document = shared.clone();
+            thread::spawn(move || {
+                document.as_object_mut().unwrap().insert("id".to_string(), ArcValue::from(json!("0")));
+                document.pointer(&format!("/workers/{}", i)).unwrap().as_f64().unwrap()
+            })
+        }).map(|handle| handle.join().unwrap()).collect();
+        assert_eq!(vec![1.0, 2.0, 3.0, 4.0], sums);
+        assert_eq!(None, shared.get("id"));
+    }
+}
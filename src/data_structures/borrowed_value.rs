@@ -0,0 +1,121 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::str::FromStr;
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+
+/// A JSON value whose nodes live in an arena and whose strings borrow from the parsed input.
+///
+/// Created by [`parse_in`](crate::parser::parse_in). Dropping the arena frees the whole tree at
+/// once, which makes it cheap to parse a document, read a few fields, and throw it away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorrowedValue<'a> {
+    Object(&'a [(&'a str, BorrowedValue<'a>)]),
+    Array(&'a [BorrowedValue<'a>]),
+    String(&'a str),
+    /// The number exactly as it appears in the input
+    Number(&'a str),
+    Boolean(bool),
+    Null,
+}
+
+impl<'a> BorrowedValue<'a> {
+    /// Returns the value of the first member with the given key if this is an object.
+    /// Otherwise returns [`None`].
+    ///
+    /// ```
+    /// # use json::parser::{parse_in, Bump};
+    /// # use json::data_structures::BorrowedValue;
+    /// let arena = Bump::new();
+    /// let value = parse_in(&arena, "{\"id\": 7, \"name\": \"json\"}").unwrap();
+    /// assert_eq!(Some(BorrowedValue::String("json")), value.get("name"));
+    /// assert_eq!(None, value.get("missing"));
+    /// ```
+    pub fn get(&self, key: &str) -> Option<BorrowedValue<'a>> {
+        match self {
+            BorrowedValue::Object(members) => members.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| *v),
+            _ => None
+        }
+    }
+
+    /// Returns the element at the given index if this is an array. Otherwise returns [`None`].
+    pub fn index(&self, index: usize) -> Option<BorrowedValue<'a>> {
+        match self {
+            BorrowedValue::Array(elements) => elements.get(index).copied(),
+            _ => None
+        }
+    }
+
+    /// Returns the string if this is a string. Otherwise returns [`None`].
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            BorrowedValue::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    /// Returns an f64 representation of the number if this is a number. Otherwise returns
+    /// [`None`].
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            BorrowedValue::Number(n) => n.parse().ok(),
+            _ => None
+        }
+    }
+
+    /// Returns the boolean if this is a boolean. Otherwise returns [`None`].
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            BorrowedValue::Boolean(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    /// Returns `true` if this is null
+    pub fn is_null(&self) -> bool {
+        *self == BorrowedValue::Null
+    }
+
+    /// Creates an owned [`JValue`] copy of this value that outlives the arena.
+    ///
+    /// ```
+    /// # use json::parser::{parse, parse_in, Bump};
+    /// let arena = Bump::new();
+    /// let json = "{\"key\": [true, null, 1.5e3, \"value\"]}";
+    /// assert_eq!(parse(json).unwrap(), parse_in(&arena, json).unwrap().to_owned_value());
+    /// ```
+    pub fn to_owned_value(&self) -> JValue {
+        match self {
+            BorrowedValue::Object(members) => {
+                let mut obj = JObject::new();
+                for (k, v) in members.iter() {
+                    obj.insert(k.to_string(), v.to_owned_value());
+                }
+                JValue::Object(obj)
+            }
+            BorrowedValue::Array(elements) => JValue::Array(
+                elements.iter().map(|v| v.to_owned_value()).collect()),
+            BorrowedValue::String(s) => JValue::String(JString::from(*s)),
+            // The arena parser has already validated the number
+            BorrowedValue::Number(n) => JValue::Number(JNumber::from_str(n).unwrap()),
+            BorrowedValue::Boolean(b) => JValue::Boolean(*b),
+            BorrowedValue::Null => JValue::Null
+        }
+    }
+}
@@ -15,8 +15,13 @@
 // You should have received a copy of the GNU General Public License
 // along with json.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Div, Mul, Neg, Sub};
 use std::str::FromStr;
+use crate::canonical::write_number;
 use crate::serializer::Serialize;
 
 /// A number is a sequence of decimal digits with no superfluous leading zero. It may have a
@@ -30,8 +35,12 @@ pub struct JNumber {
     fractional_part: String,
     exponent: String,
     e_sign: Sign,
+    #[cfg_attr(feature = "preserve-numbers", allow(dead_code))]
     e_symbol: char,
     f64_value: f64,
+    /// The number as it was written, which is displayed and serialized instead of the parts
+    #[cfg(feature = "preserve-numbers")]
+    text: String,
 }
 
 impl JNumber {
@@ -39,10 +48,129 @@ impl JNumber {
     pub fn get_f64_value(&self) -> f64 {
         self.f64_value
     }
+
+    /// Creates a number from an f64, written with the fewest digits that parse back to the same
+    /// f64 (the shortest round-trip form), in the notation [`crate::canonical`] uses. Returns
+    /// `None` for NaN and the infinities, which JSON can not represent.
+    ///
+    /// ```
+    /// # use json::data_structures::JNumber;
+    /// assert_eq!("0.1", JNumber::from_f64(0.1).unwrap().to_string());
+    /// assert_eq!("0.30000000000000004", JNumber::from_f64(0.1 + 0.2).unwrap().to_string());
+    /// assert_eq!("1e+21", JNumber::from_f64(1e21).unwrap().to_string());
+    /// assert_eq!(0.1, JNumber::from_f64(0.1).unwrap().get_f64_value());
+    /// assert_eq!(None, JNumber::from_f64(f64::NAN));
+    /// ```
+    pub fn from_f64(n: f64) -> Option<JNumber> {
+        if !n.is_finite() {
+            return None;
+        }
+        let mut text = String::new();
+        if n == 0.0 && n.is_sign_negative() {
+            text.push('-');
+        }
+        write_number(n, &mut text);
+        let mut number = JNumber::from_str(&text).ok()?;
+        number.f64_value = n;
+        Some(number)
+    }
+
+    /// Returns the sum of the numbers. Numbers with at most 38 significant digits are added
+    /// exactly, so `0.1 + 0.2` is `0.3`, unless their exponents are too far apart; others are
+    /// added as f64, and `None` is returned if that overflows. The `+` operator panics where
+    /// this returns `None`.
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use json::data_structures::JNumber;
+    /// let n = |s| JNumber::from_str(s).unwrap();
+    /// assert_eq!("0.3", n("0.1").checked_add(&n("0.2")).unwrap().to_string());
+    /// assert_eq!("9007199254740993", (&n("9007199254740992") + &n("1")).to_string());
+    /// assert_eq!("2e+308", (n("1e308") + n("1e308")).to_string());
+    /// assert_eq!(None, n("1e400").checked_add(&n("1")));
+    /// ```
+    pub fn checked_add(&self, other: &JNumber) -> Option<JNumber> {
+        match (self.decimal(), other.decimal()) {
+            (Some(a), Some(b)) => a.add(b).map(Decimal::to_number),
+            _ => None
+        }.or_else(|| JNumber::from_f64(self.f64_value + other.f64_value))
+    }
+
+    /// Returns the difference of the numbers like [`JNumber::checked_add`] returns the sum.
+    pub fn checked_sub(&self, other: &JNumber) -> Option<JNumber> {
+        self.checked_add(&-other)
+    }
+
+    /// Returns the product of the numbers like [`JNumber::checked_add`] returns the sum.
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use json::data_structures::JNumber;
+    /// let n = |s| JNumber::from_str(s).unwrap();
+    /// assert_eq!("1.21", (n("1.1") * n("1.1")).to_string());
+    /// assert_eq!("-6e+40", (n("2e20") * n("-3e20")).to_string());
+    /// ```
+    pub fn checked_mul(&self, other: &JNumber) -> Option<JNumber> {
+        match (self.decimal(), other.decimal()) {
+            (Some(a), Some(b)) => a.mul(b).map(Decimal::to_number),
+            _ => None
+        }.or_else(|| JNumber::from_f64(self.f64_value * other.f64_value))
+    }
+
+    /// Returns the quotient of the numbers, or `None` if `other` is zero. Quotients with a short
+    /// enough decimal expansion, such as `1 / 8`, are exact; others are divided as f64, and
+    /// `None` is returned if that overflows. The `/` operator panics where this returns `None`.
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use json::data_structures::JNumber;
+    /// let n = |s| JNumber::from_str(s).unwrap();
+    /// assert_eq!("0.125", (n("1") / n("8")).to_string());
+    /// assert_eq!("4", (n("10") / n("2.5")).to_string());
+    /// assert_eq!("0.3333333333333333", (n("1") / n("3")).to_string());
+    /// assert_eq!(None, n("1").checked_div(&n("0")));
+    /// ```
+    pub fn checked_div(&self, other: &JNumber) -> Option<JNumber> {
+        // The f64 of a tiny divisor such as 1e-400 is zero, so its digits are checked instead
+        if other.integer_part.bytes().chain(other.fractional_part.bytes()).all(|c| c == b'0') {
+            return None;
+        }
+        match (self.decimal(), other.decimal()) {
+            (Some(a), Some(b)) => a.div(b).map(Decimal::to_number),
+            _ => None
+        }.or_else(|| JNumber::from_f64(self.f64_value / other.f64_value))
+    }
+
+    /// The number as an exact decimal, if its significant digits fit in an i128
+    fn decimal(&self) -> Option<Decimal> {
+        let fraction = if self.fractional_part == "0" { "" } else { self.fractional_part.as_str() };
+        let digits = format!("{}{}", self.integer_part, fraction);
+        let significant = digits.trim_start_matches('0');
+        let trimmed = significant.trim_end_matches('0');
+        if trimmed.is_empty() {
+            return Some(Decimal { mantissa: 0, exponent: 0 });
+        }
+        let mut mantissa: i128 = trimmed.parse().ok()?;
+        if self.sign == Sign::Negative {
+            mantissa = -mantissa;
+        }
+        let mut exponent: i32 = self.exponent.parse().ok()?;
+        if self.e_sign == Sign::Negative {
+            exponent = -exponent;
+        }
+        let trailing_zeros = (significant.len() - trimmed.len()) as i32;
+        let exponent = exponent.checked_sub(fraction.len() as i32)?.checked_add(trailing_zeros)?;
+        Some(Decimal { mantissa, exponent })
+    }
 }
 
 impl Display for JNumber {
+    /// Writes the number as it was written with the `preserve-numbers` feature. Otherwise a
+    /// zero fractional part or exponent is left out, so `1.0` and `1e0` are written as `1`.
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        #[cfg(feature = "preserve-numbers")]
+        return write!(f, "{}", self.text);
+        #[cfg(not(feature = "preserve-numbers"))]
         write!(f, "{}{}{}{}{}{}{}",
                if self.sign == Sign::Negative { "-" } else { "" },
                self.integer_part,
@@ -76,9 +204,11 @@ impl FromStr for JNumber {
     /// assert_eq!(Err("An illegal point at index 3".to_string()), n);
     /// ```
     // Todo: High cyclomatic complexity! Optimization needed.
-    // Todo: The f64_value is not very accurate.
     // Todo: consider implementing from_str() locally i.e. without implementing the trait std::str::FromStr
     fn from_str(s: &str) -> Result<Self, String> {
+        if s.is_empty() {
+            return Err("The string is empty".to_string());
+        }
         let mut sign = Sign::None;
         let mut next_is_point = false;
         let mut next_is_digit = false;
@@ -101,7 +231,10 @@ impl FromStr for JNumber {
                     return Err(format!("Illegal symbol {} at index {}", c, i));
                 }
             } else if next_is_point {
-                if c != '.' {
+                if c == 'e' || c == 'E' {
+                    next_is_point = false;
+                    e_symbol = c;
+                } else if c != '.' {
                     return Err(format!("Illegal input! Point was expected at index {}", i));
                 } else {
                     next_is_point = false;
@@ -137,7 +270,7 @@ impl FromStr for JNumber {
                     return Err(format!("Illegal symbol {} at index {}", c, i));
                 }
             } else if c == '+' || c == '-' {
-                if e_symbol != ' ' && temp_int.len() == 0 && e_sign == Sign::None {
+                if e_symbol != ' ' && temp_int.is_empty() && e_sign == Sign::None {
                     e_sign = if c == '+' { Sign::Positive } else { Sign::Negative };
                 } else {
                     return Err(format!("An illegal sign at index {}", i));
@@ -146,7 +279,7 @@ impl FromStr for JNumber {
                 return Err(format!("Illegal first symbol {} at index {}", c, i));
             }
         }
-        if temp_int.len() > 0 {
+        if !temp_int.is_empty() {
             if e_symbol != ' ' {
                 exponent_part = temp_int.clone();
             } else if point {
@@ -155,11 +288,12 @@ impl FromStr for JNumber {
                 integer_part = temp_int.clone();
             }
         }
+        check_grammar(s)?;
         let f64_value = get_f64(&sign,
                                 &integer_part,
                                 &fractional_part,
                                 &e_sign,
-                                &exponent_part);
+                                &exponent_part)?;
         Ok(JNumber {
             sign,
             integer_part,
@@ -168,6 +302,8 @@ impl FromStr for JNumber {
             e_sign,
             e_symbol,
             f64_value,
+            #[cfg(feature = "preserve-numbers")]
+            text: s.to_string(),
         })
     }
 }
@@ -178,6 +314,106 @@ impl PartialEq for JNumber {
     }
 }
 
+impl Eq for JNumber {}
+
+impl PartialOrd for JNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for JNumber {
+    /// Numbers are ordered by their f64 value.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.f64_value.partial_cmp(&other.f64_value)
+            .unwrap_or_else(|| self.f64_value.total_cmp(&other.f64_value))
+    }
+}
+
+impl Hash for JNumber {
+    /// Numbers that are equal hash the same, e.g. `1`, `1.0` and `0.1e1`, or `0` and `-0`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let canonical = if self.f64_value == 0.0 {
+            0.0_f64
+        } else if self.f64_value.is_nan() {
+            f64::NAN
+        } else {
+            self.f64_value
+        };
+        canonical.to_bits().hash(state)
+    }
+}
+
+impl Neg for &JNumber {
+    type Output = JNumber;
+
+    /// Flips the sign, keeping the digits as written.
+    fn neg(self) -> JNumber {
+        let mut result = self.clone();
+        result.sign = if self.sign == Sign::Negative { Sign::None } else { Sign::Negative };
+        result.f64_value = -self.f64_value;
+        #[cfg(feature = "preserve-numbers")]
+        {
+            result.text = match self.text.strip_prefix('-') {
+                Some(text) => text.to_string(),
+                None => format!("-{}", self.text)
+            };
+        }
+        result
+    }
+}
+
+impl Neg for JNumber {
+    type Output = JNumber;
+
+    fn neg(self) -> JNumber {
+        -&self
+    }
+}
+
+/// Implements an arithmetic operator for all combinations of owned and borrowed numbers with
+/// the checked method, panicking like integer arithmetic does where it returns `None`
+macro_rules! impl_operator {
+    ($trait:ident, $method:ident, $checked:ident, $message:expr) => {
+        impl $trait<&JNumber> for &JNumber {
+            type Output = JNumber;
+
+            fn $method(self, other: &JNumber) -> JNumber {
+                self.$checked(other).expect($message)
+            }
+        }
+
+        impl $trait<JNumber> for JNumber {
+            type Output = JNumber;
+
+            fn $method(self, other: JNumber) -> JNumber {
+                (&self).$method(&other)
+            }
+        }
+
+        impl $trait<&JNumber> for JNumber {
+            type Output = JNumber;
+
+            fn $method(self, other: &JNumber) -> JNumber {
+                (&self).$method(other)
+            }
+        }
+
+        impl $trait<JNumber> for &JNumber {
+            type Output = JNumber;
+
+            fn $method(self, other: JNumber) -> JNumber {
+                self.$method(&other)
+            }
+        }
+    };
+}
+
+impl_operator!(Add, add, checked_add, "attempt to add with overflow");
+impl_operator!(Sub, sub, checked_sub, "attempt to subtract with overflow");
+impl_operator!(Mul, mul, checked_mul, "attempt to multiply with overflow");
+impl_operator!(Div, div, checked_div, "attempt to divide by zero or with overflow");
+
 impl Serialize for JNumber {
     /// Creates a serialization of the [`JNumber`] data-structure as a JSON string with
     /// minimal whitespace characters.
@@ -191,26 +427,169 @@ impl Serialize for JNumber {
     fn serialize(&self) -> String {
         self.to_string()
     }
+
+    #[cfg(feature = "preserve-numbers")]
+    fn serialized_len(&self) -> usize {
+        self.text.len()
+    }
+
+    #[cfg(not(feature = "preserve-numbers"))]
+    fn serialized_len(&self) -> usize {
+        let mut len = self.integer_part.len();
+        if self.sign == Sign::Negative {
+            len += 1;
+        }
+        if self.fractional_part != "0" {
+            len += 1 + self.fractional_part.len();
+        }
+        if self.exponent != "0" {
+            len += self.e_symbol.len_utf8() + self.exponent.len();
+        }
+        if self.e_sign != Sign::None {
+            len += 1;
+        }
+        len
+    }
+}
+
+/// Checks that `s` follows the number grammar of RFC 8259, which requires a digit before and
+/// after a point and after an exponent symbol and its sign
+fn check_grammar(s: &str) -> Result<(), String> {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let digits = |i: &mut usize| {
+        let from = *i;
+        while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        if *i > from { Ok(()) } else { Err(format!("Digit was expected at index {}", *i)) }
+    };
+    if bytes.first() == Some(&b'-') {
+        i += 1;
+    }
+    if bytes.get(i) == Some(&b'0') {
+        i += 1;
+    } else {
+        digits(&mut i)?;
+    }
+    if bytes.get(i) == Some(&b'.') {
+        i += 1;
+        digits(&mut i)?;
+    }
+    if matches!(bytes.get(i), Some(b'e') | Some(b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+') | Some(b'-')) {
+            i += 1;
+        }
+        digits(&mut i)?;
+    }
+    match s[i..].chars().next() {
+        Some(c) => Err(format!("Illegal symbol {} at index {}", c, i)),
+        None => Ok(())
+    }
 }
 
+/// Converts the parts of a number to the nearest f64, by `f64::from_str` on the number
+/// rewritten in the plain form it accepts. Numbers too large for an f64 become infinite.
 fn get_f64(sign: &Sign,
            integer_part: &str,
            fractional_part: &str,
            e_sign: &Sign,
-           exponent_part: &str) -> f64 {
-    let mut result: f64 = integer_part.parse::<f64>().unwrap();
-    result += fractional_part.parse::<f64>().unwrap() / 10.0_f64.powi(fractional_part.len() as i32);
-    if exponent_part != "0" {
-        if *e_sign == Sign::Negative {
-            result *= 10.0_f64.powi(-1 * (exponent_part.parse::<i32>().unwrap()));
-        } else {
-            result *= 10.0_f64.powi(exponent_part.parse::<i32>().unwrap());
+           exponent_part: &str) -> Result<f64, String> {
+    let mut normalized = String::with_capacity(integer_part.len() + fractional_part.len() + exponent_part.len() + 4);
+    if *sign == Sign::Negative {
+        normalized.push('-');
+    }
+    normalized.push_str(integer_part);
+    normalized.push('.');
+    normalized.push_str(fractional_part);
+    normalized.push('e');
+    if *e_sign == Sign::Negative {
+        normalized.push('-');
+    }
+    normalized.push_str(exponent_part);
+    normalized.parse::<f64>().map_err(|e| format!("Can not convert {} to a float: {}", normalized, e))
+}
+
+/// A number as `mantissa * 10^exponent`, with no trailing zeros in the mantissa
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Decimal {
+    mantissa: i128,
+    exponent: i32,
+}
+
+impl Decimal {
+    fn new(mut mantissa: i128, mut exponent: i32) -> Option<Decimal> {
+        if mantissa == 0 {
+            return Some(Decimal { mantissa, exponent: 0 });
+        }
+        while mantissa % 10 == 0 {
+            mantissa /= 10;
+            exponent = exponent.checked_add(1)?;
         }
+        Some(Decimal { mantissa, exponent })
     }
-    if *sign == Sign::Negative {
-        result *= -1.0;
+
+    /// The mantissa scaled to the smaller `exponent`
+    fn scaled(self, exponent: i32) -> Option<i128> {
+        let shift = u32::try_from(self.exponent.checked_sub(exponent)?).ok()?;
+        10_i128.checked_pow(shift)?.checked_mul(self.mantissa)
+    }
+
+    fn add(self, other: Decimal) -> Option<Decimal> {
+        let exponent = self.exponent.min(other.exponent);
+        Decimal::new(self.scaled(exponent)?.checked_add(other.scaled(exponent)?)?, exponent)
+    }
+
+    fn mul(self, other: Decimal) -> Option<Decimal> {
+        Decimal::new(self.mantissa.checked_mul(other.mantissa)?, self.exponent.checked_add(other.exponent)?)
+    }
+
+    /// The quotient, if its decimal expansion ends within the precision of an i128
+    fn div(self, other: Decimal) -> Option<Decimal> {
+        let mut dividend = self.mantissa;
+        let mut exponent = self.exponent.checked_sub(other.exponent)?;
+        while dividend % other.mantissa != 0 {
+            dividend = dividend.checked_mul(10)?;
+            exponent = exponent.checked_sub(1)?;
+        }
+        Decimal::new(dividend / other.mantissa, exponent)
+    }
+
+    /// Writes the decimal like ECMAScript writes numbers, which is also how
+    /// [`JNumber::from_f64`] writes them, except that integers as long as an i128 are written
+    /// in full, and parses it back
+    fn to_number(self) -> JNumber {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let k = digits.len() as i64;
+        let point = k + self.exponent as i64;
+        let mut text = String::new();
+        if self.mantissa < 0 {
+            text.push('-');
+        }
+        if k <= point && point <= 39 {
+            text.push_str(&digits);
+            text.extend(std::iter::repeat_n('0', (point - k) as usize));
+        } else if 0 < point && point <= 21 {
+            text.push_str(&digits[..point as usize]);
+            text.push('.');
+            text.push_str(&digits[point as usize..]);
+        } else if -6 < point && point <= 0 {
+            text.push_str("0.");
+            text.extend(std::iter::repeat_n('0', -point as usize));
+            text.push_str(&digits);
+        } else {
+            text.push_str(&digits[..1]);
+            if k > 1 {
+                text.push('.');
+                text.push_str(&digits[1..]);
+            }
+            text.push('e');
+            text.push(if point > 0 { '+' } else { '-' });
+            text.push_str(&(point - 1).abs().to_string());
+        }
+        JNumber::from_str(&text).unwrap()
     }
-    result
 }
 
 /// An enum that represents the signs for numbers
@@ -297,8 +676,21 @@ mod test {
         assert_eq!(340.6001e+2_f64, n.get_f64_value());
     }
 
+    #[test]
+    fn test_zero_exponent() {
+        let n = JNumber::from_str("0e5").unwrap();
+        assert_eq!("0e5".to_string(), n.to_string());
+        assert_eq!(0_f64, n.get_f64_value());
+
+        let n = JNumber::from_str("0E-2").unwrap();
+        assert_eq!("0E-2".to_string(), n.to_string());
+    }
+
     #[test]
     fn test_illegal_numbers() {
+        let n = JNumber::from_str("");
+        assert_eq!(Err("The string is empty".to_string()), n);
+
         let n = JNumber::from_str("00");
         assert_eq!(Err("Illegal input! Point was expected at index 1".to_string()), n);
 
@@ -322,6 +714,33 @@ mod test {
 
         let n = JNumber::from_str("-1123.35E2E3");
         assert_eq!(Err("Illegal symbol E at index 10".to_string()), n);
+
+        for (s, index) in [("-", 1), ("1.", 2), ("1e", 2), ("1e+", 3), ("-.5", 1), ("1.e5", 2), ("-e5", 1), ("-.e", 1)] {
+            assert_eq!(Err(format!("Digit was expected at index {}", index)), JNumber::from_str(s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_ord() {
+        let n = |s| JNumber::from_str(s).unwrap();
+        assert!(n("-1") < n("0"));
+        assert!(n("0.5") < n("1e0"));
+        assert_eq!(std::cmp::Ordering::Equal, n("0").cmp(&n("-0")));
+    }
+
+    #[test]
+    fn test_hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let hash = |s| {
+            let mut hasher = DefaultHasher::new();
+            JNumber::from_str(s).unwrap().hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash("1"), hash("1.0"));
+        assert_eq!(hash("10"), hash("1e1"));
+        assert_eq!(hash("0"), hash("-0"));
+        assert_ne!(hash("1"), hash("2"));
     }
 
     #[test]
@@ -332,4 +751,96 @@ mod test {
         n = JNumber::from_str("2.34E10").unwrap();
         assert_eq!("2.34E10".to_string(), n.serialize());
     }
+
+    #[test]
+    fn test_serialized_len() {
+        for s in ["0", "-0", "-300", "340.600", "-0.0016387E-3", "340.6001E+2", "2.34e10"].iter() {
+            let n = JNumber::from_str(s).unwrap();
+            assert_eq!(n.serialize().len(), n.serialized_len());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "preserve-numbers")]
+    fn test_preserved_text() {
+        let j_value = crate::parser::parse("[1.0, 1e2, -0.0, 1E+0, 0.100, 5]").unwrap();
+        assert_eq!("[1.0,1e2,-0.0,1E+0,0.100,5]", j_value.serialize());
+        assert_eq!(j_value.serialize().len(), j_value.serialized_len());
+        let n = JNumber::from_str("1.0e2").unwrap();
+        assert_eq!((100.0, "-1.0e2"), ((-&n).get_f64_value().abs(), (-&n).to_string().as_str()));
+        assert_eq!("1.0e2", (-(-&n)).to_string());
+        assert_eq!(n, JNumber::from_str("100").unwrap());
+    }
+
+    #[test]
+    #[cfg(not(feature = "preserve-numbers"))]
+    fn test_normalized_text() {
+        let n = |s| JNumber::from_str(s).unwrap().to_string();
+        assert_eq!(("1".to_string(), "1".to_string()), (n("1.0"), n("1e0")));
+    }
+
+    #[test]
+    fn test_from_f64() {
+        for n in [0.0, -0.0, 1.0, -1.5, 0.1, 1.0 / 3.0, 123456789.125, 5e-324, 1e-7, 1e21, f64::MAX, f64::MIN_POSITIVE] {
+            let j_number = JNumber::from_f64(n).unwrap();
+            assert_eq!(n.to_bits(), j_number.get_f64_value().to_bits());
+            assert_eq!(n, j_number.to_string().parse::<f64>().unwrap());
+            assert_eq!(j_number.to_string().len(), j_number.serialized_len());
+        }
+        assert_eq!("-0", JNumber::from_f64(-0.0).unwrap().to_string());
+        assert_eq!("100", JNumber::from_f64(100.0).unwrap().to_string());
+        assert_eq!("1e-7", JNumber::from_f64(1e-7).unwrap().to_string());
+        assert_eq!(None, JNumber::from_f64(f64::INFINITY));
+        assert_eq!(None, JNumber::from_f64(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_f64_rounding() {
+        for s in ["0.1", "0.3", "9007199254740993", "1.7976931348623157e308", "2.2250738585072011e-308",
+                  "4.9406564584124654e-324", "123456789012345678901234567890e-10", "0.000000000000000000001",
+                  "-8.589973e9", "1e400", "1e-400", "3.14159265358979323846264338327950288"] {
+            let expected = s.parse::<f64>().unwrap();
+            assert_eq!(expected.to_bits(), JNumber::from_str(s).unwrap().get_f64_value().to_bits(), "{}", s);
+        }
+        assert_eq!(f64::MAX, JNumber::from_str("1.7976931348623157e308").unwrap().get_f64_value());
+        assert_eq!((-0.0_f64).to_bits(), JNumber::from_str("-0.0").unwrap().get_f64_value().to_bits());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let n = |s| JNumber::from_str(s).unwrap();
+        assert_eq!("3.5", (n("1.25") + n("2.25")).to_string());
+        assert_eq!("-0.5", (n("1") - n("1.5")).to_string());
+        assert_eq!("100", (n("1e3") - n("900")).to_string());
+        assert_eq!("0", (n("0.1") - n("1e-1")).to_string());
+        let max = n("170141183460469231731687303715884105727");
+        assert_eq!(max.to_string(), (n("170141183460469231731687303715884105726") + n("1")).to_string());
+        assert_eq!(max.get_f64_value() * 2.0, (&max * n("2")).get_f64_value());
+        assert_eq!("1e-7", (n("1e-4") * n("0.001")).to_string());
+        assert_eq!("2.5e+40", (n("5e40") / n("2")).to_string());
+        assert_eq!("2500000000000000000000", (n("5e21") / n("2")).to_string());
+        assert_eq!("-3", (-n("3")).to_string());
+        assert_eq!("3", (-n("-3")).to_string());
+        assert_eq!("1e400", n("1e400").to_string());
+        assert_eq!(None, n("1e400").checked_add(&n("1")));
+        assert_eq!(None, n("1").checked_div(&n("0.0")));
+        let total = ["0.1", "0.2", "0.3"].iter().fold(n("0"), |sum, s| sum + n(s));
+        assert_eq!("0.6", total.to_string());
+        assert!(n("2") > n("1.5"));
+    }
+
+    #[test]
+    fn test_division_by_tiny_numbers() {
+        let n = |s| JNumber::from_str(s).unwrap();
+        assert_eq!(0.0, n("1e-400").get_f64_value());
+        assert_eq!("1e+400", (n("1") / n("1e-400")).to_string());
+        assert_eq!("2", (n("2e-400") / n("1e-400")).to_string());
+        assert_eq!(None, n("1").checked_div(&n("-0.000e-400")));
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to divide by zero")]
+    fn test_division_by_zero() {
+        let _ = JNumber::from_str("1").unwrap() / JNumber::from_str("0").unwrap();
+    }
 }
@@ -15,21 +15,30 @@
 // You should have received a copy of the GNU General Public License
 // along with json.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::collections::hash_map::{IntoIter, Iter, IterMut};
 use std::fmt::{Display, Formatter};
-use crate::data_structures::JValue;
-use crate::serializer::{Serialize, serialize_string};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use crate::data_structures::CowValue;
+use crate::data_structures::j_value::element_to_string;
+use crate::serializer::{Serialize, serialize_string, serialized_string_len};
+
+/// An owned [`CowObject`], the object of a [`JValue`](crate::data_structures::JValue)
+pub type JObject = CowObject<'static>;
 
 /// An object is an unordered set of name/value pairs.
 /// An object begins with '{' left brace and ends with '}' right brace.
 /// Each name is followed by ':' colon and the name/value pairs are separated by ',' comma.
+/// Its values may borrow the strings of the document it was parsed from, see [`CowValue`].
 #[derive(Debug, Clone)]
-pub struct JObject {
-    value: HashMap<String, JValue>,
+pub struct CowObject<'a> {
+    value: HashMap<String, CowValue<'a>>,
     size: usize,
 }
 
-impl JObject {
+impl<'a> CowObject<'a> {
     /// Creates new empty JObject
     ///
     /// ```
@@ -37,25 +46,98 @@ impl JObject {
     /// let object = JObject::new();
     /// assert_eq!("{}".to_string(), object.to_string());
     /// ```
-    pub fn new() -> JObject {
-        JObject {
+    pub fn new() -> CowObject<'a> {
+        CowObject {
             value: HashMap::new(),
             size: 0,
         }
     }
 
+    /// Creates an empty JObject with room for at least `capacity` members without reallocating
+    ///
+    /// ```
+    /// # use json::data_structures::JObject;
+    /// let object = JObject::with_capacity(100);
+    /// assert!(object.capacity() >= 100);
+    /// assert_eq!(0, object.len());
+    /// ```
+    pub fn with_capacity(capacity: usize) -> CowObject<'a> {
+        CowObject {
+            value: HashMap::with_capacity(capacity),
+            size: 0,
+        }
+    }
+
+    /// Returns the number of members the JObject can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.value.capacity()
+    }
+
+    /// Reserves room for at least `additional` more members
+    ///
+    /// ```
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut object = JObject::new();
+    /// object.insert("a".to_string(), JValue::Null);
+    /// object.reserve(10);
+    /// assert!(object.capacity() >= 11);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.value.reserve(additional)
+    }
+
+    /// Shrinks the capacity of the JObject as much as possible
+    ///
+    /// ```
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut object = JObject::with_capacity(100);
+    /// object.insert("a".to_string(), JValue::Null);
+    /// object.shrink_to_fit();
+    /// assert!(object.capacity() < 100);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.value.shrink_to_fit()
+    }
+
     /// Returns the number of elements in the JObject
     ///
     /// ```
     /// # use json::data_structures::{JObject, JValue};
     /// let mut obj = JObject::new();
     /// obj.insert("key".to_string(), JValue::Boolean(true));
-    /// assert_eq!("{key : true,}".to_string(), obj.to_string());
+    /// assert_eq!("{\"key\" : true,}".to_string(), obj.to_string());
     /// ```
     pub fn len(&self) -> usize {
         self.size
     }
 
+    /// Returns `true` if the JObject has no members
+    ///
+    /// ```
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut obj = JObject::new();
+    /// assert!(obj.is_empty());
+    /// obj.insert("key".to_string(), JValue::Null);
+    /// assert!(!obj.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Removes all members, keeping the allocated memory for reuse
+    ///
+    /// ```
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut obj = JObject::new();
+    /// obj.insert("key".to_string(), JValue::Null);
+    /// obj.clear();
+    /// assert!(obj.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.value.clear();
+        self.size = 0;
+    }
+
     /// Inserts a key-value pair into the object.
     ///
     /// If the object did not have this key present, [`None`] is returned.
@@ -68,10 +150,10 @@ impl JObject {
     /// let mut obj = JObject::new();
     /// assert_eq!(0, obj.len());
     /// obj.insert("key".to_string(), JValue::Boolean(true));
-    /// assert_eq!("{key : true,}".to_string(), obj.to_string());
+    /// assert_eq!("{\"key\" : true,}".to_string(), obj.to_string());
     /// assert_eq!(1, obj.len());
     /// ```
-    pub fn insert(&mut self, k: String, v: JValue) -> Option<JValue> {
+    pub fn insert(&mut self, k: String, v: CowValue<'a>) -> Option<CowValue<'a>> {
         match self.value.insert(k, v) {
             Some(old_v) => Some(old_v),
             None => {
@@ -95,7 +177,7 @@ impl JObject {
     /// assert_eq!(0, obj.len());
     /// assert_eq!(None, obj.remove(&s));
     /// ```
-    pub fn remove(&mut self, k: &String) -> Option<JValue> {
+    pub fn remove(&mut self, k: &String) -> Option<CowValue<'a>> {
         match self.value.remove(k) {
             Some(v) => {
                 self.size -= 1;
@@ -105,6 +187,35 @@ impl JObject {
         }
     }
 
+    /// Returns `true` if the object has a member with the key
+    ///
+    /// ```
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut obj = JObject::new();
+    /// obj.insert("key".to_string(), JValue::Null);
+    /// assert!(obj.contains_key(&"key".to_string()));
+    /// assert!(!obj.contains_key(&"other".to_string()));
+    /// ```
+    pub fn contains_key(&self, k: &String) -> bool {
+        self.value.contains_key(k)
+    }
+
+    /// Keeps only the members for which `keep` returns `true`, visiting them in arbitrary order
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut obj = JObject::new();
+    /// obj.insert("a".to_string(), JValue::Null);
+    /// obj.insert("b".to_string(), JValue::Boolean(true));
+    /// obj.retain(|_, v| *v != JValue::Null);
+    /// assert_eq!(json!("{\"b\": true}"), JValue::Object(obj));
+    /// ```
+    pub fn retain<F: FnMut(&String, &mut CowValue<'a>) -> bool>(&mut self, keep: F) {
+        self.value.retain(keep);
+        self.size = self.value.len();
+    }
+
     /// Gets a reference to the value if the key exists in the object. Otherwise returns [`None`].
     ///
     /// ```
@@ -114,7 +225,7 @@ impl JObject {
     /// obj.insert(s.clone(), JValue::Boolean(false));
     /// assert_eq!("false".to_string(), obj.get(&s).unwrap().to_string());
     /// ```
-    pub fn get(&self, k: &String) -> Option<&JValue> {
+    pub fn get(&self, k: &String) -> Option<&CowValue<'a>> {
         self.value.get(k)
     }
 
@@ -129,32 +240,181 @@ impl JObject {
     /// *obj.get_mut(&s).unwrap() = JValue::Boolean(true);
     /// assert_eq!("true".to_string(), obj.get(&s).unwrap().to_string());
     /// ```
-    pub fn get_mut(&mut self, k: &String) -> Option<&mut JValue> {
+    pub fn get_mut(&mut self, k: &String) -> Option<&mut CowValue<'a>> {
         self.value.get_mut(k)
     }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    ///
+    /// ```
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut obj = JObject::new();
+    /// obj.insert("key".to_string(), JValue::Null);
+    /// for (k, v) in obj.iter() {
+    ///     assert_eq!("key", k);
+    ///     assert_eq!(JValue::Null, *v);
+    /// }
+    /// ```
+    pub fn iter(&self) -> Iter<'_, String, CowValue<'a>> {
+        self.value.iter()
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order, with mutable references to
+    /// the values.
+    ///
+    /// ```
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut obj = JObject::new();
+    /// obj.insert("key".to_string(), JValue::Null);
+    /// for (_, v) in obj.iter_mut() {
+    ///     *v = JValue::Boolean(true);
+    /// }
+    /// assert_eq!(JValue::Boolean(true), *obj.get(&"key".to_string()).unwrap());
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, String, CowValue<'a>> {
+        self.value.iter_mut()
+    }
+
+    /// Converts the object into one that owns all of its strings, see [`CowValue::into_owned`]
+    pub fn into_owned(self) -> JObject {
+        CowObject {
+            value: self.value.into_iter().map(|(k, v)| (k, v.into_owned())).collect(),
+            size: self.size,
+        }
+    }
+
+    /// Returns the key-value pairs sorted by key
+    fn sorted(&self) -> Vec<(&String, &CowValue<'a>)> {
+        let mut members: Vec<(&String, &CowValue<'a>)> = self.value.iter().collect();
+        members.sort_unstable_by_key(|(k, _)| *k);
+        members
+    }
 }
 
+impl<'a> IntoIterator for CowObject<'a> {
+    type Item = (String, CowValue<'a>);
+    type IntoIter = IntoIter<String, CowValue<'a>>;
 
-impl Display for JObject {
+    fn into_iter(self) -> Self::IntoIter {
+        self.value.into_iter()
+    }
+}
+
+impl<'a> Default for CowObject<'a> {
+    /// Creates an empty JObject, like [`JObject::new`]
+    fn default() -> CowObject<'a> {
+        CowObject::new()
+    }
+}
+
+impl<'a> Extend<(String, CowValue<'a>)> for CowObject<'a> {
+    /// Inserts the members, replacing the values of the keys the object has already
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut obj = JObject::new();
+    /// obj.insert("a".to_string(), JValue::Null);
+    /// obj.extend(vec![("a".to_string(), JValue::Boolean(true)), ("b".to_string(), JValue::Null)]);
+    /// assert_eq!(json!("{\"a\": true, \"b\": null}"), JValue::Object(obj));
+    /// ```
+    fn extend<I: IntoIterator<Item=(String, CowValue<'a>)>>(&mut self, members: I) {
+        for (k, v) in members {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a> FromIterator<(String, CowValue<'a>)> for CowObject<'a> {
+    /// Collects the members into an object, where a later value of a key replaces an earlier one
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::{JObject, JValue};
+    /// let mut scores = JObject::new();
+    /// scores.insert("ann".to_string(), json!("1"));
+    /// scores.insert("bo".to_string(), JValue::Null);
+    /// let present: JObject = scores.iter()
+    ///     .filter(|(_, v)| **v != JValue::Null)
+    ///     .map(|(k, v)| (k.to_uppercase(), v.clone()))
+    ///     .collect();
+    /// assert_eq!(json!("{\"ANN\": 1}"), JValue::Object(present));
+    /// ```
+    fn from_iter<I: IntoIterator<Item=(String, CowValue<'a>)>>(members: I) -> CowObject<'a> {
+        let mut object = CowObject::new();
+        object.extend(members);
+        object
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b CowObject<'a> {
+    type Item = (&'b String, &'b CowValue<'a>);
+    type IntoIter = Iter<'b, String, CowValue<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b mut CowObject<'a> {
+    type Item = (&'b String, &'b mut CowValue<'a>);
+    type IntoIter = IterMut<'b, String, CowValue<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+
+impl<'a> Display for CowObject<'a> {
+    /// Writes the members with their keys and string values escaped like [`serialize_string`]
+    /// escapes them, so quotes, backslashes and control characters can not be mistaken for the
+    /// structure of the object
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut result = String::new();
         for (s, v) in &self.value {
-            result.push_str(&s.to_string());
+            result.push_str(&serialize_string(s));
             result.push_str(" : ");
-            result.push_str(&format!("{}", v));
-            result.push_str(",");
+            result.push_str(&element_to_string(v));
+            result.push(',');
         }
         write!(f, "{{{}}}", result)
     }
 }
 
-impl PartialEq for JObject {
+impl<'a> PartialEq for CowObject<'a> {
     fn eq(&self, other: &Self) -> bool {
         self.value == other.value
     }
 }
 
-impl Serialize for JObject {
+impl<'a> Eq for CowObject<'a> {}
+
+impl<'a> PartialOrd for CowObject<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for CowObject<'a> {
+    /// Objects are ordered by their key-value pairs sorted by key, compared lexicographically.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sorted().cmp(&other.sorted())
+    }
+}
+
+impl<'a> Hash for CowObject<'a> {
+    /// Objects with the same key-value pairs hash the same, regardless of insertion order.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.size.hash(state);
+        for (k, v) in self.sorted() {
+            k.hash(state);
+            v.hash(state);
+        }
+    }
+}
+
+impl<'a> Serialize for CowObject<'a> {
     /// Creates a serialization of [`JObject`] data-structure as a JSON string with
     /// minimal whitespace characters.
     /// ```
@@ -171,15 +431,26 @@ impl Serialize for JObject {
         result.push('{');
         for (i, (s, v)) in self.value.iter().enumerate() {
             result.push_str(&serialize_string(s));
-            result.push_str(":");
+            result.push(':');
             result.push_str(&v.serialize());
             if i < self.size - 1 {
-                result.push_str(",");
+                result.push(',');
             }
         }
         result.push('}');
         result
     }
+
+    fn serialized_len(&self) -> usize {
+        let mut len = 2;
+        for (s, v) in &self.value {
+            len += serialized_string_len(s) + 1 + v.serialized_len();
+        }
+        if self.size > 1 {
+            len += self.size - 1;
+        }
+        len
+    }
 }
 
 
@@ -201,12 +472,36 @@ mod test {
         let k2 = "key2".to_string();
         assert_eq!(0, obj.len());
         obj.insert(k1.clone(), JValue::Boolean(true));
-        assert_eq!("{key1 : true,}".to_string(), obj.to_string());
+        assert_eq!("{\"key1\" : true,}".to_string(), obj.to_string());
         assert_eq!(1, obj.len());
         obj.insert(k2.clone(), JValue::Null);
         assert_eq!(2, obj.len());
     }
 
+    #[test]
+    fn test_display_escapes_keys() {
+        let mut obj = JObject::new();
+        obj.insert("a \"quoted\" \\ key\n\u{0}".to_string(), JValue::String("\"x\"".into()));
+        assert_eq!("{\"a \\\"quoted\\\" \\\\ key\\n\\u0000\" : \"\\\"x\\\"\",}", obj.to_string());
+        assert_eq!("{\"a \\\"quoted\\\" \\\\ key\\n\\u0000\":\"\\\"x\\\"\"}", obj.serialize());
+        assert_eq!(obj.serialize().len(), obj.serialized_len());
+        let parsed = crate::parser::parse(&obj.serialize()).unwrap();
+        assert_eq!(JValue::Object(obj), parsed);
+    }
+
+    #[test]
+    fn test_size_is_tracked() {
+        let mut obj = JObject::new();
+        obj.extend((0..10).map(|i| (i.to_string(), JValue::Boolean(i % 2 == 0))));
+        assert_eq!(10, obj.len());
+        obj.retain(|k, v| k != "0" && *v == JValue::Boolean(true));
+        assert_eq!(4, obj.len());
+        assert!(obj.contains_key(&"2".to_string()) && !obj.contains_key(&"0".to_string()));
+        obj.clear();
+        assert_eq!((0, true), (obj.len(), obj.is_empty()));
+        assert_eq!("{}", obj.serialize());
+    }
+
     #[test]
     fn test_remove() {
         let mut obj = JObject::new();
@@ -276,7 +571,17 @@ mod test {
         obj.insert(k1.clone(), JValue::Boolean(true));
         assert_eq!("{\"key1\":true}".to_string(), obj.serialize());
         obj.insert(k2.clone(), JValue::Boolean(false));
-        assert!("{\"key1\":true,\"key2\":false}".to_string() == obj.serialize()
-            || "{\"key2\":false,\"key1\":true}".to_string() == obj.serialize());
+        assert!("{\"key1\":true,\"key2\":false}" == obj.serialize()
+            || "{\"key2\":false,\"key1\":true}" == obj.serialize());
+    }
+
+    #[test]
+    fn test_serialized_len() {
+        let mut obj = JObject::new();
+        assert_eq!(2, obj.serialized_len());
+        obj.insert("key\n1".to_string(), JValue::Boolean(true));
+        assert_eq!(obj.serialize().len(), obj.serialized_len());
+        obj.insert("key2".to_string(), JValue::Array(vec![JValue::Null, JValue::Null]));
+        assert_eq!(obj.serialize().len(), obj.serialized_len());
     }
 }
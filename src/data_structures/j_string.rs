@@ -0,0 +1,400 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::borrow::{Borrow, Cow};
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use crate::serializer::{Serialize, serialize_string, serialized_string_len};
+
+/// The maximum length in bytes of a string that is stored inline.
+const INLINE_CAPACITY: usize = 22;
+
+/// An owned [`CowString`], the string of a [`JValue`](crate::data_structures::JValue)
+pub type JString = CowString<'static>;
+
+/// A string is a sequence of Unicode code points wrapped with quotation marks (U+0022).
+///
+/// Strings of up to 22 bytes are stored inline without a heap allocation, which covers most
+/// keys and values of typical API payloads. Longer strings are stored in a [`String`]. A string
+/// may also borrow the text of the document it was parsed from, as
+/// [`parse_borrowed`](crate::parser::parse_borrowed) does for strings without escapes, and
+/// becomes owned only when it is changed.
+///
+/// ```
+/// # use json::data_structures::JString;
+/// let s = JString::from("hello");
+/// assert_eq!("hello", s.as_str());
+/// assert!(s.is_inline());
+///
+/// let s = JString::from("a string that is too long to be stored inline");
+/// assert!(!s.is_inline());
+/// ```
+#[derive(Clone)]
+pub struct CowString<'a> {
+    repr: Repr<'a>,
+}
+
+#[derive(Clone)]
+enum Repr<'a> {
+    Inline { len: u8, buf: [u8; INLINE_CAPACITY] },
+    Heap(String),
+    Borrowed(&'a str),
+}
+
+impl<'a> CowString<'a> {
+    /// Creates a new empty JString
+    ///
+    /// ```
+    /// # use json::data_structures::JString;
+    /// let s = JString::new();
+    /// assert!(s.is_empty());
+    /// ```
+    pub fn new() -> CowString<'a> {
+        CowString { repr: Repr::Inline { len: 0, buf: [0; INLINE_CAPACITY] } }
+    }
+
+    /// Returns a string slice of the whole JString
+    pub fn as_str(&self) -> &str {
+        match &self.repr {
+            // SAFETY: the inline buffer is only ever filled from complete `str`s, so the first
+            // `len` bytes are valid UTF-8.
+            Repr::Inline { len, buf } => unsafe {
+                std::str::from_utf8_unchecked(&buf[..*len as usize])
+            },
+            Repr::Heap(s) => s.as_str(),
+            Repr::Borrowed(s) => s,
+        }
+    }
+
+    /// Creates a string borrowing `s`, without copying it
+    ///
+    /// ```
+    /// # use json::data_structures::CowString;
+    /// let text = String::from("a string that is too long to be stored inline");
+    /// let s = CowString::borrowed(&text);
+    /// assert!(s.is_borrowed());
+    /// assert_eq!(text, s);
+    /// ```
+    pub fn borrowed(s: &'a str) -> CowString<'a> {
+        CowString { repr: Repr::Borrowed(s) }
+    }
+
+    /// Returns `true` if the string borrows its text
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.repr, Repr::Borrowed(_))
+    }
+
+    /// Converts the string into one that owns its text, copying a borrowed text
+    ///
+    /// ```
+    /// # use json::data_structures::{CowString, JString};
+    /// let text = String::from("text");
+    /// let owned: JString = CowString::borrowed(&text).into_owned();
+    /// drop(text);
+    /// assert!(!owned.is_borrowed());
+    /// assert_eq!("text", owned);
+    /// ```
+    pub fn into_owned(self) -> CowString<'static> {
+        match self.repr {
+            Repr::Inline { len, buf } => CowString { repr: Repr::Inline { len, buf } },
+            Repr::Heap(s) => CowString { repr: Repr::Heap(s) },
+            Repr::Borrowed(s) => CowString::from(s),
+        }
+    }
+
+    /// Returns `true` if the string is stored inline, without a heap allocation
+    pub fn is_inline(&self) -> bool {
+        matches!(self.repr, Repr::Inline { .. })
+    }
+
+    /// Appends the given string slice to the end of this JString. The string moves to the
+    /// heap once it no longer fits inline, and a borrowed string is copied first.
+    ///
+    /// ```
+    /// # use json::data_structures::JString;
+    /// let mut s = JString::from("hello");
+    /// s.push_str(" world");
+    /// assert_eq!("hello world", s.as_str());
+    /// ```
+    pub fn push_str(&mut self, string: &str) {
+        let new_len = self.len() + string.len();
+        match &mut self.repr {
+            Repr::Inline { len, buf } if new_len <= INLINE_CAPACITY => {
+                buf[*len as usize..new_len].copy_from_slice(string.as_bytes());
+                *len = new_len as u8;
+            }
+            Repr::Inline { .. } | Repr::Borrowed(_) if new_len <= INLINE_CAPACITY => {
+                let mut buf = [0; INLINE_CAPACITY];
+                buf[..self.len()].copy_from_slice(self.as_bytes());
+                buf[self.len()..new_len].copy_from_slice(string.as_bytes());
+                self.repr = Repr::Inline { len: new_len as u8, buf };
+            }
+            Repr::Inline { .. } | Repr::Borrowed(_) => {
+                let mut s = String::with_capacity(new_len);
+                s.push_str(self.as_str());
+                s.push_str(string);
+                self.repr = Repr::Heap(s);
+            }
+            Repr::Heap(s) => s.push_str(string),
+        }
+    }
+
+    /// Appends the given [`char`] to the end of this JString.
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Converts the JString into a [`String`]
+    pub fn into_string(self) -> String {
+        match self.repr {
+            Repr::Inline { .. } | Repr::Borrowed(_) => self.as_str().to_string(),
+            Repr::Heap(s) => s,
+        }
+    }
+}
+
+impl Default for CowString<'_> {
+    fn default() -> Self {
+        CowString::new()
+    }
+}
+
+impl Deref for CowString<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for CowString<'_> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for CowString<'_> {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for CowString<'_> {
+    /// Copies `s`; see [`CowString::borrowed`] for a string borrowing it
+    fn from(s: &str) -> Self {
+        if s.len() <= INLINE_CAPACITY {
+            let mut buf = [0; INLINE_CAPACITY];
+            buf[..s.len()].copy_from_slice(s.as_bytes());
+            CowString { repr: Repr::Inline { len: s.len() as u8, buf } }
+        } else {
+            CowString { repr: Repr::Heap(s.to_string()) }
+        }
+    }
+}
+
+impl From<String> for CowString<'_> {
+    fn from(s: String) -> Self {
+        if s.len() <= INLINE_CAPACITY {
+            CowString::from(s.as_str())
+        } else {
+            CowString { repr: Repr::Heap(s) }
+        }
+    }
+}
+
+impl From<&String> for CowString<'_> {
+    fn from(s: &String) -> Self {
+        CowString::from(s.as_str())
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for CowString<'a> {
+    /// Borrows a borrowed text and takes an owned one
+    fn from(s: Cow<'a, str>) -> Self {
+        match s {
+            Cow::Borrowed(s) => CowString::borrowed(s),
+            Cow::Owned(s) => CowString::from(s),
+        }
+    }
+}
+
+impl From<CowString<'_>> for String {
+    fn from(s: CowString<'_>) -> Self {
+        s.into_string()
+    }
+}
+
+impl std::fmt::Debug for CowString<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl Display for CowString<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl PartialEq for CowString<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for CowString<'_> {}
+
+impl PartialEq<str> for CowString<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for CowString<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for CowString<'_> {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<CowString<'_>> for str {
+    fn eq(&self, other: &CowString<'_>) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<CowString<'_>> for &str {
+    fn eq(&self, other: &CowString<'_>) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<CowString<'_>> for String {
+    fn eq(&self, other: &CowString<'_>) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Hash for CowString<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl Serialize for CowString<'_> {
+    /// Creates a serialization of the [`JString`] data-structure as a JSON string with
+    /// minimal whitespace characters.
+    /// ```
+    /// # use json::data_structures::JString;
+    /// # use json::serializer::Serialize;
+    /// let j_string = JString::from("a \"quoted\" word");
+    /// assert_eq!(j_string.serialize(), "\"a \\\"quoted\\\" word\"")
+    /// ```
+    fn serialize(&self) -> String {
+        serialize_string(self.as_str())
+    }
+
+    fn serialized_len(&self) -> usize {
+        serialized_string_len(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use crate::data_structures::{CowString, JString};
+
+    #[test]
+    fn test_size() {
+        assert!(std::mem::size_of::<JString>()
+            <= std::mem::size_of::<String>() + std::mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn test_inline() {
+        let s = JString::from("");
+        assert!(s.is_inline());
+        assert!(s.is_empty());
+        let s = JString::from("exactly 22 bytes long!");
+        assert_eq!(22, s.len());
+        assert!(s.is_inline());
+        let s = JString::from("ünïcødé".to_string());
+        assert!(s.is_inline());
+        assert_eq!("ünïcødé", s.as_str());
+    }
+
+    #[test]
+    fn test_heap() {
+        let s = JString::from("twenty three bytes long");
+        assert!(!s.is_inline());
+        assert_eq!("twenty three bytes long", s.as_str());
+        assert_eq!("twenty three bytes long".to_string(), s.into_string());
+    }
+
+    #[test]
+    fn test_push() {
+        let mut s = JString::new();
+        s.push_str("exactly 21 bytes long");
+        s.push('!');
+        assert!(s.is_inline());
+        assert_eq!("exactly 21 bytes long!", s);
+        s.push('ü');
+        assert!(!s.is_inline());
+        assert_eq!("exactly 21 bytes long!ü", s);
+    }
+
+    #[test]
+    fn test_from_cow() {
+        let s = JString::from(Cow::Borrowed("borrowed"));
+        assert!(s.is_borrowed() && !s.is_inline());
+        let s = JString::from(Cow::Owned("a string that is too long to be stored inline".to_string()));
+        assert_eq!("a string that is too long to be stored inline", s);
+    }
+
+    #[test]
+    fn test_borrowed() {
+        let text = "a string that is too long to be stored inline".to_string();
+        let mut s = CowString::borrowed(&text[..5]);
+        assert_eq!("a str", s);
+        s.push_str("ing");
+        assert!(s.is_inline());
+        assert_eq!("a string", s);
+        let mut s = CowString::borrowed(&text);
+        assert_eq!(JString::from(text.as_str()), s);
+        s.push('!');
+        assert!(!s.is_borrowed() && !s.is_inline());
+        assert_eq!(format!("{}!", text), s.into_string());
+        let owned = CowString::borrowed(&text).into_owned();
+        drop(text);
+        assert_eq!("a string that is too long to be stored inline", owned);
+    }
+
+    #[test]
+    fn test_eq() {
+        let s1 = JString::from("a string that is too long to be stored inline");
+        let s2 = JString::from("a string that is too long to be stored inline".to_string());
+        assert_eq!(s1, s2);
+        assert_ne!(s1, JString::from("short"));
+        assert_eq!(JString::from("short"), "short");
+    }
+}
@@ -15,9 +15,17 @@
 // You should have received a copy of the GNU General Public License
 // along with json.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
-use crate::data_structures::{JNumber, JObject};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::Index;
+use crate::data_structures::{CowObject, CowString, JNumber};
 use crate::serializer::{Serialize, serialize_string};
+use crate::visitor::{Visitor, VisitorMut};
+
+/// An owned [`CowValue`], the value that [`parse`](crate::parser::parse) returns
+pub type JValue = CowValue<'static>;
 
 /// A value can be a string, or a number, or true or false or null, or an
 /// object or an array.
@@ -32,44 +40,304 @@ use crate::serializer::{Serialize, serialize_string};
 ///
 /// assert_ne!(b, n);
 /// ```
+///
+/// Values have a total order: values of different types are ordered
+/// `Null < Boolean < Number < String < Array < Object`, and values of the same type are
+/// compared by their content. Together with [`Hash`] this allows values to be used as keys of
+/// a [`BTreeMap`](std::collections::BTreeMap) or a [`HashSet`](std::collections::HashSet).
+///
+/// ```
+/// # use json::json;
+/// # use json::data_structures::JValue;
+/// let mut values = vec![json!("{}"), json!("[]"), json!("\"a\""), json!("1"), json!("true"),
+///                       JValue::Null];
+/// values.sort();
+/// assert_eq!(vec![JValue::Null, json!("true"), json!("1"), json!("\"a\""), json!("[]"),
+///                 json!("{}")], values);
+/// ```
+///
+/// The strings of a value may borrow the document it was parsed from, as the values that
+/// [`parse_borrowed`](crate::parser::parse_borrowed) returns do. Such a value is converted into
+/// a [`JValue`] with [`CowValue::into_owned`].
 #[derive(Debug, Clone)]
-pub enum JValue {
-    Object(JObject),
-    Array(Vec<JValue>),
-    String(String),
+pub enum CowValue<'a> {
+    Object(CowObject<'a>),
+    Array(Vec<CowValue<'a>>),
+    String(CowString<'a>),
     Number(JNumber),
     Boolean(bool),
     Null,
 }
 
-impl Display for JValue {
+impl JValue {
+    /// Calls the hook of the given [`Visitor`] that matches the type of this value.
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        match self {
+            JValue::Object(o) => visitor.visit_object(o),
+            JValue::Array(a) => visitor.visit_array(a),
+            JValue::String(s) => visitor.visit_string(s),
+            JValue::Number(n) => visitor.visit_number(n),
+            JValue::Boolean(b) => visitor.visit_boolean(*b),
+            JValue::Null => visitor.visit_null()
+        }
+    }
+
+    /// Calls [`VisitorMut::visit_value_mut`] and then the hook of the given [`VisitorMut`] that
+    /// matches the type of this value.
+    pub fn accept_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.visit_value_mut(self);
+        match self {
+            JValue::Object(o) => visitor.visit_object_mut(o),
+            JValue::Array(a) => visitor.visit_array_mut(a),
+            JValue::String(s) => visitor.visit_string_mut(s),
+            JValue::Number(n) => visitor.visit_number_mut(n),
+            JValue::Boolean(b) => visitor.visit_boolean_mut(b),
+            JValue::Null => visitor.visit_null_mut()
+        }
+    }
+}
+
+impl<'a> Display for CowValue<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            JValue::Object(o) => write!(f, "{}", o),
-            JValue::Array(a) => write!(f, "{}", array_to_string(a, false)),
-            JValue::String(s) => write!(f, "{}", s),
-            JValue::Number(n) => write!(f, "{}", n),
-            JValue::Boolean(b) => write!(f, "{}", b),
-            JValue::Null => write!(f, "null")
+            CowValue::Object(o) => write!(f, "{}", o),
+            CowValue::Array(a) => write!(f, "{}", array_to_string(a, false)),
+            CowValue::String(s) => write!(f, "{}", s),
+            CowValue::Number(n) => write!(f, "{}", n),
+            CowValue::Boolean(b) => write!(f, "{}", b),
+            CowValue::Null => write!(f, "null")
         }
     }
 }
 
-impl PartialEq for JValue {
+impl<'a> PartialEq for CowValue<'a> {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (JValue::Object(o1), JValue::Object(o2)) => o1 == o2,
-            (JValue::Array(a1), JValue::Array(a2)) => a1 == a2,
-            (JValue::String(s1), JValue::String(s2)) => s1 == s2,
-            (JValue::Number(n1), JValue::Number(n2)) => n1 == n2,
-            (JValue::Boolean(b1), JValue::Boolean(b2)) => b1 == b2,
-            (JValue::Null, JValue::Null) => true,
+            (CowValue::Object(o1), CowValue::Object(o2)) => o1 == o2,
+            (CowValue::Array(a1), CowValue::Array(a2)) => a1 == a2,
+            (CowValue::String(s1), CowValue::String(s2)) => s1 == s2,
+            (CowValue::Number(n1), CowValue::Number(n2)) => n1 == n2,
+            (CowValue::Boolean(b1), CowValue::Boolean(b2)) => b1 == b2,
+            (CowValue::Null, CowValue::Null) => true,
             _ => false
         }
     }
 }
 
-impl Serialize for JValue {
+impl<'a> Eq for CowValue<'a> {}
+
+impl<'a> CowValue<'a> {
+    /// An empty array, for initializing constants and statics
+    ///
+    /// ```
+    /// # use json::data_structures::JValue;
+    /// static NO_TAGS: JValue = JValue::EMPTY_ARRAY;
+    /// assert_eq!("[]", NO_TAGS.to_string());
+    /// ```
+    pub const EMPTY_ARRAY: CowValue<'a> = CowValue::Array(Vec::new());
+
+    /// Returns an empty object. Unlike [`JValue::EMPTY_ARRAY`] it is not a constant, as the map
+    /// of an object can not be created in a const context.
+    ///
+    /// ```
+    /// # use json::data_structures::JValue;
+    /// assert_eq!("{}", JValue::empty_object().to_string());
+    /// ```
+    pub fn empty_object() -> CowValue<'a> {
+        CowValue::Object(CowObject::new())
+    }
+
+    /// Converts the value into one that owns all of its strings
+    ///
+    /// ```
+    /// # use json::data_structures::{JString, JValue};
+    /// # use json::parser::parse_borrowed;
+    /// let text = String::from("{\"name\": \"borrowed\"}");
+    /// let owned: JValue = parse_borrowed(&text).unwrap().into_owned();
+    /// drop(text);
+    /// assert_eq!(JValue::String(JString::from("borrowed")), owned["name"]);
+    /// ```
+    pub fn into_owned(self) -> JValue {
+        match self {
+            CowValue::Object(o) => CowValue::Object(o.into_owned()),
+            CowValue::Array(a) => CowValue::Array(a.into_iter().map(CowValue::into_owned).collect()),
+            CowValue::String(s) => CowValue::String(s.into_owned()),
+            CowValue::Number(n) => CowValue::Number(n),
+            CowValue::Boolean(b) => CowValue::Boolean(b),
+            CowValue::Null => CowValue::Null
+        }
+    }
+
+    /// Takes the value out, leaving `null` in its place
+    ///
+    /// ```
+    /// # use json::json;
+    /// let mut j_value = json!("{\"a\": [1, 2]}");
+    /// let a = j_value.pointer_mut("/a").unwrap().take();
+    /// assert_eq!((json!("[1, 2]"), json!("{\"a\": null}")), (a, j_value));
+    /// ```
+    pub fn take(&mut self) -> CowValue<'a> {
+        std::mem::take(self)
+    }
+
+    /// Creates an empty array with room for at least `capacity` elements without reallocating
+    ///
+    /// ```
+    /// # use json::data_structures::JValue;
+    /// let mut array = JValue::array_with_capacity(2);
+    /// if let JValue::Array(elements) = &mut array {
+    ///     assert!(elements.capacity() >= 2);
+    ///     elements.push(JValue::Null);
+    /// }
+    /// assert_eq!("[null]", array.to_string());
+    /// ```
+    pub fn array_with_capacity(capacity: usize) -> CowValue<'a> {
+        CowValue::Array(Vec::with_capacity(capacity))
+    }
+
+    /// Creates an empty object with room for at least `capacity` members without reallocating
+    pub fn object_with_capacity(capacity: usize) -> CowValue<'a> {
+        CowValue::Object(CowObject::with_capacity(capacity))
+    }
+
+    /// Reserves room for at least `additional` more elements of an array or members of an
+    /// object. Does nothing for other values.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::JValue;
+    /// let mut j_value = json!("[1]");
+    /// j_value.reserve(10);
+    /// assert!(matches!(&j_value, JValue::Array(a) if a.capacity() >= 11));
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        match self {
+            CowValue::Array(a) => a.reserve(additional),
+            CowValue::Object(o) => o.reserve(additional),
+            _ => ()
+        }
+    }
+
+    /// The position of the type of this value in the total order
+    fn type_rank(&self) -> u8 {
+        match self {
+            CowValue::Null => 0,
+            CowValue::Boolean(_) => 1,
+            CowValue::Number(_) => 2,
+            CowValue::String(_) => 3,
+            CowValue::Array(_) => 4,
+            CowValue::Object(_) => 5
+        }
+    }
+}
+
+impl<'a> PartialOrd for CowValue<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for CowValue<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (CowValue::Object(o1), CowValue::Object(o2)) => o1.cmp(o2),
+            (CowValue::Array(a1), CowValue::Array(a2)) => a1.cmp(a2),
+            (CowValue::String(s1), CowValue::String(s2)) => s1.as_str().cmp(s2.as_str()),
+            (CowValue::Number(n1), CowValue::Number(n2)) => n1.cmp(n2),
+            (CowValue::Boolean(b1), CowValue::Boolean(b2)) => b1.cmp(b2),
+            _ => self.type_rank().cmp(&other.type_rank())
+        }
+    }
+}
+
+impl<'a> Hash for CowValue<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_rank().hash(state);
+        match self {
+            CowValue::Object(o) => o.hash(state),
+            CowValue::Array(a) => a.hash(state),
+            CowValue::String(s) => s.hash(state),
+            CowValue::Number(n) => n.hash(state),
+            CowValue::Boolean(b) => b.hash(state),
+            CowValue::Null => ()
+        }
+    }
+}
+
+impl<'a> Default for CowValue<'a> {
+    /// Returns `null`
+    ///
+    /// ```
+    /// # use json::data_structures::JValue;
+    /// #[derive(Default)]
+    /// struct Event {
+    ///     payload: JValue,
+    /// }
+    /// assert_eq!(JValue::Null, Event::default().payload);
+    /// ```
+    fn default() -> CowValue<'a> {
+        CowValue::Null
+    }
+}
+
+impl<'a> FromIterator<CowValue<'a>> for CowValue<'a> {
+    /// Collects the values into an array
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::{JNumber, JValue};
+    /// let squares: JValue = (1..4).map(|i| JValue::Number(JNumber::from_f64(f64::from(i * i)).unwrap())).collect();
+    /// assert_eq!(json!("[1, 4, 9]"), squares);
+    /// ```
+    fn from_iter<I: IntoIterator<Item=CowValue<'a>>>(values: I) -> CowValue<'a> {
+        CowValue::Array(values.into_iter().collect())
+    }
+}
+
+/// The value every missing member or element indexes to
+static NULL: JValue = JValue::Null;
+
+impl<'a> Index<&str> for CowValue<'a> {
+    type Output = CowValue<'a>;
+
+    /// Returns the member `key` of an object, or `null` if the value is not an object or has
+    /// no such member, so lookups can be chained.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("{\"a\": {\"b\": [true]}}");
+    /// assert_eq!(json!("[true]"), j_value["a"]["b"]);
+    /// assert_eq!(json!("null"), j_value["a"]["c"]["d"]);
+    /// ```
+    fn index(&self, key: &str) -> &CowValue<'a> {
+        match self {
+            CowValue::Object(o) => o.get(&key.to_string()).unwrap_or(&NULL),
+            _ => &NULL
+        }
+    }
+}
+
+impl<'a> Index<usize> for CowValue<'a> {
+    type Output = CowValue<'a>;
+
+    /// Returns the element `index` of an array, or `null` if the value is not an array or is
+    /// not that long.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("[{\"id\": 1}]");
+    /// assert_eq!(json!("1"), j_value[0]["id"]);
+    /// assert_eq!(json!("null"), j_value[1]["id"]);
+    /// ```
+    fn index(&self, index: usize) -> &CowValue<'a> {
+        match self {
+            CowValue::Array(a) => a.get(index).unwrap_or(&NULL),
+            _ => &NULL
+        }
+    }
+}
+
+impl<'a> Serialize for CowValue<'a> {
     /// Creates a serialization of the [`JValue`] data-structure as a JSON string with
     /// minimal whitespace characters.
     /// ```
@@ -81,37 +349,59 @@ impl Serialize for JValue {
     fn serialize(&self) -> String {
         let mut result = String::new();
         let s = match self {
-            JValue::Object(o) => o.serialize(),
-            JValue::Array(a) => array_to_string(a, true),
-            JValue::String(s) => serialize_string(s),
-            JValue::Number(n) => n.serialize(),
-            JValue::Boolean(b) => format!("{}", b),
-            JValue::Null => "null".to_string()
+            CowValue::Object(o) => o.serialize(),
+            CowValue::Array(a) => array_to_string(a, true),
+            CowValue::String(s) => s.serialize(),
+            CowValue::Number(n) => n.serialize(),
+            CowValue::Boolean(b) => format!("{}", b),
+            CowValue::Null => "null".to_string()
         };
         result.push_str(&s);
         result
     }
+
+    fn serialized_len(&self) -> usize {
+        match self {
+            CowValue::Object(o) => o.serialized_len(),
+            CowValue::Array(a) => {
+                let mut len = 2;
+                for v in a {
+                    len += v.serialized_len();
+                }
+                if a.len() > 1 {
+                    len += a.len() - 1;
+                }
+                len
+            }
+            CowValue::String(s) => s.serialized_len(),
+            CowValue::Number(n) => n.serialized_len(),
+            CowValue::Boolean(true) => 4,
+            CowValue::Boolean(false) => 5,
+            CowValue::Null => 4
+        }
+    }
 }
 
-fn array_to_string(array: &Vec<JValue>, serialize: bool) -> String {
+/// Displays an element of an array or object, with a string escaped and in quotes
+pub(crate) fn element_to_string(value: &CowValue<'_>) -> String {
+    match value {
+        CowValue::String(s) => serialize_string(s.as_str()),
+        _ => value.to_string()
+    }
+}
+
+fn array_to_string(array: &[CowValue<'_>], serialize: bool) -> String {
     let mut result = String::new();
     result.push('[');
     for (i, v) in array.iter().enumerate() {
         if serialize {
             result.push_str(&v.serialize());
         } else {
-            match v {
-                JValue::String(_) => {
-                    result.push('"');
-                    result.push_str(&v.to_string());
-                    result.push('"');
-                }
-                _ => result.push_str(&v.to_string())
-            }
+            result.push_str(&element_to_string(v));
         }
         if i < array.len() - 1 {
             if serialize {
-                result.push_str(",")
+                result.push(',')
             } else {
                 result.push_str(", ")
             }
@@ -124,7 +414,8 @@ fn array_to_string(array: &Vec<JValue>, serialize: bool) -> String {
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
-    use crate::data_structures::{JValue, JNumber, JObject};
+    use crate::data_structures::{JValue, JNumber, JObject, JString};
+    use crate::json;
     use crate::serializer::Serialize;
 
     #[test]
@@ -137,14 +428,14 @@ mod test {
         let k1 = "key1".to_string();
         obj.insert(k1, JValue::Null);
         let o3: JValue = JValue::Object(obj);
-        assert_eq!("{key1 : null,}".to_string(), o3.to_string());
+        assert_eq!("{\"key1\" : null,}".to_string(), o3.to_string());
         assert_ne!(o1, o3);
     }
 
     #[test]
     fn test_valid_array() {
         let a1: JValue = JValue::Array(vec![JValue::Boolean(true),
-                                            JValue::String("123".to_string()),
+                                            JValue::String(JString::from("123")),
                                             JValue::Number(JNumber::from_str("3.4e-3").unwrap())]);
         assert_eq!("[true, \"123\", 3.4e-3]".to_string(), a1.to_string());
         let a2: JValue = JValue::Array(vec![JValue::Boolean(true)]);
@@ -153,9 +444,9 @@ mod test {
 
     #[test]
     fn test_valid_j_string() {
-        let s1: JValue = JValue::String("Hello World!".to_string());
+        let s1: JValue = JValue::String(JString::from("Hello World!"));
         assert_eq!("Hello World!".to_string(), s1.to_string());
-        let s2: JValue = JValue::String("Hello World!".to_string());
+        let s2: JValue = JValue::String(JString::from("Hello World!"));
         assert_eq!(s1, s2);
     }
 
@@ -187,6 +478,33 @@ mod test {
         assert_eq!(x1, x2);
     }
 
+    #[test]
+    fn test_ord() {
+        assert!(json!("[1, 2]") < json!("[1, 3]"));
+        assert!(json!("[1, 2]") < json!("[1, 2, 0]"));
+        assert!(json!("{\"a\": 1}") < json!("{\"a\": 1, \"b\": 0}"));
+        assert!(json!("{\"a\": 2}") > json!("{\"a\": 1, \"b\": 0}"));
+        assert!(json!("\"abc\"") < json!("\"abd\""));
+        assert!(json!("false") < json!("true"));
+        assert!(json!("1000") < json!("\"0\""));
+    }
+
+    #[test]
+    fn test_collections() {
+        use std::collections::{BTreeMap, HashSet};
+        let mut set = HashSet::new();
+        set.insert(json!("{\"a\": 1, \"b\": [1.0]}"));
+        set.insert(json!("{\"b\": [1], \"a\": 1}"));
+        set.insert(json!("{\"b\": [1], \"a\": 2}"));
+        assert_eq!(2, set.len());
+
+        let mut map = BTreeMap::new();
+        map.insert(json!("[2]"), 2);
+        map.insert(json!("[1]"), 1);
+        map.insert(JValue::Null, 0);
+        assert_eq!(vec![0, 1, 2], map.values().copied().collect::<Vec<i32>>());
+    }
+
     #[test]
     fn test_serialization() {
         let mut v = JValue::Null;
@@ -214,13 +532,30 @@ mod test {
         assert_eq!("{\"key1\":null}".to_string(), obj.serialize());
         obj.insert(k2, JValue::Boolean(false));
         v = JValue::Object(obj);
-        assert!("{\"key1\":null,\"key2\":false}".to_string() == v.serialize()
-            || "{\"key2\":false,\"key1\":null}".to_string() == v.serialize());
+        assert!("{\"key1\":null,\"key2\":false}" == v.serialize()
+            || "{\"key2\":false,\"key1\":null}" == v.serialize());
 
         v = JValue::Number(JNumber::from_str("355.3").unwrap());
         assert_eq!("355.3".to_string(), v.serialize());
 
-        v = JValue::String("hello".to_string());
+        v = JValue::String(JString::from("hello"));
         assert_eq!("\"hello\"".to_string(), v.serialize());
     }
+
+    #[test]
+    fn test_serialized_len() {
+        let values = [
+            JValue::Null,
+            JValue::Boolean(true),
+            JValue::Boolean(false),
+            JValue::Array(vec![]),
+            JValue::Array(vec![JValue::Boolean(true), JValue::Null, JValue::Array(vec![])]),
+            JValue::Object(JObject::new()),
+            JValue::Number(JNumber::from_str("-355.3e+2").unwrap()),
+            JValue::String(JString::from("h\"e/l\\l\to ünïcødé")),
+        ];
+        for v in values.iter() {
+            assert_eq!(v.serialize().len(), v.serialized_len());
+        }
+    }
 }
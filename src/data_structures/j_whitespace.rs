@@ -76,6 +76,10 @@ impl Serialize for JWhitespace {
     fn serialize(&self) -> String {
         " ".to_string()
     }
+
+    fn serialized_len(&self) -> usize {
+        1
+    }
 }
 
 #[cfg(test)]
@@ -115,5 +119,6 @@ mod tests {
 
         ws = JWhitespace::new("  \n  \t ").unwrap();
         assert_eq!(" ".to_string(), ws.serialize());
+        assert_eq!(1, ws.serialized_len());
     }
 }
@@ -21,8 +21,20 @@ mod j_whitespace;
 mod j_number;
 mod j_value;
 mod j_object;
+mod j_string;
+mod arc_value;
+#[cfg(feature = "arena")]
+mod borrowed_value;
+#[cfg(feature = "persistent")]
+mod persistent_value;
 
 pub use j_whitespace::JWhitespace;
 pub use j_number::JNumber;
-pub use j_value::JValue;
-pub use j_object::JObject;
+pub use j_value::{CowValue, JValue};
+pub use j_object::{CowObject, JObject};
+pub use j_string::{CowString, JString};
+pub use arc_value::ArcValue;
+#[cfg(feature = "arena")]
+pub use borrowed_value::BorrowedValue;
+#[cfg(feature = "persistent")]
+pub use persistent_value::PersistentValue;
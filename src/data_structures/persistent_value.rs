@@ -0,0 +1,277 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::sync::Arc;
+
+use im::{HashMap, Vector};
+
+use crate::convert::describe;
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::{array_index, parse_pointer};
+
+/// An immutable JSON value whose objects are hash array mapped tries and whose arrays are
+/// persistent vectors.
+///
+/// Changing a value creates a new version and leaves the old one as it was. The new version
+/// shares every subtree that did not change with the old one, and only the nodes on the path to
+/// the change are copied, so a change costs about the depth of the document rather than its
+/// size and any number of versions can be kept and read from many threads.
+///
+/// ```
+/// # use json::json;
+/// # use json::data_structures::PersistentValue;
+/// let v1 = PersistentValue::from(&json!("{\"users\": {\"ann\": {\"role\": \"admin\"}}, \"log\": []}"));
+/// let v2 = v1.set_path("/users/bob", PersistentValue::from(&json!("{\"role\": \"dev\"}"))).unwrap();
+/// let v3 = v2.remove_path("/users/ann").unwrap();
+///
+/// assert_eq!(json!("{\"users\": {\"ann\": {\"role\": \"admin\"}}, \"log\": []}"), v1.to_value());
+/// assert_eq!(json!("{\"users\": {\"bob\": {\"role\": \"dev\"}}, \"log\": []}"), v3.to_value());
+/// assert!(v2.pointer("/users/bob").unwrap().ptr_eq(v3.pointer("/users/bob").unwrap()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum PersistentValue {
+    Object(HashMap<String, PersistentValue>),
+    Array(Vector<PersistentValue>),
+    String(Arc<str>),
+    Number(JNumber),
+    Boolean(bool),
+    Null,
+}
+
+impl PersistentValue {
+    /// Returns the value of the member with the given key if this is an object. Otherwise
+    /// returns [`None`].
+    pub fn get(&self, key: &str) -> Option<&PersistentValue> {
+        match self {
+            PersistentValue::Object(members) => members.get(key),
+            _ => None
+        }
+    }
+
+    /// Returns the element at the given index if this is an array. Otherwise returns [`None`].
+    pub fn index(&self, index: usize) -> Option<&PersistentValue> {
+        match self {
+            PersistentValue::Array(elements) => elements.get(index),
+            _ => None
+        }
+    }
+
+    /// Returns the value at the given JSON Pointer, like [`JValue::pointer`]
+    pub fn pointer(&self, pointer: &str) -> Option<&PersistentValue> {
+        let mut value = self;
+        for token in parse_pointer(pointer).ok()? {
+            value = match value {
+                PersistentValue::Object(_) => value.get(&token)?,
+                PersistentValue::Array(_) => value.index(array_index(&token)?)?,
+                _ => return None
+            };
+        }
+        Some(value)
+    }
+
+    /// Returns a version with the value at the given JSON Pointer set to `value`. A missing
+    /// object member is added, and the array index `-` or the length of the array appends to
+    /// it. The parent of the target must exist.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::PersistentValue;
+    /// let tags = PersistentValue::from(&json!("[\"a\"]"));
+    /// let appended = tags.set_path("/-", PersistentValue::from(&json!("\"b\""))).unwrap();
+    /// assert_eq!(json!("[\"a\", \"b\"]"), appended.to_value());
+    /// assert!(tags.set_path("/5", PersistentValue::Null).is_err());
+    /// ```
+    pub fn set_path(&self, pointer: &str, value: PersistentValue) -> Result<PersistentValue, JsonError> {
+        let tokens = parse_pointer(pointer)?;
+        set(self, &tokens, value, pointer)
+    }
+
+    /// Returns a version without the value at the given JSON Pointer
+    pub fn remove_path(&self, pointer: &str) -> Result<PersistentValue, JsonError> {
+        let tokens = parse_pointer(pointer)?;
+        if tokens.is_empty() {
+            return Err(JsonError::new(ErrorKind::InvalidPointer, "The root can not be removed"));
+        }
+        remove(self, &tokens, pointer)
+    }
+
+    /// Whether both values share the same object or string, or the same array once it is too
+    /// long to be stored inline
+    pub fn ptr_eq(&self, other: &PersistentValue) -> bool {
+        match (self, other) {
+            (PersistentValue::Object(a), PersistentValue::Object(b)) => a.ptr_eq(b),
+            (PersistentValue::Array(a), PersistentValue::Array(b)) => a.ptr_eq(b),
+            (PersistentValue::String(a), PersistentValue::String(b)) => Arc::ptr_eq(a, b),
+            _ => false
+        }
+    }
+
+    /// Creates an owned [`JValue`] copy of this value
+    pub fn to_value(&self) -> JValue {
+        match self {
+            PersistentValue::Object(members) => {
+                let mut obj = JObject::new();
+                for (k, v) in members.iter() {
+                    obj.insert(k.clone(), v.to_value());
+                }
+                JValue::Object(obj)
+            }
+            PersistentValue::Array(elements) => JValue::Array(elements.iter().map(|v| v.to_value()).collect()),
+            PersistentValue::String(s) => JValue::String(JString::from(&**s)),
+            PersistentValue::Number(n) => JValue::Number(n.clone()),
+            PersistentValue::Boolean(b) => JValue::Boolean(*b),
+            PersistentValue::Null => JValue::Null
+        }
+    }
+}
+
+fn set(node: &PersistentValue, tokens: &[String], value: PersistentValue, pointer: &str)
+       -> Result<PersistentValue, JsonError> {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return Ok(value)
+    };
+    match node {
+        PersistentValue::Object(members) if rest.is_empty() => {
+            Ok(PersistentValue::Object(members.update(token.clone(), value)))
+        }
+        PersistentValue::Object(members) => match members.get(token) {
+            Some(child) => Ok(PersistentValue::Object(members.update(token.clone(), set(child, rest, value, pointer)?))),
+            None => Err(no_parent(pointer))
+        },
+        PersistentValue::Array(elements) if rest.is_empty()
+            && (token == "-" || array_index(token) == Some(elements.len())) => {
+            let mut elements = elements.clone();
+            elements.push_back(value);
+            Ok(PersistentValue::Array(elements))
+        }
+        PersistentValue::Array(elements) => match array_index(token).filter(|i| *i < elements.len()) {
+            Some(i) if rest.is_empty() => Ok(PersistentValue::Array(elements.update(i, value))),
+            Some(i) => Ok(PersistentValue::Array(elements.update(i, set(&elements[i], rest, value, pointer)?))),
+            None if rest.is_empty() => Err(not_found(pointer)),
+            None => Err(no_parent(pointer))
+        },
+        _ if rest.is_empty() => Err(not_a_container(node, pointer)),
+        _ => Err(no_parent(pointer))
+    }
+}
+
+fn remove(node: &PersistentValue, tokens: &[String], pointer: &str) -> Result<PersistentValue, JsonError> {
+    let (token, rest) = tokens.split_first().unwrap();
+    match node {
+        PersistentValue::Object(members) => match members.get(token) {
+            Some(_) if rest.is_empty() => Ok(PersistentValue::Object(members.without(token))),
+            Some(child) => Ok(PersistentValue::Object(members.update(token.clone(), remove(child, rest, pointer)?))),
+            None => Err(not_found(pointer))
+        },
+        PersistentValue::Array(elements) => match array_index(token).filter(|i| *i < elements.len()) {
+            Some(i) if rest.is_empty() => {
+                let mut elements = elements.clone();
+                elements.remove(i);
+                Ok(PersistentValue::Array(elements))
+            }
+            Some(i) => Ok(PersistentValue::Array(elements.update(i, remove(&elements[i], rest, pointer)?))),
+            None => Err(not_found(pointer))
+        },
+        _ => Err(not_found(pointer))
+    }
+}
+
+fn not_found(pointer: &str) -> JsonError {
+    JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer))
+}
+
+fn no_parent(pointer: &str) -> JsonError {
+    JsonError::new(ErrorKind::NotFound, format!("No parent value for {}", pointer))
+}
+
+fn not_a_container(value: &PersistentValue, pointer: &str) -> JsonError {
+    JsonError::new(ErrorKind::InvalidType, format!("Expected an object or an array as the parent of {}, found {}",
+                                                   pointer, describe(&value.to_value())))
+}
+
+impl From<&JValue> for PersistentValue {
+    fn from(value: &JValue) -> Self {
+        match value {
+            JValue::Object(o) => PersistentValue::Object(
+                o.iter().map(|(k, v)| (k.clone(), PersistentValue::from(v))).collect()),
+            JValue::Array(a) => PersistentValue::Array(a.iter().map(PersistentValue::from).collect()),
+            JValue::String(s) => PersistentValue::String(Arc::from(s.as_str())),
+            JValue::Number(n) => PersistentValue::Number(n.clone()),
+            JValue::Boolean(b) => PersistentValue::Boolean(*b),
+            JValue::Null => PersistentValue::Null
+        }
+    }
+}
+
+impl From<JValue> for PersistentValue {
+    fn from(value: JValue) -> Self {
+        PersistentValue::from(&value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::data_structures::PersistentValue;
+    use crate::error::{ErrorKind, JsonError};
+    use crate::json;
+
+    #[test]
+    fn test_versions() {
+        let v1 = PersistentValue::from(json!("{\"a\": {\"b\": [1, 2]}, \"c\": {\"d\": true}}"));
+        let v2 = v1.set_path("/a/b/0", PersistentValue::Null).unwrap();
+        let v3 = v2.set_path("/a/b/2", PersistentValue::Boolean(false)).unwrap();
+        let v4 = v3.remove_path("/a/b/1").unwrap();
+        let v5 = v4.set_path("", PersistentValue::from(json!("[]"))).unwrap();
+
+        assert_eq!(json!("{\"a\": {\"b\": [1, 2]}, \"c\": {\"d\": true}}"), v1.to_value());
+        assert_eq!(json!("{\"a\": {\"b\": [null, 2]}, \"c\": {\"d\": true}}"), v2.to_value());
+        assert_eq!(json!("{\"a\": {\"b\": [null, 2, false]}, \"c\": {\"d\": true}}"), v3.to_value());
+        assert_eq!(json!("{\"a\": {\"b\": [null, false]}, \"c\": {\"d\": true}}"), v4.to_value());
+        assert_eq!(json!("[]"), v5.to_value());
+        assert!(v1.get("c").unwrap().ptr_eq(v4.get("c").unwrap()));
+        assert!(!v1.get("a").unwrap().ptr_eq(v2.get("a").unwrap()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let value = PersistentValue::from(json!("{\"a\": [1], \"s\": \"x\"}"));
+        let kind = |result: Result<PersistentValue, JsonError>| result.unwrap_err().kind();
+        assert_eq!(ErrorKind::NotFound, kind(value.set_path("/b/c", PersistentValue::Null)));
+        assert_eq!(ErrorKind::NotFound, kind(value.set_path("/a/3", PersistentValue::Null)));
+        assert_eq!(ErrorKind::InvalidType, kind(value.set_path("/s/t", PersistentValue::Null)));
+        assert_eq!(ErrorKind::InvalidPointer, kind(value.set_path("a", PersistentValue::Null)));
+        assert_eq!(ErrorKind::NotFound, kind(value.remove_path("/a/1")));
+        assert_eq!(ErrorKind::NotFound, kind(value.remove_path("/s/t")));
+        assert_eq!(ErrorKind::InvalidPointer, kind(value.remove_path("")));
+    }
+
+    #[test]
+    fn test_share_across_threads() {
+        let base = PersistentValue::from(json!("{\"counts\": [0, 0, 0, 0]}"));
+        let versions: Vec<PersistentValue> = (0..4).map(|i| {
+            let base = base.clone();
+            thread::spawn(move || base.set_path(&format!("/counts/{}", i), PersistentValue::from(json!("1"))).unwrap())
+        }).map(|handle| handle.join().unwrap()).collect();
+        assert_eq!(json!("{\"counts\": [0, 0, 1, 0]}"), versions[2].to_value());
+        assert_eq!(json!("{\"counts\": [0, 0, 0, 0]}"), base.to_value());
+    }
+}
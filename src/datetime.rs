@@ -0,0 +1,61 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Timestamps stored in JSON documents as [RFC 3339] strings, with [chrono].
+//!
+//! [RFC 3339]:https://datatracker.ietf.org/doc/html/rfc3339#section-5.6
+//! [chrono]:https://crates.io/crates/chrono
+
+use std::fmt::Display;
+
+use chrono::{DateTime, FixedOffset, SecondsFormat, TimeZone};
+
+use crate::data_structures::{JString, JValue};
+
+impl JValue {
+    /// Creates a string value holding `datetime` in RFC 3339 format, with as many fractional
+    /// digits as needed and `Z` for UTC.
+    ///
+    /// ```
+    /// # use chrono::{TimeZone, Utc};
+    /// # use json::data_structures::JValue;
+    /// let datetime = Utc.with_ymd_and_hms(2024, 2, 29, 13, 45, 0).unwrap();
+    /// assert_eq!(JValue::String("2024-02-29T13:45:00Z".into()), JValue::from_datetime(&datetime));
+    /// ```
+    pub fn from_datetime<Tz: TimeZone>(datetime: &DateTime<Tz>) -> JValue where Tz::Offset: Display {
+        JValue::String(JString::from(datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true)))
+    }
+
+    /// Parses a string value in RFC 3339 format, keeping its offset. Returns [`None`] for other
+    /// values and for strings that are not RFC 3339 date-times.
+    ///
+    /// ```
+    /// # use chrono::{TimeZone, Utc};
+    /// # use json::data_structures::JValue;
+    /// let j_value = JValue::String("2024-02-29T14:45:00.5+01:00".into());
+    /// let datetime = j_value.as_datetime().unwrap();
+    /// assert_eq!(3600, datetime.offset().local_minus_utc());
+    /// assert_eq!(Utc.with_ymd_and_hms(2024, 2, 29, 13, 45, 0).unwrap().timestamp(), datetime.timestamp());
+    /// assert_eq!(None, JValue::String("2024-02-29".into()).as_datetime());
+    /// ```
+    pub fn as_datetime(&self) -> Option<DateTime<FixedOffset>> {
+        match self {
+            JValue::String(s) => DateTime::parse_from_rfc3339(s).ok(),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Finding structurally equal documents.
+//!
+//! Documents are compared by their [canonical serialization](crate::canonical), so documents
+//! that differ only in key order, whitespace or number spelling are duplicates. A document with
+//! no canonical form, as it has a number out of the range of a double, is a duplicate of none.
+
+use std::collections::HashMap;
+
+use crate::data_structures::JValue;
+
+/// Groups the indices of the documents in `values` that are structurally equal. Only groups
+/// with more than one document are returned, in the order of their first documents, and the
+/// indices in a group are ascending.
+///
+/// ```
+/// # use json::json;
+/// # use json::dedup::find_duplicates;
+/// let values = [json!("{\"id\": 1, \"ok\": true}"), json!("[1]"), json!("{\"ok\": true, \"id\": 1.0}"),
+///               json!("[1.0]"), json!("null"), json!("[1]")];
+/// assert_eq!(vec![vec![0, 2], vec![1, 3, 5]], find_duplicates(&values));
+/// ```
+pub fn find_duplicates(values: &[JValue]) -> Vec<Vec<usize>> {
+    groups(values).into_iter().filter(|group| group.len() > 1).collect()
+}
+
+/// Returns the first of each group of structurally equal documents in `values`, in their
+/// original order.
+///
+/// ```
+/// # use json::json;
+/// # use json::dedup::unique;
+/// let values = [json!("{\"event\": 7}"), json!("{\"event\": 8}"), json!("{ \"event\" : 7 }")];
+/// assert_eq!(vec![json!("{\"event\": 7}"), json!("{\"event\": 8}")], unique(&values));
+/// ```
+pub fn unique(values: &[JValue]) -> Vec<JValue> {
+    groups(values).into_iter().map(|group| values[group[0]].clone()).collect()
+}
+
+/// Groups the indices of all documents in `values` by their canonical serializations
+fn groups(values: &[JValue]) -> Vec<Vec<usize>> {
+    let canonical: Vec<Option<String>> = values.iter().map(|v| v.to_canonical_string().ok()).collect();
+    let mut group_of: HashMap<&str, usize> = HashMap::with_capacity(values.len());
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (i, key) in canonical.iter().enumerate() {
+        match key.as_deref().map(|key| (key, group_of.get(key))) {
+            Some((_, Some(&group))) => groups[group].push(i),
+            Some((key, None)) => {
+                group_of.insert(key, groups.len());
+                groups.push(vec![i]);
+            }
+            None => groups.push(vec![i])
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dedup::{find_duplicates, unique};
+    use crate::json;
+
+    #[test]
+    fn test_find_duplicates() {
+        assert!(find_duplicates(&[]).is_empty());
+        assert!(find_duplicates(&[json!("1"), json!("\"1\""), json!("[1, 2]"), json!("[2, 1]")]).is_empty());
+        let values = [json!("{\"a\": {\"b\": [1e2, {}]}}"), json!("{\"a\": {\"b\": [100, {}]}}"), json!("{}"),
+                      json!("{ }")];
+        assert_eq!(vec![vec![0, 1], vec![2, 3]], find_duplicates(&values));
+        let values = [json!("[1e400]"), json!("[null]"), json!("[1e400]"), json!("[null]")];
+        assert_eq!(vec![vec![1, 3]], find_duplicates(&values));
+    }
+
+    #[test]
+    fn test_unique() {
+        assert!(unique(&[]).is_empty());
+        let values = [json!("null"), json!("true"), json!("null"), json!("false"), json!("true")];
+        assert_eq!(vec![json!("null"), json!("true"), json!("false")], unique(&values));
+    }
+}
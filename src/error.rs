@@ -0,0 +1,270 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The error type of the library
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::source_map::SourceMap;
+
+/// The category of a [`JsonError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The input is not valid JSON
+    Syntax,
+    /// A JSON Pointer is malformed
+    InvalidPointer,
+    /// No value exists at the requested location
+    NotFound,
+    /// A value exists but has a type that can not be converted to the requested one
+    InvalidType,
+    /// A schema document is malformed or uses an unsupported keyword
+    InvalidSchema,
+    /// A value does not satisfy a schema
+    Validation,
+    /// A signature does not match the signed content
+    InvalidSignature,
+    /// A file can not be read
+    Io,
+    /// References form a cycle
+    Cycle,
+    /// A body is not declared to be JSON by its content type
+    UnsupportedMediaType,
+    /// A body is in a charset that can not be read
+    UnsupportedCharset,
+    /// A location may not be read or written
+    PermissionDenied,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::Syntax => write!(f, "syntax"),
+            ErrorKind::InvalidPointer => write!(f, "invalid pointer"),
+            ErrorKind::NotFound => write!(f, "not found"),
+            ErrorKind::InvalidType => write!(f, "invalid type"),
+            ErrorKind::InvalidSchema => write!(f, "invalid schema"),
+            ErrorKind::Validation => write!(f, "validation"),
+            ErrorKind::InvalidSignature => write!(f, "invalid signature"),
+            ErrorKind::Io => write!(f, "io"),
+            ErrorKind::Cycle => write!(f, "cycle"),
+            ErrorKind::UnsupportedMediaType => write!(f, "unsupported media type"),
+            ErrorKind::UnsupportedCharset => write!(f, "unsupported charset"),
+            ErrorKind::PermissionDenied => write!(f, "permission denied"),
+        }
+    }
+}
+
+/// An error with a [`ErrorKind`] and a human readable message, optionally located in the
+/// source text it was found in and with a hint on how to fix it.
+///
+/// ```
+/// # use json::error::{ErrorKind, JsonError};
+/// let error = JsonError::new(ErrorKind::NotFound, "No value at /a");
+/// assert_eq!(ErrorKind::NotFound, error.kind());
+/// assert_eq!("No value at /a", error.to_string());
+/// ```
+///
+/// A located error displays the offending line with a caret under the column:
+///
+/// ```
+/// # use json::error::{ErrorKind, JsonError};
+/// let error = JsonError::new(ErrorKind::Syntax, "Invalid token: 2")
+///     .with_position("{\"a\": 1,\n \"b\" 2}", 14)
+///     .with_hint("expected ':'");
+/// assert_eq!((Some(2), Some(6)), (error.line(), error.column()));
+/// assert_eq!("Invalid token: 2 at line 2, column 6\n\
+///             2 |  \"b\" 2}\n\
+///            \x20 |      ^ expected ':'", error.to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+    kind: ErrorKind,
+    message: String,
+    position: Option<Position>,
+    hint: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+    /// The line containing the offset, shortened around it if it is long
+    snippet: String,
+    /// The column of the offset in the snippet
+    snippet_column: usize,
+}
+
+/// The number of chars of a long line shown on each side of the offset
+const SNIPPET_CONTEXT: usize = 40;
+
+impl Position {
+    fn new(map: &SourceMap, offset: usize) -> Position {
+        let offset = map.char_boundary(offset);
+        let (line, column) = map.line_column(offset);
+        let (snippet, snippet_column) = map.snippet(offset, SNIPPET_CONTEXT);
+        Position { offset, line, column, snippet, snippet_column }
+    }
+}
+
+impl JsonError {
+    /// Creates a new JsonError
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> JsonError {
+        JsonError { kind, message: message.into(), position: None, hint: None }
+    }
+
+    /// Locates the error at the byte `offset` of `source`, the text being processed
+    pub fn with_position(self, source: &str, offset: usize) -> JsonError {
+        self.with_source_map(&SourceMap::new(source), offset)
+    }
+
+    /// Locates the error at the byte `offset` of the source text of `map`, like
+    /// [`JsonError::with_position`] but without scanning the source again for each error
+    pub fn with_source_map(mut self, map: &SourceMap, offset: usize) -> JsonError {
+        self.position = Some(Position::new(map, offset));
+        self
+    }
+
+    /// Adds a hint on how to fix the error, such as `expected ',' or '}'`
+    pub fn with_hint<S: Into<String>>(mut self, hint: S) -> JsonError {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Returns the category of the error
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the message of the error
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the hint on how to fix the error
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    /// Returns the byte offset of the error in the source text
+    pub fn offset(&self) -> Option<usize> {
+        self.position.as_ref().map(|p| p.offset)
+    }
+
+    /// Returns the line of the error in the source text, starting at 1
+    pub fn line(&self) -> Option<usize> {
+        self.position.as_ref().map(|p| p.line)
+    }
+
+    /// Returns the column of the error in the source text, in chars and starting at 1
+    pub fn column(&self) -> Option<usize> {
+        self.position.as_ref().map(|p| p.column)
+    }
+
+    /// Returns the error as an object, for returning machine-readable errors to clients. It has
+    /// the members `kind` and `message`, `line`, `column` and `offset` if the error is located,
+    /// and `hint` if it has one.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::parser::parse;
+    /// let error = parse("{\"a\": 1,\n \"b\" 2}").unwrap_err();
+    /// let j_value = error.to_jvalue();
+    /// assert_eq!(json!("\"syntax\""), j_value["kind"]);
+    /// assert_eq!((json!("2"), json!("6")), (j_value["line"].clone(), j_value["column"].clone()));
+    /// assert_eq!(json!("\"expected ':'\""), j_value["hint"]);
+    /// ```
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = JObject::new();
+        let number = |n: usize| JValue::Number(JNumber::from_str(&n.to_string()).unwrap());
+        object.insert("kind".to_string(), JValue::String(JString::from(self.kind.to_string())));
+        object.insert("message".to_string(), JValue::String(JString::from(self.message.as_str())));
+        if let Some(position) = &self.position {
+            object.insert("line".to_string(), number(position.line));
+            object.insert("column".to_string(), number(position.column));
+            object.insert("offset".to_string(), number(position.offset));
+        }
+        if let Some(hint) = &self.hint {
+            object.insert("hint".to_string(), JValue::String(JString::from(hint.as_str())));
+        }
+        JValue::Object(object)
+    }
+}
+
+impl Display for JsonError {
+    /// Writes the message, followed by the location, the source line and the hint if known
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        match (&self.position, &self.hint) {
+            (Some(p), hint) => {
+                let gutter = p.line.to_string();
+                let indent: String = p.snippet.chars().take(p.snippet_column)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+                write!(f, " at line {}, column {}\n{} | {}\n{} | {}^", p.line, p.column, gutter, p.snippet,
+                       " ".repeat(gutter.len()), indent)?;
+                if let Some(hint) = hint {
+                    write!(f, " {}", hint)?;
+                }
+                Ok(())
+            }
+            (None, Some(hint)) => write!(f, " ({})", hint),
+            (None, None) => Ok(())
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{ErrorKind, JsonError};
+
+    #[test]
+    fn test_position() {
+        let error = JsonError::new(ErrorKind::Syntax, "x").with_position("ab\r\ncd", 8);
+        assert_eq!((Some(6), Some(2), Some(3)), (error.offset(), error.line(), error.column()));
+        assert_eq!("x at line 2, column 3\n2 | cd\n  |   ^", error.to_string());
+        let error = JsonError::new(ErrorKind::Syntax, "x").with_position("\t\u{e9}\u{e9}", 3);
+        assert_eq!(Some(3), error.column());
+        assert_eq!("x at line 1, column 3\n1 | \t\u{e9}\u{e9}\n  | \t ^", error.to_string());
+    }
+
+    #[test]
+    fn test_long_lines() {
+        let source = format!("[{}1]", "0, ".repeat(100));
+        let error = JsonError::new(ErrorKind::Syntax, "x").with_position(&source, 301).with_hint("h");
+        assert_eq!(Some(302), error.column());
+        let display = error.to_string();
+        let snippet = display.lines().nth(1).unwrap();
+        let caret = display.lines().nth(2).unwrap();
+        assert_eq!(format!("1 | ...{}1]", " 0,".repeat(13) + " "), snippet);
+        assert_eq!(caret.find('^'), snippet.find("1]"));
+        assert_eq!("x (h)", JsonError::new(ErrorKind::Syntax, "x").with_hint("h").to_string());
+    }
+
+    #[test]
+    fn test_to_jvalue() {
+        use crate::json;
+        let error = JsonError::new(ErrorKind::NotFound, "No value at /a");
+        assert_eq!(json!("{\"kind\": \"not found\", \"message\": \"No value at /a\"}"), error.to_jvalue());
+        let error = JsonError::new(ErrorKind::Syntax, "x").with_hint("z").with_position("ab", 1);
+        assert_eq!(json!("{\"kind\": \"syntax\", \"message\": \"x\", \"line\": 1, \"column\": 2, \"offset\": 1, \"hint\": \"z\"}"),
+                   error.to_jvalue());
+    }
+}
@@ -0,0 +1,480 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Rendering of JSON data-structures for humans, as HTML reports and Markdown tables, and for
+//! databases, as SQL `INSERT` statements and CSV for `COPY`.
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JObject, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::serializer::{serialize_string, Serialize};
+
+/// Options for [`to_html`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlOptions {
+    /// Write a complete page with this title instead of a fragment to embed in a page
+    pub title: Option<String>,
+    /// The arrays and objects nested less deeply than this are expanded initially
+    pub expanded_depth: usize,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        HtmlOptions { title: None, expanded_depth: 1 }
+    }
+}
+
+const STYLE: &str = "<style>\
+.json-tree{font-family:monospace;font-size:13px}\
+.json-tree ul{list-style:none;margin:0;padding-left:1.5em}\
+.json-tree summary{cursor:pointer}\
+.json-tree .key{color:#0451a5}.json-tree .string{color:#a31515}\
+.json-tree .number{color:#098658}.json-tree .boolean,.json-tree .null{color:#0000ff}\
+.json-tree .size{color:#888;font-style:italic}\
+</style>";
+
+const SCRIPT: &str = "<script>\
+function jsonTreeToggle(button,open){\
+button.parentNode.querySelectorAll('details').forEach(function(d){d.open=open;});}\
+</script>";
+
+/// Renders a value as a collapsible tree of nested `<details>` elements, with buttons to
+/// expand and collapse everything. The styles and the script are inline, so the result needs
+/// nothing else. Object members are ordered by key.
+///
+/// ```
+/// # use json::json;
+/// # use json::export::{to_html, HtmlOptions};
+/// let html = to_html(&json!("{\"user\": {\"name\": \"<b>\"}}"), &HtmlOptions::default());
+/// assert!(html.starts_with("<div class=\"json-tree\">"));
+/// assert!(html.contains("<span class=\"string\">&quot;&lt;b&gt;&quot;</span>"));
+///
+/// let page = to_html(&json!("[]"), &HtmlOptions { title: Some("Report".to_string()), ..HtmlOptions::default() });
+/// assert!(page.starts_with("<!DOCTYPE html>"));
+/// ```
+pub fn to_html(value: &JValue, options: &HtmlOptions) -> String {
+    let mut out = String::new();
+    if let Some(title) = &options.title {
+        out.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>");
+        escape(&mut out, title);
+        out.push_str("</title></head><body>\n");
+    }
+    out.push_str("<div class=\"json-tree\">");
+    out.push_str(STYLE);
+    out.push_str(SCRIPT);
+    out.push_str("<button onclick=\"jsonTreeToggle(this,true)\">Expand all</button>\
+                  <button onclick=\"jsonTreeToggle(this,false)\">Collapse all</button>\n");
+    node(&mut out, None, value, 0, options);
+    out.push_str("</div>\n");
+    if options.title.is_some() {
+        out.push_str("</body></html>\n");
+    }
+    out
+}
+
+fn escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c)
+        }
+    }
+}
+
+fn span(out: &mut String, class: &str, text: &str) {
+    out.push_str("<span class=\"");
+    out.push_str(class);
+    out.push_str("\">");
+    escape(out, text);
+    out.push_str("</span>");
+}
+
+fn label(out: &mut String, key: Option<&str>) {
+    if let Some(key) = key {
+        span(out, "key", &serialize_string(key));
+        out.push_str(": ");
+    }
+}
+
+/// The members or elements of a container, with the keys of the members
+type Children<'a> = Vec<(Option<&'a str>, &'a JValue)>;
+
+fn node(out: &mut String, key: Option<&str>, value: &JValue, depth: usize, options: &HtmlOptions) {
+    let (open, close, size, children): (&str, &str, String, Children) = match value {
+        JValue::Object(o) if !o.is_empty() => {
+            let mut members: Children = o.iter().map(|(k, v)| (Some(k.as_str()), v)).collect();
+            members.sort_by_key(|(k, _)| *k);
+            ("{", "}", format!("{} {}", o.len(), if o.len() == 1 { "key" } else { "keys" }), members)
+        }
+        JValue::Array(a) if !a.is_empty() => {
+            ("[", "]", format!("{} {}", a.len(), if a.len() == 1 { "item" } else { "items" }),
+             a.iter().map(|v| (None, v)).collect())
+        }
+        _ => {
+            label(out, key);
+            let class = match value {
+                JValue::String(_) => "string",
+                JValue::Number(_) => "number",
+                JValue::Boolean(_) => "boolean",
+                JValue::Null => "null",
+                _ => "empty"
+            };
+            span(out, class, &value.serialize());
+            return;
+        }
+    };
+    out.push_str(if depth < options.expanded_depth { "<details open><summary>" } else { "<details><summary>" });
+    label(out, key);
+    out.push_str(open);
+    out.push(' ');
+    span(out, "size", &size);
+    out.push_str("</summary><ul>\n");
+    for (key, child) in children {
+        out.push_str("<li>");
+        node(out, key, child, depth + 1, options);
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ul>");
+    out.push_str(close);
+    out.push_str("</details>");
+}
+
+/// Options for [`to_markdown_table`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableOptions {
+    /// The keys of the columns in order. By default every key of every object is a column, in
+    /// the order of the keys.
+    pub columns: Option<Vec<String>>,
+    /// The text of a cell whose value is null. A missing member is always an empty cell.
+    pub null: String,
+}
+
+/// Renders an array of objects as a GitHub-flavored Markdown table with one row per object.
+/// Strings are written without quotes, and arrays and objects as compact JSON.
+///
+/// ```
+/// # use json::json;
+/// # use json::export::{to_markdown_table, TableOptions};
+/// let users = json!("[{\"id\": 1, \"name\": \"ada\"}, {\"id\": 2, \"name\": null, \"tags\": [\"x\"]}]");
+/// assert_eq!("| id | name | tags |\n| --- | --- | --- |\n| 1 | ada |  |\n| 2 |  | [\"x\"] |\n",
+///            to_markdown_table(&users, &TableOptions::default()).unwrap());
+///
+/// let options = TableOptions { columns: Some(vec!["name".to_string()]), null: "-".to_string() };
+/// assert_eq!("| name |\n| --- |\n| ada |\n| - |\n", to_markdown_table(&users, &options).unwrap());
+/// ```
+pub fn to_markdown_table(value: &JValue, options: &TableOptions) -> Result<String, JsonError> {
+    let objects = objects(value)?;
+    let columns = columns(&objects, &options.columns);
+    let mut out = String::new();
+    let line = |out: &mut String, cells: &mut dyn Iterator<Item = String>| {
+        out.push('|');
+        for cell in cells {
+            out.push(' ');
+            out.push_str(&cell);
+            out.push_str(" |");
+        }
+        out.push('\n');
+    };
+    line(&mut out, &mut columns.iter().map(|c| cell_text(c)));
+    line(&mut out, &mut columns.iter().map(|_| "---".to_string()));
+    for object in objects {
+        line(&mut out, &mut columns.iter().map(|column| match object.get(column) {
+            None => String::new(),
+            Some(JValue::Null) => cell_text(&options.null),
+            Some(JValue::String(s)) => cell_text(s.as_str()),
+            Some(value) => cell_text(&value.serialize())
+        }));
+    }
+    Ok(out)
+}
+
+/// Escapes the pipes and line breaks that would end a cell
+fn cell_text(text: &str) -> String {
+    text.replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>")
+}
+
+/// How the parameters of a statement are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Placeholder {
+    /// `$1`, `$2`, ..., as in PostgreSQL
+    #[default]
+    Dollar,
+    /// `?`, as in MySQL and SQLite
+    Question,
+}
+
+/// Options for [`to_sql_inserts`] and [`to_sql_copy`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlOptions {
+    /// The keys of the columns in order. By default every key of every object is a column, in
+    /// the order of the keys.
+    pub columns: Option<Vec<String>>,
+    pub placeholder: Placeholder,
+    /// The number of rows inserted by each statement, 1 by default
+    pub rows_per_statement: usize,
+    /// Write `DEFAULT` for the columns a row has no member for, instead of a `NULL` parameter
+    pub missing_as_default: bool,
+}
+
+impl Default for SqlOptions {
+    fn default() -> Self {
+        SqlOptions { columns: None, placeholder: Placeholder::Dollar, rows_per_statement: 1, missing_as_default: false }
+    }
+}
+
+/// The value of a statement parameter, with the SQL type a JSON value maps to
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Boolean(bool),
+    /// An integer which fits a `BIGINT`
+    BigInt(i64),
+    /// Any other number, for a `DOUBLE PRECISION` column
+    Double(f64),
+    Text(String),
+    /// An array or an object as compact JSON, for a `JSON` or `JSONB` column
+    Json(String),
+}
+
+/// A statement with the values of its parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlStatement {
+    pub sql: String,
+    pub params: Vec<SqlValue>,
+}
+
+/// A `COPY ... FROM STDIN` statement with the CSV data to send with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlCopy {
+    pub statement: String,
+    pub data: String,
+}
+
+impl SqlValue {
+    fn from_jvalue(value: &JValue) -> SqlValue {
+        match value {
+            JValue::Null => SqlValue::Null,
+            JValue::Boolean(b) => SqlValue::Boolean(*b),
+            JValue::Number(n) => i64::from_jvalue(value).map_or(SqlValue::Double(n.get_f64_value()), SqlValue::BigInt),
+            JValue::String(s) => SqlValue::Text(s.to_string()),
+            JValue::Array(_) | JValue::Object(_) => SqlValue::Json(value.serialize())
+        }
+    }
+}
+
+/// Turns an array of objects into parameterized `INSERT` statements into `table`, with a
+/// column for each key. A row with no member for a column inserts `NULL`, or `DEFAULT` with
+/// [`SqlOptions::missing_as_default`]. Values map to parameters as [`SqlValue`] describes.
+///
+/// Identifiers are quoted with double quotes, and a `table` with a dot is a table of a schema.
+///
+/// ```
+/// # use json::json;
+/// # use json::export::{to_sql_inserts, SqlOptions, SqlValue};
+/// let rows = json!("[{\"id\": 1, \"name\": \"ada\"}, {\"id\": 2, \"tags\": [\"x\"]}]");
+/// let statements = to_sql_inserts(&rows, "public.users", &SqlOptions::default()).unwrap();
+/// assert_eq!("INSERT INTO \"public\".\"users\" (\"id\", \"name\", \"tags\") VALUES ($1, $2, $3)", statements[0].sql);
+/// assert_eq!(vec![SqlValue::BigInt(2), SqlValue::Null, SqlValue::Json("[\"x\"]".to_string())], statements[1].params);
+/// ```
+pub fn to_sql_inserts(value: &JValue, table: &str, options: &SqlOptions) -> Result<Vec<SqlStatement>, JsonError> {
+    let objects = objects(value)?;
+    let columns = columns(&objects, &options.columns);
+    let prefix = format!("INSERT INTO {} ({}) VALUES ", quote_table(table), quote_columns(&columns));
+    let mut statements = Vec::new();
+    for chunk in objects.chunks(options.rows_per_statement.max(1)) {
+        let mut sql = prefix.clone();
+        let mut params = Vec::new();
+        for (i, object) in chunk.iter().enumerate() {
+            sql.push_str(if i == 0 { "(" } else { ", (" });
+            for (j, column) in columns.iter().enumerate() {
+                if j > 0 {
+                    sql.push_str(", ");
+                }
+                match object.get(column) {
+                    None if options.missing_as_default => sql.push_str("DEFAULT"),
+                    value => {
+                        params.push(value.map_or(SqlValue::Null, SqlValue::from_jvalue));
+                        match options.placeholder {
+                            Placeholder::Dollar => sql.push_str(&format!("${}", params.len())),
+                            Placeholder::Question => sql.push('?')
+                        }
+                    }
+                }
+            }
+            sql.push(')');
+        }
+        statements.push(SqlStatement { sql, params });
+    }
+    Ok(statements)
+}
+
+/// Turns an array of objects into a PostgreSQL `COPY` statement into `table` and the rows as
+/// CSV data for it. A `null` or missing member is an unquoted empty field, which `COPY` reads as
+/// `NULL`; strings and JSON text are always quoted, so an empty string stays one.
+///
+/// ```
+/// # use json::json;
+/// # use json::export::{to_sql_copy, SqlOptions};
+/// let rows = json!("[{\"id\": 1, \"note\": \"say \\\"hi\\\"\"}, {\"id\": 2, \"note\": null}]");
+/// let copy = to_sql_copy(&rows, "notes", &SqlOptions::default()).unwrap();
+/// assert_eq!("COPY \"notes\" (\"id\", \"note\") FROM STDIN WITH (FORMAT csv)", copy.statement);
+/// assert_eq!("1,\"say \"\"hi\"\"\"\n2,\n", copy.data);
+/// ```
+pub fn to_sql_copy(value: &JValue, table: &str, options: &SqlOptions) -> Result<SqlCopy, JsonError> {
+    let objects = objects(value)?;
+    let columns = columns(&objects, &options.columns);
+    let statement = format!("COPY {} ({}) FROM STDIN WITH (FORMAT csv)", quote_table(table), quote_columns(&columns));
+    let mut data = String::new();
+    for object in objects {
+        for (j, column) in columns.iter().enumerate() {
+            if j > 0 {
+                data.push(',');
+            }
+            match object.get(column) {
+                None | Some(JValue::Null) => {}
+                Some(value @ (JValue::Boolean(_) | JValue::Number(_))) => data.push_str(&value.serialize()),
+                Some(JValue::String(s)) => quote_field(s.as_str(), &mut data),
+                Some(value) => quote_field(&value.serialize(), &mut data)
+            }
+        }
+        data.push('\n');
+    }
+    Ok(SqlCopy { statement, data })
+}
+
+fn quote_field(text: &str, data: &mut String) {
+    data.push('"');
+    data.push_str(&text.replace('"', "\"\""));
+    data.push('"');
+}
+
+fn objects(value: &JValue) -> Result<Vec<&JObject>, JsonError> {
+    let rows = match value {
+        JValue::Array(a) => a,
+        _ => return Err(JsonError::new(
+            ErrorKind::InvalidType, format!("Expected an array of objects, found {}", describe(value))))
+    };
+    rows.iter().enumerate().map(|(i, row)| match row {
+        JValue::Object(o) => Ok(o),
+        _ => Err(JsonError::new(ErrorKind::InvalidType, format!("Expected an object, found {} at /{}", describe(row), i)))
+    }).collect()
+}
+
+fn columns(objects: &[&JObject], columns: &Option<Vec<String>>) -> Vec<String> {
+    match columns {
+        Some(columns) => columns.clone(),
+        None => {
+            let mut keys: Vec<String> = objects.iter().flat_map(|o| o.iter().map(|(k, _)| k.clone())).collect();
+            keys.sort();
+            keys.dedup();
+            keys
+        }
+    }
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn quote_table(table: &str) -> String {
+    table.split('.').map(quote_identifier).collect::<Vec<_>>().join(".")
+}
+
+fn quote_columns(columns: &[String]) -> String {
+    columns.iter().map(|c| quote_identifier(c)).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::export::{to_html, to_markdown_table, to_sql_copy, to_sql_inserts, HtmlOptions, Placeholder,
+                        SqlOptions, SqlValue, TableOptions};
+    use crate::json;
+
+    #[test]
+    fn test_to_html() {
+        let html = to_html(&json!("{\"b\": [1, null], \"a\": {}, \"c\": []}"), &HtmlOptions::default());
+        let tree = &html[html.find("<details").unwrap()..];
+        assert_eq!("<details open><summary>{ <span class=\"size\">3 keys</span></summary><ul>\n\
+                    <li><span class=\"key\">&quot;a&quot;</span>: <span class=\"empty\">{}</span></li>\n\
+                    <li><details><summary><span class=\"key\">&quot;b&quot;</span>: [ <span class=\"size\">2 items</span></summary><ul>\n\
+                    <li><span class=\"number\">1</span></li>\n\
+                    <li><span class=\"null\">null</span></li>\n\
+                    </ul>]</details></li>\n\
+                    <li><span class=\"key\">&quot;c&quot;</span>: <span class=\"empty\">[]</span></li>\n\
+                    </ul>}</details></div>\n", tree);
+    }
+
+    #[test]
+    fn test_to_html_page() {
+        let options = HtmlOptions { title: Some("A & B".to_string()), expanded_depth: 0 };
+        let page = to_html(&json!("[true]"), &options);
+        assert!(page.contains("<title>A &amp; B</title>"));
+        assert!(page.contains("<details><summary>[ <span class=\"size\">1 item</span>"));
+        assert!(page.ends_with("</div>\n</body></html>\n"));
+        assert!(to_html(&json!("1"), &HtmlOptions::default()).contains("<span class=\"number\">1</span></div>"));
+    }
+
+    #[test]
+    fn test_to_markdown_table() {
+        let rows = json!("[{\"a|b\": \"x|y\", \"c\": {\"d\": true}}, {}]");
+        assert_eq!("| a\\|b | c |\n| --- | --- |\n| x\\|y | {\"d\":true} |\n|  |  |\n",
+                   to_markdown_table(&rows, &TableOptions::default()).unwrap());
+        assert_eq!("| x |\n| --- |\n", to_markdown_table(&json!("[]"), &TableOptions {
+            columns: Some(vec!["x".to_string()]), ..TableOptions::default()
+        }).unwrap());
+        assert_eq!("Expected an object, found the number 1 at /1",
+                   to_markdown_table(&json!("[{}, 1]"), &TableOptions::default()).unwrap_err().message());
+        assert_eq!(ErrorKind::InvalidType, to_markdown_table(&json!("{}"), &TableOptions::default()).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_to_sql_inserts() {
+        let rows = json!("[{\"a\": 1.5, \"b\": true}, {\"a\": 12345678901234567890, \"c\\\"\": {}}, {\"b\": \"x\"}]");
+        let options = SqlOptions { placeholder: Placeholder::Question, rows_per_statement: 2, missing_as_default: true,
+                                   ..SqlOptions::default() };
+        let statements = to_sql_inserts(&rows, "t", &options).unwrap();
+        assert_eq!(2, statements.len());
+        assert_eq!("INSERT INTO \"t\" (\"a\", \"b\", \"c\"\"\") VALUES (?, ?, DEFAULT), (?, DEFAULT, ?)", statements[0].sql);
+        assert_eq!(vec![SqlValue::Double(1.5), SqlValue::Boolean(true), SqlValue::Double(12345678901234567890.0),
+                        SqlValue::Json("{}".to_string())], statements[0].params);
+        assert_eq!("INSERT INTO \"t\" (\"a\", \"b\", \"c\"\"\") VALUES (DEFAULT, ?, DEFAULT)", statements[1].sql);
+        assert_eq!(vec![SqlValue::Text("x".to_string())], statements[1].params);
+
+        let options = SqlOptions { columns: Some(vec!["b".to_string()]), ..SqlOptions::default() };
+        let statements = to_sql_inserts(&rows, "t", &options).unwrap();
+        assert_eq!("INSERT INTO \"t\" (\"b\") VALUES ($1)", statements[2].sql);
+        assert_eq!(vec![SqlValue::Null], statements[1].params);
+        assert_eq!(Ok(vec![]), to_sql_inserts(&json!("[]"), "t", &SqlOptions::default()));
+        assert_eq!("Expected an object, found null at /0",
+                   to_sql_inserts(&json!("[null]"), "t", &SqlOptions::default()).unwrap_err().message());
+    }
+
+    #[test]
+    fn test_to_sql_copy() {
+        let rows = json!("[{\"a\": \"\", \"b\": [1, \"x,y\"]}, {\"a\": \"line\\nbreak\", \"b\": false}]");
+        let copy = to_sql_copy(&rows, "s.t", &SqlOptions::default()).unwrap();
+        assert_eq!("COPY \"s\".\"t\" (\"a\", \"b\") FROM STDIN WITH (FORMAT csv)", copy.statement);
+        assert_eq!("\"\",\"[1,\"\"x,y\"\"]\"\n\"line\nbreak\",false\n", copy.data);
+        assert_eq!(ErrorKind::InvalidType, to_sql_copy(&json!("{}"), "t", &SqlOptions::default()).unwrap_err().kind());
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Trimming documents with allow-lists and deny-lists of glob path patterns.
+//!
+//! A pattern is a dotted path such as `data.*.id`, where an array index is a segment like an
+//! object key. Within a segment `*` matches any characters and `?` any single character, and a
+//! segment `**` matches any number of segments, including none. A `.` in a key is written
+//! `\.`.
+
+use crate::data_structures::{JObject, JValue};
+use crate::glob::glob_match;
+
+fn split(pattern: &str) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('.') => segments.last_mut().unwrap().push('.'),
+                Some(c) => {
+                    let segment = segments.last_mut().unwrap();
+                    segment.push('\\');
+                    segment.push(c);
+                }
+                None => segments.last_mut().unwrap().push('\\')
+            },
+            '.' => segments.push(String::new()),
+            c => segments.last_mut().unwrap().push(c)
+        }
+    }
+    segments
+}
+
+/// Whether the path matches the pattern
+fn matches(pattern: &[String], path: &[String]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(segment) if segment == "**" =>
+            (0..=path.len()).any(|skipped| matches(&pattern[1..], &path[skipped..])),
+        Some(segment) => !path.is_empty() && glob_match(segment, &path[0]) && matches(&pattern[1..], &path[1..])
+    }
+}
+
+/// Whether a path below the path may match the pattern
+fn matches_below(pattern: &[String], path: &[String]) -> bool {
+    match (pattern.first(), path.first()) {
+        (Some(segment), _) if segment == "**" => true,
+        (Some(_), None) => true,
+        (Some(segment), Some(token)) => glob_match(segment, token) && matches_below(&pattern[1..], &path[1..]),
+        (None, _) => false
+    }
+}
+
+struct Patterns(Vec<Vec<String>>);
+
+impl Patterns {
+    fn new(patterns: &[&str]) -> Patterns {
+        Patterns(patterns.iter().map(|p| split(p)).collect())
+    }
+
+    fn matches(&self, path: &[String]) -> bool {
+        self.0.iter().any(|p| matches(p, path))
+    }
+
+    fn matches_below(&self, path: &[String]) -> bool {
+        self.0.iter().any(|p| matches_below(p, path))
+    }
+}
+
+/// Returns a copy of `value` with only the values whose path matches one of `patterns`, and the
+/// arrays and objects on the way to them. Arrays and objects that hold nothing kept are left
+/// out, so the indices of array elements may change. If nothing is kept the result is an empty
+/// array or object, or null if `value` is neither.
+///
+/// ```
+/// # use json::json;
+/// # use json::filter::keep;
+/// let j_value = json!("{\"data\": [{\"id\": 1, \"secret\": 2}, {\"id\": 3}], \"meta\": {\"page\": {\"n\": 1}}, \"x\": 0}");
+/// assert_eq!(json!("{\"data\": [{\"id\": 1}, {\"id\": 3}], \"meta\": {\"page\": {\"n\": 1}}}"),
+///            keep(&j_value, &["data.*.id", "meta.**"]));
+/// ```
+pub fn keep(value: &JValue, patterns: &[&str]) -> JValue {
+    let patterns = Patterns::new(patterns);
+    let mut path = Vec::new();
+    match keep_matching(value, &mut path, &patterns) {
+        Some(kept) => kept,
+        None => match value {
+            JValue::Object(_) => JValue::Object(JObject::new()),
+            JValue::Array(_) => JValue::Array(Vec::new()),
+            _ => JValue::Null
+        }
+    }
+}
+
+fn keep_matching(value: &JValue, path: &mut Vec<String>, patterns: &Patterns) -> Option<JValue> {
+    if patterns.matches(path) {
+        return Some(value.clone());
+    }
+    if !patterns.matches_below(path) {
+        return None;
+    }
+    match value {
+        JValue::Object(o) => {
+            let mut result = JObject::new();
+            for (key, member) in o.iter() {
+                path.push(key.clone());
+                if let Some(kept) = keep_matching(member, path, patterns) {
+                    result.insert(key.clone(), kept);
+                }
+                path.pop();
+            }
+            if !result.is_empty() { Some(JValue::Object(result)) } else { None }
+        }
+        JValue::Array(a) => {
+            let mut result = Vec::new();
+            for (i, element) in a.iter().enumerate() {
+                path.push(i.to_string());
+                if let Some(kept) = keep_matching(element, path, patterns) {
+                    result.push(kept);
+                }
+                path.pop();
+            }
+            if result.is_empty() { None } else { Some(JValue::Array(result)) }
+        }
+        _ => None
+    }
+}
+
+/// Returns a copy of `value` without the values whose path matches one of `patterns`. The
+/// value itself is never dropped.
+///
+/// ```
+/// # use json::json;
+/// # use json::filter::drop;
+/// let j_value = json!("{\"user\": {\"name\": \"ada\", \"password_hash\": \"x\"}, \"debug\": {\"trace\": []}}");
+/// assert_eq!(json!("{\"user\": {\"name\": \"ada\"}}"), drop(&j_value, &["**.password_*", "debug"]));
+/// ```
+pub fn drop(value: &JValue, patterns: &[&str]) -> JValue {
+    drop_matching(value, &mut Vec::new(), &Patterns::new(patterns))
+}
+
+fn drop_matching(value: &JValue, path: &mut Vec<String>, patterns: &Patterns) -> JValue {
+    if !patterns.matches_below(path) {
+        return value.clone();
+    }
+    let child = |path: &mut Vec<String>, token: String, child: &JValue| {
+        path.push(token);
+        let result = if patterns.matches(path) { None } else { Some(drop_matching(child, path, patterns)) };
+        path.pop();
+        result
+    };
+    match value {
+        JValue::Object(o) => {
+            let mut result = JObject::new();
+            for (key, member) in o.iter() {
+                if let Some(kept) = child(path, key.clone(), member) {
+                    result.insert(key.clone(), kept);
+                }
+            }
+            JValue::Object(result)
+        }
+        JValue::Array(a) => JValue::Array(a.iter().enumerate()
+            .filter_map(|(i, element)| child(path, i.to_string(), element)).collect()),
+        _ => value.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::{drop, keep, split};
+    use crate::json;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(vec!["a", "b.c", "*"], split("a.b\\.c.*"));
+        assert_eq!(vec![""], split(""));
+    }
+
+    #[test]
+    fn test_keep() {
+        let j_value = json!("{\"a\": [{\"b\": 1}, {\"c\": 2}, [3]], \"d.e\": {\"f\": true}, \"g\": {\"h\": {\"b\": 4}}}");
+        assert_eq!(json!("{\"a\": [{\"b\": 1}], \"g\": {\"h\": {\"b\": 4}}}"), keep(&j_value, &["**.b"]));
+        assert_eq!(json!("{\"a\": [[3]]}"), keep(&j_value, &["a.2"]));
+        assert_eq!(json!("{\"d.e\": {\"f\": true}}"), keep(&j_value, &["d\\.e"]));
+        assert_eq!(json!("{\"a\": [{\"c\": 2}]}"), keep(&j_value, &["?.*.c"]));
+        assert_eq!(j_value, keep(&j_value, &["**"]));
+        assert_eq!(json!("{}"), keep(&j_value, &["x"]));
+        assert_eq!(json!("null"), keep(&json!("1"), &["x"]));
+        assert_eq!(json!("{\"*ba\": 1}"), keep(&json!("{\"*ba\": 1}"), &["*a"]));
+    }
+
+    #[test]
+    fn test_drop() {
+        let j_value = json!("{\"a\": [{\"b\": 1}, {\"c\": 2}], \"b\": 3, \"d\": {\"b\": [4]}}");
+        assert_eq!(json!("{\"a\": [{}, {\"c\": 2}], \"d\": {}}"), drop(&j_value, &["**.b"]));
+        assert_eq!(json!("{\"a\": [{\"c\": 2}], \"b\": 3, \"d\": {\"b\": [4]}}"), drop(&j_value, &["a.0"]));
+        assert_eq!(json!("{}"), drop(&j_value, &["**"]));
+        assert_eq!(j_value, drop(&j_value, &[]));
+    }
+}
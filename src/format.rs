@@ -0,0 +1,726 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Validators for the string formats of JSON Schema's `format` keyword, and human-readable
+//! formatting of values.
+
+use std::io::{BufWriter, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::ParseOptions;
+use crate::serializer::{serialize_string, Serialize};
+use crate::stream::Scanner;
+
+/// Options for [`to_pretty_string`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrettyOptions {
+    /// The number of spaces per level of nesting
+    pub indent: usize,
+    /// Write the members of objects ordered by key instead of in no particular order
+    pub sort_keys: bool,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions { indent: 2, sort_keys: true }
+    }
+}
+
+/// The ANSI escape sequences [`to_colored_string`] writes before each kind of token. Every
+/// colored token is followed by a reset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub key: &'static str,
+    pub string: &'static str,
+    pub number: &'static str,
+    pub boolean: &'static str,
+    pub null: &'static str,
+    /// Brackets, braces, colons and commas
+    pub punctuation: &'static str,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            key: "\x1b[34m",
+            string: "\x1b[32m",
+            number: "\x1b[36m",
+            boolean: "\x1b[35m",
+            null: "\x1b[2m",
+            punctuation: "",
+        }
+    }
+}
+
+impl Theme {
+    /// A theme without colors, for output that is not a terminal
+    pub fn none() -> Theme {
+        Theme { key: "", string: "", number: "", boolean: "", null: "", punctuation: "" }
+    }
+
+    /// The default theme, or no colors if the `NO_COLOR` environment variable is set
+    pub fn from_env() -> Theme {
+        if std::env::var_os("NO_COLOR").is_some() {
+            Theme::none()
+        } else {
+            Theme::default()
+        }
+    }
+}
+
+/// Serializes a value with one array element or object member per line.
+///
+/// ```
+/// # use json::json;
+/// # use json::format::{to_pretty_string, PrettyOptions};
+/// let j_value = json!("{\"b\": [1, {}], \"a\": \"x\"}");
+/// assert_eq!("{\n  \"a\": \"x\",\n  \"b\": [\n    1,\n    {}\n  ]\n}",
+///            to_pretty_string(&j_value, &PrettyOptions::default()));
+/// ```
+pub fn to_pretty_string(value: &JValue, options: &PrettyOptions) -> String {
+    let mut printer = Printer { options, theme: &Theme::none(), out: String::new() };
+    printer.value(value, 0);
+    printer.out
+}
+
+/// Serializes a value like [`to_pretty_string`] with the default [`PrettyOptions`], coloring
+/// the tokens with ANSI escape sequences for a terminal. [`Theme::none`] writes no colors.
+///
+/// ```
+/// # use json::json;
+/// # use json::format::{to_colored_string, to_pretty_string, Theme};
+/// let j_value = json!("{\"ok\": true}");
+/// assert_eq!("{\n  \x1b[34m\"ok\"\x1b[0m: \x1b[35mtrue\x1b[0m\n}",
+///            to_colored_string(&j_value, &Theme::default()));
+/// assert_eq!(to_pretty_string(&j_value, &Default::default()), to_colored_string(&j_value, &Theme::none()));
+/// ```
+pub fn to_colored_string(value: &JValue, theme: &Theme) -> String {
+    let mut printer = Printer { options: &PrettyOptions::default(), theme, out: String::new() };
+    printer.value(value, 0);
+    printer.out
+}
+
+/// Copies the document read from `reader` to `writer` without whitespace, token by token, so
+/// that documents of any size are minified in constant memory. Strings and numbers are copied
+/// as they are written in the document, and only one of them is held in memory at a time.
+/// Documents nested deeper than [`ParseOptions::max_depth`] levels are refused, as they are by
+/// the parser.
+///
+/// ```
+/// # use json::format::minify_stream;
+/// let mut out = Vec::new();
+/// minify_stream("{ \"a\" : [1, 2.50, \"\\u0041\"] }\n".as_bytes(), &mut out).unwrap();
+/// assert_eq!("{\"a\":[1,2.50,\"\\u0041\"]}", String::from_utf8(out).unwrap());
+/// ```
+pub fn minify_stream<R: Read, W: Write>(reader: R, writer: W) -> Result<(), JsonError> {
+    StreamPrinter { writer: BufWriter::new(writer), indent: None }.print(reader)
+}
+
+/// Copies the document read from `reader` to `writer` formatted like [`to_pretty_string`],
+/// token by token, so that documents of any size are formatted in constant memory. The members
+/// of objects are written in document order, as sorting them would require holding the
+/// objects in memory; [`PrettyOptions::sort_keys`] is ignored. The nesting depth is limited
+/// as in [`minify_stream`].
+///
+/// ```
+/// # use json::format::{pretty_stream, PrettyOptions};
+/// let mut out = Vec::new();
+/// pretty_stream("{\"b\":[1,{}],\"a\":\"x\"}".as_bytes(), &mut out, &PrettyOptions::default()).unwrap();
+/// assert_eq!("{\n  \"b\": [\n    1,\n    {}\n  ],\n  \"a\": \"x\"\n}", String::from_utf8(out).unwrap());
+/// ```
+pub fn pretty_stream<R: Read, W: Write>(reader: R, writer: W, options: &PrettyOptions)
+                                        -> Result<(), JsonError> {
+    StreamPrinter { writer: BufWriter::new(writer), indent: Some(options.indent) }.print(reader)
+}
+
+struct StreamPrinter<W: Write> {
+    writer: BufWriter<W>,
+    /// The number of spaces per level of nesting, or [`None`] to minify
+    indent: Option<usize>,
+}
+
+impl<W: Write> StreamPrinter<W> {
+    fn print<R: Read>(mut self, reader: R) -> Result<(), JsonError> {
+        let mut scanner = Scanner::new(reader);
+        self.value(&mut scanner, 0)?;
+        scanner.end_of_document()?;
+        self.writer.flush().map_err(write_error)
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), JsonError> {
+        self.writer.write_all(text.as_bytes()).map_err(write_error)
+    }
+
+    fn newline(&mut self, depth: usize) -> Result<(), JsonError> {
+        if let Some(indent) = self.indent {
+            self.write("\n")?;
+            for _ in 0..depth * indent {
+                self.write(" ")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn value<R: Read>(&mut self, scanner: &mut Scanner<R>, depth: usize) -> Result<(), JsonError> {
+        scanner.skip_whitespace()?;
+        let (open, close) = match scanner.peek()? {
+            Some(b'{') => (b'{', b'}'),
+            Some(b'[') => (b'[', b']'),
+            _ => {
+                let (text, _) = scanner.parse_value()?;
+                return self.write(&text);
+            }
+        };
+        let max_depth = ParseOptions::default().max_depth;
+        if depth == max_depth {
+            return Err(JsonError::new(ErrorKind::Syntax, format!(
+                "The value is nested deeper than the limit of {} levels at byte {}", max_depth, scanner.offset())));
+        }
+        scanner.bump()?;
+        scanner.skip_whitespace()?;
+        self.write(if open == b'{' { "{" } else { "[" })?;
+        if scanner.peek()? == Some(close) {
+            scanner.bump()?;
+            return self.write(if open == b'{' { "}" } else { "]" });
+        }
+        loop {
+            self.newline(depth + 1)?;
+            if open == b'{' {
+                scanner.skip_whitespace()?;
+                if scanner.peek()? != Some(b'"') {
+                    let b = scanner.next()?;
+                    return Err(scanner.unexpected(b));
+                }
+                let (key, _) = scanner.parse_value()?;
+                self.write(&key)?;
+                scanner.skip_whitespace()?;
+                scanner.expect(b':')?;
+                self.write(if self.indent.is_some() { ": " } else { ":" })?;
+            }
+            self.value(scanner, depth + 1)?;
+            scanner.skip_whitespace()?;
+            match scanner.next()? {
+                b',' => self.write(",")?,
+                b if b == close => break,
+                b => return Err(scanner.unexpected(b))
+            }
+        }
+        self.newline(depth)?;
+        self.write(if open == b'{' { "}" } else { "]" })
+    }
+}
+
+fn write_error(e: std::io::Error) -> JsonError {
+    JsonError::new(ErrorKind::Io, format!("Can not write the document: {}", e))
+}
+
+struct Printer<'a> {
+    options: &'a PrettyOptions,
+    theme: &'a Theme,
+    out: String,
+}
+
+impl Printer<'_> {
+    fn token(&mut self, color: &str, token: &str) {
+        if color.is_empty() {
+            self.out.push_str(token);
+        } else {
+            self.out.push_str(color);
+            self.out.push_str(token);
+            self.out.push_str("\x1b[0m");
+        }
+    }
+
+    fn newline(&mut self, depth: usize) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', depth * self.options.indent));
+    }
+
+    fn value(&mut self, value: &JValue, depth: usize) {
+        let punctuation = self.theme.punctuation;
+        match value {
+            JValue::Object(o) if !o.is_empty() => {
+                let mut members: Vec<_> = o.iter().collect();
+                if self.options.sort_keys {
+                    members.sort_by_key(|(key, _)| *key);
+                }
+                self.token(punctuation, "{");
+                for (i, (key, member)) in members.into_iter().enumerate() {
+                    if i > 0 {
+                        self.token(punctuation, ",");
+                    }
+                    self.newline(depth + 1);
+                    self.token(self.theme.key, &serialize_string(key));
+                    self.token(punctuation, ":");
+                    self.out.push(' ');
+                    self.value(member, depth + 1);
+                }
+                self.newline(depth);
+                self.token(punctuation, "}");
+            }
+            JValue::Array(a) if !a.is_empty() => {
+                self.token(punctuation, "[");
+                for (i, element) in a.iter().enumerate() {
+                    if i > 0 {
+                        self.token(punctuation, ",");
+                    }
+                    self.newline(depth + 1);
+                    self.value(element, depth + 1);
+                }
+                self.newline(depth);
+                self.token(punctuation, "]");
+            }
+            JValue::Object(_) | JValue::Array(_) => self.token(punctuation, &value.serialize()),
+            JValue::String(_) => self.token(self.theme.string, &value.serialize()),
+            JValue::Number(_) => self.token(self.theme.number, &value.serialize()),
+            JValue::Boolean(_) => self.token(self.theme.boolean, &value.serialize()),
+            JValue::Null => self.token(self.theme.null, "null")
+        }
+    }
+}
+
+/// Marks the elided part of a truncated value
+const ELISION: &str = "…";
+
+impl JValue {
+    /// Returns a copy of the value whose serialization fits in `max_bytes`, for logging.
+    /// Long strings keep their beginning followed by the number of elided bytes, as in
+    /// `"abcd…(+3021 bytes)"`. Long arrays keep their first elements followed by `"…"`, objects
+    /// with many members keep the first keys in sorted order and a `"…"` member, and containers
+    /// nested too deep are replaced by `"…"`. The limits are tightened until the value fits; if
+    /// nothing but `"…"` is left it is returned even if `max_bytes` is smaller.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::serializer::Serialize;
+    /// let j_value = json!("{\"id\": 7, \"body\": \"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\",\
+    ///                        \"items\": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]}");
+    /// let truncated = j_value.truncate_for_log(80);
+    /// assert!(truncated.serialized_len() <= 80);
+    /// assert_eq!(json!("{\"id\": 7, \"body\": \"aaaaaaaaaa…(+47 bytes)\",\
+    ///                   \"items\": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, \"…\"]}"),
+    ///            truncated);
+    /// ```
+    pub fn truncate_for_log(&self, max_bytes: usize) -> JValue {
+        if self.serialized_len() <= max_bytes {
+            return self.clone();
+        }
+        let mut limits = Limits { string: max_bytes, elements: max_bytes / 2, members: max_bytes / 2, depth: depth(self) };
+        loop {
+            let truncated = limits.apply(self, 0);
+            if truncated.serialized_len() <= max_bytes || (limits.string, limits.members, limits.depth) == (0, 0, 0) {
+                return truncated;
+            }
+            // Strings go first, then long arrays, then nesting, and keys last
+            if limits.string > 16 {
+                limits.string /= 2;
+            } else if limits.elements > 4 {
+                limits.elements /= 2;
+            } else if limits.depth > 1 {
+                limits.depth -= 1;
+            } else if limits.string > 0 || limits.members > 0 {
+                limits.string /= 2;
+                limits.elements /= 2;
+                limits.members /= 2;
+            } else {
+                limits.depth = 0;
+            }
+        }
+    }
+}
+
+/// The limits [`JValue::truncate_for_log`] applies: the longest string in bytes, the most
+/// elements of an array and members of an object, and the deepest container
+struct Limits {
+    string: usize,
+    elements: usize,
+    members: usize,
+    depth: usize,
+}
+
+impl Limits {
+    fn apply(&self, value: &JValue, depth: usize) -> JValue {
+        match value {
+            JValue::Array(_) | JValue::Object(_) if depth >= self.depth => JValue::String(JString::from(ELISION)),
+            JValue::String(s) if s.as_str().len() > self.string => {
+                let s = s.as_str();
+                let mut end = self.string;
+                while !s.is_char_boundary(end) {
+                    end -= 1;
+                }
+                JValue::String(JString::from(format!("{}{}(+{} bytes)", &s[..end], ELISION, s.len() - end)))
+            }
+            JValue::Array(elements) => {
+                let mut truncated: Vec<JValue> =
+                    elements.iter().take(self.elements).map(|e| self.apply(e, depth + 1)).collect();
+                if elements.len() > self.elements {
+                    truncated.push(JValue::String(JString::from(ELISION)));
+                }
+                JValue::Array(truncated)
+            }
+            JValue::Object(object) => {
+                let mut members: Vec<(&String, &JValue)> = object.iter().collect();
+                members.sort_by_key(|(key, _)| *key);
+                let mut truncated = JObject::new();
+                for (key, member) in members.iter().take(self.members) {
+                    truncated.insert(key.to_string(), self.apply(member, depth + 1));
+                }
+                if members.len() > self.members {
+                    truncated.insert(ELISION.to_string(), JValue::String(JString::from(ELISION)));
+                }
+                JValue::Object(truncated)
+            }
+            _ => value.clone()
+        }
+    }
+}
+
+/// The number of containers nested in `value`, itself included
+fn depth(value: &JValue) -> usize {
+    match value {
+        JValue::Array(a) => 1 + a.iter().map(depth).max().unwrap_or(0),
+        JValue::Object(o) => 1 + o.iter().map(|(_, v)| depth(v)).max().unwrap_or(0),
+        _ => 0
+    }
+}
+
+/// Returns `true` if `s` is an [RFC 3339] date-time such as `2024-02-29T13:45:00.5+01:00`.
+/// The `T` and `Z` may be lowercase, and a leap second `60` is accepted.
+///
+/// [RFC 3339]:https://datatracker.ietf.org/doc/html/rfc3339#section-5.6
+///
+/// ```
+/// # use json::format::is_date_time;
+/// assert!(is_date_time("2024-02-29T13:45:00Z"));
+/// assert!(is_date_time("1990-12-31t23:59:60.123-08:00"));
+/// assert!(!is_date_time("2023-02-29T13:45:00Z"));
+/// assert!(!is_date_time("2024-02-29 13:45:00"));
+/// ```
+pub fn is_date_time(s: &str) -> bool {
+    let b = s.as_bytes();
+    if b.len() < 20 || !(b[10] == b'T' || b[10] == b't') {
+        return false;
+    }
+    date(&b[..10]) && time(&b[11..])
+}
+
+/// Returns `true` if `s` is an RFC 3339 full-date such as `2024-02-29`
+pub fn is_date(s: &str) -> bool {
+    date(s.as_bytes())
+}
+
+/// Returns `true` if `s` is an RFC 3339 full-time such as `13:45:00.5+01:00`
+pub fn is_time(s: &str) -> bool {
+    time(s.as_bytes())
+}
+
+/// Returns `true` if `s` is a [RFC 4122] UUID in the hyphenated form
+/// `8-4-4-4-12` of hexadecimal digits, in either case.
+///
+/// [RFC 4122]:https://datatracker.ietf.org/doc/html/rfc4122#section-3
+///
+/// ```
+/// # use json::format::is_uuid;
+/// assert!(is_uuid("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+/// assert!(is_uuid("67E55044-10B1-426F-9247-BB680E5FE0C8"));
+/// assert!(!is_uuid("67e5504410b1426f9247bb680e5fe0c8"));
+/// assert!(!is_uuid("{67e55044-10b1-426f-9247-bb680e5fe0c8}"));
+/// ```
+pub fn is_uuid(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 36 && b.iter().enumerate().all(|(i, c)| match i {
+        8 | 13 | 18 | 23 => *c == b'-',
+        _ => c.is_ascii_hexdigit()
+    })
+}
+
+/// Returns `true` if `s` is an e-mail address such as `jane.doe@example.com`: a dot-atom
+/// local part as in [RFC 5322], an `@` and a hostname or a bracketed IPv4 address.
+/// Quoted local parts are not accepted.
+///
+/// [RFC 5322]:https://datatracker.ietf.org/doc/html/rfc5322#section-3.4.1
+///
+/// ```
+/// # use json::format::is_email;
+/// assert!(is_email("jane.doe+json@example.com"));
+/// assert!(is_email("root@[192.168.0.1]"));
+/// assert!(!is_email("jane..doe@example.com"));
+/// assert!(!is_email("example.com"));
+/// ```
+pub fn is_email(s: &str) -> bool {
+    let (local, domain) = match s.rsplit_once('@') {
+        Some(parts) => parts,
+        None => return false
+    };
+    let atext = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c);
+    let local_valid = local.len() <= 64 && local.split('.').all(|atom| !atom.is_empty() && atom.chars().all(atext));
+    let domain_valid = match domain.strip_prefix('[').and_then(|d| d.strip_suffix(']')) {
+        Some(address) => is_ipv4(address),
+        None => is_hostname(domain)
+    };
+    local_valid && domain_valid
+}
+
+/// Returns `true` if `s` is an [RFC 1123] hostname such as `api.example.com`: labels of at
+/// most 63 letters, digits and hyphens, which neither start nor end with a hyphen.
+///
+/// [RFC 1123]:https://datatracker.ietf.org/doc/html/rfc1123#section-2.1
+pub fn is_hostname(s: &str) -> bool {
+    let s = s.strip_suffix('.').unwrap_or(s);
+    !s.is_empty() && s.len() <= 253 && s.split('.').all(|label| {
+        !label.is_empty() && label.len() <= 63 && !label.starts_with('-') && !label.ends_with('-')
+            && label.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-')
+    })
+}
+
+/// Returns `true` if `s` is an IPv4 address in dotted-decimal form such as `192.168.0.1`.
+/// Leading zeros are not accepted, as they are read as octal by some parsers.
+pub fn is_ipv4(s: &str) -> bool {
+    s.parse::<Ipv4Addr>().is_ok()
+}
+
+/// Returns `true` if `s` is an [RFC 4291] IPv6 address such as `2001:db8::1` or
+/// `::ffff:192.168.0.1`
+///
+/// [RFC 4291]:https://datatracker.ietf.org/doc/html/rfc4291#section-2.2
+pub fn is_ipv6(s: &str) -> bool {
+    s.parse::<Ipv6Addr>().is_ok()
+}
+
+/// Returns `true` if `s` is an absolute [RFC 3986] URI such as `https://example.com/a?b#c`:
+/// a scheme, a `:` and characters that are allowed in URIs, with `%` only starting an escape.
+///
+/// [RFC 3986]:https://datatracker.ietf.org/doc/html/rfc3986#section-3
+///
+/// ```
+/// # use json::format::is_uri;
+/// assert!(is_uri("https://example.com/a%20b?c=d#e"));
+/// assert!(is_uri("urn:isbn:0451450523"));
+/// assert!(!is_uri("/relative/path"));
+/// assert!(!is_uri("https://example.com/a b"));
+/// ```
+pub fn is_uri(s: &str) -> bool {
+    let (scheme, rest) = match s.split_once(':') {
+        Some(parts) => parts,
+        None => return false
+    };
+    let scheme_valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    let b = rest.as_bytes();
+    scheme_valid && b.iter().enumerate().all(|(i, c)| match c {
+        b'%' => b.len() > i + 2 && b[i + 1].is_ascii_hexdigit() && b[i + 2].is_ascii_hexdigit(),
+        _ => c.is_ascii_alphanumeric() || b"-._~:/?#[]@!$&'()*+,;=".contains(c)
+    })
+}
+
+fn date(b: &[u8]) -> bool {
+    if b.len() != 10 || b[4] != b'-' || b[7] != b'-' {
+        return false;
+    }
+    let (year, month, day) = match (digits(&b[0..4]), digits(&b[5..7]), digits(&b[8..10])) {
+        (Some(year), Some(month), Some(day)) => (year, month, day),
+        _ => return false
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false
+    };
+    (1..=days).contains(&day)
+}
+
+fn time(b: &[u8]) -> bool {
+    if b.len() < 9 || b[2] != b':' || b[5] != b':' {
+        return false;
+    }
+    match (digits(&b[0..2]), digits(&b[3..5]), digits(&b[6..8])) {
+        (Some(hour), Some(minute), Some(second)) if hour < 24 && minute < 60 && second <= 60 => (),
+        _ => return false
+    }
+    let mut rest = &b[8..];
+    if rest[0] == b'.' {
+        let fraction = rest[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if fraction == 0 {
+            return false;
+        }
+        rest = &rest[1 + fraction..];
+    }
+    match rest {
+        [b'Z'] | [b'z'] => true,
+        [sign, h1, h2, b':', m1, m2] if *sign == b'+' || *sign == b'-' => matches!(
+            (digits(&[*h1, *h2]), digits(&[*m1, *m2])), (Some(hour), Some(minute)) if hour < 24 && minute < 60),
+        _ => false
+    }
+}
+
+fn digits(b: &[u8]) -> Option<u32> {
+    if b.iter().all(|c| c.is_ascii_digit()) {
+        Some(b.iter().fold(0, |n, c| n * 10 + (c - b'0') as u32))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::format::{is_date, is_date_time, is_email, is_hostname, is_ipv4, is_ipv6, is_time, is_uri, minify_stream,
+                        pretty_stream, to_colored_string, to_pretty_string, PrettyOptions, Theme};
+    use crate::json;
+    use crate::serializer::Serialize;
+
+    #[test]
+    fn test_to_pretty_string() {
+        let j_value = json!("[[], {\"k\": [null]}, \"s\"]");
+        let options = PrettyOptions { indent: 4, ..PrettyOptions::default() };
+        assert_eq!("[\n    [],\n    {\n        \"k\": [\n            null\n        ]\n    },\n    \"s\"\n]",
+                   to_pretty_string(&j_value, &options));
+        let options = PrettyOptions { indent: 0, ..PrettyOptions::default() };
+        assert_eq!("{\n\"a\": 1,\n\"b\": 2\n}", to_pretty_string(&json!("{\"b\": 2, \"a\": 1}"), &options));
+        assert_eq!("true", to_pretty_string(&json!("true"), &options));
+    }
+
+    #[test]
+    fn test_pretty_stream() {
+        let document = " [[], {\"k\" :[null, \"a b\"]}, -1e5 ] ";
+        let options = PrettyOptions { indent: 4, ..PrettyOptions::default() };
+        let mut out = Vec::new();
+        pretty_stream(document.as_bytes(), &mut out, &options).unwrap();
+        assert_eq!(to_pretty_string(&json!(document), &options).replace("-100000", "-1e5"),
+                   String::from_utf8(out).unwrap());
+        let mut out = Vec::new();
+        minify_stream(document.as_bytes(), &mut out).unwrap();
+        assert_eq!("[[],{\"k\":[null,\"a b\"]},-1e5]", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_minify_stream_errors() {
+        for document in ["[1 2]", "{\"a\" 1}", "{1: 2}", "[tru]", "[1] 2", "{\"a\": 1"].iter() {
+            let error = minify_stream(document.as_bytes(), Vec::new()).unwrap_err();
+            assert_eq!(ErrorKind::Syntax, error.kind(), "{}", document);
+        }
+        assert_eq!("Unexpected character 2 at byte 3",
+                   minify_stream("[1 2]".as_bytes(), Vec::new()).unwrap_err().message());
+    }
+
+    #[test]
+    fn test_stream_max_depth() {
+        let nested = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        let mut out = Vec::new();
+        minify_stream(nested.as_bytes(), &mut out).unwrap();
+        assert_eq!(nested, String::from_utf8(out).unwrap());
+        let deep = "[".repeat(200_000);
+        let error = minify_stream(deep.as_bytes(), Vec::new()).unwrap_err();
+        assert_eq!(ErrorKind::Syntax, error.kind());
+        assert_eq!("The value is nested deeper than the limit of 128 levels at byte 128", error.message());
+        let error = pretty_stream(deep.as_bytes(), Vec::new(), &PrettyOptions::default()).unwrap_err();
+        assert_eq!(ErrorKind::Syntax, error.kind());
+    }
+
+    #[test]
+    fn test_to_colored_string() {
+        let theme = Theme { key: "K", string: "S", number: "N", boolean: "B", null: "Z", punctuation: "P" };
+        let colored = to_colored_string(&json!("{\"a\": [\"x\", 1, false, null, {}]}"), &theme);
+        let reset = "\x1b[0m";
+        assert_eq!(["P{", "\n  K\"a\"", "P:", " P[", "\n    S\"x\"", "P,", "\n    N1", "P,", "\n    Bfalse",
+                    "P,", "\n    Znull", "P,", "\n    P{}", "\n  P]", "\nP}", ""].join(reset), colored);
+    }
+
+    #[test]
+    fn test_date() {
+        assert!(is_date("2000-02-29"));
+        assert!(!is_date("1900-02-29"));
+        assert!(!is_date("2021-04-31"));
+        assert!(!is_date("2021-13-01"));
+        assert!(!is_date("2021-00-01"));
+        assert!(!is_date("2021-1-01"));
+        assert!(!is_date("+021-01-01"));
+    }
+
+    #[test]
+    fn test_time() {
+        assert!(is_time("00:00:00Z"));
+        assert!(is_time("23:59:59.999999+14:00"));
+        assert!(!is_time("24:00:00Z"));
+        assert!(!is_time("12:00:00"));
+        assert!(!is_time("12:00:00.Z"));
+        assert!(!is_time("12:00:00+1:00"));
+        assert!(!is_time("12:00:00+01:60"));
+    }
+
+    #[test]
+    fn test_date_time() {
+        assert!(is_date_time("2024-01-01T00:00:00+00:00"));
+        assert!(!is_date_time("2024-01-01T00:00:00"));
+        assert!(!is_date_time("2024-01-01X00:00:00Z"));
+        assert!(!is_date_time("é024-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_email_and_hostname() {
+        assert!(is_email("a@b"));
+        assert!(!is_email("@example.com"));
+        assert!(!is_email("a.@example.com"));
+        assert!(!is_email("a@-example.com"));
+        assert!(!is_email("a@[::1]"));
+        assert!(!is_email(&format!("{}@example.com", "a".repeat(65))));
+        assert!(is_hostname("example.com."));
+        assert!(is_hostname("xn--bcher-kva.example"));
+        assert!(!is_hostname("a_b.example"));
+        assert!(!is_hostname(&"a".repeat(64)));
+        assert!(!is_hostname("a..b"));
+    }
+
+    #[test]
+    fn test_ip() {
+        assert!(is_ipv4("0.0.0.0"));
+        assert!(!is_ipv4("256.0.0.1"));
+        assert!(!is_ipv4("01.2.3.4"));
+        assert!(!is_ipv4("1.2.3"));
+        assert!(is_ipv6("::"));
+        assert!(is_ipv6("::ffff:192.168.0.1"));
+        assert!(!is_ipv6("1::2::3"));
+        assert!(!is_ipv6("192.168.0.1"));
+    }
+
+    #[test]
+    fn test_uri() {
+        assert!(is_uri("mailto:jane@example.com"));
+        assert!(is_uri("http://[::1]:8080/"));
+        assert!(!is_uri("1http://example.com"));
+        assert!(!is_uri("http://example.com/%2"));
+        assert!(!is_uri("http://example.com/é"));
+    }
+
+    #[test]
+    fn test_truncate_for_log() {
+        let j_value = json!("{\"a\": {\"b\": {\"c\": [1, 2]}}, \"s\": \"ééééé\"}");
+        assert_eq!(j_value, j_value.truncate_for_log(1000));
+        for max_bytes in [0, 5, 10, 20, 30, 40] {
+            let truncated = j_value.truncate_for_log(max_bytes);
+            assert!(truncated.serialized_len() <= max_bytes.max(5), "{} for {}", truncated.serialize(), max_bytes);
+        }
+        assert_eq!(json!("\"…\""), j_value.truncate_for_log(5));
+        assert_eq!(json!("\"éé…(+16 bytes)\""), json!("\"éééééééééé\"").truncate_for_log(20));
+        assert_eq!(json!("[\"…\"]"), json!("[[[1]], 2, 3]").truncate_for_log(10));
+    }
+}
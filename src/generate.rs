@@ -0,0 +1,628 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Reproducible pseudo-random documents, and corrupted variants of documents, for load testing
+//! and fuzzing.
+
+use std::str::FromStr;
+
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::schema::{Node, Rules, Schema, SchemaType};
+use crate::transform::push_token;
+
+/// Options for [`random`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerateOptions {
+    /// Arrays and objects are nested at most this deep where the schema leaves it open
+    pub max_depth: usize,
+    /// The largest number of elements of an array or members of an object, unless the schema
+    /// requires more
+    pub max_items: usize,
+    /// The longest string, unless the schema requires a longer one
+    pub max_string_len: usize,
+    /// The chars strings and keys are made of
+    pub alphabet: String,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            max_depth: 3,
+            max_items: 4,
+            max_string_len: 12,
+            alphabet: "abcdefghijklmnopqrstuvwxyz".to_string(),
+        }
+    }
+}
+
+/// A small, fast generator of pseudo-random numbers (SplitMix64). The same seed always gives
+/// the same numbers, on every platform.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number from 0 to `n - 1`, or 0 if `n` is 0
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        if n == 0 { 0 } else { (self.next_u64() % n as u64) as usize }
+    }
+
+    /// Returns a number from `low` to `high`, both included
+    pub(crate) fn between(&mut self, low: usize, high: usize) -> usize {
+        low + self.below(high.saturating_sub(low) + 1)
+    }
+
+    /// Returns a number from 0 to 1, 1 excluded
+    pub(crate) fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    pub(crate) fn chance(&mut self, probability: f64) -> bool {
+        self.unit() < probability
+    }
+}
+
+/// Converts a finite f64 to a number value
+pub(crate) fn number(n: f64) -> JValue {
+    JValue::Number(JNumber::from_f64(n).unwrap())
+}
+
+/// Generates a document from `seed`. Without a schema the document is any mix of values
+/// within `options`; with a schema it is made to be valid against it. The supported keywords
+/// are followed directly, and for schemas whose keywords combine in ways the generator does
+/// not follow, such as `not`, a few candidates are generated and the first valid one is
+/// returned. The same arguments always give the same document.
+///
+/// ```
+/// # use json::json;
+/// # use json::generate::{random, GenerateOptions};
+/// # use json::schema::Schema;
+/// let schema = Schema::compile(&json!("{\"type\": \"object\", \"required\": [\"id\", \"email\"],\
+///     \"properties\": {\"id\": {\"type\": \"integer\", \"minimum\": 1}, \"email\": {\"type\": \"string\"},\
+///                    \"tags\": {\"type\": \"array\", \"items\": {\"enum\": [\"a\", \"b\"]}}}}")).unwrap();
+/// let options = GenerateOptions::default();
+/// let document = random(Some(&schema), &options, 42);
+/// assert!(schema.is_valid(&document));
+/// assert_eq!(document, random(Some(&schema), &options, 42));
+/// assert_ne!(document, random(Some(&schema), &options, 43));
+/// ```
+pub fn random(schema: Option<&Schema>, options: &GenerateOptions, seed: u64) -> JValue {
+    let mut generator = Generator {
+        rng: Rng::new(seed),
+        options,
+        alphabet: options.alphabet.chars().collect(),
+    };
+    let schema = match schema {
+        Some(schema) => schema,
+        None => return generator.any(0)
+    };
+    let mut candidate = generator.node(&schema.root, 0);
+    for _ in 0..16 {
+        if schema.is_valid(&candidate) {
+            break;
+        }
+        candidate = generator.node(&schema.root, 0);
+    }
+    candidate
+}
+
+/// Huge numbers [`mutate`] injects: beyond f64, u64 and i64, and with an absurdly long fraction
+const HUGE_NUMBERS: [&str; 4] = [
+    "1e400",
+    "18446744073709551616",
+    "-9223372036854775809",
+    "0.000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001",
+];
+
+/// The length of the strings [`mutate`] injects
+const HUGE_STRING_LEN: usize = 1 << 16;
+
+/// Returns a corrupted variant of `value` with `n_mutations` mutations applied, for testing how
+/// consumers of JSON cope with unexpected input. Each mutation picks a value in the document
+/// and flips its type, removes one of its keys, replaces it with a huge number or a huge string,
+/// or reorders its elements. The same arguments always give the same variant; vary the seed to
+/// get more.
+///
+/// ```
+/// # use json::json;
+/// # use json::generate::mutate;
+/// let document = json!("{\"id\": 7, \"tags\": [\"a\", \"b\", \"c\"]}");
+/// let variant = mutate(&document, 3, 1);
+/// assert_ne!(document, variant);
+/// assert_eq!(variant, mutate(&document, 3, 1));
+/// assert_eq!(document, mutate(&document, 0, 1));
+/// ```
+pub fn mutate(value: &JValue, n_mutations: usize, seed: u64) -> JValue {
+    let mut rng = Rng::new(seed);
+    let mut variant = value.clone();
+    for _ in 0..n_mutations {
+        mutate_once(&mut variant, &mut rng);
+    }
+    variant
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mutation {
+    FlipType,
+    RemoveKey,
+    HugeNumber,
+    HugeString,
+    Reorder,
+}
+
+const MUTATIONS: [Mutation; 5] =
+    [Mutation::FlipType, Mutation::RemoveKey, Mutation::HugeNumber, Mutation::HugeString, Mutation::Reorder];
+
+fn mutate_once(value: &mut JValue, rng: &mut Rng) {
+    let mut pointers = Vec::new();
+    collect_pointers(value, &mut String::new(), &mut pointers);
+    let mut mutation = MUTATIONS[rng.below(MUTATIONS.len())];
+    let applies = |pointer: &String, mutation: Mutation| match (mutation, value.pointer(pointer)) {
+        (Mutation::RemoveKey, Some(JValue::Object(o))) => !o.is_empty(),
+        (Mutation::RemoveKey, _) => false,
+        (Mutation::Reorder, Some(JValue::Array(a))) => a.len() > 1,
+        (Mutation::Reorder, _) => false,
+        _ => true
+    };
+    let mut candidates: Vec<&String> = pointers.iter().filter(|p| applies(p, mutation)).collect();
+    if candidates.is_empty() {
+        mutation = Mutation::FlipType;
+        candidates = pointers.iter().collect();
+    }
+    let pointer = candidates[rng.below(candidates.len())].clone();
+    let target = value.pointer_mut(&pointer).unwrap();
+    match mutation {
+        Mutation::FlipType => *target = flipped(target, rng),
+        Mutation::RemoveKey => if let JValue::Object(object) = target {
+            let mut keys: Vec<String> = object.iter().map(|(k, _)| k.clone()).collect();
+            keys.sort();
+            object.remove(&keys[rng.below(keys.len())]);
+        },
+        Mutation::HugeNumber => {
+            let text = HUGE_NUMBERS[rng.below(HUGE_NUMBERS.len())];
+            *target = JValue::Number(JNumber::from_str(text).unwrap());
+        }
+        Mutation::HugeString => *target = JValue::String(JString::from("x".repeat(HUGE_STRING_LEN))),
+        Mutation::Reorder => if let JValue::Array(elements) = target {
+            let original = elements.clone();
+            for i in (1..elements.len()).rev() {
+                elements.swap(i, rng.below(i + 1));
+            }
+            if *elements == original {
+                elements.rotate_left(1);
+            }
+        }
+    }
+}
+
+/// Collects the pointers of `value` and everything in it, keys sorted
+fn collect_pointers(value: &JValue, path: &mut String, pointers: &mut Vec<String>) {
+    pointers.push(path.clone());
+    let len = path.len();
+    match value {
+        JValue::Object(object) => {
+            let mut members: Vec<(&String, &JValue)> = object.iter().collect();
+            members.sort_by_key(|(k, _)| *k);
+            for (key, member) in members {
+                push_token(path, key);
+                collect_pointers(member, path, pointers);
+                path.truncate(len);
+            }
+        }
+        JValue::Array(elements) => {
+            for (i, element) in elements.iter().enumerate() {
+                push_token(path, &i.to_string());
+                collect_pointers(element, path, pointers);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns a value of another type than `value`, made from it where that is plausible
+fn flipped(value: &JValue, rng: &mut Rng) -> JValue {
+    let current = match value {
+        JValue::Null => 0,
+        JValue::Boolean(_) => 1,
+        JValue::Number(_) => 2,
+        JValue::String(_) => 3,
+        JValue::Array(_) => 4,
+        JValue::Object(_) => 5,
+    };
+    let mut kind = rng.below(5);
+    if kind >= current {
+        kind += 1;
+    }
+    match kind {
+        0 => JValue::Null,
+        1 => JValue::Boolean(rng.chance(0.5)),
+        2 => number(rng.between(0, 2000) as f64 - 1000.0),
+        3 => match value {
+            JValue::Array(_) | JValue::Object(_) => JValue::String(JString::from("[object]")),
+            scalar => JValue::String(JString::from(scalar.to_string())),
+        },
+        4 => JValue::Array(vec![value.clone()]),
+        _ => {
+            let mut object = JObject::new();
+            object.insert("value".to_string(), value.clone());
+            JValue::Object(object)
+        }
+    }
+}
+
+struct Generator<'a> {
+    rng: Rng,
+    options: &'a GenerateOptions,
+    alphabet: Vec<char>,
+}
+
+impl Generator<'_> {
+    fn text(&mut self, min: usize, max: usize) -> String {
+        let len = self.rng.between(min, max.max(min));
+        if self.alphabet.is_empty() {
+            return String::new();
+        }
+        (0..len).map(|_| self.alphabet[self.rng.below(self.alphabet.len())]).collect()
+    }
+
+    /// Lowercase letters, which are valid in hostnames, e-mail addresses and URIs alike
+    fn label(&mut self) -> String {
+        let len = self.rng.between(1, 8);
+        (0..len).map(|_| (b'a' + self.rng.below(26) as u8) as char).collect()
+    }
+
+    fn string(&mut self, min: usize, max: usize) -> JValue {
+        JValue::String(JString::from(self.text(min, max)))
+    }
+
+    fn any(&mut self, depth: usize) -> JValue {
+        let kinds = if depth < self.options.max_depth { 6 } else { 4 };
+        match self.rng.below(kinds) {
+            0 => JValue::Null,
+            1 => JValue::Boolean(self.rng.chance(0.5)),
+            2 => if self.rng.chance(0.5) {
+                number(self.rng.between(0, 2000) as f64 - 1000.0)
+            } else {
+                number((self.rng.unit() * 100_000.0).round() / 100.0)
+            },
+            3 => self.string(0, self.options.max_string_len),
+            4 => {
+                let len = self.rng.between(0, self.options.max_items);
+                JValue::Array((0..len).map(|_| self.any(depth + 1)).collect())
+            }
+            _ => {
+                let mut object = JObject::new();
+                for _ in 0..self.rng.between(0, self.options.max_items) {
+                    let key = self.text(1, 8);
+                    let value = self.any(depth + 1);
+                    object.insert(key, value);
+                }
+                JValue::Object(object)
+            }
+        }
+    }
+
+    fn node(&mut self, node: &Node, depth: usize) -> JValue {
+        match node {
+            Node::Bool(true) => self.any(depth),
+            // Nothing is valid
+            Node::Bool(false) => JValue::Null,
+            Node::Rules(rules) => self.rules(rules, depth)
+        }
+    }
+
+    fn rules(&mut self, rules: &Rules, depth: usize) -> JValue {
+        if let Some(value) = &rules.const_value {
+            return value.clone();
+        }
+        if let Some(values) = &rules.enum_values {
+            if !values.is_empty() {
+                return values[self.rng.below(values.len())].clone();
+            }
+        }
+        let branches = if !rules.one_of.is_empty() { &rules.one_of } else { &rules.any_of };
+        if !branches.is_empty() {
+            let branch = &branches[self.rng.below(branches.len())];
+            return self.node(branch, depth);
+        }
+        let schema_type = match &rules.types {
+            Some(types) if !types.is_empty() => types[self.rng.below(types.len())],
+            _ => match inferred_type(rules) {
+                Some(schema_type) => schema_type,
+                None if !rules.all_of.is_empty() => return self.node(&rules.all_of[0], depth),
+                None if rules.reference.is_some() => {
+                    return rules.reference.as_ref().unwrap().with(|node| self.node(node, depth));
+                }
+                None => return self.any(depth)
+            }
+        };
+        match schema_type {
+            SchemaType::Null => JValue::Null,
+            SchemaType::Boolean => JValue::Boolean(self.rng.chance(0.5)),
+            SchemaType::Number => self.number(rules, false),
+            SchemaType::Integer => self.number(rules, true),
+            SchemaType::String => self.string_value(rules),
+            SchemaType::Array => self.array(rules, depth),
+            SchemaType::Object => self.object(rules, depth),
+        }
+    }
+
+    fn number(&mut self, rules: &Rules, integer: bool) -> JValue {
+        let low = rules.minimum.or(rules.exclusive_minimum);
+        let high = rules.maximum.or(rules.exclusive_maximum);
+        let (low, high) = match (low, high) {
+            (Some(low), Some(high)) => (low, high),
+            (Some(low), None) => (low, low + 1000.0),
+            (None, Some(high)) => (high - 1000.0, high),
+            (None, None) => (0.0, 1000.0)
+        };
+        let in_range = |n: f64| rules.exclusive_minimum.is_none_or(|m| n > m)
+            && rules.exclusive_maximum.is_none_or(|m| n < m) && n >= low && n <= high;
+        let step = match (rules.multiple_of, integer) {
+            (Some(m), true) if m.fract() != 0.0 => Some(m * (1.0 / m).ceil().max(1.0)),
+            (Some(m), _) => Some(m),
+            (None, true) => Some(1.0),
+            (None, false) => None
+        };
+        let mut candidate = low;
+        for _ in 0..16 {
+            candidate = match step {
+                Some(step) => {
+                    let (first, last) = ((low / step).ceil() as i64, (high / step).floor() as i64);
+                    if last < first {
+                        return number(low);
+                    }
+                    let k = first + self.rng.below((last - first) as usize + 1) as i64;
+                    k as f64 * step
+                }
+                None => ((low + self.rng.unit() * (high - low)) * 100.0).round() / 100.0
+            };
+            if in_range(candidate) {
+                break;
+            }
+        }
+        number(candidate)
+    }
+
+    fn string_value(&mut self, rules: &Rules) -> JValue {
+        if let Some(format) = &rules.format {
+            let (year, month, day) = (self.rng.between(1970, 2037), self.rng.between(1, 12), self.rng.between(1, 28));
+            let (hour, minute, second) = (self.rng.below(24), self.rng.below(60), self.rng.below(60));
+            let date = format!("{:04}-{:02}-{:02}", year, month, day);
+            let time = format!("{:02}:{:02}:{:02}Z", hour, minute, second);
+            let text = match format.name.as_str() {
+                "date" => Some(date),
+                "time" => Some(time),
+                "date-time" => Some(format!("{}T{}", date, time)),
+                "uuid" => {
+                    let (a, b) = (self.rng.next_u64(), self.rng.next_u64());
+                    Some(format!("{:08x}-{:04x}-4{:03x}-{:x}{:03x}-{:012x}", a >> 32, (a >> 16) & 0xffff,
+                                 a & 0xfff, 8 + (b >> 62), (b >> 48) & 0xfff, b & 0xffff_ffff_ffff))
+                }
+                "email" => Some(format!("{}@{}.example", self.label(), self.label())),
+                "hostname" => Some(format!("{}.example", self.label())),
+                "uri" => Some(format!("https://{}.example/{}", self.label(), self.label())),
+                "ipv4" => Some(format!("{}.{}.{}.{}", self.rng.below(256), self.rng.below(256), self.rng.below(256),
+                                       self.rng.below(256))),
+                "ipv6" => Some((0..8).map(|_| format!("{:x}", self.rng.below(0x10000))).collect::<Vec<_>>().join(":")),
+                _ => None
+            };
+            if let Some(text) = text {
+                return JValue::String(JString::from(text));
+            }
+        }
+        let min = rules.min_length.unwrap_or(0);
+        let max = rules.max_length.unwrap_or_else(|| self.options.max_string_len.max(min));
+        self.string(min, max)
+    }
+
+    fn array(&mut self, rules: &Rules, depth: usize) -> JValue {
+        let min = rules.min_items.unwrap_or(0);
+        let max = rules.max_items.unwrap_or_else(|| self.options.max_items.max(min));
+        // Items that may refer back to this array would otherwise recurse without end
+        let len = match &rules.items {
+            Some(items) if depth >= self.options.max_depth && refers(items) => min,
+            _ => self.rng.between(min, max)
+        };
+        let mut elements: Vec<JValue> = Vec::with_capacity(len);
+        for _ in 0..len {
+            let mut element = self.element(rules, depth);
+            for _ in 0..8 {
+                if !rules.unique_items || !elements.contains(&element) {
+                    break;
+                }
+                element = self.element(rules, depth);
+            }
+            elements.push(element);
+        }
+        JValue::Array(elements)
+    }
+
+    fn element(&mut self, rules: &Rules, depth: usize) -> JValue {
+        match &rules.items {
+            Some(items) => self.node(items, depth + 1),
+            None => self.any(depth + 1)
+        }
+    }
+
+    fn object(&mut self, rules: &Rules, depth: usize) -> JValue {
+        let mut object = JObject::new();
+        let max = rules.max_properties.unwrap_or(usize::MAX);
+        for (name, node) in &rules.properties {
+            let optional = object.len() < max && (depth < self.options.max_depth || !refers(node));
+            if rules.required.contains(name) || (optional && self.rng.chance(0.7)) {
+                let value = self.node(node, depth + 1);
+                object.insert(name.clone(), value);
+            }
+        }
+        for name in &rules.required {
+            if object.get(name).is_none() {
+                let value = self.member(rules, depth);
+                object.insert(name.clone(), value);
+            }
+        }
+        let additional = !matches!(rules.additional_properties, Some(Node::Bool(false)));
+        let min = rules.min_properties.unwrap_or(0);
+        let mut attempts = 0;
+        while additional && object.len() < min && attempts < 4 * min {
+            attempts += 1;
+            let key = self.text(1, 8);
+            if !object.contains_key(&key) && rules.properties.iter().all(|(name, _)| *name != key) {
+                let value = self.member(rules, depth);
+                object.insert(key, value);
+            }
+        }
+        JValue::Object(object)
+    }
+
+    /// Generates a member that is not one of the properties
+    fn member(&mut self, rules: &Rules, depth: usize) -> JValue {
+        match &rules.additional_properties {
+            Some(node) => self.node(node, depth + 1),
+            None => self.any(depth + 1)
+        }
+    }
+}
+
+/// The type the keywords of a schema without `type` constrain
+/// Whether a node contains a `$ref`, which may lead back to where it is
+fn refers(node: &Node) -> bool {
+    matches!(node, Node::Rules(rules) if rules.reference.is_some())
+}
+
+fn inferred_type(rules: &Rules) -> Option<SchemaType> {
+    if !rules.properties.is_empty() || !rules.required.is_empty() || rules.additional_properties.is_some()
+        || rules.min_properties.is_some() || rules.max_properties.is_some() {
+        Some(SchemaType::Object)
+    } else if rules.items.is_some() || rules.min_items.is_some() || rules.max_items.is_some() {
+        Some(SchemaType::Array)
+    } else if rules.min_length.is_some() || rules.max_length.is_some() || rules.format.is_some() {
+        Some(SchemaType::String)
+    } else if rules.minimum.is_some() || rules.maximum.is_some() || rules.exclusive_minimum.is_some()
+        || rules.exclusive_maximum.is_some() || rules.multiple_of.is_some() {
+        Some(SchemaType::Number)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_structures::JValue;
+    use crate::generate::{mutate, random, GenerateOptions, Rng, HUGE_STRING_LEN};
+    use crate::json;
+    use crate::schema::Schema;
+
+    fn depth(value: &JValue) -> usize {
+        match value {
+            JValue::Array(a) => 1 + a.iter().map(depth).max().unwrap_or(0),
+            JValue::Object(o) => 1 + o.iter().map(|(_, v)| depth(v)).max().unwrap_or(0),
+            _ => 0
+        }
+    }
+
+    #[test]
+    fn test_rng() {
+        let mut rng = Rng::new(7);
+        let first: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+        let mut again = Rng::new(7);
+        assert_eq!(first, (0..3).map(|_| again.next_u64()).collect::<Vec<_>>());
+        assert!((0..1000).all(|_| rng.between(3, 5) >= 3 && rng.between(3, 5) <= 5 && rng.unit() < 1.0));
+        assert_eq!(0, rng.below(0));
+    }
+
+    #[test]
+    fn test_random_without_schema() {
+        let options = GenerateOptions { max_depth: 2, max_items: 3, max_string_len: 4, alphabet: "xy".to_string() };
+        for seed in 0..200 {
+            let value = random(None, &options, seed);
+            assert!(depth(&value) <= 2);
+            assert_eq!(value, random(None, &options, seed));
+            if let JValue::String(s) = &value {
+                assert!(s.as_str().len() <= 4 && s.as_str().chars().all(|c| c == 'x' || c == 'y'));
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_with_schema() {
+        let schemas = [
+            "{\"type\": \"integer\", \"minimum\": 10, \"exclusiveMaximum\": 13}",
+            "{\"type\": \"number\", \"multipleOf\": 0.25, \"minimum\": -1, \"maximum\": 1}",
+            "{\"type\": \"string\", \"minLength\": 20, \"maxLength\": 25}",
+            "{\"format\": \"date-time\"}",
+            "{\"type\": \"string\", \"format\": \"uuid\"}",
+            "{\"items\": {\"anyOf\": [{\"format\": \"email\"}, {\"format\": \"uri\"}, {\"format\": \"ipv4\"},
+                {\"format\": \"ipv6\"}, {\"format\": \"hostname\"}]}}",
+            "{\"type\": \"array\", \"minItems\": 3, \"maxItems\": 3, \"uniqueItems\": true, \"items\": {\"type\": \"integer\", \"minimum\": 0, \"maximum\": 20}}",
+            "{\"required\": [\"a\"], \"minProperties\": 3, \"additionalProperties\": {\"type\": \"boolean\"}}",
+            "{\"properties\": {\"a\": {\"const\": 1}}, \"additionalProperties\": false, \"required\": [\"a\"]}",
+            "{\"oneOf\": [{\"type\": \"null\"}, {\"type\": \"string\", \"maxLength\": 0}]}",
+            "{\"allOf\": [{\"type\": \"boolean\"}]}",
+            "{\"$defs\": {\"id\": {\"type\": \"integer\"}}, \"items\": {\"$ref\": \"#/$defs/id\"}}",
+            "{\"type\": \"integer\", \"not\": {\"const\": 1}, \"minimum\": 1, \"maximum\": 2}",
+            "true",
+        ];
+        for schema in schemas.iter() {
+            let compiled = Schema::compile(&crate::parser::parse(schema).unwrap()).unwrap();
+            for seed in 0..50 {
+                let value = random(Some(&compiled), &GenerateOptions::default(), seed);
+                assert!(compiled.is_valid(&value), "{} is not valid against {}", value, schema);
+            }
+        }
+        let tree = json!("{\"type\": \"object\", \"properties\": {\"children\": {\"type\": \"array\", \"items\": {\"$ref\": \"#\"}},
+                                \"parent\": {\"$ref\": \"#\"}}}");
+        let compiled = Schema::compile(&tree).unwrap();
+        for seed in 0..50 {
+            let value = random(Some(&compiled), &GenerateOptions::default(), seed);
+            assert!(compiled.is_valid(&value) && depth(&value) <= 8, "{} is not valid against {}", value, tree);
+        }
+        let schema = Schema::compile(&json!("false")).unwrap();
+        assert_eq!(JValue::Null, random(Some(&schema), &GenerateOptions::default(), 0));
+    }
+
+    #[test]
+    fn test_mutate() {
+        let document = json!("{\"id\": 7, \"name\": \"x\", \"tags\": [1, 2, 3], \"owner\": {\"id\": 1}}");
+        let (mut removed, mut huge, mut reordered) = (false, false, false);
+        for seed in 0..200 {
+            let variant = mutate(&document, 1, seed);
+            assert_ne!(document, variant);
+            assert_eq!(variant, mutate(&document, 1, seed));
+            let JValue::Object(object) = &variant else { continue };
+            removed |= object.len() < 4 || variant.pointer("/owner").is_some_and(|o| o == &json!("{}"));
+            huge |= matches!(variant.pointer("/name"), Some(JValue::String(s)) if s.as_str().len() == HUGE_STRING_LEN);
+            reordered |= matches!(variant.pointer("/tags"), Some(JValue::Array(a)) if a.len() == 3 && a[0] != json!("1"));
+        }
+        assert!(removed && huge && reordered);
+        assert_eq!(JValue::Null, mutate(&JValue::Null, 0, 3));
+        assert_ne!(JValue::Null, mutate(&JValue::Null, 1, 3));
+    }
+}
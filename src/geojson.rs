@@ -0,0 +1,394 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! [GeoJSON] (RFC 7946) geometries and features, read from and written to [`JValue`]s.
+//!
+//! Reading checks the structure of the coordinates: a position has at least two numbers, a
+//! line string at least two positions, and a linear ring of a polygon at least four, the last
+//! being the first. Errors name the location of the problem in the document, such as
+//! `/features/0/geometry/coordinates/1`.
+//!
+//! [GeoJSON]:https://datatracker.ietf.org/doc/html/rfc7946
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::location;
+use crate::transform::push_token;
+
+/// A longitude, a latitude and optionally an altitude, in that order
+pub type Position = Vec<f64>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    Point(Position),
+    MultiPoint(Vec<Position>),
+    LineString(Vec<Position>),
+    MultiLineString(Vec<Vec<Position>>),
+    /// The exterior ring followed by the holes
+    Polygon(Vec<Vec<Position>>),
+    MultiPolygon(Vec<Vec<Vec<Position>>>),
+    GeometryCollection(Vec<Geometry>),
+}
+
+/// A geometry with properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feature {
+    /// A string or a number, if the feature has an identifier
+    pub id: Option<JValue>,
+    pub geometry: Option<Geometry>,
+    pub properties: Option<JObject>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureCollection {
+    pub features: Vec<Feature>,
+}
+
+/// Any GeoJSON document
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoJson {
+    Geometry(Geometry),
+    Feature(Feature),
+    FeatureCollection(FeatureCollection),
+}
+
+impl Geometry {
+    /// Returns the name of the geometry's type, as in its `type` member
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Geometry::Point(_) => "Point",
+            Geometry::MultiPoint(_) => "MultiPoint",
+            Geometry::LineString(_) => "LineString",
+            Geometry::MultiLineString(_) => "MultiLineString",
+            Geometry::Polygon(_) => "Polygon",
+            Geometry::MultiPolygon(_) => "MultiPolygon",
+            Geometry::GeometryCollection(_) => "GeometryCollection"
+        }
+    }
+
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = typed_object(self.type_name());
+        let coordinates = match self {
+            Geometry::Point(p) => position_to_jvalue(p),
+            Geometry::MultiPoint(ps) | Geometry::LineString(ps) => positions_to_jvalue(ps),
+            Geometry::MultiLineString(lines) | Geometry::Polygon(lines) => {
+                JValue::Array(lines.iter().map(|ps| positions_to_jvalue(ps)).collect())
+            }
+            Geometry::MultiPolygon(polygons) => JValue::Array(polygons.iter()
+                .map(|lines| JValue::Array(lines.iter().map(|ps| positions_to_jvalue(ps)).collect()))
+                .collect()),
+            Geometry::GeometryCollection(geometries) => {
+                let geometries = geometries.iter().map(Geometry::to_jvalue).collect();
+                object.insert("geometries".to_string(), JValue::Array(geometries));
+                return JValue::Object(object);
+            }
+        };
+        object.insert("coordinates".to_string(), coordinates);
+        JValue::Object(object)
+    }
+}
+
+impl Feature {
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = typed_object("Feature");
+        if let Some(id) = &self.id {
+            object.insert("id".to_string(), id.clone());
+        }
+        object.insert("geometry".to_string(), self.geometry.as_ref().map_or(JValue::Null, Geometry::to_jvalue));
+        object.insert("properties".to_string(),
+                      self.properties.as_ref().map_or(JValue::Null, |p| JValue::Object(p.clone())));
+        JValue::Object(object)
+    }
+
+    /// Returns the property with the given name, if the feature has it
+    pub fn property(&self, name: &str) -> Option<&JValue> {
+        self.properties.as_ref().and_then(|p| p.get(&name.to_string()))
+    }
+}
+
+impl FeatureCollection {
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = typed_object("FeatureCollection");
+        object.insert("features".to_string(), JValue::Array(self.features.iter().map(Feature::to_jvalue).collect()));
+        JValue::Object(object)
+    }
+}
+
+impl GeoJson {
+    pub fn to_jvalue(&self) -> JValue {
+        match self {
+            GeoJson::Geometry(geometry) => geometry.to_jvalue(),
+            GeoJson::Feature(feature) => feature.to_jvalue(),
+            GeoJson::FeatureCollection(collection) => collection.to_jvalue()
+        }
+    }
+}
+
+impl FromJValue for Geometry {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        geometry(value, &mut String::new())
+    }
+}
+
+impl FromJValue for Feature {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        feature(value, &mut String::new())
+    }
+}
+
+impl FromJValue for FeatureCollection {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        feature_collection(value, &mut String::new())
+    }
+}
+
+impl FromJValue for GeoJson {
+    /// Reads a geometry, a feature or a feature collection, as the `type` member says
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::convert::FromJValue;
+    /// # use json::geojson::{GeoJson, Geometry};
+    /// let j_value = json!("{\"type\": \"FeatureCollection\", \"features\": [{\"type\": \"Feature\",\
+    ///     \"geometry\": {\"type\": \"Point\", \"coordinates\": [2.35, 48.85]}, \"properties\": {\"name\": \"Paris\"}}]}");
+    /// let collection = match GeoJson::from_jvalue(&j_value).unwrap() {
+    ///     GeoJson::FeatureCollection(collection) => collection,
+    ///     _ => unreachable!()
+    /// };
+    /// let paris = &collection.features[0];
+    /// assert_eq!(Some(&Geometry::Point(vec![2.35, 48.85])), paris.geometry.as_ref());
+    /// assert_eq!(Some(&json!("\"Paris\"")), paris.property("name"));
+    /// assert_eq!(j_value, collection.to_jvalue());
+    ///
+    /// let line = json!("{\"type\": \"LineString\", \"coordinates\": [[0, 0]]}");
+    /// assert_eq!("A line string needs at least 2 positions at /coordinates",
+    ///            GeoJson::from_jvalue(&line).unwrap_err().message());
+    /// ```
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        let mut path = String::new();
+        match type_of(value, &mut path)? {
+            "Feature" => feature(value, &mut path).map(GeoJson::Feature),
+            "FeatureCollection" => feature_collection(value, &mut path).map(GeoJson::FeatureCollection),
+            _ => geometry(value, &mut path).map(GeoJson::Geometry)
+        }
+    }
+}
+
+fn typed_object(type_name: &str) -> JObject {
+    let mut object = JObject::new();
+    object.insert("type".to_string(), JValue::String(JString::from(type_name)));
+    object
+}
+
+fn position_to_jvalue(position: &[f64]) -> JValue {
+    JValue::Array(position.iter().map(|&n| JNumber::from_f64(n).map_or(JValue::Null, JValue::Number)).collect())
+}
+
+fn positions_to_jvalue(positions: &[Position]) -> JValue {
+    JValue::Array(positions.iter().map(|p| position_to_jvalue(p)).collect())
+}
+
+fn invalid(message: String, path: &str) -> JsonError {
+    JsonError::new(ErrorKind::InvalidType, format!("{} at {}", message, location(path)))
+}
+
+/// Calls `f` with the path of the member `key` and the member, which is `null` if missing
+fn member<T>(value: &JValue, key: &str, path: &mut String,
+             f: impl FnOnce(&JValue, &mut String) -> Result<T, JsonError>) -> Result<T, JsonError> {
+    let len = path.len();
+    push_token(path, key);
+    let result = f(&value[key], path);
+    path.truncate(len);
+    result
+}
+
+/// Calls `f` with the path and value of every element of an array
+fn elements<T>(value: &JValue, path: &mut String, what: &str,
+               mut f: impl FnMut(&JValue, &mut String) -> Result<T, JsonError>) -> Result<Vec<T>, JsonError> {
+    let array = match value {
+        JValue::Array(a) => a,
+        _ => return Err(invalid(format!("Expected {}, found {}", what, describe(value)), path))
+    };
+    let len = path.len();
+    let mut result = Vec::with_capacity(array.len());
+    for (i, element) in array.iter().enumerate() {
+        push_token(path, &i.to_string());
+        result.push(f(element, path)?);
+        path.truncate(len);
+    }
+    Ok(result)
+}
+
+fn type_of<'a>(value: &'a JValue, path: &mut String) -> Result<&'a str, JsonError> {
+    if !matches!(value, JValue::Object(_)) {
+        return Err(invalid(format!("Expected a GeoJSON object, found {}", describe(value)), path));
+    }
+    match &value["type"] {
+        JValue::String(s) => Ok(s.as_str()),
+        other => member(value, "type", path, |_, path| Err(invalid(format!("Expected a type name, found {}", describe(other)), path)))
+    }
+}
+
+fn position(value: &JValue, path: &mut String) -> Result<Position, JsonError> {
+    let position = elements(value, path, "a position", |n, path| match n {
+        JValue::Number(n) => Ok(n.get_f64_value()),
+        _ => Err(invalid(format!("Expected a coordinate, found {}", describe(n)), path))
+    })?;
+    if position.len() < 2 {
+        return Err(invalid(format!("A position needs at least 2 coordinates, found {}", position.len()), path));
+    }
+    Ok(position)
+}
+
+fn positions(value: &JValue, path: &mut String) -> Result<Vec<Position>, JsonError> {
+    elements(value, path, "an array of positions", position)
+}
+
+fn line_string(value: &JValue, path: &mut String) -> Result<Vec<Position>, JsonError> {
+    let line = positions(value, path)?;
+    if line.len() < 2 {
+        return Err(invalid("A line string needs at least 2 positions".to_string(), path));
+    }
+    Ok(line)
+}
+
+fn polygon(value: &JValue, path: &mut String) -> Result<Vec<Vec<Position>>, JsonError> {
+    elements(value, path, "an array of linear rings", |ring, path| {
+        let ring = positions(ring, path)?;
+        if ring.len() < 4 {
+            return Err(invalid("A linear ring needs at least 4 positions".to_string(), path));
+        }
+        if ring.first() != ring.last() {
+            return Err(invalid("A linear ring must end with its first position".to_string(), path));
+        }
+        Ok(ring)
+    })
+}
+
+fn geometry(value: &JValue, path: &mut String) -> Result<Geometry, JsonError> {
+    let coordinates = |value, path: &mut String, f: fn(&JValue, &mut String) -> Result<Geometry, JsonError>| {
+        member(value, "coordinates", path, f)
+    };
+    match type_of(value, path)? {
+        "Point" => coordinates(value, path, |c, path| position(c, path).map(Geometry::Point)),
+        "MultiPoint" => coordinates(value, path, |c, path| positions(c, path).map(Geometry::MultiPoint)),
+        "LineString" => coordinates(value, path, |c, path| line_string(c, path).map(Geometry::LineString)),
+        "MultiLineString" => coordinates(value, path, |c, path| {
+            elements(c, path, "an array of line strings", line_string).map(Geometry::MultiLineString)
+        }),
+        "Polygon" => coordinates(value, path, |c, path| polygon(c, path).map(Geometry::Polygon)),
+        "MultiPolygon" => coordinates(value, path, |c, path| {
+            elements(c, path, "an array of polygons", polygon).map(Geometry::MultiPolygon)
+        }),
+        "GeometryCollection" => member(value, "geometries", path, |g, path| {
+            elements(g, path, "an array of geometries", geometry).map(Geometry::GeometryCollection)
+        }),
+        other => member(value, "type", path, |_, path| Err(invalid(format!("Unknown geometry type {}", other), path)))
+    }
+}
+
+fn feature(value: &JValue, path: &mut String) -> Result<Feature, JsonError> {
+    if type_of(value, path)? != "Feature" {
+        return member(value, "type", path, |t, path| Err(invalid(format!("Expected a feature, found {}", describe(t)), path)));
+    }
+    let id = member(value, "id", path, |id, path| match id {
+        JValue::Null => Ok(None),
+        JValue::String(_) | JValue::Number(_) => Ok(Some(id.clone())),
+        _ => Err(invalid(format!("Expected a string or a number, found {}", describe(id)), path))
+    })?;
+    let geometry = member(value, "geometry", path, |g, path| match g {
+        JValue::Null => Ok(None),
+        _ => geometry(g, path).map(Some)
+    })?;
+    let properties = member(value, "properties", path, |p, path| match p {
+        JValue::Null => Ok(None),
+        JValue::Object(o) => Ok(Some(o.clone())),
+        _ => Err(invalid(format!("Expected an object, found {}", describe(p)), path))
+    })?;
+    Ok(Feature { id, geometry, properties })
+}
+
+fn feature_collection(value: &JValue, path: &mut String) -> Result<FeatureCollection, JsonError> {
+    if type_of(value, path)? != "FeatureCollection" {
+        return member(value, "type", path, |t, path| {
+            Err(invalid(format!("Expected a feature collection, found {}", describe(t)), path))
+        });
+    }
+    let features = member(value, "features", path, |f, path| elements(f, path, "an array of features", feature))?;
+    Ok(FeatureCollection { features })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::FromJValue;
+    use crate::geojson::{Feature, FeatureCollection, GeoJson, Geometry};
+    use crate::json;
+
+    #[test]
+    fn test_geometries_round_trip() {
+        let ring = vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![1.0, 1.0], vec![0.0, 0.0]];
+        let geometries = vec![
+            Geometry::Point(vec![1.5, -2.0, 30.0]),
+            Geometry::MultiPoint(vec![]),
+            Geometry::LineString(vec![vec![0.0, 0.0], vec![1.0, 1.0]]),
+            Geometry::MultiLineString(vec![vec![vec![0.0, 0.0], vec![1.0, 1.0]]]),
+            Geometry::Polygon(vec![ring.clone()]),
+            Geometry::MultiPolygon(vec![vec![ring.clone(), ring]]),
+        ];
+        let collection = Geometry::GeometryCollection(geometries.clone());
+        for geometry in geometries.iter().chain(std::iter::once(&collection)) {
+            assert_eq!(Ok(geometry.clone()), Geometry::from_jvalue(&geometry.to_jvalue()));
+            assert_eq!(Ok(GeoJson::Geometry(geometry.clone())), GeoJson::from_jvalue(&geometry.to_jvalue()));
+        }
+    }
+
+    #[test]
+    fn test_features() {
+        let j_value = json!("{\"type\": \"Feature\", \"id\": 7, \"geometry\": null, \"properties\": null}");
+        let feature = Feature::from_jvalue(&j_value).unwrap();
+        assert_eq!(Feature { id: Some(json!("7")), geometry: None, properties: None }, feature);
+        assert_eq!(j_value, feature.to_jvalue());
+        assert_eq!(None, feature.property("a"));
+        let collection = FeatureCollection { features: vec![feature] };
+        assert_eq!(Ok(collection.clone()), FeatureCollection::from_jvalue(&collection.to_jvalue()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| GeoJson::from_jvalue(&json!(text)).unwrap_err().message().to_string();
+        assert_eq!("Expected a GeoJSON object, found an array at the root", error("[]"));
+        assert_eq!("Expected a type name, found null at /type", error("{}"));
+        assert_eq!("Unknown geometry type Circle at /type", error("{\"type\": \"Circle\"}"));
+        assert_eq!("A position needs at least 2 coordinates, found 1 at /coordinates",
+                   error("{\"type\": \"Point\", \"coordinates\": [1]}"));
+        assert_eq!("Expected a coordinate, found the string \"1\" at /coordinates/1/0",
+                   error("{\"type\": \"MultiPoint\", \"coordinates\": [[0, 0], [\"1\", 1]]}"));
+        assert_eq!("A linear ring must end with its first position at /coordinates/0",
+                   error("{\"type\": \"Polygon\", \"coordinates\": [[[0, 0], [1, 0], [1, 1], [0, 1]]]}"));
+        assert_eq!("A linear ring needs at least 4 positions at /coordinates/0/1",
+                   error("{\"type\": \"MultiPolygon\", \"coordinates\": [[[[0, 0], [1, 0], [1, 1], [0, 0]], [[0, 0]]]]}"));
+        assert_eq!("Expected an array of positions, found null at /features/0/geometry/coordinates",
+                   error("{\"type\": \"FeatureCollection\", \"features\": [{\"type\": \"Feature\",\
+                          \"geometry\": {\"type\": \"LineString\"}, \"properties\": {}}]}"));
+        assert_eq!("Expected an object, found the number 1 at /properties",
+                   error("{\"type\": \"Feature\", \"geometry\": null, \"properties\": 1}"));
+        assert_eq!("Expected a feature, found the string \"Point\" at /type",
+                   Feature::from_jvalue(&json!("{\"type\": \"Point\"}")).unwrap_err().message());
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Undo and redo of the edits of a document.
+
+use std::collections::VecDeque;
+
+use crate::data_structures::JValue;
+use crate::error::JsonError;
+use crate::patch::{equal_as_written, Operation};
+
+#[derive(Debug, Clone)]
+struct Edit {
+    patch: Vec<Operation>,
+    inverse: Vec<Operation>,
+}
+
+/// A [`JValue`] which records every edit as a JSON Patch and the patch undoing it, so edits can
+/// be undone and redone. A new edit discards the edits which were undone. With a limit the
+/// oldest edits are forgotten once there are more than the limit.
+///
+/// ```
+/// # use json::json;
+/// # use json::history::EditHistory;
+/// let mut history = EditHistory::new(json!("{\"title\": \"Draft\"}"));
+/// history.set("/title", json!("\"Final\"")).unwrap();
+/// history.set("/tags", json!("[\"news\"]")).unwrap();
+///
+/// assert!(history.undo());
+/// assert!(history.undo());
+/// assert!(!history.undo());
+/// assert_eq!(&json!("{\"title\": \"Draft\"}"), history.document());
+///
+/// assert!(history.redo());
+/// assert_eq!(&json!("{\"title\": \"Final\"}"), history.document());
+/// ```
+#[derive(Debug, Clone)]
+pub struct EditHistory {
+    document: JValue,
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    limit: Option<usize>,
+}
+
+impl EditHistory {
+    /// Starts the history of a document without edits and without a limit
+    pub fn new(document: JValue) -> EditHistory {
+        EditHistory { document, undo: VecDeque::new(), redo: Vec::new(), limit: None }
+    }
+
+    /// Keeps at most `limit` edits which can be undone
+    pub fn with_limit(mut self, limit: usize) -> EditHistory {
+        self.limit = Some(limit);
+        self.trim();
+        self
+    }
+
+    /// Returns the document
+    pub fn document(&self) -> &JValue {
+        &self.document
+    }
+
+    /// Drops the history and returns the document
+    pub fn into_inner(self) -> JValue {
+        self.document
+    }
+
+    /// Applies a JSON Patch as one edit. If an operation fails, the document and the history
+    /// are left as they were. A patch which changes nothing is not recorded.
+    pub fn apply_patch(&mut self, operations: &[Operation]) -> Result<(), JsonError> {
+        let inverse = self.document.apply_patch_with_inverse(operations)?;
+        if !inverse.iter().all(|operation| matches!(operation, Operation::Test { .. })) {
+            self.record(Edit { patch: operations.to_vec(), inverse });
+        }
+        Ok(())
+    }
+
+    /// Sets the value at the given JSON Pointer. A missing object member is added, and the
+    /// array index `-` or the length of the array appends to it. Setting a value to itself is
+    /// not recorded, with numbers compared as they are written.
+    pub fn set(&mut self, pointer: &str, value: JValue) -> Result<(), JsonError> {
+        let operation = match self.document.pointer(pointer) {
+            Some(old) if equal_as_written(old, &value) => return Ok(()),
+            Some(_) => Operation::Replace { path: pointer.to_string(), value },
+            None => Operation::Add { path: pointer.to_string(), value }
+        };
+        self.apply_patch(&[operation])
+    }
+
+    /// Removes the value at the given JSON Pointer
+    pub fn remove(&mut self, pointer: &str) -> Result<(), JsonError> {
+        self.apply_patch(&[Operation::Remove { path: pointer.to_string() }])
+    }
+
+    /// Replaces the document as one edit, recording the difference to the current document
+    pub fn replace_document(&mut self, document: JValue) {
+        let patch = self.document.diff_patch(&document);
+        if !patch.is_empty() {
+            let inverse = document.diff_patch(&self.document);
+            self.document = document;
+            self.record(Edit { patch, inverse });
+        }
+    }
+
+    /// Undoes the last edit, returning whether there was one
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop_back() {
+            Some(edit) => {
+                self.document.apply_patch(&edit.inverse).expect("the inverse of an edit applies");
+                self.redo.push(edit);
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Redoes the last undone edit, returning whether there was one
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                self.document.apply_patch(&edit.patch).expect("an undone edit applies again");
+                self.undo.push_back(edit);
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Returns the number of edits which can be undone
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Returns the number of edits which can be redone
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Forgets all edits, keeping the document
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn record(&mut self, edit: Edit) {
+        self.redo.clear();
+        self.undo.push_back(edit);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        if let Some(limit) = self.limit {
+            while self.undo.len() > limit {
+                self.undo.pop_front();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::history::EditHistory;
+    use crate::json;
+    use crate::parser::parse;
+    use crate::patch::parse_patch;
+    use crate::serializer::Serialize;
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = EditHistory::new(json!("{\"items\": [1, 2, 3]}"));
+        history.remove("/items/0").unwrap();
+        history.apply_patch(&parse_patch(&json!("[{\"op\": \"move\", \"from\": \"/items\", \"path\": \"/list\"},
+                                                   {\"op\": \"add\", \"path\": \"/list/-\", \"value\": 4}]")).unwrap()).unwrap();
+        history.replace_document(json!("{\"list\": [9]}"));
+        assert_eq!(3, history.undo_len());
+
+        assert!(history.undo());
+        assert_eq!(&json!("{\"list\": [2, 3, 4]}"), history.document());
+        assert!(history.undo());
+        assert_eq!(&json!("{\"items\": [2, 3]}"), history.document());
+        assert!(history.undo());
+        assert_eq!(&json!("{\"items\": [1, 2, 3]}"), history.document());
+        assert_eq!(3, history.redo_len());
+
+        while history.redo() {}
+        assert_eq!(&json!("{\"list\": [9]}"), history.document());
+    }
+
+    #[test]
+    fn test_new_edit_discards_redo() {
+        let mut history = EditHistory::new(json!("{}"));
+        history.set("/a", json!("1")).unwrap();
+        history.undo();
+        history.set("/b", json!("2")).unwrap();
+        assert!(!history.redo());
+        assert_eq!(&json!("{\"b\": 2}"), history.document());
+    }
+
+    #[test]
+    fn test_unchanged_and_failed_edits() {
+        let mut history = EditHistory::new(json!("{\"a\": 1}"));
+        history.set("/a", json!("1")).unwrap();
+        history.replace_document(json!("{\"a\": 1}"));
+        history.apply_patch(&parse_patch(&json!("[{\"op\": \"test\", \"path\": \"/a\", \"value\": 1}]")).unwrap()).unwrap();
+        assert_eq!(ErrorKind::NotFound, history.remove("/b").unwrap_err().kind());
+        assert_eq!(0, history.undo_len());
+    }
+
+    #[test]
+    fn test_numbers_are_compared_as_written() {
+        let mut history = EditHistory::new(json!("{\"id\": 12345678901234567891}"));
+        history.set("/id", json!("12345678901234567892")).unwrap();
+        assert_eq!(1, history.undo_len());
+        assert_eq!("{\"id\":12345678901234567892}", history.document().serialize());
+        assert!(history.undo());
+        assert_eq!("{\"id\":12345678901234567891}", history.document().serialize());
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut history = EditHistory::new(json!("0"));
+        for i in 1..=5 {
+            history.set("", parse(&i.to_string()).unwrap()).unwrap();
+        }
+        let mut history = history.with_limit(2);
+        assert_eq!(2, history.undo_len());
+        history.set("", json!("6")).unwrap();
+        while history.undo() {}
+        assert_eq!(&json!("4"), history.document());
+
+        history.clear();
+        assert!(!history.redo());
+        assert_eq!(&json!("4"), history.document());
+    }
+}
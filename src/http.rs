@@ -0,0 +1,278 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Request and response bodies for web frameworks built on the [http] crate, such as hyper and
+//! axum.
+//!
+//! [http]:https://crates.io/crates/http
+
+use bytes::Bytes;
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+
+use crate::convert::FromJValue;
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::parse;
+use crate::serializer::Serialize;
+
+/// The media type of JSON documents
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+impl JValue {
+    /// Parses a request or response body, which must be UTF-8
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::JValue;
+    /// # use bytes::Bytes;
+    /// assert_eq!(Ok(json!("{\"a\": 1}")), JValue::from_body(Bytes::from_static(b"{\"a\": 1}")));
+    /// assert_eq!("Invalid UTF-8 in the body at byte 2",
+    ///            JValue::from_body(Bytes::from_static(b"[\"\xff\"]")).unwrap_err().message());
+    /// ```
+    pub fn from_body(body: Bytes) -> Result<JValue, JsonError> {
+        from_utf8(&body).and_then(parse)
+    }
+}
+
+/// Whether the value of a `Content-Type` header is JSON: `application/json` or a type with the
+/// `+json` suffix, such as `application/problem+json`, with any parameters
+///
+/// ```
+/// # use json::http::is_json_content_type;
+/// assert!(is_json_content_type("application/json; charset=utf-8"));
+/// assert!(is_json_content_type("Application/Problem+JSON"));
+/// assert!(!is_json_content_type("text/plain"));
+/// ```
+pub fn is_json_content_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    media_type == JSON_CONTENT_TYPE
+        || media_type.strip_prefix("application/").is_some_and(|subtype| subtype.ends_with("+json"))
+}
+
+/// Parses a body in the charset named by the `charset` parameter of its `Content-Type` header,
+/// for clients that send documents in legacy encodings. UTF-8, US-ASCII, ISO-8859-1 and UTF-16
+/// are read: `utf-16le` and `utf-16be` in that byte order, and `utf-16` in the order of its
+/// byte order mark, or big-endian without one. A byte order mark is skipped. Without a header
+/// or a charset parameter the body must be UTF-8, as JSON requires.
+///
+/// Fails with [`ErrorKind::UnsupportedMediaType`] if the content type is not JSON, with
+/// [`ErrorKind::UnsupportedCharset`] for any other charset, and with [`ErrorKind::Syntax`] if
+/// the body is not valid in its charset or is not valid JSON.
+///
+/// ```
+/// # use json::json;
+/// # use json::http::parse_http_body;
+/// let body = [b'"', 0, 0xe9, 0, b'"', 0];
+/// assert_eq!(Ok(json!("\"\\u00e9\"")), parse_http_body(&body, Some("application/json; charset=UTF-16LE")));
+/// assert_eq!(Ok(json!("\"\\u00e9\"")), parse_http_body(b"\"\xe9\"", Some("application/json; charset=latin1")));
+/// assert_eq!("The charset shift_jis is not supported",
+///            parse_http_body(b"1", Some("application/json;charset=\"Shift_JIS\"")).unwrap_err().message());
+/// ```
+pub fn parse_http_body(body: &[u8], content_type: Option<&str>) -> Result<JValue, JsonError> {
+    let mut charset = "utf-8".to_string();
+    if let Some(content_type) = content_type {
+        if !is_json_content_type(content_type) {
+            return Err(JsonError::new(ErrorKind::UnsupportedMediaType,
+                                      format!("The content type {} is not JSON", content_type)));
+        }
+        for parameter in content_type.split(';').skip(1) {
+            if let Some((name, value)) = parameter.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("charset") {
+                    charset = value.trim().trim_matches('"').to_ascii_lowercase();
+                }
+            }
+        }
+    }
+    let text = match charset.as_str() {
+        "utf-8" | "utf8" => return from_utf8(body.strip_prefix(b"\xef\xbb\xbf").unwrap_or(body)).and_then(parse),
+        "us-ascii" | "ascii" => match body.iter().position(|b| !b.is_ascii()) {
+            Some(at) => return Err(JsonError::new(
+                ErrorKind::Syntax, format!("Invalid US-ASCII in the body at byte {}", at))),
+            None => return from_utf8(body).and_then(parse)
+        },
+        "iso-8859-1" | "latin1" | "latin-1" => body.iter().map(|&b| char::from(b)).collect(),
+        "utf-16le" => from_utf16(body, u16::from_le_bytes, 0)?,
+        "utf-16be" => from_utf16(body, u16::from_be_bytes, 0)?,
+        "utf-16" => match body {
+            [0xff, 0xfe, rest @ ..] => from_utf16(rest, u16::from_le_bytes, 2)?,
+            [0xfe, 0xff, rest @ ..] => from_utf16(rest, u16::from_be_bytes, 2)?,
+            _ => from_utf16(body, u16::from_be_bytes, 0)?
+        },
+        _ => return Err(JsonError::new(ErrorKind::UnsupportedCharset,
+                                       format!("The charset {} is not supported", charset)))
+    };
+    parse(text.strip_prefix('\u{feff}').unwrap_or(&text))
+}
+
+fn from_utf8(body: &[u8]) -> Result<&str, JsonError> {
+    std::str::from_utf8(body).map_err(|e| JsonError::new(
+        ErrorKind::Syntax, format!("Invalid UTF-8 in the body at byte {}", e.valid_up_to())))
+}
+
+/// Decodes UTF-16 in the byte order of `unit`; `offset` is the offset of `body` in the body
+fn from_utf16(body: &[u8], unit: fn([u8; 2]) -> u16, offset: usize) -> Result<String, JsonError> {
+    let invalid = |at: usize| JsonError::new(ErrorKind::Syntax, format!("Invalid UTF-16 in the body at byte {}", at));
+    if !body.len().is_multiple_of(2) {
+        return Err(invalid(offset + body.len() - 1));
+    }
+    let units = body.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    let mut text = String::with_capacity(body.len() / 2);
+    let mut at = offset;
+    for c in std::char::decode_utf16(units) {
+        let c = c.map_err(|_| invalid(at))?;
+        at += 2 * c.len_utf16();
+        text.push(c);
+    }
+    Ok(text)
+}
+
+/// A JSON body, converted from a [`JValue`] when it is read with [`FromJValue`], for handlers
+/// that take and return JSON.
+///
+/// ```
+/// # use json::json;
+/// # use json::http::Json;
+/// # use json::data_structures::JValue;
+/// # use bytes::Bytes;
+/// let request = http::Request::post("/items")
+///     .header("content-type", "application/json")
+///     .body(Bytes::from_static(b"{\"name\": \"pen\"}"))
+///     .unwrap();
+/// let Json(item) = Json::<JValue>::from_request(request).unwrap();
+/// assert_eq!(json!("\"pen\""), item["name"]);
+///
+/// let response = Json(item).into_response();
+/// assert_eq!("application/json", response.headers()["content-type"]);
+/// assert_eq!("{\"name\":\"pen\"}", response.body());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Json<T = JValue>(pub T);
+
+impl<T> Json<T> {
+    /// Returns the value in the body
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: FromJValue> Json<T> {
+    /// Parses a body and converts it to `T`
+    pub fn from_body(body: Bytes) -> Result<Json<T>, JsonError> {
+        T::from_jvalue(&JValue::from_body(body)?).map(Json)
+    }
+
+    /// Parses the body of a request with [`parse_http_body`] and converts it to `T`, if the
+    /// request has a JSON `Content-Type` header. Fails with [`ErrorKind::UnsupportedMediaType`]
+    /// otherwise.
+    pub fn from_request(request: Request<Bytes>) -> Result<Json<T>, JsonError> {
+        match request.headers().get(CONTENT_TYPE).map(|value| value.to_str()) {
+            Some(Ok(content_type)) => T::from_jvalue(&parse_http_body(request.body(), Some(content_type))?).map(Json),
+            Some(Err(_)) => Err(JsonError::new(
+                ErrorKind::UnsupportedMediaType, "The content type is not readable")),
+            None => Err(JsonError::new(ErrorKind::UnsupportedMediaType, "The request has no content type"))
+        }
+    }
+}
+
+impl<T: Serialize> Json<T> {
+    /// Serializes the value into a `200 OK` response with a JSON `Content-Type` header
+    pub fn into_response(self) -> Response<Bytes> {
+        self.into_response_with_status(StatusCode::OK)
+    }
+
+    /// Serializes the value into a response with the given status and a JSON `Content-Type`
+    /// header
+    pub fn into_response_with_status(self, status: StatusCode) -> Response<Bytes> {
+        let mut response = Response::new(Bytes::from(self.0.serialize()));
+        *response.status_mut() = status;
+        response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use http::{Request, StatusCode};
+
+    use crate::error::ErrorKind;
+    use crate::http::{is_json_content_type, parse_http_body, Json};
+    use crate::json;
+
+    fn request(content_type: Option<&str>, body: &'static str) -> Request<Bytes> {
+        let mut builder = Request::post("/");
+        if let Some(content_type) = content_type {
+            builder = builder.header("content-type", content_type);
+        }
+        builder.body(Bytes::from_static(body.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_from_request() {
+        assert_eq!(Ok(Json(3)), Json::<u8>::from_request(request(Some("application/json"), "3")));
+        assert_eq!(Ok(Json(json!("[1]"))), Json::from_request(request(Some("application/x+json"), "[1]")));
+        for (content_type, kind) in [(Some("text/json"), ErrorKind::UnsupportedMediaType),
+                                     (None, ErrorKind::UnsupportedMediaType),
+                                     (Some("application/json"), ErrorKind::InvalidType)].iter() {
+            let error = Json::<u8>::from_request(request(*content_type, "\"x\"")).unwrap_err();
+            assert_eq!(*kind, error.kind());
+        }
+        assert_eq!(ErrorKind::Syntax, Json::<u8>::from_request(request(Some("application/json"), "{")).unwrap_err().kind());
+        assert!(!is_json_content_type("application/jsonx"));
+        assert!(!is_json_content_type("text/x+json"));
+    }
+
+    #[test]
+    fn test_parse_http_body_charsets() {
+        let expected = Ok(json!("[\"\\u00e9\\u20ac\\ud83d\\ude00\"]"));
+        let text = "[\"\u{e9}\u{20ac}\u{1f600}\"]";
+        let utf16: Vec<u16> = text.encode_utf16().collect();
+        let le: Vec<u8> = utf16.iter().flat_map(|unit| unit.to_le_bytes().to_vec()).collect();
+        let be: Vec<u8> = utf16.iter().flat_map(|unit| unit.to_be_bytes().to_vec()).collect();
+        let with_bom = |bom: &[u8], body: &[u8]| [bom, body].concat();
+        assert_eq!(expected, parse_http_body(text.as_bytes(), None));
+        assert_eq!(expected, parse_http_body(&with_bom(b"\xef\xbb\xbf", text.as_bytes()), Some("application/json")));
+        assert_eq!(expected, parse_http_body(&le, Some("application/json; charset=utf-16le")));
+        assert_eq!(expected, parse_http_body(&be, Some("application/json; charset=UTF-16BE")));
+        assert_eq!(expected, parse_http_body(&be, Some("application/json; charset=utf-16")));
+        assert_eq!(expected, parse_http_body(&with_bom(&[0xff, 0xfe], &le), Some("application/json; charset=utf-16")));
+        assert_eq!(Ok(json!("1")), parse_http_body(b"1", Some("application/json; charset=us-ascii")));
+    }
+
+    #[test]
+    fn test_parse_http_body_errors() {
+        let error = |body: &[u8], content_type| parse_http_body(body, Some(content_type)).unwrap_err();
+        assert_eq!(ErrorKind::UnsupportedCharset, error(b"1", "application/json; charset=koi8-r").kind());
+        assert_eq!(ErrorKind::UnsupportedMediaType, error(b"1", "text/plain; charset=utf-8").kind());
+        assert_eq!("Invalid UTF-16 in the body at byte 4",
+                   error(&[b'1', 0, b'2', 0, 0x00, 0xdc], "application/json; charset=utf-16le").message());
+        assert_eq!("Invalid UTF-16 in the body at byte 2", error(&[0, b'1', 0], "application/json; charset=utf-16be").message());
+        assert_eq!("Invalid US-ASCII in the body at byte 1", error(b"\"\xe9\"", "application/json; charset=ascii").message());
+        assert_eq!("Invalid UTF-8 in the body at byte 1", error(b"\"\xe9\"", "application/json").message());
+    }
+
+    #[test]
+    fn test_into_response() {
+        let response = Json(json!("{\"error\": \"gone\"}")).into_response_with_status(StatusCode::GONE);
+        assert_eq!(StatusCode::GONE, response.status());
+        assert_eq!("application/json", response.headers()["content-type"]);
+        assert_eq!(Bytes::from("{\"error\":\"gone\"}"), response.into_body());
+        assert_eq!(json!("2"), Json(json!("2")).into_inner());
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Documents indexed by JSON Pointer for repeated lookups.
+
+use std::collections::HashMap;
+
+use crate::convert::FromJValue;
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::transform::push_token;
+
+/// A borrowed [`JValue`] with an index from the JSON Pointer of every value in it to the value.
+/// Building the index walks the document once; every lookup after that is a single hash map
+/// lookup instead of a walk from the root.
+///
+/// ```
+/// # use json::json;
+/// # use json::indexed::IndexedDocument;
+/// let j_value = json!("{\"rules\": [{\"when\": {\"a~b\": 1}}, {\"when\": null}]}");
+/// let document = IndexedDocument::new(&j_value);
+/// assert_eq!(Some(&json!("1")), document.get("/rules/0/when/a~0b"));
+/// assert_eq!(j_value.pointer("/rules/1"), document.get("/rules/1"));
+/// assert_eq!(None, document.get("/rules/2"));
+/// assert_eq!(7, document.len());
+/// ```
+#[derive(Debug, Clone)]
+pub struct IndexedDocument<'a> {
+    value: &'a JValue,
+    index: HashMap<String, &'a JValue>,
+}
+
+impl<'a> IndexedDocument<'a> {
+    /// Indexes every value in `value`, including `value` itself at the pointer `""`.
+    pub fn new(value: &'a JValue) -> IndexedDocument<'a> {
+        let mut index = HashMap::new();
+        build(value, &mut String::new(), &mut index);
+        IndexedDocument { value, index }
+    }
+
+    /// Returns the document
+    pub fn value(&self) -> &'a JValue {
+        self.value
+    }
+
+    /// Returns the value at the given JSON Pointer, like [`JValue::pointer`]
+    pub fn get(&self, pointer: &str) -> Option<&'a JValue> {
+        self.index.get(pointer).copied()
+    }
+
+    /// Whether there is a value at the given JSON Pointer
+    pub fn contains(&self, pointer: &str) -> bool {
+        self.index.contains_key(pointer)
+    }
+
+    /// Converts the value at the given JSON Pointer to `T`, like [`JValue::get_path_as`]
+    pub fn get_as<T: FromJValue>(&self, pointer: &str) -> Result<T, JsonError> {
+        match self.get(pointer) {
+            Some(value) => T::from_jvalue(value).map_err(|e| JsonError::new(
+                e.kind(), format!("{} at {}", e.message(), pointer))),
+            None => Err(JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer)))
+        }
+    }
+
+    /// Returns the number of values indexed
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Always false, as the document itself is indexed
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Returns the pointers of all values, in no particular order
+    pub fn pointers(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(|pointer| pointer.as_str())
+    }
+}
+
+fn build<'a>(value: &'a JValue, path: &mut String, index: &mut HashMap<String, &'a JValue>) {
+    index.insert(path.clone(), value);
+    let len = path.len();
+    match value {
+        JValue::Object(o) => {
+            for (key, member) in o.iter() {
+                push_token(path, key);
+                build(member, path, index);
+                path.truncate(len);
+            }
+        }
+        JValue::Array(a) => {
+            for (i, element) in a.iter().enumerate() {
+                push_token(path, &i.to_string());
+                build(element, path, index);
+                path.truncate(len);
+            }
+        }
+        _ => ()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::indexed::IndexedDocument;
+    use crate::json;
+
+    #[test]
+    fn test_lookups_match_pointer() {
+        let j_value = json!("{\"a\": [1, {\"b/c\": [true]}], \"\": {\"\": 0}, \"d\": {}}");
+        let document = IndexedDocument::new(&j_value);
+        for pointer in document.pointers() {
+            assert_eq!(j_value.pointer(pointer), document.get(pointer), "{}", pointer);
+        }
+        assert_eq!(9, document.len());
+        assert!(document.contains("//"));
+        assert!(document.contains("/a/1/b~1c/0"));
+        assert!(!document.contains("/a/01"));
+        assert!(!document.is_empty());
+    }
+
+    #[test]
+    fn test_get_as() {
+        let j_value = json!("{\"timeout\": \"30\", \"flag\": true}");
+        let document = IndexedDocument::new(&j_value);
+        assert_eq!(Ok(30_u32), document.get_as::<u32>("/timeout"));
+        assert_eq!(ErrorKind::NotFound, document.get_as::<u32>("/none").unwrap_err().kind());
+        assert_eq!("Expected an integer, found the boolean true at /flag",
+                   document.get_as::<u32>("/flag").unwrap_err().message());
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A subset of [JSONPath] for selecting values from JSON data-structures.
+//!
+//! Supported syntax: the root `$`, child names `.name` and `['name']`, wildcards `.*` and
+//! `[*]`, indices `[0]` and `[-1]`, slices `[1:3]`, unions `['a','b']` and `[0,2]`, and
+//! recursive descent `..name`, `..*` and `..[0]`. Filter expressions are not supported.
+//!
+//! [JSONPath]:https://goessner.net/articles/JsonPath/
+
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+
+/// One step of the location of a value in a tree: an object key or an array index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathElement {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+    Union(Vec<Selector>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Child(Selector),
+    Descendant(Selector),
+}
+
+/// A compiled JSONPath expression.
+///
+/// ```
+/// # use json::json;
+/// # use json::jsonpath::JsonPath;
+/// let j_value = json!("{\"store\": {\"book\": [{\"price\": 8}, {\"price\": 12}], \"bike\": {\"price\": 20}}}");
+/// let path = JsonPath::parse("$.store.book[*].price").unwrap();
+/// assert_eq!(vec![&json!("8"), &json!("12")], path.select(&j_value));
+///
+/// let mut prices = JsonPath::parse("$..price").unwrap().select(&j_value);
+/// prices.sort();
+/// assert_eq!(vec![&json!("8"), &json!("12"), &json!("20")], prices);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+fn syntax_error(path: &str, message: &str) -> JsonError {
+    JsonError::new(ErrorKind::InvalidPointer, format!("Invalid JSONPath {}: {}", path, message))
+}
+
+impl JsonPath {
+    /// Compiles a JSONPath expression. The leading `$` is optional.
+    pub fn parse(path: &str) -> Result<JsonPath, JsonError> {
+        let chars: Vec<char> = path.chars().collect();
+        let mut i = 0;
+        if chars.first() == Some(&'$') {
+            i += 1;
+        }
+        let mut segments = Vec::new();
+        while i < chars.len() {
+            let descendant = chars[i] == '.' && chars.get(i + 1) == Some(&'.');
+            if descendant {
+                i += 2;
+            } else if chars[i] == '.' {
+                i += 1;
+            } else if chars[i] != '[' {
+                return Err(syntax_error(path, &format!("unexpected '{}' at {}", chars[i], i)));
+            }
+            let selector = if chars.get(i) == Some(&'[') {
+                let end = find_closing_bracket(&chars, i)
+                    .ok_or_else(|| syntax_error(path, "missing ']'"))?;
+                let selector = parse_bracket(&chars[i + 1..end].iter().collect::<String>())
+                    .map_err(|e| syntax_error(path, &e))?;
+                i = end + 1;
+                selector
+            } else {
+                let start = i;
+                while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                    i += 1;
+                }
+                let name: String = chars[start..i].iter().collect();
+                match name.as_str() {
+                    "" => return Err(syntax_error(path, &format!("missing name at {}", start))),
+                    "*" => Selector::Wildcard,
+                    _ => Selector::Name(name)
+                }
+            };
+            segments.push(if descendant { Segment::Descendant(selector) } else { Segment::Child(selector) });
+        }
+        Ok(JsonPath { segments })
+    }
+
+    /// Returns references to all the values the path selects
+    pub fn select<'a>(&self, value: &'a JValue) -> Vec<&'a JValue> {
+        let mut nodes = vec![value];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for node in nodes {
+                match segment {
+                    Segment::Child(selector) => select_children(node, selector, &mut next),
+                    Segment::Descendant(selector) => select_descendants(node, selector, &mut next),
+                }
+            }
+            nodes = next;
+        }
+        nodes
+    }
+
+    /// Returns `true` if the value at the given location is selected by the path. Negative
+    /// indices and slice bounds never match, since the array lengths are not known.
+    ///
+    /// ```
+    /// # use json::jsonpath::{JsonPath, PathElement};
+    /// let path = JsonPath::parse("$..user.password").unwrap();
+    /// let location = vec![PathElement::Key("users".to_string()), PathElement::Index(3),
+    ///                     PathElement::Key("user".to_string()), PathElement::Key("password".to_string())];
+    /// assert!(path.matches(&location));
+    /// assert!(!path.matches(&location[..3]));
+    /// ```
+    pub fn matches(&self, location: &[PathElement]) -> bool {
+        matches(&self.segments, location)
+    }
+}
+
+fn find_closing_bracket(chars: &[char], open: usize) -> Option<usize> {
+    let mut quote = None;
+    let mut i = open + 1;
+    while i < chars.len() {
+        match (quote, chars[i]) {
+            (Some(_), '\\') => i += 1,
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'') | (None, '"') => quote = Some(chars[i]),
+            (None, ']') => return Some(i),
+            _ => ()
+        }
+        i += 1;
+    }
+    None
+}
+
+fn parse_bracket(content: &str) -> Result<Selector, String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for c in content.chars() {
+        match quote {
+            Some(q) => {
+                current.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == ',' => parts.push(std::mem::take(&mut current)),
+            None => {
+                if c == '\'' || c == '"' {
+                    quote = Some(c);
+                }
+                current.push(c);
+            }
+        }
+    }
+    parts.push(current);
+    let mut selectors = parts.iter().map(|p| parse_selector(p.trim()))
+        .collect::<Result<Vec<Selector>, String>>()?;
+    if selectors.len() == 1 {
+        Ok(selectors.remove(0))
+    } else {
+        Ok(Selector::Union(selectors))
+    }
+}
+
+fn parse_selector(part: &str) -> Result<Selector, String> {
+    let quoted = part.len() >= 2
+        && ((part.starts_with('\'') && part.ends_with('\'')) || (part.starts_with('"') && part.ends_with('"')));
+    if quoted {
+        let mut name = String::new();
+        let mut chars = part[1..part.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(c) = chars.next() {
+                    name.push(c);
+                }
+            } else {
+                name.push(c);
+            }
+        }
+        Ok(Selector::Name(name))
+    } else if part == "*" {
+        Ok(Selector::Wildcard)
+    } else if let Some(colon) = part.find(':') {
+        let bound = |s: &str| -> Result<Option<i64>, String> {
+            let s = s.trim();
+            if s.is_empty() {
+                Ok(None)
+            } else {
+                s.parse().map(Some).map_err(|_| format!("invalid slice bound '{}'", s))
+            }
+        };
+        Ok(Selector::Slice(bound(&part[..colon])?, bound(&part[colon + 1..])?))
+    } else if part.starts_with('?') || part.starts_with('(') {
+        Err("filter and script expressions are not supported".to_string())
+    } else {
+        part.parse().map(Selector::Index).map_err(|_| format!("invalid selector '{}'", part))
+    }
+}
+
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    if index >= 0 && (index as usize) < len {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+fn select_children<'a>(value: &'a JValue, selector: &Selector, result: &mut Vec<&'a JValue>) {
+    match (selector, value) {
+        (Selector::Name(name), JValue::Object(o)) => result.extend(o.get(name)),
+        (Selector::Wildcard, JValue::Object(o)) => result.extend(o.iter().map(|(_, v)| v)),
+        (Selector::Wildcard, JValue::Array(a)) => result.extend(a.iter()),
+        (Selector::Index(i), JValue::Array(a)) =>
+            result.extend(resolve_index(*i, a.len()).map(|i| &a[i])),
+        (Selector::Slice(start, end), JValue::Array(a)) => {
+            let clamp = |b: i64| if b < 0 { (a.len() as i64 + b).max(0) as usize } else { (b as usize).min(a.len()) };
+            let start = start.map(clamp).unwrap_or(0);
+            let end = end.map(clamp).unwrap_or(a.len());
+            if start < end {
+                result.extend(a[start..end].iter());
+            }
+        }
+        (Selector::Union(selectors), _) => {
+            for selector in selectors {
+                select_children(value, selector, result);
+            }
+        }
+        _ => ()
+    }
+}
+
+fn select_descendants<'a>(value: &'a JValue, selector: &Selector, result: &mut Vec<&'a JValue>) {
+    select_children(value, selector, result);
+    match value {
+        JValue::Object(o) => {
+            for (_, v) in o.iter() {
+                select_descendants(v, selector, result);
+            }
+        }
+        JValue::Array(a) => {
+            for v in a {
+                select_descendants(v, selector, result);
+            }
+        }
+        _ => ()
+    }
+}
+
+fn selector_matches(selector: &Selector, element: &PathElement) -> bool {
+    match (selector, element) {
+        (Selector::Wildcard, _) => true,
+        (Selector::Name(name), PathElement::Key(k)) => name == k,
+        (Selector::Index(i), PathElement::Index(j)) => *i >= 0 && *i as usize == *j,
+        (Selector::Slice(start, end), PathElement::Index(j)) => {
+            let j = *j as i64;
+            start.is_none_or(|s| s >= 0 && j >= s) && end.is_none_or(|e| e >= 0 && j < e)
+        }
+        (Selector::Union(selectors), _) => selectors.iter().any(|s| selector_matches(s, element)),
+        _ => false
+    }
+}
+
+fn matches(segments: &[Segment], location: &[PathElement]) -> bool {
+    match segments.first() {
+        None => location.is_empty(),
+        Some(Segment::Child(selector)) => !location.is_empty()
+            && selector_matches(selector, &location[0])
+            && matches(&segments[1..], &location[1..]),
+        Some(Segment::Descendant(selector)) => (0..location.len()).any(|k| {
+            selector_matches(selector, &location[k]) && matches(&segments[1..], &location[k + 1..])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json;
+    use crate::jsonpath::{JsonPath, PathElement};
+
+    fn select(path: &str, json: &str) -> Vec<String> {
+        let j_value = crate::parser::parse(json).unwrap();
+        let mut result: Vec<String> = JsonPath::parse(path).unwrap().select(&j_value)
+            .iter().map(|v| v.to_string()).collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn test_select() {
+        let json = "{\"a\": [1, 2, 3, 4], \"b\": {\"a\": 5, \"c d\": 6}}";
+        assert_eq!(vec!["1"], select("$.a[0]", json));
+        assert_eq!(vec!["4"], select("$.a[-1]", json));
+        assert_eq!(vec!["2", "3"], select("$.a[1:3]", json));
+        assert_eq!(vec!["3", "4"], select("$.a[-2:]", json));
+        assert_eq!(vec!["1", "3"], select("$.a[0,2]", json));
+        assert_eq!(vec!["6"], select("$.b['c d']", json));
+        assert_eq!(vec!["5", "6"], select("$['b'][*]", json));
+        assert_eq!(vec!["5", "[1, 2, 3, 4]"], select("$..a", json));
+        assert_eq!(vec!["1", "5"], select("$..[0,'a']", json).into_iter()
+            .filter(|s| !s.starts_with('[')).collect::<Vec<String>>());
+        assert!(select("$.x.y", json).is_empty());
+        assert_eq!(vec![json!("{}").to_string()], select("$", "{}"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(JsonPath::parse("$.").is_err());
+        assert!(JsonPath::parse("$[").is_err());
+        assert!(JsonPath::parse("$[?(@.a)]").is_err());
+        assert!(JsonPath::parse("$x").is_err());
+        assert!(JsonPath::parse("$['a]").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let location = |elements: &[&str]| -> Vec<PathElement> {
+            elements.iter().map(|e| match e.parse() {
+                Ok(i) => PathElement::Index(i),
+                Err(_) => PathElement::Key(e.to_string())
+            }).collect()
+        };
+        let path = JsonPath::parse("$.a[*].b").unwrap();
+        assert!(path.matches(&location(&["a", "0", "b"])));
+        assert!(!path.matches(&location(&["a", "b"])));
+        let path = JsonPath::parse("$..b").unwrap();
+        assert!(path.matches(&location(&["b"])));
+        assert!(path.matches(&location(&["x", "1", "b"])));
+        assert!(!path.matches(&location(&["b", "x"])));
+        let path = JsonPath::parse("$.a[1:]").unwrap();
+        assert!(path.matches(&location(&["a", "1"])));
+        assert!(!path.matches(&location(&["a", "0"])));
+    }
+}
@@ -0,0 +1,512 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! [JSON-RPC 2.0] messages, for clients and servers exchanging them over any transport, such
+//! as WebSocket messages or HTTP bodies.
+//!
+//! [JSON-RPC 2.0]:https://www.jsonrpc.org/specification
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::parse;
+use crate::serializer::Serialize;
+
+/// The code of the error for a message that is not valid JSON
+pub const PARSE_ERROR: i64 = -32700;
+/// The code of the error for a message that is not a valid request
+pub const INVALID_REQUEST: i64 = -32600;
+/// The code of the error for a request to a method that does not exist
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The code of the error for a request with invalid parameters
+pub const INVALID_PARAMS: i64 = -32602;
+/// The code of the error for a request that failed inside the server
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// The identifier correlating a response with its request
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Id {
+    Number(i64),
+    String(String),
+    /// The id of the responses to messages whose id can not be read
+    Null,
+}
+
+/// A call that expects a [`Response`] with the same id
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub id: Id,
+    pub method: String,
+    /// An array or an object, if there are parameters
+    pub params: Option<JValue>,
+}
+
+/// A call that expects no response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub method: String,
+    /// An array or an object, if there are parameters
+    pub params: Option<JValue>,
+}
+
+/// The error of a failed call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<JValue>,
+}
+
+/// The outcome of a [`Request`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub id: Id,
+    pub result: Result<JValue, Error>,
+}
+
+/// Any message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Request(Request),
+    Notification(Notification),
+    Response(Response),
+}
+
+/// A single message or a batch of them, sent as an array
+#[derive(Debug, Clone, PartialEq)]
+pub enum Batch<T> {
+    Single(T),
+    Batch(Vec<T>),
+}
+
+impl Id {
+    fn to_jvalue(&self) -> JValue {
+        match self {
+            Id::Number(n) => number(*n),
+            Id::String(s) => JValue::String(JString::from(s.as_str())),
+            Id::Null => JValue::Null
+        }
+    }
+}
+
+impl FromJValue for Id {
+    /// Reads a string, an integer or `null`
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        match value {
+            JValue::String(s) => Ok(Id::String(s.to_string())),
+            JValue::Number(_) => i64::from_jvalue(value).map(Id::Number)
+                .map_err(|_| invalid(format!("the id is {}, which is not an integer", describe(value)))),
+            JValue::Null => Ok(Id::Null),
+            _ => Err(invalid(format!("the id is {}", describe(value))))
+        }
+    }
+}
+
+impl Request {
+    pub fn new(id: Id, method: &str, params: Option<JValue>) -> Request {
+        Request { id, method: method.to_string(), params }
+    }
+
+    /// Returns the response with the outcome of this request
+    pub fn respond(&self, result: Result<JValue, Error>) -> Response {
+        Response { id: self.id.clone(), result }
+    }
+}
+
+impl Notification {
+    pub fn new(method: &str, params: Option<JValue>) -> Notification {
+        Notification { method: method.to_string(), params }
+    }
+}
+
+impl Error {
+    pub fn new(code: i64, message: &str) -> Error {
+        Error { code, message: message.to_string(), data: None }
+    }
+
+    /// Adds details about the error
+    pub fn with_data(mut self, data: JValue) -> Error {
+        self.data = Some(data);
+        self
+    }
+
+    /// The error for a request to the given method, which does not exist
+    pub fn method_not_found(method: &str) -> Error {
+        Error::new(METHOD_NOT_FOUND, &format!("Method not found: {}", method))
+    }
+
+    /// The error for a request with invalid parameters, described by `error` such as the one
+    /// of converting them
+    pub fn invalid_params(error: &JsonError) -> Error {
+        Error::new(INVALID_PARAMS, "Invalid params").with_data(JValue::String(JString::from(error.message())))
+    }
+}
+
+impl Message {
+    /// Returns the message as an object with a `"jsonrpc": "2.0"` member
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = JObject::new();
+        object.insert("jsonrpc".to_string(), JValue::String(JString::from("2.0")));
+        let (method, params) = match self {
+            Message::Request(request) => {
+                object.insert("id".to_string(), request.id.to_jvalue());
+                (&request.method, &request.params)
+            }
+            Message::Notification(notification) => (&notification.method, &notification.params),
+            Message::Response(response) => {
+                object.insert("id".to_string(), response.id.to_jvalue());
+                match &response.result {
+                    Ok(result) => object.insert("result".to_string(), result.clone()),
+                    Err(error) => object.insert("error".to_string(), error.to_jvalue())
+                };
+                return JValue::Object(object);
+            }
+        };
+        object.insert("method".to_string(), JValue::String(JString::from(method.as_str())));
+        if let Some(params) = params {
+            object.insert("params".to_string(), params.clone());
+        }
+        JValue::Object(object)
+    }
+}
+
+impl Error {
+    fn to_jvalue(&self) -> JValue {
+        let mut object = JObject::new();
+        object.insert("code".to_string(), number(self.code));
+        object.insert("message".to_string(), JValue::String(JString::from(self.message.as_str())));
+        if let Some(data) = &self.data {
+            object.insert("data".to_string(), data.clone());
+        }
+        JValue::Object(object)
+    }
+}
+
+impl FromJValue for Error {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        let code = match value.get_path_as::<i64>("/code") {
+            Ok(code) => code,
+            Err(e) => return Err(invalid(format!("the error has no integer code: {}", e.message())))
+        };
+        match &value["message"] {
+            JValue::String(message) => Ok(Error { code, message: message.to_string(), data: member(value, "data") }),
+            other => Err(invalid(format!("the error message is {}", describe(other))))
+        }
+    }
+}
+
+impl FromJValue for Message {
+    /// Reads a request, a notification or a response. A message with a `method` is a request
+    /// if it has an `id`, even `null`, and a notification otherwise.
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        if !matches!(value, JValue::Object(_)) {
+            return Err(invalid(format!("the message is {}", describe(value))));
+        }
+        match &value["jsonrpc"] {
+            JValue::String(version) if version.as_str() == "2.0" => (),
+            other => return Err(invalid(format!("the version is {} instead of \"2.0\"", describe(other))))
+        }
+        let id = member(value, "id").map(|id| Id::from_jvalue(&id)).transpose()?;
+        if let Some(method) = member(value, "method") {
+            let method = match method {
+                JValue::String(method) => method.to_string(),
+                other => return Err(invalid(format!("the method is {}", describe(&other))))
+            };
+            let params = member(value, "params");
+            if let Some(params) = &params {
+                if !matches!(params, JValue::Array(_) | JValue::Object(_)) {
+                    return Err(invalid(format!("the params are {}", describe(params))));
+                }
+            }
+            return Ok(match id {
+                Some(id) => Message::Request(Request { id, method, params }),
+                None => Message::Notification(Notification { method, params })
+            });
+        }
+        let id = id.ok_or_else(|| invalid("the message has neither a method nor an id".to_string()))?;
+        let result = match (member(value, "result"), member(value, "error")) {
+            (Some(result), None) => Ok(result),
+            (None, Some(error)) => Err(Error::from_jvalue(&error)?),
+            _ => return Err(invalid("a response must have either a result or an error".to_string()))
+        };
+        Ok(Message::Response(Response { id, result }))
+    }
+}
+
+impl Batch<Message> {
+    /// Parses a message or a batch of messages as a server receives them. Every message that
+    /// is not valid is replaced by the error to respond with, as is a document that is not
+    /// valid JSON or an empty batch. The response with the error has the id `null`.
+    ///
+    /// ```
+    /// # use json::jsonrpc::{Batch, Message, INVALID_REQUEST};
+    /// let batch = Batch::parse("[{\"jsonrpc\": \"2.0\", \"method\": \"ping\"}, {\"jsonrpc\": \"1.0\"}]");
+    /// let messages = batch.into_vec();
+    /// assert!(matches!(&messages[0], Ok(Message::Notification(n)) if n.method == "ping"));
+    /// assert_eq!(INVALID_REQUEST, messages[1].as_ref().unwrap_err().code);
+    /// ```
+    // A number keeps its text with preserve-numbers, which makes the error as large as a value
+    #[cfg_attr(feature = "preserve-numbers", allow(clippy::result_large_err))]
+    pub fn parse(text: &str) -> Batch<Result<Message, Error>> {
+        let invalid_request = |error: JsonError| Error::new(INVALID_REQUEST, "Invalid Request")
+            .with_data(JValue::String(JString::from(error.message())));
+        match parse(text) {
+            Ok(JValue::Array(messages)) if messages.is_empty() => {
+                Batch::Single(Err(invalid_request(invalid("the batch is empty".to_string()))))
+            }
+            Ok(JValue::Array(messages)) => Batch::Batch(messages.iter()
+                .map(|message| Message::from_jvalue(message).map_err(invalid_request))
+                .collect()),
+            Ok(message) => Batch::Single(Message::from_jvalue(&message).map_err(invalid_request)),
+            Err(e) => Batch::Single(Err(Error::new(PARSE_ERROR, "Parse error")
+                .with_data(JValue::String(JString::from(e.message())))))
+        }
+    }
+
+    /// Serializes the message, or the batch as an array
+    pub fn serialize(&self) -> String {
+        match self {
+            Batch::Single(message) => message.to_jvalue().serialize(),
+            Batch::Batch(messages) => JValue::Array(messages.iter().map(Message::to_jvalue).collect()).serialize()
+        }
+    }
+}
+
+impl<T> Batch<T> {
+    /// Returns the messages, one for a single message
+    pub fn into_vec(self) -> Vec<T> {
+        match self {
+            Batch::Single(message) => vec![message],
+            Batch::Batch(messages) => messages
+        }
+    }
+}
+
+/// Handles a message or a batch of messages as a server, passing the method and params of
+/// every request and notification to `handler`, and returns the serialized reply. The results
+/// for notifications are dropped, and there is no reply if there are only notifications.
+/// Responses sent to the server are ignored.
+///
+/// ```
+/// # use json::json;
+/// # use json::jsonrpc::{handle, Error};
+/// let reply = handle("[{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"add\", \"params\": [2, 3]},\
+///                      {\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"sub\"}]", |method, params| {
+///     match method {
+///         "add" => Ok(json!("5")),
+///         _ => Err(Error::method_not_found(method))
+///     }
+/// });
+/// let reply = json::parser::parse(&reply.unwrap()).unwrap();
+/// assert_eq!(json!("5"), reply[0]["result"]);
+/// assert_eq!(json!("-32601"), reply[1]["error"]["code"]);
+/// ```
+pub fn handle<F>(text: &str, mut handler: F) -> Option<String>
+    where F: FnMut(&str, Option<&JValue>) -> Result<JValue, Error> {
+    let mut reply = |message: Result<Message, Error>| match message {
+        Ok(Message::Request(request)) => {
+            let result = handler(&request.method, request.params.as_ref());
+            Some(Message::Response(request.respond(result)))
+        }
+        Ok(Message::Notification(notification)) => {
+            // A notification gets no reply, even if it fails
+            let _ = handler(&notification.method, notification.params.as_ref());
+            None
+        }
+        Ok(Message::Response(_)) => None,
+        Err(error) => Some(Message::Response(Response { id: Id::Null, result: Err(error) }))
+    };
+    let replies = match Batch::parse(text) {
+        Batch::Single(message) => reply(message).map(Batch::Single),
+        Batch::Batch(messages) => {
+            let replies: Vec<Message> = messages.into_iter().filter_map(&mut reply).collect();
+            if replies.is_empty() { None } else { Some(Batch::Batch(replies)) }
+        }
+    };
+    replies.map(|replies| replies.serialize())
+}
+
+/// The method of a request and the outcome of the call
+pub type Outcome = (String, Result<JValue, Error>);
+
+/// Assigns the ids of the requests a client sends and matches the responses with them
+///
+/// ```
+/// # use json::json;
+/// # use json::jsonrpc::{Batch, Correlator, Message};
+/// let mut correlator = Correlator::new();
+/// let request = correlator.request("echo", Some(json!("[\"hi\"]")));
+/// let sent = Batch::Single(Message::Request(request)).serialize();
+/// # assert!(sent.contains("\"id\":1"));
+///
+/// let (method, result) = correlator.resolve("{\"jsonrpc\": \"2.0\", \"id\": 1, \"result\": \"hi\"}").unwrap().remove(0);
+/// assert_eq!(("echo".to_string(), Ok(json!("\"hi\""))), (method, result));
+/// assert!(correlator.is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Correlator {
+    next_id: i64,
+    /// The method of every request without a response yet, by id
+    pending: HashMap<Id, String>,
+}
+
+impl Correlator {
+    pub fn new() -> Correlator {
+        Correlator::default()
+    }
+
+    /// Creates a request with a new id, and waits for its response
+    pub fn request(&mut self, method: &str, params: Option<JValue>) -> Request {
+        self.next_id += 1;
+        let id = Id::Number(self.next_id);
+        self.pending.insert(id.clone(), method.to_string());
+        Request::new(id, method, params)
+    }
+
+    /// Parses a response or a batch of responses, and returns the method of the request and
+    /// the outcome for each, in the order of the responses. Fails if a message is not a
+    /// response or has the id of no request waiting for a response; the responses before it
+    /// are resolved.
+    pub fn resolve(&mut self, text: &str) -> Result<Vec<Outcome>, JsonError> {
+        let messages = match parse(text)? {
+            JValue::Array(messages) => messages,
+            message => vec![message]
+        };
+        let mut outcomes = Vec::with_capacity(messages.len());
+        for message in messages.iter() {
+            let response = match Message::from_jvalue(message)? {
+                Message::Response(response) => response,
+                _ => return Err(invalid("a client can only receive responses".to_string()))
+            };
+            match self.pending.remove(&response.id) {
+                Some(method) => outcomes.push((method, response.result)),
+                None => return Err(JsonError::new(
+                    ErrorKind::NotFound, format!("No request is waiting for a response with the id {}",
+                                                 response.id.to_jvalue().serialize())))
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Returns the number of requests without a response yet
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether every request has its response
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+fn invalid(problem: String) -> JsonError {
+    JsonError::new(ErrorKind::InvalidType, format!("Invalid JSON-RPC message: {}", problem))
+}
+
+fn number(n: i64) -> JValue {
+    JValue::Number(JNumber::from_str(&n.to_string()).unwrap())
+}
+
+/// Returns a copy of the member named `key`, if the object has one
+fn member(object: &JValue, key: &str) -> Option<JValue> {
+    match object {
+        JValue::Object(o) => o.get(&key.to_string()).cloned(),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::FromJValue;
+    use crate::data_structures::JValue;
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::jsonrpc::{handle, Batch, Correlator, Error, Id, Message, Notification, Request, Response,
+                         INVALID_REQUEST, PARSE_ERROR};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_round_trip() {
+        let messages = vec![
+            Message::Request(Request::new(Id::String("a".to_string()), "f", Some(json!("{\"x\": 1}")))),
+            Message::Request(Request::new(Id::Null, "g", None)),
+            Message::Notification(Notification::new("h", Some(json!("[]")))),
+            Message::Response(Response { id: Id::Number(-3), result: Ok(json!("null")) }),
+            Message::Response(Response { id: Id::Number(4), result: Err(Error::new(1, "no").with_data(json!("[2]"))) }),
+        ];
+        for message in messages.iter() {
+            assert_eq!(Ok(message.clone()), Message::from_jvalue(&message.to_jvalue()));
+        }
+        let batch = Batch::Batch(messages.clone());
+        let parsed: Vec<Message> = Batch::parse(&batch.serialize()).into_vec().into_iter().map(Result::unwrap).collect();
+        assert_eq!(messages, parsed);
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        for text in ["1", "{\"method\": \"f\"}", "{\"jsonrpc\": \"2.0\", \"method\": 1}",
+                     "{\"jsonrpc\": \"2.0\", \"method\": \"f\", \"params\": 1}",
+                     "{\"jsonrpc\": \"2.0\", \"id\": 1.5, \"method\": \"f\"}",
+                     "{\"jsonrpc\": \"2.0\", \"id\": 1}", "{\"jsonrpc\": \"2.0\", \"id\": 1, \"result\": 1, \"error\": {}}",
+                     "{\"jsonrpc\": \"2.0\", \"id\": 1, \"error\": {\"code\": \"x\", \"message\": \"m\"}}"].iter() {
+            let error = Message::from_jvalue(&parse(text).unwrap()).unwrap_err();
+            assert_eq!(ErrorKind::InvalidType, error.kind(), "{}", text);
+            assert!(error.message().starts_with("Invalid JSON-RPC message: "), "{}", error);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "preserve-numbers", allow(clippy::result_large_err))]
+    fn test_handle() {
+        let mut calls = Vec::new();
+        let mut handler = |method: &str, _: Option<&JValue>| {
+            calls.push(method.to_string());
+            Ok(json!("true"))
+        };
+        assert_eq!(None, handle("{\"jsonrpc\": \"2.0\", \"method\": \"n\"}", &mut handler));
+        assert_eq!(None, handle("[{\"jsonrpc\": \"2.0\", \"method\": \"n\"}]", &mut handler));
+        let code = |reply: &JValue| i64::from_jvalue(&reply["error"]["code"]).unwrap();
+        let reply = parse(&handle("[]", &mut handler).unwrap()).unwrap();
+        assert_eq!(INVALID_REQUEST, code(&reply));
+        let reply = parse(&handle("[1, {\"jsonrpc\": \"2.0\", \"id\": \"r\", \"method\": \"m\"}]", &mut handler).unwrap()).unwrap();
+        assert_eq!((INVALID_REQUEST, json!("null")), (code(&reply[0]), reply[0]["id"].clone()));
+        assert_eq!((json!("\"r\""), json!("true")), (reply[1]["id"].clone(), reply[1]["result"].clone()));
+        assert_eq!(vec!["n", "n", "m"], calls);
+        let reply = parse(&handle("{", |_, _| unreachable!()).unwrap()).unwrap();
+        assert_eq!((PARSE_ERROR, json!("null")), (code(&reply), reply["id"].clone()));
+    }
+
+    #[test]
+    fn test_correlator() {
+        let mut correlator = Correlator::new();
+        let first = correlator.request("a", None);
+        let second = correlator.request("b", None);
+        assert_ne!(first.id, second.id);
+        assert_eq!(2, correlator.len());
+        let outcomes = correlator.resolve("[{\"jsonrpc\": \"2.0\", \"id\": 2, \"error\": {\"code\": 5, \"message\": \"m\"}},\
+                                            {\"jsonrpc\": \"2.0\", \"id\": 1, \"result\": 0}]").unwrap();
+        assert_eq!(vec![("b".to_string(), Err(Error::new(5, "m"))), ("a".to_string(), Ok(json!("0")))], outcomes);
+        let error = correlator.resolve("{\"jsonrpc\": \"2.0\", \"id\": 1, \"result\": 0}").unwrap_err();
+        assert_eq!("No request is waiting for a response with the id 1", error.message());
+        assert_eq!(ErrorKind::InvalidType,
+                   correlator.resolve("{\"jsonrpc\": \"2.0\", \"method\": \"x\"}").unwrap_err().kind());
+    }
+}
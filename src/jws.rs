@@ -0,0 +1,192 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Signing of JSON payloads as [JSON Web Signatures] with HMAC-SHA256 (`HS256`).
+//!
+//! The signed payload is the canonical serialization of the value (see
+//! [`JValue::to_canonical_string`]), so a signature stays valid when the document is
+//! re-serialized with a different key order or whitespace. A value with no canonical form, as it
+//! has a number out of the range of a double, can be neither signed nor verified.
+//!
+//! [JSON Web Signatures]:https://datatracker.ietf.org/doc/html/rfc7515
+//!
+//! ```
+//! # use json::json;
+//! # use json::jws;
+//! let key = b"webhook secret";
+//! let token = jws::sign(&json!("{\"event\": \"push\", \"id\": 1}"), key).unwrap();
+//! assert_eq!(Ok(json!("{\"id\": 1, \"event\": \"push\"}")), jws::verify(&token, key));
+//! assert!(jws::verify(&token, b"another secret").is_err());
+//! ```
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::base64;
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+
+/// The protected header of every signature, `{"alg":"HS256"}`
+const HEADER: &str = "{\"alg\":\"HS256\"}";
+
+fn mac(key: &[u8]) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+fn signature(key: &[u8], header: &str, payload: &str) -> String {
+    let mut mac = mac(key);
+    mac.update(header.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    base64::encode(&mac.finalize().into_bytes(), true)
+}
+
+fn encoded_payload(value: &JValue) -> Result<String, JsonError> {
+    value.to_canonical_string().map(|payload| base64::encode(payload.as_bytes(), true))
+}
+
+/// Signs `value` with `key`, returning a JWS in compact serialization
+/// `header.payload.signature` that embeds the payload. Fails like
+/// [`JValue::to_canonical_string`] if the value has no canonical form.
+pub fn sign(value: &JValue, key: &[u8]) -> Result<String, JsonError> {
+    let header = base64::encode(HEADER.as_bytes(), true);
+    let payload = encoded_payload(value)?;
+    let signature = signature(key, &header, &payload);
+    Ok(format!("{}.{}.{}", header, payload, signature))
+}
+
+/// Signs `value` with `key`, returning a JWS with a detached payload `header..signature`, to be
+/// sent alongside the document, e.g. in an HTTP header. Fails like [`sign`].
+///
+/// ```
+/// # use json::json;
+/// # use json::jws;
+/// let body = json!("{\"event\": \"push\"}");
+/// let signature = jws::sign_detached(&body, b"secret").unwrap();
+/// assert!(signature.contains(".."));
+/// assert_eq!(Ok(()), jws::verify_detached(&body, &signature, b"secret"));
+/// assert!(jws::verify_detached(&json!("{\"event\": \"pull\"}"), &signature, b"secret").is_err());
+/// ```
+pub fn sign_detached(value: &JValue, key: &[u8]) -> Result<String, JsonError> {
+    let header = base64::encode(HEADER.as_bytes(), true);
+    let signature = signature(key, &header, &encoded_payload(value)?);
+    Ok(format!("{}..{}", header, signature))
+}
+
+/// Verifies a token created by [`sign`] and returns its payload. Fails with
+/// [`ErrorKind::Syntax`] on a malformed token, with [`ErrorKind::InvalidSignature`] if the
+/// signature does not match or the algorithm is not `HS256`, and with
+/// [`ErrorKind::InvalidType`] if the payload has no canonical form, as no token [`sign`] creates
+/// has such a payload.
+pub fn verify(token: &str, key: &[u8]) -> Result<JValue, JsonError> {
+    let (header, payload, signature) = split(token)?;
+    if payload.is_empty() {
+        return Err(JsonError::new(ErrorKind::Syntax, "The token has a detached payload"));
+    }
+    check(key, header, payload, signature)?;
+    let payload = base64::decode(payload, true)
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| JsonError::new(ErrorKind::Syntax, "The payload is not valid base64url"))?;
+    let value = crate::parser::parse(&payload)?;
+    value.to_canonical_string()?;
+    Ok(value)
+}
+
+/// Verifies a detached signature created by [`sign_detached`] for `value`. A token embedding
+/// its payload is accepted too, as long as the payload matches `value`. Fails like [`verify`],
+/// so a value with no canonical form never verifies.
+pub fn verify_detached(value: &JValue, signature: &str, key: &[u8]) -> Result<(), JsonError> {
+    let (header, payload, signature) = split(signature)?;
+    let expected = encoded_payload(value)?;
+    if !payload.is_empty() && payload != expected {
+        return Err(JsonError::new(ErrorKind::InvalidSignature, "The embedded payload does not match"));
+    }
+    check(key, header, &expected, signature)
+}
+
+fn split(token: &str) -> Result<(&str, &str, &str), JsonError> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    match parts.as_slice() {
+        [header, payload, signature] => Ok((header, payload, signature)),
+        _ => Err(JsonError::new(ErrorKind::Syntax, "Expected a token of three parts separated by '.'"))
+    }
+}
+
+fn check(key: &[u8], header: &str, payload: &str, signature: &str) -> Result<(), JsonError> {
+    let decoded_header = base64::decode(header, true)
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|header| crate::parser::parse(&header).ok())
+        .ok_or_else(|| JsonError::new(ErrorKind::Syntax, "The header is not valid base64url JSON"))?;
+    match decoded_header.pointer("/alg") {
+        Some(JValue::String(alg)) if alg == "HS256" => (),
+        _ => return Err(JsonError::new(ErrorKind::InvalidSignature, "Unsupported algorithm, expected HS256"))
+    }
+    let signature = base64::decode(signature, true)
+        .ok_or_else(|| JsonError::new(ErrorKind::Syntax, "The signature is not valid base64url"))?;
+    let mut mac = mac(key);
+    mac.update(header.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| JsonError::new(ErrorKind::InvalidSignature, "The signature does not match"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::base64;
+    use crate::jws::{sign, sign_detached, signature, verify, verify_detached, HEADER};
+
+    #[test]
+    fn test_canonical_payload() {
+        let a = sign(&json!("{\"a\": 1.0, \"b\": [true]}"), b"k").unwrap();
+        let b = sign(&json!("{ \"b\": [true], \"a\": 1 }"), b"k").unwrap();
+        assert_eq!(a, b);
+        assert_eq!("eyJhbGciOiJIUzI1NiJ9.eyJhIjoxLCJiIjpbdHJ1ZV19",
+                   a.rsplit_once('.').unwrap().0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let token = sign(&json!("[1]"), b"k").unwrap();
+        let kind = |token: &str| verify(token, b"k").unwrap_err().kind();
+        assert_eq!(ErrorKind::Syntax, kind("abc"));
+        assert_eq!(ErrorKind::Syntax, kind(&sign_detached(&json!("[1]"), b"k").unwrap()));
+        let (head, _) = token.rsplit_once('.').unwrap();
+        assert_eq!(ErrorKind::InvalidSignature, kind(&format!("{}.{}", head, "AAAA")));
+        // {"alg":"none"}
+        let unsigned = token.replacen("eyJhbGciOiJIUzI1NiJ9", "eyJhbGciOiJub25lIn0", 1);
+        assert_eq!(ErrorKind::InvalidSignature, kind(&unsigned));
+        assert_eq!(ErrorKind::InvalidSignature, verify_detached(&json!("[2]"), &token, b"k").unwrap_err().kind());
+        assert_eq!(Ok(()), verify_detached(&json!("[1]"), &token, b"k"));
+    }
+
+    #[test]
+    fn test_payloads_without_canonical_form() {
+        let out_of_range = json!("{\"a\": 1e400}");
+        assert_eq!(ErrorKind::InvalidType, sign(&out_of_range, b"k").unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidType, sign_detached(&out_of_range, b"k").unwrap_err().kind());
+        // Once written as null, numbers out of range verified against signatures of null
+        let signature_of_null = sign_detached(&json!("{\"a\": null}"), b"k").unwrap();
+        assert_eq!(ErrorKind::InvalidType, verify_detached(&out_of_range, &signature_of_null, b"k").unwrap_err().kind());
+        let header = base64::encode(HEADER.as_bytes(), true);
+        let payload = base64::encode(b"{\"a\":1e400}", true);
+        let token = format!("{}.{}.{}", header, payload, signature(b"k", &header, &payload));
+        assert_eq!(ErrorKind::InvalidType, verify(&token, b"k").unwrap_err().kind());
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Documents that are scanned once and parsed lazily, one value at a time.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::convert::FromJValue;
+use crate::data_structures::{JNumber, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::{parse, ParseOptions};
+use crate::parser::{tokenize, Token};
+use crate::pointer::{array_index, parse_pointer};
+
+/// A document checked once and indexed as a tape of the positions of its values, like the tape
+/// of simdjson. No [`JValue`] is built until one is asked for: [`LazyDocument::get`] walks the
+/// tape to the value at a JSON Pointer, skipping whole subtrees at a time, and parses only that
+/// value. Reading a few fields of a large document costs one scan and the fields themselves.
+///
+/// ```
+/// # use json::json;
+/// # use json::lazy::LazyDocument;
+/// let document = LazyDocument::parse("{\"meta\": {\"count\": 2}, \"items\": [{\"id\": 1}, {\"id\": 2}]}").unwrap();
+/// assert_eq!(Ok(Some(json!("{\"id\": 2}"))), document.get("/items/1"));
+/// assert_eq!(Some("2"), document.raw("/meta/count"));
+/// assert_eq!(Ok(2), document.get_as::<u32>("/meta/count"));
+/// assert_eq!(Ok(None), document.get("/items/2"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LazyDocument<'s> {
+    source: &'s str,
+    tape: Vec<Node>,
+}
+
+/// A value, or the key of an object member, which is followed by the member's value
+#[derive(Debug, Clone)]
+struct Node {
+    kind: Kind,
+    /// The byte range of the value's text in the source
+    start: usize,
+    end: usize,
+    /// The index of the node after the value and everything inside it
+    next: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Object,
+    Array,
+    Scalar,
+    /// A key, with its text if it differs from the text between the quotes
+    Key(Option<Box<str>>),
+}
+
+impl<'s> LazyDocument<'s> {
+    /// Checks `source` as [`parse`] does and builds its tape. Unlike [`parse`], anything but
+    /// whitespace after the value is an error. Values may be nested as deep as the default
+    /// [`ParseOptions::max_depth`] allows.
+    pub fn parse(source: &'s str) -> Result<LazyDocument<'s>, JsonError> {
+        let tokens = tokenize(source)?;
+        let mut builder = Builder { source, tokens: &tokens, position: 0, tape: Vec::new(), depth: 0,
+                                    max_depth: ParseOptions::default().max_depth };
+        builder.value()?;
+        if let Some((token, offset)) = tokens.get(builder.position) {
+            return Err(builder.error(format!("Invalid token '{}' after the document", token), *offset,
+                                     "remove everything after the value"));
+        }
+        Ok(LazyDocument { source, tape: builder.tape })
+    }
+
+    /// Returns the document's text
+    pub fn source(&self) -> &'s str {
+        self.source
+    }
+
+    /// Returns the text of the value at the given JSON Pointer, as it is written in the document
+    pub fn raw(&self, pointer: &str) -> Option<&'s str> {
+        self.find(pointer).map(|node| &self.source[node.start..node.end])
+    }
+
+    /// Parses the value at the given JSON Pointer, like [`JValue::pointer`] on the parsed document.
+    /// The tape holds only values [`parse`] accepts, so an error is not expected.
+    pub fn get(&self, pointer: &str) -> Result<Option<JValue>, JsonError> {
+        self.raw(pointer).map(parse).transpose()
+    }
+
+    /// Whether there is a value at the given JSON Pointer, without parsing it
+    pub fn contains(&self, pointer: &str) -> bool {
+        self.find(pointer).is_some()
+    }
+
+    /// Parses and converts the value at the given JSON Pointer to `T`, like
+    /// [`JValue::get_path_as`]
+    pub fn get_as<T: FromJValue>(&self, pointer: &str) -> Result<T, JsonError> {
+        parse_pointer(pointer)?;
+        match self.get(pointer)? {
+            Some(value) => T::from_jvalue(&value).map_err(|e| JsonError::new(
+                e.kind(), format!("{} at {}", e.message(), pointer))),
+            None => Err(JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer)))
+        }
+    }
+
+    fn find(&self, pointer: &str) -> Option<&Node> {
+        let mut i = 0;
+        for token in parse_pointer(pointer).ok()? {
+            let parent = &self.tape[i];
+            let mut child = i + 1;
+            match parent.kind {
+                Kind::Object => {
+                    while child < parent.next && !self.key_is(&self.tape[child], &token) {
+                        child = self.tape[child + 1].next;
+                    }
+                    child += 1;
+                }
+                Kind::Array => {
+                    for _ in 0..array_index(&token)? {
+                        if child >= parent.next {
+                            return None;
+                        }
+                        child = self.tape[child].next;
+                    }
+                }
+                _ => return None
+            }
+            if child >= parent.next {
+                return None;
+            }
+            i = child;
+        }
+        Some(&self.tape[i])
+    }
+
+    fn key_is(&self, node: &Node, token: &str) -> bool {
+        match &node.kind {
+            Kind::Key(Some(key)) => key.as_ref() == token,
+            _ => &self.source[node.start + 1..node.end - 1] == token
+        }
+    }
+}
+
+struct Builder<'s, 't> {
+    source: &'s str,
+    tokens: &'t [(Token<'s>, usize)],
+    position: usize,
+    tape: Vec<Node>,
+    /// The number of arrays and objects the builder is in, and its limit
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'s, 't> Builder<'s, 't> {
+    /// Returns the next token with its offset, or the end of the input
+    fn next(&mut self) -> (Option<&'t Token<'s>>, usize) {
+        let tokens = self.tokens;
+        match tokens.get(self.position) {
+            Some((token, offset)) => {
+                self.position += 1;
+                (Some(token), *offset)
+            }
+            None => (None, self.source.len())
+        }
+    }
+
+    fn error(&self, message: String, offset: usize, hint: &str) -> JsonError {
+        JsonError::new(ErrorKind::Syntax, message).with_position(self.source, offset).with_hint(hint)
+    }
+
+    /// Adds a node for the token just consumed, which starts at `start`
+    fn leaf(&mut self, kind: Kind, start: usize) {
+        let following = self.tokens.get(self.position).map_or(self.source.len(), |(_, offset)| *offset);
+        let end = start + self.source[start..following].trim_end().len();
+        let next = self.tape.len() + 1;
+        self.tape.push(Node { kind, start, end, next });
+    }
+
+    fn value(&mut self) -> Result<(), JsonError> {
+        match self.next() {
+            (Some(Token::CurlyBracketOpen), offset) => self.object(offset),
+            (Some(Token::SquareBracketOpen), offset) => self.array(offset),
+            (Some(Token::Number(n)), offset) => {
+                JNumber::from_str(n).map_err(|e| JsonError::new(ErrorKind::Syntax, e)
+                    .with_position(self.source, offset))?;
+                self.leaf(Kind::Scalar, offset);
+                Ok(())
+            }
+            (Some(Token::String(_) | Token::True | Token::False | Token::Null), offset) => {
+                self.leaf(Kind::Scalar, offset);
+                Ok(())
+            }
+            (Some(t), offset) => Err(self.error(format!("Invalid token '{}'", t), offset, "expected a value")),
+            (None, offset) => Err(self.error("No Token Found".to_string(), offset, "expected a value"))
+        }
+    }
+
+    /// Adds the container node starting at `start` once everything inside it is added
+    fn container(&mut self, kind: Kind, start: usize, inside: impl FnOnce(&mut Self) -> Result<usize, JsonError>)
+                 -> Result<(), JsonError> {
+        if self.depth == self.max_depth {
+            let message = format!("The value is nested deeper than the limit of {} levels", self.max_depth);
+            return Err(self.error(message, start, "expected fewer nested arrays and objects"));
+        }
+        let index = self.tape.len();
+        self.tape.push(Node { kind, start, end: start, next: index });
+        self.depth += 1;
+        let close = inside(self)?;
+        self.depth -= 1;
+        let next = self.tape.len();
+        let node = &mut self.tape[index];
+        node.end = close + 1;
+        node.next = next;
+        Ok(())
+    }
+
+    fn array(&mut self, start: usize) -> Result<(), JsonError> {
+        let missing_bracket = "Invalid JSON array! Missing a closing square bracket \"]\"";
+        self.container(Kind::Array, start, |builder| {
+            if let Some((Token::SquareBracketClose, offset)) = builder.tokens.get(builder.position) {
+                builder.position += 1;
+                return Ok(*offset);
+            }
+            loop {
+                builder.value()?;
+                match builder.next() {
+                    (Some(Token::Comma), _) => (),
+                    (Some(Token::SquareBracketClose), offset) => return Ok(offset),
+                    (Some(t), offset) => return Err(builder.error(
+                        format!("Invalid JSON array! Invalid token: {}", t), offset, "expected ',' or ']'")),
+                    (None, offset) => return Err(builder.error(missing_bracket.to_string(), offset, "expected ',' or ']'"))
+                }
+            }
+        })
+    }
+
+    fn object(&mut self, start: usize) -> Result<(), JsonError> {
+        let missing_bracket = "Invalid JSON object! Missing a closing curly bracket \"}\"";
+        self.container(Kind::Object, start, |builder| {
+            if let Some((Token::CurlyBracketClose, offset)) = builder.tokens.get(builder.position) {
+                builder.position += 1;
+                return Ok(*offset);
+            }
+            let mut keys = HashSet::new();
+            loop {
+                let key = match builder.next() {
+                    (Some(Token::String(key)), offset) => {
+                        let escaped = match key {
+                            Cow::Owned(key) => Some(key.as_str().into()),
+                            Cow::Borrowed(_) => None
+                        };
+                        builder.leaf(Kind::Key(escaped), offset);
+                        (key, offset)
+                    }
+                    (Some(t), offset) => return Err(builder.error(
+                        format!("Invalid JSON object! Invalid token:  {}", t), offset, "expected a string key")),
+                    (None, offset) => return Err(builder.error(missing_bracket.to_string(), offset, "expected a string key"))
+                };
+                match builder.next() {
+                    (Some(Token::Colon), _) => (),
+                    (Some(t), offset) => return Err(builder.error(
+                        format!("Invalid JSON object! Invalid token: {} instead of \":\"", t), offset, "expected ':'")),
+                    (None, offset) => return Err(builder.error(
+                        "Invalid JSON object! Missing a colon \":\"".to_string(), offset, "expected ':'"))
+                }
+                builder.value()?;
+                if !keys.insert(key.0) {
+                    return Err(JsonError::new(ErrorKind::Syntax, format!("Invalid JSON object: the key {} is not unique", key.0))
+                        .with_position(builder.source, key.1));
+                }
+                match builder.next() {
+                    (Some(Token::Comma), _) => (),
+                    (Some(Token::CurlyBracketClose), offset) => return Ok(offset),
+                    (Some(t), offset) => return Err(builder.error(
+                        format!("Invalid JSON object! Invalid token: {}", t), offset, "expected ',' or '}'")),
+                    (None, offset) => return Err(builder.error(missing_bracket.to_string(), offset, "expected ',' or '}'"))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::lazy::LazyDocument;
+
+    #[test]
+    fn test_get_matches_pointer() {
+        let source = " {\"a\": [1, {\"b/c\": [true, \"x\"]}, []], \"\": {\"\": -0.5}, \"d\\u0065\": {}, \"f\": null } ";
+        let document = LazyDocument::parse(source).unwrap();
+        let j_value = json!(source);
+        for pointer in ["", "/a", "/a/0", "/a/1", "/a/1/b~1c", "/a/1/b~1c/1", "/a/2", "/", "//", "/de", "/f"].iter() {
+            assert_eq!(Ok(j_value.pointer(pointer).cloned()), document.get(pointer), "{}", pointer);
+        }
+        for pointer in ["/a/3", "/a/2/0", "/a/01", "/b", "/f/0", "/a/1/b~1c/1/0", "a", "/\\u0065"].iter() {
+            assert!(!document.contains(pointer), "{}", pointer);
+        }
+        assert_eq!(Some("[true, \"x\"]"), document.raw("/a/1/b~1c"));
+        assert_eq!(Some(source.trim()), document.raw(""));
+        assert_eq!("No value at /g", document.get_as::<bool>("/g").unwrap_err().message());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for source in ["", "[1,", "[1 2]", "{\"a\" 1}", "{1: 2}", "{\"a\": 1,}", "[01]", "[1] 2", "{\"a\": 1, \"a\": 2}"].iter() {
+            let error = LazyDocument::parse(source).unwrap_err();
+            assert_eq!(ErrorKind::Syntax, error.kind(), "{}", source);
+            assert!(error.offset().is_some(), "{}", source);
+        }
+        assert_eq!("Invalid token '2' after the document", LazyDocument::parse("[1] 2").unwrap_err().message());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let nested = |depth| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+        let source = nested(128);
+        let document = LazyDocument::parse(&source).unwrap();
+        assert_eq!(Ok(Some(crate::parser::parse(&source).unwrap())), document.get(""));
+        for (source, offset) in [(nested(129), 128), (nested(200), 128), ("[".repeat(200_000), 128),
+                                 ("{\"a\": ".repeat(200_000), 128 * 6)] {
+            let error = LazyDocument::parse(&source).unwrap_err();
+            assert_eq!(("The value is nested deeper than the limit of 128 levels", Some(offset)),
+                       (error.message(), error.offset()));
+        }
+    }
+}
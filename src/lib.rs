@@ -17,9 +17,74 @@
 
 //! A library for parsing, serializing and creating json objects.
 
+pub mod aggregate;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod avro_compat;
+mod base64;
+pub mod binary;
+pub mod canonical;
+pub mod codegen;
+pub mod coerce;
+pub mod compare;
+pub mod config;
+pub mod convert;
+pub mod crdt;
 pub mod data_structures;
+#[cfg(feature = "chrono")]
+pub mod datetime;
+pub mod dedup;
+pub mod error;
+pub mod export;
+pub mod filter;
+pub mod format;
+pub mod generate;
+pub mod geojson;
+mod glob;
+pub mod history;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod indexed;
+pub mod jsonpath;
+pub mod jsonrpc;
+#[cfg(feature = "jws")]
+pub mod jws;
+pub mod lazy;
+pub mod logging;
+pub mod merge;
 pub mod parser;
+pub mod patch;
+pub mod pointer;
+pub mod problem;
+pub mod projection;
+pub mod properties;
+#[cfg(feature = "prost")]
+pub mod protobuf;
+pub mod redact;
+pub mod replicate;
+pub mod schema;
+pub mod secure;
 pub mod serializer;
+pub mod shared;
+pub mod source_map;
+pub mod sse;
+pub mod stats;
+#[cfg(feature = "store")]
+pub mod store;
+pub mod stream;
+pub mod template;
+pub mod tools;
+pub mod transform;
+pub mod typed;
+pub mod urlencoded;
+#[cfg(feature = "uuid")]
+pub mod uuid;
+pub mod visitor;
+#[cfg(feature = "watch")]
+pub mod watch;
+pub mod watched;
+#[cfg(feature = "xml")]
+pub mod xml;
 
 #[macro_use]
 mod macros;
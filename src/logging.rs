@@ -0,0 +1,99 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Values as fields of log records and, with the `tracing` feature, of [tracing] spans and
+//! events.
+//!
+//! [tracing]:https://crates.io/crates/tracing
+
+use std::fmt::{Display, Formatter};
+
+use crate::data_structures::JValue;
+use crate::serializer::Serialize;
+
+/// Displays a value as its serialization with minimal whitespace, on a single line, for log
+/// messages. Control characters in strings are escaped, so a value can not break a log line.
+#[derive(Debug, Clone, Copy)]
+pub struct Compact<'a>(&'a JValue);
+
+impl Display for Compact<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0.serialize())
+    }
+}
+
+impl JValue {
+    /// Returns the value displayed on a single line, for log messages such as
+    /// `log::info!("payload {}", payload.compact())`
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("{\"lines\": [\"a\\nb\"]}");
+    /// assert_eq!("{\"lines\":[\"a\\nb\"]}", j_value.compact().to_string());
+    /// ```
+    pub fn compact(&self) -> Compact<'_> {
+        Compact(self)
+    }
+
+    /// Returns the value as a field of a tracing span or event, recorded as its [compact]
+    /// serialization, as in `tracing::info!(payload = j_value.as_field())`
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("{\"user\": 7}");
+    /// assert_eq!("{\"user\":7}", format!("{:?}", j_value.as_field()));
+    /// ```
+    ///
+    /// [compact]: JValue::compact
+    #[cfg(feature = "tracing")]
+    pub fn as_field(&self) -> tracing::field::DisplayValue<Compact<'_>> {
+        tracing::field::display(self.compact())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json;
+
+    #[test]
+    fn test_compact_is_one_line() {
+        let j_value = json!("[\"\\r\\n\\u0000\", {\"k\\t\": null}]");
+        assert_eq!("[\"\\r\\n\\u0000\",{\"k\\t\":null}]", j_value.compact().to_string());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_as_field_records_the_compact_form() {
+        use std::fmt::Debug;
+        use tracing::field::{Field, Value, Visit};
+
+        struct Recorder(Vec<String>);
+        impl Visit for Recorder {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
+
+        let j_value = json!("{\"a\": [1, true]}");
+        let mut recorder = Recorder(Vec::new());
+        let callsite = tracing::callsite! { name: "test", kind: tracing::metadata::Kind::EVENT, fields: payload };
+        let field = tracing::callsite::Callsite::metadata(callsite).fields().field("payload").unwrap();
+        j_value.as_field().record(&field, &mut recorder);
+        assert_eq!(vec!["payload={\"a\":[1,true]}"], recorder.0);
+    }
+}
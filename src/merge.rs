@@ -0,0 +1,160 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Structural three-way merge of concurrently edited documents.
+
+use std::fmt::{Display, Formatter};
+
+use crate::data_structures::{JObject, JValue};
+use crate::serializer::Serialize;
+use crate::transform::push_token;
+
+/// Edits of both sides to the same value which can not both be kept, found by [`three_way`].
+/// A missing value is [`None`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub pointer: String,
+    /// The value in the common ancestor
+    pub base: Option<JValue>,
+    /// The value on our side
+    pub ours: Option<JValue>,
+    /// The value on their side
+    pub theirs: Option<JValue>,
+}
+
+impl Display for Conflict {
+    /// Writes `/path: base -> ours | theirs`, with `(none)` for a missing value
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let show = |value: &Option<JValue>| value.as_ref().map_or("(none)".to_string(), |v| v.serialize());
+        write!(f, "{}: {} -> {} | {}", self.pointer, show(&self.base), show(&self.ours), show(&self.theirs))
+    }
+}
+
+/// Merges the edits which `ours` and `theirs` made to their common ancestor `base`.
+///
+/// A value changed on one side only takes that change, and a value changed the same way on both
+/// sides takes the shared change. Objects changed on both sides are merged member by member,
+/// so edits to different members never conflict, and arrays of the same length on all sides are
+/// merged element by element. Any other value changed differently on both sides, including a
+/// value removed on one side and changed on the other, is a conflict. All the conflicts are
+/// returned, ordered by pointer.
+///
+/// ```
+/// # use json::json;
+/// # use json::merge::three_way;
+/// let base = json!("{\"name\": \"app\", \"port\": 80, \"debug\": false}");
+/// let ours = json!("{\"name\": \"app\", \"port\": 8080, \"debug\": false}");
+/// let theirs = json!("{\"name\": \"web\", \"port\": 80}");
+/// assert_eq!(Ok(json!("{\"name\": \"web\", \"port\": 8080}")), three_way(&base, &ours, &theirs));
+///
+/// let conflicts = three_way(&base, &ours, &json!("{\"name\": \"app\", \"port\": 443}")).unwrap_err();
+/// assert_eq!("/port: 80 -> 8080 | 443", conflicts[0].to_string());
+/// ```
+pub fn three_way(base: &JValue, ours: &JValue, theirs: &JValue) -> Result<JValue, Vec<Conflict>> {
+    let mut conflicts = Vec::new();
+    let merged = merge(Some(base), Some(ours), Some(theirs), &mut String::new(), &mut conflicts);
+    if conflicts.is_empty() {
+        Ok(merged.expect("both sides have a root"))
+    } else {
+        Err(conflicts)
+    }
+}
+
+/// Merges the values at `path`, returning the merged value or [`None`] if it was removed. On a
+/// conflict our value is returned, so the merge can go on to find the other conflicts.
+fn merge(base: Option<&JValue>, ours: Option<&JValue>, theirs: Option<&JValue>,
+         path: &mut String, conflicts: &mut Vec<Conflict>) -> Option<JValue> {
+    if ours == theirs || base == theirs {
+        return ours.cloned();
+    }
+    if base == ours {
+        return theirs.cloned();
+    }
+    let empty = JObject::new();
+    match (base, ours, theirs) {
+        (None, Some(JValue::Object(o)), Some(JValue::Object(t))) => {
+            return Some(JValue::Object(merge_objects(&empty, o, t, path, conflicts)));
+        }
+        (Some(JValue::Object(b)), Some(JValue::Object(o)), Some(JValue::Object(t))) => {
+            return Some(JValue::Object(merge_objects(b, o, t, path, conflicts)));
+        }
+        (Some(JValue::Array(b)), Some(JValue::Array(o)), Some(JValue::Array(t)))
+            if b.len() == o.len() && b.len() == t.len() => {
+            let mut elements = Vec::with_capacity(b.len());
+            for i in 0..b.len() {
+                let len = path.len();
+                push_token(path, &i.to_string());
+                elements.extend(merge(Some(&b[i]), Some(&o[i]), Some(&t[i]), path, conflicts));
+                path.truncate(len);
+            }
+            return Some(JValue::Array(elements));
+        }
+        _ => {}
+    }
+    conflicts.push(Conflict { pointer: path.clone(), base: base.cloned(), ours: ours.cloned(), theirs: theirs.cloned() });
+    ours.cloned()
+}
+
+fn merge_objects(base: &JObject, ours: &JObject, theirs: &JObject,
+                 path: &mut String, conflicts: &mut Vec<Conflict>) -> JObject {
+    let mut keys: Vec<&String> = base.iter().chain(ours.iter()).chain(theirs.iter()).map(|(k, _)| k).collect();
+    keys.sort_unstable();
+    keys.dedup();
+    let mut merged = JObject::new();
+    for key in keys {
+        let len = path.len();
+        push_token(path, key);
+        if let Some(value) = merge(base.get(key), ours.get(key), theirs.get(key), path, conflicts) {
+            merged.insert(key.clone(), value);
+        }
+        path.truncate(len);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json;
+    use crate::merge::{three_way, Conflict};
+
+    #[test]
+    fn test_clean_merges() {
+        let base = json!("{\"a\": {\"x\": 1, \"y\": 2}, \"list\": [1, 2, 3], \"gone\": true}");
+        let ours = json!("{\"a\": {\"x\": 10, \"y\": 2}, \"list\": [1, 20, 3], \"new\": {\"k\": 1}}");
+        let theirs = json!("{\"a\": {\"x\": 1, \"y\": 2, \"z\": 3}, \"list\": [1, 2, 30], \"gone\": true, \"new\": {\"j\": 2}}");
+        assert_eq!(Ok(json!("{\"a\": {\"x\": 10, \"y\": 2, \"z\": 3}, \"list\": [1, 20, 30], \"new\": {\"k\": 1, \"j\": 2}}")),
+                   three_way(&base, &ours, &theirs));
+        assert_eq!(Ok(json!("[1]")), three_way(&json!("[]"), &json!("[1]"), &json!("[1]")));
+        assert_eq!(Ok(json!("2")), three_way(&json!("1"), &json!("1"), &json!("2")));
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let base = json!("{\"a\": 1, \"b\": {\"c\": 1}, \"list\": [1], \"d\": 1}");
+        let ours = json!("{\"a\": 2, \"list\": [1, 2], \"d\": 1}");
+        let theirs = json!("{\"a\": 3, \"b\": {\"c\": 2}, \"list\": [0], \"d\": 1}");
+        let conflicts = three_way(&base, &ours, &theirs).unwrap_err();
+        assert_eq!(vec!["/a", "/b", "/list"], conflicts.iter().map(|c| c.pointer.as_str()).collect::<Vec<_>>());
+        assert_eq!(Conflict { pointer: "/b".to_string(), base: Some(json!("{\"c\": 1}")), ours: None, theirs: Some(json!("{\"c\": 2}")) },
+                   conflicts[1]);
+        assert_eq!("/b: {\"c\":1} -> (none) | {\"c\":2}", conflicts[1].to_string());
+
+        let conflicts = three_way(&json!("{}"), &json!("{\"k\": [1]}"), &json!("{\"k\": {\"a\": 1}}")).unwrap_err();
+        assert_eq!(None, conflicts[0].base);
+        assert_eq!(1, three_way(&json!("[1, 2]"), &json!("[3, 2]"), &json!("[4, 2]")).unwrap_err().len());
+    }
+}
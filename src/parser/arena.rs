@@ -0,0 +1,339 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A parser that allocates the whole JSON data-structure in an arena
+
+use std::collections::HashSet;
+use bumpalo::Bump;
+use bumpalo::collections::Vec as BumpVec;
+use crate::data_structures::BorrowedValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::repair::suggest;
+use crate::parser::parser::{ParseOptions, SurrogatePolicy};
+use crate::parser::tokenizer::{control_character, unescape};
+
+/// Parses a JSON string into a [`BorrowedValue`] whose nodes are allocated in the given arena
+/// and whose strings and numbers borrow from the input.
+///
+/// Strings without escape sequences borrow from the input; the others are unescaped into the
+/// arena, the same way [`parse`](crate::parser::parse) unescapes them. The same inputs are
+/// accepted as by `parse`: numbers follow the grammar of RFC 8259, objects must not have
+/// duplicate keys, values must not be nested deeper than the default
+/// [`ParseOptions::max_depth`] and nothing but whitespace may follow the value.
+///
+/// ```
+/// # use json::parser::{parse_in, Bump};
+/// let arena = Bump::new();
+/// let value = parse_in(&arena, "{\"user\": {\"id\": 42, \"tags\": [\"a\", \"b\"]}}").unwrap();
+/// let user = value.get("user").unwrap();
+/// assert_eq!(Some(42.0), user.get("id").unwrap().as_f64());
+/// assert_eq!(Some("b"), user.get("tags").unwrap().index(1).unwrap().as_str());
+/// ```
+pub fn parse_in<'a>(arena: &'a Bump, json_string: &'a str) -> Result<BorrowedValue<'a>, JsonError> {
+    let mut parser = ArenaParser { arena, input: json_string, position: 0, depth: 0 };
+    let value = parser.get_value().map_err(|e| suggest(json_string, e))?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(suggest(json_string, parser.invalid_char(c, "expected the end of the input")))
+    }
+}
+
+struct ArenaParser<'a> {
+    arena: &'a Bump,
+    input: &'a str,
+    position: usize,
+    /// The number of arrays and objects the parser is in
+    depth: usize,
+}
+
+impl<'a> ArenaParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\n') | Some(b'\r') | Some(b'\t') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn error(&self, message: &str, offset: usize, hint: &str) -> JsonError {
+        JsonError::new(ErrorKind::Syntax, message).with_position(self.input, offset).with_hint(hint)
+    }
+
+    fn invalid_char(&self, c: u8, hint: &str) -> JsonError {
+        let c = self.input[self.position..].chars().next().unwrap_or(c as char);
+        self.error(&format!("Invalid char \'{}\' ({:#06x})", c, c as usize), self.position, hint)
+    }
+
+    fn get_value(&mut self) -> Result<BorrowedValue<'a>, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.nested(Self::get_object),
+            Some(b'[') => self.nested(Self::get_array),
+            Some(b'"') => Ok(BorrowedValue::String(self.get_string()?)),
+            Some(b'0'..=b'9') | Some(b'-') => self.get_number(),
+            Some(b't') => self.get_literal("true", BorrowedValue::Boolean(true)),
+            Some(b'f') => self.get_literal("false", BorrowedValue::Boolean(false)),
+            Some(b'n') => self.get_literal("null", BorrowedValue::Null),
+            Some(c) => Err(self.invalid_char(c, "expected a value")),
+            None => Err(self.error("No Token Found", self.position, "expected a value"))
+        }
+    }
+
+    /// Parses the array or object at the position with `f`, unless it is nested deeper than the
+    /// default options of `parse` allow
+    fn nested(&mut self, f: fn(&mut Self) -> Result<BorrowedValue<'a>, JsonError>)
+              -> Result<BorrowedValue<'a>, JsonError> {
+        let max_depth = ParseOptions::default().max_depth;
+        if self.depth == max_depth {
+            let message = format!("The value is nested deeper than the limit of {} levels", max_depth);
+            return Err(self.error(&message, self.position, "expected fewer nested arrays and objects"));
+        }
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn get_literal(&mut self, literal: &str, value: BorrowedValue<'a>)
+                   -> Result<BorrowedValue<'a>, JsonError> {
+        if self.input[self.position..].starts_with(literal) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("Invalid token", self.position, &format!("expected {}", literal)))
+        }
+    }
+
+    fn get_string(&mut self) -> Result<&'a str, JsonError> {
+        let bytes = self.input.as_bytes();
+        let start = self.position + 1;
+        let mut i = start;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'"' => {
+                    self.position = i + 1;
+                    let raw = &self.input[start..i];
+                    if !raw.contains('\\') {
+                        return Ok(raw);
+                    }
+                    let unescaped = unescape(raw, '"', SurrogatePolicy::Error, |at, message, hint| Err(self.error(&message, start + at, hint)))?;
+                    return Ok(self.arena.alloc_str(&unescaped));
+                }
+                b'\\' => i += 2,
+                b if b < b' ' => return Err(control_character(b as char).with_position(self.input, i)),
+                _ => i += 1
+            }
+        }
+        Err(self.error("Invalid string token at the end of file!", self.position, "expected a closing '\"'"))
+    }
+
+    fn get_number(&mut self) -> Result<BorrowedValue<'a>, JsonError> {
+        let bytes = self.input.as_bytes();
+        let start = self.position;
+        let mut i = start;
+        let digits = |i: &mut usize| {
+            let from = *i;
+            while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+                *i += 1;
+            }
+            *i > from
+        };
+        if bytes[i] == b'-' {
+            i += 1;
+        }
+        if i < bytes.len() && bytes[i] == b'0' {
+            i += 1;
+        } else if !digits(&mut i) {
+            return Err(self.error("Digit was expected", i, "expected a digit"));
+        }
+        if i < bytes.len() && bytes[i] == b'.' {
+            i += 1;
+            if !digits(&mut i) {
+                return Err(self.error("Digit was expected", i, "expected a digit"));
+            }
+        }
+        if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+            i += 1;
+            if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+                i += 1;
+            }
+            if !digits(&mut i) {
+                return Err(self.error("Digit was expected", i, "expected a digit"));
+            }
+        }
+        self.position = i;
+        Ok(BorrowedValue::Number(&self.input[start..i]))
+    }
+
+    fn get_array(&mut self) -> Result<BorrowedValue<'a>, JsonError> {
+        self.position += 1;
+        let mut elements = BumpVec::new_in(self.arena);
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(BorrowedValue::Array(elements.into_bump_slice()));
+        }
+        loop {
+            elements.push(self.get_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(BorrowedValue::Array(elements.into_bump_slice()));
+                }
+                Some(c) => return Err(self.invalid_char(c, "expected ',' or ']'")),
+                None => return Err(self.error("Invalid JSON array! Missing a closing square bracket \"]\"",
+                                              self.position, "expected ',' or ']'"))
+            }
+        }
+    }
+
+    /// Returns `false` if `key` is one of the keys of `members`, as [`parse`](crate::parser::parse)
+    /// rejects objects with duplicate keys
+    fn insert_key(&self, key: &'a str, members: &[(&'a str, BorrowedValue<'a>)], keys: &mut HashSet<&'a str>) -> bool {
+        if members.len() < 16 {
+            return members.iter().all(|(k, _)| *k != key);
+        }
+        if keys.is_empty() {
+            keys.extend(members.iter().map(|(k, _)| *k));
+        }
+        keys.insert(key)
+    }
+
+    fn get_object(&mut self) -> Result<BorrowedValue<'a>, JsonError> {
+        self.position += 1;
+        let mut members = BumpVec::new_in(self.arena);
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(BorrowedValue::Object(members.into_bump_slice()));
+        }
+        // Filled once the object is large enough for a linear search to be slow
+        let mut keys = HashSet::new();
+        loop {
+            self.skip_whitespace();
+            let key_offset = self.position;
+            let key = match self.peek() {
+                Some(b'"') => self.get_string()?,
+                Some(c) => return Err(self.invalid_char(c, "expected a string key")),
+                None => return Err(self.error("Invalid JSON object! Missing a closing curly bracket \"}\"",
+                                              self.position, "expected a string key"))
+            };
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b':') => self.position += 1,
+                Some(c) => return Err(self.invalid_char(c, "expected ':'")),
+                None => return Err(self.error("Invalid JSON object! Missing a colon \":\"", self.position, "expected ':'"))
+            }
+            if !self.insert_key(key, &members, &mut keys) {
+                return Err(JsonError::new(ErrorKind::Syntax, format!("Invalid JSON object: the key {} is not unique", key))
+                    .with_position(self.input, key_offset));
+            }
+            members.push((key, self.get_value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(BorrowedValue::Object(members.into_bump_slice()));
+                }
+                Some(c) => return Err(self.invalid_char(c, "expected ',' or '}'")),
+                None => return Err(self.error("Invalid JSON object! Missing a closing curly bracket \"}\"",
+                                              self.position, "expected ',' or '}'"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+    use crate::data_structures::BorrowedValue;
+    use crate::parser::{parse, parse_in};
+
+    #[test]
+    fn test_scalars() {
+        let arena = Bump::new();
+        assert_eq!(BorrowedValue::Null, parse_in(&arena, " null ").unwrap());
+        assert_eq!(BorrowedValue::Boolean(false), parse_in(&arena, "false").unwrap());
+        assert_eq!(BorrowedValue::Number("-0.5e+3"), parse_in(&arena, "-0.5e+3").unwrap());
+        assert_eq!(BorrowedValue::String("a \" b"), parse_in(&arena, "\"a \\\" b\"").unwrap());
+        assert_eq!(BorrowedValue::String("\u{e9}"), parse_in(&arena, "\"\\u00e9\"").unwrap());
+        assert_eq!(Some(2), parse_in(&arena, "[\"\\q\"]").unwrap_err().offset());
+    }
+
+    #[test]
+    fn test_nested() {
+        let arena = Bump::new();
+        let json = "{\"a\": [1, {\"b\": []}, {}], \"c\": \"d\"}";
+        let value = parse_in(&arena, json).unwrap();
+        assert_eq!(Some(BorrowedValue::String("d")), value.get("c"));
+        assert_eq!(parse(json).unwrap(), value.to_owned_value());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let arena = Bump::new();
+        let error = |json: &str| {
+            let e = parse_in(&arena, json).unwrap_err();
+            (e.message().to_string(), e.offset().unwrap(), e.hint().unwrap().to_string())
+        };
+        assert_eq!(("Digit was expected".to_string(), 1, "expected a digit".to_string()), error("-"));
+        assert_eq!(("Digit was expected".to_string(), 2, "expected a digit".to_string()), error("1."));
+        assert_eq!(("Invalid char \']\' (0x005d)".to_string(), 3,
+                    "remove the trailing comma before ']'".to_string()), error("[1,]"));
+        assert_eq!(("Invalid char \'1\' (0x0031)".to_string(), 5, "expected the end of the input".to_string()),
+                   error("true 1"));
+        assert_eq!(("Invalid token".to_string(), 0, "expected null".to_string()), error("nul"));
+        assert_eq!(("Invalid JSON object! Missing a closing curly bracket \"}\"".to_string(), 6,
+                    "expected ',' or '}'".to_string()), error("{\"a\":1"));
+    }
+
+    #[test]
+    fn test_same_language_as_parse() {
+        let corpus = [
+            "0", "-0", "-0.5e+3", "1E-2", "10", "1e400", "\"\"", "\" a \\u00e9 \\ud83d\\ude00 \"", "[]", "{}",
+            " [1, [2, {\"a\": null}], true, false] ", "{\"a\": 1, \"b\": {\"a\": 2}}",
+            "-", "1.", "1e", "1e+", "-.5", "1.e5", "-e5", "[-.e]", "01", "+1", ".5", "1-2", "0x10", "NaN",
+            "{\"a\": 1, \"a\": 2}", "{\"a\": {\"b\": 1, \"b\": 1}}", "1 2", "[1] ]", "{} {}", "null,",
+            "", " ", "[1,]", "{\"a\": 1,}", "[1 2]", "{\"a\" 1}", "{1: 2}", "'a'", "\"\\q\"", "\"\\ud800\"", "\"a\tb\"",
+            "\"unterminated", "[", "{", "nul", "truex", "[true false]",
+        ];
+        let nested = |depth| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+        let deep = [nested(128), nested(129), "{\"a\": ".repeat(200), "[".repeat(1_000_000)];
+        let arena = Bump::new();
+        for json in corpus.iter().copied().chain(deep.iter().map(String::as_str)) {
+            let borrowed = parse_in(&arena, json).map(|value| value.to_owned_value());
+            assert_eq!(parse(json).ok(), borrowed.clone().ok(), "{}", json);
+            if let (Err(e), Err(borrowed)) = (parse(json), borrowed) {
+                assert_eq!(e.kind(), borrowed.kind(), "{}", json);
+            }
+        }
+        let members: Vec<String> = (0..20).map(|i| format!("\"k{}\": {}", i % 19, i)).collect();
+        let large = format!("{{{}}}", members.join(", "));
+        assert!(parse(&large).is_err() && parse_in(&arena, &large).is_err());
+        let duplicate = "{\"a\": 1, \"b\": 2, \"c\": 3, \"a\": 4}";
+        let (error, expected) = (parse_in(&arena, duplicate).unwrap_err(), parse(duplicate).unwrap_err());
+        assert_eq!((Some(25), "Invalid JSON object: the key a is not unique"), (error.offset(), error.message()));
+        assert_eq!((expected.offset(), expected.message()), (error.offset(), error.message()));        let (error, expected) = (parse_in(&arena, &deep[3]).unwrap_err(), parse(&deep[3]).unwrap_err());
+        assert_eq!((expected.offset(), expected.message()), (error.offset(), error.message()));
+    }
+}
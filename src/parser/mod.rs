@@ -18,6 +18,20 @@
 //! Parsing functions to create a JSON data-structure
 
 mod tokenizer;
+#[allow(clippy::module_inception)]
 mod parser;
+mod repair;
+mod reusable;
+mod stats;
+#[cfg(feature = "arena")]
+mod arena;
 
-pub use parser::parse;
\ No newline at end of file
+pub use parser::{parse, parse_borrowed, parse_collecting, parse_with, ParseOptions, SurrogatePolicy};
+pub use repair::{repair, repair_and_parse, Fix, FixKind};
+pub use reusable::{with_default_parser, Parser, ParserBuilder};
+pub use stats::{parse_with_stats, CountingAllocator, ParseStats};
+pub(crate) use tokenizer::{tokenize, unescape, Token};
+#[cfg(feature = "arena")]
+pub use arena::parse_in;
+#[cfg(feature = "arena")]
+pub use bumpalo::Bump;
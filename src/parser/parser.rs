@@ -15,77 +15,618 @@
 // You should have received a copy of the GNU General Public License
 // along with json.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::borrow::Cow;
 use std::slice::Iter;
 use std::str::FromStr;
-use crate::data_structures::{JNumber, JObject, JValue};
-use crate::parser::tokenizer::{tokenize, Token};
+use crate::data_structures::{CowObject, CowString, CowValue, JNumber, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::repair::suggest;
+use crate::parser::tokenizer::{tokenize, tokenize_collecting, tokenize_into, Token};
 
 
-pub fn parse(json_string: &str) -> Result<JValue, String> {
-    let tokens = tokenize(json_string)?;
-    let mut tokens_itr = tokens.iter();
-    get_jvalue(&mut tokens_itr)
+/// Parses a JSON string into a [`JValue`]. Errors are located in the input and carry a hint,
+/// which suggests a fix for common mistakes such as trailing commas and single quotes.
+///
+/// ```
+/// # use json::parser::parse;
+/// let error = parse("{\"a\": 1 \"b\": 2}").unwrap_err();
+/// assert_eq!((Some(1), Some(9)), (error.line(), error.column()));
+/// assert_eq!(Some("expected ',' or '}'"), error.hint());
+/// ```
+pub fn parse(json_string: &str) -> Result<JValue, JsonError> {
+    tokenize(json_string).and_then(|tokens| parse_tokens(json_string, &tokens))
+        .map_err(|e| suggest(json_string, e))
 }
 
-fn get_jvalue(tokens_itr: &mut Iter<Token>) -> Result<JValue, String> {
-    let result = tokens_itr.next();
-    match result {
-        Some(Token::CurlyBracketOpen) => get_jobject(tokens_itr),
-        Some(Token::SquareBracketOpen) => get_jarray(tokens_itr),
-        Some(Token::Number(n)) => Ok(JValue::Number(JNumber::from_str(n)?)),
-        Some(Token::String(s)) => Ok(JValue::String(s.clone())),
-        Some(Token::True) => Ok(JValue::Boolean(true)),
-        Some(Token::False) => Ok(JValue::Boolean(false)),
-        Some(Token::Null) => Ok(JValue::Null),
-        Some(invalid_token) => Err(format!("Invalid token '{}'", invalid_token)),
-        None => Err("No Token Found".to_string())
+/// Parses a JSON string like [`parse`], into a value whose strings borrow `json_string` instead
+/// of copying it. Only strings with escapes, which differ from their text in the input, are
+/// copied, so parsing a large document with long strings allocates much less.
+///
+/// ```
+/// # use json::data_structures::{CowValue, JString, JValue};
+/// # use json::parser::parse_borrowed;
+/// let json_string = "{\"text\": \"a string that is too long to be stored inline\", \"escaped\": \"\\n\"}";
+/// let j_value = parse_borrowed(json_string).unwrap();
+/// assert!(matches!(&j_value["text"], CowValue::String(s) if s.is_borrowed()));
+/// assert_eq!(JValue::String(JString::from("\n")), j_value["escaped"]);
+/// ```
+pub fn parse_borrowed(json_string: &str) -> Result<CowValue<'_>, JsonError> {
+    let tokens = tokenize(json_string).map_err(|e| suggest(json_string, e))?;
+    let mut parser = Parser { source: json_string, tokens: tokens.iter(), options: ParseOptions::default(),
+                              errors: None, warnings: Vec::new(), string: CowString::borrowed, depth: 0 };
+    parser.get_document().map_err(|e| suggest(json_string, e))
+}
+
+/// Options for [`parse_with`], which make the parser accept mistakes common in hand-edited and
+/// generated documents. The default accepts only valid JSON, like [`parse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Keep invalid escape sequences in strings, such as `\q`, and control characters which are
+    /// not escaped, such as a tab, as they are written. With [`SurrogatePolicy::Error`] this
+    /// includes lone surrogates.
+    pub allow_invalid_escapes: bool,
+    /// What to do with an escaped surrogate that is not part of a pair, such as `"\uD800"`
+    pub lone_surrogates: SurrogatePolicy,
+    /// Accept a `,` after the last element of an array or member of an object, as in
+    /// `[1, 2, 3,]` and `{"a": 1,}`
+    pub allow_trailing_commas: bool,
+    /// Accept strings in single quotes, as in `{'a': 'it\'s'}`, where `\'` escapes a quote
+    pub allow_single_quotes: bool,
+    /// Accept object keys without quotes that are identifiers, as in JavaScript object literals
+    /// such as `{key: 1}`. An identifier starts with a letter, `_` or `$`, followed by letters,
+    /// digits, `_` and `$`; the literals `true`, `false` and `null` are not identifiers.
+    pub allow_unquoted_keys: bool,
+    /// The maximum length of a string in bytes, as written between its quotes, or no limit
+    pub max_string_len: Option<usize>,
+    /// The maximum length of a number in bytes, or no limit
+    pub max_number_len: Option<usize>,
+    /// The maximum number of arrays and objects a value may be nested in, 128 by default. The
+    /// parser recurses into nested values, so without a limit a document such as `[[[[...]]]]`
+    /// with enough brackets overflows the stack.
+    pub max_depth: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            allow_invalid_escapes: false,
+            lone_surrogates: SurrogatePolicy::default(),
+            allow_trailing_commas: false,
+            allow_single_quotes: false,
+            allow_unquoted_keys: false,
+            max_string_len: None,
+            max_number_len: None,
+            max_depth: 128,
+        }
     }
 }
 
-fn get_jarray(tokens_itr: &mut Iter<Token>) -> Result<JValue, String> {
-    let mut vec: Vec<JValue> = Vec::new();
-    loop {
-        match tokens_itr.next() {
-            Some(Token::CurlyBracketOpen) => vec.push(get_jobject(tokens_itr)?),
-            Some(Token::SquareBracketOpen) => vec.push(get_jarray(tokens_itr)?),
-            Some(Token::String(s)) => vec.push(JValue::String(s.clone())),
-            Some(Token::Number(n)) => vec.push(JValue::Number(JNumber::from_str(n)?)),
-            Some(Token::True) => vec.push(JValue::Boolean(true)),
-            Some(Token::False) => vec.push(JValue::Boolean(false)),
-            Some(Token::Null) => vec.push(JValue::Null),
-            Some(Token::SquareBracketClose) => return Ok(JValue::Array(vec)),
-            Some(t) => return Err(format!("Invalid JSON array! Invalid token: {}", t)),
-            None => return Err(format!("Invalid JSON array! Missing a closing square bracket \"]\""))
-        }
-        match tokens_itr.next() {
-            Some(Token::Comma) => continue,
-            Some(Token::SquareBracketClose) => return Ok(JValue::Array(vec)),
-            Some(t) => return Err(format!("Invalid JSON array! Invalid token: {}", t)),
-            None => return Err(format!("Invalid JSON array! Missing a closing square bracket \"]\""))
+impl ParseOptions {
+    /// Options accepting every mistake the parser can tolerate
+    pub fn lenient() -> ParseOptions {
+        ParseOptions {
+            allow_invalid_escapes: true,
+            allow_trailing_commas: true,
+            allow_single_quotes: true,
+            allow_unquoted_keys: true,
+            ..ParseOptions::default()
         }
     }
 }
 
-fn get_jobject(tokens_itr: &mut Iter<Token>) -> Result<JValue, String> {
-    let mut obj = JObject::new();
-    loop {
-        let key = match tokens_itr.next() {
-            Some(Token::String(s)) => s.clone(),
-            Some(Token::CurlyBracketClose) => return Ok(JValue::Object(obj)),
-            Some(t) => return Err(format!("Invalid JSON object! Invalid token:  {}", t)),
-            None => return
-                Err(format!("Invalid JSON object! Missing a closing curly bracket \"}}\""))
+/// How the parser handles an escaped lone surrogate, which JavaScript strings may contain, as
+/// they are sequences of UTF-16 code units, but Rust strings can not. A [`JString`] can not
+/// hold the surrogate itself, as WTF-8 would, so [`SurrogatePolicy::Preserve`] keeps its escape
+/// sequence as text instead. Serializing such a string escapes the backslash, so parsing it
+/// again gives the same string, not the surrogate.
+///
+/// [`JString`]:crate::data_structures::JString
+///
+/// ```
+/// # use json::data_structures::{JString, JValue};
+/// # use json::parser::{parse, parse_with, ParseOptions, SurrogatePolicy};
+/// # use json::serializer::Serialize;
+/// let json_string = "\"\\ud800!\"";
+/// assert_eq!("Lone surrogate '\\ud800' in a string", parse(json_string).unwrap_err().message());
+/// let options = ParseOptions { lone_surrogates: SurrogatePolicy::Replace, ..ParseOptions::default() };
+/// assert_eq!(JValue::String(JString::from("\u{fffd}!")), parse_with(json_string, &options).unwrap().0);
+/// let options = ParseOptions { lone_surrogates: SurrogatePolicy::Preserve, ..ParseOptions::default() };
+/// let preserved = parse_with(json_string, &options).unwrap().0;
+/// assert_eq!(JValue::String(JString::from("\\ud800!")), preserved);
+/// assert_eq!("\"\\\\ud800!\"", preserved.serialize());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SurrogatePolicy {
+    /// Fail with a syntax error, or warn if [`ParseOptions::allow_invalid_escapes`] is set
+    #[default]
+    Error,
+    /// Replace the surrogate with U+FFFD, the replacement character
+    Replace,
+    /// Keep the escape sequence as the six characters it is written with, e.g. `\ud800`, without
+    /// a warning, for systems which expect the code unit back. The string is then not what
+    /// JavaScript would read.
+    Preserve,
+}
+
+/// Parses a JSON string like [`parse`], accepting the mistakes `options` allows. Returns the
+/// value with a warning for each mistake accepted, located in the input like an error.
+///
+/// ```
+/// # use json::data_structures::{JString, JValue};
+/// # use json::json;
+/// # use json::parser::{parse, parse_with, ParseOptions};
+/// let json_string = "[\"C:\\windows\", \"caf\\u00e9\"]";
+/// assert_eq!("Invalid escape '\\w' in a string", parse(json_string).unwrap_err().message());
+/// let (j_value, warnings) = parse_with(json_string, &ParseOptions::lenient()).unwrap();
+/// assert_eq!(JValue::String(JString::from("C:\\windows")), j_value[0]);
+/// assert_eq!(JValue::String(JString::from("caf\u{e9}")), j_value[1]);
+/// assert_eq!((Some(1), Some(5)), (warnings[0].line(), warnings[0].column()));
+///
+/// let options = ParseOptions { allow_trailing_commas: true, ..ParseOptions::default() };
+/// let (j_value, warnings) = parse_with("{\"a\": [1, 2,],\n}", &options).unwrap();
+/// assert_eq!(json!("{\"a\": [1, 2]}"), j_value);
+/// assert_eq!("Trailing comma before '}'", warnings[1].message());
+/// ```
+pub fn parse_with(json_string: &str, options: &ParseOptions) -> Result<(JValue, Vec<JsonError>), JsonError> {
+    let mut warnings = Vec::new();
+    let value = parse_into(json_string, options, &mut Vec::new(), &mut warnings)?;
+    Ok((value, warnings))
+}
+
+/// Parses like [`parse_with`], using the storage of `tokens` and `warnings`, which must be
+/// empty. The tokens are left in `tokens`.
+pub(crate) fn parse_into<'s>(json_string: &'s str, options: &ParseOptions, tokens: &mut Vec<(Token<'s>, usize)>,
+                             warnings: &mut Vec<JsonError>) -> Result<JValue, JsonError> {
+    tokenize_into(json_string, options, warnings, tokens).map_err(|e| suggest(json_string, e))?;
+    let mut parser = Parser { source: json_string, tokens: tokens.iter(), options: *options, errors: None,
+                              warnings: std::mem::take(warnings), string: JString::from, depth: 0 };
+    let value = parser.get_document().map_err(|e| suggest(json_string, e));
+    *warnings = parser.warnings;
+    warnings.sort_by_key(|w| w.offset());
+    value
+}
+
+/// Parses a JSON string like [`parse`], but goes on after an error to report every problem
+/// found in one pass, as editors and linters do. After an error the parser skips to the next
+/// `,` or closing bracket of the enclosing array or object, so one mistake usually gives one
+/// error. The errors are in the order of their positions.
+///
+/// ```
+/// # use json::parser::parse_collecting;
+/// let errors = parse_collecting("{\"a\": tru, \"b\": [1 2], \"c\": 3,}").unwrap_err();
+/// let positions: Vec<_> = errors.iter().map(|e| e.column().unwrap()).collect();
+/// assert_eq!(vec![7, 20, 31], positions);
+/// assert_eq!(Some("remove the trailing comma before '}'"), errors[2].hint());
+/// assert!(parse_collecting("[1, 2]").is_ok());
+/// ```
+pub fn parse_collecting(json_string: &str) -> Result<JValue, Vec<JsonError>> {
+    let mut errors = Vec::new();
+    let tokens = tokenize_collecting(json_string, &mut errors);
+    let mut parser = Parser { source: json_string, tokens: tokens.iter(), options: ParseOptions::default(),
+                              errors: Some(Vec::new()), warnings: Vec::new(), string: JString::from, depth: 0 };
+    let value = parser.get_document();
+    errors.append(parser.errors.as_mut().unwrap());
+    if let Err(e) = &value {
+        errors.push(e.clone());
+    }
+    if errors.is_empty() {
+        return value.map_err(|e| vec![e]);
+    }
+    errors.sort_by_key(|e| e.offset());
+    Err(errors.into_iter().map(|e| suggest(json_string, e)).collect())
+}
+
+pub(crate) fn parse_tokens(source: &str, tokens: &[(Token, usize)]) -> Result<JValue, JsonError> {
+    let mut parser = Parser { source, tokens: tokens.iter(), options: ParseOptions::default(), errors: None,
+                              warnings: Vec::new(), string: JString::from, depth: 0 };
+    parser.get_document()
+}
+
+struct Parser<'s, 't, 'v> {
+    source: &'s str,
+    tokens: Iter<'t, (Token<'s>, usize)>,
+    options: ParseOptions,
+    /// The errors recovered from, if the parser goes on after errors
+    errors: Option<Vec<JsonError>>,
+    /// The mistakes accepted as the options allow
+    warnings: Vec<JsonError>,
+    /// Creates the string of a value from the text of a string token without escapes
+    string: fn(&'s str) -> CowString<'v>,
+    /// The number of arrays and objects the parser is in
+    depth: usize,
+}
+
+/// Where the parser is after an element or member of an array or object
+enum Step {
+    /// After a `,`, at the next element or member
+    Continue,
+    /// After the closing bracket
+    Close,
+    /// At the end of the input
+    End,
+}
+
+/// An error with the token it was found at
+type Failure<'s, 't> = (JsonError, Option<&'t Token<'s>>);
+
+impl<'s, 't, 'v> Parser<'s, 't, 'v> {
+    /// Returns the next token with its offset, or the end of the input
+    fn next(&mut self) -> (Option<&'t Token<'s>>, usize) {
+        match self.tokens.next() {
+            Some((token, offset)) => (Some(token), *offset),
+            None => (None, self.source.len())
+        }
+    }
+
+    fn error(&self, message: String, offset: usize, hint: &str) -> JsonError {
+        JsonError::new(ErrorKind::Syntax, message).with_position(self.source, offset).with_hint(hint)
+    }
+
+    /// Returns `error` if the parser stops at errors. Otherwise it records the error and skips
+    /// to the next `,` or closing bracket of the current array or object; `consumed` is the
+    /// token the error was found at.
+    fn recover(&mut self, error: JsonError, consumed: Option<&Token>) -> Result<Step, JsonError> {
+        self.report(error)?;
+        let mut depth = match consumed {
+            Some(Token::Comma) => return Ok(Step::Continue),
+            Some(Token::CurlyBracketClose) | Some(Token::SquareBracketClose) => return Ok(Step::Close),
+            Some(Token::CurlyBracketOpen) | Some(Token::SquareBracketOpen) => 1,
+            Some(_) => 0,
+            None => return Ok(Step::End)
         };
-        match tokens_itr.next() {
-            Some(Token::Colon) => (),
-            Some(t) => return
-                Err(format!("Invalid JSON object! Invalid token: {} instead of \":\"", t)),
-            None => return Err(format!("Invalid JSON object! Missing a colon \":\""))
+        for (token, _) in self.tokens.by_ref() {
+            match token {
+                Token::CurlyBracketOpen | Token::SquareBracketOpen => depth += 1,
+                Token::CurlyBracketClose | Token::SquareBracketClose if depth == 0 => return Ok(Step::Close),
+                Token::CurlyBracketClose | Token::SquareBracketClose => depth -= 1,
+                Token::Comma if depth == 0 => return Ok(Step::Continue),
+                _ => ()
+            }
+        }
+        Ok(Step::End)
+    }
+
+    fn number(&self, n: &str, offset: usize) -> Result<CowValue<'v>, JsonError> {
+        JNumber::from_str(n).map(CowValue::Number)
+            .map_err(|e| JsonError::new(ErrorKind::Syntax, e).with_position(self.source, offset))
+    }
+
+    /// Parses the value starting with `token`, or returns `None` if no value starts with it
+    fn value(&mut self, token: &'t Token<'s>, offset: usize) -> Option<Result<CowValue<'v>, JsonError>> {
+        Some(match token {
+            Token::CurlyBracketOpen => self.nested(offset, Self::get_jobject),
+            Token::SquareBracketOpen => self.nested(offset, Self::get_jarray),
+            Token::Number(n) => self.number(n, offset),
+            Token::String(Cow::Borrowed(s)) => Ok(CowValue::String((self.string)(s))),
+            Token::String(Cow::Owned(s)) => Ok(CowValue::String(CowString::from(s))),
+            Token::True => Ok(CowValue::Boolean(true)),
+            Token::False => Ok(CowValue::Boolean(false)),
+            Token::Null => Ok(CowValue::Null),
+            _ => return None
+        })
+    }
+
+    /// Parses the array or object starting at `offset` with `f`, unless it is nested deeper than
+    /// the options allow
+    fn nested(&mut self, offset: usize, f: fn(&mut Self) -> Result<CowValue<'v>, JsonError>)
+              -> Result<CowValue<'v>, JsonError> {
+        if self.depth == self.options.max_depth {
+            let message = format!("The value is nested deeper than the limit of {} levels", self.options.max_depth);
+            return Err(self.error(message, offset, "expected fewer nested arrays and objects"));
+        }
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn value_at(&mut self, token: Option<&'t Token<'s>>, offset: usize) -> Result<CowValue<'v>, JsonError> {
+        match token {
+            Some(token) => self.value(token, offset).unwrap_or_else(||
+                Err(self.error(format!("Invalid token '{}'", token), offset, "expected a value"))),
+            None => Err(self.error("No Token Found".to_string(), offset, "expected a value"))
+        }
+    }
+
+    fn get_jvalue(&mut self) -> Result<CowValue<'v>, JsonError> {
+        let (token, offset) = self.next();
+        self.value_at(token, offset)
+    }
+
+    /// Parses the value that is the whole input, which must not be followed by more tokens
+    fn get_document(&mut self) -> Result<CowValue<'v>, JsonError> {
+        let value = self.get_jvalue()?;
+        if let (Some(token), offset) = self.next() {
+            let error = self.error(format!("Expected the end of the input, found '{}'", token), offset,
+                                   "expected the end of the input");
+            self.report(error)?;
+        }
+        Ok(value)
+    }
+
+    /// Accepts the `,` before `close` if trailing commas are allowed, with a warning
+    fn trailing_comma(&mut self, close: char, offset: usize) -> bool {
+        if self.options.allow_trailing_commas {
+            let message = format!("Trailing comma before '{}'", close);
+            self.warnings.push(self.error(message, offset, &format!("remove the trailing comma before '{}'", close)));
+        }
+        self.options.allow_trailing_commas
+    }
+
+    /// Records `error` if the parser goes on after errors, or returns it
+    fn report(&mut self, error: JsonError) -> Result<(), JsonError> {
+        match self.errors.as_mut() {
+            Some(errors) => {
+                errors.push(error);
+                Ok(())
+            }
+            None => Err(error)
+        }
+    }
+
+    fn get_jarray(&mut self) -> Result<CowValue<'v>, JsonError> {
+        let mut vec: Vec<CowValue<'v>> = Vec::new();
+        loop {
+            let step = match self.element(&mut vec) {
+                Ok(step) => step,
+                Err((error, consumed)) => self.recover(error, consumed)?
+            };
+            match step {
+                Step::Continue => continue,
+                Step::Close | Step::End => return Ok(CowValue::Array(vec))
+            }
+        }
+    }
+
+    /// Parses an element of an array and the `,` or `]` after it
+    fn element(&mut self, vec: &mut Vec<CowValue<'v>>) -> Result<Step, Failure<'s, 't>> {
+        let missing_bracket = "Invalid JSON array! Missing a closing square bracket \"]\"";
+        let (token, offset) = self.next();
+        let element = match token {
+            Some(Token::SquareBracketClose) if vec.is_empty() || self.trailing_comma(']', offset) => return Ok(Step::Close),
+            Some(t) => self.value(t, offset).unwrap_or_else(|| Err(self.error(
+                format!("Invalid JSON array! Invalid token: {}", t), offset, "expected a value or ']'"))),
+            None => Err(self.error(missing_bracket.to_string(), offset, "expected a value or ']'"))
+        };
+        vec.push(element.map_err(|e| (e, token))?);
+        match self.next() {
+            (Some(Token::Comma), _) => Ok(Step::Continue),
+            (Some(Token::SquareBracketClose), _) => Ok(Step::Close),
+            (Some(t), offset) => Err((self.error(
+                format!("Invalid JSON array! Invalid token: {}", t), offset, "expected ',' or ']'"), Some(t))),
+            (None, offset) => Err((self.error(missing_bracket.to_string(), offset, "expected ',' or ']'"), None))
+        }
+    }
+
+    fn get_jobject(&mut self) -> Result<CowValue<'v>, JsonError> {
+        let mut obj = CowObject::new();
+        loop {
+            let step = match self.member(&mut obj) {
+                Ok(step) => step,
+                Err((error, consumed)) => self.recover(error, consumed)?
+            };
+            match step {
+                Step::Continue => continue,
+                Step::Close | Step::End => return Ok(CowValue::Object(obj))
+            }
+        }
+    }
+
+    /// Parses a member of an object and the `,` or `}` after it
+    fn member(&mut self, obj: &mut CowObject<'v>) -> Result<Step, Failure<'s, 't>> {
+        let missing_bracket = "Invalid JSON object! Missing a closing curly bracket \"}\"";
+        let (key, key_offset) = match self.next() {
+            (Some(Token::String(s)), offset) => (s.to_string(), offset),
+            (Some(Token::Identifier(i)), offset) => {
+                self.warnings.push(self.error(format!("Unquoted key {}", i), offset, "put the key in double quotes"));
+                (i.to_string(), offset)
+            }
+            (Some(Token::CurlyBracketClose), offset) if obj.is_empty() || self.trailing_comma('}', offset) => {
+                return Ok(Step::Close)
+            }
+            (Some(t), offset) => return Err((self.error(
+                format!("Invalid JSON object! Invalid token:  {}", t), offset, "expected a string key or '}'"), Some(t))),
+            (None, offset) => return Err((self.error(missing_bracket.to_string(), offset, "expected a string key or '}'"), None))
+        };
+        match self.next() {
+            (Some(Token::Colon), _) => (),
+            (Some(t), offset) => return Err((self.error(
+                format!("Invalid JSON object! Invalid token: {} instead of \":\"", t), offset, "expected ':'"), Some(t))),
+            (None, offset) => return Err((self.error(
+                "Invalid JSON object! Missing a colon \":\"".to_string(), offset, "expected ':'"), None))
         };
-        match obj.insert(key.clone(), get_jvalue(tokens_itr)?) {
-            Some(_) => return
-                Err(format!("Invalid JSON object: the key {} is not unique", key)),
-            None => ()
+        let (token, offset) = self.next();
+        let value = self.value_at(token, offset).map_err(|e| (e, token))?;
+        if obj.insert(key.clone(), value).is_some() {
+            let error = JsonError::new(ErrorKind::Syntax, format!("Invalid JSON object: the key {} is not unique", key))
+                .with_position(self.source, key_offset);
+            self.report(error).map_err(|e| (e, None))?;
+        }
+        match self.next() {
+            (Some(Token::Comma), _) => Ok(Step::Continue),
+            (Some(Token::CurlyBracketClose), _) => Ok(Step::Close),
+            (Some(t), offset) => Err((self.error(
+                format!("Invalid JSON object! Invalid token: {}", t), offset, "expected ',' or '}'"), Some(t))),
+            (None, offset) => Err((self.error(missing_bracket.to_string(), offset, "expected ',' or '}'"), None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse;
+
+    fn error(json: &str) -> (usize, usize, Option<String>) {
+        let e = parse(json).unwrap_err();
+        (e.line().unwrap(), e.column().unwrap(), e.hint().map(|h| h.to_string()))
+    }
+
+    #[test]
+    fn test_error_positions() {
+        assert_eq!((1, 7, Some("expected ',' or ']'".to_string())), error("[1, 2 3]"));
+        assert_eq!((2, 7, Some("expected ':'".to_string())), error("{\n  \"a\" 1}"));
+        assert_eq!((1, 2, Some("expected a string key or '}'".to_string())), error("{[1]: 2}"));
+        assert_eq!((1, 8, Some("expected ',' or '}'".to_string())), error("{\"a\": 1"));
+        assert_eq!((1, 1, Some("expected a value".to_string())), error(""));
+        assert_eq!((1, 9, None), error("{\"a\": 1,\"a\": 2}"));
+        assert_eq!((1, 2, None), error("[1.2.3]"));
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        for json in ["[-.e]", "-", "1.", "1e", "1e+", "-.5", "1.e5", "-e5", "[1., 2]", "{\"a\": 1e}"] {
+            let error = parse(json).unwrap_err();
+            assert_eq!(crate::error::ErrorKind::Syntax, error.kind(), "{}", json);
+            assert!(error.message().starts_with("Digit was expected"), "{}: {}", json, error.message());
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_trailing_input() {
+        use crate::parser::{parse_borrowed, parse_collecting, parse_with, parse_with_stats, ParseOptions, Parser};
+        for json in ["1 2", "[1,2] ]", "{} {}", "null,", "\"a\" \"b\""] {
+            let error = parse(json).unwrap_err();
+            assert_eq!(Some("expected the end of the input"), error.hint(), "{}", json);
+            assert!(parse_with(json, &ParseOptions::lenient()).is_err(), "{}", json);
+            assert!(parse_with_stats(json).is_err(), "{}", json);
+            assert!(Parser::new().parse(json).is_err(), "{}", json);
+            assert_eq!(1, parse_collecting(json).unwrap_err().len(), "{}", json);
+            assert_eq!(Some(error), parse_borrowed(json).err(), "{}", json);
+        }
+        let error = parse("[1, 2] ]").unwrap_err();
+        assert_eq!(("Expected the end of the input, found ']'", Some(7)), (error.message(), error.offset()));
+        assert!(parse(" [1, 2]\n ").is_ok());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        use crate::parser::{parse_borrowed, parse_collecting, parse_with, parse_with_stats, ParseOptions, Parser};
+        let deep = "[".repeat(1_000_000);
+        let error = parse(&deep).unwrap_err();
+        assert_eq!(("The value is nested deeper than the limit of 128 levels", Some(128)),
+                   (error.message(), error.offset()));
+        assert!(parse_borrowed(&deep).is_err());
+        assert!(parse_with(&deep, &ParseOptions::lenient()).is_err());
+        assert!(parse_with_stats(&deep).is_err());
+        assert!(Parser::new().parse(&deep).is_err());
+        assert!(parse_collecting(&deep).is_err());
+        let deep = format!("{}{}", "{\"a\": ".repeat(1_000_000), "}".repeat(1_000_000));
+        assert_eq!(Some(128 * 6), parse(&deep).unwrap_err().offset());
+
+        let nested = |depth| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(128)).is_ok());
+        assert!(parse(&nested(129)).is_err());
+        let options = ParseOptions { max_depth: 2, ..ParseOptions::default() };
+        assert!(parse_with("[{\"a\": 1}, [2]]", &options).is_ok());
+        let error = parse_with("[{\"a\": [1]}]", &options).unwrap_err();
+        assert_eq!(("The value is nested deeper than the limit of 2 levels", Some(7)),
+                   (error.message(), error.offset()));
+        let errors = parse_collecting(&format!("[{}, 1 2]", nested(200))).unwrap_err();
+        assert_eq!(vec![Some(128), Some(406)], errors.iter().map(|e| e.offset()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_parse_borrowed() {
+        use crate::data_structures::CowValue;
+        use crate::parser::parse_borrowed;
+        let json = "{\"long\": \"a string that is too long to be stored inline\", \"short\": \"s\", \
+                    \"escaped\": \"a\\tb\", \"values\": [1.5, true, null, \"\\u00e9\", {\"\\n\": \"n\"}]}";
+        let borrowed = parse_borrowed(json).unwrap();
+        let is_borrowed = |v: &CowValue| matches!(v, CowValue::String(s) if s.is_borrowed());
+        assert!(is_borrowed(&borrowed["long"]) && is_borrowed(&borrowed["short"]));
+        assert!(is_borrowed(&borrowed["values"][4]["\n"]));
+        assert!(!is_borrowed(&borrowed["escaped"]) && !is_borrowed(&borrowed["values"][3]));
+        assert_eq!(CowValue::String("a\tb".into()), borrowed["escaped"]);
+        let owned = parse(json).unwrap();
+        assert_eq!(owned, borrowed.into_owned());
+        for json in ["", "[1, 2", "{\"a\": 1, \"a\": 2}", "[01]", "\"\\x\""] {
+            assert_eq!(parse(json).err(), parse_borrowed(json).err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_surrogates_round_trip() {
+        use crate::parser::{parse_with, ParseOptions, SurrogatePolicy};
+        use crate::serializer::Serialize;
+        let value = parse("\"\\ud83d\\ude00 caf\\u00e9\"").unwrap();
+        assert_eq!(value, parse(&value.serialize()).unwrap());
+        let options = ParseOptions { lone_surrogates: SurrogatePolicy::Replace, ..ParseOptions::default() };
+        let (value, _) = parse_with("[\"\\ud800\", \"a\\udfffb\"]", &options).unwrap();
+        assert_eq!("[\"\u{fffd}\",\"a\u{fffd}b\"]", value.serialize());
+        assert_eq!(value, parse(&value.serialize()).unwrap());
+        let options = ParseOptions { lone_surrogates: SurrogatePolicy::Preserve, ..ParseOptions::default() };
+        let (value, warnings) = parse_with("[\"\\ud800\", \"a\\udfffb\"]", &options).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!("[\"\\\\ud800\",\"a\\\\udfffb\"]", value.serialize());
+        assert_eq!(value, parse(&value.serialize()).unwrap());
+        assert_eq!(value, parse_with(&value.serialize(), &options).unwrap().0);
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!("Invalid JSON array! Invalid token: 3 at line 2, column 3\n\
+                    2 | 2 3]\n  \
+                      |   ^ expected ',' or ']'",
+                   parse("[1,\n2 3]").unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_parse_collecting() {
+        use crate::parser::parse_collecting;
+        let messages = |json: &str| -> Vec<(usize, String)> {
+            parse_collecting(json).unwrap_err().iter().map(|e| (e.offset().unwrap(), e.message().to_string())).collect()
+        };
+        assert_eq!(vec![(1, "Invalid char ''' (0x0027)".to_string()), (11, "Invalid token \"nul]\"".to_string())],
+                   messages("['a b', 4, nul]"));
+        assert_eq!(vec![(1, "Invalid JSON object! Invalid token:  [".to_string()),
+                        (13, "Invalid JSON object! Invalid token: 3 instead of \":\"".to_string())],
+                   messages("{[1]: 2, \"b\" 3, \"c\": 4}"));
+        assert_eq!(vec![(9, "Invalid JSON object: the key a is not unique".to_string())],
+                   messages("{\"a\": 1, \"a\": 2}"));
+        assert_eq!(vec![(8, "Invalid JSON object! Missing a closing curly bracket \"}\"".to_string()),
+                        (8, "Invalid JSON array! Missing a closing square bracket \"]\"".to_string())],
+                   messages("[{\"a\": 1"));
+        assert_eq!(2, messages("[1.2.3, [1 2]]").len());
+        assert_eq!(1, messages("").len());
+        assert_eq!(Ok(crate::json!("{\"a\": [1, {}]}")), parse_collecting("{\"a\": [1, {}]}"));
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        use crate::parser::{parse_with, ParseOptions};
+        let options = ParseOptions { allow_trailing_commas: true, ..ParseOptions::default() };
+        let parse_lenient = |json: &str| parse_with(json, &options).map(|(value, warnings)| {
+            (value, warnings.iter().map(|w| w.offset().unwrap()).collect::<Vec<_>>())
+        });
+        assert_eq!(Ok((crate::json!("[1, 2, 3]"), vec![9])), parse_lenient("[1, 2, 3,]"));
+        assert_eq!(Ok((crate::json!("{\"a\": [{}]}"), vec![10, 12])), parse_lenient("{\"a\": [{},],}"));
+        assert_eq!(Ok((crate::json!("[[], {}]"), vec![])), parse_lenient("[[], {}]"));
+        assert!(parse_lenient("[,]").is_err());
+        assert!(parse_lenient("{,}").is_err());
+        assert!(parse_lenient("[1,,]").is_err());
+        assert!(parse_with("[1,]", &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_single_quotes() {
+        use crate::parser::{parse_with, ParseOptions};
+        let (value, warnings) = parse_with("{'a': ['it\\'s', \"b\"]}", &ParseOptions::lenient()).unwrap();
+        assert_eq!(crate::json!("{\"a\": [\"it's\", \"b\"]}"), value);
+        assert_eq!(vec!["Single-quoted string"; 2], warnings.iter().map(|w| w.message()).collect::<Vec<_>>());
+        assert_eq!(Some("JSON strings must be in double quotes"), parse("{'a': 1}").unwrap_err().hint());
+    }
+
+    #[test]
+    fn test_unquoted_keys() {
+        use crate::parser::{parse_with, ParseOptions};
+        let options = ParseOptions { allow_unquoted_keys: true, ..ParseOptions::default() };
+        let (value, warnings) = parse_with("{name: \"x\", nested: {ok: true}, \"quoted\": null}", &options).unwrap();
+        assert_eq!(crate::json!("{\"name\": \"x\", \"nested\": {\"ok\": true}, \"quoted\": null}"), value);
+        assert_eq!(vec![(1, "Unquoted key name"), (12, "Unquoted key nested"), (21, "Unquoted key ok")],
+                   warnings.iter().map(|w| (w.offset().unwrap(), w.message())).collect::<Vec<_>>());
+        assert_eq!("Invalid JSON array! Invalid token: name", parse_with("[name]", &options).unwrap_err().message());
+        assert!(parse_with("{null: 1}", &options).is_err());
+        assert!(parse_with("{a b: 1}", &options).is_err());
+        assert!(parse_with("{1a: 1}", &options).is_err());
+    }
+}
@@ -0,0 +1,405 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Detection and repair of common mistakes in almost-JSON text
+
+use std::fmt::{Display, Formatter};
+
+use crate::data_structures::JValue;
+use crate::error::JsonError;
+use crate::parser::parse;
+
+/// A kind of mistake fixed by [`repair`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FixKind {
+    /// A `,` before a closing `]` or `}` was removed
+    TrailingComma,
+    /// A string in single quotes was put in double quotes
+    SingleQuotes,
+    /// An object key without quotes was put in double quotes
+    UnquotedKey,
+    /// A Python literal `True`, `False` or `None` was replaced by `true`, `false` or `null`
+    PythonLiteral,
+    /// A `//` or `/* */` comment was removed
+    Comment,
+    /// A newline, carriage return or tab in a string was escaped
+    UnescapedControl,
+    /// A string unterminated at the end of the text was closed
+    MissingQuote,
+    /// An array or object unterminated at the end of the text was closed
+    MissingBracket,
+}
+
+impl Display for FixKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixKind::TrailingComma => write!(f, "removed a trailing comma"),
+            FixKind::SingleQuotes => write!(f, "replaced single quotes with double quotes"),
+            FixKind::UnquotedKey => write!(f, "quoted an object key"),
+            FixKind::PythonLiteral => write!(f, "replaced a Python literal"),
+            FixKind::Comment => write!(f, "removed a comment"),
+            FixKind::UnescapedControl => write!(f, "escaped a control character in a string"),
+            FixKind::MissingQuote => write!(f, "closed a string"),
+            FixKind::MissingBracket => write!(f, "closed an array or object"),
+        }
+    }
+}
+
+/// A fix applied by [`repair`], at a byte offset of the original text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fix {
+    pub kind: FixKind,
+    pub offset: usize,
+}
+
+impl Display for Fix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+/// Fixes trailing commas, single-quoted strings, unquoted keys, the Python literals `True`,
+/// `False` and `None`, comments, newlines and tabs in strings, and strings, arrays and objects
+/// left open at the end of the text. Returns the repaired text if it is valid JSON, or else the
+/// error of parsing `text` as it is.
+///
+/// ```
+/// # use json::parser::repair;
+/// assert_eq!(Ok("{\"a\": [\"x\", true],  \"b\": null}".to_string()),
+///            repair("{a: ['x', True,], // comment\n 'b': None,}").map(|s| s.replace('\n', "")));
+/// assert!(repair("{\"a\": }").is_err());
+/// ```
+pub fn repair(text: &str) -> Result<String, JsonError> {
+    let (repaired, _) = repair_text(text);
+    match parse(&repaired) {
+        Ok(_) => Ok(repaired),
+        Err(e) => Err(parse(text).err().unwrap_or(e))
+    }
+}
+
+/// Repairs `text` like [`repair`] and parses it, returning the value and the fixes applied in
+/// the order of their offsets. Fails with the error of parsing `text` as it is if the repaired
+/// text is still not valid JSON.
+///
+/// ```
+/// # use json::json;
+/// # use json::parser::{repair_and_parse, FixKind};
+/// let (j_value, fixes) = repair_and_parse("{answer: 'line 1\nline 2', \"items\": [1, 2,").unwrap();
+/// assert_eq!(json!("{\"answer\": \"line 1\\nline 2\", \"items\": [1, 2]}"), j_value);
+/// let kinds: Vec<FixKind> = fixes.iter().map(|fix| fix.kind).collect();
+/// assert_eq!(vec![FixKind::UnquotedKey, FixKind::SingleQuotes, FixKind::UnescapedControl,
+///                 FixKind::TrailingComma, FixKind::MissingBracket, FixKind::MissingBracket], kinds);
+/// ```
+pub fn repair_and_parse(text: &str) -> Result<(JValue, Vec<Fix>), JsonError> {
+    let (repaired, mut fixes) = repair_text(text);
+    match parse(&repaired) {
+        Ok(value) => {
+            fixes.sort_by_key(|fix| fix.offset);
+            Ok((value, fixes))
+        }
+        Err(e) => Err(parse(text).err().unwrap_or(e))
+    }
+}
+
+/// Applies the fixes of [`repair`] without checking the result, returning the fixes applied
+pub(crate) fn repair_text(text: &str) -> (String, Vec<Fix>) {
+    let mut repairer = Repairer {
+        text,
+        position: 0,
+        out: String::with_capacity(text.len()),
+        fixes: Vec::new(),
+        open: Vec::new(),
+    };
+    repairer.run();
+    repairer.close();
+    (repairer.out, repairer.fixes)
+}
+
+struct Repairer<'a> {
+    text: &'a str,
+    position: usize,
+    out: String,
+    fixes: Vec<Fix>,
+    /// The closing brackets of the arrays and objects left open
+    open: Vec<char>,
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+impl Repairer<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn fix(&mut self, kind: FixKind, offset: usize) {
+        self.fixes.push(Fix { kind, offset });
+    }
+
+    fn run(&mut self) {
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            match c {
+                '"' => self.copy_string(),
+                '\'' => self.single_quoted_string(),
+                '/' if self.rest().starts_with("//") => {
+                    let end = self.rest().find('\n').map_or(self.text.len(), |i| start + i);
+                    self.position = end;
+                    self.fix(FixKind::Comment, start);
+                }
+                '/' if self.rest().starts_with("/*") => {
+                    let end = self.rest()[2..].find("*/").map_or(self.text.len(), |i| start + i + 4);
+                    self.position = end;
+                    self.fix(FixKind::Comment, start);
+                }
+                ',' if next_significant(&self.rest()[1..]).is_some_and(|c| c == ']' || c == '}') => {
+                    self.position += 1;
+                    self.fix(FixKind::TrailingComma, start);
+                }
+                '0'..='9' | '-' => {
+                    let len = self.rest().find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+                        .unwrap_or(self.rest().len());
+                    self.out.push_str(&self.text[start..start + len]);
+                    self.position += len;
+                }
+                c if is_identifier_start(c) => self.word(),
+                '{' | '[' => {
+                    self.open.push(if c == '{' { '}' } else { ']' });
+                    self.out.push(c);
+                    self.position += 1;
+                }
+                '}' | ']' => {
+                    if self.open.last() == Some(&c) {
+                        self.open.pop();
+                    }
+                    self.out.push(c);
+                    self.position += 1;
+                }
+                c => {
+                    self.out.push(c);
+                    self.position += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Closes the strings, arrays and objects left open at the end of the text
+    fn close(&mut self) {
+        if self.open.is_empty() {
+            return;
+        }
+        let trimmed = self.out.trim_end_matches([' ', '\n', '\r', '\t']).len();
+        if self.out[..trimmed].ends_with(',') {
+            self.out.truncate(trimmed - 1);
+            let offset = self.text.trim_end_matches([' ', '\n', '\r', '\t']).len() - 1;
+            self.fix(FixKind::TrailingComma, offset);
+        }
+        while let Some(bracket) = self.open.pop() {
+            self.out.push(bracket);
+            self.fix(FixKind::MissingBracket, self.text.len());
+        }
+    }
+
+    /// Pushes a char of a string, escaping the control chars a string can not contain
+    fn push_string_char(&mut self, c: char, offset: usize) {
+        let escape = match c {
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            c => {
+                self.out.push(c);
+                return;
+            }
+        };
+        self.out.push_str(escape);
+        self.fix(FixKind::UnescapedControl, offset);
+    }
+
+    fn close_string(&mut self) {
+        self.out.push('"');
+        self.position = self.text.len();
+        self.fix(FixKind::MissingQuote, self.text.len());
+    }
+
+    fn copy_string(&mut self) {
+        let start = self.position;
+        let mut escaped = false;
+        self.out.push('"');
+        for (i, c) in self.text[start + 1..].char_indices() {
+            if escaped {
+                escaped = false;
+                self.out.push(c);
+            } else if c == '\\' {
+                escaped = true;
+                self.out.push(c);
+            } else if c == '"' {
+                self.position = start + 1 + i + 1;
+                self.out.push('"');
+                return;
+            } else {
+                self.push_string_char(c, start + 1 + i);
+            }
+        }
+        self.close_string();
+    }
+
+    fn single_quoted_string(&mut self) {
+        let start = self.position;
+        let mut chars = self.text[start + 1..].char_indices();
+        self.out.push('"');
+        self.fix(FixKind::SingleQuotes, start);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, '\'')) => self.out.push('\''),
+                    Some((_, escaped)) => {
+                        self.out.push('\\');
+                        self.out.push(escaped);
+                    }
+                    None => self.out.push('\\')
+                },
+                '"' => self.out.push_str("\\\""),
+                '\'' => {
+                    self.out.push('"');
+                    self.position = start + 1 + i + 1;
+                    return;
+                }
+                c => self.push_string_char(c, start + 1 + i)
+            }
+        }
+        self.close_string();
+    }
+
+    fn word(&mut self) {
+        let start = self.position;
+        let len = self.rest().find(|c: char| !is_identifier(c)).unwrap_or(self.rest().len());
+        let word = &self.text[start..start + len];
+        self.position += len;
+        let literal = match word {
+            "True" => Some("true"),
+            "False" => Some("false"),
+            "None" => Some("null"),
+            _ => None
+        };
+        if next_significant(self.rest()) == Some(':') && !matches!(word, "true" | "false" | "null") {
+            self.out.push('"');
+            self.out.push_str(word);
+            self.out.push('"');
+            self.fix(FixKind::UnquotedKey, start);
+        } else if let Some(literal) = literal {
+            self.out.push_str(literal);
+            self.fix(FixKind::PythonLiteral, start);
+        } else {
+            self.out.push_str(word);
+        }
+    }
+}
+
+/// Returns the first char of `text` that is not whitespace
+fn next_significant(text: &str) -> Option<char> {
+    text.chars().find(|c| !matches!(c, ' ' | '\n' | '\r' | '\t'))
+}
+
+/// Replaces the hint of a parse error with a suggestion when the text at its offset looks like a
+/// common mistake
+pub(crate) fn suggest(text: &str, error: JsonError) -> JsonError {
+    let offset = match error.offset() {
+        Some(offset) if offset < text.len() => offset,
+        _ => return error
+    };
+    let rest = &text[offset..];
+    let before = text[..offset].trim_end_matches([' ', '\n', '\r', '\t']);
+    let word_len = rest.find(|c: char| !is_identifier(c)).unwrap_or(rest.len());
+    let word = &rest[..word_len];
+    let suggestion = if rest.starts_with('\'') {
+        "JSON strings must be in double quotes".to_string()
+    } else if rest.starts_with("//") || rest.starts_with("/*") {
+        "JSON does not allow comments".to_string()
+    } else if (rest.starts_with(']') || rest.starts_with('}')) && before.ends_with(',') {
+        format!("remove the trailing comma before '{}'", &rest[..1])
+    } else if !word.is_empty() && is_identifier_start(rest.chars().next().unwrap())
+        && next_significant(&rest[word_len..]) == Some(':') {
+        format!("object keys must be in double quotes: \"{}\"", word)
+    } else {
+        match word {
+            "True" => "use true instead of True".to_string(),
+            "False" => "use false instead of False".to_string(),
+            "None" => "use null instead of None".to_string(),
+            "NaN" | "Infinity" | "undefined" => format!("JSON has no {}, use null or a string", word),
+            _ => return error
+        }
+    };
+    error.with_hint(suggestion)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse;
+    use crate::parser::repair::{repair, repair_and_parse, repair_text, Fix, FixKind};
+
+    fn hint(json: &str) -> String {
+        parse(json).unwrap_err().hint().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!("remove the trailing comma before ']'", hint("[1, 2,\n]"));
+        assert_eq!("remove the trailing comma before '}'", hint("{\"a\": 1,}"));
+        assert_eq!("JSON strings must be in double quotes", hint("['a']"));
+        assert_eq!("object keys must be in double quotes: \"key\"", hint("{key : 1}"));
+        assert_eq!("use null instead of None", hint("[None]"));
+        assert_eq!("use true instead of True", hint("{\"a\": True}"));
+        assert_eq!("JSON does not allow comments", hint("[1 // one\n]"));
+        assert_eq!("JSON has no NaN, use null or a string", hint("NaN"));
+        assert_eq!("expected ',' or ']'", hint("[1 2]"));
+    }
+
+    #[test]
+    fn test_repair() {
+        let (repaired, fixes) = repair_text("/* c */{'it\\'s': \"a\\\"b\", n: [1e5, False,],}");
+        assert_eq!("{\"it's\": \"a\\\"b\", \"n\": [1e5, false]}", repaired);
+        let kinds: Vec<FixKind> = fixes.iter().map(|f| f.kind).collect();
+        assert_eq!(vec![FixKind::Comment, FixKind::SingleQuotes, FixKind::UnquotedKey, FixKind::PythonLiteral,
+                        FixKind::TrailingComma, FixKind::TrailingComma], kinds);
+        assert_eq!(vec![0, 8, 25, 34, 39, 41], fixes.iter().map(|f| f.offset).collect::<Vec<usize>>());
+        assert_eq!(Ok("{\"a\": \"'\\\"\"}".to_string()), repair("{\"a\": '\\'\"'}"));
+        let valid = "{\"true\": [true, \"//\", 1.5e-3]}";
+        assert_eq!(Ok(valid.to_string()), repair(valid));
+        assert_eq!(Some(1), repair("[x]").unwrap_err().offset());
+    }
+
+    #[test]
+    fn test_repair_and_parse() {
+        let (value, fixes) = repair_and_parse("[\"a\tb\", {\"c\": \"unterminated").unwrap();
+        assert_eq!(crate::json!("[\"a\\tb\", {\"c\": \"unterminated\"}]"), value);
+        assert_eq!(vec![Fix { kind: FixKind::UnescapedControl, offset: 3 }, Fix { kind: FixKind::MissingQuote, offset: 27 },
+                        Fix { kind: FixKind::MissingBracket, offset: 27 }, Fix { kind: FixKind::MissingBracket, offset: 27 }],
+                   fixes);
+        let (_, fixes) = repair_and_parse(" {\"a\": [1]}").unwrap();
+        assert!(fixes.is_empty());
+        assert!(repair_and_parse("{\"a\": ").is_err());
+    }
+}
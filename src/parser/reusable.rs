@@ -0,0 +1,243 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::cell::RefCell;
+
+use crate::data_structures::JValue;
+use crate::error::JsonError;
+use crate::parser::parser::{parse_into, ParseOptions, SurrogatePolicy};
+use crate::parser::tokenizer::Token;
+
+/// A parser that keeps its scratch storage between documents, for services that parse many
+/// small documents. The storage of the tokens and of the warnings grows to fit the largest
+/// document parsed so far and is reused after that, so parsing a document like the ones before
+/// allocates only for the value built and for the strings with escape sequences.
+///
+/// ```
+/// # use json::json;
+/// # use json::parser::{ParseOptions, Parser};
+/// let mut parser = Parser::new();
+/// let ids: Vec<_> = ["{\"id\": 1}", "{\"id\": 2}"].iter()
+///     .map(|body| parser.parse(body).unwrap()["id"].clone())
+///     .collect();
+/// assert_eq!(vec![json!("1"), json!("2")], ids);
+///
+/// let mut lenient = Parser::with_options(ParseOptions::lenient());
+/// assert_eq!(json!("[1]"), lenient.parse("[1,]").unwrap());
+/// assert_eq!("Trailing comma before ']'", lenient.warnings()[0].message());
+/// ```
+#[derive(Debug, Default)]
+pub struct Parser {
+    options: ParseOptions,
+    /// Always empty between calls; only its storage is kept
+    tokens: Vec<(Token<'static>, usize)>,
+    warnings: Vec<JsonError>,
+}
+
+impl Parser {
+    /// Creates a parser that accepts only valid JSON, like [`parse`](crate::parser::parse)
+    pub fn new() -> Parser {
+        Parser::default()
+    }
+
+    /// Creates a parser that accepts the mistakes `options` allows, like
+    /// [`parse_with`](crate::parser::parse_with)
+    pub fn with_options(options: ParseOptions) -> Parser {
+        Parser { options, ..Parser::default() }
+    }
+
+    /// Starts configuring a parser, from the options of [`Parser::new`]
+    pub fn builder() -> ParserBuilder {
+        ParserBuilder::default()
+    }
+
+    /// Returns the options the parser was created with
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// Parses a JSON string into a [`JValue`]. The warnings for the mistakes the options
+    /// allow are kept until the next call, in [`Parser::warnings`].
+    pub fn parse(&mut self, json_string: &str) -> Result<JValue, JsonError> {
+        self.warnings.clear();
+        let mut tokens = recycle(std::mem::take(&mut self.tokens));
+        let value = parse_into(json_string, &self.options, &mut tokens, &mut self.warnings);
+        self.tokens = recycle(tokens);
+        value
+    }
+
+    /// Returns the warnings of the last call to [`Parser::parse`], in the order of their
+    /// positions
+    pub fn warnings(&self) -> &[JsonError] {
+        &self.warnings
+    }
+}
+
+/// Configures a [`Parser`] one option at a time, as an alternative to filling in
+/// [`ParseOptions`]. The parser built is `Send`, so it can be moved to the thread that uses it.
+///
+/// ```
+/// # use json::json;
+/// # use json::parser::Parser;
+/// let mut parser = Parser::builder().allow_trailing_commas(true).max_string_len(8).build();
+/// assert_eq!(json!("[\"short\"]"), parser.parse("[\"short\",]").unwrap());
+/// assert!(parser.parse("[\"much too long\"]").is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParserBuilder {
+    options: ParseOptions,
+}
+
+impl ParserBuilder {
+    /// Replaces all the options set so far
+    pub fn options(mut self, options: ParseOptions) -> ParserBuilder {
+        self.options = options;
+        self
+    }
+
+    /// Sets [`ParseOptions::allow_invalid_escapes`]
+    pub fn allow_invalid_escapes(mut self, allow: bool) -> ParserBuilder {
+        self.options.allow_invalid_escapes = allow;
+        self
+    }
+
+    /// Sets [`ParseOptions::lone_surrogates`]
+    pub fn lone_surrogates(mut self, policy: SurrogatePolicy) -> ParserBuilder {
+        self.options.lone_surrogates = policy;
+        self
+    }
+
+    /// Sets [`ParseOptions::allow_trailing_commas`]
+    pub fn allow_trailing_commas(mut self, allow: bool) -> ParserBuilder {
+        self.options.allow_trailing_commas = allow;
+        self
+    }
+
+    /// Sets [`ParseOptions::allow_single_quotes`]
+    pub fn allow_single_quotes(mut self, allow: bool) -> ParserBuilder {
+        self.options.allow_single_quotes = allow;
+        self
+    }
+
+    /// Sets [`ParseOptions::allow_unquoted_keys`]
+    pub fn allow_unquoted_keys(mut self, allow: bool) -> ParserBuilder {
+        self.options.allow_unquoted_keys = allow;
+        self
+    }
+
+    /// Sets [`ParseOptions::max_string_len`]
+    pub fn max_string_len(mut self, max: usize) -> ParserBuilder {
+        self.options.max_string_len = Some(max);
+        self
+    }
+
+    /// Sets [`ParseOptions::max_number_len`]
+    pub fn max_number_len(mut self, max: usize) -> ParserBuilder {
+        self.options.max_number_len = Some(max);
+        self
+    }
+
+    /// Sets [`ParseOptions::max_depth`]
+    pub fn max_depth(mut self, max: usize) -> ParserBuilder {
+        self.options.max_depth = max;
+        self
+    }
+
+    /// Creates the parser
+    pub fn build(self) -> Parser {
+        Parser::with_options(self.options)
+    }
+}
+
+thread_local! {
+    static DEFAULT: RefCell<Parser> = RefCell::new(Parser::new());
+}
+
+/// Runs `f` with this thread's default [`Parser`], which accepts only valid JSON, so that code
+/// parsing many documents reuses the parser's storage without passing a parser around. If `f`
+/// calls this function again, the inner call gets a new parser.
+///
+/// ```
+/// # use json::json;
+/// # use json::parser::with_default_parser;
+/// let j_value = with_default_parser(|parser| parser.parse("{\"a\": 1}")).unwrap();
+/// assert_eq!(json!("{\"a\": 1}"), j_value);
+/// ```
+pub fn with_default_parser<T, F: FnOnce(&mut Parser) -> T>(f: F) -> T {
+    DEFAULT.with(|parser| match parser.try_borrow_mut() {
+        Ok(mut parser) => f(&mut parser),
+        Err(_) => f(&mut Parser::new())
+    })
+}
+
+/// Empties `tokens` and returns its storage for tokens borrowing from another string. As the
+/// two types have the same layout, the collection reuses the storage in place.
+fn recycle<'a, 'b>(mut tokens: Vec<(Token<'a>, usize)>) -> Vec<(Token<'b>, usize)> {
+    tokens.clear();
+    tokens.into_iter().map(|_| unreachable!()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json;
+    use crate::parser::{with_default_parser, ParseOptions, Parser, SurrogatePolicy};
+
+    #[test]
+    fn test_storage_is_reused() {
+        let mut parser = Parser::new();
+        assert_eq!(json!("[1, \"a\", {\"b\": null}]"), parser.parse("[1, \"a\", {\"b\": null}]").unwrap());
+        let capacity = parser.tokens.capacity();
+        assert!(capacity >= 11);
+        assert_eq!(json!("[true]"), parser.parse("[true]").unwrap());
+        assert!(parser.parse("[1,]").is_err());
+        assert_eq!(capacity, parser.tokens.capacity());
+        assert!(parser.tokens.is_empty());
+    }
+
+    #[test]
+    fn test_warnings_are_per_call() {
+        let options = ParseOptions { allow_trailing_commas: true, ..ParseOptions::default() };
+        let mut parser = Parser::with_options(options);
+        parser.parse("{\"a\": [1,],}").unwrap();
+        assert_eq!(2, parser.warnings().len());
+        parser.parse("{}").unwrap();
+        assert!(parser.warnings().is_empty());
+        assert_eq!(&options, parser.options());
+    }
+
+    #[test]
+    fn test_builder() {
+        let parser = Parser::builder().allow_invalid_escapes(true).lone_surrogates(SurrogatePolicy::Replace)
+            .allow_single_quotes(true).allow_unquoted_keys(true).max_number_len(3).max_depth(2).build();
+        let options = ParseOptions { allow_invalid_escapes: true, lone_surrogates: SurrogatePolicy::Replace,
+                                     allow_single_quotes: true, allow_unquoted_keys: true, max_number_len: Some(3),
+                                     max_depth: 2, ..ParseOptions::default() };
+        assert_eq!(&options, parser.options());
+        let parser = Parser::builder().max_string_len(1).options(ParseOptions::lenient()).build();
+        assert_eq!(&ParseOptions::lenient(), parser.options());
+    }
+
+    #[test]
+    fn test_default_parser_is_reentrant() {
+        let (outer, inner) = with_default_parser(|parser| {
+            let inner = with_default_parser(|inner| inner.parse("2"));
+            (parser.parse("1"), inner)
+        });
+        assert_eq!((Ok(json!("1")), Ok(json!("2"))), (outer, inner));
+    }
+}
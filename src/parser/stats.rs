@@ -0,0 +1,161 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Measurement helpers for profiling the tokenizer and the parser
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use crate::data_structures::JValue;
+use crate::error::JsonError;
+use crate::parser::parser::parse_tokens;
+use crate::parser::repair::suggest;
+use crate::parser::tokenizer::tokenize;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// A global allocator that counts every allocation made through it before delegating to
+/// [`System`]. The allocation counts reported by [`ParseStats`] are only meaningful when
+/// this allocator is installed.
+///
+/// ```
+/// use json::parser::CountingAllocator;
+///
+/// #[global_allocator]
+/// static GLOBAL: CountingAllocator = CountingAllocator;
+/// # fn main() {}
+/// ```
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Measurements collected while parsing a single JSON string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseStats {
+    bytes: usize,
+    tokens: usize,
+    elapsed: Duration,
+    allocations: usize,
+    allocated_bytes: usize,
+}
+
+impl ParseStats {
+    /// Returns the length of the parsed input in bytes
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the number of tokens produced by the tokenizer
+    pub fn tokens(&self) -> usize {
+        self.tokens
+    }
+
+    /// Returns the wall-clock time spent tokenizing and parsing
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the number of heap allocations made while parsing. Always zero unless
+    /// [`CountingAllocator`] is the global allocator.
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    /// Returns the number of bytes requested from the heap while parsing. Always zero unless
+    /// [`CountingAllocator`] is the global allocator.
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated_bytes
+    }
+
+    /// Returns the parsing throughput in bytes per second
+    pub fn bytes_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.bytes as f64 / seconds
+        }
+    }
+}
+
+/// Parses a JSON string like [`parse`](crate::parser::parse) and also reports how much work
+/// it took.
+///
+/// The allocation counters are process-wide, so allocations made by other threads while
+/// parsing are included in the result.
+///
+/// ```
+/// # use json::parser::parse_with_stats;
+/// # use json::data_structures::JValue;
+/// let (j_value, stats) = parse_with_stats("[true, null]").unwrap();
+/// assert_eq!(JValue::Array(vec![JValue::Boolean(true), JValue::Null]), j_value);
+/// assert_eq!(12, stats.bytes());
+/// assert_eq!(5, stats.tokens());
+/// ```
+pub fn parse_with_stats(json_string: &str) -> Result<(JValue, ParseStats), JsonError> {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let tokens = tokenize(json_string).map_err(|e| suggest(json_string, e))?;
+    let j_value = parse_tokens(json_string, &tokens).map_err(|e| suggest(json_string, e))?;
+    let elapsed = start.elapsed();
+    let stats = ParseStats {
+        bytes: json_string.len(),
+        tokens: tokens.len(),
+        elapsed,
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        allocated_bytes: ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes,
+    };
+    Ok((j_value, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_with_stats;
+
+    #[test]
+    fn test_parse_with_stats() {
+        let (_, stats) = parse_with_stats("{\"key\": [1, 2, 3]}").unwrap();
+        assert_eq!(18, stats.bytes());
+        assert_eq!(11, stats.tokens());
+        assert!(stats.bytes_per_second() >= 0.0);
+        assert_eq!("Invalid char 'x' (0x0078)", parse_with_stats("x").unwrap_err().message());
+    }
+}
@@ -18,152 +18,392 @@
 //! A lexical analyzer for JSON
 
 
+use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 use std::str::Chars;
 
-pub fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::parser::{ParseOptions, SurrogatePolicy};
+
+/// Splits the given string into tokens, each with the byte offset it starts at. String and
+/// number tokens borrow from the input, unless a string has escape sequences to replace.
+pub fn tokenize(s: &str) -> Result<Vec<(Token<'_>, usize)>, JsonError> {
+    tokenize_with(s, &ParseOptions::default(), &mut Vec::new())
+}
+
+/// Splits the given string into tokens like [`tokenize`], accepting the mistakes `options`
+/// allows with a warning in `warnings` for each
+pub(crate) fn tokenize_with<'a>(s: &'a str, options: &ParseOptions, warnings: &mut Vec<JsonError>)
+                                -> Result<Vec<(Token<'a>, usize)>, JsonError> {
+    let mut tokens = Vec::new();
+    scan(s, options, None, warnings, &mut tokens).map(|_| tokens)
+}
+
+/// Splits the given string into tokens like [`tokenize_with`], appending them to `tokens`, so
+/// that its storage can be reused
+pub(crate) fn tokenize_into<'a>(s: &'a str, options: &ParseOptions, warnings: &mut Vec<JsonError>,
+                                tokens: &mut Vec<(Token<'a>, usize)>) -> Result<(), JsonError> {
+    scan(s, options, None, warnings, tokens)
+}
+
+/// Splits the given string into tokens like [`tokenize`], but collects the lexical errors in
+/// `errors` instead of stopping at the first. An invalid token is replaced by a `null` token,
+/// so the parser can go on, and scanning resumes after it.
+pub(crate) fn tokenize_collecting<'a>(s: &'a str, errors: &mut Vec<JsonError>) -> Vec<(Token<'a>, usize)> {
+    let mut tokens = Vec::new();
+    scan(s, &ParseOptions::default(), Some(errors), &mut Vec::new(), &mut tokens).map(|_| tokens).unwrap_or_default()
+}
+
+fn scan<'a>(s: &'a str, options: &ParseOptions, mut errors: Option<&mut Vec<JsonError>>,
+            warnings: &mut Vec<JsonError>, tokens: &mut Vec<(Token<'a>, usize)>) -> Result<(), JsonError> {
     let mut chars = s.chars();
-    let mut tokens: Vec<Token> = Vec::new();
 
-    loop {
-        match chars.next() {
-            Some(c) => {
-                match c {
-                    '{' => tokens.push(Token::CurlyBracketOpen),
-                    '}' => tokens.push(Token::CurlyBracketClose),
-                    '[' => tokens.push(Token::SquareBracketOpen),
-                    ']' => tokens.push(Token::SquareBracketClose),
-                    ':' => tokens.push(Token::Colon),
-                    ',' => tokens.push(Token::Comma),
-                    whitespace if whitespace == 0x0020 as char
-                        || whitespace == 0x000A as char
-                        || whitespace == 0x000D as char
-                        || whitespace == 0x0009 as char => (),
-                    '"' => tokens.push(get_string(&mut chars)?),
-                    '0'..='9' | '-' => tokens.append(&mut get_number(&mut chars, c)?),
-                    't' => tokens.push(get_true(&mut chars)?),
-                    'f' => tokens.push(get_false(&mut chars)?),
-                    'n' => tokens.push(get_null(&mut chars)?),
-                    _ => return Err(format!("Invalid char \'{}\' ({:#06x})", c, c as usize))
+    while let Some(c) = chars.next() {
+        let start = offset(s, &chars) - c.len_utf8();
+        let token = match c {
+            '{' => Ok(Some(Token::CurlyBracketOpen)),
+            '}' => Ok(Some(Token::CurlyBracketClose)),
+            '[' => Ok(Some(Token::SquareBracketOpen)),
+            ']' => Ok(Some(Token::SquareBracketClose)),
+            ':' => Ok(Some(Token::Colon)),
+            ',' => Ok(Some(Token::Comma)),
+            whitespace if whitespace == 0x0020 as char
+                || whitespace == 0x000A as char
+                || whitespace == 0x000D as char
+                || whitespace == 0x0009 as char => Ok(None),
+            '"' => get_string(s, &mut chars, '"', options, warnings).map(Some),
+            '\'' if options.allow_single_quotes => {
+                warnings.push(JsonError::new(ErrorKind::Syntax, "Single-quoted string").with_position(s, start)
+                    .with_hint("use double quotes"));
+                get_string(s, &mut chars, '\'', options, warnings).map(Some)
+            }
+            // The scan of a number consumes the structural char ending it, which comes along
+            '0'..='9' | '-' => get_number(s, &mut chars, options.max_number_len, tokens).map(|_| None),
+            c if options.allow_unquoted_keys && is_identifier_start(c) => Ok(Some(get_identifier(s, &mut chars, start))),
+            't' => get_true(&mut chars).map(Some).map_err(|e| literal_error(e, s, start, "true")),
+            'f' => get_false(&mut chars).map(Some).map_err(|e| literal_error(e, s, start, "false")),
+            'n' => get_null(&mut chars).map(Some).map_err(|e| literal_error(e, s, start, "null")),
+            _ => Err(invalid_char(c, s, start))
+        };
+        match token {
+            Ok(Some(token)) => tokens.push((token, start)),
+            Ok(None) => (),
+            Err(error) => {
+                match errors.as_mut() {
+                    Some(errors) => errors.push(error),
+                    None => return Err(error)
                 }
+                tokens.push((Token::Null, start));
+                chars = s[resume(s, start)..].chars();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The offset to resume scanning at after an invalid token starting at `start`: after the
+/// closing quote of a string, also a single-quoted one, or else at the next structural char or
+/// whitespace
+fn resume(s: &str, start: usize) -> usize {
+    let bytes = s.as_bytes();
+    let mut i = start + 1;
+    let quote = bytes[start];
+    if quote == b'"' || quote == b'\'' {
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' => i += 1,
+                b if b == quote => return i + 1,
+                b'\n' => return i,
+                _ => {}
             }
-            None => break,
+            i += 1;
         }
+        return s.len();
     }
-    Ok(tokens)
+    while i < bytes.len() && !b"{}[]:,\" \t\r\n".contains(&bytes[i]) {
+        i += 1;
+    }
+    while !s.is_char_boundary(i) {
+        i += 1;
+    }
+    i
+}
+
+fn invalid_char(c: char, s: &str, offset: usize) -> JsonError {
+    JsonError::new(ErrorKind::Syntax, format!("Invalid char \'{}\' ({:#06x})", c, c as usize))
+        .with_position(s, offset)
+}
+
+fn literal_error(message: String, s: &str, offset: usize, literal: &str) -> JsonError {
+    JsonError::new(ErrorKind::Syntax, message).with_position(s, offset)
+        .with_hint(format!("expected {}", literal))
 }
 
-fn get_true(chars: &mut Chars) -> Result<Token, String> {
+fn get_true(chars: &mut Chars) -> Result<Token<'static>, String> {
     let err = "Invalid token ";
     match chars.next() {
         Some('r') => match chars.next() {
             Some('u') => match chars.next() {
                 Some('e') => Ok(Token::True),
-                Some(x) => return Err(format!("{}\"tru{}\"", err, x)),
-                None => return Err(format!("{}\"tru\"", err)),
+                Some(x) => Err(format!("{}\"tru{}\"", err, x)),
+                None => Err(format!("{}\"tru\"", err)),
             },
-            Some(x) => return Err(format!("{}\"tr{}\"", err, x)),
-            None => return Err(format!("{}\"tr\"", err)),
+            Some(x) => Err(format!("{}\"tr{}\"", err, x)),
+            None => Err(format!("{}\"tr\"", err)),
         },
-        Some(x) => return Err(format!("{}\"t{}\"", err, x)),
-        None => return Err(format!("{}\"t\"", err)),
+        Some(x) => Err(format!("{}\"t{}\"", err, x)),
+        None => Err(format!("{}\"t\"", err)),
     }
 }
 
-fn get_false(chars: &mut Chars) -> Result<Token, String> {
+fn get_false(chars: &mut Chars) -> Result<Token<'static>, String> {
     let err = "Invalid token ";
     match chars.next() {
         Some('a') => match chars.next() {
             Some('l') => match chars.next() {
                 Some('s') => match chars.next() {
                     Some('e') => Ok(Token::False),
-                    Some(x) => return Err(format!("{}\"fals{}\"", err, x)),
-                    None => return Err(format!("{}\"fals\"", err)),
+                    Some(x) => Err(format!("{}\"fals{}\"", err, x)),
+                    None => Err(format!("{}\"fals\"", err)),
                 },
-                Some(x) => return Err(format!("{}\"fal{}\"", err, x)),
-                None => return Err(format!("{}\"fal\"", err)),
+                Some(x) => Err(format!("{}\"fal{}\"", err, x)),
+                None => Err(format!("{}\"fal\"", err)),
             },
-            Some(x) => return Err(format!("{}\"fa{}\"", err, x)),
-            None => return Err(format!("{}\"fa\"", err)),
+            Some(x) => Err(format!("{}\"fa{}\"", err, x)),
+            None => Err(format!("{}\"fa\"", err)),
         },
-        Some(x) => return Err(format!("{}\"f{}\"", err, x)),
-        None => return Err(format!("{}\"f\"", err)),
+        Some(x) => Err(format!("{}\"f{}\"", err, x)),
+        None => Err(format!("{}\"f\"", err)),
     }
 }
 
-fn get_null(chars: &mut Chars) -> Result<Token, String> {
+fn get_null(chars: &mut Chars) -> Result<Token<'static>, String> {
     let err = "Invalid token ";
     match chars.next() {
         Some('u') => match chars.next() {
             Some('l') => match chars.next() {
                 Some('l') => Ok(Token::Null),
-                Some(x) => return Err(format!("{}\"nul{}\"", err, x)),
-                None => return Err(format!("{}\"nul\"", err)),
+                Some(x) => Err(format!("{}\"nul{}\"", err, x)),
+                None => Err(format!("{}\"nul\"", err)),
             },
-            Some(x) => return Err(format!("{}\"nu{}\"", err, x)),
-            None => return Err(format!("{}\"nu\"", err)),
+            Some(x) => Err(format!("{}\"nu{}\"", err, x)),
+            None => Err(format!("{}\"nu\"", err)),
         },
-        Some(x) => return Err(format!("{}\"n{}\"", err, x)),
-        None => return Err(format!("{}\"n\"", err)),
+        Some(x) => Err(format!("{}\"n{}\"", err, x)),
+        None => Err(format!("{}\"n\"", err)),
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+/// Scans a bare word starting at `start`, which is a literal or else an identifier
+fn get_identifier<'a>(s: &'a str, chars: &mut Chars, start: usize) -> Token<'a> {
+    while chars.clone().next().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$') {
+        chars.next();
+    }
+    match &s[start..offset(s, chars)] {
+        "true" => Token::True,
+        "false" => Token::False,
+        "null" => Token::Null,
+        identifier => Token::Identifier(identifier)
+    }
+}
+
+/// Returns the byte offset of the next char in `s`
+fn offset(s: &str, chars: &Chars) -> usize {
+    s.len() - chars.as_str().len()
+}
+
+/// Scans a number and pushes it to `tokens`, followed by the structural char ending it if any
+fn get_number<'a>(s: &'a str, chars: &mut Chars, max_len: Option<usize>, tokens: &mut Vec<(Token<'a>, usize)>)
+                  -> Result<(), JsonError> {
+    let start = offset(s, chars) - 1;
+    loop {
+        let end = offset(s, chars);
+        check_len("number", start, end - start, max_len, s)?;
+        let number = (Token::Number(&s[start..end]), start);
+        let structural = match chars.next() {
+            Some(c) => match c {
+                '0'..='9' | '.' | '-' | '+' | 'e' | 'E' => continue,
+                whitespace if whitespace == 0x0020 as char
+                    || whitespace == 0x000A as char
+                    || whitespace == 0x000D as char
+                    || whitespace == 0x0009 as char => None,
+                ',' => Some(Token::Comma),
+                ']' => Some(Token::SquareBracketClose),
+                '}' => Some(Token::CurlyBracketClose),
+                _ => return Err(invalid_char(c, s, end))
+            },
+            None => None
+        };
+        tokens.push(number);
+        tokens.extend(structural.map(|token| (token, end)));
+        return Ok(());
     }
 }
 
-fn get_number(chars: &mut Chars, first_char: char) -> Result<Vec<Token>, String> {
-    let mut string = String::new();
-    string.push(first_char);
+/// Scans a string up to the closing `quote`, which is `'` for a single-quoted string
+fn get_string<'a>(s: &'a str, chars: &mut Chars, quote: char, options: &ParseOptions,
+                  warnings: &mut Vec<JsonError>) -> Result<Token<'a>, JsonError> {
+    let start = offset(s, chars);
+    let mut escaped = false;
     loop {
+        let end = offset(s, chars);
+        check_len("string", start - 1, end - start, options.max_string_len, s)?;
         match chars.next() {
-            Some(c) => {
-                match c {
-                    '0'..='9' | '.' | '-' | '+' | 'e' | 'E' => string.push(c),
-                    whitespace if whitespace == 0x0020 as char
-                        || whitespace == 0x000A as char
-                        || whitespace == 0x000D as char
-                        || whitespace == 0x0009 as char => break,
-                    ',' => return Ok(vec![Token::Number(string), Token::Comma]),
-                    ']' => return Ok(vec![Token::Number(string), Token::SquareBracketClose]),
-                    '}' => return Ok(vec![Token::Number(string), Token::CurlyBracketClose]),
-                    _ => return Err(format!("Invalid char \'{}\' ({:#06x})", c, c as usize))
+            Some(c) if c == quote && !escaped => return Ok(Token::String(Cow::Borrowed(&s[start..end]))),
+            Some(c) if c == quote => return unescape(&s[start..end], quote, options.lone_surrogates, |at, message, hint| {
+                let error = JsonError::new(ErrorKind::Syntax, message).with_position(s, start + at).with_hint(hint);
+                if !options.allow_invalid_escapes {
+                    return Err(error);
                 }
+                warnings.push(error);
+                Ok(())
+            }).map(|string| Token::String(Cow::Owned(string))),
+            Some('\\') => {
+                escaped = true;
+                chars.next();
             }
-            None => break
+            Some(c) if c < ' ' => {
+                let error = control_character(c).with_position(s, end);
+                if !options.allow_invalid_escapes {
+                    return Err(error);
+                }
+                warnings.push(error);
+            }
+            Some(_) => (),
+            None => return Err(JsonError::new(ErrorKind::Syntax, "Invalid string token at the end of file!")
+                .with_position(s, start - 1).with_hint(format!("expected a closing '{}'", quote)))
         }
     }
-    Ok(vec![Token::Number(string)])
 }
 
-fn get_string(chars: &mut Chars) -> Result<Token, String> {
-    let mut string = String::new();
-    let mut last_char = '"';
-    loop {
-        match chars.next() {
-            Some(c) => {
-                match c {
-                    '"' => {
-                        if last_char == '\\' {
-                            last_char = c;
-                            string.push(c);
-                        } else {
-                            break;
+/// The error for a control character written in a string without an escape
+pub(crate) fn control_character(c: char) -> JsonError {
+    JsonError::new(ErrorKind::Syntax, format!("Unescaped control character U+{:04X} in a string", c as u32))
+        .with_hint(format!("expected the escape \\u{:04x}", c as u32))
+}
+
+/// Fails if the `kind` of token starting at `start` is longer than `max_len` bytes, which
+/// stops the scan at the limit rather than at the end of the token
+fn check_len(kind: &str, start: usize, len: usize, max_len: Option<usize>, s: &str) -> Result<(), JsonError> {
+    match max_len {
+        Some(max_len) if len > max_len => Err(JsonError::new(
+            ErrorKind::Syntax, format!("The {} is longer than the limit of {} bytes", kind, max_len))
+            .with_position(s, start)),
+        _ => Ok(())
+    }
+}
+
+/// The hint for an invalid escape sequence
+const ESCAPES: &str = "valid escapes are \\\" \\\\ \\/ \\b \\f \\n \\r \\t and \\uXXXX";
+
+/// Replaces the escape sequences in `raw`, the text of a string between its quotes, handling
+/// escaped lone surrogates as `surrogates` asks. `\'` is an escape only if `quote` is `'`. An
+/// invalid escape sequence is passed to `invalid` with its byte offset in `raw`, a message and
+/// a hint; it is kept as it is written if `invalid` returns `Ok`.
+pub(crate) fn unescape<F>(raw: &str, quote: char, surrogates: SurrogatePolicy, mut invalid: F)
+                          -> Result<String, JsonError>
+    where F: FnMut(usize, String, &str) -> Result<(), JsonError> {
+    let mut unescaped = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(i) = rest.find('\\') {
+        unescaped.push_str(&rest[..i]);
+        let escape = &rest[i..];
+        let at = raw.len() - escape.len();
+        let len = if let Some(c) = escape.as_bytes().get(1).and_then(|&b| escaped_char(b, quote)) {
+            unescaped.push(c);
+            2
+        } else if escape[1..].starts_with('u') {
+            match unicode(escape) {
+                Unicode::Char(c, len) => {
+                    unescaped.push(c);
+                    len
+                }
+                Unicode::LoneSurrogate => {
+                    match surrogates {
+                        SurrogatePolicy::Error => {
+                            invalid(at, format!("Lone surrogate '{}' in a string", &escape[..6]),
+                                    "a \\uD800-\\uDBFF escape must be followed by a \\uDC00-\\uDFFF escape")?;
+                            unescaped.push_str(&escape[..6]);
                         }
+                        SurrogatePolicy::Replace => unescaped.push(char::REPLACEMENT_CHARACTER),
+                        SurrogatePolicy::Preserve => unescaped.push_str(&escape[..6]),
                     }
-                    _ => {
-                        last_char = c;
-                        string.push(c);
-                    }
+                    6
+                }
+                Unicode::Invalid => {
+                    invalid(at, "Invalid escape '\\u' in a string".to_string(), "expected 4 hex digits after '\\u'")?;
+                    unescaped.push_str("\\u");
+                    2
                 }
             }
-            None => return Err(format!("Invalid string token at the end of file!"))
-        }
+        } else {
+            let len = escape[1..].chars().next().map_or(1, |c| 1 + c.len_utf8());
+            invalid(at, format!("Invalid escape '{}' in a string", &escape[..len]), ESCAPES)?;
+            unescaped.push_str(&escape[..len]);
+            len
+        };
+        rest = &escape[len..];
+    }
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
+
+/// Returns the char escaped by a backslash and `b`, for the escapes other than `\uXXXX`, in a
+/// string in `quote`s
+fn escaped_char(b: u8, quote: char) -> Option<char> {
+    match b {
+        b'"' => Some('"'),
+        b'\'' if quote == '\'' => Some('\''),
+        b'\\' => Some('\\'),
+        b'/' => Some('/'),
+        b'b' => Some('\u{8}'),
+        b'f' => Some('\u{c}'),
+        b'n' => Some('\n'),
+        b'r' => Some('\r'),
+        b't' => Some('\t'),
+        _ => None
+    }
+}
+
+/// A decoded `\uXXXX` escape
+enum Unicode {
+    /// A char, with the length of its escape, which is two escapes for a surrogate pair
+    Char(char, usize),
+    /// A surrogate that is not part of a pair
+    LoneSurrogate,
+    /// Not 4 hex digits
+    Invalid,
+}
+
+/// Decodes the `\uXXXX` escape, or the surrogate pair of two, at the start of `escape`
+fn unicode(escape: &str) -> Unicode {
+    let hex = |from: usize| escape.get(from + 2..from + 6)
+        .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(|digits| u32::from_str_radix(digits, 16).unwrap());
+    match hex(0) {
+        None => Unicode::Invalid,
+        Some(high @ 0xD800..=0xDBFF) => match hex(6) {
+            Some(low @ 0xDC00..=0xDFFF) if escape[6..].starts_with("\\u") => {
+                Unicode::Char(char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).unwrap(), 12)
+            }
+            _ => Unicode::LoneSurrogate
+        },
+        Some(0xDC00..=0xDFFF) => Unicode::LoneSurrogate,
+        Some(code) => Unicode::Char(char::from_u32(code).unwrap(), 6)
     }
-    Ok(Token::String(string))
 }
 
 
 #[derive(Debug)]
-pub enum Token {
-    String(String),
-    Number(String),
+pub enum Token<'a> {
+    String(Cow<'a, str>),
+    Number(&'a str),
+    /// A bare word, scanned only if unquoted keys are allowed
+    Identifier(&'a str),
     CurlyBracketOpen,
     CurlyBracketClose,
     SquareBracketOpen,
@@ -175,11 +415,12 @@ pub enum Token {
     False,
 }
 
-impl PartialEq for Token {
+impl PartialEq for Token<'_> {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Token::String(s1), Token::String(s2)) => s1 == s2,
             (Token::Number(n1), Token::Number(n2)) => n1 == n2,
+            (Token::Identifier(i1), Token::Identifier(i2)) => i1 == i2,
             (Token::CurlyBracketOpen, Token::CurlyBracketOpen) => true,
             (Token::CurlyBracketClose, Token::CurlyBracketClose) => true,
             (Token::SquareBracketOpen, Token::SquareBracketOpen) => true,
@@ -194,11 +435,12 @@ impl PartialEq for Token {
     }
 }
 
-impl Display for Token {
+impl Display for Token<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Token::String(s) => write!(f, "{}", s),
             Token::Number(n) => write!(f, "{}", n),
+            Token::Identifier(i) => write!(f, "{}", i),
             Token::True => write!(f, "true"),
             Token::False => write!(f, "false"),
             Token::Null => write!(f, "null"),
@@ -215,87 +457,197 @@ impl Display for Token {
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::tokenizer::{Token, tokenize};
+    use std::borrow::Cow;
+    use crate::parser::parser::{ParseOptions, SurrogatePolicy};
+    use crate::parser::tokenizer::{Token, tokenize, tokenize_with, ESCAPES};
+
+    fn tokens(s: &str) -> Result<Vec<Token<'_>>, String> {
+        tokenize(s).map(|tokens| tokens.into_iter().map(|(token, _)| token).collect())
+            .map_err(|e| e.message().to_string())
+    }
 
     #[test]
     fn test_tokenize_curly() {
-        assert_eq!(vec![Token::CurlyBracketOpen], tokenize("{").unwrap());
-        assert_eq!(vec![Token::CurlyBracketClose], tokenize("}").unwrap());
-        assert_eq!(vec![Token::CurlyBracketOpen, Token::CurlyBracketClose], tokenize("{}").unwrap());
-        assert_eq!(vec![Token::CurlyBracketOpen, Token::CurlyBracketClose], tokenize(" \t{\n } ").unwrap());
+        assert_eq!(vec![Token::CurlyBracketOpen], tokens("{").unwrap());
+        assert_eq!(vec![Token::CurlyBracketClose], tokens("}").unwrap());
+        assert_eq!(vec![Token::CurlyBracketOpen, Token::CurlyBracketClose], tokens("{}").unwrap());
+        assert_eq!(vec![Token::CurlyBracketOpen, Token::CurlyBracketClose], tokens(" \t{\n } ").unwrap());
     }
 
     #[test]
     fn test_tokenize_square() {
-        assert_eq!(vec![Token::SquareBracketOpen], tokenize("[").unwrap());
-        assert_eq!(vec![Token::SquareBracketClose], tokenize("]").unwrap());
-        assert_eq!(vec![Token::SquareBracketOpen, Token::SquareBracketClose], tokenize("[]").unwrap());
-        assert_eq!(vec![Token::SquareBracketOpen, Token::SquareBracketClose], tokenize(" \t[\n ] ").unwrap());
+        assert_eq!(vec![Token::SquareBracketOpen], tokens("[").unwrap());
+        assert_eq!(vec![Token::SquareBracketClose], tokens("]").unwrap());
+        assert_eq!(vec![Token::SquareBracketOpen, Token::SquareBracketClose], tokens("[]").unwrap());
+        assert_eq!(vec![Token::SquareBracketOpen, Token::SquareBracketClose], tokens(" \t[\n ] ").unwrap());
     }
 
     #[test]
     fn test_tokenize_colon() {
-        assert_eq!(vec![Token::Colon], tokenize(":").unwrap());
-        assert_eq!(vec![Token::Colon], tokenize("\n \t \t:  \n").unwrap());
+        assert_eq!(vec![Token::Colon], tokens(":").unwrap());
+        assert_eq!(vec![Token::Colon], tokens("\n \t \t:  \n").unwrap());
     }
 
     #[test]
     fn test_tokenize_comma() {
-        assert_eq!(vec![Token::Comma], tokenize(",").unwrap());
-        assert_eq!(vec![Token::Comma], tokenize("\n \t \t,  \n").unwrap());
+        assert_eq!(vec![Token::Comma], tokens(",").unwrap());
+        assert_eq!(vec![Token::Comma], tokens("\n \t \t,  \n").unwrap());
     }
 
     #[test]
     fn test_tokenize_string() {
-        assert_eq!(vec![Token::String("hello world".to_string())], tokenize("\"hello world\"").unwrap());
-        assert_eq!(vec![Token::String("hello \\\" world".to_string())], tokenize("\"hello \\\" world\"").unwrap());
-        assert_eq!(vec![Token::String("hello world".to_string())], tokenize("\n \t \t\"hello world\"  \n").unwrap());
-        assert_eq!(Err("Invalid string token at the end of file!".to_string()), tokenize("\"hello world"));
+        assert_eq!(vec![Token::String("hello world".into())], tokens("\"hello world\"").unwrap());
+        assert_eq!(vec![Token::String("hello \" world".into())], tokens("\"hello \\\" world\"").unwrap());
+        assert_eq!(vec![Token::String("hello world".into())], tokens("\n \t \t\"hello world\"  \n").unwrap());
+        assert_eq!(Err("Invalid string token at the end of file!".to_string()), tokens("\"hello world"));
+        assert!(matches!(tokens("\"hello\"").unwrap()[0], Token::String(Cow::Borrowed("hello"))));
+    }
+
+    #[test]
+    fn test_tokenize_escapes() {
+        assert_eq!(vec![Token::String("\"\\/\u{8}\u{c}\n\r\t".into())],
+                   tokens("\"\\\"\\\\\\/\\b\\f\\n\\r\\t\"").unwrap());
+        assert_eq!(vec![Token::String("\u{e9}\u{20ac}\u{1f600}!".into())],
+                   tokens("\"\\u00E9\\u20ac\\ud83d\\ude00!\"").unwrap());
+        assert_eq!(vec![Token::String("a\\".into()), Token::Comma], tokens("\"a\\\\\",").unwrap());
+        assert_eq!(Err("Invalid escape '\\q' in a string".to_string()), tokens("\"hello \\q world\""));
+        assert_eq!(Err("Invalid escape '\\u' in a string".to_string()), tokens("\"\\u12g4\""));
+        assert_eq!(Err("Lone surrogate '\\uD800' in a string".to_string()), tokens("\"\\uD800\\u0041\""));
+        assert_eq!(Err("Lone surrogate '\\udc00' in a string".to_string()), tokens("\"\\udc00\""));
+        let error = tokenize("[\"ok\", \"\\x\"]").unwrap_err();
+        assert_eq!((Some(8), Some(ESCAPES)), (error.offset(), error.hint()));
+    }
+
+    #[test]
+    fn test_tokenize_lenient_escapes() {
+        let mut warnings = Vec::new();
+        let options = ParseOptions { allow_invalid_escapes: true, ..ParseOptions::default() };
+        let tokens = tokenize_with("\"\\q\\u12\\uDFFF\\n\"", &options, &mut warnings).unwrap();
+        assert_eq!(vec![(Token::String("\\q\\u12\\uDFFF\n".into()), 0)], tokens);
+        let offsets: Vec<_> = warnings.iter().map(|w| w.offset().unwrap()).collect();
+        assert_eq!(vec![1, 3, 7], offsets);
+    }
+
+    #[test]
+    fn test_tokenize_control_characters() {
+        let error = tokenize("[\"a\tb\"]").unwrap_err();
+        assert_eq!(("Unescaped control character U+0009 in a string", Some(3), Some("expected the escape \\u0009")),
+                   (error.message(), error.offset(), error.hint()));
+        assert!(tokenize("\"\u{0}\"").is_err());
+        assert!(tokenize("\"\u{7f}\"").is_ok());
+        let mut warnings = Vec::new();
+        let options = ParseOptions { allow_invalid_escapes: true, ..ParseOptions::default() };
+        let tokens = tokenize_with("\"a\nb\"", &options, &mut warnings).unwrap();
+        assert_eq!(vec![(Token::String("a\nb".into()), 0)], tokens);
+        assert_eq!(vec![Some(2)], warnings.iter().map(|w| w.offset()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_tokenize_single_quotes() {
+        let mut warnings = Vec::new();
+        let options = ParseOptions { allow_single_quotes: true, ..ParseOptions::default() };
+        let tokens = tokenize_with("['it\\'s', '\"a\"', 'caf\\u00e9', \"\\'\"]", &options, &mut warnings);
+        assert_eq!(Err("Invalid escape '\\'' in a string".to_string()), tokens.map_err(|e| e.message().to_string()));
+        warnings.clear();
+        let tokens = tokenize_with("['it\\'s', '\"a\"', 'caf\\u00e9']", &options, &mut warnings).unwrap();
+        let strings: Vec<_> = tokens.into_iter().filter(|(t, _)| matches!(t, Token::String(_))).collect();
+        assert_eq!(vec![(Token::String("it's".into()), 1), (Token::String("\"a\"".into()), 10),
+                        (Token::String("caf\u{e9}".into()), 17)], strings);
+        assert_eq!(vec![1, 10, 17], warnings.iter().map(|w| w.offset().unwrap()).collect::<Vec<_>>());
+        let error = tokenize_with("'open", &options, &mut warnings).unwrap_err();
+        assert_eq!(Some("expected a closing '\''"), error.hint());
+        assert!(tokenize("'a'").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_identifiers() {
+        let options = ParseOptions { allow_unquoted_keys: true, ..ParseOptions::default() };
+        let scanned = tokenize_with("{_id: null, $x2: true, n\u{e4}me:1}", &options, &mut Vec::new()).unwrap();
+        let scanned: Vec<_> = scanned.into_iter().map(|(token, _)| token).collect();
+        assert_eq!(vec![Token::CurlyBracketOpen, Token::Identifier("_id"), Token::Colon, Token::Null, Token::Comma,
+                        Token::Identifier("$x2"), Token::Colon, Token::True, Token::Comma,
+                        Token::Identifier("n\u{e4}me"), Token::Colon, Token::Number("1"), Token::CurlyBracketClose],
+                   scanned);
+        assert_eq!(Err("Invalid char '_' (0x005f)".to_string()), tokens("{_id: 1}").map(|_| ()));
+    }
+
+    #[test]
+    fn test_tokenize_limits() {
+        let options = ParseOptions { max_string_len: Some(3), max_number_len: Some(4), ..ParseOptions::default() };
+        let scan = |s: &str| tokenize_with(s, &options, &mut Vec::new()).map(|t| t.len()).map_err(|e| e.offset());
+        assert_eq!(Ok(5), scan("[\"abc\", 1234]"));
+        assert_eq!(Err(Some(1)), scan("[\"abcd\", 1]"));
+        assert_eq!(Err(Some(8)), scan("[\"abc\", -1234]"));
+        assert_eq!(Err(Some(0)), scan("\"\\u00e9\""));
+        let error = tokenize_with(&format!("\"{}", "a".repeat(1000)), &options, &mut Vec::new()).unwrap_err();
+        assert_eq!("The string is longer than the limit of 3 bytes", error.message());
+    }
+
+    #[test]
+    fn test_tokenize_lone_surrogates() {
+        let string = |policy| {
+            let options = ParseOptions { lone_surrogates: policy, ..ParseOptions::default() };
+            let tokens = tokenize_with("\"a\\ud800\\u0041\\udfff\\ud83d\\ude00\"", &options, &mut Vec::new());
+            tokens.map(|mut tokens| tokens.remove(0).0).map_err(|e| e.offset())
+        };
+        assert_eq!(Err(Some(2)), string(SurrogatePolicy::Error));
+        assert_eq!(Ok(Token::String("a\u{fffd}A\u{fffd}\u{1f600}".into())), string(SurrogatePolicy::Replace));
+        assert_eq!(Ok(Token::String("a\\ud800A\\udfff\u{1f600}".into())), string(SurrogatePolicy::Preserve));
     }
 
     #[test]
     fn test_tokenize_number() {
-        assert_eq!(vec![Token::Number("0.013e10".to_string())], tokenize("0.013e10").unwrap());
-        assert_eq!(vec![Token::Number("00E.-0+13e10".to_string())], tokenize("00E.-0+13e10").unwrap());
-        assert_eq!(vec![Token::Number("0.013".to_string()), Token::Number("0e10".to_string())], tokenize("\n\t0.013 0e10").unwrap());
+        assert_eq!(vec![Token::Number("0.013e10")], tokens("0.013e10").unwrap());
+        assert_eq!(vec![Token::Number("00E.-0+13e10")], tokens("00E.-0+13e10").unwrap());
+        assert_eq!(vec![Token::Number("0.013"), Token::Number("0e10")], tokens("\n\t0.013 0e10").unwrap());
     }
 
     #[test]
     fn test_tokenize_true() {
-        assert_eq!(vec![Token::True], tokenize("true").unwrap());
-        assert_eq!(vec![Token::True], tokenize("\n \t \ttrue  \n").unwrap());
+        assert_eq!(vec![Token::True], tokens("true").unwrap());
+        assert_eq!(vec![Token::True], tokens("\n \t \ttrue  \n").unwrap());
     }
 
     #[test]
     fn test_tokenize_false() {
-        assert_eq!(vec![Token::False], tokenize("false").unwrap());
-        assert_eq!(vec![Token::False], tokenize("\n \t \tfalse  \n").unwrap());
+        assert_eq!(vec![Token::False], tokens("false").unwrap());
+        assert_eq!(vec![Token::False], tokens("\n \t \tfalse  \n").unwrap());
     }
 
     #[test]
     fn test_tokenize_null() {
-        assert_eq!(vec![Token::Null], tokenize("null").unwrap());
-        assert_eq!(vec![Token::Null], tokenize("\n \t \tnull  \n").unwrap());
+        assert_eq!(vec![Token::Null], tokens("null").unwrap());
+        assert_eq!(vec![Token::Null], tokens("\n \t \tnull  \n").unwrap());
     }
 
     #[test]
     fn test_tokenize() {
         assert_eq!(vec![
             Token::CurlyBracketOpen,
-            Token::String("key".to_string()),
+            Token::String("key".into()),
             Token::Colon,
             Token::SquareBracketOpen,
             Token::True,
             Token::Comma,
-            Token::Number("10".to_string()),
+            Token::Number("10"),
             Token::Comma,
-            Token::Number("10e20".to_string()),
+            Token::Number("10e20"),
             Token::SquareBracketClose,
             Token::CurlyBracketClose,
-        ], tokenize("{\n\t\"key\" : [true, 10,10e20]\n}").unwrap());
-        assert_eq!(Err("Invalid char \'+\' (0x002b)".to_string()), tokenize("+10"));
-        assert_eq!(Err("Invalid char \'d\' (0x0064)".to_string()), tokenize("1d0"));
-        assert_eq!(Err("Invalid token \"tru \"".to_string()), tokenize("tru "));
-        assert_eq!(Err("Invalid token \"nul\"".to_string()), tokenize("nul"));
+        ], tokens("{\n\t\"key\" : [true, 10,10e20]\n}").unwrap());
+        assert_eq!(Err("Invalid char \'+\' (0x002b)".to_string()), tokens("+10"));
+        assert_eq!(Err("Invalid char \'d\' (0x0064)".to_string()), tokens("1d0"));
+        assert_eq!(Err("Invalid token \"tru \"".to_string()), tokens("tru "));
+        assert_eq!(Err("Invalid token \"nul\"".to_string()), tokens("nul"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tokenize_offsets() {
+        let offsets: Vec<usize> = tokenize("{\"k\u{e9}\" : [10,true]}").unwrap().iter().map(|(_, o)| *o).collect();
+        assert_eq!(vec![0, 1, 7, 9, 10, 12, 13, 17, 18], offsets);
+        let error = tokenize("[1, tru]").unwrap_err();
+        assert_eq!((Some(4), Some("expected true")), (error.offset(), error.hint()));
+        assert_eq!(Some(1), tokenize("[\"abc").unwrap_err().offset());
+        assert_eq!(Some(2), tokenize("[1x]").unwrap_err().offset());
+    }
+}
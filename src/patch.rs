@@ -0,0 +1,417 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! [JSON Patch] documents: sequences of operations that change a document into another.
+//!
+//! [JSON Patch]:https://datatracker.ietf.org/doc/html/rfc6902
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::{array_index, parse_pointer};
+use crate::transform::push_token;
+
+/// An operation of a JSON Patch. Paths are JSON Pointers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Adds a member to an object, replacing any member with the same key, or inserts an element
+    /// into an array, at the end if the last token of the path is `-`
+    Add { path: String, value: JValue },
+    Remove { path: String },
+    Replace { path: String, value: JValue },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    /// Fails the patch unless the value at the path equals `value`
+    Test { path: String, value: JValue },
+}
+
+impl Operation {
+    /// Returns the path the operation changes or tests
+    pub fn path(&self) -> &str {
+        match self {
+            Operation::Add { path, .. } | Operation::Remove { path } | Operation::Replace { path, .. }
+            | Operation::Move { path, .. } | Operation::Copy { path, .. } | Operation::Test { path, .. } => path
+        }
+    }
+
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = JObject::new();
+        let string = |s: &str| JValue::String(JString::from(s));
+        let (op, value, from) = match self {
+            Operation::Add { value, .. } => ("add", Some(value), None),
+            Operation::Remove { .. } => ("remove", None, None),
+            Operation::Replace { value, .. } => ("replace", Some(value), None),
+            Operation::Move { from, .. } => ("move", None, Some(from)),
+            Operation::Copy { from, .. } => ("copy", None, Some(from)),
+            Operation::Test { value, .. } => ("test", Some(value), None)
+        };
+        object.insert("op".to_string(), string(op));
+        if let Some(from) = from {
+            object.insert("from".to_string(), string(from));
+        }
+        object.insert("path".to_string(), string(self.path()));
+        if let Some(value) = value {
+            object.insert("value".to_string(), value.clone());
+        }
+        JValue::Object(object)
+    }
+}
+
+impl FromJValue for Operation {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        let invalid = |message: String| JsonError::new(ErrorKind::InvalidType, format!("Invalid patch operation: {}", message));
+        let object = match value {
+            JValue::Object(object) => object,
+            _ => return Err(invalid(format!("expected an object, found {}", describe(value))))
+        };
+        let pointer = |key: &str| match object.get(&key.to_string()) {
+            Some(JValue::String(s)) => parse_pointer(s).map(|_| s.to_string()),
+            Some(other) => Err(invalid(format!("the {} is {}", key, describe(other)))),
+            None => Err(invalid(format!("the {} is missing", key)))
+        };
+        let content = || object.get(&"value".to_string()).cloned().ok_or_else(|| invalid("the value is missing".to_string()));
+        let path = pointer("path")?;
+        Ok(match object.get(&"op".to_string()) {
+            Some(JValue::String(op)) => match op.as_str() {
+                "add" => Operation::Add { path, value: content()? },
+                "remove" => Operation::Remove { path },
+                "replace" => Operation::Replace { path, value: content()? },
+                "move" => Operation::Move { from: pointer("from")?, path },
+                "copy" => Operation::Copy { from: pointer("from")?, path },
+                "test" => Operation::Test { path, value: content()? },
+                other => return Err(invalid(format!("unknown op {}", other)))
+            },
+            Some(other) => return Err(invalid(format!("the op is {}", describe(other)))),
+            None => return Err(invalid("the op is missing".to_string()))
+        })
+    }
+}
+
+/// Reads a JSON Patch document, an array of operations
+///
+/// ```
+/// # use json::json;
+/// # use json::patch::{parse_patch, Operation};
+/// let patch = parse_patch(&json!("[{\"op\": \"remove\", \"path\": \"/a\"}]")).unwrap();
+/// assert_eq!(vec![Operation::Remove { path: "/a".to_string() }], patch);
+/// assert_eq!("Invalid patch operation: the path is missing at /0",
+///            parse_patch(&json!("[{\"op\": \"remove\"}]")).unwrap_err().message());
+/// ```
+pub fn parse_patch(value: &JValue) -> Result<Vec<Operation>, JsonError> {
+    match value {
+        JValue::Array(operations) => operations.iter().enumerate().map(|(i, operation)| {
+            Operation::from_jvalue(operation).map_err(|e| JsonError::new(e.kind(), format!("{} at /{}", e.message(), i)))
+        }).collect(),
+        _ => Err(JsonError::new(ErrorKind::InvalidType,
+                                format!("Expected an array of patch operations, found {}", describe(value))))
+    }
+}
+
+/// Writes operations as a JSON Patch document
+pub fn patch_to_jvalue(operations: &[Operation]) -> JValue {
+    JValue::Array(operations.iter().map(Operation::to_jvalue).collect())
+}
+
+impl JValue {
+    /// Applies a patch. If an operation fails, the value is left as it was before the patch.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::patch::parse_patch;
+    /// let mut j_value = json!("{\"tags\": [\"a\"], \"old\": 1}");
+    /// let patch = parse_patch(&json!("[{\"op\": \"add\", \"path\": \"/tags/-\", \"value\": \"b\"},
+    ///                                  {\"op\": \"move\", \"from\": \"/old\", \"path\": \"/new\"}]")).unwrap();
+    /// j_value.apply_patch(&patch).unwrap();
+    /// assert_eq!(json!("{\"tags\": [\"a\", \"b\"], \"new\": 1}"), j_value);
+    /// ```
+    pub fn apply_patch(&mut self, operations: &[Operation]) -> Result<(), JsonError> {
+        self.apply_patch_with_inverse(operations).map(|_| ())
+    }
+
+    /// Applies a patch like [`JValue::apply_patch`] and returns the patch which undoes it
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::patch::Operation;
+    /// let mut j_value = json!("[1, 2]");
+    /// let inverse = j_value.apply_patch_with_inverse(&[Operation::Remove { path: "/0".to_string() }]).unwrap();
+    /// assert_eq!(vec![Operation::Add { path: "/0".to_string(), value: json!("1") }], inverse);
+    /// j_value.apply_patch(&inverse).unwrap();
+    /// assert_eq!(json!("[1, 2]"), j_value);
+    /// ```
+    pub fn apply_patch_with_inverse(&mut self, operations: &[Operation]) -> Result<Vec<Operation>, JsonError> {
+        let mut inverse = Vec::new();
+        for operation in operations {
+            if let Err(e) = apply(self, operation, &mut inverse) {
+                for undo in inverse.iter().rev() {
+                    apply(self, undo, &mut Vec::new()).expect("the inverse of an applied operation applies");
+                }
+                return Err(e);
+            }
+        }
+        inverse.reverse();
+        Ok(inverse)
+    }
+
+    /// Computes a patch which changes this value into `other`. Members of objects are compared
+    /// by key and elements of arrays by index, and any other change replaces the value. Numbers
+    /// are compared as they are written, so a patch keeps numbers which no f64 holds exactly.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let old = json!("{\"a\": [1, 2, 3], \"b\": true}");
+    /// let mut new = json!("{\"a\": [1, 5]}");
+    /// let patch = old.diff_patch(&new);
+    /// let mut patched = old.clone();
+    /// patched.apply_patch(&patch).unwrap();
+    /// assert_eq!(new, patched);
+    /// ```
+    pub fn diff_patch(&self, other: &JValue) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        diff(self, other, &mut String::new(), &mut operations);
+        operations
+    }
+}
+
+/// Compares two values like [`PartialEq`], but compares numbers as they are written, so numbers
+/// which round to the same f64 differ
+pub(crate) fn equal_as_written(v1: &JValue, v2: &JValue) -> bool {
+    match (v1, v2) {
+        (JValue::Object(o1), JValue::Object(o2)) => o1.len() == o2.len() && o1.iter()
+            .all(|(k, m1)| o2.get(k).is_some_and(|m2| equal_as_written(m1, m2))),
+        (JValue::Array(a1), JValue::Array(a2)) => a1.len() == a2.len() && a1.iter().zip(a2)
+            .all(|(e1, e2)| equal_as_written(e1, e2)),
+        (JValue::Number(n1), JValue::Number(n2)) => n1.to_string() == n2.to_string(),
+        _ => v1 == v2
+    }
+}
+
+fn not_found(pointer: &str) -> JsonError {
+    JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer))
+}
+
+/// Splits a pointer into the pointer to the parent and the last token
+fn split(pointer: &str) -> Result<(&str, String), JsonError> {
+    let mut tokens = parse_pointer(pointer)?;
+    match tokens.pop() {
+        Some(last) => Ok((&pointer[..pointer.rfind('/').unwrap()], last)),
+        None => Err(JsonError::new(ErrorKind::InvalidPointer, "The root has no parent"))
+    }
+}
+
+/// Adds a value and returns the operation which undoes it
+fn add(document: &mut JValue, path: &str, value: JValue) -> Result<Operation, JsonError> {
+    if path.is_empty() {
+        let old = std::mem::replace(document, value);
+        return Ok(Operation::Replace { path: String::new(), value: old });
+    }
+    let (parent, last) = split(path)?;
+    match document.pointer_mut(parent) {
+        Some(JValue::Object(object)) => Ok(match object.insert(last, value) {
+            Some(old) => Operation::Replace { path: path.to_string(), value: old },
+            None => Operation::Remove { path: path.to_string() }
+        }),
+        Some(JValue::Array(array)) => {
+            let index = match (last.as_str(), array_index(&last)) {
+                ("-", _) => array.len(),
+                (_, Some(index)) if index <= array.len() => index,
+                _ => return Err(JsonError::new(ErrorKind::InvalidPointer,
+                                               format!("Can not insert at {}, which is not an index of the array", path)))
+            };
+            array.insert(index, value);
+            let mut inserted = parent.to_string();
+            push_token(&mut inserted, &index.to_string());
+            Ok(Operation::Remove { path: inserted })
+        }
+        Some(other) => Err(JsonError::new(ErrorKind::InvalidType,
+                                          format!("Can not add to {} at {}", describe(other), parent))),
+        None => Err(not_found(parent))
+    }
+}
+
+fn remove(document: &mut JValue, path: &str) -> Result<JValue, JsonError> {
+    let (parent, last) = split(path).map_err(|_| JsonError::new(ErrorKind::InvalidPointer, "The root can not be removed"))?;
+    match document.pointer_mut(parent) {
+        Some(JValue::Object(object)) => object.remove(&last).ok_or_else(|| not_found(path)),
+        Some(JValue::Array(array)) => match array_index(&last) {
+            Some(index) if index < array.len() => Ok(array.remove(index)),
+            _ => Err(not_found(path))
+        },
+        _ => Err(not_found(path))
+    }
+}
+
+/// Applies an operation, pushing the operations which undo it in the reverse of the order they
+/// are applied in
+fn apply(document: &mut JValue, operation: &Operation, inverse: &mut Vec<Operation>) -> Result<(), JsonError> {
+    match operation {
+        Operation::Add { path, value } => inverse.push(add(document, path, value.clone())?),
+        Operation::Remove { path } => {
+            let old = remove(document, path)?;
+            inverse.push(Operation::Add { path: path.clone(), value: old });
+        }
+        Operation::Replace { path, value } => {
+            let old = document.replace(path, value.clone())?;
+            inverse.push(Operation::Replace { path: path.clone(), value: old });
+        }
+        Operation::Move { from, path } => {
+            if from == path {
+                return document.pointer(from).map(|_| ()).ok_or_else(|| not_found(from));
+            }
+            if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                return Err(JsonError::new(ErrorKind::InvalidPointer,
+                                          format!("Can not move {} into itself at {}", from, path)));
+            }
+            let value = remove(document, from)?;
+            inverse.push(Operation::Add { path: from.clone(), value: value.clone() });
+            match add(document, path, value) {
+                Ok(undo) => inverse.push(undo),
+                Err(e) => {
+                    apply(document, &inverse.pop().unwrap(), &mut Vec::new())?;
+                    return Err(e);
+                }
+            }
+        }
+        Operation::Copy { from, path } => {
+            let value = document.pointer(from).cloned().ok_or_else(|| not_found(from))?;
+            inverse.push(add(document, path, value)?);
+        }
+        Operation::Test { path, value } => match document.pointer(path) {
+            Some(actual) if actual == value => {}
+            Some(actual) => return Err(JsonError::new(ErrorKind::Validation, format!(
+                "The test failed, expected {}, found {} at {}", describe(value), describe(actual), path))),
+            None => return Err(not_found(path))
+        }
+    }
+    Ok(())
+}
+
+fn diff(old: &JValue, new: &JValue, path: &mut String, operations: &mut Vec<Operation>) {
+    let len = path.len();
+    match (old, new) {
+        (JValue::Object(o1), JValue::Object(o2)) => {
+            let mut keys: Vec<&String> = o1.iter().map(|(k, _)| k)
+                .chain(o2.iter().map(|(k, _)| k).filter(|k| o1.get(k).is_none()))
+                .collect();
+            keys.sort();
+            for key in keys {
+                push_token(path, key);
+                match (o1.get(key), o2.get(key)) {
+                    (Some(m1), Some(m2)) => diff(m1, m2, path, operations),
+                    (Some(_), None) => operations.push(Operation::Remove { path: path.clone() }),
+                    (None, Some(m2)) => operations.push(Operation::Add { path: path.clone(), value: m2.clone() }),
+                    (None, None) => unreachable!()
+                }
+                path.truncate(len);
+            }
+        }
+        (JValue::Array(a1), JValue::Array(a2)) => {
+            for (i, (e1, e2)) in a1.iter().zip(a2).enumerate() {
+                push_token(path, &i.to_string());
+                diff(e1, e2, path, operations);
+                path.truncate(len);
+            }
+            // Removals go from the end, so the indices of the elements still to remove stay valid.
+            for i in (a2.len()..a1.len()).rev() {
+                push_token(path, &i.to_string());
+                operations.push(Operation::Remove { path: path.clone() });
+                path.truncate(len);
+            }
+            for (i, e2) in a2.iter().enumerate().skip(a1.len()) {
+                push_token(path, &i.to_string());
+                operations.push(Operation::Add { path: path.clone(), value: e2.clone() });
+                path.truncate(len);
+            }
+        }
+        _ if !equal_as_written(old, new) => operations.push(Operation::Replace { path: path.clone(), value: new.clone() }),
+        _ => ()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::patch::{parse_patch, patch_to_jvalue, Operation};
+
+    #[test]
+    fn test_apply_patch() {
+        // The example of appendix A.16 of RFC 6902 and others
+        let mut j_value = json!("{\"foo\": [\"bar\"], \"baz\": {\"qux\": 1}, \"a~b\": null}");
+        let patch = parse_patch(&json!("[
+            {\"op\": \"add\", \"path\": \"/foo/-\", \"value\": [\"abc\", \"def\"]},
+            {\"op\": \"add\", \"path\": \"/foo/0\", \"value\": 0},
+            {\"op\": \"test\", \"path\": \"/foo/2/1\", \"value\": \"def\"},
+            {\"op\": \"copy\", \"from\": \"/baz\", \"path\": \"/foo/1\"},
+            {\"op\": \"move\", \"from\": \"/baz/qux\", \"path\": \"/a~0b\"},
+            {\"op\": \"replace\", \"path\": \"/foo/1\", \"value\": {}},
+            {\"op\": \"remove\", \"path\": \"/foo/3\"}
+        ]")).unwrap();
+        let original = j_value.clone();
+        let inverse = j_value.apply_patch_with_inverse(&patch).unwrap();
+        assert_eq!(json!("{\"foo\": [0, {}, \"bar\"], \"baz\": {}, \"a~b\": 1}"), j_value);
+        j_value.apply_patch(&inverse).unwrap();
+        assert_eq!(original, j_value);
+        assert_eq!(Ok(patch.clone()), parse_patch(&patch_to_jvalue(&patch)));
+    }
+
+    #[test]
+    fn test_failed_patch_changes_nothing() {
+        let mut j_value = json!("{\"a\": [1], \"b\": 2}");
+        let original = j_value.clone();
+        for (patch, kind, message) in [
+            ("[{\"op\": \"remove\", \"path\": \"/b\"}, {\"op\": \"test\", \"path\": \"/a/0\", \"value\": 2}]",
+             ErrorKind::Validation, "The test failed, expected the number 2, found the number 1 at /a/0"),
+            ("[{\"op\": \"add\", \"path\": \"/a/-\", \"value\": 2}, {\"op\": \"add\", \"path\": \"/a/5\", \"value\": 3}]",
+             ErrorKind::InvalidPointer, "Can not insert at /a/5, which is not an index of the array"),
+            ("[{\"op\": \"move\", \"from\": \"/b\", \"path\": \"/c/d\"}]", ErrorKind::NotFound, "No value at /c"),
+            ("[{\"op\": \"move\", \"from\": \"/a\", \"path\": \"/a/0\"}]", ErrorKind::InvalidPointer,
+             "Can not move /a into itself at /a/0"),
+            ("[{\"op\": \"add\", \"path\": \"/b/c\", \"value\": 1}]", ErrorKind::InvalidType,
+             "Can not add to the number 2 at /b"),
+            ("[{\"op\": \"remove\", \"path\": \"\"}]", ErrorKind::InvalidPointer, "The root can not be removed"),
+            ("[{\"op\": \"replace\", \"path\": \"/x\", \"value\": 1}]", ErrorKind::NotFound, "No value at /x"),
+        ] {
+            let error = j_value.apply_patch(&parse_patch(&json!(patch)).unwrap()).unwrap_err();
+            assert_eq!((kind, message), (error.kind(), error.message()), "{}", patch);
+            assert_eq!(original, j_value);
+        }
+        assert_eq!("Invalid patch operation: unknown op set at /0",
+                   parse_patch(&json!("[{\"op\": \"set\", \"path\": \"\"}]")).unwrap_err().message());
+    }
+
+    #[test]
+    fn test_diff_patch() {
+        let old = json!("{\"a\": [1, 2, 3, 4], \"b\": {\"c\": 1, \"d\": 2}, \"e\": \"x\"}");
+        let new = json!("{\"a\": [1, 5], \"b\": {\"c\": 1, \"f\": []}, \"e\": [\"x\"]}");
+        let patch = old.diff_patch(&new);
+        assert_eq!(vec![
+            Operation::Replace { path: "/a/1".to_string(), value: json!("5") },
+            Operation::Remove { path: "/a/3".to_string() },
+            Operation::Remove { path: "/a/2".to_string() },
+            Operation::Remove { path: "/b/d".to_string() },
+            Operation::Add { path: "/b/f".to_string(), value: json!("[]") },
+            Operation::Replace { path: "/e".to_string(), value: json!("[\"x\"]") },
+        ], patch);
+        let mut patched = old.clone();
+        patched.apply_patch(&patch).unwrap();
+        assert_eq!(new, patched);
+        assert!(new.diff_patch(&new).is_empty());
+        assert_eq!(vec![Operation::Replace { path: String::new(), value: json!("1") }], old.diff_patch(&json!("1")));
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Navigation of JSON data-structures with [JSON Pointer] paths such as `/users/0/name`.
+//!
+//! [JSON Pointer]:https://datatracker.ietf.org/doc/html/rfc6901
+
+use crate::convert::FromJValue;
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+
+/// Splits a JSON Pointer into its unescaped reference tokens.
+///
+/// ```
+/// # use json::pointer::parse_pointer;
+/// assert_eq!(Ok(vec!["a/b".to_string(), "c~d".to_string(), "".to_string()]),
+///            parse_pointer("/a~1b/c~0d/"));
+/// assert_eq!(Ok(vec![]), parse_pointer(""));
+/// assert!(parse_pointer("a").is_err());
+/// ```
+pub fn parse_pointer(pointer: &str) -> Result<Vec<String>, JsonError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return Err(JsonError::new(ErrorKind::InvalidPointer,
+                                  format!("The pointer {} does not start with '/'", pointer)));
+    }
+    pointer[1..].split('/').map(|token| {
+        let mut result = String::with_capacity(token.len());
+        let mut chars = token.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '~' => match chars.next() {
+                    Some('0') => result.push('~'),
+                    Some('1') => result.push('/'),
+                    _ => return Err(JsonError::new(
+                        ErrorKind::InvalidPointer,
+                        format!("Invalid escape sequence in the pointer {}", pointer)))
+                },
+                _ => result.push(c)
+            }
+        }
+        Ok(result)
+    }).collect()
+}
+
+/// Converts an array reference token to an index. Leading zeros and `-` are not indices.
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit()) {
+        None
+    } else {
+        token.parse().ok()
+    }
+}
+
+/// Names the value at a pointer in a message, as the pointer or as "the root"
+pub(crate) fn location(pointer: &str) -> &str {
+    if pointer.is_empty() { "the root" } else { pointer }
+}
+
+/// Whether the value at `pointer` is the value at `ancestor` or inside it
+pub(crate) fn contains(ancestor: &str, pointer: &str) -> bool {
+    pointer.starts_with(ancestor) && (pointer.len() == ancestor.len() || pointer[ancestor.len()..].starts_with('/'))
+}
+
+impl JValue {
+    /// Returns a reference to the value at the given JSON Pointer, or [`None`] if the pointer
+    /// is malformed or there is no value at it.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("{\"users\": [{\"name\": \"json\"}]}");
+    /// assert_eq!(Some(&json!("\"json\"")), j_value.pointer("/users/0/name"));
+    /// assert_eq!(Some(&j_value), j_value.pointer(""));
+    /// assert_eq!(None, j_value.pointer("/users/1"));
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&JValue> {
+        let mut value = self;
+        for token in parse_pointer(pointer).ok()? {
+            value = match value {
+                JValue::Object(o) => o.get(&token)?,
+                JValue::Array(a) => a.get(array_index(&token)?)?,
+                _ => return None
+            };
+        }
+        Some(value)
+    }
+
+    /// Returns a mutable reference to the value at the given JSON Pointer, or [`None`] if the
+    /// pointer is malformed or there is no value at it.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let mut j_value = json!("{\"a\": [1, 2]}");
+    /// *j_value.pointer_mut("/a/1").unwrap() = json!("true");
+    /// assert_eq!(json!("{\"a\": [1, true]}"), j_value);
+    /// ```
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut JValue> {
+        let mut value = self;
+        for token in parse_pointer(pointer).ok()? {
+            value = match value {
+                JValue::Object(o) => o.get_mut(&token)?,
+                JValue::Array(a) => a.get_mut(array_index(&token)?)?,
+                _ => return None
+            };
+        }
+        Some(value)
+    }
+
+    /// Converts the value at the given JSON Pointer to `T`, following the coercion rules of
+    /// [`FromJValue`].
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::error::ErrorKind;
+    /// let config = json!("{\"config\": {\"timeout\": \"30\", \"verbose\": true}}");
+    /// assert_eq!(Ok(30_u32), config.get_path_as::<u32>("/config/timeout"));
+    /// assert_eq!(Ok(true), config.get_path_as::<bool>("/config/verbose"));
+    /// assert_eq!(ErrorKind::NotFound, config.get_path_as::<f64>("/config/x").unwrap_err().kind());
+    /// assert_eq!(ErrorKind::InvalidType,
+    ///            config.get_path_as::<i64>("/config/verbose").unwrap_err().kind());
+    /// ```
+    pub fn get_path_as<T: FromJValue>(&self, pointer: &str) -> Result<T, JsonError> {
+        parse_pointer(pointer)?;
+        match self.pointer(pointer) {
+            Some(value) => T::from_jvalue(value).map_err(|e| JsonError::new(
+                e.kind(), format!("{} at {}", e.message(), pointer))),
+            None => Err(JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer)))
+        }
+    }
+
+    /// Replaces the value at the given JSON Pointer with `value`, returning the value it
+    /// replaces. Fails with [`ErrorKind::InvalidPointer`] if the pointer is malformed and with
+    /// [`ErrorKind::NotFound`] if there is no value at it; a new member is not added.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let mut j_value = json!("{\"a\": [1, 2]}");
+    /// assert_eq!(Ok(json!("2")), j_value.replace("/a/1", json!("true")));
+    /// assert_eq!(json!("{\"a\": [1, true]}"), j_value);
+    /// assert!(j_value.replace("/b", json!("3")).is_err());
+    /// ```
+    pub fn replace(&mut self, pointer: &str, value: JValue) -> Result<JValue, JsonError> {
+        parse_pointer(pointer)?;
+        match self.pointer_mut(pointer) {
+            Some(old) => Ok(std::mem::replace(old, value)),
+            None => Err(JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer)))
+        }
+    }
+
+    /// Swaps the values at two JSON Pointers, moving the subtrees without copying them. Fails
+    /// like [`JValue::replace`] and also if one pointer is inside the other, as a value can not
+    /// swap places with its own descendant.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let mut j_value = json!("{\"a\": {\"x\": 1}, \"b\": [2]}");
+    /// j_value.swap("/a", "/b/0").unwrap();
+    /// assert_eq!(json!("{\"a\": 2, \"b\": [{\"x\": 1}]}"), j_value);
+    /// assert!(j_value.swap("/b", "/b/0").is_err());
+    /// ```
+    pub fn swap(&mut self, a: &str, b: &str) -> Result<(), JsonError> {
+        let (tokens_a, tokens_b) = (parse_pointer(a)?, parse_pointer(b)?);
+        for pointer in [a, b].iter() {
+            if self.pointer(pointer).is_none() {
+                return Err(JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer)));
+            }
+        }
+        if tokens_a == tokens_b {
+            return Ok(());
+        }
+        if tokens_a.starts_with(&tokens_b) || tokens_b.starts_with(&tokens_a) {
+            return Err(JsonError::new(ErrorKind::InvalidPointer,
+                                      format!("Can not swap {} and {}, as one is inside the other", a, b)));
+        }
+        let value_a = self.pointer_mut(a).unwrap().take();
+        let value_b = self.replace(b, value_a)?;
+        *self.pointer_mut(a).unwrap() = value_b;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::pointer::{array_index, contains, location};
+
+    #[test]
+    fn test_location_and_contains() {
+        assert_eq!(("the root", "/a"), (location(""), location("/a")));
+        assert!(contains("", "/a") && contains("/a", "/a") && contains("/a", "/a/b"));
+        assert!(!contains("/a", "/ab") && !contains("/a/b", "/a"));
+    }
+
+    #[test]
+    fn test_array_index() {
+        assert_eq!(Some(0), array_index("0"));
+        assert_eq!(Some(10), array_index("10"));
+        assert_eq!(None, array_index("01"));
+        assert_eq!(None, array_index("-"));
+        assert_eq!(None, array_index("+1"));
+        assert_eq!(None, array_index(""));
+    }
+
+    #[test]
+    fn test_pointer() {
+        let j_value = json!("{\"\": 0, \"a/b\": {\"m~n\": [true]}}");
+        assert_eq!(Some(&json!("0")), j_value.pointer("/"));
+        assert_eq!(Some(&json!("true")), j_value.pointer("/a~1b/m~0n/0"));
+        assert_eq!(None, j_value.pointer("/a~1b/m~0n/00"));
+        assert_eq!(None, j_value.pointer("/a~2b"));
+        assert_eq!(None, j_value.pointer("a"));
+    }
+
+    #[test]
+    fn test_replace_and_swap() {
+        let mut j_value = json!("{\"a\": [1, {\"b\": 2}], \"c\": null}");
+        assert_eq!(Ok(json!("null")), j_value.replace("/c", json!("[]")));
+        assert_eq!(ErrorKind::NotFound, j_value.replace("/a/2", json!("3")).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidPointer, j_value.replace("a", json!("3")).unwrap_err().kind());
+        j_value.swap("/a/0", "/a/1/b").unwrap();
+        assert_eq!(json!("{\"a\": [2, {\"b\": 1}], \"c\": []}"), j_value);
+        j_value.swap("/c", "/c").unwrap();
+        assert_eq!("No value at /d", j_value.swap("/c", "/d").unwrap_err().message());
+        assert_eq!(ErrorKind::InvalidPointer, j_value.swap("", "/a").unwrap_err().kind());
+        assert_eq!(Ok(json!("[]")), j_value.replace("", json!("1")).map(|old| old["c"].clone()));
+    }
+
+    #[test]
+    fn test_get_path_as_errors() {
+        let j_value = json!("{\"a\": [1.5]}");
+        let e = j_value.get_path_as::<i32>("/a/0").unwrap_err();
+        assert_eq!(ErrorKind::InvalidType, e.kind());
+        assert_eq!("Expected an integer, found the number 1.5 at /a/0", e.to_string());
+        let e = j_value.get_path_as::<i32>("a").unwrap_err();
+        assert_eq!(ErrorKind::InvalidPointer, e.kind());
+        let e = j_value.get_path_as::<String>("/b").unwrap_err();
+        assert_eq!("No value at /b", e.to_string());
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Problem details ([RFC 7807]), the JSON bodies HTTP APIs use to describe their errors.
+//!
+//! [RFC 7807]:https://datatracker.ietf.org/doc/html/rfc7807
+
+use std::str::FromStr;
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::serializer::Serialize;
+
+/// The media type of a problem details body
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// The problem type of problems without one, which are described by their status code alone
+pub const ABOUT_BLANK: &str = "about:blank";
+
+const MEMBERS: [&str; 5] = ["type", "title", "status", "detail", "instance"];
+
+/// A problem details object
+///
+/// The members the RFC defines have fields; any other member is an extension, kept in
+/// `extensions`.
+///
+/// ```
+/// # use json::json;
+/// # use json::convert::FromJValue;
+/// # use json::problem::ProblemDetails;
+/// let problem = ProblemDetails::new("https://example.com/probs/out-of-credit")
+///     .with_title("You do not have enough credit.")
+///     .with_status(403)
+///     .with_extension("balance", json!("30"));
+/// let j_value = problem.to_jvalue();
+/// assert_eq!(json!("403"), j_value["status"]);
+/// assert_eq!(json!("30"), j_value["balance"]);
+/// assert_eq!(Ok(problem), ProblemDetails::from_jvalue(&j_value));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProblemDetails {
+    /// A URI reference identifying the problem type, [`ABOUT_BLANK`] by default
+    pub problem_type: String,
+    /// A short summary of the problem type
+    pub title: Option<String>,
+    /// The HTTP status code of the response
+    pub status: Option<u16>,
+    /// An explanation of this occurrence of the problem
+    pub detail: Option<String>,
+    /// A URI reference identifying this occurrence of the problem
+    pub instance: Option<String>,
+    pub extensions: JObject,
+}
+
+impl Default for ProblemDetails {
+    fn default() -> Self {
+        ProblemDetails::new(ABOUT_BLANK)
+    }
+}
+
+impl ProblemDetails {
+    pub fn new(problem_type: &str) -> ProblemDetails {
+        ProblemDetails {
+            problem_type: problem_type.to_string(),
+            title: None,
+            status: None,
+            detail: None,
+            instance: None,
+            extensions: JObject::new(),
+        }
+    }
+
+    /// Creates an [`ABOUT_BLANK`] problem with the given status
+    pub fn from_status(status: u16) -> ProblemDetails {
+        ProblemDetails::default().with_status(status)
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_detail(mut self, detail: &str) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        self.instance = Some(instance.to_string());
+        self
+    }
+
+    /// Adds an extension member. Members the RFC defines can not be extensions, and adding one
+    /// of them is ignored; set its field instead.
+    pub fn with_extension(mut self, name: &str, value: JValue) -> Self {
+        if !MEMBERS.contains(&name) {
+            self.extensions.insert(name.to_string(), value);
+        }
+        self
+    }
+
+    /// Returns the extension member with the given name, if the problem has it
+    pub fn extension(&self, name: &str) -> Option<&JValue> {
+        self.extensions.get(&name.to_string())
+    }
+
+    /// Converts the problem to an object. The `type` member is left out when it is
+    /// [`ABOUT_BLANK`], as the RFC allows.
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = self.extensions.clone();
+        let string = |s: &str| JValue::String(JString::from(s));
+        if self.problem_type != ABOUT_BLANK {
+            object.insert("type".to_string(), string(&self.problem_type));
+        }
+        if let Some(title) = &self.title {
+            object.insert("title".to_string(), string(title));
+        }
+        if let Some(status) = self.status {
+            object.insert("status".to_string(), JValue::Number(JNumber::from_str(&status.to_string()).unwrap()));
+        }
+        if let Some(detail) = &self.detail {
+            object.insert("detail".to_string(), string(detail));
+        }
+        if let Some(instance) = &self.instance {
+            object.insert("instance".to_string(), string(instance));
+        }
+        JValue::Object(object)
+    }
+}
+
+impl FromJValue for ProblemDetails {
+    /// Reads a problem details object. A member the RFC defines with a value of the wrong type
+    /// fails the conversion, and so does a `status` which is not an HTTP status code.
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        let object = match value {
+            JValue::Object(object) => object,
+            _ => return Err(JsonError::new(ErrorKind::InvalidType,
+                                           format!("Expected a problem details object, found {}", describe(value))))
+        };
+        let string = |name: &str| match object.get(&name.to_string()) {
+            None => Ok(None),
+            Some(JValue::String(s)) => Ok(Some(s.to_string())),
+            Some(other) => Err(JsonError::new(
+                ErrorKind::InvalidType, format!("Expected a string, found {} at /{}", describe(other), name)))
+        };
+        let status = match object.get(&"status".to_string()) {
+            None => None,
+            Some(status) => {
+                let code = match status {
+                    JValue::Number(_) => u16::from_jvalue(status).ok(),
+                    _ => None
+                };
+                match code.filter(|code| (100..600).contains(code)) {
+                    Some(code) => Some(code),
+                    None => return Err(JsonError::new(
+                        ErrorKind::InvalidType, format!("Expected a status code, found {} at /status", describe(status))))
+                }
+            }
+        };
+        let mut extensions = object.clone();
+        for name in MEMBERS.iter() {
+            extensions.remove(&name.to_string());
+        }
+        Ok(ProblemDetails {
+            problem_type: string("type")?.unwrap_or_else(|| ABOUT_BLANK.to_string()),
+            title: string("title")?,
+            status,
+            detail: string("detail")?,
+            instance: string("instance")?,
+            extensions,
+        })
+    }
+}
+
+#[cfg(feature = "http")]
+impl ProblemDetails {
+    /// Serializes the problem into a response with the problem's status, or `500 Internal Server
+    /// Error` if it has none or it is not a valid status code
+    pub fn into_response(self) -> http::Response<bytes::Bytes> {
+        let status = self.status.and_then(|code| http::StatusCode::from_u16(code).ok())
+            .unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = http::Response::new(bytes::Bytes::from(self.serialize()));
+        *response.status_mut() = status;
+        response.headers_mut().insert(http::header::CONTENT_TYPE, http::HeaderValue::from_static(PROBLEM_CONTENT_TYPE));
+        response
+    }
+}
+
+impl Serialize for ProblemDetails {
+    fn serialize(&self) -> String {
+        self.to_jvalue().serialize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::FromJValue;
+    use crate::json;
+    use crate::parser::parse;
+    use crate::problem::{ProblemDetails, ABOUT_BLANK};
+    use crate::serializer::Serialize;
+
+    #[test]
+    fn test_round_trip() {
+        let problem = ProblemDetails::from_status(404).with_detail("No such order").with_instance("/orders/7");
+        assert_eq!(ABOUT_BLANK, problem.problem_type);
+        let serialized = problem.serialize();
+        assert_eq!(Ok(json!("{\"status\": 404, \"detail\": \"No such order\", \"instance\": \"/orders/7\"}")),
+                   parse(&serialized));
+        assert_eq!(Ok(problem), ProblemDetails::from_jvalue(&parse(&serialized).unwrap()));
+
+        let problem = ProblemDetails::default().with_extension("status", json!("1")).with_extension("errors", json!("[]"));
+        assert_eq!(None, problem.status);
+        assert_eq!(Some(&json!("[]")), problem.extension("errors"));
+        assert_eq!(json!("{\"errors\": []}"), problem.to_jvalue());
+    }
+
+    #[test]
+    fn test_from_jvalue_errors() {
+        let error = |text: &str| ProblemDetails::from_jvalue(&json!(text)).unwrap_err().message().to_string();
+        assert_eq!("Expected a problem details object, found an array", error("[]"));
+        assert_eq!("Expected a string, found the number 1 at /title", error("{\"title\": 1}"));
+        assert_eq!("Expected a status code, found the number 99 at /status", error("{\"status\": 99}"));
+        assert_eq!("Expected a status code, found the string \"404\" at /status", error("{\"status\": \"404\"}"));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_into_response() {
+        let response = ProblemDetails::from_status(422).with_title("Invalid order").into_response();
+        assert_eq!(http::StatusCode::UNPROCESSABLE_ENTITY, response.status());
+        assert_eq!("application/problem+json", response.headers()["content-type"]);
+        assert!(crate::http::is_json_content_type(crate::problem::PROBLEM_CONTENT_TYPE));
+        assert_eq!(Ok(json!("{\"status\": 422, \"title\": \"Invalid order\"}")),
+                   parse(std::str::from_utf8(response.body()).unwrap()));
+        assert_eq!(http::StatusCode::INTERNAL_SERVER_ERROR, ProblemDetails::default().into_response().status());
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Pruning documents with GraphQL-style selection sets.
+
+use crate::data_structures::{JObject, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::ParseOptions;
+
+/// A parsed selection set such as `{ user { id, name, address { city } } }`.
+///
+/// A field keeps the member with its name, and a field with a selection set of its own keeps
+/// only the selected members of that member. Selections apply to each element of an array,
+/// as in GraphQL lists, and leave other values as they are. Fields the value does not have
+/// are left out. Commas between fields are optional, and a name with characters other than
+/// letters, digits, `_` and `-` is written in double quotes.
+///
+/// ```
+/// # use json::json;
+/// # use json::projection::Selection;
+/// let selection = Selection::parse("{ users { id, address { city } } }").unwrap();
+/// let j_value = json!("{\"users\": [{\"id\": 1, \"address\": {\"city\": \"Oslo\", \"zip\": \"0150\"}}, {\"id\": 2}],\
+///                      \"total\": 2}");
+/// assert_eq!(json!("{\"users\": [{\"id\": 1, \"address\": {\"city\": \"Oslo\"}}, {\"id\": 2}]}"),
+///            selection.apply(&j_value));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    fields: Vec<(String, Option<Selection>)>,
+}
+
+impl Selection {
+    /// Parses a selection set. The outermost braces are optional, and selection sets may be
+    /// nested up to [`ParseOptions::max_depth`] levels.
+    pub fn parse(selection: &str) -> Result<Selection, JsonError> {
+        let mut parser = SelectionParser { source: selection, position: 0, depth: 0 };
+        parser.skip_whitespace();
+        let result = if parser.peek() == Some('{') {
+            parser.selection()?
+        } else {
+            parser.fields(None)?
+        };
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(result),
+            Some(c) => Err(parser.error(format!("Unexpected {}", c)))
+        }
+    }
+
+    /// Returns a copy of `value` with only the selected members
+    pub fn apply(&self, value: &JValue) -> JValue {
+        match value {
+            JValue::Object(o) => {
+                let mut result = JObject::new();
+                for (name, selection) in &self.fields {
+                    if let Some(member) = o.get(name) {
+                        let member = match selection {
+                            Some(selection) => selection.apply(member),
+                            None => member.clone()
+                        };
+                        result.insert(name.clone(), member);
+                    }
+                }
+                JValue::Object(result)
+            }
+            JValue::Array(a) => JValue::Array(a.iter().map(|element| self.apply(element)).collect()),
+            _ => value.clone()
+        }
+    }
+}
+
+impl JValue {
+    /// Returns a copy of this value pruned to a GraphQL-style selection set, see [`Selection`].
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("{\"user\": {\"id\": 7, \"name\": \"ada\", \"token\": \"secret\"}}");
+    /// assert_eq!(Ok(json!("{\"user\": {\"id\": 7, \"name\": \"ada\"}}")), j_value.project("{ user { id name } }"));
+    /// assert!(j_value.project("{ user { id }").is_err());
+    /// ```
+    pub fn project(&self, selection: &str) -> Result<JValue, JsonError> {
+        Ok(Selection::parse(selection)?.apply(self))
+    }
+}
+
+struct SelectionParser<'a> {
+    source: &'a str,
+    position: usize,
+    /// The number of selection sets enclosing the position
+    depth: usize,
+}
+
+impl SelectionParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn error(&self, message: String) -> JsonError {
+        JsonError::new(ErrorKind::Syntax, message).with_position(self.source, self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !(c.is_whitespace() || c == ',') {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+    }
+
+    /// Parses `{ fields }`
+    fn selection(&mut self) -> Result<Selection, JsonError> {
+        let open = self.position;
+        let max_depth = ParseOptions::default().max_depth;
+        if self.depth == max_depth {
+            return Err(self.error(format!("The selection set is nested deeper than the limit of {} levels",
+                                          max_depth)));
+        }
+        self.depth += 1;
+        self.position += 1;
+        let selection = self.fields(Some(open))?;
+        self.position += 1;
+        self.depth -= 1;
+        Ok(selection)
+    }
+
+    /// Parses fields up to the `}` closing the brace at `open`, or up to the end
+    fn fields(&mut self, open: Option<usize>) -> Result<Selection, JsonError> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('}') if open.is_some() => break,
+                None if open.is_none() => break,
+                None => return Err(JsonError::new(ErrorKind::Syntax, "Unclosed selection set")
+                    .with_position(self.source, open.unwrap_or(0))
+                    .with_hint("expected '}'")),
+                _ => ()
+            }
+            let name = self.name()?;
+            self.skip_whitespace();
+            let selection = if self.peek() == Some('{') { Some(self.selection()?) } else { None };
+            fields.push((name, selection));
+        }
+        if fields.is_empty() {
+            return Err(self.error("Empty selection set".to_string()));
+        }
+        Ok(Selection { fields })
+    }
+
+    fn name(&mut self) -> Result<String, JsonError> {
+        let start = self.position;
+        if self.peek() == Some('"') {
+            return match self.source[start + 1..].find('"') {
+                Some(end) => {
+                    self.position = start + 1 + end + 1;
+                    Ok(self.source[start + 1..start + 1 + end].to_string())
+                }
+                None => Err(self.error("Unterminated field name".to_string()).with_hint("expected a closing '\"'"))
+            };
+        }
+        while let Some(c) = self.peek() {
+            if !(c.is_alphanumeric() || c == '_' || c == '-') {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+        if self.position == start {
+            let found = self.peek().map_or("the end".to_string(), |c| c.to_string());
+            return Err(self.error(format!("Expected a field name, found {}", found)));
+        }
+        Ok(self.source[start..self.position].to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::projection::Selection;
+
+    #[test]
+    fn test_apply() {
+        let j_value = json!("{\"a\": {\"b\": [1, {\"c\": 2, \"d\": 3}], \"e\": null}, \"f g\": true, \"h\": 1}");
+        let project = |selection: &str| j_value.project(selection).unwrap();
+        assert_eq!(json!("{\"a\": {\"b\": [1, {\"c\": 2}]}}"), project("{ a { b { c } } }"));
+        assert_eq!(json!("{\"f g\": true, \"h\": 1}"), project("\"f g\" h missing"));
+        assert_eq!(json!("{\"h\": 1}"), project("{h{x}}"));
+        assert_eq!(json!("[{\"h\": 1}, 2]"), json!("[{\"h\": 1, \"i\": 3}, 2]").project("h").unwrap());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = Selection::parse("{ a { b }").unwrap_err();
+        assert_eq!(ErrorKind::Syntax, error.kind());
+        assert_eq!(("Unclosed selection set", Some(1), Some("expected '}'")),
+                   (error.message(), error.column(), error.hint()));
+        let error = Selection::parse("{ a } }").unwrap_err();
+        assert_eq!(("Unexpected }", Some(7)), (error.message(), error.column()));
+        assert_eq!("Empty selection set", Selection::parse("{ a { } }").unwrap_err().message());
+        assert_eq!("Expected a field name, found :", Selection::parse("{ a: b }").unwrap_err().message());
+        assert_eq!("Unterminated field name", Selection::parse("\"a").unwrap_err().message());
+        assert_eq!("Empty selection set", Selection::parse("").unwrap_err().message());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let nested = format!("{}{}", "{a ".repeat(128), "}".repeat(128));
+        assert!(Selection::parse(&nested).is_ok());
+        let error = Selection::parse(&"{a ".repeat(200_000)).unwrap_err();
+        assert_eq!(ErrorKind::Syntax, error.kind());
+        assert_eq!(("The selection set is nested deeper than the limit of 128 levels", Some(385)),
+                   (error.message(), error.column()));
+    }
+}
@@ -0,0 +1,446 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Java `.properties` files and `.env` files as [`JValue`]s.
+//!
+//! Keys are split at dots into nested objects, so `db.pool.size=5` makes
+//! `{"db": {"pool": {"size": "5"}}}`. Values are always strings; [`FromJValue`] converts them
+//! to numbers and booleans when they are read.
+//!
+//! [`FromJValue`]:crate::convert::FromJValue
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::ParseOptions;
+use crate::transform::push_token;
+
+/// Parses the text of a `.properties` or a `.env` file into an object.
+///
+/// Both formats are read by the same rules:
+/// - Lines starting with `#` or `!` are comments.
+/// - A key ends at the first `=`, `:` or whitespace which is not escaped with a backslash. An
+///   `export` before the key, as in shell scripts, is ignored.
+/// - A value in double quotes may use the escapes `\n`, `\t`, `\"` and `\\` and span lines, a
+///   value in single quotes is taken as it is, and either may be followed by a comment.
+/// - Any other value ends with its line, without its trailing whitespace, unless the line ends
+///   with a backslash. It may use the escapes of `.properties` files, such as `\t` and `\uXXXX`,
+///   and a `#` in it is part of the value.
+///
+/// A key given again replaces the earlier value, and a key may have up to
+/// [`ParseOptions::max_depth`] segments.
+///
+/// ```
+/// # use json::json;
+/// # use json::properties;
+/// let text = "# The database\ndb.host = localhost\ndb.pool.size: 5\nexport GREETING=\"Hello,\\nWorld\"\n";
+/// assert_eq!(Ok(json!("{\"db\": {\"host\": \"localhost\", \"pool\": {\"size\": \"5\"}}, \"GREETING\": \"Hello,\\nWorld\"}")),
+///            properties::parse(text));
+/// assert_eq!("The key db.host.name conflicts with an earlier key",
+///            properties::parse("db.host=a\ndb.host.name=b").unwrap_err().message());
+/// ```
+pub fn parse(text: &str) -> Result<JValue, JsonError> {
+    let mut reader = Reader { text, chars: text.char_indices().peekable() };
+    let mut root = JObject::new();
+    while let Some(start) = reader.next_entry() {
+        let key = reader.key()?;
+        if key.is_empty() {
+            return Err(reader.error("Missing key", start));
+        }
+        let value = reader.value()?;
+        insert(&mut root, &key, value).map_err(|e| e.with_position(text, start))?;
+    }
+    Ok(JValue::Object(root))
+}
+
+/// Writes an object as a `.properties` file [`parse`] reads back to the same object, one
+/// `key=value` line per string, sorted by key.
+///
+/// Numbers and booleans become their text and `null` an empty value; parsing them back makes
+/// strings. Empty objects leave no line. Arrays, and names which are empty or have a `.`, can
+/// not be written and fail with [`ErrorKind::InvalidType`].
+///
+/// ```
+/// # use json::json;
+/// # use json::properties;
+/// assert_eq!(Ok("db.pool.size=5\ndb.user=\\ admin\n".to_string()),
+///            properties::to_properties(&json!("{\"db\": {\"user\": \" admin\", \"pool\": {\"size\": 5}}}")));
+/// ```
+pub fn to_properties(value: &JValue) -> Result<String, JsonError> {
+    let object = match value {
+        JValue::Object(object) => object,
+        _ => return Err(JsonError::new(ErrorKind::InvalidType,
+                                       format!("Expected an object, found {}", crate::convert::describe(value))))
+    };
+    let mut lines = Vec::new();
+    flatten(object, &mut String::new(), &mut String::new(), &mut lines)?;
+    lines.sort();
+    let mut text = String::new();
+    for (key, value) in lines {
+        text.push_str(&key);
+        text.push('=');
+        text.push_str(&value);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: &str, offset: usize) -> JsonError {
+        JsonError::new(ErrorKind::Syntax, message).with_position(self.text, offset)
+    }
+
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.text.len(), |&(i, _)| i)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(' ' | '\t' | '\x0c') = self.peek() {
+            self.chars.next();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        for (_, c) in self.chars.by_ref() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    /// Skips blank lines and comments, and returns the offset of the next entry
+    fn next_entry(&mut self) -> Option<usize> {
+        loop {
+            match self.peek()? {
+                ' ' | '\t' | '\x0c' | '\r' | '\n' => {
+                    self.chars.next();
+                }
+                '#' | '!' => self.skip_line(),
+                _ => break
+            }
+        }
+        let start = self.offset();
+        let rest = &self.text[start..];
+        if rest.starts_with("export") && rest[6..].starts_with([' ', '\t']) {
+            self.chars.nth(5);
+            self.skip_blanks();
+        }
+        Some(start)
+    }
+
+    fn key(&mut self) -> Result<String, JsonError> {
+        let mut key = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '=' | ':' | ' ' | '\t' | '\x0c' | '\r' | '\n' => break,
+                '\\' => {
+                    self.chars.next();
+                    self.escape(&mut key)?;
+                }
+                _ => {
+                    key.push(c);
+                    self.chars.next();
+                }
+            }
+        }
+        self.skip_blanks();
+        if let Some('=' | ':') = self.peek() {
+            self.chars.next();
+            self.skip_blanks();
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<String, JsonError> {
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                let start = self.offset();
+                self.chars.next();
+                let value = self.quoted(quote).ok_or_else(|| self.error("Unterminated quoted value", start))?;
+                self.skip_blanks();
+                match self.peek() {
+                    None | Some('\r' | '\n') => {}
+                    Some('#') => self.skip_line(),
+                    Some(_) => {
+                        let offset = self.offset();
+                        return Err(self.error("Unexpected text after the quoted value", offset));
+                    }
+                }
+                Ok(value)
+            }
+            _ => self.unquoted()
+        }
+    }
+
+    fn quoted(&mut self, quote: char) -> Option<String> {
+        let mut value = String::new();
+        loop {
+            match self.chars.next()?.1 {
+                c if c == quote => return Some(value),
+                '\\' if quote == '"' => match self.chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    c @ ('"' | '\\') => value.push(c),
+                    c => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                },
+                c => value.push(c)
+            }
+        }
+    }
+
+    fn unquoted(&mut self) -> Result<String, JsonError> {
+        let mut value = String::new();
+        // The length of the value up to its last escape, which is kept even if it is whitespace
+        let mut kept = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '\r' | '\n' => break,
+                '\\' => {
+                    self.chars.next();
+                    match self.peek() {
+                        Some('\r' | '\n') => {
+                            if self.chars.next().map(|(_, c)| c) == Some('\r') && self.peek() == Some('\n') {
+                                self.chars.next();
+                            }
+                            self.skip_blanks();
+                        }
+                        _ => {
+                            self.escape(&mut value)?;
+                            kept = value.len();
+                        }
+                    }
+                }
+                _ => {
+                    value.push(c);
+                    self.chars.next();
+                }
+            }
+        }
+        let trimmed = value[kept..].trim_end().len();
+        value.truncate(kept + trimmed);
+        Ok(value)
+    }
+
+    /// Reads the escape after a backslash into `out`
+    fn escape(&mut self, out: &mut String) -> Result<(), JsonError> {
+        let start = self.offset() - 1;
+        match self.chars.next() {
+            Some((_, 't')) => out.push('\t'),
+            Some((_, 'n')) => out.push('\n'),
+            Some((_, 'r')) => out.push('\r'),
+            Some((_, 'f')) => out.push('\x0c'),
+            Some((_, 'u')) => {
+                let high = self.code_unit().ok_or_else(|| self.error("Invalid \\u escape", start))?;
+                let c = if (0xD800..0xDC00).contains(&high) {
+                    let low = match (self.chars.next(), self.chars.next()) {
+                        (Some((_, '\\')), Some((_, 'u'))) => self.code_unit(),
+                        _ => None
+                    };
+                    low.filter(|low| (0xDC00..0xE000).contains(low))
+                        .and_then(|low| char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)))
+                } else {
+                    char::from_u32(high)
+                };
+                out.push(c.ok_or_else(|| self.error("Invalid \\u escape", start))?);
+            }
+            Some((_, c)) => out.push(c),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn code_unit(&mut self) -> Option<u32> {
+        let start = self.offset();
+        let digits = self.text.get(start..start + 4)?;
+        let unit = u32::from_str_radix(digits, 16).ok()?;
+        self.chars.nth(3);
+        Some(unit)
+    }
+}
+
+fn insert(root: &mut JObject, key: &str, value: String) -> Result<(), JsonError> {
+    let conflict = || JsonError::new(ErrorKind::InvalidType, format!("The key {} conflicts with an earlier key", key));
+    let names: Vec<&str> = key.split('.').collect();
+    if names.iter().any(|name| name.is_empty()) {
+        return Err(JsonError::new(ErrorKind::Syntax, format!("Invalid key '{}'", key)));
+    }
+    let max_depth = ParseOptions::default().max_depth;
+    if names.len() > max_depth {
+        return Err(JsonError::new(ErrorKind::Syntax, format!("A key has more than the limit of {} segments", max_depth)));
+    }
+    let (last, parents) = names.split_last().unwrap();
+    let mut object = root;
+    for name in parents {
+        if object.get(&name.to_string()).is_none() {
+            object.insert(name.to_string(), JValue::Object(JObject::new()));
+        }
+        object = match object.get_mut(&name.to_string()) {
+            Some(JValue::Object(child)) => child,
+            _ => return Err(conflict())
+        };
+    }
+    if let Some(JValue::Object(_)) = object.get(&last.to_string()) {
+        return Err(conflict());
+    }
+    object.insert(last.to_string(), JValue::String(JString::from(value)));
+    Ok(())
+}
+
+fn flatten(object: &JObject, prefix: &mut String, path: &mut String, lines: &mut Vec<(String, String)>)
+           -> Result<(), JsonError> {
+    let (prefix_len, path_len) = (prefix.len(), path.len());
+    for (name, value) in object.iter() {
+        push_token(path, name);
+        if name.is_empty() || name.contains('.') {
+            return Err(JsonError::new(ErrorKind::InvalidType,
+                                      format!("The name '{}' can not be written as a key at {}", name, path)));
+        }
+        if !prefix.is_empty() {
+            prefix.push('.');
+        }
+        escape_key(name, prefix);
+        let text = match value {
+            JValue::Object(object) => {
+                flatten(object, prefix, path, lines)?;
+                None
+            }
+            JValue::Array(_) => return Err(JsonError::new(
+                ErrorKind::InvalidType, format!("An array can not be written as a property at {}", path))),
+            JValue::String(s) => Some(s.to_string()),
+            JValue::Number(n) => Some(n.to_string()),
+            JValue::Boolean(b) => Some(b.to_string()),
+            JValue::Null => Some(String::new())
+        };
+        if let Some(text) = text {
+            lines.push((prefix.clone(), escape_value(&text)));
+        }
+        prefix.truncate(prefix_len);
+        path.truncate(path_len);
+    }
+    Ok(())
+}
+
+fn escape_key(key: &str, out: &mut String) {
+    for c in key.chars() {
+        match c {
+            '=' | ':' | ' ' | '#' | '!' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => escape_control(c, out)
+        }
+    }
+}
+
+fn escape_value(value: &str) -> String {
+    let kept = value.trim_end().len();
+    let mut out = String::with_capacity(value.len());
+    for (i, c) in value.char_indices() {
+        match c {
+            // Leading and trailing whitespace and quotes at the start are not part of the value
+            // unless they are escaped.
+            ' ' if i == 0 || i >= kept => out.push_str("\\ "),
+            '"' | '\'' if i == 0 => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\\' => out.push_str("\\\\"),
+            _ => escape_control(c, &mut out)
+        }
+    }
+    out
+}
+
+fn escape_control(c: char, out: &mut String) {
+    match c {
+        '\t' => out.push_str("\\t"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\x0c' => out.push_str("\\f"),
+        c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+        c => out.push(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::properties::{parse, to_properties};
+
+    #[test]
+    fn test_parse() {
+        let text = "! A comment\r\n\r\nkey value\nspaced  =  x y \\u00e9\\uD83D\\uDE00  \n\
+                    long = one, \\\n       two\nescaped\\ key\\=:\\ \\ v\\ \nsingle='a\\nb' # comment\n\
+                    double=\"a\nb\"\nhash=#x\nempty\nkey=again";
+        assert_eq!(Ok(json!("{\"key\": \"again\", \"spaced\": \"x y \\u00e9\\ud83d\\ude00\", \"long\": \"one, two\",\
+                              \"escaped key=\": \"  v \", \"single\": \"a\\\\nb\", \"double\": \"a\\nb\",\
+                              \"hash\": \"#x\", \"empty\": \"\"}")),
+                   parse(text));
+        assert_eq!(Ok(json!("{\"export\": \"x\", \"a\": {\"b\": \"1\", \"c\": \"2\"}}")),
+                   parse("export=x\nexport a.b=1\na.c=2"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (text, kind, message) in [
+            ("=1", ErrorKind::Syntax, "Missing key"),
+            ("a..b=1", ErrorKind::Syntax, "Invalid key 'a..b'"),
+            ("a=\"x", ErrorKind::Syntax, "Unterminated quoted value"),
+            ("a='x' y", ErrorKind::Syntax, "Unexpected text after the quoted value"),
+            ("a=\\u12", ErrorKind::Syntax, "Invalid \\u escape"),
+            ("a=\\uD800x", ErrorKind::Syntax, "Invalid \\u escape"),
+            ("a.b=1\na=2", ErrorKind::InvalidType, "The key a conflicts with an earlier key"),
+        ] {
+            let error = parse(text).unwrap_err();
+            assert_eq!((kind, message), (error.kind(), error.message()), "{}", text);
+        }
+        assert_eq!(Some(6), parse("a.b=1\na=2").unwrap_err().offset());
+        assert!(parse(&format!("{}=1", vec!["a"; 128].join("."))).is_ok());
+        let error = parse(&format!("{}=1", vec!["a"; 100_000].join("."))).unwrap_err();
+        assert_eq!((ErrorKind::Syntax, "A key has more than the limit of 128 segments"), (error.kind(), error.message()));
+    }
+
+    #[test]
+    fn test_to_properties() {
+        let j_value = json!("{\"a\": {\"b c\": \" x \", \"d\": {\"e:f\": \"\\\"q\\\\\\n\\u0001\"}}, \"#\": \"é\"}");
+        let text = to_properties(&j_value).unwrap();
+        assert_eq!("\\#=é\na.b\\ c=\\ x\\ \na.d.e\\:f=\\\"q\\\\\\n\\u0001\n", text);
+        assert_eq!(Ok(j_value), parse(&text));
+        assert_eq!(Ok("a=\nb=1.5\n".to_string()), to_properties(&json!("{\"a\": null, \"b\": 1.5, \"c\": {}}")));
+        assert_eq!("An array can not be written as a property at /a/b",
+                   to_properties(&json!("{\"a\": {\"b\": []}}")).unwrap_err().message());
+        assert_eq!("The name 'a.b' can not be written as a key at /a.b",
+                   to_properties(&json!("{\"a.b\": 1}")).unwrap_err().message());
+    }
+}
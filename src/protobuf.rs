@@ -0,0 +1,156 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Conversion between values and the well-known types `google.protobuf.Struct` and
+//! `google.protobuf.Value` of [prost-types], which gRPC services use for dynamic JSON.
+//!
+//! Protocol buffers numbers are `f64`s, so integers beyond 2^53 lose precision when converted to
+//! a [`Value`]. A [`Value`] without a kind is read as `null`, and a number which is NaN or
+//! infinite can not be converted to a [`JValue`].
+//!
+//! ```
+//! # use std::convert::TryFrom;
+//! # use json::json;
+//! # use json::data_structures::JValue;
+//! use prost_types::value::Kind;
+//! use prost_types::Value;
+//!
+//! let j_value = json!("{\"name\": \"pen\", \"tags\": [\"blue\"], \"price\": 1.5, \"stock\": null}");
+//! let value = Value::from(&j_value);
+//! let fields = match &value.kind {
+//!     Some(Kind::StructValue(s)) => &s.fields,
+//!     _ => unreachable!()
+//! };
+//! assert_eq!(Some(&Kind::NumberValue(1.5)), fields["price"].kind.as_ref());
+//! assert_eq!(Ok(j_value), JValue::try_from(value));
+//! ```
+//!
+//! [prost-types]:https://crates.io/crates/prost-types
+
+use std::convert::TryFrom;
+
+use prost_types::value::Kind;
+use prost_types::{ListValue, NullValue, Struct, Value};
+
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+
+impl From<&JValue> for Value {
+    fn from(value: &JValue) -> Self {
+        let kind = match value {
+            JValue::Null => Kind::NullValue(NullValue::NullValue as i32),
+            JValue::Boolean(b) => Kind::BoolValue(*b),
+            JValue::Number(n) => Kind::NumberValue(n.get_f64_value()),
+            JValue::String(s) => Kind::StringValue(s.to_string()),
+            JValue::Array(a) => Kind::ListValue(ListValue { values: a.iter().map(Value::from).collect() }),
+            JValue::Object(o) => Kind::StructValue(Struct::from(o))
+        };
+        Value { kind: Some(kind) }
+    }
+}
+
+impl From<JValue> for Value {
+    fn from(value: JValue) -> Self {
+        Value::from(&value)
+    }
+}
+
+impl From<&JObject> for Struct {
+    fn from(object: &JObject) -> Self {
+        Struct { fields: object.iter().map(|(k, v)| (k.clone(), Value::from(v))).collect() }
+    }
+}
+
+impl From<JObject> for Struct {
+    fn from(object: JObject) -> Self {
+        Struct::from(&object)
+    }
+}
+
+impl TryFrom<&Value> for JValue {
+    type Error = JsonError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match &value.kind {
+            None | Some(Kind::NullValue(_)) => Ok(JValue::Null),
+            Some(Kind::BoolValue(b)) => Ok(JValue::Boolean(*b)),
+            Some(Kind::NumberValue(n)) => JNumber::from_f64(*n).map(JValue::Number).ok_or_else(|| JsonError::new(
+                ErrorKind::InvalidType, format!("The number {} can not be a JSON value", n))),
+            Some(Kind::StringValue(s)) => Ok(JValue::String(JString::from(s.as_str()))),
+            Some(Kind::ListValue(l)) => l.values.iter().map(JValue::try_from).collect::<Result<_, _>>().map(JValue::Array),
+            Some(Kind::StructValue(s)) => JObject::try_from(s).map(JValue::Object)
+        }
+    }
+}
+
+impl TryFrom<Value> for JValue {
+    type Error = JsonError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        JValue::try_from(&value)
+    }
+}
+
+impl TryFrom<&Struct> for JObject {
+    type Error = JsonError;
+
+    fn try_from(value: &Struct) -> Result<Self, Self::Error> {
+        value.fields.iter().map(|(k, v)| Ok((k.clone(), JValue::try_from(v)?))).collect()
+    }
+}
+
+impl TryFrom<Struct> for JObject {
+    type Error = JsonError;
+
+    fn try_from(value: Struct) -> Result<Self, Self::Error> {
+        JObject::try_from(&value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use prost_types::value::Kind;
+    use prost_types::{ListValue, Struct, Value};
+
+    use crate::data_structures::{JObject, JValue};
+    use crate::error::ErrorKind;
+    use crate::json;
+
+    #[test]
+    fn test_round_trip() {
+        let j_value = json!("{\"a\": [true, null, {\"b\": \"\\u00e9\"}], \"c\": -2e-3, \"d\": {}}");
+        let object = match &j_value {
+            JValue::Object(object) => object.clone(),
+            _ => unreachable!()
+        };
+        assert_eq!(Ok(j_value.clone()), JValue::try_from(Value::from(j_value)));
+        assert_eq!(Ok(object.clone()), JObject::try_from(Struct::from(object)));
+    }
+
+    #[test]
+    fn test_from_value() {
+        assert_eq!(Ok(JValue::Null), JValue::try_from(Value { kind: None }));
+        assert_eq!(Ok(json!("[3]")), JValue::try_from(Value {
+            kind: Some(Kind::ListValue(ListValue { values: vec![Value { kind: Some(Kind::NumberValue(3.0)) }] }))
+        }));
+        let error = JValue::try_from(Value { kind: Some(Kind::NumberValue(f64::INFINITY)) }).unwrap_err();
+        assert_eq!((ErrorKind::InvalidType, "The number inf can not be a JSON value"), (error.kind(), error.message()));
+    }
+}
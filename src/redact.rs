@@ -0,0 +1,214 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Scrubbing sensitive values out of JSON data-structures before they are logged or stored.
+//!
+//! ```
+//! # use json::json;
+//! # use json::redact::{self, Redaction, RedactRule};
+//! let mut j_value = json!("{\"user\": {\"name\": \"Ann\", \"password\": \"hunter2\", \"cards\": [\"4111\"]}}");
+//! let rules = vec![
+//!     RedactRule::key("pass*", Redaction::Mask),
+//!     RedactRule::path("$.user.cards", Redaction::Remove).unwrap(),
+//! ];
+//! redact::apply(&mut j_value, &rules);
+//! assert_eq!(json!("{\"user\": {\"name\": \"Ann\", \"password\": \"***\"}}"), j_value);
+//! ```
+
+use std::hash::{Hash, Hasher};
+
+use crate::data_structures::{JString, JValue};
+use crate::error::JsonError;
+use crate::glob::glob_match;
+use crate::jsonpath::{JsonPath, PathElement};
+
+/// The replacement [`Redaction::Mask`] puts in place of a value
+pub const MASK: &str = "***";
+
+/// What happens to a value matched by a [`RedactRule`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redaction {
+    /// Replaces the value with the string `"***"`
+    Mask,
+    /// Replaces the value with a string `"hash:<16 hex digits>"` computed from the value, so equal
+    /// values can still be correlated. The hash (64-bit FNV-1a) is stable across runs and
+    /// platforms but is not cryptographic: low-entropy values can be recovered by brute force.
+    Hash,
+    /// Removes the member or array element altogether
+    Remove,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Key(String),
+    Path(JsonPath),
+}
+
+/// A rule selecting values to redact, either by their key or by their location
+#[derive(Debug, Clone)]
+pub struct RedactRule {
+    matcher: Matcher,
+    redaction: Redaction,
+}
+
+impl RedactRule {
+    /// Matches object members whose key matches the glob `pattern` (`*` matches any sequence of
+    /// characters, `?` any single character), at any depth.
+    pub fn key(pattern: &str, redaction: Redaction) -> RedactRule {
+        RedactRule { matcher: Matcher::Key(pattern.to_string()), redaction }
+    }
+
+    /// Matches the values selected by the JSONPath expression `path`, see [`JsonPath::matches`].
+    pub fn path(path: &str, redaction: Redaction) -> Result<RedactRule, JsonError> {
+        Ok(RedactRule { matcher: Matcher::Path(JsonPath::parse(path)?), redaction })
+    }
+
+    fn matches(&self, location: &[PathElement]) -> bool {
+        match &self.matcher {
+            Matcher::Key(pattern) => match location.last() {
+                Some(PathElement::Key(key)) => glob_match(pattern, key),
+                _ => false
+            },
+            Matcher::Path(path) => path.matches(location),
+        }
+    }
+}
+
+/// Applies the rules to every member and array element of `value`. The first matching rule
+/// wins, and redacted values are not descended into. The root value itself is never redacted.
+pub fn apply(value: &mut JValue, rules: &[RedactRule]) {
+    redact(value, rules, &mut Vec::new());
+}
+
+fn find_rule(rules: &[RedactRule], location: &[PathElement]) -> Option<Redaction> {
+    rules.iter().find(|r| r.matches(location)).map(|r| r.redaction)
+}
+
+fn replace(value: &mut JValue, redaction: Redaction) {
+    *value = match redaction {
+        Redaction::Hash => JValue::String(JString::from(format!("hash:{:016x}", fnv1a(value)))),
+        _ => JValue::String(JString::from(MASK)),
+    };
+}
+
+fn redact(value: &mut JValue, rules: &[RedactRule], location: &mut Vec<PathElement>) {
+    match value {
+        JValue::Object(o) => {
+            let keys: Vec<String> = o.iter().map(|(k, _)| k.clone()).collect();
+            for key in keys {
+                location.push(PathElement::Key(key.clone()));
+                match find_rule(rules, location) {
+                    Some(Redaction::Remove) => {
+                        o.remove(&key);
+                    }
+                    Some(redaction) => replace(o.get_mut(&key).unwrap(), redaction),
+                    None => redact(o.get_mut(&key).unwrap(), rules, location),
+                }
+                location.pop();
+            }
+        }
+        JValue::Array(a) => {
+            let mut index = 0;
+            a.retain_mut(|v| {
+                location.push(PathElement::Index(index));
+                index += 1;
+                let keep = match find_rule(rules, location) {
+                    Some(Redaction::Remove) => false,
+                    Some(redaction) => {
+                        replace(v, redaction);
+                        true
+                    }
+                    None => {
+                        redact(v, rules, location);
+                        true
+                    }
+                };
+                location.pop();
+                keep
+            });
+        }
+        _ => ()
+    }
+}
+
+struct Fnv1a(u64);
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+fn fnv1a(value: &JValue) -> u64 {
+    let mut hasher = Fnv1a(0xcbf2_9ce4_8422_2325);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json;
+    use crate::redact::{apply, Redaction, RedactRule};
+
+    #[test]
+    fn test_key_rules() {
+        let mut j_value = json!("{\"token\": \"a\", \"nested\": [{\"api_token\": 1, \"id\": 2}]}");
+        apply(&mut j_value, &[RedactRule::key("*token", Redaction::Mask)]);
+        assert_eq!(json!("{\"token\": \"***\", \"nested\": [{\"api_token\": \"***\", \"id\": 2}]}"), j_value);
+    }
+
+    #[test]
+    fn test_path_rules() {
+        let mut j_value = json!("{\"a\": [1, 2, 3], \"b\": {\"c\": {\"d\": true}}}");
+        let rules = vec![
+            RedactRule::path("$.a[1:]", Redaction::Remove).unwrap(),
+            RedactRule::path("$..d", Redaction::Mask).unwrap(),
+        ];
+        apply(&mut j_value, &rules);
+        assert_eq!(json!("{\"a\": [1], \"b\": {\"c\": {\"d\": \"***\"}}}"), j_value);
+        assert!(RedactRule::path("$[?(@.x)]", Redaction::Mask).is_err());
+    }
+
+    #[test]
+    fn test_first_rule_wins() {
+        let mut j_value = json!("{\"secret\": {\"inner\": 1}}");
+        let rules = vec![
+            RedactRule::key("secret", Redaction::Mask),
+            RedactRule::key("secret", Redaction::Remove),
+        ];
+        apply(&mut j_value, &rules);
+        assert_eq!(json!("{\"secret\": \"***\"}"), j_value);
+    }
+
+    #[test]
+    fn test_hash() {
+        let mut j_value = json!("{\"a\": {\"x\": 1, \"y\": 2}, \"b\": {\"y\": 2, \"x\": 1}, \"c\": \"z\"}");
+        apply(&mut j_value, &[RedactRule::path("$.*", Redaction::Hash).unwrap()]);
+        let hashed = |k: &str| j_value.pointer(k).unwrap().to_string();
+        assert_eq!(hashed("/a"), hashed("/b"));
+        assert_ne!(hashed("/a"), hashed("/c"));
+        assert!(hashed("/a").starts_with("hash:"));
+        assert_eq!("hash:".len() + 16, hashed("/a").len());
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Replication of a document over a channel as a stream of JSON Patches.
+//!
+//! A [`Producer`] turns each new version of a document into a [`Message::Patch`] from the
+//! version before, numbered with a sequence number. A [`Consumer`] applies the patches in order
+//! to its mirror of the document. When the consumer misses a message, or has no document yet,
+//! it asks for a resync, and the producer answers with a [`Message::Snapshot`] of the whole
+//! document.
+//!
+//! ```
+//! # use json::json;
+//! # use json::replicate::{Consumer, Message, Producer, Status};
+//! let mut producer = Producer::new(json!("{\"port\": 80}"));
+//! let mut consumer = Consumer::new();
+//! assert_eq!(Status::ResyncNeeded, consumer.receive(&producer.update(json!("{\"port\": 81}")).unwrap()));
+//! assert_eq!(Status::Applied, consumer.receive(&producer.snapshot()));
+//!
+//! let message = producer.update(json!("{\"port\": 81, \"tls\": true}")).unwrap();
+//! let line = message.serialize();
+//! assert_eq!(json!("{\"seq\": 2, \"patch\": [{\"op\": \"add\", \"path\": \"/tls\", \"value\": true}]}"),
+//!            json::parser::parse(&line).unwrap());
+//! assert_eq!(Status::Applied, consumer.receive(&Message::parse(&line).unwrap()));
+//! assert_eq!(producer.document(), consumer.document().unwrap());
+//! ```
+
+use std::str::FromStr;
+
+use crate::convert::{describe, FromJValue};
+use crate::data_structures::{JNumber, JObject, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::patch::{parse_patch, patch_to_jvalue, Operation};
+use crate::serializer::Serialize;
+
+/// A message from a [`Producer`] to its consumers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// The whole document at the version `seq`
+    Snapshot { seq: u64, document: JValue },
+    /// The changes from the version `seq - 1` to the version `seq`
+    Patch { seq: u64, operations: Vec<Operation> },
+}
+
+/// What a [`Consumer`] did with a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Applied,
+    /// The message is for a version the consumer already has, and was ignored
+    Stale,
+    /// The consumer can not apply the message, as it missed a version, has no document yet or
+    /// the patch does not apply. It needs a snapshot.
+    ResyncNeeded,
+}
+
+impl Message {
+    /// Returns the version of the document the message brings a consumer to
+    pub fn seq(&self) -> u64 {
+        match self {
+            Message::Snapshot { seq, .. } | Message::Patch { seq, .. } => *seq
+        }
+    }
+
+    /// Converts the message to `{"seq": 1, "snapshot": ...}` or `{"seq": 2, "patch": [...]}`
+    pub fn to_jvalue(&self) -> JValue {
+        let mut object = JObject::new();
+        object.insert("seq".to_string(), JValue::Number(JNumber::from_str(&self.seq().to_string()).unwrap()));
+        match self {
+            Message::Snapshot { document, .. } => object.insert("snapshot".to_string(), document.clone()),
+            Message::Patch { operations, .. } => object.insert("patch".to_string(), patch_to_jvalue(operations))
+        };
+        JValue::Object(object)
+    }
+
+    /// Parses a message written by [`Message::serialize`]
+    pub fn parse(text: &str) -> Result<Message, JsonError> {
+        Message::from_jvalue(&crate::parser::parse(text)?)
+    }
+
+    /// Serializes the message on a single line
+    pub fn serialize(&self) -> String {
+        self.to_jvalue().serialize()
+    }
+}
+
+impl FromJValue for Message {
+    fn from_jvalue(value: &JValue) -> Result<Self, JsonError> {
+        let invalid = |message: String| JsonError::new(ErrorKind::InvalidType, format!("Invalid replication message: {}", message));
+        let object = match value {
+            JValue::Object(object) => object,
+            _ => return Err(invalid(format!("expected an object, found {}", describe(value))))
+        };
+        let seq = match object.get(&"seq".to_string()) {
+            Some(seq @ JValue::Number(_)) => u64::from_jvalue(seq)
+                .map_err(|_| invalid(format!("the seq is {}", describe(seq))))?,
+            Some(seq) => return Err(invalid(format!("the seq is {}", describe(seq)))),
+            None => return Err(invalid("the seq is missing".to_string()))
+        };
+        match (object.get(&"snapshot".to_string()), object.get(&"patch".to_string())) {
+            (Some(document), None) => Ok(Message::Snapshot { seq, document: document.clone() }),
+            (None, Some(patch)) => Ok(Message::Patch { seq, operations: parse_patch(patch)? }),
+            _ => Err(invalid("expected either a snapshot or a patch".to_string()))
+        }
+    }
+}
+
+/// The source of a replicated document
+#[derive(Debug, Clone)]
+pub struct Producer {
+    document: JValue,
+    seq: u64,
+    snapshot_interval: Option<u64>,
+}
+
+impl Producer {
+    /// Creates a producer of the document, at the version 0
+    pub fn new(document: JValue) -> Producer {
+        Producer { document, seq: 0, snapshot_interval: None }
+    }
+
+    /// Sends a snapshot instead of a patch for every `interval`th version, so consumers which
+    /// can not ask for a resync catch up eventually
+    pub fn with_snapshot_interval(mut self, interval: u64) -> Producer {
+        self.snapshot_interval = Some(interval.max(1));
+        self
+    }
+
+    pub fn document(&self) -> &JValue {
+        &self.document
+    }
+
+    /// Returns the version of the document
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Makes `document` the next version and returns the message to send for it, or [`None`] if
+    /// it equals the current version, with numbers compared as they are written
+    pub fn update(&mut self, document: JValue) -> Option<Message> {
+        let operations = self.document.diff_patch(&document);
+        if operations.is_empty() {
+            return None;
+        }
+        self.document = document;
+        self.seq += 1;
+        match self.snapshot_interval {
+            Some(interval) if self.seq.is_multiple_of(interval) => Some(self.snapshot()),
+            _ => Some(Message::Patch { seq: self.seq, operations })
+        }
+    }
+
+    /// Returns a snapshot of the current version, the answer to a consumer needing a resync
+    pub fn snapshot(&self) -> Message {
+        Message::Snapshot { seq: self.seq, document: self.document.clone() }
+    }
+}
+
+/// A mirror of a replicated document
+#[derive(Debug, Clone, Default)]
+pub struct Consumer {
+    state: Option<(u64, JValue)>,
+}
+
+impl Consumer {
+    /// Creates a consumer without a document, which needs a snapshot first
+    pub fn new() -> Consumer {
+        Consumer { state: None }
+    }
+
+    /// Returns the document, if the consumer has received one
+    pub fn document(&self) -> Option<&JValue> {
+        self.state.as_ref().map(|(_, document)| document)
+    }
+
+    /// Returns the version of the document, if the consumer has received one
+    pub fn seq(&self) -> Option<u64> {
+        self.state.as_ref().map(|(seq, _)| *seq)
+    }
+
+    /// Applies a message. A snapshot always applies unless it is older than the document, and a
+    /// patch only to the version just before it. A patch which fails to apply leaves the
+    /// document unchanged.
+    pub fn receive(&mut self, message: &Message) -> Status {
+        match (message, &mut self.state) {
+            (Message::Snapshot { seq, .. }, Some((current, _))) if *seq < *current => Status::Stale,
+            (Message::Snapshot { seq, document }, state) => {
+                *state = Some((*seq, document.clone()));
+                Status::Applied
+            }
+            (Message::Patch { .. }, None) => Status::ResyncNeeded,
+            (Message::Patch { seq, .. }, Some((current, _))) if *seq <= *current => Status::Stale,
+            (Message::Patch { seq, operations }, Some((current, document))) if *seq == *current + 1 => {
+                match document.apply_patch(operations) {
+                    Ok(()) => {
+                        *current = *seq;
+                        Status::Applied
+                    }
+                    Err(_) => Status::ResyncNeeded
+                }
+            }
+            (Message::Patch { .. }, Some(_)) => Status::ResyncNeeded
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json;
+    use crate::patch::Operation;
+    use crate::replicate::{Consumer, Message, Producer, Status};
+    use crate::serializer::Serialize;
+
+    #[test]
+    fn test_replication() {
+        let mut producer = Producer::new(json!("{\"a\": [1, 2, 3]}"));
+        let mut consumer = Consumer::new();
+        assert_eq!(None, consumer.seq());
+        assert_eq!(Status::Applied, consumer.receive(&producer.snapshot()));
+        assert_eq!(None, producer.update(json!("{\"a\": [1, 2, 3]}")));
+
+        let first = producer.update(json!("{\"a\": [1]}")).unwrap();
+        let second = producer.update(json!("{\"a\": [1], \"b\": null}")).unwrap();
+        let third = producer.update(json!("{\"b\": null}")).unwrap();
+        assert_eq!(Status::ResyncNeeded, consumer.receive(&second));
+        assert_eq!(Status::Applied, consumer.receive(&first));
+        assert_eq!(Status::Stale, consumer.receive(&first));
+        assert_eq!(Status::Applied, consumer.receive(&Message::parse(&second.serialize()).unwrap()));
+        assert_eq!(Status::Applied, consumer.receive(&third));
+        assert_eq!((Some(3), Some(producer.document())), (consumer.seq(), consumer.document()));
+        assert_eq!(Status::Stale, consumer.receive(&Message::Snapshot { seq: 1, document: json!("{}") }));
+
+        let broken = Message::Patch { seq: 4, operations: vec![Operation::Remove { path: "/x".to_string() }] };
+        assert_eq!(Status::ResyncNeeded, consumer.receive(&broken));
+        assert_eq!(Some(3), consumer.seq());
+    }
+
+    #[test]
+    fn test_snapshot_interval_and_messages() {
+        let mut producer = Producer::new(json!("0")).with_snapshot_interval(2);
+        assert!(matches!(producer.update(json!("1")), Some(Message::Patch { seq: 1, .. })));
+        let snapshot = producer.update(json!("2")).unwrap();
+        assert_eq!(Message::Snapshot { seq: 2, document: json!("2") }, snapshot);
+        assert_eq!(Ok(snapshot.clone()), Message::parse(&snapshot.serialize()));
+
+        for (text, message) in [
+            ("[]", "Invalid replication message: expected an object, found an array"),
+            ("{\"seq\": -1, \"patch\": []}", "Invalid replication message: the seq is the number -1"),
+            ("{\"seq\": 1}", "Invalid replication message: expected either a snapshot or a patch"),
+            ("{\"seq\": 1, \"patch\": {}}", "Expected an array of patch operations, found an object"),
+        ] {
+            assert_eq!(message, Message::parse(text).unwrap_err().message());
+        }
+    }
+
+    #[test]
+    fn test_numbers_are_replicated_as_written() {
+        let mut producer = Producer::new(json!("{\"id\": 12345678901234567891}"));
+        let mut consumer = Consumer::new();
+        consumer.receive(&producer.snapshot());
+        let message = producer.update(json!("{\"id\": 12345678901234567892}")).unwrap();
+        assert_eq!(Status::Applied, consumer.receive(&Message::parse(&message.serialize()).unwrap()));
+        assert_eq!(Some("{\"id\":12345678901234567892}".to_string()), consumer.document().map(|d| d.serialize()));
+    }
+}
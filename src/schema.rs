@@ -0,0 +1,1477 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Validation of JSON data-structures against a [JSON Schema].
+//!
+//! A [`Schema`] is compiled once from a schema document and can then validate any number of
+//! values. The supported keywords are `type`, `enum`, `const`, `minimum`, `maximum`,
+//! `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`, `minLength`, `maxLength`, `format`,
+//! `properties`, `required`, `additionalProperties`, `minProperties`, `maxProperties`,
+//! `items`, `minItems`, `maxItems`, `uniqueItems`, `allOf`, `anyOf`, `oneOf` and `not`, as well
+//! as the boolean schemas `true` and `false`. Annotations and unknown keywords are ignored;
+//! `pattern` and `patternProperties` are rejected rather than silently ignored. The
+//! `default` annotation is used by [`apply_defaults`] to fill in missing properties, and
+//! [`coerce`] converts values to the types a schema demands.
+//!
+//! Strings with a `format` are checked by the validators of a [`Formats`] registry, to which
+//! custom formats can be added; strings of formats without a validator are not checked.
+//!
+//! `$ref` may point into the schema itself, or, relative to the nearest `$id`, into other
+//! documents which [`Schema::compile_with`] loads through a [`Resolver`] such as
+//! [`FileResolver`]. [`bundle`] inlines those documents to produce a standalone schema.
+//!
+//! [JSON Schema]:https://json-schema.org/
+//!
+//! ```
+//! # use json::json;
+//! # use json::schema::Schema;
+//! let schema = Schema::compile(&json!("{
+//!     \"type\": \"object\",
+//!     \"properties\": {\"port\": {\"type\": \"integer\", \"minimum\": 1, \"maximum\": 65535}},
+//!     \"required\": [\"port\"]
+//! }")).unwrap();
+//!
+//! assert!(schema.validate(&json!("{\"port\": 8080}")).is_ok());
+//! assert_eq!("Expected at most 65535, found the number 70000 at /port",
+//!            schema.validate(&json!("{\"port\": 70000}")).unwrap_err().message());
+//! assert_eq!("Missing required property port at the root",
+//!            schema.validate(&json!("{}")).unwrap_err().message());
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, Weak};
+
+use crate::coerce::{to_bool, to_number, to_string, Mode};
+use crate::convert::describe;
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::format;
+use crate::parser::parse;
+use crate::pointer::location;
+use crate::transform::push_token;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SchemaType {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    Integer,
+    String,
+}
+
+impl SchemaType {
+    fn parse(name: &str) -> Option<SchemaType> {
+        Some(match name {
+            "null" => SchemaType::Null,
+            "boolean" => SchemaType::Boolean,
+            "object" => SchemaType::Object,
+            "array" => SchemaType::Array,
+            "number" => SchemaType::Number,
+            "integer" => SchemaType::Integer,
+            "string" => SchemaType::String,
+            _ => return None
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SchemaType::Null => "null",
+            SchemaType::Boolean => "boolean",
+            SchemaType::Object => "object",
+            SchemaType::Array => "array",
+            SchemaType::Number => "number",
+            SchemaType::Integer => "integer",
+            SchemaType::String => "string",
+        }
+    }
+
+    fn accepts(self, value: &JValue) -> bool {
+        match (self, value) {
+            (SchemaType::Null, JValue::Null) => true,
+            (SchemaType::Boolean, JValue::Boolean(_)) => true,
+            (SchemaType::Object, JValue::Object(_)) => true,
+            (SchemaType::Array, JValue::Array(_)) => true,
+            (SchemaType::Number, JValue::Number(_)) => true,
+            (SchemaType::Integer, JValue::Number(n)) => n.get_f64_value().fract() == 0.0,
+            (SchemaType::String, JValue::String(_)) => true,
+            _ => false
+        }
+    }
+}
+
+type Validator = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct Format {
+    pub(crate) name: String,
+    pub(crate) validator: Validator,
+}
+
+impl fmt::Debug for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Format").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+/// The validators of the strings of the `format` keyword by format name. Strings of a format
+/// without a validator are not checked, as the JSON Schema specification recommends.
+///
+/// [`Formats::new`] has validators for `date-time`, `date`, `time`, `uuid`, `email`,
+/// `hostname`, `ipv4`, `ipv6` and `uri` from [`format`](crate::format), and further formats
+/// are added with [`Formats::with_format`].
+///
+/// ```
+/// # use json::json;
+/// # use json::schema::{CompileOptions, Formats, Schema};
+/// let formats = Formats::new().with_format("aws-arn", |s| s.starts_with("arn:") && s.split(':').count() >= 6);
+/// let schema = Schema::compile_with_options(&json!("{\"format\": \"aws-arn\"}"),
+///                                           &CompileOptions { formats: &formats, ..CompileOptions::default() }).unwrap();
+/// assert!(schema.is_valid(&json!("\"arn:aws:s3:::my-bucket\"")));
+/// assert_eq!("Expected an aws-arn, found the string \"my-bucket\" at the root",
+///            schema.validate(&json!("\"my-bucket\"")).unwrap_err().message());
+/// ```
+#[derive(Clone)]
+pub struct Formats {
+    validators: HashMap<String, Validator>,
+}
+
+impl Formats {
+    /// Returns the registry of the built-in formats
+    pub fn new() -> Formats {
+        let mut formats = Formats { validators: HashMap::new() };
+        formats.register("date-time", format::is_date_time);
+        formats.register("date", format::is_date);
+        formats.register("time", format::is_time);
+        formats.register("uuid", format::is_uuid);
+        formats.register("email", format::is_email);
+        formats.register("hostname", format::is_hostname);
+        formats.register("ipv4", format::is_ipv4);
+        formats.register("ipv6", format::is_ipv6);
+        formats.register("uri", format::is_uri);
+        formats
+    }
+
+    /// Validates the strings of the format `name` with `validator`, replacing the validator the
+    /// format had
+    pub fn with_format<F: Fn(&str) -> bool + Send + Sync + 'static>(mut self, name: &str, validator: F) -> Self {
+        self.register(name, validator);
+        self
+    }
+
+    /// Validates the strings of the format `name` with `validator` like [`Formats::with_format`]
+    pub fn register<F: Fn(&str) -> bool + Send + Sync + 'static>(&mut self, name: &str, validator: F) {
+        self.validators.insert(name.to_string(), Arc::new(validator));
+    }
+
+    /// Whether the strings of the format `name` are validated
+    pub fn contains(&self, name: &str) -> bool {
+        self.validators.contains_key(name)
+    }
+
+    fn get(&self, name: &str) -> Option<Format> {
+        self.validators.get(name).map(|validator| Format { name: name.to_string(), validator: validator.clone() })
+    }
+}
+
+impl Default for Formats {
+    fn default() -> Self {
+        Formats::new()
+    }
+}
+
+impl fmt::Debug for Formats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.validators.keys().collect();
+        names.sort();
+        f.debug_struct("Formats").field("names", &names).finish()
+    }
+}
+
+/// Options for [`Schema::compile_with_options`]
+#[derive(Clone, Copy)]
+pub struct CompileOptions<'a> {
+    /// Loads the documents that `$ref`s point to outside of the schema
+    pub resolver: &'a dyn Resolver,
+    /// The validators of the `format` keyword
+    pub formats: &'a Formats,
+}
+
+impl Default for CompileOptions<'_> {
+    fn default() -> Self {
+        static FORMATS: OnceLock<Formats> = OnceLock::new();
+        CompileOptions { resolver: &no_resolver, formats: FORMATS.get_or_init(Formats::new) }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Rules {
+    pub(crate) types: Option<Vec<SchemaType>>,
+    pub(crate) enum_values: Option<Vec<JValue>>,
+    pub(crate) const_value: Option<JValue>,
+    pub(crate) default: Option<JValue>,
+    pub(crate) minimum: Option<f64>,
+    pub(crate) maximum: Option<f64>,
+    pub(crate) exclusive_minimum: Option<f64>,
+    pub(crate) exclusive_maximum: Option<f64>,
+    pub(crate) multiple_of: Option<f64>,
+    pub(crate) min_length: Option<usize>,
+    pub(crate) max_length: Option<usize>,
+    pub(crate) format: Option<Format>,
+    pub(crate) properties: Vec<(String, Node)>,
+    pub(crate) required: Vec<String>,
+    pub(crate) additional_properties: Option<Node>,
+    pub(crate) min_properties: Option<usize>,
+    pub(crate) max_properties: Option<usize>,
+    pub(crate) items: Option<Node>,
+    pub(crate) min_items: Option<usize>,
+    pub(crate) max_items: Option<usize>,
+    pub(crate) unique_items: bool,
+    pub(crate) all_of: Vec<Node>,
+    pub(crate) any_of: Vec<Node>,
+    pub(crate) one_of: Vec<Node>,
+    pub(crate) not: Option<Node>,
+    pub(crate) reference: Option<Reference>,
+}
+
+/// The target of a `$ref`. Targets are owned by their [`Schema`], so that a schema can refer to
+/// itself.
+#[derive(Debug, Clone)]
+pub(crate) struct Reference {
+    index: usize,
+    target: Weak<OnceLock<Node>>,
+}
+
+impl Reference {
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&Node) -> R) -> R {
+        let target = self.target.upgrade().expect("the schema owns the targets of its references");
+        f(target.get().expect("the targets of references are compiled with the schema"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Node {
+    Bool(bool),
+    Rules(Box<Rules>),
+}
+
+/// A compiled JSON Schema
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub(crate) root: Node,
+    /// Owns the targets of references, which the references only point to weakly
+    _targets: Vec<Arc<OnceLock<Node>>>,
+}
+
+impl Schema {
+    /// Compiles a schema document. Fails with [`ErrorKind::InvalidSchema`] if a keyword has
+    /// a value of the wrong type or is not supported, and with [`ErrorKind::Cycle`] if a
+    /// `$ref` refers to itself without a member or an item in between, like
+    /// `{"$ref": "#"}`. A `$ref` may only point into the schema itself; see
+    /// [`Schema::compile_with`] for references to other documents.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::schema::Schema;
+    /// let tree = Schema::compile(&json!("{
+    ///     \"$defs\": {\"name\": {\"type\": \"string\"}},
+    ///     \"properties\": {\"name\": {\"$ref\": \"#/$defs/name\"}, \"children\": {\"items\": {\"$ref\": \"#\"}}}
+    /// }")).unwrap();
+    /// assert!(tree.is_valid(&json!("{\"name\": \"a\", \"children\": [{\"name\": \"b\", \"children\": []}]}")));
+    /// assert_eq!("Expected string, found the number 1 at /children/0/name",
+    ///            tree.validate(&json!("{\"children\": [{\"name\": 1}]}")).unwrap_err().message());
+    /// ```
+    pub fn compile(schema: &JValue) -> Result<Schema, JsonError> {
+        Schema::compile_with_options(schema, &CompileOptions::default())
+    }
+
+    /// Compiles a schema document like [`Schema::compile`], loading the documents that `$ref`s
+    /// point to outside of it with `resolver`. References are resolved against the `$id` of
+    /// the schema containing them, and each document is loaded once.
+    pub fn compile_with(schema: &JValue, resolver: &dyn Resolver) -> Result<Schema, JsonError> {
+        Schema::compile_with_options(schema, &CompileOptions { resolver, ..CompileOptions::default() })
+    }
+
+    /// Compiles a schema document like [`Schema::compile_with`], with the resolver and the
+    /// [`Formats`] of `options`
+    pub fn compile_with_options(schema: &JValue, options: &CompileOptions) -> Result<Schema, JsonError> {
+        let mut compiler = Compiler {
+            resolver: options.resolver,
+            formats: options.formats,
+            documents: HashMap::new(),
+            indices: HashMap::new(),
+            targets: Vec::new(),
+            pending: Vec::new(),
+        };
+        let root = compiler.compile(schema, &mut String::new(), "")?;
+        compiler.resolve_pending()?;
+        compiler.check_cycles()?;
+        Ok(Schema { root, _targets: compiler.targets })
+    }
+
+    /// Validates `value`, failing with [`ErrorKind::Validation`] on the first violation found.
+    /// The error message names the JSON Pointer of the offending value.
+    pub fn validate(&self, value: &JValue) -> Result<(), JsonError> {
+        validate(&self.root, value, &mut String::new(), &mut None)
+    }
+
+    /// Validates `value` like [`Schema::validate`], but goes on after a violation to report all
+    /// of them in one pass. A value may violate several keywords, each giving an error.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::schema::Schema;
+    /// let schema = Schema::compile(&json!("{\"required\": [\"name\"], \"properties\": {\"port\": {\"type\": \"integer\"},\
+    ///                                       \"tags\": {\"items\": {\"maxLength\": 3}}}}")).unwrap();
+    /// let errors = schema.validate_all(&json!("{\"port\": \"80\", \"tags\": [\"a\", \"long\", \"b\", \"longer\"]}")).unwrap_err();
+    /// let messages: Vec<&str> = errors.iter().map(|e| e.message()).collect();
+    /// assert_eq!(vec!["Missing required property name at the root",
+    ///                 "Expected integer, found the string \"80\" at /port",
+    ///                 "Expected at most 3 characters, found 4 at /tags/1",
+    ///                 "Expected at most 3 characters, found 6 at /tags/3"], messages);
+    /// ```
+    pub fn validate_all(&self, value: &JValue) -> Result<(), Vec<JsonError>> {
+        let mut errors = Vec::new();
+        let _ = validate(&self.root, value, &mut String::new(), &mut Some(&mut errors));
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Returns `true` if `value` is valid against the schema
+    pub fn is_valid(&self, value: &JValue) -> bool {
+        self.validate(value).is_ok()
+    }
+}
+
+/// Fills in the missing properties of objects whose schemas have a `default`, recursively: a
+/// default is itself filled in, and the members of `additionalProperties`, the items of
+/// `items` and the subschemas of `allOf` are filled in too. The branches of `anyOf` and
+/// `oneOf` are not used, as it is not known which of them applies.
+///
+/// ```
+/// # use json::json;
+/// # use json::schema::{apply_defaults, Schema};
+/// let schema = Schema::compile(&json!("{
+///     \"properties\": {
+///         \"port\": {\"type\": \"integer\", \"default\": 80},
+///         \"tls\": {\"default\": {}, \"properties\": {\"enabled\": {\"default\": false}}}
+///     }
+/// }")).unwrap();
+/// let mut config = json!("{\"port\": 8080}");
+/// apply_defaults(&mut config, &schema);
+/// schema.validate(&config).unwrap();
+/// assert_eq!(json!("{\"port\": 8080, \"tls\": {\"enabled\": false}}"), config);
+/// ```
+pub fn apply_defaults(value: &mut JValue, schema: &Schema) {
+    fill_defaults(&schema.root, value);
+}
+
+fn fill_defaults(node: &Node, value: &mut JValue) {
+    let rules = match node {
+        Node::Rules(rules) => rules,
+        Node::Bool(_) => return
+    };
+    for schema in &rules.all_of {
+        fill_defaults(schema, value);
+    }
+    if let Some(reference) = &rules.reference {
+        reference.with(|node| fill_defaults(node, value));
+    }
+    match value {
+        JValue::Object(o) => {
+            for (name, schema) in &rules.properties {
+                if let (None, Node::Rules(property)) = (o.get(name), schema) {
+                    if let Some(default) = &property.default {
+                        o.insert(name.clone(), default.clone());
+                    }
+                }
+                if let Some(member) = o.get_mut(name) {
+                    fill_defaults(schema, member);
+                }
+            }
+            if let Some(schema) = &rules.additional_properties {
+                for (key, member) in o.iter_mut() {
+                    if rules.properties.binary_search_by(|(name, _)| name.cmp(key)).is_err() {
+                        fill_defaults(schema, member);
+                    }
+                }
+            }
+        }
+        JValue::Array(a) => if let Some(schema) = &rules.items {
+            for item in a {
+                fill_defaults(schema, item);
+            }
+        },
+        _ => ()
+    }
+}
+
+/// A conversion made by [`coerce`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coercion {
+    pub pointer: String,
+    /// The value before the conversion
+    pub from: JValue,
+    /// The value after the conversion
+    pub to: JValue,
+}
+
+/// Converts values to the `type` their schema demands, for documents whose values all start out
+/// as strings, like form data and environment variables. Returns the conversions made.
+///
+/// A value of none of the types is converted to the first type it can be converted to, following
+/// [`Mode::Strict`] of [`coerce`](crate::coerce): a string containing a JSON number becomes a
+/// number, or an integer if it has no fraction, `"true"` and `"false"` become booleans, `"null"`
+/// becomes `null`, a number or a boolean becomes a string, and any value but an array becomes an
+/// array of that one value. A value that can not be converted is left for validation to report.
+/// The members of objects are converted against `properties` and `additionalProperties`, the
+/// items of arrays against `items`, and every value against the subschemas of `allOf`.
+///
+/// ```
+/// # use json::json;
+/// # use json::schema::{coerce, Schema};
+/// let schema = Schema::compile(&json!("{\"properties\": {
+///     \"port\": {\"type\": \"integer\"},
+///     \"debug\": {\"type\": \"boolean\"},
+///     \"hosts\": {\"type\": \"array\", \"items\": {\"type\": \"string\"}}
+/// }}")).unwrap();
+/// let mut form = json!("{\"port\": \"8080\", \"debug\": \"true\", \"hosts\": \"example.com\"}");
+/// let coercions = coerce(&mut form, &schema);
+///
+/// assert_eq!(json!("{\"port\": 8080, \"debug\": true, \"hosts\": [\"example.com\"]}"), form);
+/// assert_eq!(vec!["/debug", "/hosts", "/port"], coercions.iter().map(|c| c.pointer.as_str()).collect::<Vec<_>>());
+/// ```
+pub fn coerce(value: &mut JValue, schema: &Schema) -> Vec<Coercion> {
+    let mut coercions = Vec::new();
+    coerce_at(&schema.root, value, &mut String::new(), &mut coercions);
+    coercions
+}
+
+fn coerce_at(node: &Node, value: &mut JValue, path: &mut String, coercions: &mut Vec<Coercion>) {
+    let rules = match node {
+        Node::Rules(rules) => rules,
+        Node::Bool(_) => return
+    };
+    if let Some(types) = &rules.types {
+        if !types.iter().any(|t| t.accepts(value)) {
+            if let Some(converted) = types.iter().find_map(|t| convert(value, *t)) {
+                let from = std::mem::replace(value, converted);
+                coercions.push(Coercion { pointer: path.clone(), from, to: value.clone() });
+            }
+        }
+    }
+    for schema in &rules.all_of {
+        coerce_at(schema, value, path, coercions);
+    }
+    if let Some(reference) = &rules.reference {
+        reference.with(|node| coerce_at(node, value, path, coercions));
+    }
+    let len = path.len();
+    match value {
+        JValue::Object(o) => {
+            let mut keys: Vec<String> = o.iter().map(|(k, _)| k.clone()).collect();
+            keys.sort_unstable();
+            for key in keys {
+                let schema = match rules.properties.binary_search_by(|(name, _)| name.cmp(&key)) {
+                    Ok(i) => &rules.properties[i].1,
+                    Err(_) => match &rules.additional_properties {
+                        Some(schema) => schema,
+                        None => continue
+                    }
+                };
+                push_token(path, &key);
+                coerce_at(schema, o.get_mut(&key).unwrap(), path, coercions);
+                path.truncate(len);
+            }
+        }
+        JValue::Array(a) => if let Some(schema) = &rules.items {
+            for (i, item) in a.iter_mut().enumerate() {
+                push_token(path, &i.to_string());
+                coerce_at(schema, item, path, coercions);
+                path.truncate(len);
+            }
+        },
+        _ => ()
+    }
+}
+
+/// Converts a value to a type, or returns `None` if it can not be
+fn convert(value: &JValue, schema_type: SchemaType) -> Option<JValue> {
+    let converted = match schema_type {
+        SchemaType::Number | SchemaType::Integer => JValue::Number(to_number(value, Mode::Strict).ok()?),
+        SchemaType::Boolean => JValue::Boolean(to_bool(value, Mode::Strict).ok()?),
+        SchemaType::String => JValue::String(JString::from(to_string(value, Mode::Strict).ok()?)),
+        SchemaType::Null => match value {
+            JValue::String(s) if s.as_str() == "null" => JValue::Null,
+            _ => return None
+        },
+        SchemaType::Array => JValue::Array(vec![value.clone()]),
+        SchemaType::Object => return None
+    };
+    Some(converted).filter(|converted| schema_type.accepts(converted))
+}
+
+fn invalid_schema(path: &str, message: &str) -> JsonError {
+    JsonError::new(ErrorKind::InvalidSchema, format!("{} at {}", message, location(path)))
+}
+
+/// Loads the documents that `$ref`s point to outside of the schema being compiled
+///
+/// ```
+/// # use json::json;
+/// # use json::error::{ErrorKind, JsonError};
+/// # use json::schema::Schema;
+/// let resolver = |uri: &str| match uri {
+///     "https://example.com/port.json" => Ok(json!("{\"type\": \"integer\", \"maximum\": 65535}")),
+///     _ => Err(JsonError::new(ErrorKind::NotFound, format!("Unknown schema {}", uri)))
+/// };
+/// let schema = Schema::compile_with(&json!("{
+///     \"$id\": \"https://example.com/config.json\",
+///     \"properties\": {\"port\": {\"$ref\": \"port.json\"}}
+/// }"), &resolver).unwrap();
+/// assert!(schema.is_valid(&json!("{\"port\": 80}")));
+/// assert!(!schema.is_valid(&json!("{\"port\": 80000}")));
+/// ```
+pub trait Resolver {
+    /// Returns the document at the absolute URI `uri`, which has no fragment
+    fn resolve(&self, uri: &str) -> Result<JValue, JsonError>;
+}
+
+impl<F: Fn(&str) -> Result<JValue, JsonError>> Resolver for F {
+    fn resolve(&self, uri: &str) -> Result<JValue, JsonError> {
+        self(uri)
+    }
+}
+
+/// A [`Resolver`] reading schema files. A relative URI is a path relative to the directory of
+/// the resolver, and a `file://` URI is an absolute path.
+#[derive(Debug, Clone)]
+pub struct FileResolver {
+    dir: PathBuf,
+}
+
+impl FileResolver {
+    /// Resolves relative URIs against `dir`
+    pub fn new<P: Into<PathBuf>>(dir: P) -> FileResolver {
+        FileResolver { dir: dir.into() }
+    }
+}
+
+impl Resolver for FileResolver {
+    fn resolve(&self, uri: &str) -> Result<JValue, JsonError> {
+        let path = match uri.strip_prefix("file://") {
+            Some(path) => PathBuf::from(percent_decode(path)),
+            None if has_scheme(uri) => return Err(JsonError::new(
+                ErrorKind::NotFound, format!("Can not resolve the schema {}, only files are resolved", uri))),
+            None => self.dir.join(percent_decode(uri))
+        };
+        let text = fs::read_to_string(&path)
+            .map_err(|e| JsonError::new(ErrorKind::Io, format!("Can not read {}: {}", path.display(), e)))?;
+        parse(&text)
+    }
+}
+
+fn no_resolver(uri: &str) -> Result<JValue, JsonError> {
+    Err(JsonError::new(ErrorKind::NotFound, format!("Can not resolve the schema {} without a resolver", uri)))
+}
+
+/// The id of the document `schema` is, resolved against `base`
+fn document_id(schema: &JValue, base: &str) -> String {
+    match schema {
+        JValue::Object(o) => match o.get(&"$id".to_string()) {
+            Some(JValue::String(id)) => without_fragment(&resolve_uri(base, id)).to_string(),
+            _ => base.to_string()
+        },
+        _ => base.to_string()
+    }
+}
+
+struct Compiler<'a> {
+    resolver: &'a dyn Resolver,
+    formats: &'a Formats,
+    /// The documents by URI, including the subschemas with an `$id`
+    documents: HashMap<String, JValue>,
+    /// The targets of references by URI with fragment, as indices of `targets`
+    indices: HashMap<String, usize>,
+    targets: Vec<Arc<OnceLock<Node>>>,
+    /// The targets to compile, with their document, fragment and the first `$ref` to them
+    pending: Vec<(usize, String, String, String)>,
+}
+
+impl Compiler<'_> {
+    fn compile(&mut self, schema: &JValue, path: &mut String, base: &str) -> Result<Node, JsonError> {
+        let object = match schema {
+            JValue::Boolean(b) => return Ok(Node::Bool(*b)),
+            JValue::Object(o) => o,
+            other => return Err(invalid_schema(path, &format!("Expected a schema, found {}", describe(other))))
+        };
+        let base = &document_id(schema, base);
+        if !self.documents.contains_key(base) {
+            self.documents.insert(base.clone(), schema.clone());
+        }
+        let len = path.len();
+        let mut rules = Rules::default();
+        for (keyword, value) in object {
+            push_token(path, keyword);
+            match keyword.as_str() {
+                "type" => rules.types = Some(compile_types(value, path)?),
+                "enum" => match value {
+                    JValue::Array(a) => rules.enum_values = Some(a.clone()),
+                    _ => return Err(invalid_schema(path, "Expected an array"))
+                },
+                "const" => rules.const_value = Some(value.clone()),
+                "default" => rules.default = Some(value.clone()),
+                "minimum" => rules.minimum = Some(number(value, path)?),
+                "maximum" => rules.maximum = Some(number(value, path)?),
+                "exclusiveMinimum" => rules.exclusive_minimum = Some(number(value, path)?),
+                "exclusiveMaximum" => rules.exclusive_maximum = Some(number(value, path)?),
+                "multipleOf" => match number(value, path)? {
+                    m if m > 0.0 => rules.multiple_of = Some(m),
+                    _ => return Err(invalid_schema(path, "Expected a positive number"))
+                },
+                "minLength" => rules.min_length = Some(count(value, path)?),
+                "maxLength" => rules.max_length = Some(count(value, path)?),
+                "format" => match value {
+                    JValue::String(name) => rules.format = self.formats.get(name),
+                    _ => return Err(invalid_schema(path, "Expected a string"))
+                },
+                "minProperties" => rules.min_properties = Some(count(value, path)?),
+                "maxProperties" => rules.max_properties = Some(count(value, path)?),
+                "minItems" => rules.min_items = Some(count(value, path)?),
+                "maxItems" => rules.max_items = Some(count(value, path)?),
+                "uniqueItems" => match value {
+                    JValue::Boolean(b) => rules.unique_items = *b,
+                    _ => return Err(invalid_schema(path, "Expected a boolean"))
+                },
+                "properties" => match value {
+                    JValue::Object(properties) => rules.properties = self.compile_properties(properties, path, base)?,
+                    _ => return Err(invalid_schema(path, "Expected an object"))
+                },
+                "required" => rules.required = strings(value, path)?,
+                "additionalProperties" => rules.additional_properties = Some(self.compile(value, path, base)?),
+                "items" => rules.items = Some(self.compile(value, path, base)?),
+                "allOf" => rules.all_of = self.compile_all(value, path, base)?,
+                "anyOf" => rules.any_of = self.compile_all(value, path, base)?,
+                "oneOf" => rules.one_of = self.compile_all(value, path, base)?,
+                "not" => rules.not = Some(self.compile(value, path, base)?),
+                "$ref" => match value {
+                    JValue::String(reference) => rules.reference = Some(self.reference(reference, path, base)?),
+                    _ => return Err(invalid_schema(path, "Expected a string"))
+                },
+                "$id" if !matches!(value, JValue::String(_)) => return Err(invalid_schema(path, "Expected a string")),
+                "pattern" | "patternProperties" =>
+                    return Err(invalid_schema(path, &format!("The keyword {} is not supported", keyword))),
+                _ => ()
+            }
+            path.truncate(len);
+        }
+        Ok(Node::Rules(Box::new(rules)))
+    }
+
+    fn compile_properties(&mut self, properties: &JObject, path: &mut String, base: &str)
+                          -> Result<Vec<(String, Node)>, JsonError> {
+        let len = path.len();
+        let mut result = Vec::with_capacity(properties.len());
+        for (name, schema) in properties {
+            push_token(path, name);
+            result.push((name.clone(), self.compile(schema, path, base)?));
+            path.truncate(len);
+        }
+        result.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(result)
+    }
+
+    fn compile_all(&mut self, value: &JValue, path: &mut String, base: &str) -> Result<Vec<Node>, JsonError> {
+        match value {
+            JValue::Array(a) if !a.is_empty() => {
+                let len = path.len();
+                let mut result = Vec::with_capacity(a.len());
+                for (i, schema) in a.iter().enumerate() {
+                    push_token(path, &i.to_string());
+                    result.push(self.compile(schema, path, base)?);
+                    path.truncate(len);
+                }
+                Ok(result)
+            }
+            _ => Err(invalid_schema(path, "Expected a non-empty array of schemas"))
+        }
+    }
+
+    /// Returns the reference to the target of `$ref`, which is compiled later
+    fn reference(&mut self, reference: &str, path: &str, base: &str) -> Result<Reference, JsonError> {
+        let uri = resolve_uri(base, reference);
+        let document = without_fragment(&uri).to_string();
+        let fragment = percent_decode(uri.get(document.len() + 1..).unwrap_or(""));
+        if !fragment.is_empty() && !fragment.starts_with('/') {
+            return Err(invalid_schema(path, "Only JSON Pointer fragments are supported"));
+        }
+        let uri = format!("{}#{}", document, fragment);
+        let index = match self.indices.get(&uri) {
+            Some(index) => *index,
+            None => {
+                let index = self.targets.len();
+                self.targets.push(Arc::new(OnceLock::new()));
+                self.indices.insert(uri.clone(), index);
+                self.pending.push((index, document, fragment, path.to_string()));
+                index
+            }
+        };
+        Ok(Reference { index, target: Arc::downgrade(&self.targets[index]) })
+    }
+
+    /// Compiles the targets of the references, loading the documents they are in
+    fn resolve_pending(&mut self) -> Result<(), JsonError> {
+        while let Some((index, document, fragment, path)) = self.pending.pop() {
+            if !self.documents.contains_key(&document) {
+                let loaded = self.resolver.resolve(&document)?;
+                self.documents.insert(document.clone(), loaded);
+            }
+            let target = self.documents[&document].pointer(&fragment).cloned().ok_or_else(|| invalid_schema(
+                &path, &format!("The reference {}#{} points to no schema", document, fragment)))?;
+            let node = self.compile(&target, &mut fragment.clone(), &document)?;
+            self.targets[index].set(node).expect("every target is compiled once");
+        }
+        Ok(())
+    }
+
+    /// Fails if a target refers to itself without validating a member or an item in between,
+    /// which would make validation recurse forever
+    fn check_cycles(&self) -> Result<(), JsonError> {
+        fn immediate(node: &Node, out: &mut Vec<usize>) {
+            if let Node::Rules(rules) = node {
+                out.extend(rules.reference.as_ref().map(|reference| reference.index));
+                for schema in rules.all_of.iter().chain(&rules.any_of).chain(&rules.one_of).chain(&rules.not) {
+                    immediate(schema, out);
+                }
+            }
+        }
+        let edges: Vec<Vec<usize>> = self.targets.iter().map(|target| {
+            let mut out = Vec::new();
+            immediate(target.get().unwrap(), &mut out);
+            out
+        }).collect();
+        // 0 unvisited, 1 on the current path, 2 done
+        let mut state = vec![0u8; edges.len()];
+        fn visit(i: usize, edges: &[Vec<usize>], state: &mut [u8]) -> Option<usize> {
+            state[i] = 1;
+            for &next in &edges[i] {
+                match state[next] {
+                    1 => return Some(next),
+                    0 => if let Some(cycle) = visit(next, edges, state) {
+                        return Some(cycle);
+                    },
+                    _ => ()
+                }
+            }
+            state[i] = 2;
+            None
+        }
+        for i in 0..edges.len() {
+            if state[i] == 0 {
+                if let Some(cycle) = visit(i, &edges, &mut state) {
+                    let uri = self.indices.iter().find(|(_, index)| **index == cycle).unwrap().0;
+                    return Err(JsonError::new(ErrorKind::Cycle, format!("The reference {} refers to itself", uri)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inlines the documents that the `$ref`s of `schema` point to, loaded with `resolver`, into a
+/// standalone schema which [`Schema::compile`] compiles without a resolver.
+///
+/// Every other document is copied, without its `$id`, into the `$defs` of the root under a
+/// name taken from its URI, and the references to it are rewritten to point there. References
+/// within the schema are kept as they are.
+///
+/// ```
+/// # use json::json;
+/// # use json::error::{ErrorKind, JsonError};
+/// # use json::schema::{bundle, Schema};
+/// let resolver = |uri: &str| match uri {
+///     "types/port.json" => Ok(json!("{\"$defs\": {\"max\": {\"maximum\": 65535}}, \"allOf\": [{\"$ref\": \"#/$defs/max\"}]}")),
+///     _ => Err(JsonError::new(ErrorKind::NotFound, format!("Unknown schema {}", uri)))
+/// };
+/// let bundled = bundle(&json!("{\"properties\": {\"port\": {\"$ref\": \"types/port.json\"}}}"), &resolver).unwrap();
+/// assert_eq!(Some(&json!("\"#/$defs/port\"")), bundled.pointer("/properties/port/$ref"));
+/// assert_eq!(Some(&json!("\"#/$defs/port/$defs/max\"")), bundled.pointer("/$defs/port/allOf/0/$ref"));
+///
+/// let schema = Schema::compile(&bundled).unwrap();
+/// assert!(!schema.is_valid(&json!("{\"port\": 80000}")));
+/// ```
+pub fn bundle(schema: &JValue, resolver: &dyn Resolver) -> Result<JValue, JsonError> {
+    let root = document_id(schema, "");
+    let mut bundler = Bundler { root: root.clone(), names: HashMap::new(), used: HashSet::new(), pending: Vec::new() };
+    if let Some(JValue::Object(defs)) = schema.pointer("/$defs") {
+        bundler.used.extend(defs.iter().map(|(name, _)| name.clone()));
+    }
+    let mut bundled = schema.clone();
+    bundler.rewrite(&mut bundled, &root)?;
+
+    let mut defs = Vec::new();
+    while let Some((name, uri)) = bundler.pending.pop() {
+        let mut document = resolver.resolve(&uri)?;
+        if let JValue::Object(o) = &mut document {
+            o.remove(&"$id".to_string());
+        }
+        bundler.rewrite(&mut document, &uri)?;
+        defs.push((name, document));
+    }
+    if defs.is_empty() {
+        return Ok(bundled);
+    }
+    let object = match &mut bundled {
+        JValue::Object(o) => o,
+        _ => unreachable!("only objects have references")
+    };
+    if !matches!(object.get(&"$defs".to_string()), Some(JValue::Object(_))) {
+        object.insert("$defs".to_string(), JValue::Object(JObject::new()));
+    }
+    if let Some(JValue::Object(target)) = object.get_mut(&"$defs".to_string()) {
+        for (name, document) in defs {
+            target.insert(name, document);
+        }
+    }
+    Ok(bundled)
+}
+
+struct Bundler {
+    root: String,
+    /// The names in `$defs` of the documents by URI
+    names: HashMap<String, String>,
+    used: HashSet<String>,
+    pending: Vec<(String, String)>,
+}
+
+impl Bundler {
+    fn rewrite(&mut self, schema: &mut JValue, base: &str) -> Result<(), JsonError> {
+        let base = &document_id(schema, base);
+        match schema {
+            JValue::Object(o) => {
+                if let Some(JValue::String(reference)) = o.get(&"$ref".to_string()) {
+                    let rewritten = self.reference(reference, base);
+                    o.insert("$ref".to_string(), JValue::String(JString::from(rewritten.as_str())));
+                }
+                for (keyword, value) in o.iter_mut() {
+                    // These hold values rather than schemas
+                    if !matches!(keyword.as_str(), "enum" | "const" | "default" | "examples" | "$ref") {
+                        self.rewrite(value, base)?;
+                    }
+                }
+            }
+            JValue::Array(a) => for item in a {
+                self.rewrite(item, base)?;
+            },
+            _ => ()
+        }
+        Ok(())
+    }
+
+    fn reference(&mut self, reference: &str, base: &str) -> String {
+        let uri = resolve_uri(base, reference);
+        let document = without_fragment(&uri);
+        let fragment = &uri[document.len()..];
+        if document == self.root {
+            return if fragment.is_empty() { "#".to_string() } else { fragment.to_string() };
+        }
+        let name = match self.names.get(document) {
+            Some(name) => name.clone(),
+            None => {
+                let stem = document.rsplit('/').next().unwrap_or("");
+                let stem = stem.split('.').next().filter(|stem| !stem.is_empty()).unwrap_or("schema");
+                let mut name = stem.to_string();
+                let mut i = 1;
+                while self.used.contains(&name) {
+                    i += 1;
+                    name = format!("{}-{}", stem, i);
+                }
+                self.used.insert(name.clone());
+                self.names.insert(document.to_string(), name.clone());
+                self.pending.push((name.clone(), document.to_string()));
+                name
+            }
+        };
+        let mut pointer = "/$defs".to_string();
+        push_token(&mut pointer, &name);
+        let fragment = fragment.strip_prefix('#').unwrap_or("");
+        format!("#{}{}", percent_encode(&pointer), fragment)
+    }
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b'$' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte))
+        }
+    }
+    encoded
+}
+
+fn has_scheme(uri: &str) -> bool {
+    match uri.find(':') {
+        Some(i) => uri[..i].chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+            && uri.starts_with(|c: char| c.is_ascii_alphabetic()),
+        None => false
+    }
+}
+
+fn without_fragment(uri: &str) -> &str {
+    uri.split('#').next().unwrap()
+}
+
+/// Resolves a URI reference against a base URI, like RFC 3986 without queries
+fn resolve_uri(base: &str, reference: &str) -> String {
+    if has_scheme(reference) {
+        return reference.to_string();
+    }
+    let base = without_fragment(base);
+    if reference.starts_with('#') || reference.is_empty() {
+        return format!("{}{}", base, reference);
+    }
+    let (authority, path) = match base.find("://") {
+        Some(i) => match base[i + 3..].find('/') {
+            Some(j) => base.split_at(i + 3 + j),
+            None => (base, "")
+        },
+        None => ("", base)
+    };
+    let joined = if reference.starts_with('/') {
+        reference.to_string()
+    } else {
+        format!("{}{}", &path[..path.rfind('/').map_or(0, |i| i + 1)], reference)
+    };
+    let (joined, fragment) = match joined.find('#') {
+        Some(i) => joined.split_at(i),
+        None => (joined.as_str(), "")
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    let parts: Vec<&str> = joined.split('/').collect();
+    for (i, segment) in parts.iter().enumerate() {
+        match *segment {
+            "." => if i == parts.len() - 1 { segments.push("") },
+            ".." => {
+                if segments.len() > 1 || segments.first().is_some_and(|s| !s.is_empty()) {
+                    segments.pop();
+                }
+                if i == parts.len() - 1 {
+                    segments.push("");
+                }
+            }
+            segment => segments.push(segment)
+        }
+    }
+    format!("{}{}{}", authority, segments.join("/"), fragment)
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match text.get(i + 1..i + 3).filter(|_| bytes[i] == b'%').and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn compile_types(value: &JValue, path: &str) -> Result<Vec<SchemaType>, JsonError> {
+    let names = match value {
+        JValue::String(s) => vec![s.to_string()],
+        _ => strings(value, path)?
+    };
+    names.iter().map(|name| SchemaType::parse(name)
+        .ok_or_else(|| invalid_schema(path, &format!("Unknown type {}", name)))).collect()
+}
+
+fn number(value: &JValue, path: &str) -> Result<f64, JsonError> {
+    match value {
+        JValue::Number(n) => Ok(n.get_f64_value()),
+        _ => Err(invalid_schema(path, "Expected a number"))
+    }
+}
+
+fn count(value: &JValue, path: &str) -> Result<usize, JsonError> {
+    match value {
+        JValue::Number(n) if n.get_f64_value() >= 0.0 && n.get_f64_value().fract() == 0.0 =>
+            Ok(n.get_f64_value() as usize),
+        _ => Err(invalid_schema(path, "Expected a non-negative integer"))
+    }
+}
+
+fn strings(value: &JValue, path: &str) -> Result<Vec<String>, JsonError> {
+    match value {
+        JValue::Array(a) => a.iter().map(|v| match v {
+            JValue::String(s) => Ok(s.to_string()),
+            _ => Err(invalid_schema(path, "Expected an array of strings"))
+        }).collect(),
+        _ => Err(invalid_schema(path, "Expected an array of strings"))
+    }
+}
+
+fn violation(path: &str, message: String) -> JsonError {
+    JsonError::new(ErrorKind::Validation, format!("{} at {}", message, location(path)))
+}
+
+/// Where the violations go when validation goes on after the first, see [`Schema::validate_all`]
+type Errors<'a> = Option<&'a mut Vec<JsonError>>;
+
+/// Records `error` if validation goes on after violations, or returns it
+fn fail(errors: &mut Errors, error: JsonError) -> Result<(), JsonError> {
+    match errors {
+        Some(errors) => {
+            errors.push(error);
+            Ok(())
+        }
+        None => Err(error)
+    }
+}
+
+fn validate(node: &Node, value: &JValue, path: &mut String, errors: &mut Errors) -> Result<(), JsonError> {
+    let rules = match node {
+        Node::Bool(true) => return Ok(()),
+        Node::Bool(false) => return fail(errors, violation(path, format!("Found {}, which is not allowed", describe(value)))),
+        Node::Rules(rules) => rules
+    };
+    if let Some(types) = &rules.types {
+        if !types.iter().any(|t| t.accepts(value)) {
+            let names: Vec<&str> = types.iter().map(|t| t.name()).collect();
+            fail(errors, violation(path, format!("Expected {}, found {}", names.join(" or "), describe(value))))?;
+        }
+    }
+    if let Some(values) = &rules.enum_values {
+        if !values.contains(value) {
+            fail(errors, violation(path, format!("Expected one of the enumerated values, found {}",
+                                               describe(value))))?;
+        }
+    }
+    if let Some(expected) = &rules.const_value {
+        if expected != value {
+            fail(errors, violation(path, format!("Expected {}, found {}", describe(expected), describe(value))))?;
+        }
+    }
+    match value {
+        JValue::Number(n) => validate_number(rules, n.get_f64_value(), value, path, errors)?,
+        JValue::String(s) => {
+            let len = s.chars().count();
+            if rules.min_length.is_some_and(|min| len < min) {
+                fail(errors, violation(path, format!("Expected at least {} characters, found {}",
+                                                   rules.min_length.unwrap(), len)))?;
+            }
+            if rules.max_length.is_some_and(|max| len > max) {
+                fail(errors, violation(path, format!("Expected at most {} characters, found {}",
+                                                   rules.max_length.unwrap(), len)))?;
+            }
+            if let Some(format) = &rules.format {
+                if !(format.validator)(s) {
+                    // "a uri" but "an ipv4"
+                    let article = if format.name.starts_with(['a', 'e', 'i', 'o']) { "an" } else { "a" };
+                    fail(errors, violation(path, format!("Expected {} {}, found {}", article, format.name, describe(value))))?;
+                }
+            }
+        }
+        JValue::Object(o) => validate_object(rules, o, path, errors)?,
+        JValue::Array(a) => validate_array(rules, a, path, errors)?,
+        _ => ()
+    }
+    for schema in &rules.all_of {
+        validate(schema, value, path, errors)?;
+    }
+    if let Some(reference) = &rules.reference {
+        reference.with(|node| validate(node, value, path, errors))?;
+    }
+    if !rules.any_of.is_empty() && !rules.any_of.iter().any(|s| validate(s, value, path, &mut None).is_ok()) {
+        fail(errors, violation(path, format!("Expected a match of any of the schemas, found {}",
+                                           describe(value))))?;
+    }
+    if !rules.one_of.is_empty() {
+        let matches = rules.one_of.iter().filter(|s| validate(s, value, path, &mut None).is_ok()).count();
+        if matches != 1 {
+            fail(errors, violation(path, format!("Expected a match of exactly one of the schemas, found {}",
+                                               matches)))?;
+        }
+    }
+    if let Some(schema) = &rules.not {
+        if validate(schema, value, path, &mut None).is_ok() {
+            fail(errors, violation(path, format!("Found {}, which matches a forbidden schema", describe(value))))?;
+        }
+    }
+    Ok(())
+}
+
+type Comparison = fn(f64, f64) -> bool;
+
+fn validate_number(rules: &Rules, n: f64, value: &JValue, path: &str, errors: &mut Errors) -> Result<(), JsonError> {
+    let bounds: [(Option<f64>, Comparison, &str); 4] = [
+        (rules.minimum, |n, bound| n >= bound, "at least"),
+        (rules.maximum, |n, bound| n <= bound, "at most"),
+        (rules.exclusive_minimum, |n, bound| n > bound, "more than"),
+        (rules.exclusive_maximum, |n, bound| n < bound, "less than"),
+    ];
+    for (bound, satisfies, relation) in bounds.iter() {
+        if let Some(bound) = bound {
+            if !satisfies(n, *bound) {
+                fail(errors, violation(path, format!("Expected {} {}, found {}", relation, bound, describe(value))))?;
+            }
+        }
+    }
+    if let Some(m) = rules.multiple_of {
+        let quotient = n / m;
+        if (quotient - quotient.round()).abs() > 1e-9 {
+            fail(errors, violation(path, format!("Expected a multiple of {}, found {}", m, describe(value))))?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_object(rules: &Rules, object: &JObject, path: &mut String, errors: &mut Errors) -> Result<(), JsonError> {
+    if rules.min_properties.is_some_and(|min| object.len() < min) {
+        fail(errors, violation(path, format!("Expected at least {} properties, found {}",
+                                           rules.min_properties.unwrap(), object.len())))?;
+    }
+    if rules.max_properties.is_some_and(|max| object.len() > max) {
+        fail(errors, violation(path, format!("Expected at most {} properties, found {}",
+                                           rules.max_properties.unwrap(), object.len())))?;
+    }
+    for name in &rules.required {
+        if object.get(name).is_none() {
+            fail(errors, violation(path, format!("Missing required property {}", name)))?;
+        }
+    }
+    let len = path.len();
+    let mut keys: Vec<&String> = object.iter().map(|(k, _)| k).collect();
+    keys.sort_unstable();
+    for key in keys {
+        let schema = match rules.properties.binary_search_by(|(name, _)| name.cmp(key)) {
+            Ok(i) => &rules.properties[i].1,
+            Err(_) => match &rules.additional_properties {
+                Some(schema) => schema,
+                None => continue
+            }
+        };
+        push_token(path, key);
+        validate(schema, object.get(key).unwrap(), path, errors)?;
+        path.truncate(len);
+    }
+    Ok(())
+}
+
+fn validate_array(rules: &Rules, array: &[JValue], path: &mut String, errors: &mut Errors) -> Result<(), JsonError> {
+    if rules.min_items.is_some_and(|min| array.len() < min) {
+        fail(errors, violation(path, format!("Expected at least {} items, found {}",
+                                           rules.min_items.unwrap(), array.len())))?;
+    }
+    if rules.max_items.is_some_and(|max| array.len() > max) {
+        fail(errors, violation(path, format!("Expected at most {} items, found {}",
+                                           rules.max_items.unwrap(), array.len())))?;
+    }
+    if rules.unique_items {
+        let mut seen = HashSet::with_capacity(array.len());
+        if let Some(duplicate) = array.iter().find(|v| !seen.insert(*v)) {
+            fail(errors, violation(path, format!("Expected unique items, found {} twice", describe(duplicate))))?;
+        }
+    }
+    if let Some(schema) = &rules.items {
+        let len = path.len();
+        for (i, item) in array.iter().enumerate() {
+            push_token(path, &i.to_string());
+            validate(schema, item, path, errors)?;
+            path.truncate(len);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use std::cell::RefCell;
+    use crate::data_structures::JValue;
+    use crate::error::JsonError;
+    use crate::schema::{apply_defaults, bundle, coerce, resolve_uri, Coercion, CompileOptions, FileResolver, Formats, Schema};
+
+    fn error(schema: &str, value: &str) -> String {
+        let schema = Schema::compile(&crate::parser::parse(schema).unwrap()).unwrap();
+        schema.validate(&crate::parser::parse(value).unwrap()).unwrap_err().message().to_string()
+    }
+
+    fn valid(schema: &str, value: &str) -> bool {
+        let schema = Schema::compile(&crate::parser::parse(schema).unwrap()).unwrap();
+        schema.is_valid(&crate::parser::parse(value).unwrap())
+    }
+
+    #[test]
+    fn test_types() {
+        assert!(valid("{\"type\": \"integer\"}", "3.0"));
+        assert!(!valid("{\"type\": \"integer\"}", "3.5"));
+        assert!(valid("{\"type\": [\"string\", \"null\"]}", "null"));
+        assert_eq!("Expected string or null, found the boolean true at the root",
+                   error("{\"type\": [\"string\", \"null\"]}", "true"));
+        assert!(valid("true", "1"));
+        assert_eq!("Found the number 1, which is not allowed at the root", error("false", "1"));
+    }
+
+    #[test]
+    fn test_values() {
+        assert!(valid("{\"enum\": [1, \"a\"]}", "\"a\""));
+        assert!(!valid("{\"enum\": [1, \"a\"]}", "2"));
+        assert!(!valid("{\"const\": {\"a\": 1}}", "{\"a\": 2}"));
+        assert!(valid("{\"exclusiveMinimum\": 0, \"multipleOf\": 0.1}", "0.3"));
+        assert_eq!("Expected more than 0, found the number 0 at the root",
+                   error("{\"exclusiveMinimum\": 0}", "0"));
+        assert_eq!("Expected at most 2 characters, found 3 at the root",
+                   error("{\"maxLength\": 2}", "\"äöü\""));
+    }
+
+    #[test]
+    fn test_formats() {
+        assert!(valid("{\"format\": \"date-time\"}", "\"2024-02-29T12:00:00Z\""));
+        assert!(valid("{\"format\": \"date-time\"}", "5"));
+        assert!(valid("{\"format\": \"house-format\"}", "\"anything\""));
+        assert!(!valid("{\"format\": \"uuid\"}", "\"67e55044\""));
+        assert_eq!("Expected a date, found the string \"2023-02-29\" at the root",
+                   error("{\"format\": \"date\"}", "\"2023-02-29\""));
+        assert!(valid("{\"items\": {\"format\": \"ipv6\"}}", "[\"::1\", \"2001:db8::8a2e:370:7334\"]"));
+        assert!(!valid("{\"format\": \"uri\"}", "\"example.com\""));
+        assert_eq!("Expected an email, found the string \"a@\" at the root", error("{\"format\": \"email\"}", "\"a@\""));
+    }
+
+    #[test]
+    fn test_custom_formats() {
+        let mut formats = Formats::new()
+            .with_format("house-format", |s| s.starts_with("H-"))
+            .with_format("date", |s| s.len() == 4);
+        formats.register("upper", |s| s.chars().all(|c| c.is_ascii_uppercase()));
+        assert!(formats.contains("uuid") && formats.contains("upper") && !formats.contains("aws-arn"));
+        let options = CompileOptions { formats: &formats, ..CompileOptions::default() };
+        let schema = Schema::compile_with_options(&json!("{\"properties\": {\"a\": {\"format\": \"house-format\"},
+            \"b\": {\"format\": \"date\"}, \"c\": {\"format\": \"upper\"}, \"d\": {\"format\": \"uuid\"}}}"), &options).unwrap();
+        assert!(schema.is_valid(&json!("{\"a\": \"H-1\", \"b\": \"2024\", \"c\": \"ABC\"}")));
+        let errors = schema.validate_all(&json!("{\"a\": \"1\", \"b\": \"2024-01-01\", \"c\": \"abc\", \"d\": \"x\"}")).unwrap_err();
+        assert_eq!(4, errors.len());
+        assert!(valid("{\"format\": \"house-format\"}", "\"1\""));
+
+        // Compiled schemas keep their validators and can be shared between threads
+        drop(formats);
+        let handle = std::thread::spawn(move || schema.is_valid(&json!("{\"a\": \"H-2\"}")));
+        assert!(handle.join().unwrap());
+    }
+
+    #[test]
+    fn test_objects_and_arrays() {
+        let schema = "{\"properties\": {\"a\": {\"items\": {\"type\": \"number\"}, \"uniqueItems\": true}},
+                       \"additionalProperties\": false}";
+        assert!(valid(schema, "{\"a\": [1, 2]}"));
+        assert_eq!("Expected number, found the string \"x\" at /a/1", error(schema, "{\"a\": [1, \"x\"]}"));
+        assert_eq!("Expected unique items, found the number 1 twice at /a", error(schema, "{\"a\": [1, 1]}"));
+        assert_eq!("Found the number 1, which is not allowed at /b", error(schema, "{\"b\": 1}"));
+        assert_eq!("Expected at least 1 items, found 0 at the root", error("{\"minItems\": 1}", "[]"));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = "{\"oneOf\": [{\"type\": \"number\"}, {\"minimum\": 5}]}";
+        assert!(valid(schema, "3"));
+        assert!(valid(schema, "\"x\""));
+        assert!(!valid(schema, "7"));
+        assert!(valid("{\"anyOf\": [{\"type\": \"null\"}, {\"type\": \"array\"}]}", "[]"));
+        assert!(!valid("{\"allOf\": [{\"minimum\": 1}, {\"maximum\": 2}]}", "3"));
+        assert!(!valid("{\"not\": {\"type\": \"null\"}}", "null"));
+    }
+
+    #[test]
+    fn test_validate_all() {
+        let schema = Schema::compile(&json!("{\"type\": \"object\", \"minProperties\": 3, \"properties\": {\"a\":
+                                                {\"anyOf\": [{\"type\": \"null\"}, {\"minimum\": 5}], \"maximum\": 1}}}")).unwrap();
+        let errors = schema.validate_all(&json!("{\"a\": 3}")).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message()).collect();
+        assert_eq!(vec!["Expected at least 3 properties, found 1 at the root",
+                        "Expected at most 1, found the number 3 at /a",
+                        "Expected a match of any of the schemas, found the number 3 at /a"], messages);
+        assert!(errors.iter().all(|e| e.kind() == ErrorKind::Validation));
+        assert_eq!(Ok(()), schema.validate_all(&json!("{\"a\": null, \"b\": 1, \"c\": 2}")).map_err(|e| e.len()));
+        assert_eq!(1, schema.validate_all(&json!("[]")).unwrap_err().len());
+    }
+
+    #[test]
+    fn test_apply_defaults() {
+        let schema = Schema::compile(&json!("{
+            \"allOf\": [{\"properties\": {\"level\": {\"default\": \"info\"}}}],
+            \"properties\": {\"servers\": {\"items\": {\"properties\": {\"weight\": {\"default\": 1}}}}},
+            \"additionalProperties\": {\"properties\": {\"enabled\": {\"default\": true}}},
+            \"anyOf\": [{\"properties\": {\"unused\": {\"default\": 0}}}]
+        }")).unwrap();
+        let mut value = json!("{\"servers\": [{}, {\"weight\": 5}], \"cache\": {\"enabled\": null}, \"db\": {}}");
+        apply_defaults(&mut value, &schema);
+        assert_eq!(json!("{\"servers\": [{\"weight\": 1}, {\"weight\": 5}], \"cache\": {\"enabled\": null},
+                          \"db\": {\"enabled\": true}, \"level\": \"info\"}"),
+                   value);
+        let mut scalar = json!("3");
+        apply_defaults(&mut scalar, &schema);
+        assert_eq!(json!("3"), scalar);
+    }
+
+    #[test]
+    fn test_coerce() {
+        let schema = Schema::compile(&json!("{
+            \"properties\": {
+                \"ids\": {\"type\": \"array\", \"items\": {\"type\": \"integer\"}},
+                \"ratio\": {\"type\": [\"null\", \"number\"]},
+                \"name\": {\"type\": \"string\"},
+                \"count\": {\"type\": \"integer\"}
+            },
+            \"additionalProperties\": {\"type\": \"boolean\"},
+            \"allOf\": [{\"properties\": {\"name\": {\"maxLength\": 5}}}]
+        }")).unwrap();
+        let mut value = json!("{\"ids\": \"7\", \"ratio\": \"null\", \"name\": 42, \"count\": \"1.5\", \"flag\": \"false\",
+                               \"other\": \"yes\"}");
+        let coercions = coerce(&mut value, &schema);
+        assert_eq!(json!("{\"ids\": [7], \"ratio\": null, \"name\": \"42\", \"count\": \"1.5\", \"flag\": false,
+                          \"other\": \"yes\"}"), value);
+        assert_eq!(vec!["/flag", "/ids", "/ids/0", "/name", "/ratio"],
+                   coercions.iter().map(|c| c.pointer.as_str()).collect::<Vec<_>>());
+        assert_eq!(Coercion { pointer: "/ids".to_string(), from: json!("\"7\""), to: json!("[\"7\"]") }, coercions[1]);
+        assert_eq!(2, schema.validate_all(&value).unwrap_err().len());
+        assert!(coerce(&mut value, &schema).is_empty());
+    }
+
+    #[test]
+    fn test_local_references() {
+        let list = "{\"$defs\": {\"node\": {\"type\": \"object\", \"properties\": {\"value\": {\"type\": \"integer\"},
+                     \"next\": {\"$ref\": \"#/$defs/node\"}}}}, \"$ref\": \"#/$defs/node\"}";
+        assert!(valid(list, "{\"value\": 1, \"next\": {\"value\": 2, \"next\": {}}}"));
+        assert_eq!("Expected integer, found the string \"x\" at /next/next/value",
+                   error(list, "{\"next\": {\"next\": {\"value\": \"x\"}}}"));
+        assert!(valid("{\"$defs\": {\"a~b\": {\"const\": 1}}, \"items\": {\"$ref\": \"#/$defs/a~0b\"}}", "[1, 1]"));
+        assert!(!valid("{\"$defs\": {\"a b\": {\"const\": 1}}, \"items\": {\"$ref\": \"#/$defs/a%20b\"}}", "[2]"));
+
+        let compile = |s: &str| Schema::compile(&crate::parser::parse(s).unwrap()).unwrap_err();
+        let cycle = compile("{\"$defs\": {\"a\": {\"allOf\": [{\"$ref\": \"#/$defs/b\"}]}, \"b\": {\"$ref\": \"#/$defs/a\"}},
+                             \"$ref\": \"#/$defs/a\"}");
+        assert_eq!(ErrorKind::Cycle, cycle.kind());
+        assert_eq!(ErrorKind::Cycle, compile("{\"$ref\": \"#\"}").kind());
+        let missing = compile("{\"properties\": {\"a\": {\"$ref\": \"#/$defs/a\"}}}");
+        assert_eq!(ErrorKind::InvalidSchema, missing.kind());
+        assert_eq!("The reference #/$defs/a points to no schema at /properties/a/$ref", missing.message());
+        assert_eq!(ErrorKind::NotFound, compile("{\"$ref\": \"other.json\"}").kind());
+    }
+
+    #[test]
+    fn test_external_references() {
+        let loaded = RefCell::new(Vec::new());
+        let resolver = |uri: &str| -> Result<JValue, JsonError> {
+            loaded.borrow_mut().push(uri.to_string());
+            match uri {
+                "https://example.com/schemas/address.json" => Ok(json!("{\"properties\": {\"zip\": {\"$ref\": \"types.json#/$defs/zip\"}}}")),
+                "https://example.com/schemas/types.json" => Ok(json!("{\"$defs\": {\"zip\": {\"type\": \"string\", \"maxLength\": 5}}}")),
+                _ => Err(JsonError::new(ErrorKind::NotFound, format!("Unknown schema {}", uri)))
+            }
+        };
+        let document = json!("{
+            \"$id\": \"https://example.com/schemas/person.json\",
+            \"properties\": {\"home\": {\"$ref\": \"address.json\"}, \"work\": {\"$ref\": \"address.json\"},
+                             \"id\": {\"$ref\": \"types.json#/$defs/zip\"}}
+        }");
+        let schema = Schema::compile_with(&document, &resolver).unwrap();
+        loaded.borrow_mut().sort();
+        assert_eq!(vec!["https://example.com/schemas/address.json", "https://example.com/schemas/types.json"], *loaded.borrow());
+        assert!(schema.is_valid(&json!("{\"home\": {\"zip\": \"12345\"}, \"id\": \"1\"}")));
+        assert_eq!("Expected at most 5 characters, found 6 at /work/zip",
+                   schema.validate(&json!("{\"work\": {\"zip\": \"123456\"}}")).unwrap_err().message());
+
+        let bundled = bundle(&document, &resolver).unwrap();
+        assert_eq!(Some(&json!("\"#/$defs/address\"")), bundled.pointer("/properties/home/$ref"));
+        assert_eq!(Some(&json!("\"#/$defs/types/$defs/zip\"")), bundled.pointer("/$defs/address/properties/zip/$ref"));
+        let standalone = Schema::compile(&bundled).unwrap();
+        for value in ["{\"home\": {\"zip\": \"12345\"}}", "{\"work\": {\"zip\": \"123456\"}}", "{\"id\": 1}"] {
+            let value = crate::parser::parse(value).unwrap();
+            assert_eq!(schema.is_valid(&value), standalone.is_valid(&value));
+        }
+
+        let missing = Schema::compile_with(&json!("{\"$ref\": \"missing.json\"}"), &resolver).unwrap_err();
+        assert_eq!(ErrorKind::NotFound, missing.kind());
+    }
+
+    #[test]
+    fn test_bundle_names() {
+        let resolver = |uri: &str| -> Result<JValue, JsonError> {
+            Ok(crate::parser::parse(&format!("{{\"$id\": \"{}\", \"const\": \"{}\"}}", uri, uri)).unwrap())
+        };
+        let bundled = bundle(&json!("{\"$defs\": {\"a\": true}, \"anyOf\": [{\"$ref\": \"x/a.json\"}, {\"$ref\": \"y/a.json\"},
+                                     {\"$ref\": \"a b.json\"}, {\"$ref\": \"#/$defs/a\"}]}"), &resolver).unwrap();
+        assert_eq!(json!("[{\"$ref\": \"#/$defs/a-2\"}, {\"$ref\": \"#/$defs/a-3\"}, {\"$ref\": \"#/$defs/a%20b\"},
+                          {\"$ref\": \"#/$defs/a\"}]"), bundled.pointer("/anyOf").unwrap().clone());
+        assert_eq!(Some(&json!("{\"const\": \"y/a.json\"}")), bundled.pointer("/$defs/a-3"));
+        let schema = Schema::compile(&bundled).unwrap();
+        assert!(schema.is_valid(&json!("\"a b.json\"")));
+    }
+
+    #[test]
+    fn test_file_resolver() {
+        let dir = std::env::temp_dir().join(format!("json-schema-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("types")).unwrap();
+        std::fs::write(dir.join("types/port.json"), "{\"$ref\": \"../range.json\", \"type\": \"integer\"}").unwrap();
+        std::fs::write(dir.join("range.json"), "{\"minimum\": 1, \"maximum\": 65535}").unwrap();
+
+        let resolver = FileResolver::new(&dir);
+        let schema = Schema::compile_with(&json!("{\"items\": {\"$ref\": \"types/port.json\"}}"), &resolver).unwrap();
+        assert!(schema.is_valid(&json!("[1, 80]")));
+        assert!(!schema.is_valid(&json!("[0]")));
+        let absolute = format!("file://{}", dir.join("range.json").display());
+        let schema = Schema::compile_with(&crate::parser::parse(&format!("{{\"$ref\": \"{}\"}}", absolute)).unwrap(),
+                                          &resolver).unwrap();
+        assert!(!schema.is_valid(&json!("70000")));
+        let error = Schema::compile_with(&json!("{\"$ref\": \"missing.json\"}"), &resolver).unwrap_err();
+        assert_eq!(ErrorKind::Io, error.kind());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_uri() {
+        assert_eq!("https://a.com/x/c.json", resolve_uri("https://a.com/x/b.json", "c.json"));
+        assert_eq!("https://a.com/c.json#/d", resolve_uri("https://a.com/x/y/b.json", "../../c.json#/d"));
+        assert_eq!("https://a.com/c.json", resolve_uri("https://a.com/x/b.json", "/c.json"));
+        assert_eq!("https://a.com/x/b.json#/d", resolve_uri("https://a.com/x/b.json#/e", "#/d"));
+        assert_eq!("urn:other", resolve_uri("https://a.com/x/b.json", "urn:other"));
+        assert_eq!("types/c.json", resolve_uri("types/b.json", "./c.json"));
+        assert_eq!("c.json", resolve_uri("", "c.json"));
+    }
+
+    #[test]
+    fn test_invalid_schemas() {
+        let compile = |s: &str| Schema::compile(&crate::parser::parse(s).unwrap()).unwrap_err();
+        assert_eq!(ErrorKind::InvalidSchema, compile("{\"type\": \"float\"}").kind());
+        assert_eq!("Expected a non-negative integer at /properties/a/minLength",
+                   compile("{\"properties\": {\"a\": {\"minLength\": -1}}}").message());
+        assert_eq!("The keyword pattern is not supported at /pattern", compile("{\"pattern\": \"a+\"}").message());
+        assert!(Schema::compile(&json!("1")).is_err());
+    }
+}
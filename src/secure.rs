@@ -0,0 +1,200 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Views of a document limited to some of its subtrees.
+
+use crate::convert::FromJValue;
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::patch::Operation;
+use crate::pointer::{contains, location, parse_pointer};
+
+/// The subtrees a [`SecureView`] may read and write, as JSON Pointer prefixes. A prefix grants
+/// access to the value at it and to everything inside of it, and the prefix `""` grants access
+/// to the whole document.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Permissions {
+    read: Vec<String>,
+    write: Vec<String>,
+}
+
+impl Permissions {
+    /// Grants nothing
+    pub fn new() -> Permissions {
+        Permissions::default()
+    }
+
+    /// Allows reading the subtree at `prefix`
+    pub fn allow_read(mut self, prefix: &str) -> Result<Permissions, JsonError> {
+        parse_pointer(prefix)?;
+        self.read.push(prefix.to_string());
+        Ok(self)
+    }
+
+    /// Allows reading and writing the subtree at `prefix`
+    pub fn allow_write(mut self, prefix: &str) -> Result<Permissions, JsonError> {
+        parse_pointer(prefix)?;
+        self.read.push(prefix.to_string());
+        self.write.push(prefix.to_string());
+        Ok(self)
+    }
+
+    /// Whether the value at `pointer` may be read
+    pub fn can_read(&self, pointer: &str) -> bool {
+        self.read.iter().any(|prefix| contains(prefix, pointer))
+    }
+
+    /// Whether the value at `pointer` may be written
+    pub fn can_write(&self, pointer: &str) -> bool {
+        self.write.iter().any(|prefix| contains(prefix, pointer))
+    }
+}
+
+/// Access to a document restricted by [`Permissions`], to hand a plugin the parts of a shared
+/// configuration it needs. Reading or writing outside of the allowed subtrees fails with
+/// [`ErrorKind::PermissionDenied`], whether or not there is a value there.
+///
+/// ```
+/// # use json::json;
+/// # use json::error::ErrorKind;
+/// # use json::secure::{Permissions, SecureView};
+/// let mut config = json!("{\"plugins\": {\"cache\": {\"size\": 10}}, \"ui\": {\"theme\": \"dark\"}, \"secrets\": {}}");
+/// let permissions = Permissions::new().allow_write("/plugins/cache").unwrap().allow_read("/ui").unwrap();
+/// let mut view = SecureView::new(&mut config, permissions);
+///
+/// assert_eq!(Ok("dark".to_string()), view.get_as::<String>("/ui/theme"));
+/// view.set("/plugins/cache/size", json!("20")).unwrap();
+/// assert_eq!(ErrorKind::PermissionDenied, view.set("/ui/theme", json!("\"light\"")).unwrap_err().kind());
+/// assert_eq!(ErrorKind::PermissionDenied, view.get("/secrets").unwrap_err().kind());
+/// assert_eq!(ErrorKind::PermissionDenied, view.get("").unwrap_err().kind());
+/// assert_eq!(Some(&json!("20")), config.pointer("/plugins/cache/size"));
+/// ```
+#[derive(Debug)]
+pub struct SecureView<'a> {
+    document: &'a mut JValue,
+    permissions: Permissions,
+}
+
+impl<'a> SecureView<'a> {
+    /// Restricts the access to `document` to `permissions`
+    pub fn new(document: &'a mut JValue, permissions: Permissions) -> SecureView<'a> {
+        SecureView { document, permissions }
+    }
+
+    /// Returns the permissions of the view
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    /// Returns the value at the given JSON Pointer, or [`None`] if there is none
+    pub fn get(&self, pointer: &str) -> Result<Option<&JValue>, JsonError> {
+        self.check(pointer, self.permissions.can_read(pointer), "read")?;
+        Ok(self.document.pointer(pointer))
+    }
+
+    /// Converts the value at the given JSON Pointer to `T`, like [`JValue::get_path_as`]
+    pub fn get_as<T: FromJValue>(&self, pointer: &str) -> Result<T, JsonError> {
+        self.check(pointer, self.permissions.can_read(pointer), "read")?;
+        self.document.get_path_as(pointer)
+    }
+
+    /// Sets the value at the given JSON Pointer, returning the value it replaced. A missing
+    /// object member is added, and the array index `-` or the length of the array appends to
+    /// it. The parent of the target must exist.
+    pub fn set(&mut self, pointer: &str, value: JValue) -> Result<Option<JValue>, JsonError> {
+        self.check(pointer, self.permissions.can_write(pointer), "write")?;
+        if self.document.pointer(pointer).is_some() {
+            self.document.replace(pointer, value).map(Some)
+        } else {
+            self.document.apply_patch(&[Operation::Add { path: pointer.to_string(), value }]).map(|_| None)
+        }
+    }
+
+    /// Removes the value at the given JSON Pointer and returns it
+    pub fn remove(&mut self, pointer: &str) -> Result<JValue, JsonError> {
+        self.check(pointer, self.permissions.can_write(pointer), "write")?;
+        match self.document.apply_patch_with_inverse(&[Operation::Remove { path: pointer.to_string() }])?.pop() {
+            Some(Operation::Add { value, .. }) => Ok(value),
+            _ => unreachable!()
+        }
+    }
+
+    /// Applies a JSON Patch if every path it reads or writes is allowed. Otherwise nothing is
+    /// changed.
+    pub fn apply_patch(&mut self, operations: &[Operation]) -> Result<(), JsonError> {
+        for operation in operations {
+            match operation {
+                Operation::Test { path, .. } => self.check(path, self.permissions.can_read(path), "read")?,
+                Operation::Copy { from, .. } => self.check(from, self.permissions.can_read(from), "read")?,
+                Operation::Move { from, .. } => self.check(from, self.permissions.can_write(from), "write")?,
+                _ => {}
+            }
+            if !matches!(operation, Operation::Test { .. }) {
+                let path = operation.path();
+                self.check(path, self.permissions.can_write(path), "write")?;
+            }
+        }
+        self.document.apply_patch(operations)
+    }
+
+    fn check(&self, pointer: &str, allowed: bool, access: &str) -> Result<(), JsonError> {
+        parse_pointer(pointer)?;
+        if allowed {
+            Ok(())
+        } else {
+            Err(JsonError::new(ErrorKind::PermissionDenied, format!("Not allowed to {} {}", access, location(pointer))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::patch::parse_patch;
+    use crate::secure::{Permissions, SecureView};
+
+    #[test]
+    fn test_prefixes() {
+        let permissions = Permissions::new().allow_read("/a/b").unwrap().allow_write("/c~1d").unwrap();
+        assert!(permissions.can_read("/a/b") && permissions.can_read("/a/b/0") && permissions.can_read("/c~1d/e"));
+        assert!(!permissions.can_read("/a") && !permissions.can_read("/a/bc") && !permissions.can_read(""));
+        assert!(permissions.can_write("/c~1d") && !permissions.can_write("/a/b"));
+        assert!(Permissions::new().allow_write("").unwrap().can_write("/anything"));
+        assert_eq!(ErrorKind::InvalidPointer, Permissions::new().allow_read("a").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_writes() {
+        let mut document = json!("{\"own\": {\"list\": [1]}, \"other\": 1}");
+        let mut view = SecureView::new(&mut document, Permissions::new().allow_write("/own").unwrap());
+        assert_eq!(Ok(None), view.set("/own/list/-", json!("2")));
+        assert_eq!(Ok(json!("1")), view.remove("/own/list/0"));
+        assert_eq!(Ok(None), view.get("/own/none"));
+        assert_eq!("Not allowed to write /other", view.remove("/other").unwrap_err().message());
+        assert_eq!("Not allowed to write the root", view.set("", json!("{}")).unwrap_err().message());
+        assert_eq!(ErrorKind::NotFound, view.remove("/own/none").unwrap_err().kind());
+
+        let escape = parse_patch(&json!("[{\"op\": \"add\", \"path\": \"/own/x\", \"value\": 1},
+                                          {\"op\": \"move\", \"from\": \"/other\", \"path\": \"/own/y\"}]")).unwrap();
+        assert_eq!(ErrorKind::PermissionDenied, view.apply_patch(&escape).unwrap_err().kind());
+        let copy = parse_patch(&json!("[{\"op\": \"copy\", \"from\": \"/other\", \"path\": \"/own/y\"}]")).unwrap();
+        assert_eq!(ErrorKind::PermissionDenied, view.apply_patch(&copy).unwrap_err().kind());
+        view.apply_patch(&escape[..1]).unwrap();
+        assert_eq!(json!("{\"own\": {\"list\": [2], \"x\": 1}, \"other\": 1}"), document);
+    }
+}
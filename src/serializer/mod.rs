@@ -1,13 +1,25 @@
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::{array_index, parse_pointer};
+
 /// Serialize the implementing data-structure.
 pub trait Serialize {
     /// Creates a serialization of the implementing data-structure as a JSON string with
     /// minimal whitespace characters.
     fn serialize(&self) -> String;
+
+    /// Returns the exact length in bytes of the string [`Serialize::serialize`] would create.
+    ///
+    /// The default implementation serializes the data-structure and measures the result.
+    /// Implementations should override it to compute the length without building the string.
+    fn serialized_len(&self) -> usize {
+        self.serialize().len()
+    }
 }
 
 /// Creates a serialization of a [`String`] object as a JSON string with minimal
 /// whitespace characters.
-pub fn serialize_string(string: &String) -> String {
+pub fn serialize_string(string: &str) -> String {
     let mut s = String::new();
     s.push('\"');
     for c in string.chars() {
@@ -35,6 +47,8 @@ pub fn serialize_string(string: &String) -> String {
         } else if c == 0x000D as char {
             s.push('\\');
             s.push('r');
+        } else if c < 0x0020 as char {
+            s.push_str(&format!("\\u{:04x}", c as u32));
         } else {
             s.push(c);
         }
@@ -42,3 +56,224 @@ pub fn serialize_string(string: &String) -> String {
     s.push('\"');
     s
 }
+
+/// Returns the exact length in bytes of the string [`serialize_string`] would create for the
+/// given [`str`], without building it.
+///
+/// ```
+/// # use json::serializer::{serialize_string, serialized_string_len};
+/// let s = "tab\tand \"quotes\"";
+/// assert_eq!(serialize_string(s).len(), serialized_string_len(s));
+/// ```
+pub fn serialized_string_len(string: &str) -> usize {
+    let mut len = 2;
+    for c in string.chars() {
+        len += match c {
+            '\n' | '\t' | '"' | '\\' | '/' | '\u{8}' | '\u{c}' | '\r' => 2,
+            c if c < ' ' => 6,
+            _ => c.len_utf8(),
+        };
+    }
+    len
+}
+
+/// Options for [`serialize_with`]. The default omits nothing, like [`Serialize::serialize`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerializeOptions {
+    /// Omit object members whose value is `null`
+    pub skip_nulls: bool,
+    /// Omit object members whose value is an empty object, after omitting its own members
+    pub skip_empty_objects: bool,
+    /// Omit object members whose value is an empty array
+    pub skip_empty_arrays: bool,
+}
+
+/// Creates a serialization of a [`JValue`] as a JSON string with minimal whitespace characters,
+/// omitting the object members `options` asks for at any depth, in arrays too. Array elements
+/// are never omitted, as that would shift the indices of the others.
+///
+/// ```
+/// # use json::json;
+/// # use json::serializer::{serialize_with, SerializeOptions};
+/// let j_value = json!("[{\"a\": null, \"b\": {\"c\": [], \"d\": null}}, null]");
+/// let options = SerializeOptions { skip_nulls: true, skip_empty_arrays: true, ..SerializeOptions::default() };
+/// assert_eq!("[{\"b\":{}},null]", serialize_with(&j_value, &options));
+/// let options = SerializeOptions { skip_empty_objects: true, ..options };
+/// assert_eq!("[{},null]", serialize_with(&j_value, &options));
+/// ```
+pub fn serialize_with(value: &JValue, options: &SerializeOptions) -> String {
+    let mut result = String::new();
+    write_with(value, options, &mut result);
+    result
+}
+
+impl JValue {
+    /// Serializes only the value at the given JSON Pointer, like [`serialize_with`]
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::serializer::SerializeOptions;
+    /// let j_value = json!("{\"a\": {\"b\": [1, null]}, \"c\": 2}");
+    /// assert_eq!(Ok("[1,null]".to_string()), j_value.serialize_path("/a/b", &SerializeOptions::default()));
+    /// assert!(j_value.serialize_path("/d", &SerializeOptions::default()).is_err());
+    /// ```
+    pub fn serialize_path(&self, pointer: &str, options: &SerializeOptions) -> Result<String, JsonError> {
+        parse_pointer(pointer)?;
+        match self.pointer(pointer) {
+            Some(value) => Ok(serialize_with(value, options)),
+            None => Err(JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer)))
+        }
+    }
+
+    /// Serializes the value with minimal whitespace characters, omitting the values at the given
+    /// JSON Pointers. Pointers without a value are ignored. The elements after an omitted array
+    /// element are written at lower indices. Fails if a pointer is malformed or is the root's.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("{\"user\": {\"name\": \"x\", \"password\": \"y\"}, \"tags\": [1, 2, 3]}");
+    /// assert_eq!(Ok("{\"user\":{\"name\":\"x\"}}".to_string()),
+    ///            j_value.serialize_excluding(&["/user/password", "/tags", "/missing"]));
+    /// assert_eq!(Ok("[1,3]".to_string()), json!("[1, 2, 3]").serialize_excluding(&["/1"]));
+    /// ```
+    pub fn serialize_excluding(&self, pointers: &[&str]) -> Result<String, JsonError> {
+        let mut excluded = Vec::with_capacity(pointers.len());
+        for pointer in pointers {
+            let tokens = parse_pointer(pointer)?;
+            if tokens.is_empty() {
+                return Err(JsonError::new(ErrorKind::InvalidPointer, "The root can not be excluded".to_string()));
+            }
+            excluded.push(tokens);
+        }
+        let excluded: Vec<&[String]> = excluded.iter().map(|tokens| tokens.as_slice()).collect();
+        let mut result = String::new();
+        write_excluding(self, &excluded, &mut result);
+        Ok(result)
+    }
+}
+
+/// Writes `value` without the values at `excluded`, the remaining tokens of the pointers that
+/// lead into it
+fn write_excluding(value: &JValue, excluded: &[&[String]], out: &mut String) {
+    if excluded.is_empty() {
+        out.push_str(&value.serialize());
+        return;
+    }
+    match value {
+        JValue::Object(o) => {
+            out.push('{');
+            let mut empty = true;
+            for (k, v) in o.iter() {
+                let inside: Vec<&[String]> = excluded.iter().filter(|tokens| tokens[0] == *k)
+                    .map(|tokens| &tokens[1..]).collect();
+                if inside.iter().any(|tokens| tokens.is_empty()) {
+                    continue;
+                }
+                if !empty {
+                    out.push(',');
+                }
+                empty = false;
+                out.push_str(&serialize_string(k));
+                out.push(':');
+                write_excluding(v, &inside, out);
+            }
+            out.push('}');
+        }
+        JValue::Array(a) => {
+            out.push('[');
+            let mut empty = true;
+            for (i, v) in a.iter().enumerate() {
+                let inside: Vec<&[String]> = excluded.iter().filter(|tokens| array_index(&tokens[0]) == Some(i))
+                    .map(|tokens| &tokens[1..]).collect();
+                if inside.iter().any(|tokens| tokens.is_empty()) {
+                    continue;
+                }
+                if !empty {
+                    out.push(',');
+                }
+                empty = false;
+                write_excluding(v, &inside, out);
+            }
+            out.push(']');
+        }
+        _ => out.push_str(&value.serialize())
+    }
+}
+
+fn write_with(value: &JValue, options: &SerializeOptions, out: &mut String) {
+    match value {
+        JValue::Object(o) => {
+            out.push('{');
+            let mut empty = true;
+            for (k, v) in o.iter() {
+                if options.skip_nulls && *v == JValue::Null {
+                    continue;
+                }
+                let start = out.len();
+                if !empty {
+                    out.push(',');
+                }
+                out.push_str(&serialize_string(k));
+                out.push(':');
+                let value_start = out.len();
+                write_with(v, options, out);
+                let omitted = match v {
+                    JValue::Object(_) => options.skip_empty_objects && &out[value_start..] == "{}",
+                    JValue::Array(a) => options.skip_empty_arrays && a.is_empty(),
+                    _ => false
+                };
+                if omitted {
+                    out.truncate(start);
+                } else {
+                    empty = false;
+                }
+            }
+            out.push('}');
+        }
+        JValue::Array(a) => {
+            out.push('[');
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_with(v, options, out);
+            }
+            out.push(']');
+        }
+        _ => out.push_str(&value.serialize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::parser::parse;
+    use crate::serializer::{serialize_with, Serialize, SerializeOptions};
+
+    #[test]
+    fn test_serialize_with() {
+        let j_value = json!("{\"a\": {\"b\": {\"c\": null}}}");
+        assert_eq!(j_value.serialize(), serialize_with(&j_value, &SerializeOptions::default()));
+        let options = SerializeOptions { skip_nulls: true, skip_empty_objects: true, skip_empty_arrays: true };
+        assert_eq!("{}", serialize_with(&j_value, &options));
+        assert_eq!("[[],{}]", serialize_with(&json!("[[], {\"x\": []}]"), &options));
+        let options = SerializeOptions { skip_empty_arrays: true, ..SerializeOptions::default() };
+        assert_eq!("{\"k\":{\"x\":null}}", serialize_with(&json!("{\"k\": {\"x\": null, \"y\": []}}"), &options));
+    }
+
+    #[test]
+    fn test_serialize_excluding() {
+        let j_value = json!("{\"a\": [{\"b\": 1, \"c\": 2}, {\"b\": 3}], \"d/e\": {\"f\": null}}");
+        assert_eq!(Ok(j_value.serialize()), j_value.serialize_excluding(&[]));
+        assert_eq!(Ok("{\"a\":[{\"c\":2},{\"b\":3}]}".to_string()),
+                   j_value.serialize_excluding(&["/a/0/b", "/d~1e", "/a/0/b/x", "/a/01"]));
+        let serialized = j_value.serialize_excluding(&["/a/0", "/d~1e/f"]).unwrap();
+        assert_eq!(Ok(json!("{\"a\": [{\"b\": 3}], \"d/e\": {}}")), parse(&serialized));
+        assert_eq!(ErrorKind::InvalidPointer, j_value.serialize_excluding(&[""]).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidPointer, j_value.serialize_excluding(&["a"]).unwrap_err().kind());
+        let options = SerializeOptions { skip_nulls: true, ..SerializeOptions::default() };
+        assert_eq!(Ok("{}".to_string()), j_value.serialize_path("/d~1e", &options));
+        assert_eq!("No value at /a/2", j_value.serialize_path("/a/2", &options).unwrap_err().message());
+    }
+}
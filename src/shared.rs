@@ -0,0 +1,199 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Sharing parsed documents between threads.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::config;
+use crate::convert::FromJValue;
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+
+struct Entry {
+    value: Arc<JValue>,
+    /// The file the document was loaded from, if any
+    source: Option<PathBuf>,
+}
+
+/// Named documents shared between threads.
+///
+/// Readers get an `Arc<JValue>` snapshot that stays valid while the store is updated; a reload
+/// replaces the document atomically and only the readers that ask again see the new version.
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use std::thread;
+/// # use json::json;
+/// # use json::shared::DocumentStore;
+/// let store = Arc::new(DocumentStore::new());
+/// store.insert("limits", json!("{\"requests\": 100}"));
+///
+/// let reader = Arc::clone(&store);
+/// let requests = thread::spawn(move || reader.get_path_as::<u32>("limits", "/requests"))
+///     .join().unwrap();
+/// assert_eq!(Ok(100), requests);
+///
+/// let snapshot = store.get("limits").unwrap();
+/// store.insert("limits", json!("{\"requests\": 200}"));
+/// assert_eq!(json!("{\"requests\": 100}"), *snapshot);
+/// ```
+#[derive(Default)]
+pub struct DocumentStore {
+    documents: RwLock<HashMap<String, Entry>>,
+}
+
+impl DocumentStore {
+    /// Creates an empty store
+    pub fn new() -> DocumentStore {
+        DocumentStore::default()
+    }
+
+    // A panic while the lock is held can not leave a document half replaced, so a poisoned
+    // lock is still safe to use
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Entry>> {
+        self.documents.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Entry>> {
+        self.documents.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stores a document under `name`, returning the document it replaced.
+    pub fn insert<V: Into<Arc<JValue>>>(&self, name: &str, value: V) -> Option<Arc<JValue>> {
+        let entry = Entry { value: value.into(), source: None };
+        self.write().insert(name.to_string(), entry).map(|old| old.value)
+    }
+
+    /// Loads the configuration file at `path` with [`config::load`] and stores it under `name`,
+    /// remembering the path for [`reload`](DocumentStore::reload). Returns the stored document.
+    pub fn load<P: AsRef<Path>>(&self, name: &str, path: P) -> Result<Arc<JValue>, JsonError> {
+        let value = Arc::new(config::load(&path)?);
+        let entry = Entry { value: Arc::clone(&value), source: Some(path.as_ref().to_path_buf()) };
+        self.write().insert(name.to_string(), entry);
+        Ok(value)
+    }
+
+    /// Loads the document stored under `name` again from its file. If the file can not be
+    /// loaded the stored document is kept and the error is returned.
+    pub fn reload(&self, name: &str) -> Result<Arc<JValue>, JsonError> {
+        let source = match self.read().get(name) {
+            Some(Entry { source: Some(source), .. }) => source.clone(),
+            Some(_) => return Err(JsonError::new(
+                ErrorKind::NotFound, format!("The document {} was not loaded from a file", name))),
+            None => return Err(no_document(name))
+        };
+        // The file is read without holding the lock
+        let value = Arc::new(config::load(&source)?);
+        if let Some(entry) = self.write().get_mut(name) {
+            entry.value = Arc::clone(&value);
+        }
+        Ok(value)
+    }
+
+    /// Reloads every document loaded from a file, returning the errors of the documents that
+    /// could not be reloaded by name.
+    pub fn reload_all(&self) -> Vec<(String, JsonError)> {
+        let names: Vec<String> = self.read().iter()
+            .filter(|(_, entry)| entry.source.is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.into_iter().filter_map(|name| self.reload(&name).err().map(|e| (name, e))).collect()
+    }
+
+    /// Returns the document stored under `name`
+    pub fn get(&self, name: &str) -> Option<Arc<JValue>> {
+        self.read().get(name).map(|entry| Arc::clone(&entry.value))
+    }
+
+    /// Converts the value at the given JSON Pointer of the document stored under `name` to `T`,
+    /// like [`JValue::get_path_as`]
+    pub fn get_path_as<T: FromJValue>(&self, name: &str, pointer: &str) -> Result<T, JsonError> {
+        self.get(name).ok_or_else(|| no_document(name))?.get_path_as(pointer)
+    }
+
+    /// Removes the document stored under `name`, returning it
+    pub fn remove(&self, name: &str) -> Option<Arc<JValue>> {
+        self.write().remove(name).map(|entry| entry.value)
+    }
+
+    /// Returns the names of the stored documents, in no particular order
+    pub fn names(&self) -> Vec<String> {
+        self.read().keys().cloned().collect()
+    }
+}
+
+fn no_document(name: &str) -> JsonError {
+    JsonError::new(ErrorKind::NotFound, format!("No document named {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::shared::DocumentStore;
+
+    #[test]
+    fn test_reload() {
+        let dir = std::env::temp_dir().join(format!("json-shared-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.json");
+        fs::write(&path, "{\"workers\": 2}").unwrap();
+        let store = DocumentStore::new();
+        store.load("app", &path).unwrap();
+        store.insert("inline", json!("[]"));
+
+        fs::write(&path, "{\"workers\": 4}").unwrap();
+        assert_eq!(Ok(json!("{\"workers\": 4}")), store.reload("app").map(|v| (*v).clone()));
+        fs::write(&path, "{\"workers\": ").unwrap();
+        let errors = store.reload_all();
+        assert_eq!(vec!["app".to_string()], errors.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>());
+        assert_eq!(ErrorKind::Syntax, errors[0].1.kind());
+        assert_eq!(Ok(4_u32), store.get_path_as("app", "/workers"));
+
+        assert_eq!(ErrorKind::NotFound, store.reload("inline").unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, store.reload("none").unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, store.get_path_as::<u32>("none", "").unwrap_err().kind());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_access() {
+        let store = Arc::new(DocumentStore::new());
+        store.insert("counter", json!("0"));
+        let writers: Vec<_> = (1..=4).map(|i| {
+            let store = Arc::clone(&store);
+            thread::spawn(move || {
+                store.insert(&format!("doc{}", i), json!("null"));
+                store.get("counter").is_some()
+            })
+        }).collect();
+        assert!(writers.into_iter().all(|writer| writer.join().unwrap()));
+        let mut names = store.names();
+        names.sort();
+        assert_eq!(vec!["counter", "doc1", "doc2", "doc3", "doc4"], names);
+        assert_eq!(Some(Arc::new(json!("0"))), store.remove("counter"));
+        assert_eq!(None, store.get("counter"));
+    }
+}
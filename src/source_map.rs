@@ -0,0 +1,151 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Mapping byte offsets in a source text to lines and columns
+
+/// An index of the lines of a source text, which maps byte offsets, such as those of tokens and
+/// errors, to lines and columns and back, and extracts the lines for showing them. Building the
+/// map scans the source once; each lookup is a binary search.
+///
+/// Lines end at `\n`; a `\r` before it is not part of the line. Lines and columns start at 1 and
+/// columns count chars, not bytes.
+///
+/// ```
+/// # use json::source_map::SourceMap;
+/// let map = SourceMap::new("{\"a\": 1,\r\n \"\u{e9}\": 2}");
+/// assert_eq!(2, map.line_count());
+/// assert_eq!((2, 7), map.line_column(17));
+/// assert_eq!(Some(17), map.offset(2, 7));
+/// assert_eq!(Some(" \"\u{e9}\": 2}"), map.line(2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SourceMap<'s> {
+    source: &'s str,
+    /// The byte offsets at which the lines start
+    line_starts: Vec<usize>,
+}
+
+impl<'s> SourceMap<'s> {
+    /// Indexes the lines of `source`
+    pub fn new(source: &'s str) -> SourceMap<'s> {
+        let line_starts = std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        SourceMap { source, line_starts }
+    }
+
+    /// Returns the source text
+    pub fn source(&self) -> &'s str {
+        self.source
+    }
+
+    /// Returns the number of lines, which is at least 1
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns `offset` clamped to the length of the source and moved back to the start of the
+    /// char it falls in
+    pub fn char_boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    /// Returns the line and column of the byte `offset`, see [`SourceMap::char_boundary`] for
+    /// offsets that are not at a char
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = self.char_boundary(offset);
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let column = self.source[self.line_starts[line - 1]..offset].chars().count();
+        (line, column + 1)
+    }
+
+    /// Returns the byte offset of `column` in `line`, or `None` if there is no such line. A
+    /// column past the end of the line gives the offset of its end.
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let text = self.line(line)?;
+        Some(start + text.char_indices().nth(column.saturating_sub(1)).map_or(text.len(), |(i, _)| i))
+    }
+
+    /// Returns the text of `line` without its line ending, or `None` if there is no such line
+    pub fn line(&self, line: usize) -> Option<&'s str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).map_or(self.source.len(), |&next| next - 1);
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+
+    /// Returns the line of the byte `offset` for showing it, shortened to `context` chars on each
+    /// side of the offset with `...` marking the cut ends, and the column of the offset in it,
+    /// starting at 0.
+    ///
+    /// ```
+    /// # use json::source_map::SourceMap;
+    /// let map = SourceMap::new("[1, 2, 3, 4, 5]\n");
+    /// assert_eq!(("...2, 3, ...".to_string(), 6), map.snippet(7, 3));
+    /// assert_eq!(("[1, 2, 3, 4, 5]".to_string(), 15), map.snippet(15, 20));
+    /// ```
+    pub fn snippet(&self, offset: usize, context: usize) -> (String, usize) {
+        let (line, column) = self.line_column(offset);
+        let chars: Vec<char> = self.line(line).unwrap().chars().collect();
+        let column = column - 1;
+        let from = column.saturating_sub(context);
+        let to = (column + context).min(chars.len());
+        let mut snippet = String::new();
+        let mut snippet_column = column - from;
+        if from > 0 {
+            snippet.push_str("...");
+            snippet_column += 3;
+        }
+        snippet.extend(&chars[from..to]);
+        if to < chars.len() {
+            snippet.push_str("...");
+        }
+        (snippet, snippet_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_map::SourceMap;
+
+    #[test]
+    fn test_line_column() {
+        let map = SourceMap::new("ab\n\ncd\n");
+        assert_eq!(4, map.line_count());
+        assert_eq!((1, 1), map.line_column(0));
+        assert_eq!((1, 3), map.line_column(2));
+        assert_eq!((2, 1), map.line_column(3));
+        assert_eq!((3, 2), map.line_column(5));
+        assert_eq!((4, 1), map.line_column(7));
+        assert_eq!((4, 1), map.line_column(100));
+        assert_eq!((1, 2), SourceMap::new("\u{e9}\u{e9}").line_column(3));
+        assert_eq!((1, 1), SourceMap::new("").line_column(0));
+    }
+
+    #[test]
+    fn test_lines_and_offsets() {
+        let map = SourceMap::new("ab\r\ncd");
+        assert_eq!((Some("ab"), Some("cd"), None, None), (map.line(1), map.line(2), map.line(3), map.line(0)));
+        assert_eq!(Some(5), map.offset(2, 2));
+        assert_eq!(Some(6), map.offset(2, 9));
+        assert_eq!(None, map.offset(3, 1));
+        assert_eq!(map.line_column(5), (2, 2));
+    }
+}
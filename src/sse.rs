@@ -0,0 +1,195 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! JSON payloads of [Server-Sent Events], the `text/event-stream` format of streaming and
+//! notification APIs.
+//!
+//! [Server-Sent Events]:https://html.spec.whatwg.org/multipage/server-sent-events.html
+
+use std::io::{BufRead, BufReader, ErrorKind as IoErrorKind, Read};
+
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::parse;
+
+/// Reads the events of an event stream and parses the data of each as JSON, yielding the
+/// event type with it, or `None` for an event without an `event:` field. The `data:` lines of
+/// an event are joined with newlines, comments and the `id:` and `retry:` fields are skipped,
+/// and an event without data is not dispatched, as browsers do. An event whose data is not
+/// JSON, such as the `[DONE]` some APIs end with, or which is not UTF-8, is yielded as an error
+/// and reading goes on with the next event; an I/O error ends the iteration.
+///
+/// ```
+/// # use json::json;
+/// # use json::sse::parse_event_stream;
+/// let stream = ": keep-alive\n\ndata: {\"delta\": \"Hel\"}\n\nevent: done\ndata: {\"a\":\ndata: 1}\n\n";
+/// let events: Vec<_> = parse_event_stream(stream.as_bytes()).collect();
+/// assert_eq!(Ok((None, json!("{\"delta\": \"Hel\"}"))), events[0]);
+/// assert_eq!(Ok((Some("done".to_string()), json!("{\"a\": 1}"))), events[1]);
+/// assert_eq!(2, events.len());
+/// ```
+pub fn parse_event_stream<R: Read>(reader: R) -> EventStream<R> {
+    EventStream { reader: BufReader::new(reader), line: Vec::new(), after_cr: false, at_start: true, done: false }
+}
+
+/// The iterator of [`parse_event_stream`]
+#[derive(Debug)]
+pub struct EventStream<R> {
+    reader: BufReader<R>,
+    line: Vec<u8>,
+    /// Whether the last line ended with `\r`, so a `\n` right after it is part of its end
+    after_cr: bool,
+    /// Whether nothing was read yet, so a byte order mark is skipped
+    at_start: bool,
+    done: bool,
+}
+
+impl<R: Read> EventStream<R> {
+    /// Reads the next line into `self.line` without its end, or returns `false` at the end of
+    /// the stream. A last line without an end is dropped, as it can not end an event.
+    fn read_line(&mut self) -> Result<bool, JsonError> {
+        self.line.clear();
+        loop {
+            let buffer = match self.reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(e) if e.kind() == IoErrorKind::Interrupted => continue,
+                Err(e) => return Err(JsonError::new(ErrorKind::Io, format!("Can not read the event stream: {}", e)))
+            };
+            if buffer.is_empty() {
+                return Ok(false);
+            }
+            let mut start = 0;
+            if self.after_cr && buffer[0] == b'\n' {
+                start = 1;
+            }
+            self.after_cr = false;
+            match buffer[start..].iter().position(|&b| b == b'\n' || b == b'\r') {
+                Some(i) => {
+                    self.line.extend_from_slice(&buffer[start..start + i]);
+                    self.after_cr = buffer[start + i] == b'\r';
+                    self.reader.consume(start + i + 1);
+                    break;
+                }
+                None => {
+                    let len = buffer.len();
+                    self.line.extend_from_slice(&buffer[start..]);
+                    self.reader.consume(len);
+                }
+            }
+        }
+        if self.at_start {
+            self.at_start = false;
+            if self.line.starts_with(b"\xef\xbb\xbf") {
+                self.line.drain(..3);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reads the lines of the next event with data, returning its type and data
+    fn next_event(&mut self) -> Result<Option<(Option<String>, String)>, JsonError> {
+        let mut event = None;
+        let mut data: Option<String> = None;
+        while self.read_line()? {
+            if self.line.is_empty() {
+                if let Some(data) = data.take() {
+                    return Ok(Some((event, data)));
+                }
+                event = None;
+                continue;
+            }
+            let line = match std::str::from_utf8(&self.line) {
+                Ok(line) => line,
+                Err(_) => {
+                    // The rest of the event is skipped, so its other lines are not taken as an event
+                    while self.read_line()? && !self.line.is_empty() {}
+                    return Err(JsonError::new(ErrorKind::Syntax, "Invalid UTF-8 in the event stream"));
+                }
+            };
+            let (field, value) = match line.find(':') {
+                Some(i) => (&line[..i], line[i + 1..].strip_prefix(' ').unwrap_or(&line[i + 1..])),
+                None => (line, "")
+            };
+            match field {
+                "event" => event = Some(value.to_string()),
+                "data" => match &mut data {
+                    Some(data) => {
+                        data.push('\n');
+                        data.push_str(value);
+                    }
+                    None => data = Some(value.to_string())
+                },
+                // Comments, "id", "retry" and unknown fields
+                _ => ()
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: Read> Iterator for EventStream<R> {
+    type Item = Result<(Option<String>, JValue), JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_event() {
+            Ok(Some((event, data))) => Some(parse(&data).map(|value| (event, value))),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = e.kind() == ErrorKind::Io;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::sse::parse_event_stream;
+
+    #[test]
+    fn test_line_endings_and_fields() {
+        let stream = "\u{feff}id: 1\r\nevent: a\r\ndata:[1,\r\ndata\r\ndata: 2]\r\n\r\nretry: 10\rdata: true\r\r";
+        let events: Vec<_> = parse_event_stream(stream.as_bytes()).collect();
+        assert_eq!(vec![Ok((Some("a".to_string()), json!("[1,\n\n2]"))), Ok((None, json!("true")))], events);
+    }
+
+    #[test]
+    fn test_events_without_data_or_end() {
+        let stream = "event: ignored\n\ndata: 1\n\nevent: x\ndata: 2";
+        let events: Vec<_> = parse_event_stream(stream.as_bytes()).collect();
+        assert_eq!(vec![Ok((None, json!("1")))], events);
+    }
+
+    #[test]
+    fn test_invalid_data_does_not_end_the_stream() {
+        let stream: &[u8] = b"data: [DONE]\n\ndata: \xff\n\ndata: {}\n\n";
+        let events: Vec<_> = parse_event_stream(stream).map(|event| event.map_err(|e| e.kind())).collect();
+        assert_eq!(vec![Err(ErrorKind::Syntax), Err(ErrorKind::Syntax), Ok((None, json!("{}")))], events);
+        let stream: &[u8] = b"data: \xff\ndata: 2\n\ndata: 3\n\n";
+        let events: Vec<_> = parse_event_stream(stream).map(|event| event.map_err(|e| e.kind())).collect();
+        assert_eq!(vec![Err(ErrorKind::Syntax), Ok((None, json!("3")))], events);
+    }
+}
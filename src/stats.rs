@@ -0,0 +1,328 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Statistics about the shape of documents, for exploring unknown data.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::ParseOptions;
+use crate::stream::Scanner;
+use crate::transform::push_token;
+
+/// The number of values of each type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeCounts {
+    pub null: usize,
+    pub boolean: usize,
+    pub number: usize,
+    pub string: usize,
+    pub array: usize,
+    pub object: usize,
+}
+
+/// A summary of a set of sizes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Distribution {
+    pub count: usize,
+    pub min: usize,
+    pub max: usize,
+    pub total: usize,
+    /// `buckets[0]` counts the zeros and `buckets[i]` the sizes from `2^(i-1)` to `2^i - 1`
+    pub buckets: Vec<usize>,
+}
+
+impl Distribution {
+    /// Returns the mean size, or [`None`] if there are no sizes
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.total as f64 / self.count as f64)
+        }
+    }
+
+    fn add(&mut self, size: usize) {
+        self.min = if self.count == 0 { size } else { self.min.min(size) };
+        self.max = self.max.max(size);
+        self.count += 1;
+        self.total += size;
+        let bucket = (usize::BITS - size.leading_zeros()) as usize;
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+}
+
+/// The type of a value, with the length of a string
+#[derive(Clone, Copy)]
+enum Kind {
+    Null,
+    Boolean,
+    Number,
+    String(usize),
+    Array,
+    Object,
+}
+
+fn kind(value: &JValue) -> Kind {
+    match value {
+        JValue::Null => Kind::Null,
+        JValue::Boolean(_) => Kind::Boolean,
+        JValue::Number(_) => Kind::Number,
+        JValue::String(s) => Kind::String(s.as_str().chars().count()),
+        JValue::Array(_) => Kind::Array,
+        JValue::Object(_) => Kind::Object,
+    }
+}
+
+/// Statistics about a document, collected by [`analyze`] or [`analyze_reader`].
+///
+/// Paths are JSON Pointers in which every array index is replaced by `*`, so that the elements
+/// of an array are counted together.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocStats {
+    values: usize,
+    max_depth: usize,
+    keys: BTreeMap<String, usize>,
+    types: BTreeMap<String, TypeCounts>,
+    string_lengths: Distribution,
+    array_sizes: Distribution,
+}
+
+impl DocStats {
+    /// Returns the number of values, counting the containers and everything inside them
+    pub fn values(&self) -> usize {
+        self.values
+    }
+
+    /// Returns the largest number of nested arrays and objects, 0 for a scalar document
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Returns the number of members with each key, across all objects
+    pub fn key_frequency(&self) -> &BTreeMap<String, usize> {
+        &self.keys
+    }
+
+    /// Returns the types of the values found at each path
+    pub fn types(&self) -> &BTreeMap<String, TypeCounts> {
+        &self.types
+    }
+
+    /// Returns the lengths of the strings in chars, not counting keys
+    pub fn string_lengths(&self) -> &Distribution {
+        &self.string_lengths
+    }
+
+    /// Returns the numbers of elements of the arrays
+    pub fn array_sizes(&self) -> &Distribution {
+        &self.array_sizes
+    }
+
+    fn record(&mut self, path: &str, kind: Kind, depth: usize) {
+        self.values += 1;
+        let counts = self.types.entry(path.to_string()).or_default();
+        match kind {
+            Kind::Null => counts.null += 1,
+            Kind::Boolean => counts.boolean += 1,
+            Kind::Number => counts.number += 1,
+            Kind::String(length) => {
+                counts.string += 1;
+                self.string_lengths.add(length);
+            }
+            Kind::Array => counts.array += 1,
+            Kind::Object => counts.object += 1,
+        }
+        if let Kind::Array | Kind::Object = kind {
+            self.max_depth = self.max_depth.max(depth + 1);
+        }
+    }
+
+    fn key(&mut self, key: &str) {
+        *self.keys.entry(key.to_string()).or_default() += 1;
+    }
+
+    fn visit(&mut self, value: &JValue, path: &mut String, depth: usize) {
+        self.record(path, kind(value), depth);
+        let len = path.len();
+        match value {
+            JValue::Object(o) => {
+                for (key, member) in o.iter() {
+                    self.key(key);
+                    push_token(path, key);
+                    self.visit(member, path, depth + 1);
+                    path.truncate(len);
+                }
+            }
+            JValue::Array(a) => {
+                self.array_sizes.add(a.len());
+                path.push_str("/*");
+                for element in a {
+                    self.visit(element, path, depth + 1);
+                }
+                path.truncate(len);
+            }
+            _ => ()
+        }
+    }
+
+    fn scan<R: Read>(&mut self, scanner: &mut Scanner<R>, path: &mut String, depth: usize)
+                     -> Result<(), JsonError> {
+        scanner.skip_whitespace()?;
+        let len = path.len();
+        let max_depth = ParseOptions::default().max_depth;
+        if depth == max_depth && matches!(scanner.peek()?, Some(b'{' | b'[')) {
+            return Err(JsonError::new(ErrorKind::Syntax, format!(
+                "The value is nested deeper than the limit of {} levels at byte {}", max_depth, scanner.offset())));
+        }
+        match scanner.peek()? {
+            Some(b'{') => {
+                self.record(path, Kind::Object, depth);
+                scanner.bump()?;
+                scanner.skip_whitespace()?;
+                if scanner.peek()? == Some(b'}') {
+                    return scanner.bump();
+                }
+                loop {
+                    scanner.skip_whitespace()?;
+                    let key = scanner.key()?;
+                    scanner.skip_whitespace()?;
+                    scanner.expect(b':')?;
+                    self.key(&key);
+                    push_token(path, &key);
+                    self.scan(scanner, path, depth + 1)?;
+                    path.truncate(len);
+                    scanner.skip_whitespace()?;
+                    match scanner.next()? {
+                        b',' => (),
+                        b'}' => return Ok(()),
+                        b => return Err(scanner.unexpected(b))
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.record(path, Kind::Array, depth);
+                scanner.bump()?;
+                scanner.skip_whitespace()?;
+                let mut size = 0;
+                if scanner.peek()? == Some(b']') {
+                    scanner.bump()?;
+                } else {
+                    path.push_str("/*");
+                    loop {
+                        self.scan(scanner, path, depth + 1)?;
+                        size += 1;
+                        scanner.skip_whitespace()?;
+                        match scanner.next()? {
+                            b',' => (),
+                            b']' => break,
+                            b => return Err(scanner.unexpected(b))
+                        }
+                    }
+                    path.truncate(len);
+                }
+                self.array_sizes.add(size);
+                Ok(())
+            }
+            _ => {
+                let (_, value) = scanner.parse_value()?;
+                self.record(path, kind(&value), depth);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Collects statistics about a value.
+///
+/// ```
+/// # use json::json;
+/// # use json::stats::analyze;
+/// let stats = analyze(&json!("{\"users\": [{\"name\": \"ada\", \"age\": 36}, {\"name\": \"alan\", \"age\": null}]}"));
+/// assert_eq!(3, stats.max_depth());
+/// assert_eq!(Some(&2), stats.key_frequency().get("name"));
+/// let ages = stats.types()["/users/*/age"];
+/// assert_eq!((1, 1), (ages.number, ages.null));
+/// assert_eq!(Some(3.5), stats.string_lengths().mean());
+/// assert_eq!(2, stats.array_sizes().max);
+/// ```
+pub fn analyze(value: &JValue) -> DocStats {
+    let mut stats = DocStats::default();
+    stats.visit(value, &mut String::new(), 0);
+    stats
+}
+
+/// Collects the statistics of [`analyze`] about the document read from `reader`, scanning it
+/// without building it. Only one scalar is held in memory at a time. Documents nested deeper
+/// than [`ParseOptions::max_depth`] levels are refused, as they are by the parser.
+pub fn analyze_reader<R: Read>(reader: R) -> Result<DocStats, JsonError> {
+    let mut stats = DocStats::default();
+    let mut scanner = Scanner::new(reader);
+    stats.scan(&mut scanner, &mut String::new(), 0)?;
+    scanner.end_of_document()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::parser::parse;
+    use crate::stats::{analyze, analyze_reader, Distribution, TypeCounts};
+
+    #[test]
+    fn test_analyze() {
+        let document = "[{\"a\": \"\", \"b\": [1, [true]]}, {\"a\": \"xyz\", \"a/b\": []}, \"hello\", 7]";
+        let stats = analyze(&parse(document).unwrap());
+        assert_eq!(12, stats.values());
+        assert_eq!(4, stats.max_depth());
+        assert_eq!(vec![("a", 2), ("a/b", 1), ("b", 1)],
+                   stats.key_frequency().iter().map(|(k, n)| (k.as_str(), *n)).collect::<Vec<_>>());
+        assert_eq!(TypeCounts { object: 2, string: 1, number: 1, ..TypeCounts::default() }, stats.types()["/*"]);
+        assert_eq!(TypeCounts { array: 1, number: 1, ..TypeCounts::default() }, stats.types()["/*/b/*"]);
+        assert!(stats.types().contains_key("/*/a~1b"));
+        assert_eq!(Distribution { count: 3, min: 0, max: 5, total: 8, buckets: vec![1, 0, 1, 1] },
+                   *stats.string_lengths());
+        assert_eq!(vec![1, 1, 1, 1], stats.array_sizes().buckets);
+        assert_eq!(0, analyze(&parse("1").unwrap()).max_depth());
+        assert_eq!(None, analyze(&parse("1").unwrap()).array_sizes().mean());
+    }
+
+    #[test]
+    fn test_analyze_reader() {
+        for document in ["[{\"a\": \"\", \"b\": [1, [true]]}, {\"a\": \"xyz\", \"a/b\": []}, \"hello\", 7]",
+                         " {\"a\": {}, \"b\": [ ], \"c\": \"\u{e9}\"} ", "null"] {
+            assert_eq!(analyze(&parse(document).unwrap()), analyze_reader(document.as_bytes()).unwrap());
+        }
+        assert_eq!(ErrorKind::Syntax, analyze_reader("[1, 2".as_bytes()).unwrap_err().kind());
+        assert_eq!(ErrorKind::Syntax, analyze_reader("{\"a\" 1}".as_bytes()).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_analyze_reader_max_depth() {
+        let nested = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert_eq!(128, analyze_reader(nested.as_bytes()).unwrap().max_depth());
+        let error = analyze_reader("[".repeat(200_000).as_bytes()).unwrap_err();
+        assert_eq!(ErrorKind::Syntax, error.kind());
+        assert_eq!("The value is nested deeper than the limit of 128 levels at byte 128", error.message());
+    }
+}
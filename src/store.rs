@@ -0,0 +1,414 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Content-addressed storage of documents, deduplicating the subtrees they share.
+//!
+//! A document is stored as one chunk per object and array. A chunk is the serialization of the
+//! container with its keys sorted and every nested container replaced by the SHA-256 hash of its
+//! own chunk, and it is stored under its hash:
+//!
+//! ```text
+//! {"object": {"name": {"value": "x"}, "tags": {"hash": "9f86d0..."}}}
+//! {"array": [{"value": 1}, {"value": 2}]}
+//! ```
+//!
+//! A scalar document is stored as `{"value": ...}`. Equal subtrees have equal hashes and are
+//! stored once, within a document and across all the versions of it in the store, so a new
+//! version only adds the chunks on the paths to its changes. Numbers are stored as written, not
+//! as doubles, so a document is read back exactly as it was stored.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+use crate::convert::describe;
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::parse;
+use crate::serializer::{Serialize, serialize_string};
+
+/// The SHA-256 hash of a chunk, displayed and parsed as 64 lowercase hexadecimal digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentHash(pub [u8; 32]);
+
+impl ContentHash {
+    /// Hashes a chunk
+    pub fn of(chunk: &[u8]) -> ContentHash {
+        ContentHash(Sha256::digest(chunk).into())
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ContentHash {
+    type Err = JsonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || JsonError::new(ErrorKind::InvalidType, format!("Invalid content hash {:?}", s));
+        if s.len() != 64 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let mut hash = [0; 32];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(ContentHash(hash))
+    }
+}
+
+/// A key-value store the chunks are kept in
+pub trait Backend {
+    /// Returns the chunk stored under `hash`, or [`None`] if there is none
+    fn get(&self, hash: &ContentHash) -> Result<Option<Vec<u8>>, JsonError>;
+
+    /// Stores a chunk under its hash
+    fn put(&mut self, hash: ContentHash, chunk: Vec<u8>) -> Result<(), JsonError>;
+
+    /// Whether a chunk is stored under `hash`
+    fn contains(&self, hash: &ContentHash) -> Result<bool, JsonError> {
+        self.get(hash).map(|chunk| chunk.is_some())
+    }
+}
+
+/// A backend keeping the chunks in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBackend {
+    chunks: HashMap<ContentHash, Vec<u8>>,
+}
+
+impl MemoryBackend {
+    /// Creates an empty backend
+    pub fn new() -> MemoryBackend {
+        MemoryBackend::default()
+    }
+
+    /// Returns the number of stored chunks
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns `true` if no chunk is stored
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn get(&self, hash: &ContentHash) -> Result<Option<Vec<u8>>, JsonError> {
+        Ok(self.chunks.get(hash).cloned())
+    }
+
+    fn put(&mut self, hash: ContentHash, chunk: Vec<u8>) -> Result<(), JsonError> {
+        self.chunks.insert(hash, chunk);
+        Ok(())
+    }
+
+    fn contains(&self, hash: &ContentHash) -> Result<bool, JsonError> {
+        Ok(self.chunks.contains_key(hash))
+    }
+}
+
+/// A backend keeping every chunk in a file of a directory, named by its hash
+#[derive(Debug, Clone)]
+pub struct DirectoryBackend {
+    dir: PathBuf,
+}
+
+impl DirectoryBackend {
+    /// Uses the directory `dir`, creating it if it does not exist
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<DirectoryBackend, JsonError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| JsonError::new(ErrorKind::Io, format!("Can not create {}: {}", dir.display(), e)))?;
+        Ok(DirectoryBackend { dir })
+    }
+
+    fn path(&self, hash: &ContentHash) -> PathBuf {
+        self.dir.join(hash.to_string())
+    }
+}
+
+impl Backend for DirectoryBackend {
+    fn get(&self, hash: &ContentHash) -> Result<Option<Vec<u8>>, JsonError> {
+        let path = self.path(hash);
+        match fs::read(&path) {
+            Ok(chunk) => Ok(Some(chunk)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(JsonError::new(ErrorKind::Io, format!("Can not read {}: {}", path.display(), e)))
+        }
+    }
+
+    fn put(&mut self, hash: ContentHash, chunk: Vec<u8>) -> Result<(), JsonError> {
+        let path = self.path(&hash);
+        // Written next to its place and renamed, so a reader never sees a partial chunk
+        let temporary = self.dir.join(format!(".{}.tmp", hash));
+        fs::write(&temporary, chunk).and_then(|_| fs::rename(&temporary, &path))
+            .map_err(|e| JsonError::new(ErrorKind::Io, format!("Can not write {}: {}", path.display(), e)))
+    }
+
+    fn contains(&self, hash: &ContentHash) -> Result<bool, JsonError> {
+        Ok(self.path(hash).exists())
+    }
+}
+
+/// Documents stored as deduplicated chunks in a [`Backend`].
+///
+/// ```
+/// # use json::json;
+/// # use json::store::{MemoryBackend, Store};
+/// let mut store = Store::new(MemoryBackend::new());
+/// let v1 = store.put(&json!("{\"users\": [{\"name\": \"ann\"}], \"settings\": {\"theme\": \"dark\"}}")).unwrap();
+/// assert_eq!(4, store.backend().len());
+///
+/// // Only the root chunk and the changed array are new
+/// let v2 = store.put(&json!("{\"users\": [{\"name\": \"ann\"}, {\"name\": \"ann\"}], \"settings\": {\"theme\": \"dark\"}}")).unwrap();
+/// assert_eq!(6, store.backend().len());
+///
+/// assert_eq!(Ok(json!("{\"users\": [{\"name\": \"ann\"}], \"settings\": {\"theme\": \"dark\"}}")), store.get(&v1));
+/// assert_ne!(v1, v2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Store<B> {
+    backend: B,
+}
+
+impl<B: Backend> Store<B> {
+    /// Stores documents in `backend`
+    pub fn new(backend: B) -> Store<B> {
+        Store { backend }
+    }
+
+    /// Returns the backend
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the backend
+    pub fn into_backend(self) -> B {
+        self.backend
+    }
+
+    /// Stores a document, returning the hash of its root chunk. Chunks already in the backend
+    /// are not written again.
+    pub fn put(&mut self, value: &JValue) -> Result<ContentHash, JsonError> {
+        let chunk = match value {
+            JValue::Object(o) => {
+                let mut members = JObject::new();
+                for (k, v) in o.iter() {
+                    members.insert(k.clone(), self.entry(v)?);
+                }
+                single("object", JValue::Object(members))
+            }
+            JValue::Array(a) => {
+                let elements = a.iter().map(|v| self.entry(v)).collect::<Result<_, _>>()?;
+                single("array", JValue::Array(elements))
+            }
+            scalar => single("value", scalar.clone())
+        };
+        let mut text = String::new();
+        write_chunk(&chunk, &mut text);
+        let chunk = text.into_bytes();
+        let hash = ContentHash::of(&chunk);
+        if !self.backend.contains(&hash)? {
+            self.backend.put(hash, chunk)?;
+        }
+        Ok(hash)
+    }
+
+    /// Reassembles the document whose root chunk has the hash `hash`. Fails with
+    /// [`ErrorKind::NotFound`] if a chunk is missing and with [`ErrorKind::Validation`] if a
+    /// chunk does not match its hash.
+    pub fn get(&self, hash: &ContentHash) -> Result<JValue, JsonError> {
+        let chunk = self.backend.get(hash)?
+            .ok_or_else(|| JsonError::new(ErrorKind::NotFound, format!("No chunk {} in the store", hash)))?;
+        if ContentHash::of(&chunk) != *hash {
+            return Err(JsonError::new(ErrorKind::Validation, format!("The chunk {} does not match its hash", hash)));
+        }
+        let invalid = |message: String| JsonError::new(ErrorKind::InvalidType, format!("Invalid chunk {}: {}", hash, message));
+        let text = String::from_utf8(chunk).map_err(|_| invalid("not UTF-8".to_string()))?;
+        let chunk = parse(&text).map_err(|e| invalid(e.to_string()))?;
+        match unwrap_single(&chunk) {
+            Some(("object", JValue::Object(members))) => {
+                let mut object = JObject::new();
+                for (k, v) in members.iter() {
+                    object.insert(k.clone(), self.resolve(v, hash)?);
+                }
+                Ok(JValue::Object(object))
+            }
+            Some(("array", JValue::Array(elements))) => {
+                elements.iter().map(|v| self.resolve(v, hash)).collect::<Result<_, _>>().map(JValue::Array)
+            }
+            Some(("value", scalar)) => Ok(scalar.clone()),
+            _ => Err(invalid(format!("expected an object, an array or a value, found {}", describe(&chunk))))
+        }
+    }
+
+    /// Stores a nested container and references it, or inlines a scalar
+    fn entry(&mut self, value: &JValue) -> Result<JValue, JsonError> {
+        match value {
+            JValue::Object(_) | JValue::Array(_) => {
+                let hash = self.put(value)?;
+                Ok(single("hash", JValue::String(JString::from(hash.to_string().as_str()))))
+            }
+            scalar => Ok(single("value", scalar.clone()))
+        }
+    }
+
+    fn resolve(&self, entry: &JValue, parent: &ContentHash) -> Result<JValue, JsonError> {
+        match unwrap_single(entry) {
+            Some(("value", scalar)) => Ok(scalar.clone()),
+            Some(("hash", JValue::String(hash))) => self.get(&hash.as_str().parse()?),
+            _ => Err(JsonError::new(ErrorKind::InvalidType,
+                                    format!("Invalid chunk {}: the entry {} is neither a value nor a hash", parent, entry)))
+        }
+    }
+}
+
+fn single(key: &str, value: JValue) -> JValue {
+    let mut object = JObject::new();
+    object.insert(key.to_string(), value);
+    JValue::Object(object)
+}
+
+/// Writes a chunk with its keys sorted, so equal chunks are written the same, and its numbers
+/// as written
+fn write_chunk(value: &JValue, out: &mut String) {
+    match value {
+        JValue::Object(o) => {
+            let mut members: Vec<(&String, &JValue)> = o.iter().collect();
+            members.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (k, v)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serialize_string(k));
+                out.push(':');
+                write_chunk(v, out);
+            }
+            out.push('}');
+        }
+        JValue::Array(a) => {
+            out.push('[');
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_chunk(v, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.serialize())
+    }
+}
+
+/// Returns the only member of an object
+fn unwrap_single(value: &JValue) -> Option<(&str, &JValue)> {
+    match value {
+        JValue::Object(o) if o.len() == 1 => o.iter().next().map(|(k, v)| (k.as_str(), v)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::store::{Backend, ContentHash, DirectoryBackend, MemoryBackend, Store};
+
+    #[test]
+    fn test_round_trip() {
+        let mut store = Store::new(MemoryBackend::new());
+        for value in [json!("null"), json!("\"text\""), json!("[]"), json!("{}"),
+                      json!("{\"a\": [[1, 2], [1, 2], {\"b\": null}], \"c\": {\"b\": null}}")] {
+            let hash = store.put(&value).unwrap();
+            assert_eq!(Ok(value), store.get(&hash));
+        }
+        // The repeated [1, 2] and {"b": null} are stored once
+        assert_eq!(4 + 4, store.backend().len());
+    }
+
+    #[test]
+    fn test_numbers_are_stored_as_written() {
+        use crate::serializer::Serialize;
+        let mut store = Store::new(MemoryBackend::new());
+        let value = json!("{\"id\": 12345678901234567891, \"price\": 0.10000000000000000001, \"big\": 1e400, \
+                           \"list\": [-98765432109876543210.5]}");
+        let hash = store.put(&value).unwrap();
+        let stored = store.get(&hash).unwrap();
+        for key in ["id", "price", "big", "list"] {
+            assert_eq!(value[key].serialize(), stored[key].serialize(), "{}", key);
+        }
+        assert_eq!("12345678901234567891", stored["id"].serialize());
+        // Numbers equal as doubles are different chunks
+        let other = store.put(&json!("[12345678901234567892]")).unwrap();
+        assert_ne!(store.put(&json!("[12345678901234567891]")).unwrap(), other);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let hash = ContentHash::of(b"{}");
+        assert_eq!("44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a", hash.to_string());
+        assert_eq!(Ok(hash), hash.to_string().parse());
+        assert_eq!(ErrorKind::InvalidType, "44136f".parse::<ContentHash>().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_corrupt_and_missing_chunks() {
+        let mut store = Store::new(MemoryBackend::new());
+        let root = store.put(&json!("{\"a\": {\"b\": 1}}")).unwrap();
+        let child = store.put(&json!("{\"b\": 1}")).unwrap();
+        let mut backend = store.into_backend();
+        backend.put(child, b"{\"value\":2}".to_vec()).unwrap();
+        let mut store = Store::new(backend);
+        assert_eq!(ErrorKind::Validation, store.get(&root).unwrap_err().kind());
+
+        let bogus = b"{\"hash\":1}".to_vec();
+        let bogus_hash = ContentHash::of(&bogus);
+        let mut backend = store.into_backend();
+        backend.put(bogus_hash, bogus).unwrap();
+        store = Store::new(backend);
+        assert_eq!(ErrorKind::InvalidType, store.get(&bogus_hash).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, Store::new(MemoryBackend::new()).get(&root).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_directory_backend() {
+        let dir = std::env::temp_dir().join(format!("json-store-test-{}", std::process::id()));
+        let mut store = Store::new(DirectoryBackend::new(&dir).unwrap());
+        let value = json!("{\"list\": [1, {\"x\": true}]}");
+        let hash = store.put(&value).unwrap();
+        assert!(store.backend().contains(&hash).unwrap());
+
+        let reopened = Store::new(DirectoryBackend::new(&dir).unwrap());
+        assert_eq!(Ok(value), reopened.get(&hash));
+        assert_eq!(3, std::fs::read_dir(&dir).unwrap().count());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,451 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Streaming extraction of values from documents too large to parse into memory.
+
+use std::io::{ErrorKind as IoErrorKind, Read};
+
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::jsonpath::{JsonPath, PathElement};
+use crate::parser::parse;
+use crate::parser::{unescape, SurrogatePolicy};
+use crate::pointer::{array_index, parse_pointer};
+
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Scans the document read from `reader` and passes each value at `path` to `sink`, in document
+/// order. Only the selected values are parsed; everything else is skipped without being built.
+///
+/// `path` is either a JSONPath expression starting with `$`, or a JSON Pointer in which a `*`
+/// token matches any key or index, such as `/results/*/id`. The values inside a selected value
+/// are not selected again, and negative JSONPath indices never match, as the lengths of the
+/// arrays are not known while they are scanned. Returns the number of values selected.
+///
+/// ```
+/// # use json::json;
+/// # use json::stream::extract;
+/// let document = "{\"results\": [{\"id\": 1, \"tags\": [\"a\"]}, {\"id\": 2}], \"total\": 2}";
+/// let mut ids = Vec::new();
+/// assert_eq!(Ok(2), extract(document.as_bytes(), "/results/*/id", |id| ids.push(id)));
+/// assert_eq!(vec![json!("1"), json!("2")], ids);
+///
+/// let mut tags = Vec::new();
+/// extract(document.as_bytes(), "$..tags[0]", |tag| tags.push(tag)).unwrap();
+/// assert_eq!(vec![json!("\"a\"")], tags);
+/// ```
+pub fn extract<R: Read, F: FnMut(JValue)>(reader: R, path: &str, sink: F) -> Result<usize, JsonError> {
+    let mut scanner = Scanner::new(reader);
+    let mut extractor = Extractor { pattern: Pattern::parse(path)?, sink, count: 0, first_scalar: false };
+    extractor.value(&mut scanner, &mut Vec::new())?;
+    scanner.end_of_document()?;
+    Ok(extractor.count)
+}
+
+/// Scans `input` for the first scalar value at `path`, which is given as to [`extract`], and
+/// stops as soon as it is found, so that one field is read from a large body without scanning
+/// the rest of it. Objects and arrays at `path` are skipped. Returns `None` if there is no
+/// scalar at `path`; the input is then checked to the end.
+///
+/// ```
+/// # use json::json;
+/// # use json::stream::first_scalar;
+/// let body = "{\"status\": \"ok\", \"checks\": [{\"id\": {}}, {\"id\": 7}], \"payload\": [1, 2, 3]}";
+/// assert_eq!(Ok(Some(json!("\"ok\""))), first_scalar(body, "/status"));
+/// assert_eq!(Ok(Some(json!("7"))), first_scalar(body, "/checks/*/id"));
+/// assert_eq!(Ok(None), first_scalar(body, "/payload"));
+/// // Nothing after the field is scanned
+/// assert_eq!(Ok(Some(json!("1"))), first_scalar("{\"a\": 1, oops", "/a"));
+/// ```
+pub fn first_scalar(input: &str, path: &str) -> Result<Option<JValue>, JsonError> {
+    let mut found = None;
+    let mut scanner = Scanner::new(input.as_bytes());
+    let mut extractor = Extractor { pattern: Pattern::parse(path)?, sink: |value| found = Some(value), count: 0,
+                                    first_scalar: true };
+    extractor.value(&mut scanner, &mut Vec::new())?;
+    if !extractor.done() {
+        scanner.end_of_document()?;
+    }
+    Ok(found)
+}
+
+enum Pattern {
+    Path(JsonPath),
+    /// The tokens of a pointer, [`None`] standing for `*`
+    Pointer(Vec<Option<String>>),
+}
+
+impl Pattern {
+    fn parse(path: &str) -> Result<Pattern, JsonError> {
+        Ok(if path.starts_with('$') {
+            Pattern::Path(JsonPath::parse(path)?)
+        } else {
+            Pattern::Pointer(parse_pointer(path)?.into_iter()
+                .map(|token| if token == "*" { None } else { Some(token) }).collect())
+        })
+    }
+
+    fn matches(&self, location: &[PathElement]) -> bool {
+        match self {
+            Pattern::Path(path) => path.matches(location),
+            Pattern::Pointer(tokens) => tokens.len() == location.len() && self.prefix_matches(location)
+        }
+    }
+
+    /// Whether a value inside the value at `location` may be selected
+    fn may_match_inside(&self, location: &[PathElement]) -> bool {
+        match self {
+            Pattern::Path(_) => true,
+            Pattern::Pointer(tokens) => tokens.len() > location.len() && self.prefix_matches(location)
+        }
+    }
+
+    fn prefix_matches(&self, location: &[PathElement]) -> bool {
+        match self {
+            Pattern::Path(_) => true,
+            Pattern::Pointer(tokens) => tokens.iter().zip(location).all(|(token, element)| {
+                match (token, element) {
+                    (None, _) => true,
+                    (Some(token), PathElement::Key(key)) => token == key,
+                    (Some(token), PathElement::Index(index)) => array_index(token) == Some(*index)
+                }
+            })
+        }
+    }
+}
+
+struct Extractor<F> {
+    pattern: Pattern,
+    sink: F,
+    count: usize,
+    /// Select only scalars, and stop after the first
+    first_scalar: bool,
+}
+
+impl<F: FnMut(JValue)> Extractor<F> {
+    fn done(&self) -> bool {
+        self.first_scalar && self.count > 0
+    }
+
+    fn value<R: Read>(&mut self, scanner: &mut Scanner<R>, location: &mut Vec<PathElement>)
+                      -> Result<(), JsonError> {
+        scanner.skip_whitespace()?;
+        if self.first_scalar && matches!(scanner.peek()?, Some(b'{' | b'[')) && self.pattern.matches(location) {
+            return scanner.skip_value();
+        }
+        if self.pattern.matches(location) {
+            let (_, value) = scanner.parse_value()?;
+            (self.sink)(value);
+            self.count += 1;
+            return Ok(());
+        }
+        if !self.pattern.may_match_inside(location) {
+            return scanner.skip_value();
+        }
+        match scanner.peek()? {
+            Some(b'{') => {
+                scanner.bump()?;
+                scanner.skip_whitespace()?;
+                if scanner.peek()? == Some(b'}') {
+                    return scanner.bump();
+                }
+                loop {
+                    scanner.skip_whitespace()?;
+                    let key = scanner.key()?;
+                    scanner.skip_whitespace()?;
+                    scanner.expect(b':')?;
+                    location.push(PathElement::Key(key));
+                    self.value(scanner, location)?;
+                    location.pop();
+                    if self.done() {
+                        return Ok(());
+                    }
+                    scanner.skip_whitespace()?;
+                    match scanner.next()? {
+                        b',' => (),
+                        b'}' => return Ok(()),
+                        b => return Err(scanner.unexpected(b))
+                    }
+                }
+            }
+            Some(b'[') => {
+                scanner.bump()?;
+                scanner.skip_whitespace()?;
+                if scanner.peek()? == Some(b']') {
+                    return scanner.bump();
+                }
+                let mut index = 0;
+                loop {
+                    location.push(PathElement::Index(index));
+                    self.value(scanner, location)?;
+                    location.pop();
+                    if self.done() {
+                        return Ok(());
+                    }
+                    index += 1;
+                    scanner.skip_whitespace()?;
+                    match scanner.next()? {
+                        b',' => (),
+                        b']' => return Ok(()),
+                        b => return Err(scanner.unexpected(b))
+                    }
+                }
+            }
+            _ => scanner.skip_value()
+        }
+    }
+}
+
+/// A buffered byte reader that copies the bytes it consumes while capturing
+pub(crate) struct Scanner<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    position: usize,
+    end: usize,
+    /// The offset in the document of the next byte
+    offset: usize,
+    capture: Option<Vec<u8>>,
+}
+
+impl<R: Read> Scanner<R> {
+    pub(crate) fn new(reader: R) -> Scanner<R> {
+        Scanner { reader, buffer: vec![0; BUFFER_SIZE], position: 0, end: 0, offset: 0, capture: None }
+    }
+
+    /// Scans the next value and parses it, returning its text as well
+    pub(crate) fn parse_value(&mut self) -> Result<(String, JValue), JsonError> {
+        self.skip_whitespace()?;
+        let start = self.offset;
+        self.capture = Some(Vec::new());
+        let skipped = self.skip_value();
+        let captured = self.capture.take().unwrap_or_default();
+        skipped?;
+        let text = String::from_utf8(captured).map_err(|_| JsonError::new(
+            ErrorKind::Syntax, format!("Invalid UTF-8 in the value at byte {}", start)))?;
+        let value = parse(&text).map_err(|e| JsonError::new(
+            e.kind(), format!("{} at byte {}", e.message(), start + e.offset().unwrap_or(0))))?;
+        Ok((text, value))
+    }
+
+    /// Checks that only whitespace is left
+    pub(crate) fn end_of_document(&mut self) -> Result<(), JsonError> {
+        self.skip_whitespace()?;
+        match self.peek()? {
+            None => Ok(()),
+            Some(b) => {
+                self.bump()?;
+                Err(self.unexpected(b))
+            }
+        }
+    }
+
+    pub(crate) fn peek(&mut self) -> Result<Option<u8>, JsonError> {
+        while self.position == self.end {
+            match self.reader.read(&mut self.buffer) {
+                Ok(0) => return Ok(None),
+                Ok(n) => {
+                    self.position = 0;
+                    self.end = n;
+                }
+                Err(e) if e.kind() == IoErrorKind::Interrupted => (),
+                Err(e) => return Err(JsonError::new(
+                    ErrorKind::Io, format!("Can not read the document: {}", e)))
+            }
+        }
+        Ok(Some(self.buffer[self.position]))
+    }
+
+    pub(crate) fn next(&mut self) -> Result<u8, JsonError> {
+        match self.peek()? {
+            Some(b) => {
+                self.position += 1;
+                self.offset += 1;
+                if let Some(capture) = &mut self.capture {
+                    capture.push(b);
+                }
+                Ok(b)
+            }
+            None => Err(JsonError::new(
+                ErrorKind::Syntax, format!("Unexpected end of the document at byte {}", self.offset)))
+        }
+    }
+
+    pub(crate) fn bump(&mut self) -> Result<(), JsonError> {
+        self.next().map(|_| ())
+    }
+
+    pub(crate) fn expect(&mut self, expected: u8) -> Result<(), JsonError> {
+        match self.next()? {
+            b if b == expected => Ok(()),
+            b => Err(self.unexpected(b))
+        }
+    }
+
+    /// The offset in the document of the next byte
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The error for the byte just consumed, or the next one if nothing was consumed yet
+    pub(crate) fn unexpected(&self, b: u8) -> JsonError {
+        JsonError::new(ErrorKind::Syntax, format!("Unexpected character {} at byte {}",
+                                                  char::from(b).escape_default(),
+                                                  self.offset.saturating_sub(1)))
+    }
+
+    pub(crate) fn skip_whitespace(&mut self) -> Result<(), JsonError> {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.peek()? {
+            self.bump()?;
+        }
+        Ok(())
+    }
+
+    /// Reads an object key, replacing its escape sequences as the parser does
+    pub(crate) fn key(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut key = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    key.push(b'\\');
+                    key.push(self.next()?);
+                }
+                b => key.push(b)
+            }
+        }
+        let key = String::from_utf8(key).map_err(|_| JsonError::new(
+            ErrorKind::Syntax, format!("Invalid UTF-8 in a key before byte {}", self.offset)))?;
+        if !key.contains('\\') {
+            return Ok(key);
+        }
+        unescape(&key, '"', SurrogatePolicy::Error, |_, message, _| Err(JsonError::new(
+            ErrorKind::Syntax, format!("{} before byte {}", message, self.offset))))
+    }
+
+    fn skip_string(&mut self) -> Result<(), JsonError> {
+        loop {
+            match self.next()? {
+                b'"' => return Ok(()),
+                b'\\' => self.bump()?,
+                _ => ()
+            }
+        }
+    }
+
+    /// Skips a value, checking only the nesting of its brackets. The value is fully checked
+    /// when it is captured and parsed.
+    pub(crate) fn skip_value(&mut self) -> Result<(), JsonError> {
+        self.skip_whitespace()?;
+        let mut closers = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => self.skip_string()?,
+                b'{' => closers.push(b'}'),
+                b'[' => closers.push(b']'),
+                b @ (b'}' | b']') if closers.last() != Some(&b) => return Err(self.unexpected(b)),
+                b'}' | b']' => {
+                    closers.pop();
+                }
+                b @ (b',' | b':') if closers.is_empty() => return Err(self.unexpected(b)),
+                _ => ()
+            }
+            if closers.is_empty() {
+                // A scalar ends at the next delimiter
+                while let Some(b) = self.peek()? {
+                    if matches!(b, b' ' | b'\n' | b'\r' | b'\t' | b',' | b']' | b'}' | b':'
+                                   | b'"' | b'[' | b'{') {
+                        break;
+                    }
+                    self.bump()?;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_structures::JValue;
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::stream::{extract, first_scalar};
+
+    fn extract_all(document: &str, path: &str) -> Vec<JValue> {
+        let mut values = Vec::new();
+        extract(document.as_bytes(), path, |value| values.push(value)).unwrap();
+        values
+    }
+
+    #[test]
+    fn test_extract() {
+        let document = " {\"a\": [{\"b\": 1}, {\"b\": [true, null]}, {\"c\": \"}\"}], \"b\": \"x\" } ";
+        assert_eq!(vec![json!("1"), json!("[true, null]")], extract_all(document, "/a/*/b"));
+        assert_eq!(vec![json!("null")], extract_all(document, "/a/1/b/1"));
+        assert_eq!(vec![json!("1"), json!("[true, null]"), json!("\"x\"")], extract_all(document, "$..b"));
+        assert_eq!(vec![json!("{\"c\": \"}\"}")], extract_all(document, "$.a[2]"));
+        assert!(extract_all(document, "/a/*/d").is_empty());
+        assert_eq!(vec![json!("\"x\"")], extract_all(document, "/b"));
+        assert_eq!(vec![json!("42")], extract_all("42", ""));
+        assert_eq!(vec![json!("2")], extract_all("{\"a\\u002fb\": 1, \"a/\\\"b\": 2}", "/a~1\"b"));
+    }
+
+    #[test]
+    fn test_extract_small_reads() {
+        struct OneByte<'a>(&'a [u8]);
+        impl std::io::Read for OneByte<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() || buf.is_empty() {
+                    return Ok(0);
+                }
+                buf[0] = self.0[0];
+                self.0 = &self.0[1..];
+                Ok(1)
+            }
+        }
+        let mut ids = Vec::new();
+        let count = extract(OneByte(b"[{\"id\": 10}, {\"id\": 20}]"), "$[*].id", |id| ids.push(id)).unwrap();
+        assert_eq!(2, count);
+        assert_eq!(vec![json!("10"), json!("20")], ids);
+    }
+
+    #[test]
+    fn test_first_scalar() {
+        let document = "[{\"a\": [1]}, {\"a\": null}, {\"a\": 3}]";
+        assert_eq!(Ok(Some(json!("null"))), first_scalar(document, "/*/a"));
+        assert_eq!(Ok(Some(json!("1"))), first_scalar(document, "$..a[0]"));
+        assert_eq!(Ok(None), first_scalar(document, "/*/b"));
+        assert_eq!(ErrorKind::Syntax, first_scalar("", "/a").unwrap_err().kind());
+        assert_eq!(ErrorKind::Syntax, first_scalar("[{\"b\": }]", "/0/a").unwrap_err().kind());
+        assert_eq!(ErrorKind::Syntax, first_scalar("{\"a\": [1]} x", "/a").unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidPointer, first_scalar("{}", "a").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_extract_errors() {
+        let error = extract("{\"a\": [1, 2}".as_bytes(), "/a/0", |_| ()).unwrap_err();
+        assert_eq!(ErrorKind::Syntax, error.kind());
+        assert_eq!("Unexpected character } at byte 11", error.message());
+        assert_eq!(ErrorKind::Syntax, extract("{\"a\": tru}".as_bytes(), "/a", |_| ()).unwrap_err().kind());
+        assert_eq!(ErrorKind::Syntax, extract("[1] 2".as_bytes(), "/0", |_| ()).unwrap_err().kind());
+        assert_eq!(ErrorKind::Syntax, extract("{\"a\": ".as_bytes(), "/b", |_| ()).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidPointer, extract("[]".as_bytes(), "a", |_| ()).unwrap_err().kind());
+        assert_eq!("Invalid escape '\\x' in a string before byte 5",
+                   extract("{\"\\x\": 1}".as_bytes(), "/a", |_| ()).unwrap_err().message());
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Substitution of `${var}` placeholders in the string values of a JSON document.
+//!
+//! A placeholder is `${` followed by a variable name and `}`. `$${` produces a literal `${`,
+//! and a `$` not followed by `{` is kept as is. Object keys are left untouched.
+//!
+//! ```
+//! # use std::collections::HashMap;
+//! # use json::json;
+//! # use json::template;
+//! let config = json!("{\"url\": \"https://${host}:${port}/\", \"note\": \"costs $$${price}\"}");
+//! let mut vars = HashMap::new();
+//! vars.insert("host".to_string(), "example.com".to_string());
+//! vars.insert("port".to_string(), "8080".to_string());
+//! vars.insert("price".to_string(), "5".to_string());
+//!
+//! let rendered = template::render(&config, &vars).unwrap();
+//! assert_eq!(json!("{\"url\": \"https://example.com:8080/\", \"note\": \"costs $${price}\"}"), rendered);
+//! ```
+
+use std::collections::HashMap;
+
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::transform::push_token;
+
+/// Substitutes the placeholders with the values of `vars`. Fails with
+/// [`ErrorKind::NotFound`] on a variable missing from `vars`.
+pub fn render(value: &JValue, vars: &HashMap<String, String>) -> Result<JValue, JsonError> {
+    render_with(value, |name| vars.get(name).cloned())
+}
+
+/// Substitutes the placeholders with the values of the environment variables of the process.
+/// Fails with [`ErrorKind::NotFound`] on an unset variable.
+pub fn render_env(value: &JValue) -> Result<JValue, JsonError> {
+    render_with(value, |name| std::env::var(name).ok())
+}
+
+/// Substitutes the placeholders with the values returned by `resolve`, failing with
+/// [`ErrorKind::NotFound`] when it returns `None` and with [`ErrorKind::Syntax`] on a
+/// placeholder without a closing `}`. Errors name the JSON Pointer of the offending string.
+pub fn render_with<F: FnMut(&str) -> Option<String>>(value: &JValue, mut resolve: F)
+                                                     -> Result<JValue, JsonError> {
+    render_value(value, &mut String::new(), &mut resolve)
+}
+
+fn render_value<F: FnMut(&str) -> Option<String>>(value: &JValue, path: &mut String, resolve: &mut F)
+                                                  -> Result<JValue, JsonError> {
+    let len = path.len();
+    Ok(match value {
+        JValue::Object(o) => {
+            let mut obj = JObject::new();
+            for (k, v) in o {
+                push_token(path, k);
+                let v = render_value(v, path, resolve)?;
+                path.truncate(len);
+                obj.insert(k.clone(), v);
+            }
+            JValue::Object(obj)
+        }
+        JValue::Array(a) => {
+            let mut vec = Vec::with_capacity(a.len());
+            for (i, v) in a.iter().enumerate() {
+                push_token(path, &i.to_string());
+                vec.push(render_value(v, path, resolve)?);
+                path.truncate(len);
+            }
+            JValue::Array(vec)
+        }
+        JValue::String(s) => JValue::String(JString::from(substitute(s, path, resolve)?)),
+        other => other.clone(),
+    })
+}
+
+fn substitute<F: FnMut(&str) -> Option<String>>(s: &str, path: &str, resolve: &mut F)
+                                                -> Result<String, JsonError> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("$${") {
+            result.push_str("${");
+            rest = &rest[3..];
+        } else if let Some(placeholder) = rest.strip_prefix("${") {
+            let end = placeholder.find('}').ok_or_else(|| JsonError::new(
+                ErrorKind::Syntax, format!("Unterminated placeholder at {}", path)))?;
+            let name = &placeholder[..end];
+            let value = resolve(name).ok_or_else(|| JsonError::new(
+                ErrorKind::NotFound, format!("Unresolved variable {} at {}", name, path)))?;
+            result.push_str(&value);
+            rest = &placeholder[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::template::{render, render_with};
+
+    #[test]
+    fn test_render() {
+        let mut vars = HashMap::new();
+        vars.insert("a".to_string(), "1".to_string());
+        vars.insert("b".to_string(), "${a}".to_string());
+        let template = json!("[\"${a}${b}\", \"$5 $$ ${a}$${a}\", {\"${a}\": \"x${a}y\"}, 3]");
+        assert_eq!(json!("[\"1${a}\", \"$5 $$ 1${a}\", {\"${a}\": \"x1y\"}, 3]"),
+                   render(&template, &vars).unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        let template = json!("{\"a\": [\"ok\", \"${missing}\"]}");
+        let error = render_with(&template, |_| None).unwrap_err();
+        assert_eq!(ErrorKind::NotFound, error.kind());
+        assert_eq!("Unresolved variable missing at /a/1", error.message());
+
+        let error = render_with(&json!("[\"${open\"]"), |_| Some(String::new())).unwrap_err();
+        assert_eq!(ErrorKind::Syntax, error.kind());
+        assert_eq!("Unterminated placeholder at /0", error.message());
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Tools for working with documents too large to parse into memory.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{ErrorKind, JsonError};
+use crate::stream::Scanner;
+
+/// The bound on the size of a shard written by [`split_array`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardLimit {
+    /// At most this many elements per shard
+    Items(usize),
+    /// At most this many bytes per shard, unless a single element is larger
+    Bytes(usize),
+}
+
+/// What [`merge_objects`] does with a key found in more than one file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+    /// Fail with a [`ErrorKind::Validation`] error
+    Error,
+    /// Keep the member of the first file with the key
+    First,
+    /// Keep the member of the last file with the key
+    Last,
+}
+
+fn write_error(path: &Path, e: std::io::Error) -> JsonError {
+    JsonError::new(ErrorKind::Io, format!("Can not write {}: {}", path.display(), e))
+}
+
+fn open(path: &Path) -> Result<Scanner<BufReader<File>>, JsonError> {
+    File::open(path).map(|file| Scanner::new(BufReader::new(file))).map_err(|e| JsonError::new(
+        ErrorKind::Io, format!("Can not read {}: {}", path.display(), e)))
+}
+
+fn in_file(path: &Path, e: JsonError) -> JsonError {
+    match e.kind() {
+        ErrorKind::Io => e,
+        kind => JsonError::new(kind, format!("{} in {}", e.message(), path.display()))
+    }
+}
+
+/// Calls `f` with the scanner positioned at each element of the top-level array
+fn elements<R: Read, F>(scanner: &mut Scanner<R>, mut f: F) -> Result<(), JsonError>
+    where F: FnMut(&mut Scanner<R>) -> Result<(), JsonError> {
+    scanner.skip_whitespace()?;
+    scanner.expect(b'[')?;
+    scanner.skip_whitespace()?;
+    if scanner.peek()? == Some(b']') {
+        scanner.bump()?;
+    } else {
+        loop {
+            f(scanner)?;
+            scanner.skip_whitespace()?;
+            match scanner.next()? {
+                b',' => (),
+                b']' => break,
+                b => return Err(scanner.unexpected(b))
+            }
+        }
+    }
+    scanner.end_of_document()
+}
+
+/// Calls `f` with each key of the top-level object and the scanner positioned at its value
+fn members<R: Read, F>(scanner: &mut Scanner<R>, mut f: F) -> Result<(), JsonError>
+    where F: FnMut(&mut Scanner<R>, String) -> Result<(), JsonError> {
+    scanner.skip_whitespace()?;
+    scanner.expect(b'{')?;
+    scanner.skip_whitespace()?;
+    if scanner.peek()? == Some(b'}') {
+        scanner.bump()?;
+    } else {
+        loop {
+            scanner.skip_whitespace()?;
+            let key = scanner.key()?;
+            scanner.skip_whitespace()?;
+            scanner.expect(b':')?;
+            f(scanner, key)?;
+            scanner.skip_whitespace()?;
+            match scanner.next()? {
+                b',' => (),
+                b'}' => break,
+                b => return Err(scanner.unexpected(b))
+            }
+        }
+    }
+    scanner.end_of_document()
+}
+
+/// Streams the top-level array read from `reader` into shard files in `out_dir`, each holding
+/// an array of consecutive elements within `limit`. The elements are copied as they are written
+/// in the input, and only one element is held in memory at a time.
+///
+/// The shards are named `shard-00000.json`, `shard-00001.json`, ... and their paths are returned
+/// in order. An empty array writes no shards. `out_dir` is created if it does not exist.
+///
+/// ```
+/// # use json::tools::{split_array, ShardLimit};
+/// let dir = std::env::temp_dir().join(format!("json-split-doc-{}", std::process::id()));
+/// let shards = split_array("[1, 2, 3, {\"a\": [4]}, 5]".as_bytes(), ShardLimit::Items(2), &dir).unwrap();
+/// assert_eq!(3, shards.len());
+/// assert_eq!("[3,{\"a\": [4]}]", std::fs::read_to_string(&shards[1]).unwrap());
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+pub fn split_array<R: Read, P: AsRef<Path>>(reader: R, limit: ShardLimit, out_dir: P)
+                                            -> Result<Vec<PathBuf>, JsonError> {
+    let out_dir = out_dir.as_ref();
+    fs::create_dir_all(out_dir).map_err(|e| write_error(out_dir, e))?;
+    let mut shards = Splitter { out_dir, limit, paths: Vec::new(), current: None, items: 0, bytes: 0 };
+    elements(&mut Scanner::new(reader), |scanner| {
+        let (text, _) = scanner.parse_value()?;
+        shards.push(&text)
+    })?;
+    shards.finish()?;
+    Ok(shards.paths)
+}
+
+/// Writes to `writer` one array of the elements of the top-level arrays in the files at `paths`,
+/// in order, streaming them one element at a time. This reassembles the shards of
+/// [`split_array`]. Returns the number of elements written.
+///
+/// ```
+/// # use json::tools::{concat_arrays, split_array, ShardLimit};
+/// let dir = std::env::temp_dir().join(format!("json-concat-doc-{}", std::process::id()));
+/// let shards = split_array("[1, 2, 3]".as_bytes(), ShardLimit::Items(2), &dir).unwrap();
+/// let mut out = Vec::new();
+/// assert_eq!(Ok(3), concat_arrays(&shards, &mut out));
+/// assert_eq!("[1,2,3]", String::from_utf8(out).unwrap());
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+pub fn concat_arrays<P: AsRef<Path>, W: Write>(paths: &[P], writer: W) -> Result<usize, JsonError> {
+    let mut writer = BufWriter::new(writer);
+    let mut count = 0;
+    let output = Path::new("the output");
+    writer.write_all(b"[").map_err(|e| write_error(output, e))?;
+    for path in paths {
+        let path = path.as_ref();
+        elements(&mut open(path)?, |scanner| {
+            let (text, _) = scanner.parse_value()?;
+            let separator: &[u8] = if count == 0 { b"" } else { b"," };
+            count += 1;
+            writer.write_all(separator).and_then(|_| writer.write_all(text.as_bytes()))
+                .map_err(|e| write_error(output, e))
+        }).map_err(|e| in_file(path, e))?;
+    }
+    writer.write_all(b"]").and_then(|_| writer.flush()).map_err(|e| write_error(output, e))?;
+    Ok(count)
+}
+
+/// Writes to `writer` one object of the members of the top-level objects in the files at
+/// `paths`, in order, streaming them one member at a time. Keys found in more than one file are
+/// treated according to `policy`; only the keys are held in memory. Returns the number of
+/// members written.
+///
+/// ```
+/// # use json::tools::{merge_objects, DuplicateKeyPolicy};
+/// let dir = std::env::temp_dir().join(format!("json-merge-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("a.json"), "{\"a\": 1, \"b\": 2}").unwrap();
+/// std::fs::write(dir.join("b.json"), "{\"b\": 3, \"c\": 4}").unwrap();
+/// let paths = [dir.join("a.json"), dir.join("b.json")];
+/// let mut out = Vec::new();
+/// assert_eq!(Ok(3), merge_objects(&paths, DuplicateKeyPolicy::Last, &mut out));
+/// assert_eq!("{\"a\":1,\"b\":3,\"c\":4}", String::from_utf8(out).unwrap());
+/// assert!(merge_objects(&paths, DuplicateKeyPolicy::Error, &mut Vec::new()).is_err());
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+pub fn merge_objects<P: AsRef<Path>, W: Write>(paths: &[P], policy: DuplicateKeyPolicy, writer: W)
+                                               -> Result<usize, JsonError> {
+    // With the last member winning, the files are scanned once to find the last file of each key
+    let mut last_file = HashMap::new();
+    if policy == DuplicateKeyPolicy::Last {
+        for (i, path) in paths.iter().enumerate() {
+            let path = path.as_ref();
+            members(&mut open(path)?, |scanner, key| {
+                last_file.insert(key, i);
+                scanner.skip_value()
+            }).map_err(|e| in_file(path, e))?;
+        }
+    }
+    let mut writer = BufWriter::new(writer);
+    let mut written = HashSet::new();
+    let output = Path::new("the output");
+    writer.write_all(b"{").map_err(|e| write_error(output, e))?;
+    for (i, path) in paths.iter().enumerate() {
+        let path = path.as_ref();
+        members(&mut open(path)?, |scanner, key| {
+            let keep = match policy {
+                DuplicateKeyPolicy::Error if written.contains(&key) => return Err(JsonError::new(
+                    ErrorKind::Validation, format!("Duplicate key {}", key))),
+                DuplicateKeyPolicy::Last => last_file.get(&key) == Some(&i) && !written.contains(&key),
+                _ => !written.contains(&key),
+            };
+            if !keep {
+                return scanner.skip_value();
+            }
+            let (text, _) = scanner.parse_value()?;
+            let separator: &[u8] = if written.is_empty() { b"\"" } else { b",\"" };
+            writer.write_all(separator)
+                .and_then(|_| writer.write_all(key.as_bytes()))
+                .and_then(|_| writer.write_all(b"\":"))
+                .and_then(|_| writer.write_all(text.as_bytes()))
+                .map_err(|e| write_error(output, e))?;
+            written.insert(key);
+            Ok(())
+        }).map_err(|e| in_file(path, e))?;
+    }
+    writer.write_all(b"}").and_then(|_| writer.flush()).map_err(|e| write_error(output, e))?;
+    Ok(written.len())
+}
+
+struct Splitter<'a> {
+    out_dir: &'a Path,
+    limit: ShardLimit,
+    paths: Vec<PathBuf>,
+    current: Option<BufWriter<File>>,
+    items: usize,
+    bytes: usize,
+}
+
+impl Splitter<'_> {
+    fn push(&mut self, element: &str) -> Result<(), JsonError> {
+        let full = match self.limit {
+            ShardLimit::Items(max) => self.items >= max.max(1),
+            // The brackets and the comma before the element count too
+            ShardLimit::Bytes(max) => self.items > 0 && self.bytes + 1 + element.len() > max,
+        };
+        if full {
+            self.finish()?;
+        }
+        let separator = if self.items == 0 { "[" } else { "," };
+        let writer = match &mut self.current {
+            Some(writer) => writer,
+            None => {
+                let path = self.out_dir.join(format!("shard-{:05}.json", self.paths.len()));
+                let file = File::create(&path).map_err(|e| write_error(&path, e))?;
+                self.paths.push(path);
+                self.bytes = 2;
+                self.current.insert(BufWriter::new(file))
+            }
+        };
+        let path = &self.paths[self.paths.len() - 1];
+        writer.write_all(separator.as_bytes()).and_then(|_| writer.write_all(element.as_bytes()))
+            .map_err(|e| write_error(path, e))?;
+        self.bytes += element.len() + if self.items == 0 { 0 } else { 1 };
+        self.items += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), JsonError> {
+        if let Some(mut writer) = self.current.take() {
+            let path = &self.paths[self.paths.len() - 1];
+            writer.write_all(b"]").and_then(|_| writer.flush()).map_err(|e| write_error(path, e))?;
+        }
+        self.items = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::tools::{concat_arrays, merge_objects, split_array, DuplicateKeyPolicy, ShardLimit};
+
+    fn directory(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("json-tools-test-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_split_array() {
+        let dir = directory("split");
+        let input = " [\"a\", [1, 2], {\"b\": \"]\"}, true ] ";
+        let shards = split_array(input.as_bytes(), ShardLimit::Bytes(12), &dir).unwrap();
+        let contents: Vec<String> = shards.iter().map(|p| fs::read_to_string(p).unwrap()).collect();
+        assert_eq!(vec!["[\"a\",[1, 2]]", "[{\"b\": \"]\"}]", "[true]"], contents);
+        assert!(contents.iter().all(|c| c.len() <= 12));
+        let shards = split_array(input.as_bytes(), ShardLimit::Items(3), &dir).unwrap();
+        assert_eq!(json!("[\"a\", [1, 2], {\"b\": \"]\"}]"),
+                   crate::parser::parse(&fs::read_to_string(&shards[0]).unwrap()).unwrap());
+        assert_eq!(2, shards.len());
+        // An element larger than the limit gets a shard of its own
+        let shards = split_array("[[1, 2, 3], 4]".as_bytes(), ShardLimit::Bytes(4), &dir).unwrap();
+        assert_eq!("[[1, 2, 3]]", fs::read_to_string(&shards[0]).unwrap());
+        assert!(split_array("[ ]".as_bytes(), ShardLimit::Items(1), &dir).unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_split_array_errors() {
+        let dir = directory("errors");
+        assert_eq!(ErrorKind::Syntax, split_array("{}".as_bytes(), ShardLimit::Items(1), &dir).unwrap_err().kind());
+        assert_eq!(ErrorKind::Syntax, split_array("[1, tru]".as_bytes(), ShardLimit::Items(1), &dir).unwrap_err().kind());
+        assert_eq!(ErrorKind::Syntax, split_array("[1, 2".as_bytes(), ShardLimit::Items(1), &dir).unwrap_err().kind());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_concat_arrays() {
+        let dir = directory("concat");
+        let input = "[{\"id\": 1}, [], \"x,y\", 4, 5]";
+        let shards = split_array(input.as_bytes(), ShardLimit::Items(2), &dir).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(Ok(5), concat_arrays(&shards, &mut out));
+        assert_eq!(crate::parser::parse(input).unwrap(),
+                   crate::parser::parse(&String::from_utf8(out).unwrap()).unwrap());
+        fs::write(dir.join("empty.json"), " [] ").unwrap();
+        let mut out = Vec::new();
+        assert_eq!(Ok(0), concat_arrays(&[dir.join("empty.json")], &mut out));
+        assert_eq!(b"[]".to_vec(), out);
+        fs::write(dir.join("object.json"), "{}").unwrap();
+        let error = concat_arrays(&[dir.join("object.json")], Vec::new()).unwrap_err();
+        assert!(error.message().ends_with("object.json"));
+        assert_eq!(ErrorKind::Io, concat_arrays(&[dir.join("none.json")], Vec::new()).unwrap_err().kind());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_objects() {
+        let dir = directory("merge");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.json"), "{\"a\": [1], \"b\": {\"x\": 1}}").unwrap();
+        fs::write(dir.join("b.json"), "{}").unwrap();
+        fs::write(dir.join("c.json"), "{\"b\": 2, \"c\": null, \"a\": 3}").unwrap();
+        let paths = [dir.join("a.json"), dir.join("b.json"), dir.join("c.json")];
+        let merge = |policy| {
+            let mut out = Vec::new();
+            merge_objects(&paths, policy, &mut out).map(|_| crate::parser::parse(&String::from_utf8(out).unwrap()).unwrap())
+        };
+        assert_eq!(Ok(json!("{\"a\": [1], \"b\": {\"x\": 1}, \"c\": null}")), merge(DuplicateKeyPolicy::First));
+        assert_eq!(Ok(json!("{\"b\": 2, \"c\": null, \"a\": 3}")), merge(DuplicateKeyPolicy::Last));
+        let error = merge(DuplicateKeyPolicy::Error).unwrap_err();
+        assert_eq!(ErrorKind::Validation, error.kind());
+        assert!(error.message().starts_with("Duplicate key b in "));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,445 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Combinators that rebuild or mutate a [`JValue`] tree.
+//!
+//! Values are identified by their [JSON Pointer] path, e.g. `/users/0/name`. The path of the
+//! root value is the empty string.
+//!
+//! [JSON Pointer]:https://datatracker.ietf.org/doc/html/rfc6901
+
+use crate::data_structures::{JObject, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::ParseOptions;
+
+impl JValue {
+    /// Rebuilds the tree by passing every value and its path to `f`, from the root down to the
+    /// leaves. If `f` returns an object or an array, its children are passed to `f` next.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::{JString, JValue};
+    /// let j_value = json!("{\"user\": {\"name\": \"x\", \"password\": \"hunter2\"}}");
+    /// let redacted = j_value.map_values(|path, value| {
+    ///     if path.ends_with("/password") {
+    ///         JValue::String(JString::from("***"))
+    ///     } else {
+    ///         value
+    ///     }
+    /// });
+    /// assert_eq!(json!("{\"user\": {\"name\": \"x\", \"password\": \"***\"}}"), redacted);
+    /// ```
+    pub fn map_values<F: FnMut(&str, JValue) -> JValue>(self, mut f: F) -> JValue {
+        map_values(self, &mut String::new(), &mut f)
+    }
+
+    /// Rebuilds the tree keeping only the object members whose key satisfies `predicate`, at
+    /// every depth.
+    ///
+    /// ```
+    /// # use json::json;
+    /// let j_value = json!("{\"id\": 1, \"password\": \"x\", \"friends\": [{\"password\": \"y\"}]}");
+    /// let filtered = j_value.filter_keys(|key| key != "password");
+    /// assert_eq!(json!("{\"id\": 1, \"friends\": [{}]}"), filtered);
+    /// ```
+    pub fn filter_keys<P: FnMut(&str) -> bool>(self, mut predicate: P) -> JValue {
+        filter_keys(self, &mut predicate)
+    }
+
+    /// Removes every object member and array element for which `predicate` returns `false`,
+    /// at every depth. The predicate receives the path and the value; the children of a
+    /// removed value are not visited.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::JValue;
+    /// let mut j_value = json!("{\"a\": null, \"b\": [1, null, {\"c\": null}]}");
+    /// j_value.retain(|_, value| *value != JValue::Null);
+    /// assert_eq!(json!("{\"b\": [1, {}]}"), j_value);
+    /// ```
+    pub fn retain<P: FnMut(&str, &JValue) -> bool>(&mut self, mut predicate: P) {
+        retain(self, &mut String::new(), &mut predicate)
+    }
+
+    /// Clones the tree in a single pass, passing every copied value and its path to `f` from
+    /// the leaves up. `f` returns the value to keep, which may be a replacement, or `None` to
+    /// drop it from its parent. The paths are those of the original tree, so dropping an array
+    /// element does not shift the paths of the ones after it. Returns `None` if the root value
+    /// is dropped.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::data_structures::{JString, JValue};
+    /// let j_value = json!("{\"token\": \"x\", \"users\": [{\"name\": \"a\", \"id\": 1}]}");
+    /// let copy = j_value.clone_with(|path, value| match path {
+    ///     "/token" => None,
+    ///     _ if path.ends_with("/name") => Some(JValue::String(JString::from("***"))),
+    ///     _ => Some(value)
+    /// });
+    /// assert_eq!(Some(json!("{\"users\": [{\"name\": \"***\", \"id\": 1}]}")), copy);
+    /// ```
+    pub fn clone_with<F: FnMut(&str, JValue) -> Option<JValue>>(&self, mut f: F) -> Option<JValue> {
+        clone_with(self, &mut String::new(), &mut f)
+    }
+}
+
+/// Flattens a tree into an object with a single level, whose keys are the paths of the leaf
+/// values with their segments joined by `separator`. Empty objects and empty arrays are kept
+/// as leaves. A scalar root value is stored under the empty key.
+///
+/// ```
+/// # use json::json;
+/// # use json::transform::flatten;
+/// # use json::data_structures::JValue;
+/// let flat = flatten(&json!("{\"a\": {\"b\": [{\"c\": true}, []]}}"), ".");
+/// assert_eq!(JValue::Object(flat), json!("{\"a.b.0.c\": true, \"a.b.1\": []}"));
+/// ```
+pub fn flatten(value: &JValue, separator: &str) -> JObject {
+    let mut result = JObject::new();
+    flatten_into(value, &mut String::new(), separator, &mut result);
+    result
+}
+
+fn flatten_into(value: &JValue, path: &mut String, separator: &str, result: &mut JObject) {
+    let len = path.len();
+    let push_segment = |path: &mut String, segment: &str| {
+        if len > 0 {
+            path.push_str(separator);
+        }
+        path.push_str(segment);
+    };
+    match value {
+        JValue::Object(o) if !o.is_empty() => {
+            for (k, v) in o.iter() {
+                push_segment(path, k);
+                flatten_into(v, path, separator, result);
+                path.truncate(len);
+            }
+        }
+        JValue::Array(a) if !a.is_empty() => {
+            for (i, v) in a.iter().enumerate() {
+                push_segment(path, &i.to_string());
+                flatten_into(v, path, separator, result);
+                path.truncate(len);
+            }
+        }
+        v => {
+            result.insert(path.clone(), v.clone());
+        }
+    }
+}
+
+/// Rebuilds a tree from an object created by [`flatten`]. Every level whose keys are exactly
+/// the indices `0` to `n - 1` becomes an array.
+///
+/// Returns an [`ErrorKind::InvalidType`] error if a key is also the prefix of another key, e.g.
+/// `a` and `a.b`, or if a key has more segments than [`ParseOptions::max_depth`].
+///
+/// ```
+/// # use json::json;
+/// # use json::transform::{flatten, unflatten};
+/// let j_value = json!("{\"a\": {\"b\": [{\"c\": true}, []]}, \"d\": {}}");
+/// assert_eq!(Ok(j_value.clone()), unflatten(&flatten(&j_value, "."), "."));
+/// ```
+pub fn unflatten(flat: &JObject, separator: &str) -> Result<JValue, JsonError> {
+    if let Some(v) = flat.get(&String::new()) {
+        return if flat.len() == 1 {
+            Ok(v.clone())
+        } else {
+            Err(JsonError::new(ErrorKind::InvalidType, "The empty key can not be combined with other keys"))
+        };
+    }
+    let max_depth = ParseOptions::default().max_depth;
+    let mut root = JObject::new();
+    for (k, v) in flat.iter() {
+        if k.matches(separator).count() >= max_depth {
+            return Err(JsonError::new(ErrorKind::InvalidType, format!(
+                "A key has more than the limit of {} segments", max_depth)));
+        }
+        let mut obj = &mut root;
+        let mut start = 0;
+        for (end, _) in k.match_indices(separator) {
+            let prefix = k[..end].to_string();
+            if flat.contains_key(&prefix) {
+                return Err(JsonError::new(ErrorKind::InvalidType, format!(
+                    "The key {} is a prefix of the key {}", prefix, k)));
+            }
+            let segment = k[start..end].to_string();
+            start = end + separator.len();
+            if !obj.contains_key(&segment) {
+                obj.insert(segment.clone(), JValue::Object(JObject::new()));
+            }
+            obj = match obj.get_mut(&segment) {
+                Some(JValue::Object(o)) => o,
+                _ => unreachable!("only objects are created while unflattening")
+            };
+        }
+        obj.insert(k[start..].to_string(), v.clone());
+    }
+    Ok(objects_to_arrays(JValue::Object(root)))
+}
+
+fn objects_to_arrays(value: JValue) -> JValue {
+    match value {
+        JValue::Object(o) => {
+            let is_array = !o.is_empty() && (0..o.len()).all(|i| o.contains_key(&i.to_string()));
+            if is_array {
+                let mut members: Vec<(usize, JValue)> = o.into_iter()
+                    .map(|(k, v)| (k.parse().unwrap(), objects_to_arrays(v)))
+                    .collect();
+                members.sort_by_key(|(i, _)| *i);
+                JValue::Array(members.into_iter().map(|(_, v)| v).collect())
+            } else {
+                let mut obj = JObject::new();
+                for (k, v) in o {
+                    obj.insert(k, objects_to_arrays(v));
+                }
+                JValue::Object(obj)
+            }
+        }
+        v => v
+    }
+}
+
+/// Appends a reference token to a JSON Pointer, escaping `~` and `/`.
+pub(crate) fn push_token(path: &mut String, token: &str) {
+    path.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => path.push_str("~0"),
+            '/' => path.push_str("~1"),
+            _ => path.push(c),
+        }
+    }
+}
+
+fn map_values<F: FnMut(&str, JValue) -> JValue>(value: JValue, path: &mut String, f: &mut F)
+                                                -> JValue {
+    let len = path.len();
+    match f(path, value) {
+        JValue::Object(o) => {
+            let mut obj = JObject::new();
+            for (k, v) in o {
+                push_token(path, &k);
+                let v = map_values(v, path, f);
+                path.truncate(len);
+                obj.insert(k, v);
+            }
+            JValue::Object(obj)
+        }
+        JValue::Array(a) => {
+            let mut vec = Vec::with_capacity(a.len());
+            for (i, v) in a.into_iter().enumerate() {
+                push_token(path, &i.to_string());
+                vec.push(map_values(v, path, f));
+                path.truncate(len);
+            }
+            JValue::Array(vec)
+        }
+        v => v
+    }
+}
+
+fn clone_with<F: FnMut(&str, JValue) -> Option<JValue>>(value: &JValue, path: &mut String,
+                                                        f: &mut F) -> Option<JValue> {
+    let len = path.len();
+    let copy = match value {
+        JValue::Object(o) => {
+            let mut obj = JObject::with_capacity(o.len());
+            for (k, v) in o.iter() {
+                push_token(path, k);
+                if let Some(v) = clone_with(v, path, f) {
+                    obj.insert(k.clone(), v);
+                }
+                path.truncate(len);
+            }
+            JValue::Object(obj)
+        }
+        JValue::Array(a) => {
+            let mut vec = Vec::with_capacity(a.len());
+            for (i, v) in a.iter().enumerate() {
+                push_token(path, &i.to_string());
+                vec.extend(clone_with(v, path, f));
+                path.truncate(len);
+            }
+            JValue::Array(vec)
+        }
+        v => v.clone()
+    };
+    f(path, copy)
+}
+
+fn filter_keys<P: FnMut(&str) -> bool>(value: JValue, predicate: &mut P) -> JValue {
+    match value {
+        JValue::Object(o) => {
+            let mut obj = JObject::new();
+            for (k, v) in o {
+                if predicate(&k) {
+                    let v = filter_keys(v, predicate);
+                    obj.insert(k, v);
+                }
+            }
+            JValue::Object(obj)
+        }
+        JValue::Array(a) => JValue::Array(a.into_iter().map(|v| filter_keys(v, predicate)).collect()),
+        v => v
+    }
+}
+
+fn retain<P: FnMut(&str, &JValue) -> bool>(value: &mut JValue, path: &mut String,
+                                           predicate: &mut P) {
+    let len = path.len();
+    match value {
+        JValue::Object(o) => {
+            let mut removed = Vec::new();
+            for (k, v) in o.iter_mut() {
+                push_token(path, k);
+                if predicate(path, v) {
+                    retain(v, path, predicate);
+                } else {
+                    removed.push(k.clone());
+                }
+                path.truncate(len);
+            }
+            for k in removed.iter() {
+                o.remove(k);
+            }
+        }
+        JValue::Array(a) => {
+            let mut index = 0;
+            a.retain_mut(|v| {
+                push_token(path, &index.to_string());
+                let keep = predicate(path, v);
+                if keep {
+                    retain(v, path, predicate);
+                }
+                path.truncate(len);
+                index += 1;
+                keep
+            });
+        }
+        _ => ()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_structures::{JNumber, JObject, JValue};
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::transform::{flatten, unflatten};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_map_values_paths() {
+        let mut paths = Vec::new();
+        json!("{\"a/b\": [true], \"c~d\": null}").map_values(|path, value| {
+            paths.push(path.to_string());
+            value
+        });
+        paths.sort();
+        assert_eq!(vec!["", "/a~1b", "/a~1b/0", "/c~0d"], paths);
+    }
+
+    #[test]
+    fn test_map_values_replaces_containers() {
+        let j_value = json!("[[1, 2], 3]").map_values(|path, value| match path {
+            "/0" => JValue::Array(vec![JValue::Null]),
+            "/0/0" => JValue::Boolean(true),
+            _ => value
+        });
+        assert_eq!(json!("[[true], 3]"), j_value);
+    }
+
+    #[test]
+    fn test_retain_paths() {
+        let mut j_value = json!("[0, 1, 2, 3]");
+        let mut paths = Vec::new();
+        j_value.retain(|path, value| {
+            paths.push(path.to_string());
+            *value != JValue::Number(JNumber::from_str("1").unwrap())
+        });
+        assert_eq!(vec!["/0", "/1", "/2", "/3"], paths);
+        assert_eq!(json!("[0, 2, 3]"), j_value);
+    }
+
+    #[test]
+    fn test_clone_with() {
+        let j_value = json!("[[1, 2], 3, 4]");
+        let mut paths = Vec::new();
+        let copy = j_value.clone_with(|path, value| {
+            paths.push(path.to_string());
+            match path {
+                "/0/0" | "/1" => None,
+                "/0" => Some(JValue::Array(vec![value, JValue::Null])),
+                _ => Some(value)
+            }
+        });
+        assert_eq!(vec!["/0/0", "/0/1", "/0", "/1", "/2", ""], paths);
+        assert_eq!(Some(json!("[[[2], null], 4]")), copy);
+        assert_eq!(json!("[[1, 2], 3, 4]"), j_value);
+        assert_eq!(None, j_value.clone_with(|_, _| None));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let flat = flatten(&json!("{\"a\": [1, {\"b\": null}], \"c\": {}}"), "/");
+        assert_eq!(JValue::Object(flat), json!("{\"a/0\": 1, \"a/1/b\": null, \"c\": {}}"));
+        let flat = flatten(&json!("true"), ".");
+        assert_eq!(JValue::Object(flat), json!("{\"\": true}"));
+    }
+
+    #[test]
+    fn test_unflatten() {
+        let flat = flatten(&json!("[[1, 2], {\"0\": 3, \"2\": 4}]"), "_");
+        assert_eq!(Ok(json!("[[1, 2], {\"0\": 3, \"2\": 4}]")), unflatten(&flat, "_"));
+        let flat = flatten(&json!("false"), ".");
+        assert_eq!(Ok(json!("false")), unflatten(&flat, "."));
+    }
+
+    #[test]
+    fn test_unflatten_conflicts() {
+        let flat = match json!("{\"a\": 1, \"a.b\": 2}") {
+            JValue::Object(o) => o,
+            _ => unreachable!()
+        };
+        let error = unflatten(&flat, ".").unwrap_err();
+        assert_eq!((ErrorKind::InvalidType, "The key a is a prefix of the key a.b"), (error.kind(), error.message()));
+        let flat = match json!("{\"\": 1, \"b\": 2}") {
+            JValue::Object(o) => o,
+            _ => unreachable!()
+        };
+        assert_eq!("The empty key can not be combined with other keys", unflatten(&flat, ".").unwrap_err().message());
+    }
+
+    #[test]
+    fn test_unflatten_max_depth() {
+        let mut flat = JObject::new();
+        flat.insert(vec!["a"; 128].join("."), json!("1"));
+        assert!(unflatten(&flat, ".").is_ok());
+        flat.insert(vec!["a"; 200_000].join("."), json!("1"));
+        let error = unflatten(&flat, ".").unwrap_err();
+        assert_eq!((ErrorKind::InvalidType, "A key has more than the limit of 128 segments"),
+                   (error.kind(), error.message()));
+    }
+
+    #[test]
+    fn test_filter_keys() {
+        let j_value = json!("[{\"a\": {\"b\": 1, \"c\": 2}}]").filter_keys(|key| key != "b");
+        assert_eq!(json!("[{\"a\": {\"c\": 2}}]"), j_value);
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Documents that stay valid against a schema while being edited.
+
+use std::mem;
+
+use crate::convert::describe;
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::pointer::{array_index, parse_pointer};
+use crate::schema::Schema;
+use crate::transform::push_token;
+
+/// A [`JValue`] bound to a [`Schema`]. Every mutation is validated, and a mutation that would
+/// make the document invalid is rolled back and reported as an error.
+///
+/// ```
+/// # use json::json;
+/// # use json::schema::Schema;
+/// # use json::typed::TypedDocument;
+/// let schema = Schema::compile(&json!("{\"properties\": {\"port\": {\"type\": \"integer\"}}}")).unwrap();
+/// let mut config = TypedDocument::new(json!("{\"port\": 80}"), schema).unwrap();
+///
+/// config.set_path("/port", json!("8080")).unwrap();
+/// assert!(config.set_path("/port", json!("\"http\"")).is_err());
+/// assert_eq!(&json!("{\"port\": 8080}"), config.value());
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypedDocument {
+    value: JValue,
+    schema: Schema,
+}
+
+enum Undo {
+    Restore(JValue),
+    Remove,
+    Pop,
+    InsertAt(usize, JValue),
+}
+
+impl TypedDocument {
+    /// Binds `value` to `schema`, failing with [`ErrorKind::Validation`] if it is not valid.
+    pub fn new(value: JValue, schema: Schema) -> Result<TypedDocument, JsonError> {
+        schema.validate(&value)?;
+        Ok(TypedDocument { value, schema })
+    }
+
+    /// Returns the document
+    pub fn value(&self) -> &JValue {
+        &self.value
+    }
+
+    /// Returns the schema the document is bound to
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Unbinds the document from its schema
+    pub fn into_inner(self) -> JValue {
+        self.value
+    }
+
+    /// Inserts a member into the root object, returning the value it replaced.
+    pub fn insert(&mut self, key: &str, value: JValue) -> Result<Option<JValue>, JsonError> {
+        let mut pointer = String::new();
+        push_token(&mut pointer, key);
+        self.set_path(&pointer, value)
+    }
+
+    /// Sets the value at the given JSON Pointer, returning the value it replaced. A missing
+    /// object member is added, and the array index `-` or the length of the array appends to
+    /// it. The parent of the target must exist.
+    ///
+    /// ```
+    /// # use json::json;
+    /// # use json::schema::Schema;
+    /// # use json::typed::TypedDocument;
+    /// let schema = Schema::compile(&json!("{\"properties\": {\"tags\": {\"maxItems\": 2}}}")).unwrap();
+    /// let mut document = TypedDocument::new(json!("{\"tags\": []}"), schema).unwrap();
+    ///
+    /// assert_eq!(Ok(None), document.set_path("/tags/-", json!("\"a\"")));
+    /// assert_eq!(Ok(Some(json!("\"a\""))), document.set_path("/tags/0", json!("\"b\"")));
+    /// assert_eq!(Ok(None), document.set_path("/tags/1", json!("\"c\"")));
+    /// assert!(document.set_path("/tags/-", json!("\"d\"")).is_err());
+    /// assert_eq!(&json!("{\"tags\": [\"b\", \"c\"]}"), document.value());
+    /// ```
+    pub fn set_path(&mut self, pointer: &str, value: JValue) -> Result<Option<JValue>, JsonError> {
+        let (parent, token) = match split_pointer(pointer)? {
+            Some(split) => split,
+            None => {
+                self.schema.validate(&value)?;
+                return Ok(Some(mem::replace(&mut self.value, value)));
+            }
+        };
+        let (undo, replaced) = match self.parent_mut(&parent, pointer)? {
+            JValue::Object(o) => match o.insert(token.clone(), value) {
+                Some(old) => (Undo::Restore(old.clone()), Some(old)),
+                None => (Undo::Remove, None)
+            },
+            JValue::Array(a) if token == "-" || array_index(&token) == Some(a.len()) => {
+                a.push(value);
+                (Undo::Pop, None)
+            }
+            JValue::Array(a) => match array_index(&token).and_then(|i| a.get_mut(i)) {
+                Some(item) => {
+                    let old = mem::replace(item, value);
+                    (Undo::Restore(old.clone()), Some(old))
+                }
+                None => return Err(JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer)))
+            },
+            other => return Err(not_a_container(other, pointer))
+        };
+        self.check(&parent, &token, undo)?;
+        Ok(replaced)
+    }
+
+    /// Removes the value at the given JSON Pointer and returns it.
+    pub fn remove_path(&mut self, pointer: &str) -> Result<JValue, JsonError> {
+        let not_found = || JsonError::new(ErrorKind::NotFound, format!("No value at {}", pointer));
+        let (parent, token) = match split_pointer(pointer)? {
+            Some(split) => split,
+            None => return Err(JsonError::new(ErrorKind::InvalidPointer, "The root can not be removed"))
+        };
+        let (undo, removed) = match self.parent_mut(&parent, pointer)? {
+            JValue::Object(o) => {
+                let old = o.remove(&token).ok_or_else(not_found)?;
+                (Undo::Restore(old.clone()), old)
+            }
+            JValue::Array(a) => match array_index(&token).filter(|i| *i < a.len()) {
+                Some(i) => {
+                    let old = a.remove(i);
+                    (Undo::InsertAt(i, old.clone()), old)
+                }
+                None => return Err(not_found())
+            },
+            other => return Err(not_a_container(other, pointer))
+        };
+        self.check(&parent, &token, undo)?;
+        Ok(removed)
+    }
+
+    fn parent_mut(&mut self, parent: &str, pointer: &str) -> Result<&mut JValue, JsonError> {
+        self.value.pointer_mut(parent)
+            .ok_or_else(|| JsonError::new(ErrorKind::NotFound, format!("No parent value for {}", pointer)))
+    }
+
+    /// Validates the document, rolling back the last mutation if it is no longer valid
+    fn check(&mut self, parent: &str, token: &str, undo: Undo) -> Result<(), JsonError> {
+        let error = match self.schema.validate(&self.value) {
+            Ok(()) => return Ok(()),
+            Err(error) => error
+        };
+        let parent = self.value.pointer_mut(parent).unwrap();
+        match (parent, undo) {
+            (JValue::Object(o), Undo::Restore(old)) => {
+                o.insert(token.to_string(), old);
+            }
+            (JValue::Object(o), Undo::Remove) => {
+                o.remove(&token.to_string());
+            }
+            (JValue::Array(a), Undo::Restore(old)) => a[array_index(token).unwrap()] = old,
+            (JValue::Array(a), Undo::Pop) => {
+                a.pop();
+            }
+            (JValue::Array(a), Undo::InsertAt(i, old)) => a.insert(i, old),
+            _ => unreachable!()
+        }
+        Err(error)
+    }
+}
+
+/// Splits a pointer into the pointer of its parent and its last token, or `None` for the root
+fn split_pointer(pointer: &str) -> Result<Option<(String, String)>, JsonError> {
+    let mut tokens = parse_pointer(pointer)?;
+    let last = match tokens.pop() {
+        Some(last) => last,
+        None => return Ok(None)
+    };
+    let mut parent = String::new();
+    for token in &tokens {
+        push_token(&mut parent, token);
+    }
+    Ok(Some((parent, last)))
+}
+
+fn not_a_container(value: &JValue, pointer: &str) -> JsonError {
+    JsonError::new(ErrorKind::InvalidType,
+                   format!("Expected an object or an array as the parent of {}, found {}", pointer, describe(value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::schema::Schema;
+    use crate::typed::TypedDocument;
+
+    fn document() -> TypedDocument {
+        let schema = Schema::compile(&json!("{
+            \"type\": \"object\",
+            \"required\": [\"name\"],
+            \"properties\": {\"name\": {\"type\": \"string\"}, \"ids\": {\"items\": {\"type\": \"integer\"}}},
+            \"additionalProperties\": false
+        }")).unwrap();
+        TypedDocument::new(json!("{\"name\": \"x\", \"ids\": [1, 2]}"), schema).unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        let schema = Schema::compile(&json!("{\"type\": \"object\"}")).unwrap();
+        assert_eq!(ErrorKind::Validation, TypedDocument::new(json!("[]"), schema).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut document = document();
+        let original = document.value().clone();
+        assert!(document.insert("other", json!("1")).is_err());
+        assert!(document.insert("name", json!("1")).is_err());
+        assert!(document.set_path("/ids/0", json!("\"a\"")).is_err());
+        assert!(document.set_path("/ids/-", json!("1.5")).is_err());
+        assert!(document.remove_path("/name").is_err());
+        assert!(document.set_path("", json!("{}")).is_err());
+        assert_eq!(&original, document.value());
+    }
+
+    #[test]
+    fn test_mutations() {
+        let mut document = document();
+        assert_eq!(Ok(Some(json!("\"x\""))), document.insert("name", json!("\"y\"")));
+        assert_eq!(Ok(json!("1")), document.remove_path("/ids/0"));
+        assert_eq!(Ok(Some(json!("[2]"))), document.set_path("/ids", json!("[]")));
+        assert_eq!(&json!("{\"name\": \"y\", \"ids\": []}"), document.value());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut document = document();
+        assert_eq!(ErrorKind::NotFound, document.set_path("/ids/5", json!("1")).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, document.set_path("/a/b", json!("1")).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidType, document.set_path("/name/a", json!("1")).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, document.remove_path("/ids/2").unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidPointer, document.remove_path("").unwrap_err().kind());
+    }
+}
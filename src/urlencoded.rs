@@ -0,0 +1,312 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Query strings and HTML form data (`application/x-www-form-urlencoded`) as [`JValue`]s.
+//!
+//! Parameter names describe where a value goes in the document: `c.d` and `c[d]` are the member
+//! `d` of the object `c`, and `b[]` appends to the array `b`. A name given more than once
+//! collects its values into an array. Values are always strings; [`FromJValue`] converts them
+//! to numbers and booleans when they are read.
+//!
+//! [`FromJValue`]:crate::convert::FromJValue
+
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::ParseOptions;
+use crate::transform::push_token;
+
+enum Segment {
+    Name(String),
+    Append,
+}
+
+/// Parses a query string into an object. A leading `?` is ignored.
+///
+/// Names and values are decoded before the names are split, so `b%5B%5D=2`, as browsers send
+/// it, is the same as `b[]=2`. Names are members of objects even if they are digits, so
+/// `a[0]=x` makes `{"a": {"0": "x"}}`. A name may have as many segments as the default
+/// [`ParseOptions::max_depth`] allows values to be nested; a longer one is a syntax error.
+///
+/// ```
+/// # use json::json;
+/// # use json::urlencoded::parse_query;
+/// assert_eq!(Ok(json!("{\"a\": \"1\", \"b\": [\"2\", \"3\"], \"c\": {\"d\": \"x y\"}}")),
+///            parse_query("a=1&b[]=2&b[]=3&c.d=x+y"));
+/// assert_eq!(Ok(json!("{\"tag\": [\"red\", \"blue\"], \"page\": {\"size\": \"10\"}}")),
+///            parse_query("?tag=red&tag=blue&page%5Bsize%5D=10"));
+/// assert_eq!("The parameter a.b conflicts with an earlier one",
+///            parse_query("a=1&a.b=2").unwrap_err().message());
+/// ```
+pub fn parse_query(query: &str) -> Result<JValue, JsonError> {
+    let query = query.strip_prefix('?').unwrap_or(query);
+    let mut root = JValue::Object(JObject::new());
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let name = decode(name)?;
+        let segments = parse_name(&name)?;
+        if !insert(&mut root, &segments, JString::from(decode(value)?)) {
+            return Err(JsonError::new(ErrorKind::InvalidType,
+                                      format!("The parameter {} conflicts with an earlier one", name)));
+        }
+    }
+    Ok(root)
+}
+
+/// Serializes an object into a query string [`parse_query`] reads back to the same object,
+/// using dots for members of nested objects and `[]` for arrays.
+///
+/// Numbers and booleans become their text and `null` becomes an empty value; parsing them back
+/// makes strings. Empty objects and arrays leave no parameter. Arrays of objects or arrays, and
+/// names with a `.`, `[` or `]`, can not be written and fail with [`ErrorKind::InvalidType`].
+///
+/// ```
+/// # use json::json;
+/// # use json::urlencoded::to_query;
+/// assert_eq!(Ok("b[]=2&b[]=3".to_string()), to_query(&json!("{\"b\": [2, 3]}")));
+/// assert_eq!(Ok("c.d=x+y%26z".to_string()), to_query(&json!("{\"c\": {\"d\": \"x y&z\"}}")));
+/// ```
+pub fn to_query(value: &JValue) -> Result<String, JsonError> {
+    let object = match value {
+        JValue::Object(object) => object,
+        _ => return Err(JsonError::new(ErrorKind::InvalidType,
+                                       format!("Expected an object, found {}", crate::convert::describe(value))))
+    };
+    let mut query = String::new();
+    write_object(object, "", &mut String::new(), &mut query)?;
+    Ok(query)
+}
+
+fn decode(text: &str) -> Result<String, JsonError> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                // Browsers keep a '%' which does not start an escape as it is.
+                None => decoded.push(b'%')
+            },
+            byte => decoded.push(byte)
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| JsonError::new(
+        ErrorKind::Syntax, format!("The parameter {} is not valid UTF-8 when decoded", text)))
+}
+
+fn encode(text: &str, query: &mut String) {
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => query.push(byte as char),
+            b' ' => query.push('+'),
+            _ => query.push_str(&format!("%{:02X}", byte))
+        }
+    }
+}
+
+fn parse_name(name: &str) -> Result<Vec<Segment>, JsonError> {
+    let invalid = || JsonError::new(ErrorKind::Syntax, format!("Invalid parameter name '{}'", name));
+    let first_end = name.find(['.', '[']).unwrap_or(name.len());
+    if first_end == 0 {
+        return Err(invalid());
+    }
+    let mut segments = vec![Segment::Name(name[..first_end].to_string())];
+    let mut rest = &name[first_end..];
+    while !rest.is_empty() {
+        let (segment, next) = if let Some(bracketed) = rest.strip_prefix('[') {
+            let end = bracketed.find(']').ok_or_else(invalid)?;
+            match &bracketed[..end] {
+                "" => (Segment::Append, &bracketed[end + 1..]),
+                member if !member.contains('[') => (Segment::Name(member.to_string()), &bracketed[end + 1..]),
+                _ => return Err(invalid())
+            }
+        } else if let Some(dotted) = rest.strip_prefix('.') {
+            let end = dotted.find(['.', '[']).unwrap_or(dotted.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            (Segment::Name(dotted[..end].to_string()), &dotted[end..])
+        } else {
+            return Err(invalid());
+        };
+        segments.push(segment);
+        rest = next;
+        let max_depth = ParseOptions::default().max_depth;
+        if segments.len() > max_depth {
+            return Err(JsonError::new(ErrorKind::Syntax,
+                                      format!("A parameter name has more than the limit of {} segments", max_depth)));
+        }
+    }
+    Ok(segments)
+}
+
+/// Puts `value` at the place `segments` name in `target`, or returns `false` if it conflicts
+/// with what is already there
+fn insert(target: &mut JValue, segments: &[Segment], value: JString) -> bool {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return false
+    };
+    match segment {
+        Segment::Name(name) => {
+            if let JValue::Null = target {
+                *target = JValue::Object(JObject::new());
+            }
+            let object = match target {
+                JValue::Object(object) => object,
+                _ => return false
+            };
+            if !rest.is_empty() {
+                if object.get(name).is_none() {
+                    object.insert(name.clone(), JValue::Null);
+                }
+                return insert(object.get_mut(name).unwrap(), rest, value);
+            }
+            match object.get_mut(name) {
+                None => {
+                    object.insert(name.clone(), JValue::String(value));
+                }
+                Some(JValue::Array(values)) => values.push(JValue::String(value)),
+                Some(existing @ JValue::String(_)) => {
+                    let first = existing.take();
+                    *existing = JValue::Array(vec![first, JValue::String(value)]);
+                }
+                Some(_) => return false
+            }
+            true
+        }
+        Segment::Append => {
+            if let JValue::Null = target {
+                *target = JValue::Array(Vec::new());
+            }
+            let values = match target {
+                JValue::Array(values) => values,
+                _ => return false
+            };
+            if rest.is_empty() {
+                values.push(JValue::String(value));
+                return true;
+            }
+            values.push(JValue::Null);
+            insert(values.last_mut().unwrap(), rest, value)
+        }
+    }
+}
+
+fn write_object(object: &JObject, prefix: &str, path: &mut String, query: &mut String) -> Result<(), JsonError> {
+    let len = path.len();
+    for (name, value) in object.iter() {
+        push_token(path, name);
+        if name.is_empty() || name.contains(['.', '[', ']']) {
+            return Err(JsonError::new(ErrorKind::InvalidType,
+                                      format!("The name '{}' can not be written in a query at {}", name, path)));
+        }
+        let mut key = String::with_capacity(prefix.len() + name.len() + 1);
+        if !prefix.is_empty() {
+            key.push_str(prefix);
+            key.push('.');
+        }
+        encode(name, &mut key);
+        match value {
+            JValue::Object(object) => write_object(object, &key, path, query)?,
+            JValue::Array(values) => {
+                key.push_str("[]");
+                for value in values {
+                    if matches!(value, JValue::Object(_) | JValue::Array(_)) {
+                        return Err(JsonError::new(ErrorKind::InvalidType,
+                                                  format!("An array of containers can not be written in a query at {}", path)));
+                    }
+                    write_pair(&key, value, query);
+                }
+            }
+            _ => write_pair(&key, value, query)
+        }
+        path.truncate(len);
+    }
+    Ok(())
+}
+
+fn write_pair(key: &str, value: &JValue, query: &mut String) {
+    if !query.is_empty() {
+        query.push('&');
+    }
+    query.push_str(key);
+    query.push('=');
+    match value {
+        JValue::String(s) => encode(s, query),
+        JValue::Number(n) => encode(&n.to_string(), query),
+        JValue::Boolean(b) => query.push_str(if *b { "true" } else { "false" }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::urlencoded::{parse_query, to_query};
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(Ok(json!("{}")), parse_query(""));
+        assert_eq!(Ok(json!("{\"a\": \"\", \"b\": \"\"}")), parse_query("a&&b="));
+        assert_eq!(Ok(json!("{\"q\": \"100% é=\"}")), parse_query("q=100%+%C3%A9%3D"));
+        assert_eq!(Ok(json!("{\"a\": {\"b\": {\"c\": [\"1\"], \"d\": \"2\"}}}")), parse_query("a[b].c[]=1&a.b[d]=2"));
+        assert_eq!(Ok(json!("{\"a\": [{\"b\": \"1\"}, {\"b\": \"2\"}]}")), parse_query("a[][b]=1&a[].b=2"));
+        assert_eq!(Ok(json!("{\"a\": [\"1\", \"2\", \"3\"]}")), parse_query("a=1&a=2&a[]=3"));
+        for query in ["a.=1", ".a=1", "a..b=1", "a[b=1", "a[b]c=1", "=1", "a%FF=1"] {
+            assert_eq!(ErrorKind::Syntax, parse_query(query).unwrap_err().kind(), "{}", query);
+        }
+        for query in ["a.b=1&a=2", "a=1&a[b]=2", "a[]=1&a.b=2"] {
+            assert_eq!(ErrorKind::InvalidType, parse_query(query).unwrap_err().kind(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_name_segments() {
+        let name = |segments: usize| format!("a{}", "[b]".repeat(segments - 1));
+        let value = parse_query(&format!("{}=1", name(128))).unwrap();
+        assert_eq!(Some(&json!("\"1\"")), value.pointer(&format!("/a{}", "/b".repeat(127))));
+        for query in [format!("{}=1", name(129)), format!("{}=1", name(200_000)), format!("a{}=1", "[]".repeat(200_000))] {
+            let error = parse_query(&query).unwrap_err();
+            assert_eq!((ErrorKind::Syntax, "A parameter name has more than the limit of 128 segments"),
+                       (error.kind(), error.message()));
+        }
+    }
+
+    #[test]
+    fn test_to_query() {
+        let j_value = json!("{\"a\": {\"b c\": [\"x\", \"\"], \"d\": {\"e\": \"ü\"}}}");
+        let query = to_query(&j_value).unwrap();
+        assert_eq!(Ok(j_value), parse_query(&query));
+        assert_eq!(Ok("a=".to_string()), to_query(&json!("{\"a\": null}")));
+        assert_eq!(Ok("b=true".to_string()), to_query(&json!("{\"b\": true}")));
+        assert_eq!(Ok("c=1.5".to_string()), to_query(&json!("{\"c\": 1.5}")));
+        assert_eq!(Ok(String::new()), to_query(&json!("{\"a\": [], \"b\": {}}")));
+        assert_eq!("The name 'a.b' can not be written in a query at /a.b",
+                   to_query(&json!("{\"a.b\": 1}")).unwrap_err().message());
+        assert_eq!("An array of containers can not be written in a query at /a/b",
+                   to_query(&json!("{\"a\": {\"b\": [[1]]}}")).unwrap_err().message());
+        assert_eq!(ErrorKind::InvalidType, to_query(&json!("[]")).unwrap_err().kind());
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! UUIDs stored in JSON documents as hyphenated strings, with [uuid].
+//!
+//! [uuid]:https://crates.io/crates/uuid
+
+use ::uuid::Uuid;
+
+use crate::data_structures::{JString, JValue};
+use crate::format::is_uuid;
+
+impl JValue {
+    /// Creates a string value holding `uuid` in lowercase hyphenated form.
+    ///
+    /// ```
+    /// # use json::data_structures::JValue;
+    /// let uuid = uuid::Uuid::from_u128(0x67e5504410b1426f9247bb680e5fe0c8);
+    /// assert_eq!(JValue::String("67e55044-10b1-426f-9247-bb680e5fe0c8".into()), JValue::from_uuid(&uuid));
+    /// ```
+    pub fn from_uuid(uuid: &Uuid) -> JValue {
+        JValue::String(JString::from(uuid.hyphenated().to_string()))
+    }
+
+    /// Parses a string value holding a hyphenated UUID, see [`is_uuid`]. Returns [`None`] for
+    /// other values and strings, including the simple, braced and URN forms.
+    ///
+    /// ```
+    /// # use json::data_structures::JValue;
+    /// let j_value = JValue::String("67E55044-10B1-426F-9247-BB680E5FE0C8".into());
+    /// assert_eq!(Some(uuid::Uuid::from_u128(0x67e5504410b1426f9247bb680e5fe0c8)), j_value.as_uuid());
+    /// assert_eq!(None, JValue::String("67e5504410b1426f9247bb680e5fe0c8".into()).as_uuid());
+    /// ```
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match self {
+            JValue::String(s) if is_uuid(s) => Uuid::parse_str(s).ok(),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Traits for walking JSON data-structures.
+//!
+//! Implement [`Visitor`] to inspect a [`JValue`] tree, or [`VisitorMut`] to change it in
+//! place. Every hook has a default implementation, so a visitor only overrides the hooks it
+//! is interested in. The default `visit_object`, `visit_member` and `visit_array` hooks walk
+//! into the children; an overriding hook can call [`walk_object`] or [`walk_array`] (or their
+//! `_mut` counterparts) to keep descending.
+
+use crate::data_structures::{JNumber, JObject, JString, JValue};
+
+/// A visitor over a borrowed [`JValue`] tree.
+///
+/// ```
+/// # use json::json;
+/// # use json::data_structures::JString;
+/// # use json::visitor::Visitor;
+/// struct StringCounter(usize);
+///
+/// impl Visitor for StringCounter {
+///     fn visit_string(&mut self, _: &JString) {
+///         self.0 += 1;
+///     }
+/// }
+///
+/// let mut counter = StringCounter(0);
+/// json!("{\"a\": [\"x\", \"y\", 1], \"b\": \"z\"}").accept(&mut counter);
+/// assert_eq!(3, counter.0);
+/// ```
+pub trait Visitor {
+    fn visit_object(&mut self, object: &JObject) {
+        walk_object(self, object)
+    }
+
+    /// Called for every key-value pair of an object
+    fn visit_member(&mut self, _key: &str, value: &JValue) {
+        value.accept(self)
+    }
+
+    fn visit_array(&mut self, array: &[JValue]) {
+        walk_array(self, array)
+    }
+
+    fn visit_string(&mut self, _string: &JString) {}
+
+    fn visit_number(&mut self, _number: &JNumber) {}
+
+    fn visit_boolean(&mut self, _boolean: bool) {}
+
+    fn visit_null(&mut self) {}
+}
+
+/// Visits every key-value pair of the given object
+pub fn walk_object<V: Visitor + ?Sized>(visitor: &mut V, object: &JObject) {
+    for (k, v) in object.iter() {
+        visitor.visit_member(k, v);
+    }
+}
+
+/// Visits every element of the given array
+pub fn walk_array<V: Visitor + ?Sized>(visitor: &mut V, array: &[JValue]) {
+    for v in array {
+        v.accept(visitor);
+    }
+}
+
+/// A visitor over a mutable [`JValue`] tree, for in-place transformations.
+///
+/// ```
+/// # use json::json;
+/// # use json::data_structures::{JString, JValue};
+/// # use json::visitor::VisitorMut;
+/// struct Uppercase;
+///
+/// impl VisitorMut for Uppercase {
+///     fn visit_string_mut(&mut self, string: &mut JString) {
+///         *string = JString::from(string.to_uppercase());
+///     }
+/// }
+///
+/// let mut j_value = json!("[\"a\", {\"b\": \"c\"}]");
+/// j_value.accept_mut(&mut Uppercase);
+/// assert_eq!(json!("[\"A\", {\"b\": \"C\"}]"), j_value);
+/// ```
+pub trait VisitorMut {
+    /// Called for every value before its type-specific hook. Replacing the value here changes
+    /// which type-specific hook is called.
+    fn visit_value_mut(&mut self, _value: &mut JValue) {}
+
+    fn visit_object_mut(&mut self, object: &mut JObject) {
+        walk_object_mut(self, object)
+    }
+
+    /// Called for every key-value pair of an object
+    fn visit_member_mut(&mut self, _key: &str, value: &mut JValue) {
+        value.accept_mut(self)
+    }
+
+    fn visit_array_mut(&mut self, array: &mut Vec<JValue>) {
+        walk_array_mut(self, array)
+    }
+
+    fn visit_string_mut(&mut self, _string: &mut JString) {}
+
+    fn visit_number_mut(&mut self, _number: &mut JNumber) {}
+
+    fn visit_boolean_mut(&mut self, _boolean: &mut bool) {}
+
+    fn visit_null_mut(&mut self) {}
+}
+
+/// Visits every key-value pair of the given object
+pub fn walk_object_mut<V: VisitorMut + ?Sized>(visitor: &mut V, object: &mut JObject) {
+    for (k, v) in object.iter_mut() {
+        visitor.visit_member_mut(k, v);
+    }
+}
+
+/// Visits every element of the given array
+pub fn walk_array_mut<V: VisitorMut + ?Sized>(visitor: &mut V, array: &mut [JValue]) {
+    for v in array {
+        v.accept_mut(visitor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_structures::{JNumber, JValue};
+    use crate::json;
+    use crate::visitor::{walk_array, Visitor, VisitorMut};
+
+    #[derive(Default)]
+    struct Depth {
+        current: usize,
+        max: usize,
+        numbers: f64,
+    }
+
+    impl Visitor for Depth {
+        fn visit_array(&mut self, array: &[JValue]) {
+            self.current += 1;
+            self.max = self.max.max(self.current);
+            walk_array(self, array);
+            self.current -= 1;
+        }
+
+        fn visit_number(&mut self, number: &JNumber) {
+            self.numbers += number.get_f64_value();
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut depth = Depth::default();
+        json!("[1, [2, [3]], {\"a\": [[4]]}]").accept(&mut depth);
+        assert_eq!(3, depth.max);
+        assert_eq!(10.0, depth.numbers);
+    }
+
+    struct NullToFalse;
+
+    impl VisitorMut for NullToFalse {
+        fn visit_value_mut(&mut self, value: &mut JValue) {
+            if *value == JValue::Null {
+                *value = JValue::Boolean(false);
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let mut j_value = json!("{\"a\": null, \"b\": [null, true]}");
+        j_value.accept_mut(&mut NullToFalse);
+        assert_eq!(json!("{\"a\": false, \"b\": [false, true]}"), j_value);
+    }
+}
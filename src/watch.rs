@@ -0,0 +1,222 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Configuration files reloaded when they change on disk.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::config;
+use crate::data_structures::JValue;
+use crate::error::{ErrorKind, JsonError};
+use crate::schema::Schema;
+
+struct State {
+    path: PathBuf,
+    schema: Option<Schema>,
+    current: Arc<JValue>,
+    last_error: Option<JsonError>,
+    subscribers: Vec<Sender<Arc<JValue>>>,
+}
+
+impl State {
+    fn load(path: &Path, schema: Option<&Schema>) -> Result<JValue, JsonError> {
+        let value = config::load(path)?;
+        if let Some(schema) = schema {
+            schema.validate(&value)?;
+        }
+        Ok(value)
+    }
+
+    fn reload(&mut self) -> Result<Arc<JValue>, JsonError> {
+        let value = match State::load(&self.path, self.schema.as_ref()) {
+            Ok(value) => value,
+            Err(e) => {
+                self.last_error = Some(e.clone());
+                return Err(e);
+            }
+        };
+        self.last_error = None;
+        // A save usually raises several events, only a change is published
+        if value != *self.current {
+            self.current = Arc::new(value);
+            let current = &self.current;
+            self.subscribers.retain(|subscriber| subscriber.send(Arc::clone(current)).is_ok());
+        }
+        Ok(Arc::clone(&self.current))
+    }
+}
+
+/// A configuration file loaded with [`config::load`] and loaded again whenever it changes.
+///
+/// A new version is validated against the optional schema before it is published. A version
+/// that can not be loaded or is not valid is not published and the previous version stays
+/// current; its error is kept in [`last_error`](ConfigWatcher::last_error). Changes to files
+/// included with `$include` are picked up with the next change to the watched file.
+///
+/// ```no_run
+/// # use json::watch::ConfigWatcher;
+/// let watcher = ConfigWatcher::new("app.json", None).unwrap();
+/// let updates = watcher.subscribe();
+/// for config in updates {
+///     println!("workers: {:?}", config.pointer("/workers"));
+/// }
+/// ```
+pub struct ConfigWatcher {
+    state: Arc<Mutex<State>>,
+    // Watching stops when the watcher is dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Loads the file at `path` and starts watching it. Fails if the file can not be loaded, is
+    /// not valid against `schema`, or can not be watched.
+    pub fn new<P: AsRef<Path>>(path: P, schema: Option<Schema>) -> Result<ConfigWatcher, JsonError> {
+        let path = path.as_ref().to_path_buf();
+        let current = Arc::new(State::load(&path, schema.as_ref())?);
+        let state = Arc::new(Mutex::new(State {
+            path: path.clone(),
+            schema,
+            current,
+            last_error: None,
+            subscribers: Vec::new(),
+        }));
+        let file_name = path.file_name().map(|name| name.to_os_string());
+        let handler_state = Arc::clone(&state);
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let event = match event {
+                Ok(event) => event,
+                Err(_) => return
+            };
+            if matches!(event.kind, EventKind::Access(_))
+                || !event.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == file_name) {
+                return;
+            }
+            // The error is kept for last_error
+            let _ = lock(&handler_state).reload();
+        }).map_err(|e| watch_error(&path, e))?;
+        // Editors often replace a file instead of writing it, so the directory is watched
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new(".")
+        };
+        watcher.watch(directory, RecursiveMode::NonRecursive).map_err(|e| watch_error(&path, e))?;
+        Ok(ConfigWatcher { state, _watcher: watcher })
+    }
+
+    /// Returns the current version of the configuration
+    pub fn current(&self) -> Arc<JValue> {
+        Arc::clone(&lock(&self.state).current)
+    }
+
+    /// Returns a receiver of every new version of the configuration. The receiver can be
+    /// dropped to unsubscribe.
+    pub fn subscribe(&self) -> Receiver<Arc<JValue>> {
+        let (sender, receiver) = mpsc::channel();
+        lock(&self.state).subscribers.push(sender);
+        receiver
+    }
+
+    /// Loads the file again without waiting for a change, publishing the new version if it
+    /// differs from the current one. Returns the current version.
+    pub fn reload(&self) -> Result<Arc<JValue>, JsonError> {
+        lock(&self.state).reload()
+    }
+
+    /// Returns the error of the last attempt to load the file, if it failed
+    pub fn last_error(&self) -> Option<JsonError> {
+        lock(&self.state).last_error.clone()
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    // Every update of the state is complete before anything that could panic
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn watch_error(path: &Path, e: notify::Error) -> JsonError {
+    JsonError::new(ErrorKind::Io, format!("Can not watch {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::schema::Schema;
+    use crate::watch::ConfigWatcher;
+
+    fn directory(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("json-watch-test-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = directory("reload");
+        let path = dir.join("app.json");
+        fs::write(&path, "{\"workers\": 2}").unwrap();
+        let schema = Schema::compile(&json!("{\"properties\": {\"workers\": {\"type\": \"integer\"}}}")).unwrap();
+        let watcher = ConfigWatcher::new(&path, Some(schema)).unwrap();
+        let updates = watcher.subscribe();
+
+        fs::write(&path, "{\"workers\": \"many\"}").unwrap();
+        assert_eq!(ErrorKind::Validation, watcher.reload().unwrap_err().kind());
+        assert_eq!(Some(ErrorKind::Validation), watcher.last_error().map(|e| e.kind()));
+        assert_eq!(json!("{\"workers\": 2}"), *watcher.current());
+
+        fs::write(&path, "{\"workers\": 4}").unwrap();
+        assert_eq!(json!("{\"workers\": 4}"), *watcher.reload().unwrap());
+        assert_eq!(None, watcher.last_error());
+        assert_eq!(json!("{\"workers\": 4}"), *updates.recv_timeout(Duration::from_secs(5)).unwrap());
+        drop(watcher);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch() {
+        let dir = directory("watch");
+        let path = dir.join("app.json");
+        fs::write(&path, "{\"workers\": 2}").unwrap();
+        fs::write(dir.join("other.json"), "1").unwrap();
+        let watcher = ConfigWatcher::new(&path, None).unwrap();
+        let updates = watcher.subscribe();
+        fs::write(dir.join("other.json"), "2").unwrap();
+        fs::write(&path, "{\"workers\": 8}").unwrap();
+        let update = updates.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(json!("{\"workers\": 8}"), *update);
+        assert_eq!(update, watcher.current());
+        drop(watcher);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let dir = directory("errors");
+        assert_eq!(ErrorKind::Io, ConfigWatcher::new(dir.join("none.json"), None).err().unwrap().kind());
+        fs::write(dir.join("invalid.json"), "{").unwrap();
+        assert_eq!(ErrorKind::Syntax, ConfigWatcher::new(dir.join("invalid.json"), None).err().unwrap().kind());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Documents which notify watchers when the values at JSON Pointers change.
+
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::data_structures::JValue;
+use crate::error::JsonError;
+use crate::patch::Operation;
+use crate::pointer::{contains, parse_pointer};
+
+/// A change of the value at a watched JSON Pointer. A missing value is `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// The watched pointer
+    pub pointer: String,
+    /// The value before the change
+    pub old: Option<JValue>,
+    /// The value after the change
+    pub new: Option<JValue>,
+}
+
+/// Identifies a watcher registered on a [`WatchedDocument`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchId(u64);
+
+enum Sink {
+    Callback(Box<dyn FnMut(&Change) + Send>),
+    Channel(Sender<Change>),
+}
+
+struct Watcher {
+    id: WatchId,
+    pointer: String,
+    sink: Sink,
+}
+
+/// A [`JValue`] which is changed through its methods and notifies the watchers of the pointers
+/// whose values changed. A watcher of a pointer is notified when the value at the pointer is
+/// added, removed or changed, including by a change inside of it, and is not notified when a
+/// mutation leaves the value as it was. Watchers are notified in the order they were registered.
+///
+/// ```
+/// # use json::json;
+/// # use json::watched::WatchedDocument;
+/// let mut document = WatchedDocument::new(json!("{\"theme\": \"light\", \"size\": 12}"));
+/// let changes = document.subscribe("/theme").unwrap();
+///
+/// document.set("/size", json!("14")).unwrap();
+/// document.set("/theme", json!("\"dark\"")).unwrap();
+///
+/// let change = changes.try_recv().unwrap();
+/// assert_eq!(Some(json!("\"light\"")), change.old);
+/// assert_eq!(Some(json!("\"dark\"")), change.new);
+/// assert!(changes.try_recv().is_err());
+/// ```
+pub struct WatchedDocument {
+    value: JValue,
+    watchers: Vec<Watcher>,
+    next_id: u64,
+}
+
+impl WatchedDocument {
+    /// Wraps a document without watchers
+    pub fn new(value: JValue) -> WatchedDocument {
+        WatchedDocument { value, watchers: Vec::new(), next_id: 0 }
+    }
+
+    /// Returns the document
+    pub fn document(&self) -> &JValue {
+        &self.value
+    }
+
+    /// Unwraps the document, dropping the watchers
+    pub fn into_inner(self) -> JValue {
+        self.value
+    }
+
+    /// Calls `callback` with every change of the value at `pointer`
+    pub fn watch<F>(&mut self, pointer: &str, callback: F) -> Result<WatchId, JsonError>
+        where F: FnMut(&Change) + Send + 'static {
+        self.register(pointer, Sink::Callback(Box::new(callback)))
+    }
+
+    /// Returns a receiver of the changes of the value at `pointer`. The watcher is removed once
+    /// the receiver is dropped.
+    pub fn subscribe(&mut self, pointer: &str) -> Result<Receiver<Change>, JsonError> {
+        let (sender, receiver) = mpsc::channel();
+        self.register(pointer, Sink::Channel(sender))?;
+        Ok(receiver)
+    }
+
+    /// Removes a watcher, returning whether it was registered
+    pub fn unwatch(&mut self, id: WatchId) -> bool {
+        let before = self.watchers.len();
+        self.watchers.retain(|watcher| watcher.id != id);
+        self.watchers.len() != before
+    }
+
+    /// Sets the value at the given JSON Pointer, returning the value it replaced. A missing
+    /// object member is added, and the array index `-` or the length of the array appends to
+    /// it. The parent of the target must exist.
+    pub fn set(&mut self, pointer: &str, value: JValue) -> Result<Option<JValue>, JsonError> {
+        self.mutate(Some(vec![pointer.to_string()]), |document| {
+            if document.pointer(pointer).is_some() {
+                document.replace(pointer, value).map(Some)
+            } else {
+                document.apply_patch(&[Operation::Add { path: pointer.to_string(), value }]).map(|_| None)
+            }
+        })
+    }
+
+    /// Removes the value at the given JSON Pointer and returns it
+    pub fn remove(&mut self, pointer: &str) -> Result<JValue, JsonError> {
+        self.mutate(Some(vec![pointer.to_string()]), |document| {
+            let mut inverse = document.apply_patch_with_inverse(&[Operation::Remove { path: pointer.to_string() }])?;
+            match inverse.pop() {
+                Some(Operation::Add { value, .. }) => Ok(value),
+                _ => unreachable!()
+            }
+        })
+    }
+
+    /// Replaces the whole document, returning the previous one
+    pub fn replace_document(&mut self, value: JValue) -> JValue {
+        self.mutate(None, |document| Ok(std::mem::replace(document, value))).unwrap()
+    }
+
+    /// Applies a JSON Patch. If an operation fails, the document is left as it was and no
+    /// watcher is notified.
+    pub fn apply_patch(&mut self, operations: &[Operation]) -> Result<(), JsonError> {
+        let mut changed = Vec::new();
+        for operation in operations {
+            if let Operation::Move { from, .. } = operation {
+                changed.push(from.clone());
+            }
+            changed.push(operation.path().to_string());
+        }
+        self.mutate(Some(changed), |document| document.apply_patch(operations))
+    }
+
+    /// Changes the document with a closure. Every watcher is checked for a change afterwards.
+    pub fn update<F, R>(&mut self, f: F) -> R where F: FnOnce(&mut JValue) -> R {
+        self.mutate(None, |document| Ok(f(document))).unwrap()
+    }
+
+    fn register(&mut self, pointer: &str, sink: Sink) -> Result<WatchId, JsonError> {
+        parse_pointer(pointer)?;
+        let id = WatchId(self.next_id);
+        self.next_id += 1;
+        self.watchers.push(Watcher { id, pointer: pointer.to_string(), sink });
+        Ok(id)
+    }
+
+    /// Runs a mutation of the values at the `changed` pointers, or of anything for `None`, and
+    /// notifies the watchers whose values changed
+    fn mutate<F, R>(&mut self, changed: Option<Vec<String>>, f: F) -> Result<R, JsonError>
+        where F: FnOnce(&mut JValue) -> Result<R, JsonError> {
+        let affected = |watched: &str| match &changed {
+            Some(changed) => changed.iter().any(|pointer| affects(pointer, watched)),
+            None => true
+        };
+        let before: Vec<(usize, Option<JValue>)> = self.watchers.iter().enumerate()
+            .filter(|(_, watcher)| affected(&watcher.pointer))
+            .map(|(i, watcher)| (i, self.value.pointer(&watcher.pointer).cloned()))
+            .collect();
+        let result = f(&mut self.value)?;
+
+        let mut disconnected = Vec::new();
+        for (i, old) in before {
+            let watcher = &mut self.watchers[i];
+            let new = self.value.pointer(&watcher.pointer);
+            if old.as_ref() == new {
+                continue;
+            }
+            let change = Change { pointer: watcher.pointer.clone(), old, new: new.cloned() };
+            match &mut watcher.sink {
+                Sink::Callback(callback) => callback(&change),
+                Sink::Channel(sender) => if sender.send(change).is_err() {
+                    disconnected.push(watcher.id);
+                }
+            }
+        }
+        self.watchers.retain(|watcher| !disconnected.contains(&watcher.id));
+        Ok(result)
+    }
+}
+
+impl fmt::Debug for WatchedDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchedDocument")
+            .field("value", &self.value)
+            .field("watchers", &self.watchers.len())
+            .finish()
+    }
+}
+
+/// Whether a change at `changed` can change the value at `watched`: the watched value contains
+/// the changed one, or is inside its parent, whose array elements may have moved
+fn affects(changed: &str, watched: &str) -> bool {
+    let parent = &changed[..changed.rfind('/').unwrap_or(0)];
+    contains(watched, changed) || contains(parent, watched)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::patch::parse_patch;
+    use crate::watched::{Change, WatchedDocument};
+
+    fn recorded(document: &mut WatchedDocument, pointer: &str) -> Arc<Mutex<Vec<Change>>> {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&changes);
+        document.watch(pointer, move |change| sink.lock().unwrap().push(change.clone())).unwrap();
+        changes
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let mut document = WatchedDocument::new(json!("{\"a\": {\"b\": 1}, \"c\": 2}"));
+        let a = recorded(&mut document, "/a");
+        let b = recorded(&mut document, "/a/b");
+        let root = recorded(&mut document, "");
+
+        assert_eq!(Ok(None), document.set("/a/d", json!("3")));
+        assert_eq!(Ok(Some(json!("1"))), document.set("/a/b", json!("1")));
+        assert_eq!(Ok(json!("1")), document.remove("/a/b"));
+        assert_eq!(ErrorKind::NotFound, document.remove("/a/b").unwrap_err().kind());
+
+        assert_eq!(2, a.lock().unwrap().len());
+        assert_eq!(2, root.lock().unwrap().len());
+        assert_eq!(vec![Change { pointer: "/a/b".to_string(), old: Some(json!("1")), new: None }],
+                   *b.lock().unwrap());
+        assert_eq!(Some(json!("{\"b\": 1, \"d\": 3}")), a.lock().unwrap()[1].old);
+    }
+
+    #[test]
+    fn test_array_shift() {
+        let mut document = WatchedDocument::new(json!("[1, 2, 3]"));
+        let second = recorded(&mut document, "/1");
+        let last = recorded(&mut document, "/2");
+        document.remove("/0").unwrap();
+        assert_eq!(Some(json!("3")), second.lock().unwrap()[0].new);
+        assert_eq!(None, last.lock().unwrap()[0].new);
+        document.set("/-", json!("4")).unwrap();
+        assert_eq!(Some(json!("4")), last.lock().unwrap()[1].new);
+        assert_eq!(1, second.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_patch() {
+        let mut document = WatchedDocument::new(json!("{\"a\": 1, \"b\": 2}"));
+        let a = recorded(&mut document, "/a");
+        let c = recorded(&mut document, "/c");
+        let failing = parse_patch(&json!("[{\"op\": \"move\", \"from\": \"/a\", \"path\": \"/c\"},
+                                           {\"op\": \"test\", \"path\": \"/b\", \"value\": 3}]")).unwrap();
+        assert!(document.apply_patch(&failing).is_err());
+        assert!(a.lock().unwrap().is_empty());
+
+        document.apply_patch(&failing[..1]).unwrap();
+        assert_eq!(None, a.lock().unwrap()[0].new);
+        assert_eq!(Some(json!("1")), c.lock().unwrap()[0].new);
+    }
+
+    #[test]
+    fn test_update_and_unwatch() {
+        let mut document = WatchedDocument::new(json!("{\"a\": 1}"));
+        let a = recorded(&mut document, "/a");
+        let id = document.watch("/a", |_| panic!("unwatched")).unwrap();
+        assert!(document.unwatch(id));
+        assert!(!document.unwatch(id));
+
+        document.update(|value| *value.pointer_mut("/a").unwrap() = json!("2"));
+        assert_eq!(json!("{\"a\": 2}"), document.replace_document(json!("{\"a\": 1}")));
+        document.replace_document(json!("{\"a\": 1}"));
+        assert_eq!(2, a.lock().unwrap().len());
+        assert_eq!(ErrorKind::InvalidPointer, document.watch("a", |_| {}).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_dropped_receiver() {
+        let mut document = WatchedDocument::new(json!("{\"a\": 1}"));
+        drop(document.subscribe("/a").unwrap());
+        let changes = document.subscribe("/a").unwrap();
+        document.set("/a", json!("2")).unwrap();
+        assert_eq!(Some(json!("2")), changes.recv().unwrap().new);
+        assert!(format!("{:?}", document).ends_with("watchers: 1 }"));
+    }
+}
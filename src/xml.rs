@@ -0,0 +1,408 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Conversion between XML documents and [`JValue`]s.
+//!
+//! A document becomes an object with a single member named after the root element. An element
+//! with neither attributes nor child elements becomes its text, or `null` if it has none. Any
+//! other element becomes an object, with its attributes as members named with
+//! [`XmlOptions::attribute_prefix`], its child elements as members named after them, and its
+//! text in the member [`XmlOptions::text_key`]. Child elements with the same name are folded
+//! into an array. All text is kept as strings; [`FromJValue`] converts them to numbers and
+//! booleans when they are read.
+//!
+//! ```
+//! # use json::json;
+//! # use json::xml;
+//! let text = "<order id=\"7\"><item>pen</item><item>ink</item><note lang=\"en\">urgent</note></order>";
+//! let j_value = json!("{\"order\": {\"@id\": \"7\", \"item\": [\"pen\", \"ink\"],\
+//!                                    \"note\": {\"@lang\": \"en\", \"#text\": \"urgent\"}}}");
+//! assert_eq!(Ok(j_value.clone()), xml::parse(text));
+//! let written = xml::to_xml(&j_value).unwrap();
+//! assert_eq!(Ok(j_value), xml::parse(&written));
+//! ```
+//!
+//! [`FromJValue`]:crate::convert::FromJValue
+
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::convert::describe;
+use crate::data_structures::{JObject, JString, JValue};
+use crate::error::{ErrorKind, JsonError};
+use crate::parser::ParseOptions;
+use crate::transform::push_token;
+
+/// Names the members elements are converted to
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlOptions {
+    /// Prepended to the names of attributes, `@` by default. If it is empty, attributes and child
+    /// elements with the same name are folded into an array.
+    pub attribute_prefix: String,
+    /// The member holding the text of an element with attributes or child elements, `#text` by
+    /// default
+    pub text_key: String,
+    /// Names of elements which are always in an array, even if there is only one of them, so
+    /// readers need not handle both a value and an array
+    pub always_array: Vec<String>,
+}
+
+impl Default for XmlOptions {
+    fn default() -> Self {
+        XmlOptions { attribute_prefix: "@".to_string(), text_key: "#text".to_string(), always_array: Vec::new() }
+    }
+}
+
+struct Element {
+    name: String,
+    members: JObject,
+    text: String,
+}
+
+/// Parses an XML document with the default [`XmlOptions`]
+pub fn parse(text: &str) -> Result<JValue, JsonError> {
+    parse_with(text, &XmlOptions::default())
+}
+
+/// Parses an XML document. The declaration, comments, processing instructions and the document
+/// type are skipped, and CDATA sections are text. Names keep their namespace prefixes, and
+/// namespace declarations are attributes. Elements may be nested up to
+/// [`ParseOptions::max_depth`] levels.
+///
+/// ```
+/// # use json::json;
+/// # use json::xml::{self, XmlOptions};
+/// let options = XmlOptions { attribute_prefix: "-".to_string(), always_array: vec!["item".to_string()],
+///                            ..XmlOptions::default() };
+/// assert_eq!(Ok(json!("{\"list\": {\"-size\": \"1\", \"item\": [\"a < b\"]}}")),
+///            xml::parse_with("<?xml version=\"1.0\"?><list size=\"1\"><item><![CDATA[a < b]]></item></list>", &options));
+/// ```
+pub fn parse_with(text: &str, options: &XmlOptions) -> Result<JValue, JsonError> {
+    let mut reader = Reader::from_str(text);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    let max_depth = ParseOptions::default().max_depth;
+    loop {
+        let event = reader.read_event()
+            .map_err(|e| syntax_error(format!("Invalid XML: {}", e), text, reader.error_position()))?;
+        let position = reader.buffer_position();
+        if matches!(event, Event::Start(_) | Event::Empty(_)) && stack.len() == max_depth {
+            return Err(syntax_error(format!("The elements are nested deeper than the limit of {} levels", max_depth),
+                                    text, position));
+        }
+        match event {
+            Event::Start(start) => {
+                if stack.is_empty() && root.is_some() {
+                    return Err(syntax_error("The document has a second root element".to_string(), text, position));
+                }
+                stack.push(element(&start, options).map_err(|e| syntax_error(e, text, position))?);
+            }
+            Event::Empty(start) => {
+                let element = element(&start, options).map_err(|e| syntax_error(e, text, position))?;
+                close(element, &mut stack, &mut root, options)
+                    .map_err(|e| syntax_error(e, text, position))?;
+            }
+            Event::End(_) => {
+                let element = stack.pop().expect("the reader checks end tags");
+                close(element, &mut stack, &mut root, options).map_err(|e| syntax_error(e, text, position))?;
+            }
+            Event::Text(t) => {
+                let t = t.unescape().map_err(|e| syntax_error(format!("Invalid XML: {}", e), text, position))?;
+                match stack.last_mut() {
+                    Some(element) => element.text.push_str(&t),
+                    None if t.trim().is_empty() => {}
+                    None => return Err(syntax_error("Text outside of the root element".to_string(), text, position))
+                }
+            }
+            Event::CData(data) => {
+                let data = data.decode().map_err(|e| syntax_error(format!("Invalid XML: {}", e), text, position))?;
+                match stack.last_mut() {
+                    Some(element) => element.text.push_str(&data),
+                    None => return Err(syntax_error("Text outside of the root element".to_string(), text, position))
+                }
+            }
+            Event::Eof => break,
+            Event::Comment(_) | Event::Decl(_) | Event::PI(_) | Event::DocType(_) => {}
+        }
+    }
+    if let Some(element) = stack.last() {
+        return Err(syntax_error(format!("The element <{}> is not closed", element.name), text, text.len() as u64));
+    }
+    root.ok_or_else(|| syntax_error("The document has no root element".to_string(), text, 0))
+}
+
+/// Writes a value as an XML document with the default [`XmlOptions`]
+pub fn to_xml(value: &JValue) -> Result<String, JsonError> {
+    to_xml_with(value, &XmlOptions::default())
+}
+
+/// Writes a value as an XML document, the reverse of [`parse_with`]. The value must be an
+/// object with a single member, the root element.
+///
+/// Numbers and booleans become their text and `null` an empty element. Attributes and child
+/// elements are written sorted by name, as objects do not keep the order of their members, and
+/// an array is written as an element for each of its items. Arrays in arrays, objects as
+/// attributes and names which are not XML names fail with [`ErrorKind::InvalidType`].
+///
+/// ```
+/// # use json::json;
+/// # use json::xml;
+/// assert_eq!(Ok("<a b=\"1\"><c/><d>x &amp; y</d><d>true</d></a>".to_string()),
+///            xml::to_xml(&json!("{\"a\": {\"@b\": 1, \"c\": null, \"d\": [\"x & y\", true]}}")));
+/// ```
+pub fn to_xml_with(value: &JValue, options: &XmlOptions) -> Result<String, JsonError> {
+    let (name, root) = match value {
+        JValue::Object(object) if object.len() == 1 => object.iter().next().unwrap(),
+        _ => return Err(JsonError::new(ErrorKind::InvalidType, format!(
+            "Expected an object with a single member, the root element, found {}", describe(value))))
+    };
+    let mut out = String::new();
+    let mut path = String::new();
+    push_token(&mut path, name);
+    if let JValue::Array(_) = root {
+        return Err(type_error("The root element can not be an array", &path));
+    }
+    write_element(name, root, options, &mut path, &mut out)?;
+    Ok(out)
+}
+
+fn syntax_error(message: String, text: &str, position: u64) -> JsonError {
+    JsonError::new(ErrorKind::Syntax, message).with_position(text, (position as usize).min(text.len()))
+}
+
+fn type_error(message: &str, path: &str) -> JsonError {
+    JsonError::new(ErrorKind::InvalidType, format!("{} at {}", message, path))
+}
+
+fn element(start: &BytesStart, options: &XmlOptions) -> Result<Element, String> {
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    let mut members = JObject::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| format!("Invalid XML: {}", e))?;
+        let value = attribute.unescape_value().map_err(|e| format!("Invalid XML: {}", e))?;
+        let key = format!("{}{}", options.attribute_prefix, String::from_utf8_lossy(attribute.key.as_ref()));
+        add_member(&mut members, key, JValue::String(JString::from(value.into_owned())), options);
+    }
+    Ok(Element { name, members, text: String::new() })
+}
+
+/// Adds a closed element to its parent, or makes it the root if it has none
+fn close(element: Element, stack: &mut [Element], root: &mut Option<JValue>, options: &XmlOptions)
+         -> Result<(), String> {
+    let Element { name, mut members, text } = element;
+    let text = text.trim();
+    let value = if members.is_empty() {
+        if text.is_empty() { JValue::Null } else { JValue::String(JString::from(text)) }
+    } else {
+        if !text.is_empty() {
+            members.insert(options.text_key.clone(), JValue::String(JString::from(text)));
+        }
+        JValue::Object(members)
+    };
+    match stack.last_mut() {
+        Some(parent) => add_member(&mut parent.members, name, value, options),
+        None if root.is_some() => return Err("The document has a second root element".to_string()),
+        None => {
+            let mut object = JObject::new();
+            add_member(&mut object, name, value, options);
+            *root = Some(JValue::Object(object));
+        }
+    }
+    Ok(())
+}
+
+fn add_member(members: &mut JObject, name: String, value: JValue, options: &XmlOptions) {
+    match members.get_mut(&name) {
+        // Elements and attributes are never arrays, so an array is one folded before.
+        Some(JValue::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = JValue::Array(vec![first, value]);
+        }
+        None if options.always_array.contains(&name) => {
+            members.insert(name, JValue::Array(vec![value]));
+        }
+        None => {
+            members.insert(name, value);
+        }
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+fn scalar_text(value: &JValue) -> Option<String> {
+    match value {
+        JValue::String(s) => Some(s.to_string()),
+        JValue::Number(n) => Some(n.to_string()),
+        JValue::Boolean(b) => Some(b.to_string()),
+        JValue::Null => Some(String::new()),
+        JValue::Object(_) | JValue::Array(_) => None
+    }
+}
+
+fn write_element(name: &str, value: &JValue, options: &XmlOptions, path: &mut String, out: &mut String)
+                 -> Result<(), JsonError> {
+    if !is_name(name) {
+        return Err(type_error(&format!("The name '{}' is not an XML name", name), path));
+    }
+    out.push('<');
+    out.push_str(name);
+    let object = match value {
+        JValue::Object(object) => object,
+        JValue::Null => {
+            out.push_str("/>");
+            return Ok(());
+        }
+        _ => {
+            out.push('>');
+            out.push_str(&escape(scalar_text(value).unwrap()));
+            out.push_str("</");
+            out.push_str(name);
+            out.push('>');
+            return Ok(());
+        }
+    };
+    let mut members: Vec<(&String, &JValue)> = object.iter().collect();
+    members.sort_by(|a, b| a.0.cmp(b.0));
+    let is_attribute = |key: &str| !options.attribute_prefix.is_empty() && key != options.text_key
+        && key.starts_with(&options.attribute_prefix);
+    let len = path.len();
+    for (key, value) in members.iter().filter(|(key, _)| is_attribute(key)) {
+        push_token(path, key);
+        let attribute = &key[options.attribute_prefix.len()..];
+        if !is_name(attribute) {
+            return Err(type_error(&format!("The name '{}' is not an XML name", attribute), path));
+        }
+        let text = scalar_text(value).ok_or_else(|| type_error(&format!(
+            "An attribute can not be {}", describe(value)), path))?;
+        out.push(' ');
+        out.push_str(attribute);
+        out.push_str("=\"");
+        out.push_str(&escape(text));
+        out.push('"');
+        path.truncate(len);
+    }
+    let mut content = String::new();
+    if let Some(text) = object.get(&options.text_key) {
+        push_token(path, &options.text_key);
+        let text = scalar_text(text).ok_or_else(|| type_error(&format!(
+            "The text of an element can not be {}", describe(text)), path))?;
+        content.push_str(&escape(text));
+        path.truncate(len);
+    }
+    for (key, value) in members.into_iter().filter(|(key, _)| !is_attribute(key) && **key != options.text_key) {
+        push_token(path, key);
+        match value {
+            JValue::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    let len = path.len();
+                    push_token(path, &i.to_string());
+                    if let JValue::Array(_) = value {
+                        return Err(type_error("An array in an array can not be written as XML", path));
+                    }
+                    write_element(key, value, options, path, &mut content)?;
+                    path.truncate(len);
+                }
+            }
+            _ => write_element(key, value, options, path, &mut content)?
+        }
+        path.truncate(len);
+    }
+    if content.is_empty() {
+        out.push_str("/>");
+    } else {
+        out.push('>');
+        out.push_str(&content);
+        out.push_str("</");
+        out.push_str(name);
+        out.push('>');
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::json;
+    use crate::xml::{parse, parse_with, to_xml, to_xml_with, XmlOptions};
+
+    #[test]
+    fn test_parse() {
+        let text = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!-- An envelope -->\n\
+                    <soap:Envelope xmlns:soap=\"http://www.w3.org/2003/05/soap-envelope\">\n\
+                      <soap:Body>\n    <m:Price xmlns:m=\"urn:prices\" currency=\"&#8364;\">  1 &lt; 2 </m:Price>\n\
+                      <empty></empty><empty/>\n  </soap:Body>\n</soap:Envelope>\n";
+        assert_eq!(Ok(json!("{\"soap:Envelope\": {\"@xmlns:soap\": \"http://www.w3.org/2003/05/soap-envelope\",\
+                              \"soap:Body\": {\"m:Price\": {\"@xmlns:m\": \"urn:prices\", \"@currency\": \"\\u20ac\",\
+                              \"#text\": \"1 < 2\"}, \"empty\": [null, null]}}}")),
+                   parse(text));
+        let options = XmlOptions { attribute_prefix: String::new(), text_key: "value".to_string(), ..XmlOptions::default() };
+        assert_eq!(Ok(json!("{\"a\": {\"b\": [\"1\", \"2\"], \"value\": \"x\"}}")),
+                   parse_with("<a b=\"1\">x<b>2</b></a>", &options));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (text, message) in [
+            ("", "The document has no root element"),
+            ("<a>", "The element <a> is not closed"),
+            ("<a/><b/>", "The document has a second root element"),
+            ("<a></a><b></b>", "The document has a second root element"),
+            ("x<a/>", "Text outside of the root element"),
+        ] {
+            let error = parse(text).unwrap_err();
+            assert_eq!((ErrorKind::Syntax, message), (error.kind(), error.message()), "{}", text);
+        }
+        for text in ["<a></b>", "<a>&nbsp;</a>", "<a b=1/>"] {
+            assert_eq!(ErrorKind::Syntax, parse(text).unwrap_err().kind(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_max_depth() {
+        assert!(parse(&format!("{}{}", "<a>".repeat(128), "</a>".repeat(128))).is_ok());
+        let error = parse(&"<a>".repeat(200_000)).unwrap_err();
+        assert_eq!((ErrorKind::Syntax, "The elements are nested deeper than the limit of 128 levels"),
+                   (error.kind(), error.message()));
+    }
+
+    #[test]
+    fn test_to_xml() {
+        let j_value = json!("{\"r\": {\"@k\": \"\\\"<&>\\\"\", \"#text\": \"t\", \"c\": [{\"@x\": \"1\"}, \"2\"]}}");
+        assert_eq!(Ok("<r k=\"&quot;&lt;&amp;&gt;&quot;\">t<c x=\"1\"/><c>2</c></r>".to_string()), to_xml(&j_value));
+        assert_eq!(Ok(j_value.clone()), parse(&to_xml(&j_value).unwrap()));
+        let options = XmlOptions { always_array: vec!["c".to_string()], ..XmlOptions::default() };
+        let j_value = json!("{\"r\": {\"c\": [\"1\"]}}");
+        assert_eq!(Ok(j_value.clone()), parse_with(&to_xml_with(&j_value, &options).unwrap(), &options));
+
+        for (j_value, message) in [
+            (json!("[]"), "Expected an object with a single member, the root element, found an array"),
+            (json!("{\"r\": []}"), "The root element can not be an array at /r"),
+            (json!("{\"1r\": 1}"), "The name '1r' is not an XML name at /1r"),
+            (json!("{\"r\": {\"@a\": {}}}"), "An attribute can not be an object at /r/@a"),
+            (json!("{\"r\": {\"c\": [[1]]}}"), "An array in an array can not be written as XML at /r/c/0"),
+        ] {
+            assert_eq!(message, to_xml(&j_value).unwrap_err().message());
+        }
+    }
+}
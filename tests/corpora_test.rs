@@ -0,0 +1,54 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+use json::parser::parse_with_stats;
+use json::serializer::Serialize;
+
+const CORPORA: [&str; 3] = [
+    include_str!("../benches/data/twitter.json"),
+    include_str!("../benches/data/canada.json"),
+    include_str!("../benches/data/citm_catalog.json"),
+];
+
+#[test]
+fn test_parse_corpora() {
+    for corpus in CORPORA.iter() {
+        let (_, stats) = parse_with_stats(corpus).unwrap();
+        assert_eq!(corpus.len(), stats.bytes());
+        assert!(stats.tokens() > 0);
+    }
+}
+
+#[test]
+fn test_serialized_len_corpora() {
+    for corpus in CORPORA.iter() {
+        let (j_value, _) = parse_with_stats(corpus).unwrap();
+        assert_eq!(j_value.serialize().len(), j_value.serialized_len());
+    }
+}
+
+#[cfg(feature = "arena")]
+#[test]
+fn test_parse_in_corpora() {
+    use json::parser::{parse_in, Bump};
+
+    for corpus in CORPORA.iter() {
+        let arena = Bump::new();
+        let (j_value, _) = parse_with_stats(corpus).unwrap();
+        assert_eq!(j_value, parse_in(&arena, corpus).unwrap().to_owned_value());
+    }
+}
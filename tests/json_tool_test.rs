@@ -0,0 +1,138 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn directory(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("json-tool-test-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn json_tool(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_json-tool")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn test_validate() {
+    let dir = directory("validate");
+    let valid = dir.join("valid.json");
+    let invalid = dir.join("invalid.json");
+    let schema = dir.join("schema.json");
+    fs::write(&valid, "{\"port\": 80}").unwrap();
+    fs::write(&invalid, "{\n  \"port\": 80,\n}").unwrap();
+    fs::write(&schema, "{\"properties\": {\"port\": {\"type\": \"string\"}}}").unwrap();
+
+    let output = json_tool(&["validate", valid.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("valid.json: valid\n"));
+
+    let output = json_tool(&["validate", invalid.to_str().unwrap()]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr(&output).contains("at line 3, column 1\n3 | }\n  | ^"), "{}", stderr(&output));
+
+    let output = json_tool(&["validate", valid.to_str().unwrap(), "--schema", schema.to_str().unwrap()]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr(&output).contains("Expected string, found the number 80"), "{}", stderr(&output));
+
+    let trailing = dir.join("trailing.json");
+    fs::write(&trailing, "{\"port\": 80}\n{\"port\": 81}").unwrap();
+    let output = json_tool(&["validate", trailing.to_str().unwrap()]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr(&output).contains("Expected the end of the input"), "{}", stderr(&output));
+
+    assert_eq!(Some(2), json_tool(&["validate", dir.join("none.json").to_str().unwrap()]).status.code());
+    assert_eq!(Some(2), json_tool(&["frobnicate"]).status.code());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_fmt_and_minify() {
+    let dir = directory("fmt");
+    let file = dir.join("a.json");
+    fs::write(&file, "{\"b\": [1, 2], \"a\": {}}").unwrap();
+    let output = json_tool(&["fmt", file.to_str().unwrap(), "--indent", "1"]);
+    assert_eq!("{\n \"a\": {},\n \"b\": [\n  1,\n  2\n ]\n}\n", stdout(&output));
+    let output = json_tool(&["minify", file.to_str().unwrap()]);
+    assert_eq!(json::parser::parse("{\"b\": [1, 2], \"a\": {}}").unwrap(),
+               json::parser::parse(&stdout(&output)).unwrap());
+    assert!(!stdout(&output).contains(' '));
+    assert_eq!(Some(2), json_tool(&["fmt", file.to_str().unwrap(), "--indent", "x"]).status.code());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_diff() {
+    let dir = directory("diff");
+    let (a, b) = (dir.join("a.json"), dir.join("b.json"));
+    fs::write(&a, "{\"name\": \"a\", \"tags\": [1]}").unwrap();
+    fs::write(&b, "{\"name\": \"b\", \"tags\": [1, 2]}").unwrap();
+    let output = json_tool(&["diff", a.to_str().unwrap(), b.to_str().unwrap()]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("~ /name: \"a\" -> \"b\"\n+ /tags/1: 2\n", stdout(&output));
+    assert!(json_tool(&["diff", a.to_str().unwrap(), a.to_str().unwrap()]).status.success());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_get() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json-tool")).args(["get", "/items/*/id", "-"])
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(b"{\"items\": [{\"id\": 1}, {\"id\": [2]}]}").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!("1\n[2]\n", stdout(&output));
+
+    let dir = directory("get");
+    let file = dir.join("a.json");
+    fs::write(&file, "{\"a\": 1}").unwrap();
+    assert_eq!("1\n", stdout(&json_tool(&["get", "$.a", file.to_str().unwrap()])));
+    assert_eq!(Some(1), json_tool(&["get", "/b", file.to_str().unwrap()]).status.code());
+    assert_eq!(Some(2), json_tool(&["get", "b", file.to_str().unwrap()]).status.code());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_repl() {
+    let dir = directory("repl");
+    let file = dir.join("a.json");
+    fs::write(&file, "{\"items\": [{\"id\": 1}, {\"id\": 2}]}").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json-tool")).args(["repl", file.to_str().unwrap()])
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(b"/items/1\n$.items[*].id\n/none\n$[\nfoo\n:quit\n/items\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!("{\n  \"id\": 2\n}\n1\n2\n", stdout(&output));
+    let errors: Vec<String> = stderr(&output).lines().map(|line| line.to_string()).collect();
+    assert_eq!(3, errors.len());
+    assert_eq!("No value at /none", errors[0]);
+    assert!(errors[1].starts_with("Invalid JSONPath $["));
+    assert!(errors[2].starts_with("Unknown command or expression foo"));
+    fs::remove_dir_all(dir).unwrap();
+}
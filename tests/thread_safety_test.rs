@@ -0,0 +1,59 @@
+// Copyright © 2021 Hovig Manjikian
+//
+// This file is part of json.
+//
+// json is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// json is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with json.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use json::data_structures::{ArcValue, JNumber, JObject, JString, JValue};
+use json::error::JsonError;
+use json::indexed::IndexedDocument;
+use json::jsonpath::JsonPath;
+use json::lazy::LazyDocument;
+use json::parser::{Parser, ParserBuilder};
+use json::schema::Schema;
+use json::shared::DocumentStore;
+use json::typed::TypedDocument;
+use json::watched::WatchedDocument;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn test_types_are_send_and_sync() {
+    assert_send_sync::<JValue>();
+    assert_send_sync::<JObject>();
+    assert_send_sync::<JString>();
+    assert_send_sync::<JNumber>();
+    assert_send_sync::<ArcValue>();
+    assert_send_sync::<JsonError>();
+    assert_send_sync::<JsonPath>();
+    assert_send_sync::<Schema>();
+    assert_send_sync::<TypedDocument>();
+    assert_send_sync::<IndexedDocument<'static>>();
+    assert_send_sync::<DocumentStore>();
+    assert_send_sync::<LazyDocument<'static>>();
+    assert_send_sync::<Parser>();
+    assert_send_sync::<ParserBuilder>();
+    #[cfg(feature = "arena")]
+    assert_send_sync::<json::data_structures::BorrowedValue<'static>>();
+    #[cfg(feature = "persistent")]
+    assert_send_sync::<json::data_structures::PersistentValue>();
+}
+
+#[test]
+fn test_watched_document_is_send() {
+    assert_send::<WatchedDocument>();
+}